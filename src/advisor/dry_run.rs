@@ -0,0 +1,323 @@
+//! Trying advisor rules out on stored plans
+//!
+//! Before a rule is enabled, or a threshold changed, it helps to know how
+//! often it would fire and on what. A dry run runs the chosen rules, with the
+//! chosen thresholds, over a corpus of plans that were already analyzed or
+//! imported, and reports for each rule the share of plans it fires on along
+//! with a few example matches. Nothing is stored and the shared advisor is
+//! left as it is.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::rules::{find_rule, RULES};
+use super::{AdvisorConfig, AnalysisContext, QueryAdvisor, Severity};
+use crate::db::models::ExecutionPlan;
+use crate::storage::{self, Storage};
+use crate::workload::auto_explain::AutoExplainLog;
+
+/// Example matches kept per rule when the dry run does not say
+pub const DEFAULT_EXAMPLES: usize = 3;
+
+/// Stored analyses read per page while collecting a corpus
+const PAGE_SIZE: i64 = 100;
+
+/// Rules to try and the thresholds to try them with
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DryRun {
+    /// Ids of the rules to run, whether or not they are enabled; empty runs every rule
+    pub rules: Vec<String>,
+    /// Threshold values to run the rules with, on top of the current ones
+    pub thresholds: BTreeMap<String, f64>,
+    /// Example matches kept per rule
+    pub examples: Option<usize>,
+}
+
+/// A plan the rules are tried on
+#[derive(Debug, Clone)]
+pub struct CorpusPlan {
+    /// Stored analysis the plan comes from, if it was stored
+    pub history_id: Option<i64>,
+    /// Query the plan is for
+    pub query: String,
+    /// The plan
+    pub plan: ExecutionPlan,
+}
+
+/// Plans to run a dry run on
+#[derive(Debug, Clone, Default)]
+pub struct Corpus {
+    /// Plans that could be read
+    pub plans: Vec<CorpusPlan>,
+    /// Stored analyses or log entries whose plan could not be read
+    pub skipped: usize,
+}
+
+impl Corpus {
+    /// The plans of the `limit` most recent stored analyses
+    pub async fn from_history(storage: &dyn Storage, limit: i64) -> storage::Result<Self> {
+        let mut corpus = Corpus::default();
+        let mut offset = 0;
+        while offset < limit {
+            let entries = storage
+                .list_history(PAGE_SIZE.min(limit - offset), offset)
+                .await?;
+            if entries.is_empty() {
+                break;
+            }
+            offset += entries.len() as i64;
+            for entry in entries {
+                match serde_json::from_value(entry.plan) {
+                    Ok(plan) => corpus.plans.push(CorpusPlan {
+                        history_id: Some(entry.id),
+                        query: entry.query,
+                        plan,
+                    }),
+                    Err(_) => corpus.skipped += 1,
+                }
+            }
+        }
+        Ok(corpus)
+    }
+
+    /// The plans of an auto_explain log, without storing them
+    pub fn from_log(log: AutoExplainLog) -> Self {
+        Corpus {
+            skipped: log.skipped.len(),
+            plans: log
+                .plans
+                .into_iter()
+                .map(|logged| CorpusPlan {
+                    history_id: None,
+                    query: logged.query,
+                    plan: logged.plan,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A suggestion a rule made on one plan of the corpus
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleMatch {
+    /// Stored analysis of the plan, if it was stored
+    pub history_id: Option<i64>,
+    /// Query the plan is for
+    pub query: String,
+    /// Severity of the suggestion
+    pub severity: Severity,
+    /// Title of the suggestion
+    pub title: String,
+    /// Description of the suggestion
+    pub description: String,
+    /// Plan node the suggestion is about
+    pub node_index: Option<usize>,
+}
+
+/// How often one rule fired on the corpus
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleHits {
+    /// Rule id
+    pub rule_id: String,
+    /// Rule title
+    pub title: String,
+    /// Whether the rule is enabled outside the dry run
+    pub enabled: bool,
+    /// Plans the rule made at least one suggestion on
+    pub plans_hit: usize,
+    /// Share of the corpus the rule fired on, from 0 to 1
+    pub hit_rate: f64,
+    /// Suggestions made, counting several on one plan
+    pub suggestions: usize,
+    /// The first suggestions made, in corpus order
+    pub examples: Vec<RuleMatch>,
+}
+
+/// Outcome of a dry run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DryRunReport {
+    /// Plans the rules were run on
+    pub plans: usize,
+    /// Stored analyses or log entries left out because their plan could not be read
+    pub skipped: usize,
+    /// Thresholds the rules ran with, for the rules that were run
+    pub thresholds: BTreeMap<String, f64>,
+    /// Hits of each rule that was run, most frequent first
+    pub rules: Vec<RuleHits>,
+}
+
+impl DryRun {
+    /// Configuration running only the chosen rules, with the chosen thresholds applied to `base`
+    pub fn config(&self, base: &AdvisorConfig) -> Result<AdvisorConfig, String> {
+        if let Some(id) = self.rules.iter().find(|id| find_rule(id).is_none()) {
+            return Err(format!("Unknown rule {}", id));
+        }
+        let mut config = base.clone();
+        for (name, value) in &self.thresholds {
+            config.set_threshold(name, *value)?;
+        }
+        config.disabled_rules = RULES
+            .iter()
+            .filter(|rule| !self.selects(rule.id))
+            .map(|rule| rule.id.to_string())
+            .collect();
+        Ok(config)
+    }
+
+    fn selects(&self, rule_id: &str) -> bool {
+        self.rules.is_empty() || self.rules.iter().any(|id| id == rule_id)
+    }
+
+    /// Run the chosen rules over `corpus`, with `base` as the current configuration
+    ///
+    /// Plans carry no catalog metadata here, so rules that need relation
+    /// sizes, statistics, or indexes do not fire; plans without actual row
+    /// counts are judged as estimates.
+    pub fn run(&self, base: &AdvisorConfig, corpus: &Corpus) -> Result<DryRunReport, String> {
+        let config = self.config(base)?;
+        let advisor = QueryAdvisor::with_config(config.clone());
+        let examples = self.examples.unwrap_or(DEFAULT_EXAMPLES);
+
+        let mut hits: Vec<RuleHits> = RULES
+            .iter()
+            .filter(|rule| self.selects(rule.id))
+            .map(|rule| RuleHits {
+                rule_id: rule.id.to_string(),
+                title: rule.title.to_string(),
+                enabled: base.is_rule_enabled(rule.id),
+                plans_hit: 0,
+                hit_rate: 0.0,
+                suggestions: 0,
+                examples: Vec::new(),
+            })
+            .collect();
+
+        for item in &corpus.plans {
+            let context = AnalysisContext {
+                estimates_only: item.plan.root.actual_loops == 0,
+                ..AnalysisContext::default()
+            };
+            let analysis = advisor.analyze_plan_with_context(&item.plan, &context);
+            let mut fired = BTreeSet::new();
+            for suggestion in analysis.suggestions {
+                let Some(rule) = hits.iter_mut().find(|h| h.rule_id == suggestion.rule_id) else {
+                    continue;
+                };
+                rule.suggestions += 1;
+                if fired.insert(suggestion.rule_id.clone()) {
+                    rule.plans_hit += 1;
+                }
+                if rule.examples.len() < examples {
+                    rule.examples.push(RuleMatch {
+                        history_id: item.history_id,
+                        query: item.query.clone(),
+                        severity: suggestion.severity,
+                        title: suggestion.title,
+                        description: suggestion.description,
+                        node_index: suggestion.node_index,
+                    });
+                }
+            }
+        }
+
+        let plans = corpus.plans.len();
+        for rule in &mut hits {
+            rule.hit_rate = if plans == 0 {
+                0.0
+            } else {
+                rule.plans_hit as f64 / plans as f64
+            };
+        }
+        hits.sort_by(|a, b| {
+            b.plans_hit
+                .cmp(&a.plans_hit)
+                .then_with(|| a.rule_id.cmp(&b.rule_id))
+        });
+
+        let thresholds = RULES
+            .iter()
+            .filter(|rule| self.selects(rule.id))
+            .flat_map(|rule| rule.thresholds.iter())
+            .filter_map(|name| Some((name.to_string(), config.threshold(name)?)))
+            .collect();
+
+        Ok(DryRunReport {
+            plans,
+            skipped: corpus.skipped,
+            thresholds,
+            rules: hits,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::PlanNode;
+
+    fn sort_plan(rows: u64) -> CorpusPlan {
+        CorpusPlan {
+            history_id: Some(rows as i64),
+            query: format!("SELECT * FROM orders ORDER BY created_at LIMIT {}", rows),
+            plan: ExecutionPlan {
+                root: PlanNode {
+                    node_type: "Sort".to_string(),
+                    total_cost: 100.0,
+                    actual_total_time: 5.0,
+                    actual_rows: rows,
+                    actual_loops: 1,
+                    ..PlanNode::default()
+                },
+                planning_time: 0.1,
+                execution_time: 5.0,
+                settings: None,
+                jit: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_dry_run_reports_hit_rates() {
+        let corpus = Corpus {
+            plans: vec![sort_plan(500), sort_plan(5_000), sort_plan(50_000)],
+            skipped: 1,
+        };
+        let mut base = AdvisorConfig::default();
+        base.disabled_rules.insert("large_sort".to_string());
+
+        let dry_run = DryRun {
+            rules: vec!["large_sort".to_string()],
+            thresholds: BTreeMap::from([("large_scan_threshold".to_string(), 1_000.0)]),
+            examples: Some(1),
+        };
+        let report = dry_run.run(&base, &corpus).unwrap();
+        assert_eq!(report.plans, 3);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.thresholds["large_scan_threshold"], 1_000.0);
+        assert_eq!(report.rules.len(), 1);
+
+        // Disabled rules run too, and only the chosen ones do
+        let hits = &report.rules[0];
+        assert_eq!(hits.rule_id, "large_sort");
+        assert!(!hits.enabled);
+        assert_eq!(hits.plans_hit, 2);
+        assert!((hits.hit_rate - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(hits.examples.len(), 1);
+        assert_eq!(hits.examples[0].history_id, Some(5_000));
+
+        let unknown = DryRun {
+            rules: vec!["no_such_rule".to_string()],
+            ..DryRun::default()
+        };
+        assert!(unknown
+            .run(&base, &corpus)
+            .unwrap_err()
+            .contains("no_such_rule"));
+        let invalid = DryRun {
+            thresholds: BTreeMap::from([("large_scan_threshold".to_string(), -1.0)]),
+            ..DryRun::default()
+        };
+        assert!(invalid.run(&base, &corpus).is_err());
+    }
+}
@@ -3,13 +3,26 @@
 //! This module provides rule-based analysis of PostgreSQL execution plans
 //! and suggests optimizations to improve query performance.
 
+use crate::db::index_catalog::TableIndex;
 use crate::db::models::{ExecutionPlan, PlanNode};
+use crate::db::relation_sizes::RelationSize;
+use crate::db::sequences::SequenceUsage;
+use crate::db::stats::TableStatistics;
+use crate::db::transaction::TempTableUsage;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+
+pub mod dry_run;
+pub mod rules;
+
+pub use rules::{RuleOverrides, SharedAdvisor};
 
 /// Represents a single optimization suggestion
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OptimizationSuggestion {
+    /// Identifier of the rule that produced the suggestion, stable across releases
+    #[serde(default)]
+    pub rule_id: String,
     /// Type of suggestion (e.g., "Index", "Query Rewrite", "Schema")
     pub suggestion_type: String,
     /// Severity level (High, Medium, Low)
@@ -61,6 +74,10 @@ pub struct AnalysisSummary {
     pub total_cost: f64,
     /// Potential improvement estimate
     pub potential_improvement: String,
+    /// Estimated peak memory of the plan in kilobytes, summed over the nodes
+    /// that reported their memory; absent when none did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_memory_kb: Option<u64>,
 }
 
 /// Query optimization advisor
@@ -81,6 +98,36 @@ pub struct AdvisorConfig {
     pub enable_index_suggestions: bool,
     /// Enable query rewrite suggestions
     pub enable_rewrite_suggestions: bool,
+    /// Iteration count above which a recursive CTE is considered runaway
+    pub recursion_iteration_threshold: u64,
+    /// Share of a Gather node's time spent on coordination above which parallelism is flagged
+    pub parallel_overhead_ratio: f64,
+    /// Rows per parallel process below which the work is considered too small to split
+    pub min_rows_per_worker: u64,
+    /// Share of a serial/identity column's range above which exhaustion is flagged
+    pub sequence_usage_threshold: f64,
+    /// Estimated row count below which a table is small enough that scanning it
+    /// whole is fine, so scan and index rules stay quiet about it
+    pub small_table_rows: u64,
+    /// Share of a table changed since it was last analyzed above which its
+    /// statistics are considered stale
+    pub stale_statistics_ratio: f64,
+    /// How many times the actual rows of a scan may differ from the estimate
+    /// before the estimate is considered wrong
+    pub misestimate_factor: f64,
+    /// Share of a Memoize node's lookups answered from its cache below which
+    /// the cache is considered ineffective
+    pub memoize_min_hit_ratio: f64,
+    /// Lookups a Memoize node needs before its hit ratio is judged
+    pub min_memoize_lookups: u64,
+    /// Share of a node's time spent waiting on its own reads and writes
+    /// above which the node is considered I/O bound
+    pub io_wait_ratio: f64,
+    /// Share of a query's execution time spent on JIT compilation above
+    /// which the compilation is considered not worth it
+    pub jit_overhead_ratio: f64,
+    /// Ids of rules whose suggestions are dropped
+    pub disabled_rules: BTreeSet<String>,
 }
 
 impl Default for AdvisorConfig {
@@ -90,10 +137,75 @@ impl Default for AdvisorConfig {
             large_scan_threshold: 10000,
             enable_index_suggestions: true,
             enable_rewrite_suggestions: true,
+            recursion_iteration_threshold: 1000,
+            parallel_overhead_ratio: 0.5,
+            min_rows_per_worker: 1000,
+            sequence_usage_threshold: 0.75,
+            small_table_rows: 1000,
+            stale_statistics_ratio: 0.2,
+            misestimate_factor: 10.0,
+            memoize_min_hit_ratio: 0.1,
+            min_memoize_lookups: 100,
+            io_wait_ratio: 0.5,
+            jit_overhead_ratio: 0.2,
+            disabled_rules: BTreeSet::new(),
         }
     }
 }
 
+/// Database metadata that rules can use in addition to the plan itself
+///
+/// Fields are filled in by the caller from catalog queries; rules that depend
+/// on a field simply find nothing to report when it is left empty.
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisContext {
+    /// Sequence consumption for serial/identity columns of the plan's relations
+    pub sequences: Vec<SequenceUsage>,
+    /// Size statistics of the plan's relations
+    pub relation_sizes: Vec<RelationSize>,
+    /// Planner statistics and modification counters of the plan's relations
+    pub table_statistics: Vec<TableStatistics>,
+    /// Indexes of the plan's relations
+    pub indexes: Vec<TableIndex>,
+    /// The plan holds planner estimates only, without actual row counts or
+    /// timings, so rules that judge what a query actually did are skipped
+    pub estimates_only: bool,
+}
+
+impl AnalysisContext {
+    /// Estimated rows of the table named `relation`, if known
+    ///
+    /// Plans name tables without their schema, so when tables of the same name
+    /// exist in several schemas the largest estimate is used.
+    pub fn estimated_rows(&self, relation: &str) -> Option<u64> {
+        self.relation_sizes
+            .iter()
+            .filter(|size| size.table_name == relation)
+            .filter_map(|size| size.estimated_rows)
+            .max()
+    }
+
+    /// Indexes of the tables named `relation`, in any schema
+    pub fn indexes_on<'a>(
+        &'a self,
+        relation: &'a str,
+    ) -> impl Iterator<Item = &'a TableIndex> + 'a {
+        self.indexes
+            .iter()
+            .filter(move |index| index.table_name == relation)
+    }
+
+    /// Statistics of the tables named `relation`, in any schema
+    pub fn statistics_of<'a>(
+        &'a self,
+        relation: &'a str,
+    ) -> impl Iterator<Item = &'a TableStatistics> + 'a {
+        self.table_statistics
+            .iter()
+            .filter(move |stats| stats.table_name == relation)
+    }
+}
+
 impl QueryAdvisor {
     /// Create a new query advisor with default configuration
     pub fn new() -> Self {
@@ -107,12 +219,32 @@ impl QueryAdvisor {
         Self { config }
     }
 
+    /// The configuration the advisor runs with
+    pub fn config(&self) -> &AdvisorConfig {
+        &self.config
+    }
+
     /// Analyze an execution plan and provide optimization suggestions
     pub fn analyze_plan(&self, plan: &ExecutionPlan) -> AdvisorAnalysis {
+        self.analyze_plan_with_context(plan, &AnalysisContext::default())
+    }
+
+    /// Analyze an execution plan together with database metadata
+    pub fn analyze_plan_with_context(
+        &self,
+        plan: &ExecutionPlan,
+        context: &AnalysisContext,
+    ) -> AdvisorAnalysis {
         let mut suggestions = Vec::new();
         let mut node_costs = HashMap::new();
 
-        self.analyze_node(&plan.root, &mut suggestions, &mut node_costs, 0);
+        self.analyze_node(&plan.root, context, &mut suggestions, &mut node_costs, 0);
+        self.check_sequence_headroom(context, &mut suggestions);
+        if !context.estimates_only {
+            self.check_io_timing(plan, &mut suggestions);
+            self.check_jit_overhead(plan, &mut suggestions);
+        }
+        suggestions.retain(|s| self.config.is_rule_enabled(&s.rule_id));
 
         let summary = self.generate_summary(&suggestions, &node_costs, plan);
         let performance_score = self.calculate_performance_score(&suggestions, plan);
@@ -124,10 +256,96 @@ impl QueryAdvisor {
         }
     }
 
+    /// Check the temporary tables a transaction creates before its queries join them
+    ///
+    /// `temp_buffers_bytes` is the session's `temp_buffers`; temporary tables
+    /// beyond it are read and written through the operating system instead.
+    pub fn analyze_temp_tables(
+        &self,
+        tables: &[TempTableUsage],
+        temp_buffers_bytes: Option<i64>,
+    ) -> Vec<OptimizationSuggestion> {
+        let mut suggestions = Vec::new();
+        for table in tables {
+            let Some(joined_in) = table.joined_in else {
+                continue;
+            };
+
+            if table.joined_before_analyze() {
+                suggestions.push(OptimizationSuggestion {
+                    rule_id: "temp_table_not_analyzed".to_string(),
+                    suggestion_type: "Statistics".to_string(),
+                    severity: Severity::Medium,
+                    title: "Temporary Table Joined Before ANALYZE".to_string(),
+                    description: format!(
+                        "Temporary table {} is joined in statement {} without statistics; autovacuum never analyzes temporary tables, so the planner guesses its row count and value distribution.",
+                        table.name, joined_in
+                    ),
+                    recommendation: format!(
+                        "Run ANALYZE {} after filling it and before statement {}.",
+                        table.name, joined_in
+                    ),
+                    node_index: None,
+                    impact: "Medium - Misestimated joins can pick the wrong join method or order"
+                        .to_string(),
+                });
+            }
+
+            let large = table
+                .size_bytes
+                .is_none_or(|size| size >= TEMP_TABLE_INDEX_MIN_BYTES);
+            if large && table.joined_before_index() {
+                suggestions.push(OptimizationSuggestion {
+                    rule_id: "temp_table_not_indexed".to_string(),
+                    suggestion_type: "Index".to_string(),
+                    severity: Severity::Medium,
+                    title: "Temporary Table Joined Without Index".to_string(),
+                    description: format!(
+                        "Temporary table {}{} has no index when statement {} joins it, so every join reads it in full.",
+                        table.name,
+                        table
+                            .size_bytes
+                            .map(|size| format!(" ({} kB)", size / 1024))
+                            .unwrap_or_default(),
+                        joined_in
+                    ),
+                    recommendation: format!(
+                        "Create an index on the join columns of {} before statement {}, then ANALYZE it.",
+                        table.name, joined_in
+                    ),
+                    node_index: None,
+                    impact: "Medium - Could avoid repeated full scans of the temporary table"
+                        .to_string(),
+                });
+            }
+        }
+
+        let total: i64 = tables.iter().filter_map(|table| table.size_bytes).sum();
+        if let Some(temp_buffers) = temp_buffers_bytes.filter(|&buffers| total > buffers) {
+            suggestions.push(OptimizationSuggestion {
+                rule_id: "temp_buffers_exceeded".to_string(),
+                suggestion_type: "Configuration".to_string(),
+                severity: Severity::Low,
+                title: "Temporary Tables Exceed temp_buffers".to_string(),
+                description: format!(
+                    "The temporary tables reach {} kB, more than the {} kB of temp_buffers, so part of them is written to disk.",
+                    total / 1024,
+                    temp_buffers / 1024
+                ),
+                recommendation: "Raise temp_buffers for the session before it first uses a temporary table, or stage fewer rows.".to_string(),
+                node_index: None,
+                impact: "Low - Temporary table access goes through the operating system".to_string(),
+            });
+        }
+        suggestions.retain(|s| self.config.is_rule_enabled(&s.rule_id));
+        suggestions
+    }
+
     /// Recursively analyze plan nodes
     fn analyze_node(
         &self,
         node: &PlanNode,
+        context: &AnalysisContext,
         suggestions: &mut Vec<OptimizationSuggestion>,
         node_costs: &mut HashMap<String, f64>,
         node_index: usize,
@@ -135,27 +353,65 @@ impl QueryAdvisor {
         node_costs.insert(node.node_type.clone(), node.total_cost);
 
         // Apply optimization rules
-        self.check_sequential_scan(node, suggestions, node_index);
+        self.check_sequential_scan(node, context, suggestions, node_index);
         self.check_expensive_operations(node, suggestions, node_index);
-        self.check_nested_loops(node, suggestions, node_index);
-        self.check_large_sorts(node, suggestions, node_index);
-        self.check_missing_indexes(node, suggestions, node_index);
+        // Rules that judge actual row counts and timings need an analyzed plan
+        let analyzed = !context.estimates_only;
+        if analyzed {
+            self.check_nested_loops(node, suggestions, node_index);
+            self.check_large_sorts(node, suggestions, node_index);
+        }
+        self.check_missing_indexes(node, context, suggestions, node_index);
+        self.check_system_columns(node, suggestions, node_index);
         self.check_inefficient_joins(node, suggestions, node_index);
+        self.check_foreign_join(node, suggestions, node_index);
+        self.check_recursive_cte(node, suggestions, node_index);
+        self.check_storage_scan(node, suggestions, node_index);
+        self.check_pseudo_stats(node, suggestions, node_index);
+        self.check_incremental_sort(node, context, suggestions, node_index);
+        if analyzed {
+            self.check_hash_agg_spill(node, suggestions, node_index);
+            self.check_hash_spill(node, suggestions, node_index);
+            self.check_foreign_filter(node, suggestions, node_index);
+            self.check_sort_spill(node, suggestions, node_index);
+            self.check_memoize(node, suggestions, node_index);
+            self.check_gather_overhead(node, suggestions, node_index);
+            self.check_workers_launched(node, suggestions, node_index);
+            self.check_stale_statistics(node, context, suggestions, node_index);
+            self.check_io_wait(node, suggestions, node_index);
+        }
 
         for (i, child) in node.plans.iter().enumerate() {
-            self.analyze_node(child, suggestions, node_costs, node_index + i + 1);
+            self.analyze_node(child, context, suggestions, node_costs, node_index + i + 1);
         }
     }
 
+    /// Whether statistics show that the node reads a table small enough to scan whole
+    fn reads_small_table(&self, node: &PlanNode, context: &AnalysisContext) -> bool {
+        node.relation_name
+            .as_deref()
+            .and_then(|relation| context.estimated_rows(relation))
+            .is_some_and(|rows| rows < self.config.small_table_rows)
+    }
+
     /// Check for expensive sequential scans
     fn check_sequential_scan(
         &self,
         node: &PlanNode,
+        context: &AnalysisContext,
         suggestions: &mut Vec<OptimizationSuggestion>,
         node_index: usize,
     ) {
-        if node.node_type == "Seq Scan" && node.total_cost > self.config.expensive_cost_threshold {
+        if node.node_type == "Seq Scan"
+            && node.total_cost > self.config.expensive_cost_threshold
+            && !self.reads_small_table(node, context)
+            && !node
+                .filter
+                .as_deref()
+                .is_some_and(filters_only_system_columns)
+        {
             suggestions.push(OptimizationSuggestion {
+                rule_id: "expensive_seq_scan".to_string(),
                 suggestion_type: "Index".to_string(),
                 severity: Severity::High,
                 title: "Expensive Sequential Scan Detected".to_string(),
@@ -180,6 +436,7 @@ impl QueryAdvisor {
     ) {
         if node.total_cost > self.config.expensive_cost_threshold * 2.0 {
             suggestions.push(OptimizationSuggestion {
+                rule_id: "expensive_operation".to_string(),
                 suggestion_type: "Performance".to_string(),
                 severity: Severity::Medium,
                 title: format!("Expensive {} Operation", node.node_type),
@@ -201,14 +458,15 @@ impl QueryAdvisor {
         suggestions: &mut Vec<OptimizationSuggestion>,
         node_index: usize,
     ) {
-        if node.node_type == "Nested Loop" && node.actual_rows > self.config.large_scan_threshold {
+        if node.node_type == "Nested Loop" && node.total_rows() > self.config.large_scan_threshold {
             suggestions.push(OptimizationSuggestion {
+                rule_id: "nested_loop".to_string(),
                 suggestion_type: "Join".to_string(),
                 severity: Severity::High,
                 title: "Inefficient Nested Loop Join".to_string(),
                 description: format!(
                     "Nested loop join processing {} rows. This join method is inefficient for large datasets.",
-                    node.actual_rows
+                    node.total_rows()
                 ),
                 recommendation: "Consider adding indexes on join columns or restructuring the query to use hash or merge joins.".to_string(),
                 node_index: Some(node_index),
@@ -218,56 +476,198 @@ impl QueryAdvisor {
     }
 
     /// Check for large sort operations
+    ///
+    /// A large sort that fit in memory costs CPU only, so it is reported at a
+    /// lower severity than one that spilled to disk or whose method is unknown.
     fn check_large_sorts(
         &self,
         node: &PlanNode,
         suggestions: &mut Vec<OptimizationSuggestion>,
         node_index: usize,
     ) {
-        if node.node_type == "Sort" && node.actual_rows > self.config.large_scan_threshold {
+        if node.node_type != "Sort" || node.total_rows() <= self.config.large_scan_threshold {
+            return;
+        }
+
+        let rows = node.total_rows();
+        let method = node.sort_method.as_deref();
+        let space_kb = node.sort_space_used_kb.unwrap_or(0);
+        let (severity, description, impact) = if node.sort_spilled() {
+            (
+                Severity::Medium,
+                format!(
+                    "Sort of {} rows used an {} and spilled {} kB to disk.",
+                    rows,
+                    method.unwrap_or("external sort"),
+                    space_kb
+                ),
+                "Medium - Avoiding the sort also avoids its temporary file I/O",
+            )
+        } else if node.sort_in_memory() {
+            (
+                Severity::Low,
+                format!(
+                    "Sort of {} rows ran in memory as a {} using {} kB, so it costs CPU time but no disk I/O.",
+                    rows,
+                    method.unwrap_or("quicksort"),
+                    space_kb
+                ),
+                "Low - The sort fits in work_mem; an index would only save CPU time",
+            )
+        } else {
+            (
+                Severity::Medium,
+                format!(
+                    "Sort operation processing {} rows. Large sorts can be memory intensive.",
+                    rows
+                ),
+                "Medium - Could reduce memory usage and improve performance",
+            )
+        };
+
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "large_sort".to_string(),
+            suggestion_type: "Index".to_string(),
+            severity,
+            title: "Large Sort Operation".to_string(),
+            description,
+            recommendation: "Consider adding an index on the ORDER BY columns to avoid sorting, or limit result sets.".to_string(),
+            node_index: Some(node_index),
+            impact: impact.to_string(),
+        });
+    }
+
+    /// Check for missing indexes (heuristic-based)
+    fn check_missing_indexes(
+        &self,
+        node: &PlanNode,
+        context: &AnalysisContext,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        // An index would not beat scanning a small table, and foreign tables can't be indexed
+        if !self.config.enable_index_suggestions
+            || self.reads_small_table(node, context)
+            || node.is_foreign_scan()
+        {
+            return;
+        }
+
+        // Check for filter conditions that might benefit from indexes
+        let Some(filter) = node.filter.as_deref() else {
+            return;
+        };
+
+        // System columns can't be indexed, so there is nothing to suggest
+        if filters_only_system_columns(filter) {
+            return;
+        }
+
+        // An index on a filtered column that the node does not use was passed over, not missing
+        let columns = filter_columns(filter);
+        let unused = node.relation_name.as_deref().and_then(|relation| {
+            context.indexes_on(relation).find(|index| {
+                index.is_valid
+                    && node.extra_str("Index Name") != Some(index.index_name.as_str())
+                    && index.leading_column().is_some_and(|leading| {
+                        columns.iter().any(|c| c.eq_ignore_ascii_case(leading))
+                    })
+            })
+        });
+
+        if let Some(index) = unused {
+            let partial = index
+                .predicate
+                .as_deref()
+                .map(|predicate| {
+                    format!(
+                        " It is a partial index over rows where {}, so it only applies when the query implies that condition.",
+                        predicate
+                    )
+                })
+                .unwrap_or_default();
             suggestions.push(OptimizationSuggestion {
+                rule_id: "index_not_used".to_string(),
                 suggestion_type: "Index".to_string(),
                 severity: Severity::Medium,
-                title: "Large Sort Operation".to_string(),
+                title: "Existing Index Not Used".to_string(),
                 description: format!(
-                    "Sort operation processing {} rows. Large sorts can be memory intensive.",
-                    node.actual_rows
+                    "Filter condition detected: {}. Index {} on {}.{} ({}) starts with a filtered column, but the planner did not use it.{}",
+                    filter,
+                    index.index_name,
+                    index.schema_name,
+                    index.table_name,
+                    index.columns.join(", "),
+                    partial
+                ),
+                recommendation: format!(
+                    "Compare {} itself with a value of the same type: functions, casts or a leading wildcard on the column keep the index out. If the condition matches a large share of the table, the scan is the cheaper plan; otherwise run ANALYZE on the table so the planner sees how selective it is.",
+                    index.leading_column().unwrap_or("the column")
                 ),
-                recommendation: "Consider adding an index on the ORDER BY columns to avoid sorting, or limit result sets.".to_string(),
                 node_index: Some(node_index),
-                impact: "Medium - Could reduce memory usage and improve performance".to_string(),
+                impact: "Medium - Could improve filtering performance without a new index".to_string(),
             });
+            return;
         }
+
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "index_opportunity".to_string(),
+            suggestion_type: "Index".to_string(),
+            severity: Severity::Medium,
+            title: "Potential Index Opportunity".to_string(),
+            description: format!(
+                "Filter condition detected: {}. This might benefit from an index.",
+                filter
+            ),
+            recommendation:
+                "Consider creating an index on the filtered column(s) to improve query performance."
+                    .to_string(),
+            node_index: Some(node_index),
+            impact: "Medium - Could improve filtering performance".to_string(),
+        });
     }
 
-    /// Check for missing indexes (heuristic-based)
-    fn check_missing_indexes(
+    /// Check for conditions on system columns such as `ctid`, which don't identify rows for long
+    fn check_system_columns(
         &self,
         node: &PlanNode,
         suggestions: &mut Vec<OptimizationSuggestion>,
         node_index: usize,
     ) {
-        if !self.config.enable_index_suggestions {
+        let mut columns: Vec<String> = Vec::new();
+        let conditions = [
+            node.extra_str("TID Cond"),
+            node.filter.as_deref(),
+            node.index_cond.as_deref(),
+            node.extra_str("Recheck Cond"),
+        ];
+        for condition in conditions.into_iter().flatten() {
+            for column in filter_columns(condition) {
+                let column = column.to_lowercase();
+                if is_system_column(&column) && !columns.contains(&column) {
+                    columns.push(column);
+                }
+            }
+        }
+        if columns.is_empty() {
             return;
         }
 
-        // Check for filter conditions that might benefit from indexes
-        if let Some(extra) = node.extra.as_object() {
-            if let Some(filter) = extra.get("Filter") {
-                suggestions.push(OptimizationSuggestion {
-                    suggestion_type: "Index".to_string(),
-                    severity: Severity::Medium,
-                    title: "Potential Index Opportunity".to_string(),
-                    description: format!(
-                        "Filter condition detected: {}. This might benefit from an index.",
-                        filter.as_str().unwrap_or("complex condition")
-                    ),
-                    recommendation: "Consider creating an index on the filtered column(s) to improve query performance.".to_string(),
-                    node_index: Some(node_index),
-                    impact: "Medium - Could improve filtering performance".to_string(),
-                });
-            }
-        }
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "system_column_filter".to_string(),
+            suggestion_type: "Query Rewrite".to_string(),
+            severity: Severity::Medium,
+            title: "Filter on System Column".to_string(),
+            description: format!(
+                "{} on '{}' filters on the system column(s) {}. A row's ctid changes whenever the row is updated and when VACUUM FULL or CLUSTER rewrites the table, and xmin and xmax hold transaction IDs that wrap around, so a value read earlier may later match a different row or none.",
+                node.node_type,
+                node.relation_name.as_deref().unwrap_or("unknown"),
+                columns.join(", ")
+            ),
+            recommendation: "Identify rows by the primary key or another unique key instead, and page through large tables by key ranges. System columns can't be indexed, so no index will make this filter faster.".to_string(),
+            node_index: Some(node_index),
+            impact: "Medium - Avoids queries that silently miss rows or match the wrong ones".to_string(),
+        });
     }
 
     /// Check for inefficient join strategies
@@ -281,6 +681,7 @@ impl QueryAdvisor {
         {
             let join_type = &node.node_type;
             suggestions.push(OptimizationSuggestion {
+                rule_id: "expensive_join".to_string(),
                 suggestion_type: "Join".to_string(),
                 severity: Severity::Medium,
                 title: format!("Expensive {} Operation", join_type),
@@ -295,69 +696,2121 @@ impl QueryAdvisor {
         }
     }
 
-    /// Generate analysis summary
-    fn generate_summary(
+    /// Check recursive CTEs for runaway recursion and missing cycle protection
+    fn check_recursive_cte(
         &self,
-        suggestions: &[OptimizationSuggestion],
-        node_costs: &HashMap<String, f64>,
-        plan: &ExecutionPlan,
-    ) -> AnalysisSummary {
-        let high_severity_count = suggestions
-            .iter()
-            .filter(|s| matches!(s.severity, Severity::High))
-            .count();
+        node: &PlanNode,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        if node.node_type != "Recursive Union" {
+            return;
+        }
 
-        let most_expensive_operation = node_costs
+        // The second child is the recursive term, which runs once per iteration
+        let Some(recursive_term) = node.plans.get(1) else {
+            return;
+        };
+
+        let work_table = recursive_term
             .iter()
-            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
-            .map(|(op, _)| op.clone())
-            .unwrap_or_else(|| "Unknown".to_string());
+            .find(|n| n.node_type == "WorkTable Scan");
+        let cte_name = work_table
+            .and_then(|n| n.extra_str("CTE Name"))
+            .unwrap_or("unknown");
+        let iterations = work_table.map(|n| n.actual_loops).unwrap_or(0);
+        let protected = Self::has_cycle_protection(recursive_term);
 
-        let potential_improvement = match high_severity_count {
-            0 => "Low - Query appears well optimized".to_string(),
-            1..=2 => "Medium - Some optimization opportunities available".to_string(),
-            _ => "High - Significant optimization potential".to_string(),
-        };
+        if iterations > self.config.recursion_iteration_threshold {
+            suggestions.push(OptimizationSuggestion {
+                rule_id: "runaway_recursive_cte".to_string(),
+                suggestion_type: "Query Rewrite".to_string(),
+                severity: Severity::High,
+                title: "Runaway Recursive CTE".to_string(),
+                description: format!(
+                    "Recursive CTE '{}' iterated {} times and produced {} rows. This usually means the recursion follows a cycle or lacks a termination condition.",
+                    cte_name,
+                    iterations,
+                    node.total_rows()
+                ),
+                recommendation: if protected {
+                    "Tighten the termination condition of the recursive term, e.g. a lower depth limit.".to_string()
+                } else {
+                    "Add cycle protection (the CYCLE clause on PostgreSQL 14+, or a path array checked with <> ALL) and a depth limit to the recursive term.".to_string()
+                },
+                node_index: Some(node_index),
+                impact: "High - Unbounded recursion can run until it exhausts memory or disk".to_string(),
+            });
+        } else if !protected {
+            suggestions.push(OptimizationSuggestion {
+                rule_id: "recursive_cte_cycle".to_string(),
+                suggestion_type: "Query Rewrite".to_string(),
+                severity: Severity::Low,
+                title: "Recursive CTE Without Cycle Protection".to_string(),
+                description: format!(
+                    "No cycle check or depth limit was found in the recursive term of CTE '{}'. Cyclic data would make it recurse indefinitely.",
+                    cte_name
+                ),
+                recommendation: "Add a CYCLE clause (PostgreSQL 14+) or track visited keys in a path array, and consider a depth limit.".to_string(),
+                node_index: Some(node_index),
+                impact: "Low - Protects against runaway queries on cyclic data".to_string(),
+            });
+        }
 
-        AnalysisSummary {
-            total_suggestions: suggestions.len(),
-            high_severity_count,
-            most_expensive_operation,
-            total_cost: plan.root.total_cost,
-            potential_improvement,
+        // Base relations the recursive term rescans on every iteration
+        for scan in recursive_term.iter().filter(|n| {
+            n.relation_name.is_some()
+                && n.node_type.contains("Scan")
+                && n.actual_loops > 1
+                && n.total_rows() > self.config.large_scan_threshold
+        }) {
+            suggestions.push(OptimizationSuggestion {
+                rule_id: "recursive_rescan".to_string(),
+                suggestion_type: "Query Rewrite".to_string(),
+                severity: Severity::Medium,
+                title: "Recursive Term Rescans Relation Every Iteration".to_string(),
+                description: format!(
+                    "{} on '{}' runs once per iteration of CTE '{}' ({} loops, {} rows in total).",
+                    scan.node_type,
+                    scan.relation_name.as_deref().unwrap_or("unknown"),
+                    cte_name,
+                    scan.actual_loops,
+                    scan.total_rows()
+                ),
+                recommendation: "Materialize the intermediate result into a temporary table with an index on the join key, or index the join column used by the recursive term.".to_string(),
+                node_index: Some(node_index),
+                impact: "Medium - Avoids repeating the same scan on every iteration".to_string(),
+            });
         }
     }
 
-    /// Calculate overall performance score (0-100)
-    fn calculate_performance_score(
+    /// Heuristically detect a cycle check or depth limit in a recursive term
+    fn has_cycle_protection(recursive_term: &PlanNode) -> bool {
+        const MARKERS: [&str; 5] = ["is_cycle", "<> ALL", "= ANY", " < ", " <= "];
+
+        recursive_term.iter().any(|n| {
+            [n.filter.as_deref(), n.join_filter.as_deref()]
+                .into_iter()
+                .flatten()
+                .any(|cond| MARKERS.iter().any(|marker| cond.contains(marker)))
+        })
+    }
+
+    /// Check for hash aggregates that spilled to disk
+    fn check_hash_agg_spill(
         &self,
-        suggestions: &[OptimizationSuggestion],
-        plan: &ExecutionPlan,
-    ) -> u8 {
-        let mut score = 100u8;
+        node: &PlanNode,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        if !node.is_hash_aggregate() || !node.hash_agg_spilled() {
+            return;
+        }
 
-        // Deduct points for suggestions
-        for suggestion in suggestions {
-            let deduction = match suggestion.severity {
-                Severity::High => 20,
-                Severity::Medium => 10,
-                Severity::Low => 5,
+        let peak_kb = node.peak_memory_usage_kb.unwrap_or(0);
+        let disk_kb = node.disk_usage_kb.unwrap_or(0);
+        let needed_mb = (peak_kb + disk_kb).div_ceil(1024).max(1);
+        let group_key = node
+            .extra
+            .get("Group Key")
+            .and_then(|v| v.as_array())
+            .map(|keys| {
+                keys.iter()
+                    .filter_map(|k| k.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .filter(|keys| !keys.is_empty());
+
+        let mut recommendation = format!(
+            "Raise work_mem (or hash_mem_multiplier) for this query to roughly {} MB, pre-aggregate in a subquery to shrink the number of groups",
+            needed_mb
+        );
+        match &group_key {
+            Some(keys) => recommendation.push_str(&format!(
+                ", or add an index on ({}) so the planner can use a sorted GroupAggregate instead.",
+                keys
+            )),
+            None => recommendation.push_str(
+                ", or add an index on the GROUP BY columns so the planner can use a sorted GroupAggregate instead.",
+            ),
+        }
+
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "hash_agg_spill".to_string(),
+            suggestion_type: "Configuration".to_string(),
+            severity: Severity::Medium,
+            title: "Hash Aggregate Spilled to Disk".to_string(),
+            description: format!(
+                "Hash aggregate used {} batches ({} planned partitions), peaking at {} kB of memory and writing {} kB to disk.",
+                node.hash_agg_batches.unwrap_or(1),
+                node.planned_partitions.unwrap_or(0),
+                peak_kb,
+                disk_kb
+            ),
+            recommendation,
+            node_index: Some(node_index),
+            impact: "Medium - Keeping the hash table in memory avoids temporary file I/O".to_string(),
+        });
+    }
+
+    /// Check for Hash nodes that split the inner side of a hash join into batches
+    ///
+    /// Each batch but the first is written to a temporary file, along with the
+    /// matching rows of the outer side, and read back once the previous batch
+    /// is joined. The peak memory a Hash node reports is that of one batch, so
+    /// the whole inner side takes about the peak times the number of batches.
+    fn check_hash_spill(
+        &self,
+        node: &PlanNode,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        if !node.hash_spilled() {
+            return;
+        }
+
+        let batches = node.hash_batches.unwrap_or(1);
+        let peak_kb = node.peak_memory_usage_kb.unwrap_or(0);
+        let needed_mb = (peak_kb * batches).div_ceil(1024).max(1);
+
+        let mut description = format!(
+            "Hash of {} rows was split into {} batches, peaking at {} kB of memory per batch, so both sides of the join were partly written to temporary files.",
+            node.total_rows(),
+            batches,
+            peak_kb
+        );
+        let mut recommendation = format!(
+            "Raise work_mem (or hash_mem_multiplier) for this query to roughly {} MB so the hash table fits in one batch, or put fewer or narrower rows on the inner side of the join.",
+            needed_mb
+        );
+        if node.hash_batches_grew() {
+            let planned = match node.original_hash_batches.unwrap_or(1) {
+                1 => "one batch".to_string(),
+                n => format!("{} batches", n),
             };
-            score = score.saturating_sub(deduction);
+            description.push_str(&format!(
+                " The planner expected {}; the inner side turned out larger than estimated.",
+                planned
+            ));
+            recommendation.push_str(
+                " Run ANALYZE on the inner tables so the planner sizes the join correctly.",
+            );
         }
 
-        // Consider execution time if available
-        if plan.execution_time > 1000.0 {
-            score = score.saturating_sub(10);
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "hash_join_spill".to_string(),
+            suggestion_type: "Configuration".to_string(),
+            severity: Severity::Medium,
+            title: "Hash Join Spilled to Disk".to_string(),
+            description,
+            recommendation,
+            node_index: Some(node_index),
+            impact: "Medium - Joining in one batch avoids writing and rereading both join inputs"
+                .to_string(),
+        });
+    }
+
+    /// Check for Incremental Sorts whose remaining sort keys an index could supply
+    ///
+    /// An Incremental Sort only sorts by the keys its input isn't already
+    /// ordered by, which beats a full sort, but an index covering all of the
+    /// keys would remove the sort altogether.
+    fn check_incremental_sort(
+        &self,
+        node: &PlanNode,
+        context: &AnalysisContext,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        if node.node_type != "Incremental Sort" || !self.config.enable_index_suggestions {
+            return;
+        }
+        let presorted = node.presorted_key.as_deref().unwrap_or_default();
+        let keys = node.sort_keys();
+        if presorted.is_empty() || keys.len() <= presorted.len() {
+            return;
         }
+        let remaining = &keys[presorted.len()..];
 
-        // Ensure minimum score
-        score.max(10)
+        let rows = if context.estimates_only {
+            node.extra_u64("Plan Rows").unwrap_or(0)
+        } else {
+            node.total_rows()
+        };
+        let spilled_kb = node.incremental_sort_disk_kb();
+        if rows <= self.config.large_scan_threshold && spilled_kb.is_none() {
+            return;
+        }
+
+        let mut description = format!(
+            "Input arrives ordered by {} already, so only the remaining keys {} are sorted, one group of equal leading keys at a time.",
+            presorted.join(", "),
+            remaining.join(", ")
+        );
+        if let Some(groups) = &node.full_sort_groups {
+            description.push_str(&format!(
+                " {} groups were sorted by all keys",
+                groups.group_count
+            ));
+            match &node.pre_sorted_groups {
+                Some(large) => description.push_str(&format!(
+                    " and {} large groups by the remaining keys only.",
+                    large.group_count
+                )),
+                None => description.push('.'),
+            }
+        }
+        if let Some(kb) = spilled_kb {
+            description.push_str(&format!(" The largest group spilled {} kB to disk.", kb));
+        }
+
+        // The scan supplying the presorted order is the table to index
+        let columns: Vec<String> = keys.iter().map(|key| unqualified(key)).collect();
+        let recommendation = match node.iter().skip(1).find_map(|n| n.relation_name.as_deref()) {
+            Some(relation) => match context.indexes_on(relation).find(|index| {
+                index.is_valid && index_covers(&index.columns, &columns)
+            }) {
+                Some(index) => format!(
+                    "Index {} on {} already covers all sort keys, but the planner preferred an index on the leading keys only; check that the sort directions match the index and run ANALYZE on {} if its statistics are stale.",
+                    index.index_name, relation, relation
+                ),
+                None => format!(
+                    "Create an index on {} ({}) so rows come out in the full order and the sort disappears; with a LIMIT the query can then stop after the first rows.",
+                    relation,
+                    columns.join(", ")
+                ),
+            },
+            None => format!(
+                "An index on ({}) would supply the full order and remove the sort.",
+                columns.join(", ")
+            ),
+        };
+
+        let (severity, impact) = match spilled_kb {
+            Some(_) => (
+                Severity::Medium,
+                "Medium - Removing the sort also avoids the temporary file I/O of spilled groups",
+            ),
+            None => (
+                Severity::Low,
+                "Low - The input is already partly ordered, but an index would skip sorting entirely",
+            ),
+        };
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "incremental_sort".to_string(),
+            suggestion_type: "Index".to_string(),
+            severity,
+            title: "Incremental Sort Could Be Avoided".to_string(),
+            description,
+            recommendation,
+            node_index: Some(node_index),
+            impact: impact.to_string(),
+        });
     }
-}
 
-impl Default for QueryAdvisor {
-    fn default() -> Self {
-        Self::new()
+    /// Check for sorts that spilled to disk
+    fn check_sort_spill(
+        &self,
+        node: &PlanNode,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        if !node.sort_spilled() {
+            return;
+        }
+
+        // An in-memory sort needs noticeably more space than the same rows
+        // take in a sorted run on disk
+        let disk_kb = node.sort_space_used_kb.unwrap_or(0);
+        let needed_mb = (disk_kb * 2).div_ceil(1024).max(1);
+        let method = node.sort_method.as_deref().unwrap_or("external sort");
+
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "sort_spill".to_string(),
+            suggestion_type: "Configuration".to_string(),
+            severity: Severity::Medium,
+            title: "Sort Spilled to Disk".to_string(),
+            description: format!(
+                "Sort of {} rows used an {} and wrote {} kB to disk because it did not fit in work_mem.",
+                node.total_rows(),
+                method,
+                disk_kb
+            ),
+            recommendation: format!(
+                "Raise work_mem for this query to roughly {} MB, sort fewer or narrower rows, or add an index on the sort keys so the rows come out already ordered.",
+                needed_mb
+            ),
+            node_index: Some(node_index),
+            impact: "Medium - Sorting in memory avoids temporary file I/O".to_string(),
+        });
+    }
+
+    /// Check for Memoize nodes that rarely hit their cache or had to evict entries
+    fn check_memoize(
+        &self,
+        node: &PlanNode,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        if node.node_type != "Memoize" {
+            return;
+        }
+
+        let hits = node.cache_hits.unwrap_or(0);
+        let misses = node.cache_misses.unwrap_or(0);
+        let evictions = node.cache_evictions.unwrap_or(0);
+        let overflows = node.cache_overflows.unwrap_or(0);
+        let hit_ratio = node.cache_hit_ratio().unwrap_or(0.0);
+
+        if hits + misses >= self.config.min_memoize_lookups
+            && hit_ratio < self.config.memoize_min_hit_ratio
+        {
+            let mut description = format!(
+                "Memoize answered only {:.1}% of {} lookups from its cache ({} hits, {} misses, {} evictions), so nearly every lookup still ran the inner plan and paid for caching on top.",
+                hit_ratio * 100.0,
+                hits + misses,
+                hits,
+                misses,
+                evictions
+            );
+            // PostgreSQL 17 reports the planner's expectations with VERBOSE
+            if let Some(expected) = node
+                .extra
+                .get("Estimated Hit Percent")
+                .and_then(|v| v.as_f64())
+            {
+                description.push_str(&format!(
+                    " The planner expected a {:.1}% hit ratio.",
+                    expected
+                ));
+            }
+
+            let mut recommendation = String::new();
+            if evictions > 0 {
+                recommendation.push_str(&format!(
+                    "The cache evicted {} entries; raise work_mem or hash_mem_multiplier so repeated keys stay cached. ",
+                    evictions
+                ));
+            }
+            let cache_key = node
+                .extra_str("Cache Key")
+                .map(|key| format!(" ({})", key))
+                .unwrap_or_default();
+            recommendation.push_str(&format!(
+                "If the cache key{} is mostly distinct, the planner overestimated how often keys repeat: run ANALYZE on the outer table, question the join order (a hash join of the two sides may be cheaper), or compare with SET enable_memoize = off.",
+                cache_key
+            ));
+
+            suggestions.push(OptimizationSuggestion {
+                rule_id: "memoize_ineffective".to_string(),
+                suggestion_type: "Join".to_string(),
+                severity: Severity::Medium,
+                title: "Ineffective Memoize Cache".to_string(),
+                description,
+                recommendation,
+                node_index: Some(node_index),
+                impact: "Medium - Nearly every lookup pays for both the cache and the inner plan"
+                    .to_string(),
+            });
+            return;
+        }
+
+        if evictions == 0 && overflows == 0 {
+            return;
+        }
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "memoize_evictions".to_string(),
+            suggestion_type: "Configuration".to_string(),
+            severity: Severity::Low,
+            title: "Memoize Cache Too Small".to_string(),
+            description: format!(
+                "Memoize answered {:.0}% of lookups from its cache ({} hits, {} misses) but evicted {} entries and overflowed {} times, peaking at {} kB of memory.",
+                hit_ratio * 100.0,
+                hits,
+                misses,
+                evictions,
+                overflows,
+                node.peak_memory_usage_kb.unwrap_or(0)
+            ),
+            recommendation: "Raise work_mem or hash_mem_multiplier for this query so the cache holds every distinct lookup key; evicted entries have to be looked up in the inner plan again.".to_string(),
+            node_index: Some(node_index),
+            impact: "Low - Fewer repeated executions of the parameterized inner plan".to_string(),
+        });
+    }
+
+    /// Check for foreign scans whose rows are mostly discarded by a filter
+    /// postgres_fdw could not send to the remote server
+    fn check_foreign_filter(
+        &self,
+        node: &PlanNode,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        if !node.is_foreign_scan() {
+            return;
+        }
+        let Some(filter) = node.filter.as_deref() else {
+            return;
+        };
+        let fetched = node.rows_before_filter();
+        let discarded = fetched - node.total_rows();
+        if fetched <= self.config.large_scan_threshold || discarded * 2 < fetched {
+            return;
+        }
+
+        let remote = node
+            .remote_sql
+            .as_deref()
+            .map(|sql| format!(" The remote server was sent: {}", sql))
+            .unwrap_or_default();
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "foreign_filter_not_pushed".to_string(),
+            suggestion_type: "Query Rewrite".to_string(),
+            severity: Severity::High,
+            title: "Foreign Scan Filtered Locally".to_string(),
+            description: format!(
+                "Foreign Scan on '{}' fetched {} rows from the remote server and discarded {} of them locally with Filter: {}. The condition was not pushed down, so every row crossed the network first.{}",
+                node.relation_name.as_deref().unwrap_or("unknown"),
+                fetched,
+                discarded,
+                filter,
+                remote
+            ),
+            recommendation: "postgres_fdw only sends conditions built from built-in immutable operators and functions, or from extensions listed in the server's extensions option. Rewrite the condition with those, for example comparing the column to a constant computed up front instead of calling a volatile or stable function, or ALTER SERVER ... OPTIONS (ADD extensions '...') for extension functions, and check the Remote SQL with EXPLAIN VERBOSE.".to_string(),
+            node_index: Some(node_index),
+            impact: "High - Only the matching rows would be fetched from the remote server".to_string(),
+        });
+    }
+
+    /// Check for joins of foreign tables executed locally instead of on the remote server
+    fn check_foreign_join(
+        &self,
+        node: &PlanNode,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        if !matches!(
+            node.node_type.as_str(),
+            "Hash Join" | "Merge Join" | "Nested Loop"
+        ) {
+            return;
+        }
+        let scans: Vec<&PlanNode> = node.plans.iter().filter_map(foreign_input).collect();
+        if scans.len() < 2 {
+            return;
+        }
+
+        let tables: Vec<&str> = scans
+            .iter()
+            .map(|scan| {
+                scan.relation_name
+                    .as_deref()
+                    .or(scan.foreign_relations.as_deref())
+                    .unwrap_or("unknown")
+            })
+            .collect();
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "foreign_join_not_pushed".to_string(),
+            suggestion_type: "Join".to_string(),
+            severity: Severity::Medium,
+            title: "Foreign Tables Joined Locally".to_string(),
+            description: format!(
+                "{} joins the foreign tables {} locally, so the rows of each are fetched in full before the join.",
+                node.node_type,
+                tables.join(" and ")
+            ),
+            recommendation: "postgres_fdw pushes a join to the remote server only when both tables belong to the same foreign server and user mapping and the join condition can be sent. Check that they do; with use_remote_estimate 'true' on the server, the planner can also cost the remote join. Otherwise a view joining the tables on the remote server, imported as one foreign table, moves the join there.".to_string(),
+            node_index: Some(node_index),
+            impact: "Medium - Only the joined rows would be fetched from the remote server".to_string(),
+        });
+    }
+
+    /// Check for full table scans executed in TiKV, TiDB's row store
+    fn check_storage_scan(
+        &self,
+        node: &PlanNode,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        if node.node_type != "Seq Scan" || node.extra_str("Storage Engine") != Some("TiKV") {
+            return;
+        }
+
+        let estimated = node
+            .extra
+            .get("Plan Rows")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0) as u64;
+        let rows = node.total_rows().max(estimated);
+        if rows <= self.config.large_scan_threshold {
+            return;
+        }
+
+        let table = node.relation_name.as_deref().unwrap_or("unknown");
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "tikv_full_scan".to_string(),
+            suggestion_type: "Index".to_string(),
+            severity: Severity::High,
+            title: "Full Table Scan in TiKV".to_string(),
+            description: format!(
+                "Table '{}' is read in full from TiKV ({} rows). Every region of the table is scanned by the row store and sent to TiDB.",
+                table, rows
+            ),
+            recommendation: format!(
+                "Add an index on the filtered columns, or for analytical queries add a columnar replica (ALTER TABLE {} SET TIFLASH REPLICA 1) so the scan can run in TiFlash.",
+                table
+            ),
+            node_index: Some(node_index),
+            impact: "High - Avoids reading every region of the table from the row store".to_string(),
+        });
+    }
+
+    /// Check for operators planned with pseudo statistics
+    fn check_pseudo_stats(
+        &self,
+        node: &PlanNode,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        if node.extra.get("Pseudo Stats").and_then(|v| v.as_bool()) != Some(true) {
+            return;
+        }
+
+        let table = node.relation_name.as_deref().unwrap_or("unknown");
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "pseudo_stats".to_string(),
+            suggestion_type: "Statistics".to_string(),
+            severity: Severity::Medium,
+            title: "Table Statistics Missing".to_string(),
+            description: format!(
+                "The plan for table '{}' was built from pseudo statistics, so its row estimates are guesses.",
+                table
+            ),
+            recommendation: format!("Run ANALYZE TABLE {} to collect statistics.", table),
+            node_index: Some(node_index),
+            impact: "Medium - Accurate statistics let the optimizer pick better access paths".to_string(),
+        });
+    }
+
+    /// Check for parallel plans where coordinating workers costs more than it saves
+    fn check_gather_overhead(
+        &self,
+        node: &PlanNode,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        if node.node_type != "Gather" && node.node_type != "Gather Merge" {
+            return;
+        }
+
+        let launched = node.workers_launched.unwrap_or(0);
+        let Some(child) = node.plans.first() else {
+            return;
+        };
+        if launched == 0 || node.actual_total_time <= 0.0 {
+            return;
+        }
+
+        // The child's per-loop figures are averages over the leader and each worker
+        let overhead = (node.actual_total_time - child.actual_total_time).max(0.0);
+        let overhead_ratio = overhead / node.actual_total_time;
+
+        if overhead_ratio > self.config.parallel_overhead_ratio
+            && child.actual_rows < self.config.min_rows_per_worker
+        {
+            suggestions.push(OptimizationSuggestion {
+                rule_id: "parallel_overhead".to_string(),
+                suggestion_type: "Configuration".to_string(),
+                severity: Severity::Low,
+                title: format!("{} Overhead Dominates", node.node_type),
+                description: format!(
+                    "{} launched {} workers that each processed about {} rows, while {:.0}% of the node's {:.3} ms went to starting workers and collecting their results.",
+                    node.node_type,
+                    launched,
+                    child.actual_rows,
+                    overhead_ratio * 100.0,
+                    node.actual_total_time
+                ),
+                recommendation: "Disable parallelism for this query (SET max_parallel_workers_per_gather = 0), or raise min_parallel_table_scan_size / parallel_setup_cost so small scans stay serial.".to_string(),
+                node_index: Some(node_index),
+                impact: "Low - Avoids worker startup and tuple transfer costs on small inputs".to_string(),
+            });
+        }
+    }
+
+    /// Check for parallel nodes that started fewer workers than the planner counted on
+    fn check_workers_launched(
+        &self,
+        node: &PlanNode,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        let Some(missing) = node.workers_not_launched() else {
+            return;
+        };
+        let planned = node.workers_planned.unwrap_or(0);
+        let launched = planned - missing;
+        let (severity, outcome, impact) = if launched == 0 {
+            (
+                Severity::Medium,
+                "so the leader ran the whole plan below it alone",
+                "Medium - The query ran serially on a plan costed for parallel execution",
+            )
+        } else {
+            (
+                Severity::Low,
+                "so the launched workers and the leader split the work the planner meant for more processes",
+                "Low - The query ran with less parallelism than it was planned for",
+            )
+        };
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "workers_not_launched".to_string(),
+            suggestion_type: "Configuration".to_string(),
+            severity,
+            title: "Fewer Parallel Workers Launched Than Planned".to_string(),
+            description: format!(
+                "{} planned {} parallel workers but launched {}, {}.",
+                node.node_type, planned, launched, outcome
+            ),
+            recommendation: "Workers start only while slots are free: raise max_parallel_workers, and max_worker_processes (needs a restart) to match, or run fewer parallel queries at once. If the timing matters more than throughput, lower max_parallel_workers_per_gather so the plan does not count on workers it rarely gets.".to_string(),
+            node_index: Some(node_index),
+            impact: impact.to_string(),
+        });
+    }
+
+    /// Check for scans whose row estimate is far off on a table with stale statistics
+    fn check_stale_statistics(
+        &self,
+        node: &PlanNode,
+        context: &AnalysisContext,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        let (Some(relation), Some(estimated)) =
+            (node.relation_name.as_deref(), node.extra_u64("Plan Rows"))
+        else {
+            return;
+        };
+        let actual = node.actual_rows;
+        let factor = estimated.max(actual) as f64 / estimated.min(actual).max(1) as f64;
+        if factor < self.config.misestimate_factor {
+            return;
+        }
+        let Some(stats) = context
+            .statistics_of(relation)
+            .find(|stats| stats.is_stale(self.config.stale_statistics_ratio))
+        else {
+            return;
+        };
+
+        let table = format!("{}.{}", stats.schema_name, stats.table_name);
+        let state = match (stats.last_analyzed, stats.modified_fraction()) {
+            (None, _) => "has never been analyzed".to_string(),
+            (Some(_), Some(fraction)) => format!(
+                "had {:.0}% of its rows changed since it was last analyzed",
+                fraction * 100.0
+            ),
+            (Some(_), None) => "has statistics that are out of date".to_string(),
+        };
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "stale_statistics".to_string(),
+            suggestion_type: "Statistics".to_string(),
+            severity: Severity::Medium,
+            title: "Stale Table Statistics".to_string(),
+            description: format!(
+                "{} on '{}' was estimated to return {} rows per loop but returned {}, and the table {}.",
+                node.node_type, table, estimated, actual, state
+            ),
+            recommendation: format!(
+                "Run ANALYZE {} and re-check the plan; if the table changes in bursts, lower its autovacuum_analyze_scale_factor.",
+                table
+            ),
+            node_index: Some(node_index),
+            impact: "Medium - Accurate row estimates let the planner choose join methods and access paths that fit the data".to_string(),
+        });
+    }
+
+    /// Check for nodes that spent most of their time waiting on their own reads and writes
+    fn check_io_wait(
+        &self,
+        node: &PlanNode,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+        node_index: usize,
+    ) {
+        let (Some(io_time), Some(buffers)) = (node.own_io_time(), node.own_buffers()) else {
+            return;
+        };
+        let time = node.total_time();
+        if io_time < IO_WAIT_MIN_MS || time <= 0.0 {
+            return;
+        }
+        let ratio = (io_time / time).min(1.0);
+        if ratio < self.config.io_wait_ratio {
+            return;
+        }
+
+        let target = node
+            .relation_name
+            .as_deref()
+            .map(|relation| format!(" on '{}'", relation))
+            .unwrap_or_default();
+        let mut recommendation = if buffers.temp_read + buffers.temp_written > 0 {
+            "Part of the I/O is temporary files: raise work_mem so the node keeps its data in memory. ".to_string()
+        } else {
+            String::new()
+        };
+        recommendation.push_str("Read fewer pages with a more selective index or a covering index that allows index-only scans, or keep the data cached by raising shared_buffers; if reads of data that fits in memory are slow, check the storage's latency.");
+
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "io_bound".to_string(),
+            suggestion_type: "I/O".to_string(),
+            severity: if ratio >= 0.8 {
+                Severity::High
+            } else {
+                Severity::Medium
+            },
+            title: "Node Dominated by I/O Wait".to_string(),
+            description: format!(
+                "{}{} spent {:.1} ms of its {:.1} ms ({:.0}%) waiting on I/O, reading {} blocks that were not in shared buffers and writing {}.",
+                node.node_type,
+                target,
+                io_time,
+                time,
+                ratio * 100.0,
+                buffers.read(),
+                buffers.written()
+            ),
+            recommendation,
+            node_index: Some(node_index),
+            impact: "Medium - Time waiting on storage grows with the data and with load on the disks".to_string(),
+        });
+    }
+
+    /// Check whether a plan that read blocks lacks I/O timings because `track_io_timing` is off
+    fn check_io_timing(&self, plan: &ExecutionPlan, suggestions: &mut Vec<OptimizationSuggestion>) {
+        if !plan.io_timing_missing() {
+            return;
+        }
+        let read = plan.root.buffers.map_or(0, |b| b.read());
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "io_timing_disabled".to_string(),
+            suggestion_type: "Configuration".to_string(),
+            severity: Severity::Low,
+            title: "I/O Timing Not Tracked".to_string(),
+            description: format!(
+                "The query read {} blocks from outside shared buffers, but track_io_timing is off, so the plan cannot show how much of its time went to waiting on I/O.",
+                read
+            ),
+            recommendation: "Turn on track_io_timing (SET track_io_timing = on as a superuser, or in postgresql.conf) and explain the query again; pg_test_timing shows whether the system's clock is cheap enough to leave it on.".to_string(),
+            node_index: None,
+            impact: "Low - Tells whether slow nodes are waiting on storage or on the CPU".to_string(),
+        });
+    }
+
+    /// Check whether compiling the query took a large share of its execution time
+    fn check_jit_overhead(
+        &self,
+        plan: &ExecutionPlan,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+    ) {
+        let (Some(jit), Some(jit_time), Some(share)) =
+            (plan.jit.as_ref(), plan.jit_time(), plan.jit_share())
+        else {
+            return;
+        };
+        if jit_time < JIT_OVERHEAD_MIN_MS || share < self.config.jit_overhead_ratio {
+            return;
+        }
+
+        let mut steps = Vec::new();
+        if jit.options.inlining {
+            steps.push("jit_inline_above_cost");
+        }
+        if jit.options.optimization {
+            steps.push("jit_optimize_above_cost");
+        }
+        let mut recommendation = format!(
+            "The estimated cost of {:.0} passed jit_above_cost, but the query ran too briefly to win back the compilation. Raise jit_above_cost above that cost, or SET jit = off for this query.",
+            plan.root.total_cost
+        );
+        if !steps.is_empty() {
+            recommendation.push_str(&format!(
+                " Inlining and optimization are the expensive steps; raising {} keeps the cheaper compilation while skipping them.",
+                steps.join(" and ")
+            ));
+        }
+        recommendation.push_str(" If the estimate is far above what the query does, fix the row estimates first, since they decide whether the query is compiled.");
+
+        suggestions.push(OptimizationSuggestion {
+            rule_id: "jit_overhead".to_string(),
+            suggestion_type: "Configuration".to_string(),
+            severity: if share >= 0.5 {
+                Severity::High
+            } else {
+                Severity::Medium
+            },
+            title: "JIT Compilation Outweighs Its Benefit".to_string(),
+            description: format!(
+                "Compiling {} functions took {:.1} ms of the {:.1} ms execution ({:.0}%): generation {:.1} ms, inlining {:.1} ms, optimization {:.1} ms, emission {:.1} ms.",
+                jit.functions,
+                jit_time,
+                plan.execution_time,
+                share * 100.0,
+                jit.timing.map_or(0.0, |t| t.generation),
+                jit.timing.map_or(0.0, |t| t.inlining),
+                jit.timing.map_or(0.0, |t| t.optimization),
+                jit.timing.map_or(0.0, |t| t.emission),
+            ),
+            recommendation,
+            node_index: None,
+            impact: "Medium - Compilation is paid again on every execution of the query".to_string(),
+        });
+    }
+
+    /// Check serial/identity columns of the plan's relations for exhaustion
+    fn check_sequence_headroom(
+        &self,
+        context: &AnalysisContext,
+        suggestions: &mut Vec<OptimizationSuggestion>,
+    ) {
+        for usage in &context.sequences {
+            let ratio = usage.usage_ratio();
+            if ratio < self.config.sequence_usage_threshold {
+                continue;
+            }
+
+            let column = format!(
+                "{}.{}.{}",
+                usage.schema_name, usage.table_name, usage.column_name
+            );
+            let title = if usage.is_primary_key && usage.is_narrow_integer() {
+                "Integer Primary Key Nearing Overflow"
+            } else {
+                "Sequence Nearing Exhaustion"
+            };
+            let recommendation = if usage.is_narrow_integer() {
+                format!(
+                    "Migrate {} to bigint (ALTER TABLE {}.{} ALTER COLUMN {} TYPE bigint, and ALTER SEQUENCE {} AS bigint) before it runs out.",
+                    column,
+                    usage.schema_name,
+                    usage.table_name,
+                    usage.column_name,
+                    usage.sequence_name
+                )
+            } else {
+                format!(
+                    "Raise the MAXVALUE of sequence {} or plan a key migration.",
+                    usage.sequence_name
+                )
+            };
+
+            suggestions.push(OptimizationSuggestion {
+                rule_id: "sequence_exhaustion".to_string(),
+                suggestion_type: "Schema".to_string(),
+                severity: if ratio >= 0.9 {
+                    Severity::High
+                } else {
+                    Severity::Medium
+                },
+                title: title.to_string(),
+                description: format!(
+                    "Column {} ({}) has used {:.1}% of its range via {}; {} values remain.",
+                    column,
+                    usage.data_type,
+                    ratio * 100.0,
+                    usage.sequence_name,
+                    usage.remaining()
+                ),
+                recommendation,
+                node_index: None,
+                impact: "High - Inserts fail once the sequence or column type is exhausted"
+                    .to_string(),
+            });
+        }
+    }
+
+    /// Generate analysis summary
+    fn generate_summary(
+        &self,
+        suggestions: &[OptimizationSuggestion],
+        node_costs: &HashMap<String, f64>,
+        plan: &ExecutionPlan,
+    ) -> AnalysisSummary {
+        let high_severity_count = suggestions
+            .iter()
+            .filter(|s| matches!(s.severity, Severity::High))
+            .count();
+
+        let most_expensive_operation = node_costs
+            .iter()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .map(|(op, _)| op.clone())
+            .unwrap_or_else(|| "Unknown".to_string());
+
+        let potential_improvement = match high_severity_count {
+            0 => "Low - Query appears well optimized".to_string(),
+            1..=2 => "Medium - Some optimization opportunities available".to_string(),
+            _ => "High - Significant optimization potential".to_string(),
+        };
+
+        AnalysisSummary {
+            total_suggestions: suggestions.len(),
+            high_severity_count,
+            most_expensive_operation,
+            total_cost: plan.root.total_cost,
+            potential_improvement,
+            peak_memory_kb: plan.peak_memory_kb(),
+        }
+    }
+
+    /// Calculate overall performance score (0-100)
+    fn calculate_performance_score(
+        &self,
+        suggestions: &[OptimizationSuggestion],
+        plan: &ExecutionPlan,
+    ) -> u8 {
+        let mut score = 100u8;
+
+        // Deduct points for suggestions
+        for suggestion in suggestions {
+            let deduction = match suggestion.severity {
+                Severity::High => 20,
+                Severity::Medium => 10,
+                Severity::Low => 5,
+            };
+            score = score.saturating_sub(deduction);
+        }
+
+        // Consider execution time if available
+        if plan.execution_time > 1000.0 {
+            score = score.saturating_sub(10);
+        }
+
+        // Ensure minimum score
+        score.max(10)
+    }
+}
+
+impl Default for QueryAdvisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The Foreign Scan feeding a join input, looking through nodes that only
+/// buffer or reorder its rows
+fn foreign_input(node: &PlanNode) -> Option<&PlanNode> {
+    if node.is_foreign_scan() {
+        return Some(node);
+    }
+    match (node.node_type.as_str(), node.plans.as_slice()) {
+        ("Hash" | "Materialize" | "Sort" | "Memoize", [child]) => foreign_input(child),
+        _ => None,
+    }
+}
+
+/// A sort key without the table alias qualifying its column, e.g.
+/// `created_at DESC` for `o.created_at DESC`
+fn unqualified(key: &str) -> String {
+    let (column, direction) = key.split_once(' ').unwrap_or((key, ""));
+    let column = match column.rsplit_once('.') {
+        Some((_, name)) if !column.contains('(') => name,
+        _ => column,
+    };
+    if direction.is_empty() {
+        column.to_string()
+    } else {
+        format!("{} {}", column, direction)
+    }
+}
+
+/// Whether an index with key `index_columns` returns rows ordered by all of
+/// `sort_columns`, ignoring sort directions
+fn index_covers(index_columns: &[String], sort_columns: &[String]) -> bool {
+    let name = |column: &str| {
+        column
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase()
+    };
+    index_columns.len() >= sort_columns.len()
+        && sort_columns
+            .iter()
+            .zip(index_columns)
+            .all(|(sort, index)| name(sort) == name(index))
+}
+
+/// Own I/O time below which a node is not judged I/O bound, in milliseconds
+const IO_WAIT_MIN_MS: f64 = 10.0;
+
+/// JIT compilation time below which it is not judged, in milliseconds
+const JIT_OVERHEAD_MIN_MS: f64 = 10.0;
+
+/// Temporary tables smaller than this, in bytes, are read in full cheaply enough without an index
+const TEMP_TABLE_INDEX_MIN_BYTES: i64 = 1024 * 1024;
+
+/// Columns PostgreSQL adds to every table; `oid` is left out, since the
+/// catalogs declare it as an ordinary, indexed column
+const SYSTEM_COLUMNS: [&str; 6] = ["ctid", "xmin", "xmax", "cmin", "cmax", "tableoid"];
+
+fn is_system_column(column: &str) -> bool {
+    SYSTEM_COLUMNS
+        .iter()
+        .any(|system| system.eq_ignore_ascii_case(column))
+}
+
+/// Whether every column `filter` references is a system column, leaving out
+/// the aliases qualifying them
+fn filters_only_system_columns(filter: &str) -> bool {
+    let columns = filter_columns(filter);
+    let is_qualifier = |name: &str| {
+        filter.contains(&format!("{}.", name)) || filter.contains(&format!("\"{}\".", name))
+    };
+    columns.iter().any(|column| is_system_column(column))
+        && columns
+            .iter()
+            .all(|column| is_system_column(column) || is_qualifier(column))
+}
+
+/// Keywords that appear unquoted in the conditions PostgreSQL prints
+const CONDITION_KEYWORDS: [&str; 20] = [
+    "AND", "OR", "NOT", "IS", "NULL", "TRUE", "FALSE", "ANY", "ALL", "SOME", "IN", "LIKE", "ILIKE",
+    "BETWEEN", "DISTINCT", "FROM", "ARRAY", "CASE", "WHEN", "THEN",
+];
+
+/// Column names referenced by a plan's filter condition
+///
+/// Identifiers inside string literals, type names after `::`, function names
+/// and keywords are left out; the qualifier of `alias.column` is kept as a
+/// name of its own, which is harmless when matching against known columns.
+fn filter_columns(filter: &str) -> Vec<String> {
+    let mut columns: Vec<String> = Vec::new();
+    let mut chars = filter.char_indices().peekable();
+    let mut after_cast = false;
+    while let Some((start, c)) = chars.next() {
+        let name = match c {
+            '\'' => {
+                // Doubled quotes escape a quote inside the literal
+                while let Some((_, c)) = chars.next() {
+                    if c == '\'' && chars.next_if(|&(_, c)| c == '\'').is_none() {
+                        break;
+                    }
+                }
+                after_cast = false;
+                continue;
+            }
+            ':' => {
+                after_cast = chars.next_if(|&(_, c)| c == ':').is_some();
+                continue;
+            }
+            '"' => {
+                let mut name = String::new();
+                for (_, c) in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    name.push(c);
+                }
+                name
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) =
+                    chars.next_if(|&(_, c)| c.is_alphanumeric() || c == '_' || c == '$')
+                {
+                    end = i + c.len_utf8();
+                }
+                let word = &filter[start..end];
+                if CONDITION_KEYWORDS
+                    .iter()
+                    .any(|k| k.eq_ignore_ascii_case(word))
+                {
+                    after_cast = false;
+                    continue;
+                }
+                word.to_string()
+            }
+            c => {
+                if !c.is_whitespace() {
+                    after_cast = false;
+                }
+                continue;
+            }
+        };
+
+        let is_function = filter[start..]
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$' || c == '"'))
+            .is_some_and(|end| filter[start + end..].starts_with('('));
+        if !after_cast && !is_function && !columns.contains(&name) {
+            columns.push(name);
+        }
+    }
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn plan(root: serde_json::Value) -> ExecutionPlan {
+        ExecutionPlan {
+            root: serde_json::from_value(root).unwrap(),
+            planning_time: 0.1,
+            execution_time: 1.0,
+            settings: None,
+            jit: None,
+        }
+    }
+
+    fn titles(analysis: &AdvisorAnalysis) -> Vec<&str> {
+        analysis
+            .suggestions
+            .iter()
+            .map(|s| s.title.as_str())
+            .collect()
+    }
+
+    fn recursive_union(loops: u64, filter: Option<&str>) -> ExecutionPlan {
+        let mut join = json!({
+            "Node Type": "Hash Join",
+            "Startup Cost": 0.0, "Total Cost": 10.0,
+            "Actual Total Time": 0.1, "Actual Rows": 1, "Actual Loops": loops,
+            "Plans": [{
+                "Node Type": "WorkTable Scan", "CTE Name": "tree",
+                "Startup Cost": 0.0, "Total Cost": 1.0,
+                "Actual Total Time": 0.01, "Actual Rows": 1, "Actual Loops": loops
+            }]
+        });
+        if let Some(filter) = filter {
+            join["Join Filter"] = json!(filter);
+        }
+
+        plan(json!({
+            "Node Type": "Recursive Union",
+            "Startup Cost": 0.0, "Total Cost": 100.0,
+            "Actual Total Time": 50.0, "Actual Rows": loops, "Actual Loops": 1,
+            "Plans": [
+                {
+                    "Node Type": "Result",
+                    "Startup Cost": 0.0, "Total Cost": 0.01,
+                    "Actual Total Time": 0.01, "Actual Rows": 1, "Actual Loops": 1
+                },
+                join
+            ]
+        }))
+    }
+
+    #[test]
+    fn test_hash_agg_spill() {
+        let analysis = QueryAdvisor::new().analyze_plan(&plan(json!({
+            "Node Type": "Aggregate",
+            "Strategy": "Hashed",
+            "Group Key": ["o.customer_id"],
+            "Startup Cost": 0.0, "Total Cost": 500.0,
+            "Actual Total Time": 80.0, "Actual Rows": 5000, "Actual Loops": 1,
+            "Planned Partitions": 4,
+            "HashAgg Batches": 5,
+            "Peak Memory Usage": 4145,
+            "Disk Usage": 10240
+        })));
+
+        let spill = analysis
+            .suggestions
+            .iter()
+            .find(|s| s.title == "Hash Aggregate Spilled to Disk")
+            .expect("expected a spill suggestion");
+        assert!(spill.description.contains("5 batches"));
+        assert!(spill.recommendation.contains("15 MB"));
+        assert!(spill.recommendation.contains("o.customer_id"));
+    }
+
+    #[test]
+    fn test_hash_join_spill() {
+        let hash_join = |batches: u64, original: u64| {
+            plan(json!({
+                "Node Type": "Hash Join",
+                "Startup Cost": 0.0, "Total Cost": 900.0,
+                "Actual Total Time": 120.0, "Actual Rows": 2000, "Actual Loops": 1,
+                "Plans": [{
+                    "Node Type": "Hash",
+                    "Startup Cost": 0.0, "Total Cost": 400.0,
+                    "Actual Total Time": 60.0, "Actual Rows": 200000, "Actual Loops": 1,
+                    "Hash Buckets": 131072, "Original Hash Buckets": 131072,
+                    "Hash Batches": batches, "Original Hash Batches": original,
+                    "Peak Memory Usage": 3000
+                }]
+            }))
+        };
+
+        let spilled = hash_join(4, 1);
+        let hash = &spilled.root.plans[0];
+        assert_eq!(hash.hash_buckets, Some(131072));
+        assert_eq!(hash.original_hash_batches, Some(1));
+        assert!(hash.hash_spilled() && hash.hash_batches_grew());
+
+        let analysis = QueryAdvisor::new().analyze_plan(&spilled);
+        let spill = analysis
+            .suggestions
+            .iter()
+            .find(|s| s.rule_id == "hash_join_spill")
+            .expect("expected a hash spill suggestion");
+        assert_eq!(spill.node_index, Some(1));
+        assert!(spill.description.contains("4 batches"));
+        assert!(spill.description.contains("expected one batch"));
+        assert!(spill.recommendation.contains("12 MB"));
+        assert!(spill.recommendation.contains("ANALYZE"));
+
+        // A spill the planner foresaw needs memory, not statistics
+        let analysis = QueryAdvisor::new().analyze_plan(&hash_join(4, 4));
+        let spill = analysis
+            .suggestions
+            .iter()
+            .find(|s| s.rule_id == "hash_join_spill")
+            .unwrap();
+        assert!(!spill.recommendation.contains("ANALYZE"));
+
+        let analysis = QueryAdvisor::new().analyze_plan(&hash_join(1, 1));
+        assert!(analysis
+            .suggestions
+            .iter()
+            .all(|s| s.rule_id != "hash_join_spill"));
+    }
+
+    #[test]
+    fn test_memory_rules_and_peak_estimate() {
+        let analysis = QueryAdvisor::new().analyze_plan(&plan(json!({
+            "Node Type": "Hash Join",
+            "Startup Cost": 0.0, "Total Cost": 900.0,
+            "Actual Total Time": 120.0, "Actual Rows": 2000, "Actual Loops": 1,
+            "Plans": [
+                {
+                    "Node Type": "Sort",
+                    "Startup Cost": 0.0, "Total Cost": 400.0,
+                    "Actual Total Time": 90.0, "Actual Rows": 8000, "Actual Loops": 1,
+                    "Sort Method": "external merge",
+                    "Sort Space Used": 3000,
+                    "Sort Space Type": "Disk"
+                },
+                {
+                    "Node Type": "Hash",
+                    "Startup Cost": 0.0, "Total Cost": 100.0,
+                    "Actual Total Time": 10.0, "Actual Rows": 500, "Actual Loops": 1,
+                    "Peak Memory Usage": 256,
+                    "Plans": [{
+                        "Node Type": "Memoize",
+                        "Startup Cost": 0.0, "Total Cost": 50.0,
+                        "Actual Total Time": 0.01, "Actual Rows": 1, "Actual Loops": 500,
+                        "Cache Hits": 300, "Cache Misses": 200,
+                        "Cache Evictions": 150, "Cache Overflows": 0,
+                        "Peak Memory Usage": 64
+                    }]
+                },
+                {
+                    "Node Type": "Sort",
+                    "Startup Cost": 0.0, "Total Cost": 10.0,
+                    "Actual Total Time": 1.0, "Actual Rows": 100, "Actual Loops": 1,
+                    "Sort Space Used": 40,
+                    "Sort Space Type": "Memory"
+                }
+            ]
+        })));
+
+        // The spilled sort's space is on disk, so it doesn't count as memory
+        assert_eq!(analysis.summary.peak_memory_kb, Some(256 + 64 + 40));
+
+        let sort = analysis
+            .suggestions
+            .iter()
+            .find(|s| s.rule_id == "sort_spill")
+            .expect("expected a sort spill suggestion");
+        assert_eq!(sort.node_index, Some(1));
+        assert!(sort.description.contains("external merge"));
+        assert!(sort.recommendation.contains("6 MB"));
+
+        let memoize = analysis
+            .suggestions
+            .iter()
+            .find(|s| s.rule_id == "memoize_evictions")
+            .expect("expected a memoize suggestion");
+        assert!(memoize.description.contains("60%"));
+        assert!(memoize.description.contains("evicted 150 entries"));
+        assert_eq!(
+            analysis
+                .suggestions
+                .iter()
+                .filter(|s| s.rule_id == "sort_spill")
+                .count(),
+            1
+        );
+
+        let estimates = plan(json!({
+            "Node Type": "Seq Scan",
+            "Startup Cost": 0.0, "Total Cost": 10.0
+        }));
+        assert_eq!(
+            QueryAdvisor::new()
+                .analyze_plan(&estimates)
+                .summary
+                .peak_memory_kb,
+            None
+        );
+    }
+
+    #[test]
+    fn test_memoize_ineffective() {
+        let memoize = |hits: u64, misses: u64, evictions: u64| {
+            plan(json!({
+                "Node Type": "Memoize",
+                "Startup Cost": 0.0, "Total Cost": 50.0,
+                "Actual Total Time": 0.02, "Actual Rows": 1, "Actual Loops": hits + misses,
+                "Cache Key": "o.customer_id",
+                "Cache Hits": hits, "Cache Misses": misses,
+                "Cache Evictions": evictions, "Cache Overflows": 0,
+                "Peak Memory Usage": 1024,
+                "Estimated Hit Percent": 80.0
+            }))
+        };
+        let rules = |analysis: AdvisorAnalysis| {
+            analysis
+                .suggestions
+                .into_iter()
+                .map(|s| s.rule_id)
+                .collect::<Vec<_>>()
+        };
+        let advisor = QueryAdvisor::new();
+
+        let analysis = advisor.analyze_plan(&memoize(5, 995, 0));
+        let ineffective = analysis
+            .suggestions
+            .iter()
+            .find(|s| s.rule_id == "memoize_ineffective")
+            .expect("expected an ineffective memoize suggestion");
+        assert!(ineffective.description.contains("0.5% of 1000 lookups"));
+        assert!(ineffective
+            .description
+            .contains("expected a 80.0% hit ratio"));
+        assert!(ineffective.recommendation.contains("(o.customer_id)"));
+        assert!(!ineffective.recommendation.contains("evicted"));
+
+        // Evictions explain a low hit ratio without a second suggestion
+        let evicting = advisor.analyze_plan(&memoize(5, 995, 900));
+        assert!(evicting.suggestions[0]
+            .recommendation
+            .starts_with("The cache evicted 900 entries"));
+        assert_eq!(rules(evicting), ["memoize_ineffective"]);
+
+        // Too few lookups to judge, or a cache that mostly hits
+        assert!(rules(advisor.analyze_plan(&memoize(0, 20, 0))).is_empty());
+        assert_eq!(
+            rules(advisor.analyze_plan(&memoize(700, 300, 50))),
+            ["memoize_evictions"]
+        );
+    }
+
+    #[test]
+    fn test_incremental_sort() {
+        let sort = plan(json!({
+            "Node Type": "Incremental Sort",
+            "Startup Cost": 0.0, "Total Cost": 900.0,
+            "Actual Total Time": 40.0, "Actual Rows": 50000, "Actual Loops": 1,
+            "Sort Key": ["o.customer_id", "o.created_at DESC"],
+            "Presorted Key": ["o.customer_id"],
+            "Full-sort Groups": {
+                "Group Count": 1500,
+                "Sort Methods Used": ["quicksort"],
+                "Sort Space Memory": {"Average Sort Space Used": 30, "Peak Sort Space Used": 30}
+            },
+            "Pre-sorted Groups": {
+                "Group Count": 12,
+                "Sort Methods Used": ["external merge"],
+                "Sort Space Memory": {"Average Sort Space Used": 600, "Peak Sort Space Used": 900},
+                "Sort Space Disk": {"Average Sort Space Used": 2048, "Peak Sort Space Used": 4096}
+            },
+            "Plans": [{
+                "Node Type": "Index Scan", "Relation Name": "orders", "Alias": "o",
+                "Index Name": "orders_customer_id",
+                "Startup Cost": 0.0, "Total Cost": 500.0,
+                "Actual Total Time": 20.0, "Actual Rows": 50000, "Actual Loops": 1
+            }]
+        }));
+        assert_eq!(sort.root.memory_used_kb(), Some(900));
+
+        let incremental = |context: &AnalysisContext| {
+            QueryAdvisor::new()
+                .analyze_plan_with_context(&sort, context)
+                .suggestions
+                .into_iter()
+                .find(|s| s.rule_id == "incremental_sort")
+        };
+        let suggestion = incremental(&AnalysisContext::default()).unwrap();
+        assert!(matches!(suggestion.severity, Severity::Medium));
+        assert!(suggestion.description.contains("12 large groups"));
+        assert!(suggestion.description.contains("4096 kB"));
+        assert!(suggestion
+            .recommendation
+            .contains("orders (customer_id, created_at DESC)"));
+        // Not a plain sort, so the large sort rule leaves it alone
+        assert!(!QueryAdvisor::new()
+            .analyze_plan(&sort)
+            .suggestions
+            .iter()
+            .any(|s| s.rule_id == "large_sort"));
+
+        let context = AnalysisContext {
+            indexes: vec![TableIndex {
+                schema_name: "public".to_string(),
+                table_name: "orders".to_string(),
+                index_name: "orders_customer_created".to_string(),
+                access_method: "btree".to_string(),
+                columns: vec!["customer_id".to_string(), "created_at".to_string()],
+                is_unique: false,
+                is_valid: true,
+                predicate: None,
+            }],
+            ..AnalysisContext::default()
+        };
+        assert!(incremental(&context)
+            .unwrap()
+            .recommendation
+            .starts_with("Index orders_customer_created on orders already covers"));
+    }
+
+    #[test]
+    fn test_large_sort_distinguishes_memory_and_disk() {
+        let large_sort = |method: &str, space_type: &str| {
+            QueryAdvisor::new()
+                .analyze_plan(&plan(json!({
+                    "Node Type": "Sort",
+                    "Startup Cost": 0.0, "Total Cost": 5000.0,
+                    "Actual Total Time": 80.0, "Actual Rows": 50000, "Actual Loops": 1,
+                    "Sort Method": method,
+                    "Sort Space Used": 6200,
+                    "Sort Space Type": space_type
+                })))
+                .suggestions
+                .into_iter()
+                .find(|s| s.rule_id == "large_sort")
+                .unwrap()
+        };
+
+        let in_memory = large_sort("quicksort", "Memory");
+        assert!(matches!(in_memory.severity, Severity::Low));
+        assert!(in_memory
+            .description
+            .contains("in memory as a quicksort using 6200 kB"));
+
+        let spilled = large_sort("external merge", "Disk");
+        assert!(matches!(spilled.severity, Severity::Medium));
+        assert!(spilled
+            .description
+            .contains("used an external merge and spilled 6200 kB to disk"));
+
+        let node: PlanNode = serde_json::from_value(json!({
+            "Node Type": "Sort", "Startup Cost": 0.0, "Total Cost": 1.0,
+            "Sort Method": "external sort"
+        }))
+        .unwrap();
+        assert_eq!(node.sort_method.as_deref(), Some("external sort"));
+        assert!(node.sort_spilled());
+        assert!(!node.sort_in_memory());
+    }
+
+    #[test]
+    fn test_estimates_only_skips_runtime_rules() {
+        let sort = plan(json!({
+            "Node Type": "Sort",
+            "Startup Cost": 0.0, "Total Cost": 50.0,
+            "Actual Total Time": 0.0, "Actual Rows": 50000, "Actual Loops": 1
+        }));
+        let advisor = QueryAdvisor::new();
+        assert!(advisor
+            .analyze_plan(&sort)
+            .suggestions
+            .iter()
+            .any(|s| s.rule_id == "large_sort"));
+
+        let estimates_only = AnalysisContext {
+            estimates_only: true,
+            ..AnalysisContext::default()
+        };
+        assert!(advisor
+            .analyze_plan_with_context(&sort, &estimates_only)
+            .suggestions
+            .is_empty());
+    }
+
+    #[test]
+    fn test_tidb_storage_rules() {
+        let scan = |engine: &str, rows: u64| {
+            plan(json!({
+                "Node Type": "Seq Scan", "Relation Name": "orders",
+                "Startup Cost": 0.0, "Total Cost": 0.0,
+                "Actual Total Time": 40.0, "Actual Rows": rows, "Actual Loops": 1,
+                "Storage Engine": engine,
+                "Pseudo Stats": true
+            }))
+        };
+        let rules = |analysis: AdvisorAnalysis| {
+            analysis
+                .suggestions
+                .into_iter()
+                .map(|s| s.rule_id)
+                .collect::<Vec<_>>()
+        };
+        let advisor = QueryAdvisor::new();
+
+        let tikv = rules(advisor.analyze_plan(&scan("TiKV", 50_000)));
+        assert_eq!(tikv, ["tikv_full_scan", "pseudo_stats"]);
+        assert_eq!(
+            rules(advisor.analyze_plan(&scan("TiFlash", 50_000))),
+            ["pseudo_stats"]
+        );
+        assert_eq!(
+            rules(advisor.analyze_plan(&scan("TiKV", 100))),
+            ["pseudo_stats"]
+        );
+    }
+
+    #[test]
+    fn test_gather_overhead() {
+        let gather = |child_rows: u64| {
+            plan(json!({
+                "Node Type": "Gather",
+                "Startup Cost": 1000.0, "Total Cost": 1100.0,
+                "Actual Total Time": 12.0, "Actual Rows": 300, "Actual Loops": 1,
+                "Workers Planned": 2, "Workers Launched": 2,
+                "Plans": [{
+                    "Node Type": "Seq Scan", "Relation Name": "lookup",
+                    "Startup Cost": 0.0, "Total Cost": 50.0,
+                    "Actual Total Time": 1.5, "Actual Rows": child_rows, "Actual Loops": 3
+                }]
+            }))
+        };
+
+        let advisor = QueryAdvisor::new();
+        let small = advisor.analyze_plan(&gather(100));
+        assert!(titles(&small).contains(&"Gather Overhead Dominates"));
+
+        let large = advisor.analyze_plan(&gather(100_000));
+        assert!(!titles(&large).contains(&"Gather Overhead Dominates"));
+    }
+
+    #[test]
+    fn test_io_wait() {
+        let scan = |io_read_time: f64| {
+            plan(json!({
+                "Node Type": "Hash Join",
+                "Startup Cost": 0.0, "Total Cost": 500.0,
+                "Actual Total Time": 130.0, "Actual Rows": 10, "Actual Loops": 1,
+                "Shared Hit Blocks": 20, "Shared Read Blocks": 4000,
+                "I/O Read Time": io_read_time + 5.0, "I/O Write Time": 0.0,
+                "Plans": [
+                    {
+                        "Node Type": "Seq Scan", "Relation Name": "events",
+                        "Startup Cost": 0.0, "Total Cost": 400.0,
+                        "Actual Total Time": 100.0, "Actual Rows": 50000, "Actual Loops": 1,
+                        "Shared Hit Blocks": 10, "Shared Read Blocks": 3990,
+                        "Shared I/O Read Time": io_read_time, "Shared I/O Write Time": 0.0
+                    },
+                    {
+                        "Node Type": "Hash",
+                        "Startup Cost": 0.0, "Total Cost": 10.0,
+                        "Actual Total Time": 1.0, "Actual Rows": 10, "Actual Loops": 1,
+                        "Shared Hit Blocks": 10, "Shared Read Blocks": 10,
+                        "I/O Read Time": 0.0, "I/O Write Time": 0.0
+                    }
+                ]
+            }))
+        };
+
+        let advisor = QueryAdvisor::new();
+        let slow = advisor.analyze_plan(&scan(85.0));
+        let io: Vec<_> = slow
+            .suggestions
+            .iter()
+            .filter(|s| s.rule_id == "io_bound")
+            .collect();
+        // The join's own 5 ms of I/O is too little to judge
+        assert_eq!(io.len(), 1);
+        assert_eq!(io[0].node_index, Some(1));
+        assert_eq!(io[0].severity, Severity::High);
+        assert!(io[0].description.contains("'events'"));
+        assert!(io[0].description.contains("3990 blocks"));
+        assert!(!titles(&slow).contains(&"I/O Timing Not Tracked"));
+
+        let cached = advisor.analyze_plan(&scan(20.0));
+        assert!(!titles(&cached).contains(&"Node Dominated by I/O Wait"));
+    }
+
+    #[test]
+    fn test_io_timing_disabled() {
+        let reads = json!({
+            "Node Type": "Seq Scan", "Relation Name": "events",
+            "Startup Cost": 0.0, "Total Cost": 400.0,
+            "Actual Total Time": 100.0, "Actual Rows": 50000, "Actual Loops": 1,
+            "Shared Hit Blocks": 10, "Shared Read Blocks": 3990
+        });
+        let advisor = QueryAdvisor::new();
+        let analysis = advisor.analyze_plan(&plan(reads.clone()));
+        let hint = analysis
+            .suggestions
+            .iter()
+            .find(|s| s.rule_id == "io_timing_disabled")
+            .expect("expected an I/O timing hint");
+        assert!(hint.description.contains("3990 blocks"));
+
+        // The recorded setting wins over guessing from the plan
+        let mut on = plan(reads);
+        on.settings = Some(crate::db::settings::ServerSettings(
+            [("track_io_timing".to_string(), "on".to_string())].into(),
+        ));
+        assert!(!on.io_timing_missing());
+
+        let cached = plan(json!({
+            "Node Type": "Seq Scan", "Relation Name": "events",
+            "Startup Cost": 0.0, "Total Cost": 400.0,
+            "Actual Total Time": 1.0, "Actual Rows": 50, "Actual Loops": 1,
+            "Shared Hit Blocks": 10, "Shared Read Blocks": 0
+        }));
+        assert!(!cached.io_timing_missing());
+    }
+
+    #[test]
+    fn test_workers_not_launched() {
+        let gather = |launched: u64| {
+            plan(json!({
+                "Node Type": "Gather",
+                "Startup Cost": 1000.0, "Total Cost": 1100.0,
+                "Actual Total Time": 120.0, "Actual Rows": 300000, "Actual Loops": 1,
+                "Workers Planned": 4, "Workers Launched": launched,
+                "Plans": [{
+                    "Node Type": "Parallel Seq Scan", "Relation Name": "events",
+                    "Startup Cost": 0.0, "Total Cost": 900.0,
+                    "Actual Total Time": 100.0, "Actual Rows": 150000, "Actual Loops": 2,
+                    "Workers": [{
+                        "Worker Number": 0,
+                        "Actual Startup Time": 0.5, "Actual Total Time": 98.0,
+                        "Actual Rows": 140000, "Actual Loops": 1,
+                        "Shared Hit Blocks": 1200
+                    }]
+                }]
+            }))
+        };
+
+        let advisor = QueryAdvisor::new();
+        let short = advisor.analyze_plan(&gather(1));
+        let suggestion = short
+            .suggestions
+            .iter()
+            .find(|s| s.rule_id == "workers_not_launched")
+            .expect("expected a workers suggestion");
+        assert_eq!(suggestion.severity, Severity::Low);
+        assert!(suggestion
+            .description
+            .contains("planned 4 parallel workers but launched 1"));
+
+        let serial = advisor.analyze_plan(&gather(0));
+        assert!(serial
+            .suggestions
+            .iter()
+            .any(|s| s.rule_id == "workers_not_launched" && s.severity == Severity::Medium));
+
+        let full = advisor.analyze_plan(&gather(4));
+        assert!(!titles(&full).contains(&"Fewer Parallel Workers Launched Than Planned"));
+
+        let worker = &gather(4).root.plans[0].workers[0];
+        assert_eq!(worker.worker_number, 0);
+        assert_eq!(worker.actual_rows, Some(140000));
+        assert_eq!(worker.extra["Shared Hit Blocks"], 1200);
+    }
+
+    #[test]
+    fn test_jit_overhead() {
+        let explain = |execution_time: f64| {
+            json!([{
+                "Plan": {
+                    "Node Type": "Seq Scan", "Relation Name": "events",
+                    "Startup Cost": 0.0, "Total Cost": 180000.0,
+                    "Actual Total Time": execution_time - 40.0, "Actual Rows": 20, "Actual Loops": 1
+                },
+                "Planning Time": 0.3,
+                "JIT": {
+                    "Functions": 4,
+                    "Options": {"Inlining": true, "Optimization": true, "Expressions": true, "Deforming": true},
+                    "Timing": {
+                        "Generation": {"Deform": 0.2, "Total": 1.5},
+                        "Inlining": 12.0, "Optimization": 20.0, "Emission": 6.5, "Total": 40.0
+                    }
+                },
+                "Execution Time": execution_time
+            }])
+        };
+        let parsed = crate::db::parse_execution_plan(&explain(50.0)).unwrap();
+        let jit = parsed.jit.as_ref().expect("expected the JIT block");
+        assert_eq!(jit.functions, 4);
+        assert!(jit.options.inlining);
+        assert_eq!(jit.timing.unwrap().generation, 1.5);
+        assert_eq!(parsed.jit_share(), Some(0.8));
+
+        let advisor = QueryAdvisor::new();
+        let analysis = advisor.analyze_plan(&parsed);
+        let hint = analysis
+            .suggestions
+            .iter()
+            .find(|s| s.rule_id == "jit_overhead")
+            .expect("expected a JIT overhead hint");
+        assert_eq!(hint.severity, Severity::High);
+        assert!(hint.description.contains("40.0 ms of the 50.0 ms"));
+        assert!(hint
+            .recommendation
+            .contains("jit_inline_above_cost and jit_optimize_above_cost"));
+
+        let long = crate::db::parse_execution_plan(&explain(2000.0)).unwrap();
+        assert!(!titles(&advisor.analyze_plan(&long))
+            .contains(&"JIT Compilation Outweighs Its Benefit"));
+    }
+
+    #[test]
+    fn test_small_tables_skip_scan_rules() {
+        let scan = plan(json!({
+            "Node Type": "Seq Scan", "Relation Name": "countries",
+            "Startup Cost": 0.0, "Total Cost": 1500.0,
+            "Actual Total Time": 0.2, "Actual Rows": 1, "Actual Loops": 1,
+            "Filter": "(code = 'NZ'::text)"
+        }));
+        let size = |schema: &str, rows: Option<u64>| RelationSize {
+            schema_name: schema.to_string(),
+            table_name: "countries".to_string(),
+            estimated_rows: rows,
+            pages: 2,
+        };
+        let rules = |context: &AnalysisContext| {
+            QueryAdvisor::new()
+                .analyze_plan_with_context(&scan, context)
+                .suggestions
+                .into_iter()
+                .map(|s| s.rule_id)
+                .collect::<Vec<_>>()
+        };
+
+        // Without statistics only the cost is judged
+        assert_eq!(
+            rules(&AnalysisContext::default()),
+            ["expensive_seq_scan", "index_opportunity"]
+        );
+
+        let small = AnalysisContext {
+            relation_sizes: vec![size("public", Some(200))],
+            ..AnalysisContext::default()
+        };
+        assert!(rules(&small).is_empty());
+
+        // A large table of the same name in another schema keeps the rules on
+        let ambiguous = AnalysisContext {
+            relation_sizes: vec![size("public", Some(200)), size("archive", Some(5_000_000))],
+            ..AnalysisContext::default()
+        };
+        assert_eq!(rules(&ambiguous).len(), 2);
+
+        let never_analyzed = AnalysisContext {
+            relation_sizes: vec![size("public", None)],
+            ..AnalysisContext::default()
+        };
+        assert_eq!(rules(&never_analyzed).len(), 2);
+    }
+
+    #[test]
+    fn test_filter_columns() {
+        assert_eq!(
+            filter_columns("((o.status = 'it''s open'::text) AND (lower(email) ~~ 'a%'::text))"),
+            ["o", "status", "email"]
+        );
+        assert_eq!(
+            filter_columns("(\"createdAt\" > '2024-01-01'::timestamp without time zone)"),
+            ["createdAt"]
+        );
+    }
+
+    #[test]
+    fn test_existing_index_not_used() {
+        let scan = plan(json!({
+            "Node Type": "Seq Scan", "Relation Name": "users",
+            "Startup Cost": 0.0, "Total Cost": 500.0,
+            "Actual Total Time": 3.0, "Actual Rows": 10, "Actual Loops": 1,
+            "Filter": "(lower((email)::text) = 'a@example.com'::text)"
+        }));
+        let index = |name: &str, columns: &[&str], predicate: Option<&str>| TableIndex {
+            schema_name: "public".to_string(),
+            table_name: "users".to_string(),
+            index_name: name.to_string(),
+            access_method: "btree".to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            is_unique: false,
+            is_valid: true,
+            predicate: predicate.map(str::to_string),
+        };
+        let suggestions = |indexes: Vec<TableIndex>| {
+            let context = AnalysisContext {
+                indexes,
+                ..AnalysisContext::default()
+            };
+            QueryAdvisor::new()
+                .analyze_plan_with_context(&scan, &context)
+                .suggestions
+        };
+
+        let missing = suggestions(vec![index("users_created_at", &["created_at"], None)]);
+        assert_eq!(missing[0].rule_id, "index_opportunity");
+
+        let unused = suggestions(vec![index(
+            "users_email",
+            &["email", "created_at"],
+            Some("(deleted_at IS NULL)"),
+        )]);
+        assert_eq!(unused[0].rule_id, "index_not_used");
+        assert!(unused[0].description.contains("users_email"));
+        assert!(unused[0].description.contains("deleted_at IS NULL"));
+        assert!(unused[0].recommendation.contains("email"));
+
+        let mut invalid = index("users_email", &["email"], None);
+        invalid.is_valid = false;
+        assert_eq!(suggestions(vec![invalid])[0].rule_id, "index_opportunity");
+    }
+
+    #[test]
+    fn test_system_column_filters() {
+        let analysis = |node: serde_json::Value| QueryAdvisor::new().analyze_plan(&plan(node));
+
+        let tid = analysis(json!({
+            "Node Type": "Tid Scan", "Relation Name": "orders",
+            "Startup Cost": 0.0, "Total Cost": 4.0,
+            "Actual Total Time": 0.1, "Actual Rows": 1, "Actual Loops": 1,
+            "TID Cond": "(ctid = '(0,1)'::tid)"
+        }));
+        assert_eq!(tid.suggestions.len(), 1);
+        assert_eq!(tid.suggestions[0].rule_id, "system_column_filter");
+        assert!(tid.suggestions[0].description.contains("ctid"));
+
+        let scan = analysis(json!({
+            "Node Type": "Seq Scan", "Relation Name": "orders",
+            "Startup Cost": 0.0, "Total Cost": 5000.0,
+            "Actual Total Time": 30.0, "Actual Rows": 3, "Actual Loops": 1,
+            "Filter": "(o.xmin = '812'::xid)"
+        }));
+        let rules: Vec<&str> = scan
+            .suggestions
+            .iter()
+            .map(|s| s.rule_id.as_str())
+            .collect();
+        assert!(rules.contains(&"system_column_filter"));
+        assert!(!rules.contains(&"index_opportunity"));
+        assert!(!rules.contains(&"expensive_seq_scan"));
+
+        let mixed = analysis(json!({
+            "Node Type": "Seq Scan", "Relation Name": "orders",
+            "Startup Cost": 0.0, "Total Cost": 500.0,
+            "Actual Total Time": 3.0, "Actual Rows": 3, "Actual Loops": 1,
+            "Filter": "((status = 'open'::text) AND (xmax = '0'::xid))"
+        }));
+        let rules: Vec<&str> = mixed
+            .suggestions
+            .iter()
+            .map(|s| s.rule_id.as_str())
+            .collect();
+        assert_eq!(rules, ["index_opportunity", "system_column_filter"]);
+
+        assert!(!filters_only_system_columns("(oid = '16384'::oid)"));
+    }
+
+    #[test]
+    fn test_foreign_scans() {
+        let foreign = |relation: &str, rows: u64, removed: Option<u64>| {
+            let mut scan = json!({
+                "Node Type": "Foreign Scan", "Relation Name": relation,
+                "Startup Cost": 100.0, "Total Cost": 150.0,
+                "Actual Total Time": 80.0, "Actual Rows": rows, "Actual Loops": 1,
+                "Remote SQL": format!("SELECT id, status, created_at FROM public.{}", relation)
+            });
+            if let Some(removed) = removed {
+                scan["Filter"] = json!("(created_at > (now() - '7 days'::interval))");
+                scan["Rows Removed by Filter"] = json!(removed);
+            }
+            scan
+        };
+
+        let scan = plan(foreign("orders", 200, Some(50_000)));
+        assert_eq!(scan.root.rows_removed_by_filter, Some(50_000));
+        assert!(scan.root.filter.is_some());
+        assert!(scan.root.extra.get("Filter").is_none());
+        let filtered = QueryAdvisor::new().analyze_plan(&scan);
+        assert_eq!(titles(&filtered), ["Foreign Scan Filtered Locally"]);
+        let suggestion = &filtered.suggestions[0];
+        assert_eq!(suggestion.rule_id, "foreign_filter_not_pushed");
+        assert!(suggestion.description.contains("fetched 50200 rows"));
+        assert!(suggestion
+            .description
+            .contains("SELECT id, status, created_at FROM public.orders"));
+
+        // Most fetched rows were kept, so the filter costs little
+        let selective =
+            QueryAdvisor::new().analyze_plan(&plan(foreign("orders", 40_000, Some(20_000))));
+        assert!(selective.suggestions.is_empty());
+
+        let join = plan(json!({
+            "Node Type": "Hash Join", "Startup Cost": 200.0, "Total Cost": 400.0,
+            "Actual Total Time": 90.0, "Actual Rows": 10, "Actual Loops": 1,
+            "Plans": [
+                foreign("orders", 100, None),
+                {
+                    "Node Type": "Hash", "Startup Cost": 150.0, "Total Cost": 150.0,
+                    "Actual Total Time": 5.0, "Actual Rows": 50, "Actual Loops": 1,
+                    "Plans": [foreign("users", 50, None)]
+                }
+            ]
+        }));
+        let joined = QueryAdvisor::new().analyze_plan(&join);
+        assert_eq!(joined.suggestions.len(), 1);
+        assert_eq!(joined.suggestions[0].rule_id, "foreign_join_not_pushed");
+        assert!(joined.suggestions[0]
+            .description
+            .contains("orders and users"));
+    }
+
+    #[test]
+    fn test_stale_statistics() {
+        let scan = |actual_rows: u64| {
+            plan(json!({
+                "Node Type": "Index Scan", "Relation Name": "orders",
+                "Startup Cost": 0.0, "Total Cost": 8.0, "Plan Rows": 10, "Plan Width": 8,
+                "Actual Total Time": 40.0, "Actual Rows": actual_rows, "Actual Loops": 1
+            }))
+        };
+        let stats = |modified: u64| TableStatistics {
+            schema_name: "public".to_string(),
+            table_name: "orders".to_string(),
+            reltuples: Some(1_000),
+            live_rows: Some(50_000),
+            modified_since_analyze: Some(modified),
+            last_analyzed: Some(1_700_000_000_000),
+            columns: Vec::new(),
+        };
+        let rules = |plan: &ExecutionPlan, modified: u64| {
+            let context = AnalysisContext {
+                table_statistics: vec![stats(modified)],
+                ..AnalysisContext::default()
+            };
+            QueryAdvisor::new()
+                .analyze_plan_with_context(plan, &context)
+                .suggestions
+                .into_iter()
+                .map(|s| s.rule_id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(rules(&scan(9_000), 49_000), ["stale_statistics"]);
+        // Fresh statistics mean the misestimate has another cause
+        assert!(rules(&scan(9_000), 100).is_empty());
+        // A close estimate is fine whatever the statistics
+        assert!(rules(&scan(12), 49_000).is_empty());
+    }
+
+    #[test]
+    fn test_sequence_headroom() {
+        let usage = |last_value: i64| SequenceUsage {
+            schema_name: "public".to_string(),
+            table_name: "events".to_string(),
+            column_name: "id".to_string(),
+            data_type: "integer".to_string(),
+            sequence_name: "public.events_id_seq".to_string(),
+            last_value: Some(last_value),
+            sequence_max: i32::MAX as i64,
+            column_max: i32::MAX as i64,
+            is_primary_key: true,
+        };
+        let scan = plan(json!({
+            "Node Type": "Seq Scan", "Relation Name": "events",
+            "Startup Cost": 0.0, "Total Cost": 10.0,
+            "Actual Total Time": 0.1, "Actual Rows": 1, "Actual Loops": 1
+        }));
+        let advisor = QueryAdvisor::new();
+
+        let context = AnalysisContext {
+            sequences: vec![usage(2_000_000_000)],
+            ..AnalysisContext::default()
+        };
+        let analysis = advisor.analyze_plan_with_context(&scan, &context);
+        assert_eq!(
+            titles(&analysis),
+            vec!["Integer Primary Key Nearing Overflow"]
+        );
+        assert_eq!(analysis.suggestions[0].severity, Severity::High);
+
+        let context = AnalysisContext {
+            sequences: vec![usage(1_000)],
+            ..AnalysisContext::default()
+        };
+        assert!(advisor
+            .analyze_plan_with_context(&scan, &context)
+            .suggestions
+            .is_empty());
+    }
+
+    #[test]
+    fn test_runaway_recursive_cte() {
+        let analysis = QueryAdvisor::new().analyze_plan(&recursive_union(5000, None));
+        assert!(titles(&analysis).contains(&"Runaway Recursive CTE"));
+        assert_eq!(analysis.suggestions[0].severity, Severity::High);
+    }
+
+    #[test]
+    fn test_recursive_cte_cycle_protection() {
+        let advisor = QueryAdvisor::new();
+
+        let unprotected = advisor.analyze_plan(&recursive_union(10, None));
+        assert!(titles(&unprotected).contains(&"Recursive CTE Without Cycle Protection"));
+
+        let protected =
+            advisor.analyze_plan(&recursive_union(10, Some("(NOT (e.id = ANY (t.path)))")));
+        assert!(protected.suggestions.is_empty());
+    }
+
+    #[test]
+    fn test_temp_table_advice() {
+        let advisor = QueryAdvisor::new();
+        let table =
+            |analyzed_in: Option<usize>, indexed_in: Option<usize>, size: i64| TempTableUsage {
+                name: "staged".to_string(),
+                created_in: 1,
+                analyzed_in,
+                indexed_in,
+                joined_in: Some(3),
+                size_bytes: Some(size),
+            };
+        let rules = |tables: &[TempTableUsage], temp_buffers: Option<i64>| -> Vec<String> {
+            advisor
+                .analyze_temp_tables(tables, temp_buffers)
+                .into_iter()
+                .map(|s| s.rule_id)
+                .collect()
+        };
+
+        assert_eq!(
+            rules(&[table(None, Some(4), 8 << 20)], Some(8 << 20)),
+            ["temp_table_not_analyzed", "temp_table_not_indexed"]
+        );
+        assert_eq!(
+            rules(&[table(Some(2), Some(2), 16 << 20)], Some(8 << 20)),
+            ["temp_buffers_exceeded"]
+        );
+        // Small tables are scanned cheaply enough without an index
+        assert!(rules(&[table(Some(2), None, 64 << 10)], Some(8 << 20)).is_empty());
+        let unjoined = TempTableUsage {
+            joined_in: None,
+            ..table(None, None, 8 << 20)
+        };
+        assert!(rules(&[unjoined], None).is_empty());
     }
 }
@@ -0,0 +1,444 @@
+//! Advisor rules and the thresholds they read, adjustable at runtime
+//!
+//! Every suggestion carries the id of the rule that made it. Operators can
+//! disable rules by id and change thresholds by name without restarting;
+//! the changes are kept as [`RuleOverrides`] in storage and applied on top of
+//! the startup configuration. Thresholds are shared: `large_scan_threshold`,
+//! for instance, is read by every rule that judges row counts, so changing it
+//! for one rule changes it for all of them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, RwLock};
+
+use super::{AdvisorConfig, QueryAdvisor};
+
+/// A rule of the advisor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleDefinition {
+    /// Id reported as `rule_id` by the rule's suggestions
+    pub id: &'static str,
+    /// What the rule reports
+    pub title: &'static str,
+    /// Names of the thresholds the rule reads
+    pub thresholds: &'static [&'static str],
+}
+
+const fn rule(
+    id: &'static str,
+    title: &'static str,
+    thresholds: &'static [&'static str],
+) -> RuleDefinition {
+    RuleDefinition {
+        id,
+        title,
+        thresholds,
+    }
+}
+
+/// Every rule of the advisor
+pub const RULES: [RuleDefinition; 31] = [
+    rule(
+        "expensive_seq_scan",
+        "Expensive Sequential Scan Detected",
+        &["expensive_cost_threshold", "small_table_rows"],
+    ),
+    rule(
+        "expensive_operation",
+        "Expensive Operation",
+        &["expensive_cost_threshold"],
+    ),
+    rule(
+        "nested_loop",
+        "Inefficient Nested Loop Join",
+        &["large_scan_threshold"],
+    ),
+    rule(
+        "large_sort",
+        "Large Sort Operation",
+        &["large_scan_threshold"],
+    ),
+    rule(
+        "index_not_used",
+        "Existing Index Not Used",
+        &["small_table_rows"],
+    ),
+    rule(
+        "index_opportunity",
+        "Potential Index Opportunity",
+        &["small_table_rows"],
+    ),
+    rule("system_column_filter", "Filter on System Column", &[]),
+    rule(
+        "expensive_join",
+        "Expensive Join Operation",
+        &["expensive_cost_threshold"],
+    ),
+    rule(
+        "runaway_recursive_cte",
+        "Runaway Recursive CTE",
+        &["recursion_iteration_threshold"],
+    ),
+    rule(
+        "recursive_cte_cycle",
+        "Recursive CTE Without Cycle Protection",
+        &[],
+    ),
+    rule(
+        "recursive_rescan",
+        "Recursive Term Rescans Relation Every Iteration",
+        &["large_scan_threshold"],
+    ),
+    rule("hash_agg_spill", "Hash Aggregate Spilled to Disk", &[]),
+    rule("hash_join_spill", "Hash Join Spilled to Disk", &[]),
+    rule(
+        "incremental_sort",
+        "Incremental Sort Could Be Avoided",
+        &["large_scan_threshold"],
+    ),
+    rule("sort_spill", "Sort Spilled to Disk", &[]),
+    rule(
+        "memoize_ineffective",
+        "Ineffective Memoize Cache",
+        &["memoize_min_hit_ratio", "min_memoize_lookups"],
+    ),
+    rule("memoize_evictions", "Memoize Cache Too Small", &[]),
+    rule(
+        "foreign_filter_not_pushed",
+        "Foreign Scan Filtered Locally",
+        &["large_scan_threshold"],
+    ),
+    rule(
+        "foreign_join_not_pushed",
+        "Foreign Tables Joined Locally",
+        &[],
+    ),
+    rule(
+        "tikv_full_scan",
+        "Full Table Scan in TiKV",
+        &["large_scan_threshold"],
+    ),
+    rule("pseudo_stats", "Table Statistics Missing", &[]),
+    rule(
+        "parallel_overhead",
+        "Parallel Overhead Dominates",
+        &["parallel_overhead_ratio", "min_rows_per_worker"],
+    ),
+    rule(
+        "workers_not_launched",
+        "Fewer Parallel Workers Launched Than Planned",
+        &[],
+    ),
+    rule(
+        "stale_statistics",
+        "Stale Table Statistics",
+        &["misestimate_factor", "stale_statistics_ratio"],
+    ),
+    rule(
+        "sequence_exhaustion",
+        "Sequence Nearing Exhaustion",
+        &["sequence_usage_threshold"],
+    ),
+    rule(
+        "temp_table_not_analyzed",
+        "Temporary Table Joined Before ANALYZE",
+        &[],
+    ),
+    rule(
+        "temp_table_not_indexed",
+        "Temporary Table Joined Without Index",
+        &[],
+    ),
+    rule(
+        "temp_buffers_exceeded",
+        "Temporary Tables Exceed temp_buffers",
+        &[],
+    ),
+    rule("io_bound", "Node Dominated by I/O Wait", &["io_wait_ratio"]),
+    rule("io_timing_disabled", "I/O Timing Not Tracked", &[]),
+    rule(
+        "jit_overhead",
+        "JIT Compilation Outweighs Its Benefit",
+        &["jit_overhead_ratio"],
+    ),
+];
+
+/// The rule with id `id`
+pub fn find_rule(id: &str) -> Option<&'static RuleDefinition> {
+    RULES.iter().find(|rule| rule.id == id)
+}
+
+/// A rule as an advisor configuration runs it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleStatus {
+    /// Id reported as `rule_id` by the rule's suggestions
+    pub id: String,
+    /// What the rule reports
+    pub title: String,
+    /// Whether the rule's suggestions are reported
+    pub enabled: bool,
+    /// Current values of the thresholds the rule reads, by name
+    pub thresholds: BTreeMap<String, f64>,
+}
+
+impl RuleDefinition {
+    /// How `config` runs the rule
+    pub fn status(&self, config: &AdvisorConfig) -> RuleStatus {
+        RuleStatus {
+            id: self.id.to_string(),
+            title: self.title.to_string(),
+            enabled: config.is_rule_enabled(self.id),
+            thresholds: self
+                .thresholds
+                .iter()
+                .filter_map(|name| Some((name.to_string(), config.threshold(name)?)))
+                .collect(),
+        }
+    }
+}
+
+impl AdvisorConfig {
+    /// Whether suggestions of the rule `rule_id` are reported
+    pub fn is_rule_enabled(&self, rule_id: &str) -> bool {
+        !self.disabled_rules.contains(rule_id)
+    }
+
+    /// Current value of the threshold `name`
+    pub fn threshold(&self, name: &str) -> Option<f64> {
+        Some(match name {
+            "expensive_cost_threshold" => self.expensive_cost_threshold,
+            "large_scan_threshold" => self.large_scan_threshold as f64,
+            "recursion_iteration_threshold" => self.recursion_iteration_threshold as f64,
+            "parallel_overhead_ratio" => self.parallel_overhead_ratio,
+            "min_rows_per_worker" => self.min_rows_per_worker as f64,
+            "sequence_usage_threshold" => self.sequence_usage_threshold,
+            "small_table_rows" => self.small_table_rows as f64,
+            "stale_statistics_ratio" => self.stale_statistics_ratio,
+            "misestimate_factor" => self.misestimate_factor,
+            "memoize_min_hit_ratio" => self.memoize_min_hit_ratio,
+            "min_memoize_lookups" => self.min_memoize_lookups as f64,
+            "io_wait_ratio" => self.io_wait_ratio,
+            "jit_overhead_ratio" => self.jit_overhead_ratio,
+            _ => return None,
+        })
+    }
+
+    /// Set the threshold `name`, refusing values it can't take
+    ///
+    /// Counts must be whole numbers, ratios lie between 0 and 1, and
+    /// `misestimate_factor` is at least 1.
+    pub fn set_threshold(&mut self, name: &str, value: f64) -> Result<(), String> {
+        if !value.is_finite() || value < 0.0 {
+            return Err(format!("{} must be a non-negative number", name));
+        }
+        let count = || {
+            if value.fract() == 0.0 {
+                Ok(value as u64)
+            } else {
+                Err(format!("{} must be a whole number", name))
+            }
+        };
+        let ratio = || {
+            if value <= 1.0 {
+                Ok(value)
+            } else {
+                Err(format!("{} must be between 0 and 1", name))
+            }
+        };
+        match name {
+            "expensive_cost_threshold" => self.expensive_cost_threshold = value,
+            "large_scan_threshold" => self.large_scan_threshold = count()?,
+            "recursion_iteration_threshold" => self.recursion_iteration_threshold = count()?,
+            "parallel_overhead_ratio" => self.parallel_overhead_ratio = ratio()?,
+            "min_rows_per_worker" => self.min_rows_per_worker = count()?,
+            "sequence_usage_threshold" => self.sequence_usage_threshold = ratio()?,
+            "small_table_rows" => self.small_table_rows = count()?,
+            "stale_statistics_ratio" => self.stale_statistics_ratio = ratio()?,
+            "misestimate_factor" if value < 1.0 => {
+                return Err("misestimate_factor must be at least 1".to_string())
+            }
+            "misestimate_factor" => self.misestimate_factor = value,
+            "memoize_min_hit_ratio" => self.memoize_min_hit_ratio = ratio()?,
+            "min_memoize_lookups" => self.min_memoize_lookups = count()?,
+            "io_wait_ratio" => self.io_wait_ratio = ratio()?,
+            "jit_overhead_ratio" => self.jit_overhead_ratio = ratio()?,
+            _ => return Err(format!("Unknown threshold {}", name)),
+        }
+        Ok(())
+    }
+}
+
+/// Rule changes made at runtime, applied on top of the startup configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleOverrides {
+    /// Ids of disabled rules
+    pub disabled_rules: BTreeSet<String>,
+    /// Threshold values, by name
+    pub thresholds: BTreeMap<String, f64>,
+}
+
+impl RuleOverrides {
+    /// Apply the overrides to `config`, failing on unknown rules and invalid thresholds
+    pub fn apply(&self, config: &mut AdvisorConfig) -> Result<(), String> {
+        if let Some(id) = self
+            .disabled_rules
+            .iter()
+            .find(|id| find_rule(id).is_none())
+        {
+            return Err(format!("Unknown rule {}", id));
+        }
+        for (name, value) in &self.thresholds {
+            config.set_threshold(name, *value)?;
+        }
+        config.disabled_rules = self.disabled_rules.clone();
+        Ok(())
+    }
+}
+
+/// An advisor shared by every request, whose rules can be changed while it is in use
+///
+/// Requests take a copy of the advisor as it is configured when they start,
+/// so a change never applies to half an analysis.
+#[derive(Debug, Clone, Default)]
+pub struct SharedAdvisor {
+    inner: Arc<RwLock<Tuned>>,
+}
+
+#[derive(Debug, Default)]
+struct Tuned {
+    advisor: QueryAdvisor,
+    overrides: RuleOverrides,
+}
+
+impl SharedAdvisor {
+    /// Share `advisor`, without overrides
+    pub fn new(advisor: QueryAdvisor) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Tuned {
+                advisor,
+                overrides: RuleOverrides::default(),
+            })),
+        }
+    }
+
+    /// The advisor as currently configured
+    pub fn current(&self) -> QueryAdvisor {
+        self.inner
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .advisor
+            .clone()
+    }
+
+    /// The overrides applied so far
+    pub fn overrides(&self) -> RuleOverrides {
+        self.inner
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .overrides
+            .clone()
+    }
+
+    /// The configuration `overrides` would result in, without applying it
+    pub fn preview(&self, overrides: &RuleOverrides) -> Result<AdvisorConfig, String> {
+        let mut config = self.current().config().clone();
+        overrides.apply(&mut config)?;
+        Ok(config)
+    }
+
+    /// Replace the overrides in use with `overrides`
+    pub fn apply(&self, overrides: RuleOverrides) -> Result<(), String> {
+        let mut tuned = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        let mut config = tuned.advisor.config().clone();
+        overrides.apply(&mut config)?;
+        tuned.advisor = QueryAdvisor::with_config(config);
+        tuned.overrides = overrides;
+        Ok(())
+    }
+}
+
+impl From<QueryAdvisor> for SharedAdvisor {
+    fn from(advisor: QueryAdvisor) -> Self {
+        Self::new(advisor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advisor::AnalysisContext;
+    use crate::db::models::ExecutionPlan;
+
+    #[test]
+    fn test_thresholds() {
+        let mut config = AdvisorConfig::default();
+        for rule in &RULES {
+            for name in rule.thresholds {
+                assert!(config.threshold(name).is_some(), "{}", name);
+            }
+        }
+
+        config.set_threshold("large_scan_threshold", 500.0).unwrap();
+        assert_eq!(config.large_scan_threshold, 500);
+        assert!(config.set_threshold("large_scan_threshold", 1.5).is_err());
+        assert!(config.set_threshold("stale_statistics_ratio", 2.0).is_err());
+        assert!(config.set_threshold("misestimate_factor", 0.5).is_err());
+        assert!(config
+            .set_threshold("expensive_cost_threshold", -1.0)
+            .is_err());
+        assert!(config.set_threshold("work_mem", 4.0).is_err());
+    }
+
+    #[test]
+    fn test_overrides_apply_to_shared_advisor() {
+        let shared = SharedAdvisor::default();
+        let copy = shared.clone();
+        let plan = ExecutionPlan {
+            root: serde_json::from_value(serde_json::json!({
+                "Node Type": "Seq Scan", "Relation Name": "orders",
+                "Startup Cost": 0.0, "Total Cost": 5000.0,
+                "Filter": "(status = 'open'::text)"
+            }))
+            .unwrap(),
+            planning_time: 0.1,
+            execution_time: 1.0,
+            settings: None,
+            jit: None,
+        };
+        let ids = |advisor: QueryAdvisor| -> Vec<String> {
+            advisor
+                .analyze_plan_with_context(&plan, &AnalysisContext::default())
+                .suggestions
+                .into_iter()
+                .map(|s| s.rule_id)
+                .collect()
+        };
+        assert!(ids(copy.current()).contains(&"expensive_seq_scan".to_string()));
+
+        let mut overrides = RuleOverrides::default();
+        overrides
+            .disabled_rules
+            .insert("expensive_seq_scan".to_string());
+        overrides
+            .thresholds
+            .insert("expensive_cost_threshold".to_string(), 10000.0);
+        shared.apply(overrides.clone()).unwrap();
+
+        let ids = ids(copy.current());
+        assert!(!ids.contains(&"expensive_seq_scan".to_string()));
+        assert!(!ids.contains(&"expensive_operation".to_string()));
+        assert_eq!(copy.overrides(), overrides);
+
+        let status = find_rule("expensive_seq_scan")
+            .unwrap()
+            .status(copy.current().config());
+        assert!(!status.enabled);
+        assert_eq!(status.thresholds["expensive_cost_threshold"], 10000.0);
+        assert_eq!(status.thresholds["small_table_rows"], 1000.0);
+
+        overrides.disabled_rules.insert("no_such_rule".to_string());
+        assert!(shared.preview(&overrides).is_err());
+        assert!(shared.apply(overrides).is_err());
+    }
+}
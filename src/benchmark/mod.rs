@@ -4,16 +4,38 @@
 //! metrics, and compare different query implementations.
 
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::time::{Duration, Instant};
 
-use crate::advisor::{AdvisorAnalysis, QueryAdvisor};
+use crate::advisor::{AdvisorAnalysis, AnalysisContext, OptimizationSuggestion, QueryAdvisor};
+use crate::db::background_activity::{ActivitySnapshot, BackgroundActivity};
+use crate::db::baseline::SystemBaseline;
+use crate::db::engines::{DatabaseFeature, EngineType};
+use crate::db::explain_options::ExplainOptions;
 use crate::db::models::ExecutionPlan;
+use crate::db::relation_check;
+use crate::db::session::Session;
+use crate::db::settings::{self, ServerSettings, SettingChange};
+use crate::db::transaction::{self, StatementRun, TempRelation, TempTableUsage, TransactionMode};
 use crate::db::Database;
+use crate::queue::QueueLimits;
 use crate::SqlTraceError;
 
+pub mod outliers;
+pub mod query_group;
+pub mod raw_data;
+pub mod rewrite;
+
+pub use outliers::{OutlierHandling, OutlierReport};
+pub use query_group::QueryGroupReport;
+pub use raw_data::RunTable;
+pub use rewrite::{FindingsDiff, RewriteReport};
+
 /// Configuration for benchmark runs
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BenchmarkConfig {
     /// Number of warmup runs (not included in metrics)
     pub warmup_runs: u32,
@@ -25,6 +47,20 @@ pub struct BenchmarkConfig {
     pub include_execution_plans: bool,
     /// Whether to run advisor analysis on each query
     pub include_advisor_analysis: bool,
+    /// Statements run before the warmup runs, in the session the runs use
+    pub setup: Vec<String>,
+    /// Statements run after the last run, before the session is closed
+    pub teardown: Vec<String>,
+    /// Whether to flag or exclude runs far slower or faster than the others
+    pub outliers: OutlierHandling,
+    /// Modified z-score above which a run counts as an outlier
+    pub outlier_threshold: f64,
+    /// Whether to measure a synthetic baseline load before the runs, on PostgreSQL
+    pub baseline: bool,
+    /// How long the baseline load runs, in seconds
+    pub baseline_seconds: u64,
+    /// Number of connections running the baseline load at once
+    pub baseline_clients: u32,
 }
 
 impl Default for BenchmarkConfig {
@@ -35,8 +71,172 @@ impl Default for BenchmarkConfig {
             timeout_seconds: 30,
             include_execution_plans: true,
             include_advisor_analysis: true,
+            setup: Vec::new(),
+            teardown: Vec::new(),
+            outliers: OutlierHandling::Off,
+            outlier_threshold: 3.5,
+            baseline: false,
+            baseline_seconds: 5,
+            baseline_clients: 1,
+        }
+    }
+}
+
+/// Per-request changes to the server's default benchmark configuration
+///
+/// Has the same shape as [`BenchmarkConfig`], but every field is optional so
+/// omitted values fall back to the server defaults rather than built-in ones.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BenchmarkOverrides {
+    /// Number of warmup runs
+    pub warmup_runs: Option<u32>,
+    /// Number of measured runs
+    pub benchmark_runs: Option<u32>,
+    /// Timeout for individual query execution (in seconds)
+    pub timeout_seconds: Option<u64>,
+    /// Whether to include detailed execution plans in results
+    pub include_execution_plans: Option<bool>,
+    /// Whether to run advisor analysis on each query
+    pub include_advisor_analysis: Option<bool>,
+    /// Statements run before the warmup runs
+    pub setup: Option<Vec<String>>,
+    /// Statements run after the last run
+    pub teardown: Option<Vec<String>>,
+    /// Whether to flag or exclude outlying runs
+    pub outliers: Option<OutlierHandling>,
+    /// Modified z-score above which a run counts as an outlier
+    pub outlier_threshold: Option<f64>,
+    /// Whether to measure a synthetic baseline load before the runs
+    pub baseline: Option<bool>,
+    /// How long the baseline load runs, in seconds
+    pub baseline_seconds: Option<u64>,
+    /// Number of connections running the baseline load at once
+    pub baseline_clients: Option<u32>,
+}
+
+/// Organization-wide benchmark defaults and the limits requests are held to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BenchmarkLimits {
+    /// Configuration used for values a request does not set
+    pub defaults: BenchmarkConfig,
+    /// Upper bound for warmup runs
+    pub max_warmup_runs: u32,
+    /// Upper bound for measured runs
+    pub max_benchmark_runs: u32,
+    /// Upper bound for the per-run timeout, in seconds
+    pub max_timeout_seconds: u64,
+    /// Upper bound for the duration of the baseline load, in seconds
+    pub max_baseline_seconds: u64,
+    /// Upper bound for the connections running the baseline load
+    pub max_baseline_clients: u32,
+    /// Number of benchmarks allowed to run at the same time
+    pub max_concurrent: usize,
+    /// Benchmarks allowed to wait for a slot across all users; 0 rejects
+    /// requests as soon as every slot is taken
+    pub max_queued: usize,
+    /// Benchmarks allowed to wait for a slot for one user
+    pub max_queued_per_user: usize,
+    /// Longest a benchmark waits for a slot before it is rejected, in seconds
+    pub max_queue_wait_seconds: u64,
+    /// Upper bound for rows fetched per query by a result check
+    pub max_result_rows: usize,
+    /// Allow transaction benchmarks to run INSERT, UPDATE and DELETE statements,
+    /// which are rolled back but still take locks and advance sequences
+    pub allow_write_transactions: bool,
+}
+
+impl Default for BenchmarkLimits {
+    fn default() -> Self {
+        Self {
+            defaults: BenchmarkConfig::default(),
+            max_warmup_runs: 10,
+            max_benchmark_runs: 50,
+            max_timeout_seconds: 300,
+            max_baseline_seconds: 30,
+            max_baseline_clients: 4,
+            max_concurrent: 4,
+            max_queued: 32,
+            max_queued_per_user: 8,
+            max_queue_wait_seconds: 300,
+            max_result_rows: 10_000,
+            allow_write_transactions: false,
+        }
+    }
+}
+
+impl BenchmarkLimits {
+    /// Limits of the queue benchmarks wait in for a slot
+    pub fn queue_limits(&self) -> QueueLimits {
+        QueueLimits {
+            slots: self.max_concurrent,
+            max_queued: self.max_queued,
+            max_queued_per_user: self.max_queued_per_user,
+            max_wait: Duration::from_secs(self.max_queue_wait_seconds),
+        }
+    }
+
+    /// Apply request overrides on top of the defaults, clamping them to the configured maximums
+    pub fn resolve(&self, overrides: Option<&BenchmarkOverrides>) -> BenchmarkConfig {
+        let overrides = overrides.cloned().unwrap_or_default();
+        let defaults = &self.defaults;
+
+        BenchmarkConfig {
+            warmup_runs: overrides
+                .warmup_runs
+                .unwrap_or(defaults.warmup_runs)
+                .min(self.max_warmup_runs),
+            benchmark_runs: overrides
+                .benchmark_runs
+                .unwrap_or(defaults.benchmark_runs)
+                .clamp(1, self.max_benchmark_runs.max(1)),
+            timeout_seconds: overrides
+                .timeout_seconds
+                .unwrap_or(defaults.timeout_seconds)
+                .clamp(1, self.max_timeout_seconds.max(1)),
+            include_execution_plans: overrides
+                .include_execution_plans
+                .unwrap_or(defaults.include_execution_plans),
+            include_advisor_analysis: overrides
+                .include_advisor_analysis
+                .unwrap_or(defaults.include_advisor_analysis),
+            setup: overrides.setup.unwrap_or_else(|| defaults.setup.clone()),
+            teardown: overrides
+                .teardown
+                .unwrap_or_else(|| defaults.teardown.clone()),
+            outliers: overrides.outliers.unwrap_or(defaults.outliers),
+            outlier_threshold: overrides
+                .outlier_threshold
+                .filter(|t| t.is_finite() && *t > 0.0)
+                .unwrap_or(defaults.outlier_threshold),
+            baseline: overrides.baseline.unwrap_or(defaults.baseline),
+            baseline_seconds: overrides
+                .baseline_seconds
+                .unwrap_or(defaults.baseline_seconds)
+                .clamp(1, self.max_baseline_seconds.max(1)),
+            baseline_clients: overrides
+                .baseline_clients
+                .unwrap_or(defaults.baseline_clients)
+                .clamp(1, self.max_baseline_clients.max(1)),
         }
     }
+
+    /// Rows a result check may fetch per query, given the requested number
+    pub fn result_rows(&self, requested: Option<usize>) -> usize {
+        requested
+            .unwrap_or(self.max_result_rows)
+            .clamp(1, self.max_result_rows.max(1))
+    }
+}
+
+/// Options for checking that two queries return the same results
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResultCheckOptions {
+    /// Compare rows in the order returned instead of as unordered sets
+    pub ordered: bool,
+    /// Rows to fetch from each query; capped by the server's `max_result_rows`
+    pub max_rows: Option<usize>,
 }
 
 /// Single benchmark run result
@@ -63,6 +263,75 @@ pub struct BenchmarkResult {
     pub statistics: BenchmarkStatistics,
     /// Configuration used for this benchmark
     pub config: BenchmarkConfig,
+    /// Autovacuum and checkpoint activity during the runs, on PostgreSQL
+    pub background_activity: Option<BackgroundActivity>,
+    /// Synthetic load measured before the runs, if enabled in config
+    #[serde(default)]
+    pub baseline: Option<SystemBaseline>,
+    /// Planner-relevant server settings in the benchmark session, on PostgreSQL
+    #[serde(default)]
+    pub settings: Option<ServerSettings>,
+}
+
+/// Single run of a benchmarked transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionRun {
+    /// End-to-end duration, from beginning the transaction to rolling it back
+    pub execution_time: Duration,
+    /// Timing of each statement, in order
+    pub statements: Vec<StatementRun>,
+    /// Temporary tables as they were before the rollback
+    #[serde(default)]
+    pub temp_relations: Vec<TempRelation>,
+    /// Timestamp when the run was executed
+    pub timestamp: std::time::SystemTime,
+}
+
+/// Timing summary of one statement across the runs of a transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementStatistics {
+    /// The statement
+    pub statement: String,
+    /// Average execution time
+    pub avg_execution_time: Duration,
+    /// 95th percentile execution time
+    pub p95_execution_time: Duration,
+    /// Share of the average end-to-end time spent in this statement (0.0 - 1.0)
+    pub share_of_total: f64,
+}
+
+/// Complete benchmark result for a multi-statement transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionBenchmarkResult {
+    /// Statements run in each transaction, in order
+    pub statements: Vec<String>,
+    /// How the transaction was run
+    pub mode: TransactionMode,
+    /// Individual run results
+    pub runs: Vec<TransactionRun>,
+    /// Statistical summary of the end-to-end durations
+    pub statistics: BenchmarkStatistics,
+    /// Statistical summary of each statement
+    pub statement_statistics: Vec<StatementStatistics>,
+    /// Temporary tables the statements create, with their largest size across the runs
+    #[serde(default)]
+    pub temp_tables: Vec<TempTableUsage>,
+    /// The session's `temp_buffers` in bytes, if temporary tables were created
+    #[serde(default)]
+    pub temp_buffers_bytes: Option<i64>,
+    /// Advisor suggestions about the temporary tables
+    #[serde(default)]
+    pub suggestions: Vec<OptimizationSuggestion>,
+    /// Configuration used for this benchmark
+    pub config: BenchmarkConfig,
+    /// Autovacuum and checkpoint activity during the runs, on PostgreSQL
+    pub background_activity: Option<BackgroundActivity>,
+    /// Synthetic load measured before the runs, if enabled in config
+    #[serde(default)]
+    pub baseline: Option<SystemBaseline>,
+    /// Planner-relevant server settings in the benchmark session, on PostgreSQL
+    #[serde(default)]
+    pub settings: Option<ServerSettings>,
 }
 
 /// Statistical analysis of benchmark runs
@@ -86,6 +355,8 @@ pub struct BenchmarkStatistics {
     pub avg_cost: Option<f64>,
     /// Average advisor score
     pub avg_advisor_score: Option<f64>,
+    /// Outlying runs, when outlier detection is enabled
+    pub outliers: Option<OutlierReport>,
 }
 
 /// Comparison between two benchmark results
@@ -101,6 +372,49 @@ pub struct BenchmarkComparison {
     pub statistical_significance: StatisticalSignificance,
     /// Detailed comparison metrics
     pub metrics: ComparisonMetrics,
+    /// Row counts of both queries, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row_counts: Option<RowCountCheck>,
+    /// Result set fingerprints of both queries, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub results: Option<ResultSetCheck>,
+    /// Server settings that differed between the two benchmarks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub settings_changes: Vec<SettingChange>,
+}
+
+/// Whether two queries return the same number of rows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowCountCheck {
+    /// Rows returned by the first query
+    pub rows_a: u64,
+    /// Rows returned by the second query
+    pub rows_b: u64,
+    /// Whether the counts are equal
+    pub matches: bool,
+}
+
+/// Whether two queries return the same rows
+///
+/// Only the first rows of each query are fetched. When either query returns
+/// more, `truncated` is set and the check covers those rows only, which for
+/// unordered results may differ even if the full results match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultSetCheck {
+    /// Whether row order was part of the comparison
+    pub ordered: bool,
+    /// Rows compared from the first query
+    pub rows_a: usize,
+    /// Rows compared from the second query
+    pub rows_b: usize,
+    /// Fingerprint of the first query's rows
+    pub hash_a: String,
+    /// Fingerprint of the second query's rows
+    pub hash_b: String,
+    /// Whether either query returned more rows than were fetched
+    pub truncated: bool,
+    /// Whether the fingerprints are equal
+    pub matches: bool,
 }
 
 /// Statistical significance levels
@@ -150,21 +464,29 @@ impl BenchmarkSuite {
     pub async fn benchmark_query(&self, query: &str) -> Result<BenchmarkResult, SqlTraceError> {
         let mut runs = Vec::new();
         let mut failed_runs = 0;
+        let baseline = self.measure_baseline().await;
+        let mut session = self.open_session().await?;
+        let settings = self.server_settings(session.as_mut()).await;
 
         // Warmup runs
         for _ in 0..self.config.warmup_runs {
-            if let Err(_) = self.execute_single_run(query).await {
+            #[allow(clippy::redundant_pattern_matching)]
+            if let Err(_) = self.execute_single_run(query, session.as_mut()).await {
                 // Ignore warmup failures
             }
         }
 
         // Actual benchmark runs
+        let relations = referenced_tables(&[query]);
+        let activity_before = self.activity_snapshot(&relations).await;
         for _ in 0..self.config.benchmark_runs {
-            match self.execute_single_run(query).await {
+            match self.execute_single_run(query, session.as_mut()).await {
                 Ok(run) => runs.push(run),
                 Err(_) => failed_runs += 1,
             }
         }
+        let background_activity = self.background_activity(activity_before, &relations).await;
+        close_session(session).await;
 
         if runs.is_empty() {
             return Err(SqlTraceError::Database(
@@ -179,16 +501,272 @@ impl BenchmarkSuite {
             runs,
             statistics,
             config: self.config.clone(),
+            background_activity,
+            baseline,
+            settings,
         })
     }
 
+    /// Benchmark `statements` run in order inside one transaction per run
+    ///
+    /// Every run is rolled back. Execution plans and advisor analysis are not
+    /// collected, since the statements run as they are rather than explained;
+    /// only the temporary tables the statements create are checked.
+    pub async fn benchmark_transaction(
+        &self,
+        statements: &[String],
+        mode: TransactionMode,
+    ) -> Result<TransactionBenchmarkResult, SqlTraceError> {
+        let mut runs = Vec::new();
+        let mut failed_runs = 0;
+        let mut temp_buffers_bytes = None;
+        let baseline = self.measure_baseline().await;
+        let mut session = self.open_session().await?;
+        let settings = self.server_settings(session.as_mut()).await;
+
+        for _ in 0..self.config.warmup_runs {
+            let _ = self
+                .execute_transaction_run(statements, mode, session.as_mut())
+                .await;
+        }
+
+        // A statement that fails once fails every time, so report the error
+        let mut last_error = None;
+        let relations = referenced_tables(statements);
+        let activity_before = self.activity_snapshot(&relations).await;
+        for _ in 0..self.config.benchmark_runs {
+            match self
+                .execute_transaction_run(statements, mode, session.as_mut())
+                .await
+            {
+                Ok((run, temp_buffers)) => {
+                    temp_buffers_bytes = temp_buffers.or(temp_buffers_bytes);
+                    runs.push(run);
+                }
+                Err(e) => {
+                    failed_runs += 1;
+                    last_error = Some(e);
+                }
+            }
+        }
+        let background_activity = self.background_activity(activity_before, &relations).await;
+        close_session(session).await;
+
+        if runs.is_empty() {
+            return Err(last_error.unwrap_or_else(|| {
+                SqlTraceError::Database("All benchmark runs failed".to_string())
+            }));
+        }
+
+        let execution_times: Vec<Duration> = runs.iter().map(|run| run.execution_time).collect();
+        let outliers = self.find_outliers(&execution_times, &[]);
+        let kept = outliers::retained(&runs, outliers.as_ref());
+        let kept_times: Vec<Duration> = kept.iter().map(|run| run.execution_time).collect();
+        let statistics = self.duration_statistics(&kept_times, failed_runs, outliers);
+        let statement_statistics = self.statement_statistics(statements, &kept, &statistics);
+        let temp_tables = temp_table_sizes(transaction::temp_table_usage(statements), &runs);
+        let suggestions = self
+            .advisor
+            .analyze_temp_tables(&temp_tables, temp_buffers_bytes);
+
+        Ok(TransactionBenchmarkResult {
+            statements: statements.to_vec(),
+            mode,
+            runs,
+            statistics,
+            statement_statistics,
+            temp_tables,
+            temp_buffers_bytes,
+            suggestions,
+            config: self.config.clone(),
+            background_activity,
+            baseline,
+            settings,
+        })
+    }
+
+    /// Execute a single run of a transaction benchmark, returning it with the
+    /// session's `temp_buffers` if temporary tables were created
+    async fn execute_transaction_run(
+        &self,
+        statements: &[String],
+        mode: TransactionMode,
+        session: Option<&mut Session>,
+    ) -> Result<(TransactionRun, Option<i64>), SqlTraceError> {
+        let timeout = Duration::from_secs(self.config.timeout_seconds.max(1));
+        let start_time = Instant::now();
+        let outcome = match session {
+            Some(session) => {
+                tokio::time::timeout(timeout, session.run_transaction(statements, mode, timeout))
+                    .await
+            }
+            None => {
+                tokio::time::timeout(timeout, self.db.run_transaction(statements, mode, timeout))
+                    .await
+            }
+        }
+        .map_err(|_| {
+            SqlTraceError::Database(format!(
+                "Benchmark run exceeded the {}s timeout",
+                timeout.as_secs()
+            ))
+        })??;
+
+        let run = TransactionRun {
+            execution_time: start_time.elapsed(),
+            statements: outcome.statements,
+            temp_relations: outcome.temp_relations,
+            timestamp: std::time::SystemTime::now(),
+        };
+        Ok((run, outcome.temp_buffers_bytes))
+    }
+
+    /// Summarize the time spent in each statement of a transaction
+    fn statement_statistics(
+        &self,
+        statements: &[String],
+        runs: &[TransactionRun],
+        statistics: &BenchmarkStatistics,
+    ) -> Vec<StatementStatistics> {
+        let total = statistics.avg_execution_time.as_secs_f64();
+        statements
+            .iter()
+            .enumerate()
+            .map(|(i, statement)| {
+                let times: Vec<Duration> = runs
+                    .iter()
+                    .filter_map(|run| run.statements.get(i))
+                    .map(|run| run.execution_time)
+                    .collect();
+                let avg_execution_time = self.calculate_average_duration(&times);
+                StatementStatistics {
+                    statement: statement.clone(),
+                    avg_execution_time,
+                    p95_execution_time: self.calculate_percentile(&times, 0.95),
+                    share_of_total: if total > 0.0 {
+                        avg_execution_time.as_secs_f64() / total
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Context for advisor analysis of plans from this suite's database
+    fn analysis_context(&self) -> AnalysisContext {
+        AnalysisContext {
+            estimates_only: !self.db.supports_feature(&DatabaseFeature::ActualRowCounts),
+            ..AnalysisContext::default()
+        }
+    }
+
+    /// Read the activity counters of `relations`, on PostgreSQL
+    ///
+    /// Activity capture is best-effort; a failure only leaves it out of the result.
+    async fn activity_snapshot(&self, relations: &[String]) -> Option<ActivitySnapshot> {
+        if self.db.engine_type() != EngineType::PostgreSQL {
+            return None;
+        }
+        match self.db.activity_snapshot(relations).await {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                tracing::warn!("Failed to read background activity counters: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Background activity since `before` was taken
+    async fn background_activity(
+        &self,
+        before: Option<ActivitySnapshot>,
+        relations: &[String],
+    ) -> Option<BackgroundActivity> {
+        let before = before?;
+        let after = self.activity_snapshot(relations).await?;
+        Some(BackgroundActivity::between(&before, &after))
+    }
+
+    /// Measure the baseline load, if enabled
+    ///
+    /// Like activity capture, the baseline is best-effort; a failure only
+    /// leaves it out of the result.
+    async fn measure_baseline(&self) -> Option<SystemBaseline> {
+        if !self.config.baseline {
+            return None;
+        }
+        let duration = Duration::from_secs(self.config.baseline_seconds.max(1));
+        match self
+            .db
+            .measure_baseline(self.config.baseline_clients, duration)
+            .await
+        {
+            Ok(baseline) => Some(baseline),
+            Err(e) => {
+                tracing::warn!("Failed to measure the baseline load: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Server settings the runs see, read in the session when there is one
+    async fn server_settings(&self, session: Option<&mut Session>) -> Option<ServerSettings> {
+        if self.db.engine_type() != EngineType::PostgreSQL {
+            return None;
+        }
+        let settings = match session {
+            Some(session) => session.settings().await,
+            None => self.db.server_settings().await,
+        };
+        match settings {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                tracing::warn!("Failed to read server settings: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Open a session for the configured setup and teardown statements, if any
+    async fn open_session(&self) -> Result<Option<Session>, SqlTraceError> {
+        if self.config.setup.is_empty() && self.config.teardown.is_empty() {
+            return Ok(None);
+        }
+        self.db
+            .open_session(&self.config.setup, &self.config.teardown)
+            .await
+            .map(Some)
+    }
+
     /// Execute a single benchmark run
-    async fn execute_single_run(&self, query: &str) -> Result<BenchmarkRun, SqlTraceError> {
+    async fn execute_single_run(
+        &self,
+        query: &str,
+        session: Option<&mut Session>,
+    ) -> Result<BenchmarkRun, SqlTraceError> {
         let start_time = Instant::now();
 
         // Execute the query and get execution plan
         let execution_plan = if self.config.include_execution_plans {
-            Some(self.db.explain(query).await?)
+            let timeout = Duration::from_secs(self.config.timeout_seconds.max(1));
+            let plan = match session {
+                Some(session) => {
+                    tokio::time::timeout(
+                        timeout,
+                        session.explain(query, &ExplainOptions::default()),
+                    )
+                    .await
+                }
+                None => tokio::time::timeout(timeout, self.db.explain(query)).await,
+            }
+            .map_err(|_| {
+                SqlTraceError::Database(format!(
+                    "Benchmark run exceeded the {}s timeout",
+                    timeout.as_secs()
+                ))
+            })??;
+            Some(plan)
         } else {
             None
         };
@@ -197,9 +775,10 @@ impl BenchmarkSuite {
 
         // Run advisor analysis if enabled
         let advisor_analysis = if self.config.include_advisor_analysis {
-            execution_plan
-                .as_ref()
-                .map(|plan| self.advisor.analyze_plan(plan))
+            execution_plan.as_ref().map(|plan| {
+                self.advisor
+                    .analyze_plan_with_context(plan, &self.analysis_context())
+            })
         } else {
             None
         };
@@ -215,8 +794,49 @@ impl BenchmarkSuite {
     /// Calculate statistical summary of benchmark runs
     fn calculate_statistics(&self, runs: &[BenchmarkRun], failed_runs: u32) -> BenchmarkStatistics {
         let execution_times: Vec<Duration> = runs.iter().map(|run| run.execution_time).collect();
+        let disk_reads: Vec<Option<u64>> = runs
+            .iter()
+            .map(|run| {
+                run.execution_plan
+                    .as_ref()
+                    .and_then(|plan| plan.root.buffers.map(|b| b.shared_read))
+            })
+            .collect();
+        let outliers = self.find_outliers(&execution_times, &disk_reads);
+        let kept = outliers::retained(runs, outliers.as_ref());
+        let kept_times: Vec<Duration> = kept.iter().map(|run| run.execution_time).collect();
 
-        let avg_execution_time = self.calculate_average_duration(&execution_times);
+        BenchmarkStatistics {
+            avg_cost: self.calculate_average_cost(&kept),
+            avg_advisor_score: self.calculate_average_advisor_score(&kept),
+            ..self.duration_statistics(&kept_times, failed_runs, outliers)
+        }
+    }
+
+    /// Look for outlying runs as configured
+    fn find_outliers(
+        &self,
+        execution_times: &[Duration],
+        disk_reads: &[Option<u64>],
+    ) -> Option<OutlierReport> {
+        outliers::detect(
+            execution_times,
+            disk_reads,
+            self.config.outliers,
+            self.config.outlier_threshold,
+        )
+    }
+
+    /// Calculate the timing statistics of successful runs, without plan metrics
+    ///
+    /// `execution_times` are the runs kept after excluding `outliers`.
+    fn duration_statistics(
+        &self,
+        execution_times: &[Duration],
+        failed_runs: u32,
+        outliers: Option<OutlierReport>,
+    ) -> BenchmarkStatistics {
+        let avg_execution_time = self.calculate_average_duration(execution_times);
         let min_execution_time = execution_times
             .iter()
             .min()
@@ -227,11 +847,8 @@ impl BenchmarkSuite {
             .max()
             .copied()
             .unwrap_or(Duration::ZERO);
-        let std_deviation = self.calculate_std_deviation(&execution_times, avg_execution_time);
-        let p95_execution_time = self.calculate_percentile(&execution_times, 0.95);
-
-        let avg_cost = self.calculate_average_cost(runs);
-        let avg_advisor_score = self.calculate_average_advisor_score(runs);
+        let std_deviation = self.calculate_std_deviation(execution_times, avg_execution_time);
+        let p95_execution_time = self.calculate_percentile(execution_times, 0.95);
 
         BenchmarkStatistics {
             avg_execution_time,
@@ -239,10 +856,12 @@ impl BenchmarkSuite {
             max_execution_time,
             std_deviation,
             p95_execution_time,
-            successful_runs: runs.len() as u32,
+            successful_runs: execution_times.len() as u32
+                + outliers.as_ref().map_or(0, |o| o.excluded_runs),
             failed_runs,
-            avg_cost,
-            avg_advisor_score,
+            avg_cost: None,
+            avg_advisor_score: None,
+            outliers,
         }
     }
 
@@ -337,6 +956,7 @@ impl BenchmarkSuite {
             0.0
         };
 
+        #[allow(clippy::manual_abs_diff)]
         let avg_time_diff = if avg_time_b > avg_time_a {
             avg_time_b - avg_time_a
         } else {
@@ -373,7 +993,72 @@ impl BenchmarkSuite {
                 advisor_score_diff,
                 confidence_interval,
             },
+            row_counts: None,
+            results: None,
+            settings_changes: settings::setting_changes(
+                result_a.settings.as_ref(),
+                result_b.settings.as_ref(),
+            ),
+        }
+    }
+
+    /// Count the rows both queries return, to check a rewrite kept the result cardinality
+    pub async fn compare_row_counts(
+        &self,
+        query_a: &str,
+        query_b: &str,
+    ) -> Result<RowCountCheck, SqlTraceError> {
+        let timeout = Duration::from_secs(self.config.timeout_seconds.max(1));
+        let mut counts = [0; 2];
+        for (count, query) in counts.iter_mut().zip([query_a, query_b]) {
+            *count = self.db.count_rows(query, timeout).await?;
         }
+
+        Ok(RowCountCheck {
+            rows_a: counts[0],
+            rows_b: counts[1],
+            matches: counts[0] == counts[1],
+        })
+    }
+
+    /// Fetch the first `max_rows` rows of both queries and compare their fingerprints
+    pub async fn compare_results(
+        &self,
+        query_a: &str,
+        query_b: &str,
+        options: &ResultCheckOptions,
+        max_rows: usize,
+    ) -> Result<ResultSetCheck, SqlTraceError> {
+        let timeout = Duration::from_secs(self.config.timeout_seconds.max(1));
+        let mut fetched = [Vec::new(), Vec::new()];
+        for (rows, query) in fetched.iter_mut().zip([query_a, query_b]) {
+            // One extra row tells whether the query returned more
+            *rows = tokio::time::timeout(timeout, self.db.fetch_rows(query, max_rows + 1))
+                .await
+                .map_err(|_| {
+                    SqlTraceError::Database(format!(
+                        "Fetching results exceeded the {}s timeout",
+                        timeout.as_secs()
+                    ))
+                })??;
+        }
+
+        let truncated = fetched.iter().any(|rows| rows.len() > max_rows);
+        let [mut rows_a, mut rows_b] = fetched;
+        rows_a.truncate(max_rows);
+        rows_b.truncate(max_rows);
+        let hash_a = result_hash(&mut rows_a, options.ordered);
+        let hash_b = result_hash(&mut rows_b, options.ordered);
+
+        Ok(ResultSetCheck {
+            ordered: options.ordered,
+            rows_a: rows_a.len(),
+            rows_b: rows_b.len(),
+            matches: hash_a == hash_b,
+            hash_a,
+            hash_b,
+            truncated,
+        })
     }
 
     /// Calculate statistical significance (simplified t-test)
@@ -455,7 +1140,60 @@ impl BenchmarkSuite {
     }
 }
 
+/// Fingerprint of a result set, ignoring row order unless `ordered`
+///
+/// Unordered rows are sorted first, so duplicate rows still count.
+/// Names of the tables the queries among `statements` read, without duplicates
+fn referenced_tables<S: AsRef<str>>(statements: &[S]) -> Vec<String> {
+    let mut tables = Vec::new();
+    for statement in statements {
+        for relation in relation_check::referenced_relations(statement.as_ref()) {
+            if !tables.contains(&relation.name) {
+                tables.push(relation.name);
+            }
+        }
+    }
+    tables
+}
+
+/// `tables` with the largest size each reached at the end of one of `runs`
+fn temp_table_sizes(
+    mut tables: Vec<TempTableUsage>,
+    runs: &[TransactionRun],
+) -> Vec<TempTableUsage> {
+    for table in &mut tables {
+        table.size_bytes = runs
+            .iter()
+            .flat_map(|run| &run.temp_relations)
+            .filter(|relation| relation.name == table.name)
+            .map(|relation| relation.size_bytes)
+            .max();
+    }
+    tables
+}
+
+/// Run the teardown statements of a session and close it
+///
+/// The runs already completed, so a failing teardown is only logged.
+async fn close_session(session: Option<Session>) {
+    if let Some(session) = session {
+        if let Err(e) = session.close().await {
+            tracing::warn!("Benchmark teardown failed: {}", e);
+        }
+    }
+}
+
+fn result_hash(rows: &mut [String], ordered: bool) -> String {
+    if !ordered {
+        rows.sort_unstable();
+    }
+    let mut hasher = DefaultHasher::new();
+    rows.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
 #[cfg(test)]
+#[allow(unused_variables, clippy::useless_vec)]
 mod tests {
     use super::*;
     use std::time::Duration;
@@ -468,6 +1206,95 @@ mod tests {
         assert_eq!(config.timeout_seconds, 30);
     }
 
+    #[test]
+    fn test_limits_resolve_overrides() {
+        let limits = BenchmarkLimits {
+            defaults: BenchmarkConfig {
+                warmup_runs: 1,
+                benchmark_runs: 3,
+                ..BenchmarkConfig::default()
+            },
+            max_benchmark_runs: 20,
+            max_timeout_seconds: 60,
+            ..BenchmarkLimits::default()
+        };
+
+        let config = limits.resolve(None);
+        assert_eq!(config.warmup_runs, 1);
+        assert_eq!(config.benchmark_runs, 3);
+
+        let config = limits.resolve(Some(&BenchmarkOverrides {
+            benchmark_runs: Some(1000),
+            timeout_seconds: Some(3600),
+            include_execution_plans: Some(false),
+            baseline: Some(true),
+            baseline_seconds: Some(600),
+            ..BenchmarkOverrides::default()
+        }));
+        assert_eq!(config.warmup_runs, 1);
+        assert_eq!(config.benchmark_runs, 20);
+        assert_eq!(config.timeout_seconds, 60);
+        assert!(!config.include_execution_plans);
+        assert!(config.baseline);
+        assert_eq!(config.baseline_seconds, 30);
+        assert_eq!(config.baseline_clients, 1);
+
+        assert_eq!(limits.result_rows(None), 10_000);
+        assert_eq!(limits.result_rows(Some(50)), 50);
+        assert_eq!(limits.result_rows(Some(1_000_000)), 10_000);
+    }
+
+    #[tokio::test]
+    async fn test_statement_statistics() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        let suite = BenchmarkSuite::new(db, QueryAdvisor::new(), None);
+        let run = |times: [u64; 2]| TransactionRun {
+            execution_time: Duration::from_millis(times.iter().sum::<u64>() + 2),
+            statements: times
+                .iter()
+                .map(|&ms| StatementRun {
+                    execution_time: Duration::from_millis(ms),
+                    rows: 1,
+                })
+                .collect(),
+            temp_relations: Vec::new(),
+            timestamp: std::time::SystemTime::now(),
+        };
+        let runs = [run([10, 30]), run([20, 30])];
+        let times: Vec<Duration> = runs.iter().map(|r| r.execution_time).collect();
+        let statistics = suite.duration_statistics(&times, 1, None);
+        assert_eq!(statistics.avg_execution_time, Duration::from_millis(47));
+        assert_eq!(statistics.successful_runs, 2);
+        assert_eq!(statistics.failed_runs, 1);
+
+        let statements = vec!["SELECT 1".to_string(), "SELECT 2".to_string()];
+        let per_statement = suite.statement_statistics(&statements, &runs, &statistics);
+        assert_eq!(
+            per_statement[0].avg_execution_time,
+            Duration::from_millis(15)
+        );
+        assert_eq!(
+            per_statement[1].p95_execution_time,
+            Duration::from_millis(30)
+        );
+        assert!((per_statement[1].share_of_total - 30.0 / 47.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_result_hash_ordering() {
+        let rows = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+
+        let (mut a, mut b) = (rows(&["[1]", "[2]", "[2]"]), rows(&["[2]", "[1]", "[2]"]));
+        assert_eq!(result_hash(&mut a, false), result_hash(&mut b, false));
+
+        let (mut a, mut b) = (rows(&["[1]", "[2]"]), rows(&["[2]", "[1]"]));
+        assert_ne!(result_hash(&mut a, true), result_hash(&mut b, true));
+
+        // Duplicates are not collapsed
+        let (mut a, mut b) = (rows(&["[1]", "[2]", "[2]"]), rows(&["[1]", "[1]", "[2]"]));
+        assert_ne!(result_hash(&mut a, false), result_hash(&mut b, false));
+    }
+
     #[test]
     fn test_calculate_average_duration() {
         // Test the duration calculation without database dependency
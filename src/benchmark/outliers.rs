@@ -0,0 +1,214 @@
+//! Detection of anomalous benchmark runs
+//!
+//! A checkpoint, an autovacuum worker or a cold page cache can make one run
+//! several times slower than the rest and drag the average with it. Runs are
+//! compared with the median using the modified z-score of Iglewicz and
+//! Hoaglin, which is based on the median absolute deviation (MAD) and so is
+//! not itself skewed by the outliers it looks for.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Scales the MAD to the standard deviation of normally distributed values
+const MAD_SCALE: f64 = 0.6745;
+
+/// Scales the mean absolute deviation when the MAD is zero
+const MEAN_AD_SCALE: f64 = 0.7979;
+
+/// Fewest runs outliers are looked for in
+const MIN_RUNS: usize = 3;
+
+/// What to do with runs whose execution time is far from the others
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutlierHandling {
+    /// Do not look for outliers
+    #[default]
+    Off,
+    /// Report outliers but keep them in the statistics
+    Flag,
+    /// Report outliers and leave them out of the statistics
+    Exclude,
+}
+
+/// A run whose execution time is far from the median
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Outlier {
+    /// Index of the run among the successful runs
+    pub run: usize,
+    /// Execution time of the run
+    pub execution_time: Duration,
+    /// Distance from the median in scaled MADs; negative for fast runs
+    pub modified_z_score: f64,
+    /// Likely cause, as far as the run shows it
+    pub reason: String,
+}
+
+/// Outliers found among the runs of a benchmark
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutlierReport {
+    /// How outliers were handled
+    pub handling: OutlierHandling,
+    /// Modified z-score above which a run counts as an outlier
+    pub threshold: f64,
+    /// The outlying runs
+    pub outliers: Vec<Outlier>,
+    /// Runs left out of the statistics
+    pub excluded_runs: u32,
+    /// False when the first run was a slow outlier, a sign that the warmup
+    /// runs did not bring caches to a steady state
+    pub warmup_sufficient: bool,
+}
+
+impl OutlierReport {
+    /// Whether the run at `index` is left out of the statistics
+    pub fn excludes(&self, index: usize) -> bool {
+        self.handling == OutlierHandling::Exclude && self.outliers.iter().any(|o| o.run == index)
+    }
+}
+
+/// The items of `runs` kept in the statistics under `report`
+pub fn retained<T: Clone>(runs: &[T], report: Option<&OutlierReport>) -> Vec<T> {
+    runs.iter()
+        .enumerate()
+        .filter(|(i, _)| !report.is_some_and(|r| r.excludes(*i)))
+        .map(|(_, run)| run.clone())
+        .collect()
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Find runs whose execution time is more than `threshold` scaled MADs from the median
+///
+/// `disk_reads` holds the blocks each run read from disk, when known, and is
+/// used to tell a cold cache apart from other interference.
+pub fn detect(
+    execution_times: &[Duration],
+    disk_reads: &[Option<u64>],
+    handling: OutlierHandling,
+    threshold: f64,
+) -> Option<OutlierReport> {
+    if handling == OutlierHandling::Off {
+        return None;
+    }
+
+    let mut outliers = Vec::new();
+    if execution_times.len() >= MIN_RUNS {
+        let times: Vec<f64> = execution_times.iter().map(Duration::as_secs_f64).collect();
+        let center = median(&mut times.clone());
+        let mut deviations: Vec<f64> = times.iter().map(|t| (t - center).abs()).collect();
+        let mad = median(&mut deviations);
+        let scale = if mad > 0.0 {
+            mad / MAD_SCALE
+        } else {
+            deviations.iter().sum::<f64>() / deviations.len() as f64 / MEAN_AD_SCALE
+        };
+
+        let mut reads: Vec<f64> = disk_reads.iter().flatten().map(|r| *r as f64).collect();
+        let median_reads = (!reads.is_empty()).then(|| median(&mut reads));
+
+        if scale > 0.0 {
+            for (run, time) in times.iter().enumerate() {
+                let modified_z_score = (time - center) / scale;
+                if modified_z_score.abs() <= threshold {
+                    continue;
+                }
+                let ratio = if center > 0.0 { time / center } else { 0.0 };
+                let run_reads = disk_reads.get(run).copied().flatten();
+                let reason = match (run_reads, median_reads) {
+                    (Some(read), Some(typical))
+                        if modified_z_score > 0.0 && read as f64 > typical =>
+                    {
+                        format!(
+                            "{:.1}x the median time and read {} blocks from disk where the \
+                             median run read {}; likely a cold cache",
+                            ratio, read, typical
+                        )
+                    }
+                    _ if modified_z_score > 0.0 => format!(
+                        "{:.1}x the median time; possibly a checkpoint, autovacuum or \
+                         other load on the server",
+                        ratio
+                    ),
+                    _ => format!("{:.1}x the median time; faster than the other runs", ratio),
+                };
+                outliers.push(Outlier {
+                    run,
+                    execution_time: execution_times[run],
+                    modified_z_score,
+                    reason,
+                });
+            }
+        }
+    }
+
+    let warmup_sufficient = !outliers
+        .iter()
+        .any(|o| o.run == 0 && o.modified_z_score > 0.0);
+    let excluded_runs = match handling {
+        OutlierHandling::Exclude => outliers.len() as u32,
+        _ => 0,
+    };
+    Some(OutlierReport {
+        handling,
+        threshold,
+        outliers,
+        excluded_runs,
+        warmup_sufficient,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(values: &[u64]) -> Vec<Duration> {
+        values.iter().map(|ms| Duration::from_millis(*ms)).collect()
+    }
+
+    #[test]
+    fn test_detect_outliers() {
+        let times = millis(&[100, 102, 98, 101, 450, 99]);
+        let reads = [Some(0), Some(0), Some(0), Some(0), Some(0), Some(0)];
+        let report = detect(&times, &reads, OutlierHandling::Exclude, 3.5).unwrap();
+        assert_eq!(report.outliers.len(), 1);
+        assert_eq!(report.outliers[0].run, 4);
+        assert!(report.outliers[0].reason.contains("checkpoint"));
+        assert_eq!(report.excluded_runs, 1);
+        assert!(report.warmup_sufficient);
+
+        let kept = retained(&times, Some(&report));
+        assert_eq!(kept.len(), 5);
+        assert!(!kept.contains(&Duration::from_millis(450)));
+
+        let flagged = detect(&times, &reads, OutlierHandling::Flag, 3.5).unwrap();
+        assert_eq!(flagged.excluded_runs, 0);
+        assert_eq!(retained(&times, Some(&flagged)).len(), 6);
+
+        assert!(detect(&times, &reads, OutlierHandling::Off, 3.5).is_none());
+    }
+
+    #[test]
+    fn test_cold_first_run() {
+        let times = millis(&[900, 100, 100, 100, 100]);
+        let reads = [Some(5000), Some(0), Some(0), Some(0), Some(0)];
+        let report = detect(&times, &reads, OutlierHandling::Flag, 3.5).unwrap();
+        assert_eq!(report.outliers.len(), 1);
+        assert!(report.outliers[0].reason.contains("cold cache"));
+        assert!(!report.warmup_sufficient);
+
+        // Identical runs have no outliers
+        let steady = millis(&[100, 100, 100]);
+        let report = detect(&steady, &[], OutlierHandling::Flag, 3.5).unwrap();
+        assert!(report.outliers.is_empty());
+    }
+}
@@ -0,0 +1,340 @@
+//! Benchmarks of the literal variants of one query
+//!
+//! Queries that differ only in their literals share a fingerprint, but not
+//! necessarily a performance profile: the customer with a million orders
+//! gets a different plan from the one with ten. Benchmarking a sample of the
+//! variants recorded in history answers whether a query is slow in general
+//! or only for some values.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::BenchmarkSuite;
+use crate::storage::findings;
+use crate::storage::HistoryEntry;
+
+/// A variant counts as slow when its average time is this many times the group median
+const SLOW_FACTOR: f64 = 2.0;
+
+/// A literal variant of a query found in history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Variant {
+    /// The SQL text
+    pub query: String,
+    /// Execution time of its most recent analysis, in milliseconds
+    pub recorded_time_ms: f64,
+    /// Id of its most recent history entry
+    pub history_id: i64,
+}
+
+/// Benchmark of one variant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantResult {
+    /// The variant that was benchmarked
+    pub variant: Variant,
+    /// Average execution time; `None` if the benchmark failed
+    pub avg_execution_time: Option<Duration>,
+    /// 95th percentile execution time; `None` if the benchmark failed
+    pub p95_execution_time: Option<Duration>,
+    /// Average plan cost, when plans were collected
+    pub avg_cost: Option<f64>,
+    /// Average time relative to the median of the group
+    pub relative_to_median: Option<f64>,
+    /// Why the benchmark failed
+    pub error: Option<String>,
+}
+
+/// Whether a query group performs alike across its variants
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupVerdict {
+    /// All variants take about the same time
+    Uniform,
+    /// Some variants are much slower than the rest
+    Skewed,
+    /// Fewer than two variants could be benchmarked
+    Inconclusive,
+}
+
+/// Benchmarks of a sample of a query group's variants
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryGroupReport {
+    /// Fingerprint shared by the variants
+    pub fingerprint: String,
+    /// Distinct variants found in history
+    pub variants_found: usize,
+    /// Benchmarks of the sampled variants, fastest recorded time first
+    pub variants: Vec<VariantResult>,
+    /// Median of the variants' average times
+    pub median_execution_time: Option<Duration>,
+    /// Slowest variant's average time over the fastest's
+    pub spread: Option<f64>,
+    /// Whether the group performs alike
+    pub verdict: GroupVerdict,
+    /// One-sentence answer for the report
+    pub summary: String,
+}
+
+/// Distinct variants of `fingerprint` among `entries`, in the order of the entries
+///
+/// Entries are expected newest first, so each variant carries its most
+/// recent analysis.
+pub fn collect_variants(entries: &[HistoryEntry], fingerprint: &str) -> Vec<Variant> {
+    let mut variants: Vec<Variant> = Vec::new();
+    for entry in entries {
+        let query = entry.query.trim();
+        if findings::fingerprint(query) != fingerprint || variants.iter().any(|v| v.query == query)
+        {
+            continue;
+        }
+        variants.push(Variant {
+            query: query.to_string(),
+            recorded_time_ms: entry.execution_time_ms,
+            history_id: entry.id,
+        });
+    }
+    variants
+}
+
+/// Up to `size` variants spread evenly over their recorded execution times
+///
+/// The fastest and slowest recorded variants are always included, so a
+/// sample cannot miss the customers a group is slow for.
+pub fn representative_sample(mut variants: Vec<Variant>, size: usize) -> Vec<Variant> {
+    variants.sort_by(|a, b| a.recorded_time_ms.total_cmp(&b.recorded_time_ms));
+    if variants.len() <= size {
+        return variants;
+    }
+    if size <= 1 {
+        variants.truncate(size);
+        return variants;
+    }
+
+    let last = variants.len() - 1;
+    let mut picked: Vec<usize> = (0..size)
+        .map(|i| (i * last + (size - 1) / 2) / (size - 1))
+        .collect();
+    picked.dedup();
+    picked.into_iter().map(|i| variants[i].clone()).collect()
+}
+
+/// Compare the benchmarked variants and judge the group
+pub fn summarize(
+    fingerprint: &str,
+    variants_found: usize,
+    mut variants: Vec<VariantResult>,
+) -> QueryGroupReport {
+    let mut times: Vec<Duration> = variants
+        .iter()
+        .filter_map(|v| v.avg_execution_time)
+        .collect();
+    times.sort();
+
+    let median = match times.len() {
+        0 => None,
+        n if n.is_multiple_of(2) => Some((times[n / 2 - 1] + times[n / 2]) / 2),
+        n => Some(times[n / 2]),
+    };
+    let spread = match (times.first(), times.last()) {
+        (Some(fastest), Some(slowest)) if times.len() > 1 && !fastest.is_zero() => {
+            Some(slowest.as_secs_f64() / fastest.as_secs_f64())
+        }
+        _ => None,
+    };
+
+    if let Some(median) = median.filter(|m| !m.is_zero()) {
+        for variant in &mut variants {
+            variant.relative_to_median = variant
+                .avg_execution_time
+                .map(|t| t.as_secs_f64() / median.as_secs_f64());
+        }
+    }
+    let slow = variants
+        .iter()
+        .filter(|v| v.relative_to_median.is_some_and(|r| r >= SLOW_FACTOR))
+        .count();
+
+    let (verdict, summary) = if times.len() < 2 {
+        (
+            GroupVerdict::Inconclusive,
+            format!(
+                "Only {} of {} sampled variants could be benchmarked; at least 2 are needed to \
+                 compare them",
+                times.len(),
+                variants.len()
+            ),
+        )
+    } else if slow > 0 {
+        (
+            GroupVerdict::Skewed,
+            format!(
+                "{} of {} variants take at least {}x the median time; the query is slow only \
+                 for some values",
+                slow,
+                times.len(),
+                SLOW_FACTOR
+            ),
+        )
+    } else {
+        (
+            GroupVerdict::Uniform,
+            format!(
+                "All {} variants take within {}x of the median time; the query performs alike \
+                 for all values",
+                times.len(),
+                SLOW_FACTOR
+            ),
+        )
+    };
+
+    QueryGroupReport {
+        fingerprint: fingerprint.to_string(),
+        variants_found,
+        variants,
+        median_execution_time: median,
+        spread,
+        verdict,
+        summary,
+    }
+}
+
+impl BenchmarkSuite {
+    /// Benchmark each of `variants` and compare them
+    ///
+    /// A variant whose benchmark fails is reported with its error rather
+    /// than failing the whole group.
+    pub async fn benchmark_group(
+        &self,
+        fingerprint: &str,
+        variants_found: usize,
+        variants: Vec<Variant>,
+    ) -> QueryGroupReport {
+        let mut results = Vec::with_capacity(variants.len());
+        for variant in variants {
+            let result = match self.benchmark_query(&variant.query).await {
+                Ok(result) => VariantResult {
+                    variant,
+                    avg_execution_time: Some(result.statistics.avg_execution_time),
+                    p95_execution_time: Some(result.statistics.p95_execution_time),
+                    avg_cost: result.statistics.avg_cost,
+                    relative_to_median: None,
+                    error: None,
+                },
+                Err(e) => VariantResult {
+                    variant,
+                    avg_execution_time: None,
+                    p95_execution_time: None,
+                    avg_cost: None,
+                    relative_to_median: None,
+                    error: Some(e.to_string()),
+                },
+            };
+            results.push(result);
+        }
+        summarize(fingerprint, variants_found, results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(id: i64, query: &str, execution_time_ms: f64) -> HistoryEntry {
+        HistoryEntry {
+            id,
+            query: query.to_string(),
+            plan: json!({}),
+            analysis: None,
+            performance_score: None,
+            total_cost: 0.0,
+            execution_time_ms,
+            trace_id: None,
+            correlation_id: None,
+            table_definitions: None,
+            connection: None,
+            created_at: id,
+        }
+    }
+
+    fn variant(query: &str, recorded_time_ms: f64) -> Variant {
+        Variant {
+            query: query.to_string(),
+            recorded_time_ms,
+            history_id: 0,
+        }
+    }
+
+    fn benchmarked(ms: Option<u64>) -> VariantResult {
+        VariantResult {
+            variant: variant("SELECT 1", 0.0),
+            avg_execution_time: ms.map(Duration::from_millis),
+            p95_execution_time: None,
+            avg_cost: None,
+            relative_to_median: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_collect_variants() {
+        let entries = [
+            entry(4, "SELECT * FROM orders WHERE customer_id = 7", 900.0),
+            entry(3, "SELECT * FROM users WHERE id = 7", 1.0),
+            entry(2, "SELECT * FROM orders WHERE customer_id = 7", 850.0),
+            entry(1, "SELECT *  FROM orders WHERE customer_id = 12", 3.0),
+        ];
+        let group = findings::fingerprint("SELECT * FROM orders WHERE customer_id = 1");
+        let variants = collect_variants(&entries, &group);
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[0].history_id, 4);
+        assert_eq!(variants[0].recorded_time_ms, 900.0);
+        assert_eq!(
+            variants[1].query,
+            "SELECT *  FROM orders WHERE customer_id = 12"
+        );
+    }
+
+    #[test]
+    fn test_representative_sample() {
+        let variants: Vec<Variant> = (0..10)
+            .rev()
+            .map(|i| variant(&format!("SELECT {}", i), i as f64))
+            .collect();
+        let sample = representative_sample(variants.clone(), 3);
+        let times: Vec<f64> = sample.iter().map(|v| v.recorded_time_ms).collect();
+        assert_eq!(times, [0.0, 5.0, 9.0]);
+
+        assert_eq!(representative_sample(variants.clone(), 20).len(), 10);
+        assert_eq!(representative_sample(variants, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_summarize_group() {
+        let skewed = summarize(
+            "f",
+            6,
+            vec![
+                benchmarked(Some(10)),
+                benchmarked(Some(11)),
+                benchmarked(Some(12)),
+                benchmarked(Some(95)),
+            ],
+        );
+        assert_eq!(skewed.verdict, GroupVerdict::Skewed);
+        assert_eq!(
+            skewed.median_execution_time,
+            Some(Duration::from_micros(11_500))
+        );
+        assert!((skewed.spread.unwrap() - 9.5).abs() < 1e-9);
+        assert!(skewed.variants[3].relative_to_median.unwrap() > 8.0);
+
+        let uniform = summarize("f", 3, vec![benchmarked(Some(10)), benchmarked(Some(14))]);
+        assert_eq!(uniform.verdict, GroupVerdict::Uniform);
+
+        let failed = summarize("f", 2, vec![benchmarked(Some(10)), benchmarked(None)]);
+        assert_eq!(failed.verdict, GroupVerdict::Inconclusive);
+        assert!(failed.spread.is_none());
+    }
+}
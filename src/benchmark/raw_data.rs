@@ -0,0 +1,303 @@
+//! Per-run benchmark data in columnar form
+//!
+//! The statistics of a benchmark summarize its runs; anyone who wants to
+//! fit their own distribution or plot the runs over time needs the runs
+//! themselves. [`RunTable`] lays them out one column per measurement, which
+//! loads directly into a pandas or R data frame as JSON or CSV.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::ser::{SerializeMap, Serializer};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::db::models::{BufferUsage, ExecutionPlan};
+
+use super::{BenchmarkResult, OutlierReport, TransactionBenchmarkResult};
+
+/// Reads one count out of a node's buffer usage
+type BufferCounter = fn(&BufferUsage) -> u64;
+
+/// Buffer counters of the plan root, which include those of every node below it
+const BUFFER_COUNTERS: [(&str, BufferCounter); 6] = [
+    ("shared_hit_blocks", |b| b.shared_hit),
+    ("shared_read_blocks", |b| b.shared_read),
+    ("shared_dirtied_blocks", |b| b.shared_dirtied),
+    ("shared_written_blocks", |b| b.shared_written),
+    ("temp_read_blocks", |b| b.temp_read),
+    ("temp_written_blocks", |b| b.temp_written),
+];
+
+/// One column of a [`RunTable`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    /// Column name
+    pub name: String,
+    /// One value per run; `null` where the run did not record it
+    pub values: Vec<Value>,
+}
+
+/// The runs of a benchmark, one column per measurement
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunTable {
+    /// Columns in output order, all of the same length
+    pub columns: Vec<Column>,
+}
+
+/// Serializes as an object of column name to values, in column order
+impl Serialize for RunTable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for column in &self.columns {
+            map.serialize_entry(&column.name, &column.values)?;
+        }
+        map.end()
+    }
+}
+
+impl RunTable {
+    fn push<T: Into<Value>>(&mut self, name: &str, values: impl IntoIterator<Item = T>) {
+        self.columns.push(Column {
+            name: name.to_string(),
+            values: values.into_iter().map(Into::into).collect(),
+        });
+    }
+
+    /// Number of runs
+    pub fn row_count(&self) -> usize {
+        self.columns.first().map_or(0, |c| c.values.len())
+    }
+
+    /// The runs of a query benchmark
+    ///
+    /// Plan columns are `null` for runs made without execution plans.
+    pub fn from_query(result: &BenchmarkResult) -> Self {
+        let runs = &result.runs;
+        let plans: Vec<Option<&ExecutionPlan>> =
+            runs.iter().map(|r| r.execution_plan.as_ref()).collect();
+
+        let mut table = Self::default();
+        table.push("run", 0..runs.len() as u64);
+        table.push(
+            "timestamp_ms",
+            runs.iter().map(|r| epoch_millis(r.timestamp)),
+        );
+        table.push(
+            "execution_time_ms",
+            runs.iter().map(|r| millis(r.execution_time)),
+        );
+        table.push(
+            "outlier",
+            outlier_flags(runs.len(), result.statistics.outliers.as_ref()),
+        );
+        table.push("plan_hash", plans.iter().map(|p| p.map(plan_hash)));
+        table.push(
+            "planning_time_ms",
+            plans.iter().map(|p| p.map(|p| p.planning_time)),
+        );
+        table.push(
+            "plan_execution_time_ms",
+            plans.iter().map(|p| p.map(|p| p.execution_time)),
+        );
+        table.push(
+            "total_cost",
+            plans.iter().map(|p| p.map(|p| p.root.total_cost)),
+        );
+        for (name, counter) in BUFFER_COUNTERS {
+            table.push(
+                name,
+                plans
+                    .iter()
+                    .map(|p| p.and_then(|p| p.root.buffers.as_ref().map(counter))),
+            );
+        }
+        table
+    }
+
+    /// The runs of a transaction benchmark, with one column per statement
+    pub fn from_transaction(result: &TransactionBenchmarkResult) -> Self {
+        let runs = &result.runs;
+
+        let mut table = Self::default();
+        table.push("run", 0..runs.len() as u64);
+        table.push(
+            "timestamp_ms",
+            runs.iter().map(|r| epoch_millis(r.timestamp)),
+        );
+        table.push(
+            "execution_time_ms",
+            runs.iter().map(|r| millis(r.execution_time)),
+        );
+        table.push(
+            "outlier",
+            outlier_flags(runs.len(), result.statistics.outliers.as_ref()),
+        );
+        for i in 0..result.statements.len() {
+            table.push(
+                &format!("statement_{}_ms", i + 1),
+                runs.iter()
+                    .map(|r| r.statements.get(i).map(|s| millis(s.execution_time))),
+            );
+            table.push(
+                &format!("statement_{}_rows", i + 1),
+                runs.iter().map(|r| r.statements.get(i).map(|s| s.rows)),
+            );
+        }
+        table
+    }
+
+    /// The table as CSV with a header row; missing values are left empty
+    pub fn to_csv(&self) -> String {
+        let mut csv = self
+            .columns
+            .iter()
+            .map(|c| csv_field(&c.name))
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push('\n');
+        for row in 0..self.row_count() {
+            let fields: Vec<String> = self
+                .columns
+                .iter()
+                .map(|c| match c.values.get(row) {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(s)) => csv_field(s),
+                    Some(value) => value.to_string(),
+                })
+                .collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// Hash of the plan's shape: node types and the relations they read, in order
+///
+/// Runs with the same hash used the same plan, whatever their costs and
+/// timings, so a change of hash between runs shows the planner switched plans.
+pub fn plan_hash(plan: &ExecutionPlan) -> String {
+    let mut hasher = DefaultHasher::new();
+    for node in plan.root.iter() {
+        node.node_type.hash(&mut hasher);
+        node.relation_name.hash(&mut hasher);
+        node.plans.len().hash(&mut hasher);
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+fn outlier_flags(runs: usize, report: Option<&OutlierReport>) -> Vec<bool> {
+    (0..runs)
+        .map(|i| report.is_some_and(|r| r.outliers.iter().any(|o| o.run == i)))
+        .collect()
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn epoch_millis(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_millis() as u64)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::benchmark::outliers::{self, OutlierHandling};
+    use crate::benchmark::{BenchmarkConfig, BenchmarkRun, BenchmarkStatistics};
+    use serde_json::json;
+
+    fn plan(root: Value) -> ExecutionPlan {
+        ExecutionPlan {
+            root: serde_json::from_value(root).unwrap(),
+            planning_time: 0.2,
+            execution_time: 1.5,
+            settings: None,
+            jit: None,
+        }
+    }
+
+    fn run(ms: u64, execution_plan: Option<ExecutionPlan>) -> BenchmarkRun {
+        BenchmarkRun {
+            execution_time: Duration::from_millis(ms),
+            execution_plan,
+            advisor_analysis: None,
+            timestamp: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        }
+    }
+
+    #[test]
+    fn test_run_table_from_query() {
+        let seq_scan = plan(json!({
+            "Node Type": "Seq Scan", "Relation Name": "orders",
+            "Startup Cost": 0.0, "Total Cost": 10.0, "Plan Rows": 100, "Plan Width": 8,
+            "Shared Hit Blocks": 4, "Shared Read Blocks": 1
+        }));
+        let index_scan = plan(json!({
+            "Node Type": "Index Scan", "Relation Name": "orders",
+            "Startup Cost": 0.0, "Total Cost": 2.0, "Plan Rows": 1, "Plan Width": 8
+        }));
+        let times = [100, 101, 99, 400].map(Duration::from_millis);
+        let result = BenchmarkResult {
+            query: "SELECT * FROM orders".to_string(),
+            runs: vec![
+                run(100, Some(seq_scan.clone())),
+                run(101, Some(seq_scan)),
+                run(99, Some(index_scan)),
+                run(400, None),
+            ],
+            statistics: BenchmarkStatistics {
+                avg_execution_time: Duration::from_millis(175),
+                min_execution_time: times[2],
+                max_execution_time: times[3],
+                std_deviation: Duration::ZERO,
+                p95_execution_time: times[3],
+                successful_runs: 4,
+                failed_runs: 0,
+                avg_cost: None,
+                avg_advisor_score: None,
+                outliers: outliers::detect(&times, &[], OutlierHandling::Flag, 3.5),
+            },
+            config: BenchmarkConfig::default(),
+            background_activity: None,
+            baseline: None,
+            settings: None,
+        };
+
+        let table = RunTable::from_query(&result);
+        assert_eq!(table.row_count(), 4);
+        let value = serde_json::to_value(&table).unwrap();
+        assert_eq!(value["execution_time_ms"][1], 101.0);
+        assert_eq!(value["timestamp_ms"][0], 1_700_000_000_000u64);
+        assert_eq!(value["outlier"], json!([false, false, false, true]));
+        assert_eq!(value["shared_hit_blocks"], json!([4, 4, null, null]));
+        assert_eq!(value["plan_hash"][0], value["plan_hash"][1]);
+        assert_ne!(value["plan_hash"][0], value["plan_hash"][2]);
+        assert!(value["plan_hash"][3].is_null());
+
+        let csv = table.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("run,timestamp_ms,execution_time_ms,outlier,plan_hash,"));
+        assert!(lines[4].starts_with("3,1700000000000,400.0,true,,"));
+    }
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,177 @@
+//! Before/after reports for query rewrites
+//!
+//! A rewrite is judged on three things at once: what the planner does with
+//! it, which advisor findings it resolves or introduces, and how fast it
+//! actually runs. [`BenchmarkSuite::compare_rewrite`] gathers all three into
+//! one report so a reviewer does not have to line up separate explain and
+//! benchmark results by hand.
+
+use serde::{Deserialize, Serialize};
+
+use super::{BenchmarkComparison, BenchmarkSuite};
+use crate::advisor::{AdvisorAnalysis, OptimizationSuggestion};
+use crate::db::models::ExecutionPlan;
+use crate::db::plan_diff::{self, PlanDiff};
+use crate::storage::findings::finding_key;
+use crate::SqlTraceError;
+
+/// Plan and advisor output of one side of a rewrite
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RewriteSide {
+    /// The SQL text
+    pub query: String,
+    /// Execution plan from `EXPLAIN ANALYZE`
+    pub plan: ExecutionPlan,
+    /// Advisor output for the plan
+    pub analysis: AdvisorAnalysis,
+}
+
+/// Advisor findings of the original query compared with those of the rewrite
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FindingsDiff {
+    /// Reported for the original query but not for the rewrite
+    pub fixed: Vec<OptimizationSuggestion>,
+    /// Reported for the rewrite but not for the original query
+    pub introduced: Vec<OptimizationSuggestion>,
+    /// Reported for both, as reported for the rewrite
+    pub unchanged: Vec<OptimizationSuggestion>,
+}
+
+/// What a rewrite changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RewriteReport {
+    /// The original query
+    pub before: RewriteSide,
+    /// The rewritten query
+    pub after: RewriteSide,
+    /// Differences between the two plans
+    pub plan_diff: PlanDiff,
+    /// Advisor findings fixed and introduced by the rewrite
+    pub findings: FindingsDiff,
+    /// Advisor score of the rewrite minus that of the original query
+    pub score_change: i16,
+    /// Benchmark of both queries, labelled "before" and "after", when requested
+    pub benchmark: Option<BenchmarkComparison>,
+}
+
+/// Sort advisor suggestions into fixed, introduced and unchanged findings
+///
+/// Suggestions are matched the same way stored findings are, by rule and title.
+pub fn diff_findings(
+    before: &[OptimizationSuggestion],
+    after: &[OptimizationSuggestion],
+) -> FindingsDiff {
+    let keys_before: Vec<String> = before.iter().map(finding_key).collect();
+    let keys_after: Vec<String> = after.iter().map(finding_key).collect();
+
+    let mut diff = FindingsDiff::default();
+    for (suggestion, key) in before.iter().zip(&keys_before) {
+        if !keys_after.contains(key) {
+            diff.fixed.push(suggestion.clone());
+        }
+    }
+    for (suggestion, key) in after.iter().zip(&keys_after) {
+        if keys_before.contains(key) {
+            diff.unchanged.push(suggestion.clone());
+        } else {
+            diff.introduced.push(suggestion.clone());
+        }
+    }
+    diff
+}
+
+impl BenchmarkSuite {
+    /// Explain both queries, compare their plans and advisor findings, and optionally benchmark them
+    pub async fn compare_rewrite(
+        &self,
+        before: &str,
+        after: &str,
+        benchmark: bool,
+    ) -> Result<RewriteReport, SqlTraceError> {
+        let before = self.explain_side(before).await?;
+        let after = self.explain_side(after).await?;
+
+        let benchmark = if benchmark {
+            let result_before = self.benchmark_query(&before.query).await?;
+            let result_after = self.benchmark_query(&after.query).await?;
+            Some(self.compare_benchmarks(
+                &result_before,
+                &result_after,
+                "before".to_string(),
+                "after".to_string(),
+            ))
+        } else {
+            None
+        };
+
+        Ok(RewriteReport {
+            plan_diff: plan_diff::diff_plans(&before.plan, &after.plan),
+            findings: diff_findings(&before.analysis.suggestions, &after.analysis.suggestions),
+            score_change: i16::from(after.analysis.performance_score)
+                - i16::from(before.analysis.performance_score),
+            benchmark,
+            before,
+            after,
+        })
+    }
+
+    async fn explain_side(&self, query: &str) -> Result<RewriteSide, SqlTraceError> {
+        let timeout = std::time::Duration::from_secs(self.config.timeout_seconds.max(1));
+        let plan = tokio::time::timeout(timeout, self.db.explain(query))
+            .await
+            .map_err(|_| {
+                SqlTraceError::Database(format!(
+                    "Explaining the query exceeded the {}s timeout",
+                    timeout.as_secs()
+                ))
+            })??;
+        let analysis = self
+            .advisor
+            .analyze_plan_with_context(&plan, &self.analysis_context());
+        Ok(RewriteSide {
+            query: query.to_string(),
+            plan,
+            analysis,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advisor::Severity;
+
+    fn suggestion(rule_id: &str, title: &str) -> OptimizationSuggestion {
+        OptimizationSuggestion {
+            rule_id: rule_id.to_string(),
+            suggestion_type: "Index".to_string(),
+            severity: Severity::Medium,
+            title: title.to_string(),
+            description: String::new(),
+            recommendation: String::new(),
+            node_index: None,
+            impact: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_diff_findings() {
+        let scan = suggestion("expensive_seq_scan", "Expensive Sequential Scan Detected");
+        let sort = suggestion("large_sort", "Large Sort Operation");
+        let nested = suggestion("nested_loop", "Nested Loop with Many Rows");
+
+        let diff = diff_findings(&[scan.clone(), sort.clone()], &[sort, nested]);
+        let rules = |suggestions: &[OptimizationSuggestion]| {
+            suggestions
+                .iter()
+                .map(|s| s.rule_id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rules(&diff.fixed), ["expensive_seq_scan"]);
+        assert_eq!(rules(&diff.introduced), ["nested_loop"]);
+        assert_eq!(rules(&diff.unchanged), ["large_sort"]);
+
+        let diff = diff_findings(std::slice::from_ref(&scan), std::slice::from_ref(&scan));
+        assert!(diff.fixed.is_empty() && diff.introduced.is_empty());
+    }
+}
@@ -0,0 +1,191 @@
+//! Daily execution budgets
+//!
+//! Explaining with ANALYZE runs the query, so a busy SQLTrace instance adds
+//! real load to the database it points at, often a shared replica. Budgets
+//! cap how much execution time and how many plan rows each user may spend per
+//! day (UTC). A request is admitted while the user is under every limit, and
+//! its cost is charged once its plans come back, so the last admitted request
+//! may overshoot a limit; the next one is rejected.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Milliseconds in a day
+const DAY_MILLIS: i64 = 86_400_000;
+
+/// Per-user daily limits; unset limits are not enforced
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Execution time of analyzed queries, in milliseconds
+    pub max_execution_ms_per_day: Option<f64>,
+    /// Rows produced by the nodes of analyzed plans
+    pub max_rows_per_day: Option<u64>,
+}
+
+impl BudgetConfig {
+    /// Whether any limit is set
+    pub fn is_enabled(&self) -> bool {
+        self.max_execution_ms_per_day.is_some() || self.max_rows_per_day.is_some()
+    }
+}
+
+/// What a user has spent on one day
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BudgetUsage {
+    /// Day the usage belongs to, in days since the Unix epoch (UTC)
+    pub day: i64,
+    /// Execution time of analyzed queries, in milliseconds
+    pub execution_ms: f64,
+    /// Rows produced by the nodes of analyzed plans
+    pub rows: u64,
+}
+
+/// A request rejected because its user has used up a daily limit
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetExceeded {
+    /// Which limit was reached
+    pub limit: &'static str,
+    /// The user's usage today
+    pub usage: BudgetUsage,
+}
+
+impl std::fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Daily {} budget exhausted ({:.0} ms and {} rows used today)",
+            self.limit, self.usage.execution_ms, self.usage.rows
+        )
+    }
+}
+
+/// In-memory accounting of budget usage per user
+///
+/// Usage is not persisted, so a restart gives everybody a fresh budget.
+#[derive(Debug, Default)]
+pub struct BudgetTracker {
+    config: BudgetConfig,
+    usage: Mutex<HashMap<String, BudgetUsage>>,
+}
+
+impl BudgetTracker {
+    /// Create a tracker enforcing `config`
+    pub fn new(config: BudgetConfig) -> Self {
+        Self {
+            config,
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The configured limits
+    pub fn config(&self) -> &BudgetConfig {
+        &self.config
+    }
+
+    /// What `user` has spent on the day of `now` (ms since epoch)
+    pub fn usage(&self, user: &str, now: i64) -> BudgetUsage {
+        let day = day_of(now);
+        let usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        match usage.get(user) {
+            Some(spent) if spent.day == day => spent.clone(),
+            _ => BudgetUsage {
+                day,
+                ..BudgetUsage::default()
+            },
+        }
+    }
+
+    /// Admit a request of `user` unless a daily limit has been reached
+    pub fn check(&self, user: &str, now: i64) -> Result<(), BudgetExceeded> {
+        if !self.config.is_enabled() {
+            return Ok(());
+        }
+        let usage = self.usage(user, now);
+        if self
+            .config
+            .max_execution_ms_per_day
+            .is_some_and(|max| usage.execution_ms >= max)
+        {
+            return Err(BudgetExceeded {
+                limit: "execution time",
+                usage,
+            });
+        }
+        if self
+            .config
+            .max_rows_per_day
+            .is_some_and(|max| usage.rows >= max)
+        {
+            return Err(BudgetExceeded {
+                limit: "rows",
+                usage,
+            });
+        }
+        Ok(())
+    }
+
+    /// Charge `execution_ms` and `rows` to `user`
+    pub fn record(&self, user: &str, now: i64, execution_ms: f64, rows: u64) {
+        if !self.config.is_enabled() {
+            return;
+        }
+        let day = day_of(now);
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        // Earlier days no longer count against anybody
+        usage.retain(|_, spent| spent.day == day);
+        let spent = usage.entry(user.to_string()).or_insert(BudgetUsage {
+            day,
+            ..BudgetUsage::default()
+        });
+        spent.execution_ms += execution_ms.max(0.0);
+        spent.rows = spent.rows.saturating_add(rows);
+    }
+}
+
+fn day_of(millis: i64) -> i64 {
+    millis.div_euclid(DAY_MILLIS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOON: i64 = 20_000 * DAY_MILLIS + DAY_MILLIS / 2;
+
+    #[test]
+    fn test_budget_disabled_by_default() {
+        let tracker = BudgetTracker::new(BudgetConfig::default());
+        tracker.record("alice", NOON, 1e9, u64::MAX);
+        assert!(tracker.check("alice", NOON).is_ok());
+        assert_eq!(tracker.usage("alice", NOON).rows, 0);
+    }
+
+    #[test]
+    fn test_budget_per_user_and_day() {
+        let tracker = BudgetTracker::new(BudgetConfig {
+            max_execution_ms_per_day: Some(1000.0),
+            max_rows_per_day: Some(500),
+        });
+
+        tracker.record("alice", NOON, 600.0, 100);
+        assert!(tracker.check("alice", NOON).is_ok());
+        tracker.record("alice", NOON, 600.0, 100);
+        let exceeded = tracker.check("alice", NOON).unwrap_err();
+        assert_eq!(exceeded.limit, "execution time");
+        assert_eq!(exceeded.usage.rows, 200);
+
+        // Other users have their own budget
+        assert!(tracker.check("bob", NOON).is_ok());
+        tracker.record("bob", NOON, 1.0, 500);
+        assert_eq!(tracker.check("bob", NOON).unwrap_err().limit, "rows");
+
+        // The budget resets at midnight UTC
+        let tomorrow = NOON + DAY_MILLIS / 2;
+        assert!(tracker.check("alice", tomorrow).is_ok());
+        tracker.record("alice", tomorrow, 1.0, 1);
+        assert_eq!(tracker.usage("alice", tomorrow).execution_ms, 1.0);
+        assert_eq!(tracker.usage("bob", tomorrow).rows, 0);
+    }
+}
@@ -0,0 +1,157 @@
+//! Capture of the raw EXPLAIN output behind analyzed plans
+//!
+//! SQLTrace parses EXPLAIN output into its own plan model and drops the
+//! fields it doesn't map. When a plan looks wrong, the raw output is what
+//! tells a parsing problem from a planner one. Capture is off by default;
+//! when enabled, the raw JSON of each analyzed plan is written to a directory
+//! that keeps only the most recent files, retained with the response and the
+//! history entry, or both.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::masking::MaskingConfig;
+use crate::storage::now_millis;
+
+/// Prefix of captured plan file names, so rotation leaves other files alone
+const FILE_PREFIX: &str = "plan-";
+
+/// Raw plan capture settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlanCaptureConfig {
+    /// Directory to write each raw plan to as a JSON file; nothing is written if not set
+    pub directory: Option<PathBuf>,
+    /// Captured files kept in `directory`, the oldest being removed first; 0 keeps all
+    pub max_files: usize,
+    /// Return the raw plan in explain responses and store it with the history entry
+    pub retain: bool,
+}
+
+impl Default for PlanCaptureConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            max_files: 100,
+            retain: false,
+        }
+    }
+}
+
+impl PlanCaptureConfig {
+    /// Whether raw plans are captured at all
+    pub fn is_enabled(&self) -> bool {
+        self.directory.is_some() || self.retain
+    }
+
+    /// Capture `raw`, returning it if it is to be retained
+    ///
+    /// Like stored history, the file written is masked while the plan
+    /// returned is not. Capture is best-effort: a file that can't be written
+    /// is logged and never fails the analysis.
+    pub async fn capture(&self, raw: Value, masking: &MaskingConfig) -> Option<Value> {
+        if let Some(directory) = &self.directory {
+            let mut masked = raw.clone();
+            masking.plan(&mut masked);
+            match write_capture(directory, &masked, self.max_files).await {
+                Ok(path) => tracing::debug!("Captured raw plan to {}", path.display()),
+                Err(e) => tracing::warn!(
+                    "Failed to capture raw plan to {}: {}",
+                    directory.display(),
+                    e
+                ),
+            }
+        }
+        self.retain.then_some(raw)
+    }
+}
+
+/// Write `raw` to a new file in `directory`, then remove the oldest files beyond `max_files`
+async fn write_capture(
+    directory: &Path,
+    raw: &Value,
+    max_files: usize,
+) -> std::io::Result<PathBuf> {
+    tokio::fs::create_dir_all(directory).await?;
+    // Fixed-width millisecond timestamps sort by name in capture order
+    let path = directory.join(format!(
+        "{}{:015}-{}.json",
+        FILE_PREFIX,
+        now_millis(),
+        uuid::Uuid::new_v4().simple()
+    ));
+    let json = serde_json::to_vec_pretty(raw).map_err(std::io::Error::other)?;
+    tokio::fs::write(&path, json).await?;
+
+    if max_files > 0 {
+        rotate(directory, max_files).await?;
+    }
+    Ok(path)
+}
+
+/// Remove the oldest captured files in `directory` beyond `max_files`
+async fn rotate(directory: &Path, max_files: usize) -> std::io::Result<()> {
+    let mut captured = Vec::new();
+    let mut entries = tokio::fs::read_dir(directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(FILE_PREFIX) && name.ends_with(".json") {
+            captured.push(name);
+        }
+    }
+    captured.sort();
+
+    let excess = captured.len().saturating_sub(max_files);
+    for name in &captured[..excess] {
+        tokio::fs::remove_file(directory.join(name)).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_capture_rotates_and_masks() {
+        let directory =
+            std::env::temp_dir().join(format!("sqltrace-capture-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("notes.txt"), "").unwrap();
+
+        let masking = MaskingConfig {
+            mask_literals: true,
+        };
+        let raw = json!([{"Plan": {"Node Type": "Seq Scan", "Filter": "(id = 42)"}}]);
+        assert!(PlanCaptureConfig::default()
+            .capture(raw.clone(), &masking)
+            .await
+            .is_none());
+
+        let config = PlanCaptureConfig {
+            directory: Some(directory.clone()),
+            max_files: 2,
+            retain: true,
+        };
+        for _ in 0..3 {
+            let retained = config.capture(raw.clone(), &masking).await.unwrap();
+            assert_eq!(retained, raw);
+        }
+
+        let mut names: Vec<String> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[0], "notes.txt");
+        let captured: Value =
+            serde_json::from_slice(&std::fs::read(directory.join(&names[1])).unwrap()).unwrap();
+        assert_eq!(captured[0]["Plan"]["Filter"], "(id = ?)");
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,318 @@
+//! Server configuration file
+//!
+//! Settings that are awkward to pass as command-line flags live in a TOML
+//! file given with `--config`. Every section is optional and falls back to
+//! its defaults.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::benchmark::BenchmarkLimits;
+use crate::budget::BudgetConfig;
+use crate::capture::PlanCaptureConfig;
+use crate::db::breaker::BreakerConfig;
+use crate::db::monitor::MonitorConfig;
+use crate::db::preview::PreviewConfig;
+use crate::db::relation_check::RelationCheckConfig;
+use crate::masking::MaskingConfig;
+use crate::policy::QueryPolicy;
+use crate::storage::{FindingsConfig, RetentionPolicy, StorageConfig};
+use crate::telemetry::TelemetryConfig;
+use crate::ui::assets::FrontendConfig;
+use crate::web::probes::HealthConfig;
+use crate::workload::auto_explain::AutoExplainConfig;
+use crate::SqlTraceError;
+
+/// Which endpoints the server exposes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerProfile {
+    /// Every endpoint
+    #[default]
+    Full,
+    /// Only query analysis against the configured connections; endpoints
+    /// that change connections, stored state or settings, run benchmarks or
+    /// return query results are not served
+    ReadOnly,
+}
+
+/// Top-level server configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Which endpoints the server exposes
+    pub profile: ServerProfile,
+    /// Where SQLTrace stores history and saved queries
+    pub storage: StorageConfig,
+    /// Limits on stored history
+    pub retention: RetentionPolicy,
+    /// Default benchmark configuration and per-request maximums
+    pub benchmark: BenchmarkLimits,
+    /// Query policies, keyed by connection name
+    pub policies: HashMap<String, QueryPolicy>,
+    /// Masking of literals in stored queries and logs
+    pub masking: MaskingConfig,
+    /// Relation existence pre-check
+    pub relation_check: RelationCheckConfig,
+    /// Bounded execution for result previews
+    pub preview: PreviewConfig,
+    /// Tracking of advisor findings across analyses
+    pub findings: FindingsConfig,
+    /// Daily execution limits per user
+    pub budget: BudgetConfig,
+    /// Recording of plans from a PostgreSQL log written by auto_explain
+    pub auto_explain: AutoExplainConfig,
+    /// Readiness checks and waiting for the analyzed database at startup
+    pub health: HealthConfig,
+    /// Directory the web UI is served from
+    pub frontend: FrontendConfig,
+    /// Capture of the raw EXPLAIN output of analyzed plans
+    pub plan_capture: PlanCaptureConfig,
+    /// Periodic health checks and reconnection of registered connections
+    pub monitor: MonitorConfig,
+    /// Turning analyses away from connections that stopped answering
+    pub breaker: BreakerConfig,
+    /// Opt-in reporting of advisor rule hit counts
+    pub telemetry: TelemetryConfig,
+}
+
+impl ServerConfig {
+    /// Load configuration from a TOML file
+    pub fn load(path: &Path) -> Result<Self, SqlTraceError> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_toml(&contents).map_err(|e| {
+            SqlTraceError::Config(format!("Invalid config file {}: {}", path.display(), e))
+        })
+    }
+
+    /// Parse configuration from TOML text
+    pub fn from_toml(contents: &str) -> Result<Self, SqlTraceError> {
+        toml::from_str(contents).map_err(|e| SqlTraceError::Config(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::StatementKind;
+
+    #[test]
+    fn test_parse_profile() {
+        let config = ServerConfig::from_toml(
+            r#"
+            profile = "read_only"
+
+            [storage]
+            url = "sqlite://sqltrace.db"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.profile, ServerProfile::ReadOnly);
+        assert_eq!(
+            ServerConfig::from_toml("").unwrap().profile,
+            ServerProfile::Full
+        );
+    }
+
+    #[test]
+    fn test_parse_storage_section() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [storage]
+            url = "postgres://sqltrace@db.internal/sqltrace"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.storage.url,
+            "postgres://sqltrace@db.internal/sqltrace"
+        );
+        assert_eq!(
+            config.storage.max_connections,
+            StorageConfig::default().max_connections
+        );
+
+        let empty = ServerConfig::from_toml("").unwrap();
+        assert_eq!(empty.storage.url, StorageConfig::default().url);
+        assert!(!empty.retention.is_enabled());
+    }
+
+    #[test]
+    fn test_parse_retention_section() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [retention]
+            max_age_days = 30
+            max_size_mb = 512
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.retention.max_age_days, Some(30));
+        assert_eq!(config.retention.max_count, None);
+        assert_eq!(config.retention.max_size_mb, Some(512));
+        assert_eq!(config.retention.prune_interval_secs, 3600);
+    }
+
+    #[test]
+    fn test_parse_findings_section() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [findings.sla_days]
+            high = 2
+            low = 0
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.findings.enabled);
+        assert_eq!(config.findings.sla_days.high, 2);
+        assert_eq!(config.findings.sla_days.medium, 30);
+        assert_eq!(config.findings.sla_days.low, 0);
+    }
+
+    #[test]
+    fn test_parse_budget_section() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [budget]
+            max_execution_ms_per_day = 60000.0
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.budget.is_enabled());
+        assert_eq!(config.budget.max_execution_ms_per_day, Some(60000.0));
+        assert_eq!(config.budget.max_rows_per_day, None);
+        assert!(!ServerConfig::from_toml("").unwrap().budget.is_enabled());
+    }
+
+    #[test]
+    fn test_parse_benchmark_section() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [benchmark]
+            max_benchmark_runs = 20
+            max_concurrent = 1
+            max_queued = 0
+
+            [benchmark.defaults]
+            warmup_runs = 0
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.benchmark.max_benchmark_runs, 20);
+        assert_eq!(config.benchmark.max_concurrent, 1);
+        assert_eq!(config.benchmark.max_queued, 0);
+        assert_eq!(config.benchmark.max_queued_per_user, 8);
+        assert_eq!(config.benchmark.defaults.warmup_runs, 0);
+        assert_eq!(config.benchmark.defaults.benchmark_runs, 5);
+    }
+
+    #[test]
+    fn test_parse_auto_explain_section() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [auto_explain]
+            log_path = "/var/log/postgresql/postgresql.log"
+            connection = "production"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.auto_explain.log_path.as_deref(),
+            Some(Path::new("/var/log/postgresql/postgresql.log"))
+        );
+        assert_eq!(config.auto_explain.connection, "production");
+        assert_eq!(config.auto_explain.poll_interval_secs, 5);
+        assert!(ServerConfig::from_toml("")
+            .unwrap()
+            .auto_explain
+            .log_path
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_health_section() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [health]
+            require_database = false
+            "#,
+        )
+        .unwrap();
+
+        assert!(!config.health.require_database);
+        assert!(config.health.require_storage);
+        assert_eq!(config.health.connect_retry_secs, 5);
+    }
+
+    #[test]
+    fn test_parse_monitor_section() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [monitor]
+            interval_secs = 10
+            reconnect_after_failures = 5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.monitor.interval_secs, 10);
+        assert_eq!(config.monitor.reconnect_after_failures, 5);
+        assert_eq!(config.monitor.timeout_secs, 5);
+        assert_eq!(config.monitor.max_backoff_secs, 300);
+    }
+
+    #[test]
+    fn test_parse_breaker_section() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [breaker]
+            failure_threshold = 3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.breaker.failure_threshold, 3);
+        assert_eq!(config.breaker.probe_interval_secs, 10);
+        assert_eq!(config.breaker.probe_timeout_secs, 5);
+    }
+
+    #[test]
+    fn test_parse_plan_capture_section() {
+        assert!(!ServerConfig::default().plan_capture.is_enabled());
+
+        let config = ServerConfig::from_toml(
+            r#"
+            [plan_capture]
+            directory = "/var/lib/sqltrace/plans"
+            "#,
+        )
+        .unwrap();
+        assert!(config.plan_capture.is_enabled());
+        assert!(!config.plan_capture.retain);
+        assert_eq!(config.plan_capture.max_files, 100);
+    }
+
+    #[test]
+    fn test_parse_policies_section() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [policies.default]
+            allowed_statements = ["select"]
+            denied_relations = ["pii.*"]
+            max_estimated_cost = 100000.0
+            "#,
+        )
+        .unwrap();
+
+        let policy = &config.policies["default"];
+        assert_eq!(policy.allowed_statements, vec![StatementKind::Select]);
+        assert_eq!(policy.denied_relations, vec!["pii.*".to_string()]);
+        assert_eq!(policy.max_estimated_cost, Some(100000.0));
+        assert!(policy.allowed_schemas.is_empty());
+    }
+}
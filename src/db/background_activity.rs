@@ -0,0 +1,200 @@
+//! Autovacuum and checkpoint activity during benchmarks
+//!
+//! A latency spike in the middle of a benchmark is often not the query's
+//! fault: autovacuum may have been working on the same table, or a
+//! checkpoint may have been flushing dirty buffers. Comparing the cumulative
+//! counters of `pg_stat_all_tables` and the checkpointer before and after
+//! the runs shows whether either happened.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// Maintenance counters of one table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableMaintenance {
+    /// Schema of the table
+    pub schema_name: String,
+    /// Table name
+    pub table_name: String,
+    /// Times the table was vacuumed by autovacuum
+    pub autovacuum_count: i64,
+    /// Times the table was analyzed by autovacuum
+    pub autoanalyze_count: i64,
+}
+
+/// Cumulative activity counters at one point in time
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivitySnapshot {
+    /// Counters of the tables of interest
+    pub tables: Vec<TableMaintenance>,
+    /// Checkpoints started so far, timed and requested; `None` if unavailable
+    pub checkpoints: Option<i64>,
+}
+
+/// Background activity between two snapshots
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackgroundActivity {
+    /// Checkpoints started in between; `None` if the counters were unavailable
+    pub checkpoints: Option<i64>,
+    /// Tables autovacuum vacuumed in between
+    pub autovacuumed: Vec<String>,
+    /// Tables autovacuum analyzed in between
+    pub autoanalyzed: Vec<String>,
+    /// Human-readable summary of what may have affected the timings
+    pub notes: Vec<String>,
+}
+
+impl BackgroundActivity {
+    /// Activity that happened between `before` and `after`
+    pub fn between(before: &ActivitySnapshot, after: &ActivitySnapshot) -> Self {
+        let mut activity = Self {
+            checkpoints: before
+                .checkpoints
+                .zip(after.checkpoints)
+                .map(|(before, after)| (after - before).max(0)),
+            ..Self::default()
+        };
+
+        for table in &after.tables {
+            let Some(previous) = before
+                .tables
+                .iter()
+                .find(|t| t.schema_name == table.schema_name && t.table_name == table.table_name)
+            else {
+                continue;
+            };
+            let name = format!("{}.{}", table.schema_name, table.table_name);
+            if table.autovacuum_count > previous.autovacuum_count {
+                activity.autovacuumed.push(name.clone());
+            }
+            if table.autoanalyze_count > previous.autoanalyze_count {
+                activity.autoanalyzed.push(name);
+            }
+        }
+
+        if let Some(checkpoints) = activity.checkpoints.filter(|c| *c > 0) {
+            activity.notes.push(format!(
+                "{} checkpoint(s) started during the benchmark; runs may have competed \
+                 with buffer writes",
+                checkpoints
+            ));
+        }
+        if !activity.autovacuumed.is_empty() {
+            activity.notes.push(format!(
+                "autovacuum vacuumed {} during the benchmark",
+                activity.autovacuumed.join(", ")
+            ));
+        }
+        if !activity.autoanalyzed.is_empty() {
+            activity.notes.push(format!(
+                "autovacuum analyzed {} during the benchmark; plans may have changed",
+                activity.autoanalyzed.join(", ")
+            ));
+        }
+        activity
+    }
+
+    /// Whether nothing was found that could have disturbed the timings
+    pub fn is_quiet(&self) -> bool {
+        self.notes.is_empty()
+    }
+}
+
+const TABLE_MAINTENANCE_QUERY: &str = r#"
+SELECT schemaname AS schema_name,
+       relname AS table_name,
+       autovacuum_count,
+       autoanalyze_count
+  FROM pg_stat_all_tables
+ WHERE relname = ANY ($1)
+"#;
+
+/// PostgreSQL 17 moved the checkpoint counters out of `pg_stat_bgwriter`
+const CHECKPOINT_QUERIES: [&str; 2] = [
+    "SELECT (checkpoints_timed + checkpoints_req)::int8 AS checkpoints FROM pg_stat_bgwriter",
+    "SELECT (num_timed + num_requested)::int8 AS checkpoints FROM pg_stat_checkpointer",
+];
+
+impl Database {
+    /// Read the maintenance counters of the given tables and the checkpoint counter
+    pub async fn activity_snapshot(
+        &self,
+        tables: &[String],
+    ) -> Result<ActivitySnapshot, SqlTraceError> {
+        let pool = self.pg_pool()?;
+
+        let rows = sqlx::query(TABLE_MAINTENANCE_QUERY)
+            .bind(tables)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        let tables = rows
+            .iter()
+            .map(|row| -> Result<TableMaintenance, sqlx::Error> {
+                Ok(TableMaintenance {
+                    schema_name: row.try_get("schema_name")?,
+                    table_name: row.try_get("table_name")?,
+                    autovacuum_count: row.try_get("autovacuum_count")?,
+                    autoanalyze_count: row.try_get("autoanalyze_count")?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+        let mut checkpoints = None;
+        for query in CHECKPOINT_QUERIES {
+            if let Ok(row) = sqlx::query(query).fetch_one(pool).await {
+                checkpoints = row.try_get("checkpoints").ok();
+                break;
+            }
+        }
+
+        Ok(ActivitySnapshot {
+            tables,
+            checkpoints,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(name: &str, autovacuum_count: i64, autoanalyze_count: i64) -> TableMaintenance {
+        TableMaintenance {
+            schema_name: "public".to_string(),
+            table_name: name.to_string(),
+            autovacuum_count,
+            autoanalyze_count,
+        }
+    }
+
+    #[test]
+    fn test_activity_between_snapshots() {
+        let before = ActivitySnapshot {
+            tables: vec![table("orders", 4, 10), table("users", 1, 1)],
+            checkpoints: Some(100),
+        };
+        let after = ActivitySnapshot {
+            tables: vec![table("orders", 5, 10), table("users", 1, 2)],
+            checkpoints: Some(101),
+        };
+        let activity = BackgroundActivity::between(&before, &after);
+        assert_eq!(activity.checkpoints, Some(1));
+        assert_eq!(activity.autovacuumed, ["public.orders"]);
+        assert_eq!(activity.autoanalyzed, ["public.users"]);
+        assert_eq!(activity.notes.len(), 3);
+
+        let quiet = BackgroundActivity::between(&before, &before);
+        assert!(quiet.is_quiet());
+        assert_eq!(quiet.checkpoints, Some(0));
+
+        let unknown = BackgroundActivity::between(&ActivitySnapshot::default(), &after);
+        assert_eq!(unknown.checkpoints, None);
+        assert!(unknown.is_quiet());
+    }
+}
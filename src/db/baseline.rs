@@ -0,0 +1,170 @@
+//! A reference load measured on a PostgreSQL database before benchmarking
+//!
+//! The same query runs slower on a small instance, or one busy with other
+//! work, than on a large idle one, so timings from two environments can't be
+//! compared directly. A short, fixed synthetic load in the manner of pgbench's
+//! select-only mode measures what the environment delivers at the time of the
+//! benchmark. A query's time divided by the baseline latency is a figure that
+//! carries across environments, for CPU-bound queries at least.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// Statement each baseline transaction runs: CPU work on the server, no table access
+pub const BASELINE_STATEMENT: &str = "SELECT sum(i) FROM generate_series(1, 10000) AS i";
+
+/// Throughput and latency of the synthetic load
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemBaseline {
+    /// Statement each transaction ran
+    pub statement: String,
+    /// Number of clients running transactions at the same time
+    pub clients: u32,
+    /// How long the load ran
+    pub duration: Duration,
+    /// Transactions completed
+    pub transactions: u64,
+    /// Transactions that failed; a client stops at its first failure
+    pub failed_transactions: u64,
+    /// Completed transactions per second, across all clients
+    pub tps: f64,
+    /// Average latency of a transaction
+    pub avg_latency: Duration,
+    /// 95th percentile latency of a transaction
+    pub p95_latency: Duration,
+}
+
+impl SystemBaseline {
+    /// Summarize the latencies of the transactions completed in `duration`
+    pub fn from_latencies(
+        clients: u32,
+        duration: Duration,
+        mut latencies: Vec<Duration>,
+        failed_transactions: u64,
+    ) -> Self {
+        latencies.sort();
+        let transactions = latencies.len() as u64;
+        let avg_latency = if latencies.is_empty() {
+            Duration::ZERO
+        } else {
+            latencies.iter().sum::<Duration>() / latencies.len() as u32
+        };
+        let p95_latency = latencies
+            .get((0.95 * latencies.len().saturating_sub(1) as f64) as usize)
+            .copied()
+            .unwrap_or(Duration::ZERO);
+        let seconds = duration.as_secs_f64();
+
+        Self {
+            statement: BASELINE_STATEMENT.to_string(),
+            clients,
+            duration,
+            transactions,
+            failed_transactions,
+            tps: if seconds > 0.0 {
+                transactions as f64 / seconds
+            } else {
+                0.0
+            },
+            avg_latency,
+            p95_latency,
+        }
+    }
+
+    /// `time` in multiples of the average baseline latency
+    pub fn relative_time(&self, time: Duration) -> Option<f64> {
+        (!self.avg_latency.is_zero()).then(|| time.as_secs_f64() / self.avg_latency.as_secs_f64())
+    }
+}
+
+impl Database {
+    /// Run [`BASELINE_STATEMENT`] from `clients` connections at once for `duration`
+    ///
+    /// Clients are capped at the pool size, so none of them waits for a
+    /// connection while the clock runs.
+    pub async fn measure_baseline(
+        &self,
+        clients: u32,
+        duration: Duration,
+    ) -> Result<SystemBaseline, SqlTraceError> {
+        let pool = self.pg_pool()?;
+        let clients = clients.clamp(1, pool.options().get_max_connections().max(1));
+
+        let start = Instant::now();
+        let deadline = start + duration;
+        let tasks: Vec<_> = (0..clients)
+            .map(|_| {
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    let mut latencies = Vec::new();
+                    let mut failures = 0u64;
+                    let mut last_error = None;
+                    while Instant::now() < deadline {
+                        let started = Instant::now();
+                        match sqlx::query(BASELINE_STATEMENT).execute(&pool).await {
+                            Ok(_) => latencies.push(started.elapsed()),
+                            // Like a pgbench client, stop at the first error
+                            // rather than retrying for the rest of the duration
+                            Err(e) => {
+                                failures += 1;
+                                last_error = Some(e.to_string());
+                                break;
+                            }
+                        }
+                    }
+                    (latencies, failures, last_error)
+                })
+            })
+            .collect();
+
+        let mut latencies = Vec::new();
+        let mut failures = 0;
+        let mut last_error = None;
+        for task in tasks {
+            let (client_latencies, client_failures, client_error) = task
+                .await
+                .map_err(|e| DbError::Query(format!("Baseline client failed: {}", e)))?;
+            latencies.extend(client_latencies);
+            failures += client_failures;
+            last_error = client_error.or(last_error);
+        }
+        if latencies.is_empty() {
+            return Err(DbError::Query(
+                last_error.unwrap_or_else(|| "No baseline transaction completed".to_string()),
+            )
+            .into());
+        }
+
+        Ok(SystemBaseline::from_latencies(
+            clients,
+            start.elapsed(),
+            latencies,
+            failures,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_latencies() {
+        let latencies = (1..=20).map(Duration::from_millis).collect();
+        let baseline = SystemBaseline::from_latencies(2, Duration::from_secs(2), latencies, 1);
+        assert_eq!(baseline.transactions, 20);
+        assert_eq!(baseline.failed_transactions, 1);
+        assert!((baseline.tps - 10.0).abs() < 1e-9);
+        assert_eq!(baseline.avg_latency, Duration::from_micros(10_500));
+        assert_eq!(baseline.p95_latency, Duration::from_millis(19));
+
+        assert!((baseline.relative_time(Duration::from_millis(21)).unwrap() - 2.0).abs() < 1e-9);
+        let empty = SystemBaseline::from_latencies(1, Duration::ZERO, Vec::new(), 3);
+        assert_eq!(empty.tps, 0.0);
+        assert!(empty.relative_time(Duration::from_millis(5)).is_none());
+    }
+}
@@ -0,0 +1,286 @@
+//! Circuit breakers of registered connections
+//!
+//! When the database behind a connection stops answering, every analysis
+//! sent to it waits out a pool timeout before failing, holding a benchmark
+//! slot or a worker while it does. After enough analyses in a row fail to
+//! reach the database, the connection's circuit opens: requests are turned
+//! away at once with the time to retry after, and the connection is probed
+//! in the background until it answers again, which closes the circuit.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::db::connections::{ConnectionManager, ManagedConnection};
+use crate::storage::now_millis;
+
+/// When circuits open and how their connections are probed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BreakerConfig {
+    /// Analyses in a row that fail to reach the database before the circuit opens; 0 disables breakers
+    pub failure_threshold: u32,
+    /// Time between probes of a connection whose circuit is open, in seconds
+    pub probe_interval_secs: u64,
+    /// Time a probe may take before it counts as failed, in seconds
+    pub probe_timeout_secs: u64,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            probe_interval_secs: 10,
+            probe_timeout_secs: 5,
+        }
+    }
+}
+
+impl BreakerConfig {
+    fn probe_interval(&self) -> Duration {
+        Duration::from_secs(self.probe_interval_secs.max(1))
+    }
+}
+
+/// Whether a connection accepts analyses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Analyses are sent to the database
+    #[default]
+    Closed,
+    /// Analyses are turned away until a probe reaches the database
+    Open,
+}
+
+/// Circuit breaker state of a connection
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Circuit {
+    /// Whether analyses are accepted
+    pub state: CircuitState,
+    /// Analyses that failed to reach the database since one last did
+    pub consecutive_failures: u32,
+    /// When the circuit opened, in milliseconds since the epoch
+    pub opened_at: Option<i64>,
+    /// When the connection is probed next while the circuit is open, in milliseconds since the epoch
+    pub next_probe_at: Option<i64>,
+}
+
+impl Circuit {
+    /// Whether analyses are turned away
+    pub fn is_open(&self) -> bool {
+        self.state == CircuitState::Open
+    }
+
+    /// Time until the next probe if the circuit is open, in whole seconds rounded up and at least one
+    pub fn retry_after(&self, now: i64) -> Option<Duration> {
+        if !self.is_open() {
+            return None;
+        }
+        let wait_ms = self
+            .next_probe_at
+            .map_or(0, |next| next.saturating_sub(now))
+            .max(0) as u64;
+        Some(Duration::from_secs(wait_ms.div_ceil(1000).max(1)))
+    }
+
+    /// Record an analysis that reached the database, returning whether the circuit closed
+    pub fn record_success(&mut self) -> bool {
+        let was_open = self.is_open();
+        *self = Circuit::default();
+        was_open
+    }
+
+    /// Record an analysis that failed to reach the database, returning whether the circuit opened
+    pub fn record_failure(&mut self, now: i64, config: &BreakerConfig) -> bool {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if self.is_open()
+            || config.failure_threshold == 0
+            || self.consecutive_failures < config.failure_threshold
+        {
+            return false;
+        }
+        self.state = CircuitState::Open;
+        self.opened_at = Some(now);
+        self.schedule_probe(now, config);
+        true
+    }
+
+    /// Record a probe that failed to reach the database, scheduling the next one
+    pub fn schedule_probe(&mut self, now: i64, config: &BreakerConfig) {
+        self.next_probe_at = Some(now.saturating_add(config.probe_interval().as_millis() as i64));
+    }
+}
+
+/// An analysis turned away because the circuit of its connection is open
+#[derive(Debug, Clone, Error)]
+#[error(
+    "Connection '{connection}' is degraded after {failures} failed attempts to reach the database; retry in {}s",
+    retry_after.as_secs()
+)]
+pub struct CircuitOpen {
+    /// Name of the connection
+    pub connection: String,
+    /// Analyses in a row that failed to reach the database
+    pub failures: u32,
+    /// Time until the connection is probed again
+    pub retry_after: Duration,
+}
+
+/// Turn `connection` away if its circuit is open
+pub fn admit(connection: &ManagedConnection) -> Result<(), CircuitOpen> {
+    let circuit = connection.health().circuit;
+    match circuit.retry_after(now_millis()) {
+        Some(retry_after) => Err(CircuitOpen {
+            connection: connection.name.clone(),
+            failures: circuit.consecutive_failures,
+            retry_after,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Record whether an analysis on `connection` reached the database
+///
+/// Errors raised by the database about the statement itself show that it
+/// answered, so only failures to reach it count towards opening the circuit.
+/// The circuit opening starts probing the connection in the background.
+pub fn record(
+    connections: &Arc<ConnectionManager>,
+    connection: &ManagedConnection,
+    reached: bool,
+    config: &BreakerConfig,
+) {
+    let now = now_millis();
+    let (closed, opened) = connection.update_health(|health| {
+        if reached {
+            (health.circuit.record_success(), false)
+        } else {
+            (false, health.circuit.record_failure(now, config))
+        }
+    });
+    if closed {
+        tracing::info!("Circuit of connection '{}' closed", connection.name);
+    }
+    if opened {
+        tracing::warn!(
+            "Circuit of connection '{}' opened after {} failed analyses",
+            connection.name,
+            config.failure_threshold
+        );
+        spawn_probe(connections.clone(), connection.clone(), config.clone());
+    }
+}
+
+/// Probe `connection` every `config.probe_interval_secs` until it answers or its circuit closes
+fn spawn_probe(
+    connections: Arc<ConnectionManager>,
+    connection: ManagedConnection,
+    config: BreakerConfig,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let timeout = Duration::from_secs(config.probe_timeout_secs.max(1));
+        loop {
+            tokio::time::sleep(config.probe_interval()).await;
+
+            // The monitor may have reconnected it, or it may have been removed
+            let current = match connections.get(&connection.name) {
+                Ok(current) if current.is_same(&connection) => current,
+                _ => return,
+            };
+            if !current.health().circuit.is_open() {
+                return;
+            }
+
+            let error = match tokio::time::timeout(timeout, current.db.version_info()).await {
+                Ok(Ok(_)) => None,
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(_) => Some(format!("No answer within {}s", timeout.as_secs())),
+            };
+            match error {
+                None => {
+                    current.update_health(|health| health.circuit.record_success());
+                    tracing::info!(
+                        "Connection '{}' answered a probe; circuit closed",
+                        current.name
+                    );
+                    return;
+                }
+                Some(error) => {
+                    tracing::warn!("Probe of connection '{}' failed: {}", current.name, error);
+                    current.update_health(|health| {
+                        health.circuit.schedule_probe(now_millis(), &config)
+                    });
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    #[test]
+    fn test_circuit_opens_after_threshold() {
+        let config = BreakerConfig {
+            failure_threshold: 3,
+            probe_interval_secs: 10,
+            ..BreakerConfig::default()
+        };
+        let mut circuit = Circuit::default();
+        assert!(!circuit.record_failure(1_000, &config));
+        assert!(!circuit.record_failure(2_000, &config));
+        assert_eq!(circuit.retry_after(2_000), None);
+
+        assert!(circuit.record_failure(3_000, &config));
+        assert!(circuit.is_open());
+        assert_eq!(circuit.opened_at, Some(3_000));
+        assert_eq!(circuit.retry_after(5_000), Some(Duration::from_secs(8)));
+        assert_eq!(circuit.retry_after(20_000), Some(Duration::from_secs(1)));
+
+        // Failures of analyses already running do not reopen it
+        assert!(!circuit.record_failure(4_000, &config));
+        assert_eq!(circuit.opened_at, Some(3_000));
+
+        assert!(circuit.record_success());
+        assert_eq!(circuit, Circuit::default());
+        assert!(!circuit.record_success());
+
+        let disabled = BreakerConfig {
+            failure_threshold: 0,
+            ..BreakerConfig::default()
+        };
+        for now in 0..10 {
+            assert!(!circuit.record_failure(now, &disabled));
+        }
+        assert!(!circuit.is_open());
+    }
+
+    #[tokio::test]
+    async fn test_admit_and_probe() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        let connections = Arc::new(ConnectionManager::new(db, Duration::from_secs(60)));
+        let connection = connections.default_connection();
+        let config = BreakerConfig {
+            failure_threshold: 1,
+            probe_interval_secs: 1,
+            ..BreakerConfig::default()
+        };
+
+        record(&connections, &connection, false, &config);
+        let rejected = admit(&connection).unwrap_err();
+        assert_eq!(rejected.connection, "default");
+        assert_eq!(rejected.failures, 1);
+        assert_eq!(rejected.retry_after, Duration::from_secs(1));
+        assert!(rejected.to_string().contains("degraded"));
+
+        // The database answers, so the first probe closes the circuit
+        tokio::time::sleep(Duration::from_millis(1_500)).await;
+        assert!(admit(&connection).is_ok());
+        assert_eq!(connection.health().circuit, Circuit::default());
+    }
+}
@@ -0,0 +1,225 @@
+//! Cancelling running EXPLAIN ANALYZE requests
+//!
+//! ANALYZE executes the query, which may run far longer than anyone wants to
+//! wait. A request that names itself with an id is registered here while it
+//! runs, together with the server process executing it once a connection is
+//! assigned. Ids are issued here too, as random UUIDs that are each good for
+//! one request, so a caller can neither guess the id of someone else's
+//! request nor claim it first. Cancelling the id asks that process to cancel its statement
+//! (`pg_cancel_backend` on PostgreSQL), and the request fails with
+//! [`DbError::Cancelled`] instead of returning a plan.
+//!
+//! A connection is detached from its request before it goes back to the pool,
+//! under the same lock the cancellation holds while signalling, so a late
+//! cancellation never reaches a statement of another request.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use sqlx::PgConnection;
+
+use crate::db::engines::EngineType;
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// How long an issued id can be used to register a request
+pub const ISSUED_ID_TTL: Duration = Duration::from_secs(600);
+
+/// Most issued ids waiting to be used; the oldest is dropped to make room
+const MAX_ISSUED_IDS: usize = 10_000;
+
+/// Requests that can be cancelled, by the id they were issued
+#[derive(Debug, Default)]
+pub struct RunningQueries {
+    running: Mutex<HashMap<String, Arc<RunningQuery>>>,
+    /// Ids issued and not used yet, with when they were issued
+    issued: Mutex<HashMap<String, Instant>>,
+}
+
+impl RunningQueries {
+    /// Issue a new id for one request to register under
+    pub fn issue(&self) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = Instant::now();
+        let mut issued = self.issued.lock().unwrap_or_else(|e| e.into_inner());
+        issued.retain(|_, at| now.duration_since(*at) < ISSUED_ID_TTL);
+        if issued.len() >= MAX_ISSUED_IDS {
+            if let Some(oldest) = issued
+                .iter()
+                .min_by_key(|(_, at)| **at)
+                .map(|(id, _)| id.clone())
+            {
+                issued.remove(&oldest);
+            }
+        }
+        issued.insert(id.clone(), now);
+        id
+    }
+
+    /// Register a request running on `db` as `id`, until the returned guard is dropped
+    ///
+    /// `id` must have been issued by [`RunningQueries::issue`] within
+    /// [`ISSUED_ID_TTL`], and each id registers one request only.
+    pub fn register(self: &Arc<Self>, id: &str, db: &Database) -> Result<RunningGuard, String> {
+        let issued_at = self
+            .issued
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id);
+        if issued_at.is_none_or(|at| at.elapsed() >= ISSUED_ID_TTL) {
+            return Err(format!(
+                "Request id '{}' was not issued by this server or was already used",
+                id
+            ));
+        }
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        if running.contains_key(id) {
+            return Err(format!("A request with id '{}' is already running", id));
+        }
+        let query = Arc::new(RunningQuery {
+            db: db.clone(),
+            cancelled: AtomicBool::new(false),
+            backend: tokio::sync::Mutex::new(None),
+        });
+        running.insert(id.to_string(), query.clone());
+        Ok(RunningGuard {
+            registry: self.clone(),
+            id: id.to_string(),
+            query,
+        })
+    }
+
+    /// The running request registered as `id`
+    pub fn get(&self, id: &str) -> Option<Arc<RunningQuery>> {
+        self.running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .cloned()
+    }
+}
+
+/// Keeps a request registered while it runs
+#[derive(Debug)]
+pub struct RunningGuard {
+    registry: Arc<RunningQueries>,
+    id: String,
+    query: Arc<RunningQuery>,
+}
+
+impl RunningGuard {
+    /// The registered request
+    pub fn query(&self) -> &RunningQuery {
+        &self.query
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.registry
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
+    }
+}
+
+/// A request that can be cancelled, and the server process running its statement
+#[derive(Debug)]
+pub struct RunningQuery {
+    db: Database,
+    cancelled: AtomicBool,
+    /// Process id of the connection running the statement, while it has one
+    backend: tokio::sync::Mutex<Option<i32>>,
+}
+
+impl RunningQuery {
+    /// Whether the request was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Cancel the request, signalling its statement if one is running
+    ///
+    /// Returns whether a running statement was signalled; a request still
+    /// waiting for a connection fails as soon as it gets one.
+    pub async fn cancel(&self) -> Result<bool, SqlTraceError> {
+        if self.db.engine_type() != EngineType::PostgreSQL {
+            return Err(DbError::Unsupported(format!(
+                "cancelling queries requires PostgreSQL, but the connection is {}",
+                self.db.engine_type()
+            ))
+            .into());
+        }
+        self.cancelled.store(true, Ordering::SeqCst);
+        let backend = self.backend.lock().await;
+        match *backend {
+            Some(pid) => self.db.cancel_backend(pid).await,
+            None => Ok(false),
+        }
+    }
+
+    /// Record that the statement runs on `conn`, failing if the request was already cancelled
+    pub(crate) async fn attach(&self, conn: &mut PgConnection) -> Result<(), DbError> {
+        let pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+            .fetch_one(conn)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        let mut backend = self.backend.lock().await;
+        if self.is_cancelled() {
+            return Err(DbError::Cancelled);
+        }
+        *backend = Some(pid);
+        Ok(())
+    }
+
+    /// Forget the connection before it is reused, waiting out a cancellation in progress
+    pub(crate) async fn detach(&self) {
+        *self.backend.lock().await = None;
+    }
+}
+
+impl Database {
+    /// Ask the server process `pid` to cancel its current statement
+    ///
+    /// Returns whether the signal was sent; the process may have finished already.
+    pub async fn cancel_backend(&self, pid: i32) -> Result<bool, SqlTraceError> {
+        let signalled: bool = sqlx::query_scalar("SELECT pg_cancel_backend($1)")
+            .bind(pid)
+            .fetch_one(self.pg_pool()?)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        Ok(signalled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_register_and_cancel() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        let registry = Arc::new(RunningQueries::default());
+
+        // Only issued ids can be registered, and each only once
+        assert!(registry.register("report-1", &db).is_err());
+        let id = registry.issue();
+        assert_ne!(registry.issue(), id);
+        let guard = registry.register(&id, &db).unwrap();
+        assert!(registry.register(&id, &db).is_err());
+        assert!(!guard.query().is_cancelled());
+
+        // Only PostgreSQL statements can be cancelled
+        let running = registry.get(&id).unwrap();
+        assert!(running.cancel().await.is_err());
+        assert!(!guard.query().is_cancelled());
+
+        drop(guard);
+        assert!(registry.get(&id).is_none());
+        assert!(registry.register(&id, &db).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+//! Named database connections managed at runtime
+//!
+//! The connection given on the command line is registered as `default`.
+//! Further connections can be added and dropped while the server runs, and
+//! requests pick one by name. Each connection keeps its own schema cache,
+//! since relation names differ between databases.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::db::engines::{ConnectionConfig, ConnectionSummary, DatabaseInfo, EngineType};
+use crate::db::monitor::ConnectionHealth;
+use crate::db::schema_cache::SchemaCache;
+use crate::db::Database;
+
+/// Name of the connection given on the command line
+pub const DEFAULT_CONNECTION: &str = "default";
+
+/// Longest accepted connection name
+const MAX_NAME_LEN: usize = 64;
+
+/// Errors raised by the connection manager
+#[derive(Error, Debug)]
+pub enum ConnectionError {
+    /// No connection has the name
+    #[error("No connection named '{0}'")]
+    NotFound(String),
+
+    /// A connection with the name is already registered
+    #[error("A connection named '{0}' already exists")]
+    AlreadyExists(String),
+
+    /// The name is empty, too long, or contains characters other than letters, digits, `-` and `_`
+    #[error("Invalid connection name '{0}': use up to 64 letters, digits, '-' or '_'")]
+    InvalidName(String),
+
+    /// The connection cannot be dropped
+    #[error("The {0} connection cannot be removed")]
+    Protected(String),
+
+    /// Connecting or talking to the database failed
+    #[error("{0}")]
+    Database(#[from] crate::SqlTraceError),
+}
+
+/// A registered connection
+#[derive(Debug, Clone)]
+pub struct ManagedConnection {
+    /// Name requests refer to the connection by
+    pub name: String,
+    /// The database
+    pub db: Database,
+    /// Cached list of relations in the database
+    pub schema_cache: Arc<SchemaCache>,
+    /// Outcome of the recent health checks, kept when the database is reconnected
+    health: Arc<Mutex<ConnectionHealth>>,
+}
+
+impl ManagedConnection {
+    /// The connection as listed by the API, without secrets
+    pub fn summary(&self) -> ConnectionSummary {
+        ConnectionSummary {
+            name: self.name.clone(),
+            engine_type: self.db.engine_type(),
+            url: self.db.redacted_url().map(str::to_string),
+        }
+    }
+
+    /// Outcome of the recent health checks
+    pub fn health(&self) -> ConnectionHealth {
+        self.health
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Whether `other` is this connection, not one registered again under its name
+    ///
+    /// A reconnected connection is still the same one.
+    pub fn is_same(&self, other: &ManagedConnection) -> bool {
+        Arc::ptr_eq(&self.health, &other.health)
+    }
+
+    /// Change the recorded health, returning what `update` returns
+    pub fn update_health<T>(&self, update: impl FnOnce(&mut ConnectionHealth) -> T) -> T {
+        update(&mut self.health.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Result of testing a connection
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionTest {
+    /// Database engine
+    pub engine_type: EngineType,
+    /// Version reported by the server
+    pub version: String,
+    /// Round trip time of the test query in milliseconds
+    pub latency_ms: f64,
+}
+
+/// Registry of named connections
+#[derive(Debug)]
+pub struct ConnectionManager {
+    schema_cache_ttl: Duration,
+    connections: RwLock<BTreeMap<String, ManagedConnection>>,
+}
+
+impl ConnectionManager {
+    /// Create a manager holding `default` as the [`DEFAULT_CONNECTION`]
+    ///
+    /// Schema caches of the connections expire after `schema_cache_ttl`.
+    pub fn new(default: Database, schema_cache_ttl: Duration) -> Self {
+        let manager = Self {
+            schema_cache_ttl,
+            connections: RwLock::new(BTreeMap::new()),
+        };
+        let default = manager.managed(DEFAULT_CONNECTION, default);
+        manager
+            .write()
+            .insert(DEFAULT_CONNECTION.to_string(), default);
+        manager
+    }
+
+    fn managed(&self, name: &str, db: Database) -> ManagedConnection {
+        ManagedConnection {
+            name: name.to_string(),
+            db,
+            schema_cache: Arc::new(SchemaCache::new(self.schema_cache_ttl)),
+            health: Arc::default(),
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, BTreeMap<String, ManagedConnection>> {
+        self.connections.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, BTreeMap<String, ManagedConnection>> {
+        self.connections.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The connection named `name`
+    pub fn get(&self, name: &str) -> Result<ManagedConnection, ConnectionError> {
+        self.read()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ConnectionError::NotFound(name.to_string()))
+    }
+
+    /// The [`DEFAULT_CONNECTION`]
+    pub fn default_connection(&self) -> ManagedConnection {
+        self.get(DEFAULT_CONNECTION)
+            .expect("the default connection is never removed")
+    }
+
+    /// Registered connections ordered by name
+    pub fn summaries(&self) -> Vec<ConnectionSummary> {
+        self.read()
+            .values()
+            .map(ManagedConnection::summary)
+            .collect()
+    }
+
+    /// Registered connections ordered by name
+    pub fn list(&self) -> Vec<ManagedConnection> {
+        self.read().values().cloned().collect()
+    }
+
+    /// Health of the registered connections by name
+    pub fn health(&self) -> BTreeMap<String, ConnectionHealth> {
+        self.read()
+            .iter()
+            .map(|(name, connection)| (name.clone(), connection.health()))
+            .collect()
+    }
+
+    /// Version, features and health of the connection named `name`
+    pub async fn info(&self, name: &str) -> Result<DatabaseInfo, ConnectionError> {
+        let connection = self.get(name)?;
+        let mut info = connection.db.version_info().await?;
+        info.health = Some(connection.health());
+        Ok(info)
+    }
+
+    /// Serve `connection` from `db` from now on, keeping its schema cache and health
+    ///
+    /// Returns `false` if the connection has been removed or replaced by
+    /// another of the same name in the meantime.
+    pub fn replace_database(&self, connection: &ManagedConnection, db: Database) -> bool {
+        let mut connections = self.write();
+        match connections.get_mut(&connection.name) {
+            Some(current) if current.is_same(connection) => {
+                current.db = db;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Register an open database under `name`
+    pub fn register(&self, name: &str, db: Database) -> Result<ConnectionSummary, ConnectionError> {
+        validate_name(name)?;
+        let mut connections = self.write();
+        if connections.contains_key(name) {
+            return Err(ConnectionError::AlreadyExists(name.to_string()));
+        }
+        let connection = self.managed(name, db);
+        let summary = connection.summary();
+        connections.insert(name.to_string(), connection);
+        Ok(summary)
+    }
+
+    /// Connect to the database described by `config` and register it under `name`
+    pub async fn connect(
+        &self,
+        name: &str,
+        config: ConnectionConfig,
+    ) -> Result<ConnectionSummary, ConnectionError> {
+        // Fail fast before opening a pool that would be thrown away
+        validate_name(name)?;
+        if self.read().contains_key(name) {
+            return Err(ConnectionError::AlreadyExists(name.to_string()));
+        }
+        let db = Database::with_config(config).await?;
+        self.register(name, db)
+    }
+
+    /// Check that the connection named `name` can run a query
+    pub async fn test(&self, name: &str) -> Result<ConnectionTest, ConnectionError> {
+        let connection = self.get(name)?;
+        let started = Instant::now();
+        let info = connection.db.version_info().await?;
+        Ok(ConnectionTest {
+            engine_type: info.engine_type,
+            version: info.version,
+            latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+        })
+    }
+
+    /// Drop the connection named `name`, returning whether it existed
+    ///
+    /// Requests already running on the connection finish; its pool closes
+    /// once they release it.
+    pub fn remove(&self, name: &str) -> Result<bool, ConnectionError> {
+        if name == DEFAULT_CONNECTION {
+            return Err(ConnectionError::Protected(name.to_string()));
+        }
+        Ok(self.write().remove(name).is_some())
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), ConnectionError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ConnectionError::InvalidName(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn sqlite() -> Database {
+        Database::new("sqlite::memory:").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_register_and_remove_connections() {
+        let manager = ConnectionManager::new(sqlite().await, Duration::from_secs(60));
+        assert_eq!(manager.summaries().len(), 1);
+
+        let summary = manager.register("analytics", sqlite().await).unwrap();
+        assert_eq!(summary.engine_type, EngineType::SQLite);
+        assert!(matches!(
+            manager.register("analytics", sqlite().await),
+            Err(ConnectionError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            manager.register("bad name", sqlite().await),
+            Err(ConnectionError::InvalidName(_))
+        ));
+
+        let names: Vec<String> = manager.summaries().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, ["analytics", "default"]);
+        assert_eq!(manager.get("analytics").unwrap().name, "analytics");
+
+        assert!(manager.remove("analytics").unwrap());
+        assert!(!manager.remove("analytics").unwrap());
+        assert!(matches!(
+            manager.get("analytics"),
+            Err(ConnectionError::NotFound(_))
+        ));
+        assert!(matches!(
+            manager.remove(DEFAULT_CONNECTION),
+            Err(ConnectionError::Protected(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_replace_database_keeps_health() {
+        let manager = ConnectionManager::new(sqlite().await, Duration::from_secs(60));
+        manager.register("analytics", sqlite().await).unwrap();
+        let connection = manager.get("analytics").unwrap();
+        connection.update_health(|health| health.record_success(2.0, 1_000));
+
+        let reconnected = connection.db.reconnect().await.unwrap();
+        assert!(manager.replace_database(&connection, reconnected));
+        assert_eq!(manager.health()["analytics"].latency_ms, Some(2.0));
+        let info = manager.info("analytics").await.unwrap();
+        assert_eq!(info.health.unwrap().latency_ms, Some(2.0));
+
+        // A connection registered again under the same name is a different one
+        manager.remove("analytics").unwrap();
+        manager.register("analytics", sqlite().await).unwrap();
+        assert!(!manager.replace_database(&connection, sqlite().await));
+        assert_eq!(manager.health()["analytics"], ConnectionHealth::default());
+    }
+
+    #[tokio::test]
+    async fn test_test_connection() {
+        let manager = ConnectionManager::new(sqlite().await, Duration::from_secs(60));
+        let result = manager.test(DEFAULT_CONNECTION).await.unwrap();
+        assert_eq!(result.engine_type, EngineType::SQLite);
+        assert!(!result.version.is_empty());
+        assert!(matches!(
+            manager.test("missing").await,
+            Err(ConnectionError::NotFound(_))
+        ));
+    }
+}
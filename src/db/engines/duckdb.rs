@@ -0,0 +1,687 @@
+//! DuckDB database engine implementation
+//!
+//! DuckDB runs in-process, so there is no server to connect to. This engine
+//! drives the `duckdb` command-line shell against a database file, or an
+//! empty in-memory database for queries over Parquet and CSV files. Plans
+//! come from DuckDB's JSON profiler: each operator reports its own time and
+//! output cardinality, which are mapped onto [`PlanNode`] with times made
+//! inclusive of children to match PostgreSQL's `EXPLAIN ANALYZE`.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::de::{Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlparser::ast::Statement;
+use sqlparser::dialect::DuckDbDialect;
+use sqlparser::parser::Parser;
+use tokio::process::Command;
+
+use super::{
+    count_query, ConnectionConfig, DatabaseEngine, DatabaseFeature, DatabaseInfo, EngineError,
+    EngineType, QueryCategory, SampleQuery,
+};
+use crate::db::models::{ExecutionPlan, PlanNode};
+
+/// Shell used when `SQLTRACE_DUCKDB_CLI` is not set
+const DEFAULT_CLI: &str = "duckdb";
+
+/// Profiler operators that only wrap the plan being explained
+const WRAPPER_OPERATORS: &[&str] = &["EXPLAIN_ANALYZE", "QUERY", "RESULT_COLLECTOR"];
+
+/// DuckDB database engine implementation
+#[derive(Debug)]
+pub struct DuckDBEngine {
+    /// Database file, or `None` for an in-memory database
+    database: Option<PathBuf>,
+    /// The `duckdb` shell to run
+    cli: PathBuf,
+    /// Whether queries may read files and URLs through functions such as `read_csv`
+    external_access: bool,
+    config: ConnectionConfig,
+}
+
+impl DuckDBEngine {
+    /// Create a new DuckDB engine instance
+    ///
+    /// The connection string is a `duckdb:` URL (`duckdb::memory:` or
+    /// `duckdb:///path/to/file.duckdb`) or a path ending in `.duckdb`. The
+    /// shell is looked up on `PATH` unless `SQLTRACE_DUCKDB_CLI` names it;
+    /// call [`DatabaseEngine::test_connection`] to check it can be run.
+    ///
+    /// Queries cannot read files or URLs unless `SQLTRACE_DUCKDB_EXTERNAL_ACCESS`
+    /// is `1`, since anyone who can submit a query could otherwise read any
+    /// file the server can.
+    pub async fn new(config: ConnectionConfig) -> Result<Self, EngineError> {
+        let database = database_path(&config.connection_string);
+        if let Some(path) = &database {
+            if !path.exists() {
+                return Err(EngineError::Connection(format!(
+                    "DuckDB database {} does not exist",
+                    path.display()
+                )));
+            }
+        }
+
+        let cli = std::env::var_os("SQLTRACE_DUCKDB_CLI")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CLI));
+        let external_access =
+            std::env::var("SQLTRACE_DUCKDB_EXTERNAL_ACCESS").is_ok_and(|v| v == "1");
+
+        Ok(Self {
+            database,
+            cli,
+            external_access,
+            config,
+        })
+    }
+
+    /// Run `sql` in the shell and return what it printed in JSON mode
+    async fn run(&self, sql: &str) -> Result<String, EngineError> {
+        let mut command = Command::new(&self.cli);
+        command.arg("-json").arg("-bail");
+        match &self.database {
+            // Nothing sqltrace runs should write to the user's database
+            Some(path) => command.arg("-readonly").arg(path),
+            None => command.arg(":memory:"),
+        };
+        command
+            .arg("-c")
+            .arg(sql)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+
+        let timeout = Duration::from_secs(self.config.timeout_seconds.unwrap_or(30).max(1));
+        let output = tokio::time::timeout(timeout, command.output())
+            .await
+            .map_err(|_| {
+                EngineError::QueryExecution(format!(
+                    "DuckDB did not finish within {}s",
+                    timeout.as_secs()
+                ))
+            })?
+            .map_err(|e| {
+                EngineError::Connection(format!(
+                    "Failed to run the DuckDB shell {}: {}",
+                    self.cli.display(),
+                    e
+                ))
+            })?;
+
+        if !output.status.success() {
+            return Err(EngineError::QueryExecution(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Run `setup`, then `statement` built around the caller's `query`
+    ///
+    /// `-c` runs every statement of its script, so `query` must be a single
+    /// query. External access is turned off after `setup`, which may still
+    /// write sqltrace's own files, and cannot be turned back on by the query.
+    async fn run_query(
+        &self,
+        setup: &str,
+        query: &str,
+        statement: impl FnOnce(&str) -> String,
+    ) -> Result<String, EngineError> {
+        check_single_query(query)?;
+        let sandbox = if self.external_access {
+            ""
+        } else {
+            "SET enable_external_access = false; "
+        };
+        self.run(&format!("{}{}{}", setup, sandbox, statement(query)))
+            .await
+    }
+
+    /// Run `sql` and return its rows with values in column order
+    async fn query_rows(&self, sql: &str) -> Result<Vec<Vec<Value>>, EngineError> {
+        parse_rows(&self.run(sql).await?)
+    }
+
+    /// Convert DuckDB's JSON profile to our unified ExecutionPlan format
+    fn parse_duckdb_profile(&self, profile: &Value) -> Result<ExecutionPlan, EngineError> {
+        let children = profile
+            .get("children")
+            .and_then(Value::as_array)
+            .ok_or_else(|| {
+                EngineError::PlanParsing("Missing children in DuckDB profile".to_string())
+            })?;
+
+        let mut plans: Vec<PlanNode> = children.iter().map(operator_node).collect();
+        let root = if plans.len() == 1 {
+            plans.remove(0)
+        } else {
+            PlanNode {
+                node_type: "Query Plan".to_string(),
+                actual_total_time: plans.iter().map(|p| p.actual_total_time).sum(),
+                actual_loops: 1,
+                plans,
+                extra: json!({}),
+                ..Default::default()
+            }
+        };
+
+        // Newer versions report the query's wall time as latency, older ones as timing
+        let execution_time = ["latency", "timing", "result"]
+            .iter()
+            .find_map(|key| profile.get(*key).and_then(Value::as_f64))
+            .map_or(root.actual_total_time, |seconds| seconds * 1000.0);
+
+        Ok(ExecutionPlan {
+            root,
+            planning_time: 0.0,
+            execution_time,
+            settings: None,
+            jit: None,
+        })
+    }
+}
+
+/// The database file named by a connection string, or `None` for in-memory
+fn database_path(connection_string: &str) -> Option<PathBuf> {
+    let path = connection_string
+        .strip_prefix("duckdb://")
+        .or_else(|| connection_string.strip_prefix("duckdb:"))
+        .unwrap_or(connection_string);
+    match path {
+        "" | ":memory:" => None,
+        path => Some(PathBuf::from(path)),
+    }
+}
+
+/// A plan node for one profiled operator and its children
+fn operator_node(operator: &Value) -> PlanNode {
+    let name = ["operator_name", "operator_type", "name"]
+        .iter()
+        .find_map(|key| operator.get(*key).and_then(Value::as_str))
+        .unwrap_or_default()
+        .trim();
+    let children: Vec<PlanNode> = operator
+        .get("children")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(operator_node)
+        .collect();
+
+    if WRAPPER_OPERATORS.contains(&name) && children.len() == 1 {
+        return children.into_iter().next().unwrap_or_default();
+    }
+
+    let mut extra = json!({});
+    let mut relation_name = None;
+    let mut function = None;
+    let mut filter = None;
+    let mut join_filter = None;
+    match operator.get("extra_info") {
+        Some(Value::Object(info)) => {
+            for (key, value) in info {
+                match key.as_str() {
+                    "Table" => relation_name = value.as_str().map(str::to_string),
+                    "Function" => function = value.as_str().map(str::to_string),
+                    "Estimated Cardinality" => {
+                        extra["Plan Rows"] = value
+                            .as_str()
+                            .and_then(|rows| rows.trim_start_matches('~').parse::<u64>().ok())
+                            .map_or_else(|| value.clone(), |rows| json!(rows));
+                    }
+                    "Filters" => filter = condition(value),
+                    "Conditions" => join_filter = condition(value),
+                    _ => extra[key] = value.clone(),
+                }
+            }
+        }
+        // Before DuckDB 1.1 extra_info was free text
+        Some(Value::String(info)) if !info.trim().is_empty() => {
+            extra["Detail"] = json!(info.trim());
+        }
+        _ => {}
+    }
+    extra["Operator"] = json!(name);
+
+    let self_time = ["operator_timing", "timing"]
+        .iter()
+        .find_map(|key| operator.get(*key).and_then(Value::as_f64))
+        .unwrap_or(0.0)
+        * 1000.0;
+    extra["Self Time"] = json!(self_time);
+
+    let node_type = match name {
+        "TABLE_SCAN" | "SEQ_SCAN" if relation_name.is_none() && function.is_some() => {
+            "Function Scan"
+        }
+        "TABLE_SCAN" | "SEQ_SCAN" => "Seq Scan",
+        "INDEX_SCAN" => "Index Scan",
+        "READ_PARQUET" | "READ_CSV" | "READ_CSV_AUTO" | "READ_JSON" => "Function Scan",
+        "COLUMN_DATA_SCAN" | "CHUNK_SCAN" | "DUMMY_SCAN" | "EMPTY_RESULT" => "Result",
+        "CTE_SCAN" | "RECURSIVE_CTE_SCAN" => "CTE Scan",
+        "CTE" | "RECURSIVE_CTE" => "Materialize",
+        "HASH_JOIN" | "DELIM_JOIN" | "LEFT_DELIM_JOIN" | "RIGHT_DELIM_JOIN" => "Hash Join",
+        "PIECEWISE_MERGE_JOIN" | "IE_JOIN" => "Merge Join",
+        "NESTED_LOOP_JOIN" | "BLOCKWISE_NL_JOIN" | "CROSS_PRODUCT" | "POSITIONAL_JOIN" => {
+            "Nested Loop"
+        }
+        "HASH_GROUP_BY" | "PERFECT_HASH_GROUP_BY" | "UNGROUPED_AGGREGATE" | "SIMPLE_AGGREGATE" => {
+            "Aggregate"
+        }
+        "ORDER_BY" | "TOP_N" => "Sort",
+        "LIMIT" | "STREAMING_LIMIT" | "LIMIT_PERCENT" => "Limit",
+        "WINDOW" | "STREAMING_WINDOW" => "WindowAgg",
+        "UNION" => "Append",
+        "FILTER" | "PROJECTION" => "Result",
+        _ => "",
+    };
+    let node_type = if node_type.is_empty() {
+        title_case(name)
+    } else {
+        node_type.to_string()
+    };
+
+    let strategy = match name {
+        "HASH_GROUP_BY" | "PERFECT_HASH_GROUP_BY" => Some("Hashed".to_string()),
+        "UNGROUPED_AGGREGATE" | "SIMPLE_AGGREGATE" => Some("Plain".to_string()),
+        _ => None,
+    };
+    if let Some(function) = function {
+        extra["Function Name"] = json!(function);
+    }
+
+    // DuckDB times each operator on its own; PostgreSQL's times include children
+    let total_time = self_time + children.iter().map(|c| c.actual_total_time).sum::<f64>();
+
+    PlanNode {
+        node_type,
+        relation_name,
+        actual_total_time: total_time,
+        actual_rows: ["operator_cardinality", "cardinality"]
+            .iter()
+            .find_map(|key| operator.get(*key).and_then(Value::as_u64))
+            .unwrap_or(0),
+        actual_loops: 1,
+        strategy,
+        filter,
+        join_filter,
+        plans: children,
+        extra,
+        ..Default::default()
+    }
+}
+
+/// A filter or join condition, which DuckDB lists one per entry when there are several
+fn condition(value: &Value) -> Option<String> {
+    match value {
+        Value::String(condition) => Some(condition.clone()),
+        Value::Array(conditions) => Some(
+            conditions
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(" AND "),
+        ),
+        _ => None,
+    }
+}
+
+/// `HASH_GROUP_BY` as `Hash Group By`
+fn title_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let lower = word.to_lowercase();
+            let mut chars = lower.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// One row of JSON-mode output, keeping the values in column order
+struct OrderedRow(Vec<Value>);
+
+impl<'de> Deserialize<'de> for OrderedRow {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RowVisitor;
+
+        impl<'de> Visitor<'de> for RowVisitor {
+            type Value = OrderedRow;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JSON object per row")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<OrderedRow, A::Error> {
+                let mut values = Vec::new();
+                while let Some((_, value)) = map.next_entry::<String, Value>()? {
+                    values.push(value);
+                }
+                Ok(OrderedRow(values))
+            }
+        }
+
+        deserializer.deserialize_map(RowVisitor)
+    }
+}
+
+/// Rows printed by the shell in JSON mode, which prints nothing for no rows
+fn parse_rows(output: &str) -> Result<Vec<Vec<Value>>, EngineError> {
+    if output.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let rows: Vec<OrderedRow> = serde_json::from_str(output).map_err(|e| {
+        EngineError::QueryExecution(format!("Failed to parse DuckDB output: {}", e))
+    })?;
+    Ok(rows.into_iter().map(|row| row.0).collect())
+}
+
+/// Check that `query` is one query, so nothing runs after it in the shell script
+fn check_single_query(query: &str) -> Result<(), EngineError> {
+    let statements = Parser::parse_sql(&DuckDbDialect {}, query)
+        .map_err(|e| EngineError::QueryExecution(format!("Query could not be parsed: {}", e)))?;
+    match statements.as_slice() {
+        [Statement::Query(_)] => Ok(()),
+        [_] => Err(EngineError::QueryExecution(
+            "Only SELECT queries can be run on DuckDB".to_string(),
+        )),
+        _ => Err(EngineError::QueryExecution(
+            "Only a single statement can be run on DuckDB".to_string(),
+        )),
+    }
+}
+
+/// Quote `value` as a SQL string literal
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[async_trait]
+impl DatabaseEngine for DuckDBEngine {
+    fn engine_type(&self) -> EngineType {
+        EngineType::DuckDB
+    }
+
+    async fn test_connection(&self) -> Result<bool, EngineError> {
+        self.run("SELECT 1")
+            .await
+            .map(|_| true)
+            .map_err(|e| EngineError::Connection(format!("Connection test failed: {}", e)))
+    }
+
+    async fn explain_query(&self, query: &str) -> Result<ExecutionPlan, EngineError> {
+        // The profiler writes its JSON to a file; the shell prints the text rendering
+        let profile_path =
+            std::env::temp_dir().join(format!("sqltrace-duckdb-{}.json", uuid::Uuid::new_v4()));
+        let setup = format!(
+            "SET enable_profiling = 'json'; SET profiling_output = {}; ",
+            quote_literal(&profile_path.to_string_lossy())
+        );
+
+        let result = self
+            .run_query(&setup, query, |query| format!("EXPLAIN ANALYZE {}", query))
+            .await;
+        let profile = tokio::fs::read_to_string(&profile_path).await;
+        let _ = tokio::fs::remove_file(&profile_path).await;
+        result.map_err(|e| {
+            EngineError::QueryExecution(format!("Failed to execute EXPLAIN ANALYZE: {}", e))
+        })?;
+
+        let profile: Value = profile
+            .map_err(|e| EngineError::PlanParsing(format!("Failed to read DuckDB profile: {}", e)))
+            .and_then(|text| {
+                serde_json::from_str(&text).map_err(|e| {
+                    EngineError::PlanParsing(format!("Failed to parse DuckDB profile: {}", e))
+                })
+            })?;
+
+        self.parse_duckdb_profile(&profile)
+    }
+
+    async fn validate_query(&self, query: &str) -> Result<(), EngineError> {
+        // EXPLAIN binds the statement against the catalog without executing it
+        self.run_query("", query, |query| format!("EXPLAIN {}", query))
+            .await
+            .map(|_| ())
+            .map_err(|e| EngineError::QueryExecution(format!("Query validation failed: {}", e)))
+    }
+
+    async fn count_rows(&self, query: &str, timeout: Duration) -> Result<u64, EngineError> {
+        // Dropping the run kills the shell, which stops the count
+        let output = tokio::time::timeout(timeout, self.run_query("", query, count_query))
+            .await
+            .map_err(|_| {
+                EngineError::QueryExecution(format!(
+                    "Row count exceeded the {}ms timeout",
+                    timeout.as_millis()
+                ))
+            })??;
+        let rows = parse_rows(&output)?;
+        rows.first()
+            .and_then(|row| row.first())
+            .and_then(Value::as_u64)
+            .ok_or_else(|| EngineError::QueryExecution("Failed to read row count".to_string()))
+    }
+
+    async fn fetch_rows(&self, query: &str, limit: usize) -> Result<Vec<String>, EngineError> {
+        let output = self
+            .run_query("", query, |query| {
+                format!(
+                    "SELECT * FROM ({}) AS sqltrace_rows LIMIT {}",
+                    query.trim_end().trim_end_matches(';'),
+                    limit
+                )
+            })
+            .await?;
+        Ok(parse_rows(&output)?
+            .into_iter()
+            .map(|values| Value::Array(values).to_string())
+            .collect())
+    }
+
+    async fn get_version_info(&self) -> Result<DatabaseInfo, EngineError> {
+        let rows = self
+            .query_rows("SELECT version()")
+            .await
+            .map_err(|e| EngineError::Connection(format!("Failed to get version: {}", e)))?;
+        let version = rows
+            .first()
+            .and_then(|row| row.first())
+            .and_then(Value::as_str)
+            .ok_or_else(|| EngineError::Connection("Failed to parse version".to_string()))?;
+
+        Ok(DatabaseInfo {
+            engine_type: EngineType::DuckDB,
+            version: version.to_string(),
+            connection_status: "Connected".to_string(),
+            health: None,
+            socket: None,
+            schema: None,
+            search_path: None,
+            features_supported: vec![
+                DatabaseFeature::DetailedExecutionPlan,
+                DatabaseFeature::ActualRowCounts,
+                DatabaseFeature::ParallelExecution,
+            ],
+        })
+    }
+
+    fn get_sample_queries(&self) -> Vec<SampleQuery> {
+        vec![
+            SampleQuery {
+                name: "Parquet Scan".to_string(),
+                description: "Aggregate a Parquet file without loading it; needs SQLTRACE_DUCKDB_EXTERNAL_ACCESS=1".to_string(),
+                query: "SELECT passenger_count, avg(total_amount) FROM read_parquet('trips.parquet') GROUP BY passenger_count;".to_string(),
+                category: QueryCategory::Aggregation,
+            },
+            SampleQuery {
+                name: "Hash Join".to_string(),
+                description: "DuckDB hash join between two tables".to_string(),
+                query: "SELECT c.name, sum(o.total) FROM customers c JOIN orders o ON c.id = o.customer_id GROUP BY c.name;".to_string(),
+                category: QueryCategory::Join,
+            },
+            SampleQuery {
+                name: "Top N".to_string(),
+                description: "ORDER BY with LIMIT, executed as a Top-N operator".to_string(),
+                query: "SELECT * FROM orders ORDER BY total DESC LIMIT 10;".to_string(),
+                category: QueryCategory::Performance,
+            },
+            SampleQuery {
+                name: "Window Function".to_string(),
+                description: "Running total per customer".to_string(),
+                query: "SELECT customer_id, total, sum(total) OVER (PARTITION BY customer_id ORDER BY id) AS running_total FROM orders;".to_string(),
+                category: QueryCategory::Window,
+            },
+        ]
+    }
+
+    fn supports_feature(&self, feature: &DatabaseFeature) -> bool {
+        match feature {
+            DatabaseFeature::DetailedExecutionPlan => true,
+            DatabaseFeature::ActualRowCounts => true, // The profiler reports operator cardinalities
+            DatabaseFeature::CostEstimation => false, // DuckDB reports no plan costs
+            DatabaseFeature::IndexSuggestions => false,
+            DatabaseFeature::QueryOptimizationHints => false,
+            DatabaseFeature::ParallelExecution => true,
+            DatabaseFeature::PartitionedTables => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tls::TlsConfig;
+
+    async fn memory_engine() -> DuckDBEngine {
+        DuckDBEngine::new(ConnectionConfig {
+            engine_type: EngineType::DuckDB,
+            connection_string: "duckdb::memory:".to_string(),
+            max_connections: None,
+            timeout_seconds: None,
+            password_source: None,
+            tls: TlsConfig::default(),
+            search_path: None,
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_duckdb_engine_creation() {
+        let engine = memory_engine().await;
+        assert!(engine.database.is_none());
+
+        assert_eq!(
+            database_path("duckdb:///data/analytics.duckdb"),
+            Some(PathBuf::from("/data/analytics.duckdb"))
+        );
+        assert_eq!(
+            database_path("local.duckdb"),
+            Some(PathBuf::from("local.duckdb"))
+        );
+
+        let missing = DuckDBEngine::new(ConnectionConfig {
+            connection_string: "/nonexistent/dir/data.duckdb".to_string(),
+            ..engine.config.clone()
+        })
+        .await;
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_check_single_query() {
+        assert!(check_single_query("SELECT * FROM read_parquet('trips.parquet');").is_ok());
+        let chained = check_single_query("SELECT 1; COPY (SELECT 1) TO '/tmp/out.csv'");
+        assert!(chained
+            .unwrap_err()
+            .to_string()
+            .contains("single statement"));
+        assert!(check_single_query("ATTACH '/tmp/other.duckdb'").is_err());
+        assert!(check_single_query("SELECT (").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parse_duckdb_profile() {
+        let engine = memory_engine().await;
+        let profile = json!({
+            "query_name": "EXPLAIN ANALYZE SELECT ...",
+            "latency": 0.012,
+            "children": [{
+                "operator_name": "EXPLAIN_ANALYZE",
+                "operator_timing": 0.0,
+                "operator_cardinality": 0,
+                "children": [{
+                    "operator_name": "HASH_GROUP_BY",
+                    "operator_timing": 0.002,
+                    "operator_cardinality": 3,
+                    "extra_info": {"Groups": "#0", "Aggregates": "avg(#1)"},
+                    "children": [{
+                        "operator_name": "TABLE_SCAN",
+                        "operator_timing": 0.008,
+                        "operator_cardinality": 5000,
+                        "extra_info": {
+                            "Function": "READ_PARQUET",
+                            "Filters": "total_amount>0",
+                            "Estimated Cardinality": "~5000"
+                        },
+                        "children": []
+                    }]
+                }]
+            }]
+        });
+
+        let plan = engine.parse_duckdb_profile(&profile).unwrap();
+        assert_eq!(plan.execution_time, 12.0);
+
+        let aggregate = &plan.root;
+        assert_eq!(aggregate.node_type, "Aggregate");
+        assert_eq!(aggregate.strategy.as_deref(), Some("Hashed"));
+        assert_eq!(aggregate.actual_rows, 3);
+        assert!((aggregate.actual_total_time - 10.0).abs() < 1e-9);
+
+        let scan = &aggregate.plans[0];
+        assert_eq!(scan.node_type, "Function Scan");
+        assert_eq!(scan.actual_rows, 5000);
+        assert_eq!(scan.extra["Plan Rows"], 5000);
+        assert_eq!(scan.filter.as_deref(), Some("total_amount>0"));
+        assert_eq!(scan.extra["Function Name"], "READ_PARQUET");
+
+        // Before 1.1, operators had a name, timing and free-text extra_info
+        let legacy = json!({
+            "result": 0.001,
+            "children": [{
+                "name": "SEQ_SCAN ",
+                "timing": 0.001,
+                "cardinality": 10,
+                "extra_info": "orders\nid\n",
+                "children": []
+            }]
+        });
+        let plan = engine.parse_duckdb_profile(&legacy).unwrap();
+        assert_eq!(plan.root.node_type, "Seq Scan");
+        assert_eq!(plan.root.extra["Detail"], "orders\nid");
+
+        assert!(engine.parse_duckdb_profile(&json!({})).is_err());
+    }
+
+    #[test]
+    fn test_parse_rows_keeps_column_order() {
+        let rows = parse_rows(r#"[{"z":1,"a":"x"},{"z":2,"a":null}]"#).unwrap();
+        assert_eq!(
+            rows,
+            vec![vec![json!(1), json!("x")], vec![json!(2), Value::Null]]
+        );
+        assert!(parse_rows("\n").unwrap().is_empty());
+        assert_eq!(title_case("STREAMING_SAMPLE"), "Streaming Sample");
+    }
+}
@@ -0,0 +1,583 @@
+//! MariaDB database engine implementation
+//!
+//! MariaDB speaks the MySQL protocol but its plan JSON differs from MySQL 8:
+//! `ANALYZE FORMAT=JSON` executes the query and annotates each step with
+//! `r_`-prefixed actuals (`r_rows`, `r_loops`, `r_filtered`,
+//! `r_total_time_ms`), and sorting and temporary tables appear as `filesort`
+//! and `temporary_table` wrappers. This engine maps those onto [`PlanNode`]
+//! so plans carry actual row counts and timings like PostgreSQL's
+//! `EXPLAIN ANALYZE`.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use sqlx::{Executor, MySqlPool, Row};
+
+use super::mysql::{connect_lazy, fetch_all_rolled_back, number, scan_node_type};
+use super::{
+    count_query, count_rows_rolled_back, json_rows_query, ConnectionConfig, DatabaseEngine,
+    DatabaseFeature, DatabaseInfo, EngineError, EngineType, QueryCategory, SampleQuery,
+};
+use crate::db::credentials::redact_message;
+use crate::db::models::{ExecutionPlan, PlanNode};
+
+/// Keys under which a query block or operation nests the work it wraps
+const OPERATION_KEYS: &[&str] = &[
+    "filesort",
+    "read_sorted_file",
+    "temporary_table",
+    "duplicates_removal",
+    "union_result",
+    "nested_loop",
+    "block-nl-join",
+    "table",
+];
+
+/// MariaDB database engine implementation
+#[derive(Debug)]
+pub struct MariaDBEngine {
+    pool: MySqlPool,
+    #[allow(dead_code)]
+    config: ConnectionConfig,
+}
+
+impl MariaDBEngine {
+    /// Create a new MariaDB engine instance
+    ///
+    /// Connections are opened on first use; call
+    /// [`DatabaseEngine::test_connection`] to check the server is reachable.
+    pub async fn new(config: ConnectionConfig) -> Result<Self, EngineError> {
+        let pool = connect_lazy(&config)?;
+        Ok(Self { pool, config })
+    }
+
+    /// The underlying connection pool
+    pub fn pool(&self) -> &MySqlPool {
+        &self.pool
+    }
+
+    /// Convert MariaDB `ANALYZE FORMAT=JSON` output to our unified ExecutionPlan format
+    fn parse_mariadb_analyze(&self, analyze_result: &Value) -> Result<ExecutionPlan, EngineError> {
+        let query_block = analyze_result
+            .get("query_block")
+            .and_then(Value::as_object)
+            .ok_or_else(|| {
+                EngineError::PlanParsing("Missing query_block in ANALYZE output".to_string())
+            })?;
+
+        let root = query_block_node(query_block);
+        Ok(ExecutionPlan {
+            execution_time: root.total_time(),
+            planning_time: 0.0,
+            root,
+            settings: None,
+            jit: None,
+        })
+    }
+}
+
+/// The node for a `query_block`, with the work it contains as its child
+fn query_block_node(block: &Map<String, Value>) -> PlanNode {
+    let mut plans: Vec<PlanNode> = operation(block).into_iter().collect();
+    plans.extend(subqueries(block));
+
+    let mut extra = json!({});
+    if let Some(select_id) = block.get("select_id") {
+        extra["Select ID"] = select_id.clone();
+    }
+
+    let mut node = PlanNode {
+        node_type: "Query Block".to_string(),
+        total_cost: block
+            .get("cost")
+            .and_then(number)
+            .unwrap_or_else(|| plans.first().map_or(0.0, |p| p.total_cost)),
+        actual_rows: plans.first().map_or(0, PlanNode::total_rows),
+        filter: condition(block, "having_condition"),
+        plans,
+        extra,
+        ..Default::default()
+    };
+    set_actual_time(&mut node, block, "r_total_time_ms");
+    node
+}
+
+/// The first operation nested in `object`, in the order MariaDB applies them
+fn operation(object: &Map<String, Value>) -> Option<PlanNode> {
+    let (key, value) = OPERATION_KEYS
+        .iter()
+        .find_map(|key| object.get(*key).map(|value| (*key, value)))?;
+    let value = value.as_object();
+
+    match key {
+        "table" => value.map(table_node),
+        "block-nl-join" => value.map(block_join_node),
+        "nested_loop" => object
+            .get(key)
+            .and_then(Value::as_array)
+            .and_then(|steps| nested_loop(steps)),
+        "union_result" => value.map(union_node),
+        // Reading back sorted rows has no cost of its own
+        "read_sorted_file" => value.and_then(operation),
+        _ => value.map(|wrapped| wrapper_node(key, wrapped)),
+    }
+}
+
+/// Join a `nested_loop` list left-deep, the way MariaDB executes it
+fn nested_loop(steps: &[Value]) -> Option<PlanNode> {
+    let mut nodes = steps
+        .iter()
+        .filter_map(Value::as_object)
+        .filter_map(operation);
+    let first = nodes.next()?;
+
+    Some(nodes.fold(first, |outer, inner| {
+        // BNLH and BKAH join buffers are hashed
+        let hashed = inner
+            .extra
+            .get("Join Buffer")
+            .and_then(Value::as_str)
+            .is_some_and(|join_type| join_type.ends_with('H'));
+        PlanNode {
+            node_type: if hashed { "Hash Join" } else { "Nested Loop" }.to_string(),
+            total_cost: outer.total_cost + inner.total_cost,
+            actual_total_time: outer.total_time() + inner.total_time(),
+            actual_rows: inner.total_rows(),
+            actual_loops: 1,
+            plans: vec![outer, inner],
+            extra: json!({}),
+            ..Default::default()
+        }
+    }))
+}
+
+/// A table read through a join buffer
+fn block_join_node(join: &Map<String, Value>) -> PlanNode {
+    let mut node = join
+        .get("table")
+        .and_then(Value::as_object)
+        .map(table_node)
+        .unwrap_or_default();
+    if let Some(join_type) = join.get("join_type") {
+        node.extra["Join Buffer"] = join_type.clone();
+    }
+    node.join_filter = condition(join, "attached_condition");
+    node
+}
+
+/// A sort, temporary table, or de-duplication wrapping the operation inside it
+fn wrapper_node(key: &str, object: &Map<String, Value>) -> PlanNode {
+    let child = operation(object);
+
+    let node_type = match key {
+        "filesort" => "Sort",
+        "duplicates_removal" => "Unique",
+        _ => "Materialize",
+    };
+
+    let mut extra = json!({});
+    if let Some(sort_key) = object.get("sort_key") {
+        extra["Sort Key"] = sort_key.clone();
+    }
+    if key == "temporary_table" {
+        extra["Using Temporary Table"] = json!(true);
+    }
+
+    let child_rows = child.as_ref().map_or(0, PlanNode::total_rows);
+    let mut node = PlanNode {
+        node_type: node_type.to_string(),
+        total_cost: child.as_ref().map_or(0.0, |c| c.total_cost),
+        actual_rows: object
+            .get("r_output_rows")
+            .and_then(number)
+            .map_or(child_rows, |rows| rows as u64),
+        plans: child.into_iter().collect(),
+        extra,
+        ..Default::default()
+    };
+    set_actual_time(&mut node, object, "r_total_time_ms");
+    node
+}
+
+/// A `UNION`, with one child per query specification
+fn union_node(union: &Map<String, Value>) -> PlanNode {
+    let plans: Vec<PlanNode> = union
+        .get("query_specifications")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|spec| spec.get("query_block").and_then(Value::as_object))
+        .map(query_block_node)
+        .collect();
+
+    PlanNode {
+        node_type: "Append".to_string(),
+        total_cost: plans.iter().map(|p| p.total_cost).sum(),
+        actual_total_time: plans.iter().map(PlanNode::total_time).sum(),
+        actual_rows: plans.iter().map(PlanNode::total_rows).sum(),
+        actual_loops: 1,
+        plans,
+        extra: json!({}),
+        ..Default::default()
+    }
+}
+
+/// A table access with its estimated and actual row counts
+fn table_node(table: &Map<String, Value>) -> PlanNode {
+    let access_type = table
+        .get("access_type")
+        .and_then(Value::as_str)
+        .unwrap_or("ALL");
+    let covering = table
+        .get("using_index")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+
+    // r_rows counts rows read per loop; r_filtered is the share kept by the condition
+    let rows_read = table.get("r_rows").and_then(number).unwrap_or(0.0);
+    let filtered = table.get("r_filtered").and_then(number).unwrap_or(100.0);
+    let rows_out = (rows_read * filtered / 100.0).round();
+
+    let mut extra = json!({ "Access Type": access_type });
+    if let Some(rows) = table.get("rows").and_then(number) {
+        extra["Plan Rows"] = json!(rows as u64);
+    }
+    if let Some(key) = table.get("key") {
+        extra["Index Name"] = key.clone();
+    }
+    if let Some(possible_keys) = table.get("possible_keys") {
+        extra["Possible Keys"] = possible_keys.clone();
+    }
+    if let Some(filtered) = table.get("filtered").and_then(number) {
+        extra["Filtered Percent"] = json!(filtered);
+    }
+
+    let mut plans: Vec<PlanNode> = table
+        .get("materialized")
+        .and_then(|m| m.get("query_block"))
+        .and_then(Value::as_object)
+        .map(query_block_node)
+        .into_iter()
+        .collect();
+    plans.extend(subqueries(table));
+
+    let mut node = PlanNode {
+        node_type: scan_node_type(access_type, covering).to_string(),
+        relation_name: table
+            .get("table_name")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        total_cost: table.get("cost").and_then(number).unwrap_or(0.0),
+        actual_rows: rows_out as u64,
+        filter: condition(table, "attached_condition"),
+        plans,
+        extra,
+        ..Default::default()
+    };
+
+    if node.filter.is_some() {
+        node.rows_removed_by_filter = Some((rows_read - rows_out).max(0.0) as u64);
+    }
+
+    // Time is split between reading rows and evaluating conditions on them
+    let time_ms = ["r_table_time_ms", "r_other_time_ms"]
+        .iter()
+        .filter_map(|key| table.get(*key).and_then(number))
+        .sum::<f64>();
+    let loops = table.get("r_loops").and_then(number).unwrap_or(1.0) as u64;
+    node.actual_loops = loops.max(1);
+    node.actual_total_time = time_ms / node.actual_loops as f64;
+    node
+}
+
+/// The condition under `key`, as MariaDB prints it
+fn condition(object: &Map<String, Value>, key: &str) -> Option<String> {
+    object.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+/// Record a step's loops and total time, converting the time to a per-loop average
+///
+/// MariaDB reports total time across loops; `PlanNode` follows PostgreSQL in
+/// storing the per-loop average.
+fn set_actual_time(node: &mut PlanNode, object: &Map<String, Value>, time_key: &str) {
+    let loops = object.get("r_loops").and_then(number).unwrap_or(1.0) as u64;
+    node.actual_loops = loops.max(1);
+    node.actual_total_time = object
+        .get(time_key)
+        .and_then(number)
+        .unwrap_or_else(|| node.plans.iter().map(PlanNode::total_time).sum())
+        / node.actual_loops as f64;
+}
+
+/// Query blocks of subqueries attached to a table or query block
+fn subqueries(object: &Map<String, Value>) -> Vec<PlanNode> {
+    object
+        .get("subqueries")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|sub| {
+            sub.get("query_block")
+                .or_else(|| sub.pointer("/expression_cache/query_block"))
+                .and_then(Value::as_object)
+        })
+        .map(query_block_node)
+        .collect()
+}
+
+#[async_trait]
+impl DatabaseEngine for MariaDBEngine {
+    fn engine_type(&self) -> EngineType {
+        EngineType::MariaDB
+    }
+
+    async fn test_connection(&self) -> Result<bool, EngineError> {
+        match sqlx::query("SELECT 1").fetch_one(&self.pool).await {
+            Ok(_) => Ok(true),
+            Err(e) => Err(EngineError::Connection(format!(
+                "Connection test failed: {}",
+                redact_message(&e.to_string(), &self.config.connection_string)
+            ))),
+        }
+    }
+
+    async fn explain_query(&self, query: &str) -> Result<ExecutionPlan, EngineError> {
+        // ANALYZE runs the statement and reports actuals next to the estimates,
+        // so it runs in a read-only transaction that is rolled back
+        let analyze_query = format!("ANALYZE FORMAT=JSON {}", query);
+
+        let rows = fetch_all_rolled_back(&self.pool, "START TRANSACTION READ ONLY", &analyze_query)
+            .await
+            .map_err(|e| {
+                EngineError::QueryExecution(format!("Failed to execute ANALYZE: {}", e))
+            })?;
+        let row = rows
+            .first()
+            .ok_or_else(|| EngineError::PlanParsing("ANALYZE returned no rows".to_string()))?;
+
+        let analyze_text: String = row.try_get(0).map_err(|e| {
+            EngineError::PlanParsing(format!("Failed to get ANALYZE result: {}", e))
+        })?;
+        let analyze_json: Value = serde_json::from_str(&analyze_text).map_err(|e| {
+            EngineError::PlanParsing(format!("Failed to parse ANALYZE JSON: {}", e))
+        })?;
+
+        self.parse_mariadb_analyze(&analyze_json)
+    }
+
+    async fn validate_query(&self, query: &str) -> Result<(), EngineError> {
+        // EXPLAIN checks the statement against the schema without executing it
+        let explain_query = format!("EXPLAIN {}", query);
+
+        sqlx::query(&explain_query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| EngineError::QueryExecution(format!("Query validation failed: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn count_rows(&self, query: &str, timeout: Duration) -> Result<u64, EngineError> {
+        // MariaDB ignores MAX_EXECUTION_TIME, its limit is max_statement_time in seconds
+        let count = format!(
+            "SET STATEMENT max_statement_time = {} FOR {}",
+            timeout.as_secs_f64(),
+            count_query(query)
+        );
+        count_rows_rolled_back(&self.pool, &[], &count).await
+    }
+
+    async fn fetch_rows(&self, query: &str, limit: usize) -> Result<Vec<String>, EngineError> {
+        let columns = self
+            .pool
+            .describe(query)
+            .await
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to describe query: {}", e)))?
+            .columns()
+            .len();
+
+        sqlx::query(&json_rows_query(query, columns, limit))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to fetch rows: {}", e)))?
+            .iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<_, _>>()
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to read row: {}", e)))
+    }
+
+    async fn get_version_info(&self) -> Result<DatabaseInfo, EngineError> {
+        let version_row = sqlx::query("SELECT VERSION()")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| EngineError::Connection(format!("Failed to get version: {}", e)))?;
+
+        let version: String = version_row
+            .try_get(0)
+            .map_err(|e| EngineError::Connection(format!("Failed to parse version: {}", e)))?;
+
+        Ok(DatabaseInfo {
+            engine_type: EngineType::MariaDB,
+            version,
+            connection_status: "Connected".to_string(),
+            health: None,
+            socket: self.pool.connect_options().get_socket().cloned(),
+            schema: None,
+            search_path: None,
+            features_supported: vec![
+                DatabaseFeature::DetailedExecutionPlan,
+                DatabaseFeature::ActualRowCounts,
+                DatabaseFeature::QueryOptimizationHints,
+                DatabaseFeature::PartitionedTables,
+            ],
+        })
+    }
+
+    fn get_sample_queries(&self) -> Vec<SampleQuery> {
+        vec![
+            SampleQuery {
+                name: "Simple Select".to_string(),
+                description: "Basic MariaDB table scan".to_string(),
+                query: "SELECT * FROM customers WHERE country = 'USA';".to_string(),
+                category: QueryCategory::BasicSelect,
+            },
+            SampleQuery {
+                name: "Inner Join".to_string(),
+                description: "MariaDB join, possibly through a join buffer".to_string(),
+                query: "SELECT c.name, o.total FROM customers c INNER JOIN orders o ON c.id = o.customer_id WHERE o.total > 100;".to_string(),
+                category: QueryCategory::Join,
+            },
+            SampleQuery {
+                name: "Aggregation with Sort".to_string(),
+                description: "MariaDB grouping through a temporary table and filesort".to_string(),
+                query: "SELECT category, COUNT(*) as count, AVG(price) as avg_price FROM products GROUP BY category ORDER BY avg_price DESC;".to_string(),
+                category: QueryCategory::Aggregation,
+            },
+            SampleQuery {
+                name: "Subquery with EXISTS".to_string(),
+                description: "MariaDB subquery with expression cache".to_string(),
+                query: "SELECT * FROM customers c WHERE EXISTS (SELECT 1 FROM orders o WHERE o.customer_id = c.id AND o.total > 1000);".to_string(),
+                category: QueryCategory::Subquery,
+            },
+        ]
+    }
+
+    fn supports_feature(&self, feature: &DatabaseFeature) -> bool {
+        match feature {
+            DatabaseFeature::DetailedExecutionPlan => true,
+            DatabaseFeature::ActualRowCounts => true, // ANALYZE FORMAT=JSON reports r_rows
+            DatabaseFeature::CostEstimation => false, // Costs are only reported from MariaDB 11
+            DatabaseFeature::IndexSuggestions => false,
+            DatabaseFeature::QueryOptimizationHints => true,
+            DatabaseFeature::ParallelExecution => false,
+            DatabaseFeature::PartitionedTables => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tls::TlsConfig;
+
+    async fn test_engine() -> MariaDBEngine {
+        MariaDBEngine::new(ConnectionConfig {
+            engine_type: EngineType::MariaDB,
+            connection_string: "mariadb://test".to_string(),
+            max_connections: None,
+            timeout_seconds: None,
+            password_source: None,
+            tls: TlsConfig::default(),
+            search_path: None,
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_parse_mariadb_analyze() {
+        let engine = test_engine().await;
+        let analyze = json!({
+            "query_block": {
+                "select_id": 1,
+                "r_loops": 1,
+                "r_total_time_ms": 12.5,
+                "filesort": {
+                    "sort_key": "o.total desc",
+                    "r_loops": 1,
+                    "r_total_time_ms": 1.5,
+                    "r_output_rows": 40,
+                    "temporary_table": {
+                        "nested_loop": [
+                            {"table": {
+                                "table_name": "c",
+                                "access_type": "ALL",
+                                "r_loops": 1,
+                                "rows": 1000,
+                                "r_rows": 1000,
+                                "r_table_time_ms": 2.0,
+                                "r_other_time_ms": 0.5,
+                                "filtered": 100,
+                                "r_filtered": 10,
+                                "attached_condition": "c.country = 'USA'"
+                            }},
+                            {"block-nl-join": {
+                                "table": {
+                                    "table_name": "o",
+                                    "access_type": "ALL",
+                                    "r_loops": 4,
+                                    "rows": 5000,
+                                    "r_rows": 5000,
+                                    "r_table_time_ms": 8.0,
+                                    "r_filtered": 100
+                                },
+                                "buffer_type": "flat",
+                                "join_type": "BNL",
+                                "attached_condition": "o.customer_id = c.`id`"
+                            }}
+                        ]
+                    }
+                }
+            }
+        });
+
+        let plan = engine.parse_mariadb_analyze(&analyze).unwrap();
+        assert_eq!(plan.execution_time, 12.5);
+
+        let sort = &plan.root.plans[0];
+        assert_eq!(sort.node_type, "Sort");
+        assert_eq!(sort.actual_rows, 40);
+        assert_eq!(sort.extra["Sort Key"], "o.total desc");
+
+        let temporary = &sort.plans[0];
+        assert_eq!(temporary.node_type, "Materialize");
+
+        let join = &temporary.plans[0];
+        assert_eq!(join.node_type, "Nested Loop");
+        assert_eq!(join.actual_rows, 20000);
+
+        let (customers, orders) = (&join.plans[0], &join.plans[1]);
+        assert_eq!(customers.node_type, "Seq Scan");
+        assert_eq!(customers.actual_rows, 100);
+        assert_eq!(customers.actual_total_time, 2.5);
+        assert_eq!(customers.rows_removed_by_filter, Some(900));
+        assert_eq!(orders.relation_name.as_deref(), Some("o"));
+        assert_eq!(orders.actual_loops, 4);
+        assert_eq!(orders.actual_total_time, 2.0);
+        assert_eq!(
+            orders.join_filter.as_deref(),
+            Some("o.customer_id = c.`id`")
+        );
+
+        assert!(engine.parse_mariadb_analyze(&json!({})).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mariadb_sample_queries() {
+        let engine = test_engine().await;
+        let samples = engine.get_sample_queries();
+        assert!(!samples.is_empty());
+        assert!(engine.supports_feature(&DatabaseFeature::ActualRowCounts));
+    }
+}
@@ -1,57 +1,642 @@
 //! MySQL database engine implementation
+//!
+//! MySQL's `EXPLAIN FORMAT=JSON` describes a query as nested `query_block`
+//! objects rather than a tree of plan nodes: joins are flat `nested_loop`
+//! lists, and sorting, grouping, and de-duplication wrap the tables they
+//! apply to. This engine maps that structure onto the unified [`PlanNode`]
+//! tree so the advisor and web UI can treat it like a PostgreSQL plan.
+//!
+//! MySQL only reports estimates in JSON format, so queries are explained
+//! with `EXPLAIN ANALYZE` where the server supports it (8.0.18 and later,
+//! going by the version the server reports on first use).
+//! Its output is a text tree of iterators in `FORMAT=TREE`, each line giving
+//! the estimated cost and rows followed by the measured time, rows and loops,
+//! which are mapped onto nodes the same way. Older servers fall back to the
+//! JSON estimates, where row counts are estimates and timings are zero.
+
+use std::time::Duration;
 
 use async_trait::async_trait;
-use serde_json::Value;
+use serde_json::{json, Map, Value};
+use sqlx::{
+    mysql::{MySqlConnectOptions, MySqlDatabaseError, MySqlPoolOptions, MySqlRow},
+    Executor, MySqlPool, Row,
+};
+use tokio::sync::OnceCell;
 
+use super::version::{self, ServerVersion};
 use super::{
-    ConnectionConfig, DatabaseEngine, DatabaseFeature, DatabaseInfo, EngineError, EngineType,
-    QueryCategory, SampleQuery,
+    check_socket, count_query, count_rows_rolled_back, json_rows_query, ConnectionConfig,
+    DatabaseEngine, DatabaseFeature, DatabaseInfo, EngineError, EngineType, QueryCategory,
+    SampleQuery,
 };
+use crate::db::credentials::redact_message;
 use crate::db::models::{ExecutionPlan, PlanNode};
 
+/// Keys under which a query block or operation nests the work it wraps
+const OPERATION_KEYS: &[&str] = &[
+    "ordering_operation",
+    "grouping_operation",
+    "duplicates_removal",
+    "windowing",
+    "buffer_result",
+    "union_result",
+    "nested_loop",
+    "table",
+];
+
+/// First MySQL release with `EXPLAIN ANALYZE`
+const EXPLAIN_ANALYZE_VERSION: ServerVersion = ServerVersion::new(8, 0, 18);
+
 /// MySQL database engine implementation
 #[derive(Debug)]
 pub struct MySQLEngine {
+    pool: MySqlPool,
     #[allow(dead_code)]
     config: ConnectionConfig,
+    server_version: OnceCell<ServerVersion>,
 }
 
 impl MySQLEngine {
     /// Create a new MySQL engine instance
+    ///
+    /// Connections are opened on first use; call
+    /// [`DatabaseEngine::test_connection`] to check the server is reachable.
     pub async fn new(config: ConnectionConfig) -> Result<Self, EngineError> {
-        // Note: This is a placeholder implementation
-        // In a real implementation, you would establish a MySQL connection here
-        Ok(Self { config })
+        let pool = connect_lazy(&config)?;
+        Ok(Self {
+            pool,
+            config,
+            server_version: OnceCell::new(),
+        })
     }
 
-    /// Convert MySQL EXPLAIN output to our unified ExecutionPlan format
-    #[allow(dead_code)]
-    fn parse_mysql_explain(&self, _explain_result: &Value) -> Result<ExecutionPlan, EngineError> {
-        // MySQL EXPLAIN output is different from PostgreSQL
-        // This is a simplified conversion - real implementation would be more complex
-
-        let root_node = PlanNode {
-            node_type: "MySQL Query".to_string(),
-            relation_name: None,
-            alias: None,
-            startup_cost: 0.0,
-            total_cost: 0.0,
-            actual_startup_time: Some(0.0),
-            actual_total_time: 0.0,
-            actual_rows: 0,
-            actual_loops: 1,
-            plans: vec![],
-            extra: serde_json::json!({}),
-        };
+    /// The underlying connection pool
+    pub fn pool(&self) -> &MySqlPool {
+        &self.pool
+    }
+
+    /// Version of the server, read from `VERSION()` when first needed; `None` if it couldn't be read
+    pub async fn server_version(&self) -> Option<ServerVersion> {
+        version::detect(&self.server_version, || async {
+            let version: String = sqlx::query_scalar("SELECT VERSION()")
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            ServerVersion::parse(&version)
+                .ok_or_else(|| format!("unexpected version '{}'", version))
+        })
+        .await
+    }
+
+    /// Convert MySQL `EXPLAIN FORMAT=JSON` output to our unified ExecutionPlan format
+    fn parse_mysql_explain(&self, explain_result: &Value) -> Result<ExecutionPlan, EngineError> {
+        let query_block = explain_result
+            .get("query_block")
+            .and_then(Value::as_object)
+            .ok_or_else(|| {
+                EngineError::PlanParsing("Missing query_block in EXPLAIN output".to_string())
+            })?;
 
         Ok(ExecutionPlan {
-            root: root_node,
+            root: query_block_node(query_block),
             planning_time: 0.0,
             execution_time: 0.0,
+            settings: None,
+            jit: None,
         })
     }
 }
 
+/// Build a plan from the text of MySQL's `EXPLAIN ANALYZE` (`FORMAT=TREE`)
+///
+/// Every iterator is a line starting with `-> `, indented four spaces per
+/// level below the iterator that reads from it.
+pub fn parse_mysql_tree(text: &str) -> Result<ExecutionPlan, EngineError> {
+    // Nodes whose children may still follow, with their depth
+    let mut open: Vec<(usize, PlanNode)> = Vec::new();
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let Some(iterator) = trimmed.strip_prefix("->") else {
+            continue;
+        };
+        let depth = (line.len() - trimmed.len()) / 4;
+        if depth > 0 && open.is_empty() {
+            return Err(EngineError::PlanParsing(format!(
+                "MySQL plan line has no parent: {}",
+                line
+            )));
+        }
+
+        let node = tree_node(iterator.trim());
+        close_deeper(&mut open, depth);
+        open.push((depth, node));
+    }
+
+    close_deeper(&mut open, 0);
+    let (_, root) = open
+        .pop()
+        .ok_or_else(|| EngineError::PlanParsing("Empty MySQL plan".to_string()))?;
+
+    Ok(ExecutionPlan {
+        execution_time: root.actual_total_time * root.actual_loops as f64,
+        planning_time: 0.0,
+        root,
+        settings: None,
+        jit: None,
+    })
+}
+
+/// Attach every open node at or below `depth` to its parent
+///
+/// Shared with the TiDB engine, whose plans are also indented trees.
+pub(super) fn close_deeper(open: &mut Vec<(usize, PlanNode)>, depth: usize) {
+    while open.len() > 1 && open.last().is_some_and(|(d, _)| *d >= depth) {
+        let (_, child) = open.pop().expect("checked length");
+        open.last_mut().expect("checked length").1.plans.push(child);
+    }
+}
+
+/// Convert one iterator line, without its `-> ` prefix
+///
+/// A line reads `description  (cost=1.2..3.4 rows=10) (actual time=0.1..0.5
+/// rows=8 loops=1)`; either group may be missing, and iterators that never
+/// ran report `(never executed)` instead of actuals.
+fn tree_node(line: &str) -> PlanNode {
+    let mut description = line;
+    let mut estimate = None;
+    let mut actual = None;
+    let mut never_executed = false;
+    while let Some((rest, group)) = trailing_group(description) {
+        if group.starts_with("cost=") || group.starts_with("rows=") {
+            estimate = Some(group);
+        } else if let Some(measured) = group.strip_prefix("actual ") {
+            actual = Some(measured);
+        } else if group == "never executed" {
+            never_executed = true;
+        } else {
+            break;
+        }
+        description = rest;
+    }
+    let description = description.trim();
+
+    let mut node = PlanNode {
+        actual_loops: 1,
+        extra: json!({ "Iterator": description }),
+        ..PlanNode::default()
+    };
+    describe_iterator(description, &mut node);
+
+    for (key, value) in estimate.into_iter().flat_map(tree_stats) {
+        match key {
+            "cost" => {
+                let (startup, total) = value.split_once("..").unwrap_or(("0", value));
+                node.startup_cost = startup.parse().unwrap_or(0.0);
+                node.total_cost = total.parse().unwrap_or(0.0);
+            }
+            "rows" => {
+                if let Ok(rows) = value.parse::<f64>() {
+                    node.extra["Plan Rows"] = json!(rows);
+                }
+            }
+            _ => {}
+        }
+    }
+    for (key, value) in actual.into_iter().flat_map(tree_stats) {
+        match key {
+            "time" => {
+                let (first, last) = value.split_once("..").unwrap_or((value, value));
+                node.actual_startup_time = first.parse().ok();
+                node.actual_total_time = last.parse().unwrap_or(0.0);
+            }
+            "rows" => node.actual_rows = value.parse::<f64>().unwrap_or(0.0).round() as u64,
+            "loops" => node.actual_loops = value.parse().unwrap_or(1),
+            _ => {}
+        }
+    }
+    if never_executed {
+        node.actual_loops = 0;
+        node.extra["Never Executed"] = json!(true);
+    }
+    node
+}
+
+/// Split a trailing parenthesized group off `text`, returning the text before it and its contents
+fn trailing_group(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim_end();
+    let inner = text.strip_suffix(')')?;
+    let mut nesting = 0usize;
+    for (i, c) in inner.char_indices().rev() {
+        match c {
+            ')' => nesting += 1,
+            '(' if nesting == 0 => return Some((&text[..i], &inner[i + 1..])),
+            '(' => nesting -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The `key=value` pairs of a cost or actuals group
+fn tree_stats(group: &str) -> impl Iterator<Item = (&str, &str)> {
+    group
+        .split_whitespace()
+        .filter_map(|pair| pair.split_once('='))
+}
+
+/// Set the node type, relation and details of `node` from an iterator description
+fn describe_iterator(description: &str, node: &mut PlanNode) {
+    let (name, detail) = match description.split_once(": ") {
+        Some((name, detail)) => (name, Some(detail)),
+        None => (description, None),
+    };
+
+    // Table access: "Index lookup on o using idx_customer (customer_id=c.id)"
+    if let Some((access, target)) = name.split_once(" on ") {
+        let access = access.to_ascii_lowercase();
+        let covering = access.contains("covering ");
+        let access_type = match access.replace("covering ", "").as_str() {
+            "table scan" => Some("ALL"),
+            "index scan" => Some("index"),
+            "index range scan" => Some("range"),
+            "index lookup" => Some("ref"),
+            "single-row index lookup" => Some("eq_ref"),
+            "full-text index search" => Some("fulltext"),
+            _ => None,
+        };
+        if let Some(access_type) = access_type {
+            let mut words = target.split_whitespace();
+            let table = words.next().unwrap_or_default().to_string();
+            if words.next() == Some("using") {
+                if let Some(index) = words.next() {
+                    node.extra["Index Name"] = json!(index);
+                }
+            }
+            node.node_type = scan_node_type(access_type, covering).to_string();
+            node.extra["Access Type"] = json!(access_type);
+            node.relation_name = Some(table.clone());
+            node.alias = Some(table);
+            return;
+        }
+    }
+
+    let lower = name.to_ascii_lowercase();
+    let node_type = if lower.starts_with("nested loop") {
+        "Nested Loop"
+    } else if lower.contains("hash join")
+        || lower.starts_with("hash semijoin")
+        || lower.starts_with("hash antijoin")
+    {
+        "Hash Join"
+    } else if lower == "hash" {
+        "Hash"
+    } else if lower.starts_with("filter") {
+        "Filter"
+    } else if lower.starts_with("sort") {
+        "Sort"
+    } else if lower.starts_with("limit") {
+        "Limit"
+    } else if lower == "aggregate using temporary table" {
+        "HashAggregate"
+    } else if lower.starts_with("window") {
+        "WindowAgg"
+    } else if lower.contains("aggregate") {
+        "Aggregate"
+    } else if lower.starts_with("materialize") || lower.starts_with("temporary table") {
+        "Materialize"
+    } else if lower.starts_with("append") || lower.starts_with("union") {
+        "Append"
+    } else {
+        name
+    };
+    node.node_type = node_type.to_string();
+
+    if node_type == "Nested Loop" || node_type == "Hash Join" {
+        let join_type = if lower.contains("left") {
+            "Left"
+        } else if lower.contains("semijoin") {
+            "Semi"
+        } else if lower.contains("antijoin") {
+            "Anti"
+        } else {
+            "Inner"
+        };
+        node.extra["Join Type"] = json!(join_type);
+    }
+    match (node_type, detail) {
+        ("Filter", Some(condition)) => node.filter = Some(condition.to_string()),
+        ("Sort", Some(keys)) => node.extra["Sort Key"] = json!(keys),
+        (_, Some(detail)) => node.extra["Detail"] = json!(detail),
+        _ => {}
+    }
+}
+
+/// Create a pool for a MySQL-protocol server without connecting yet
+///
+/// Shared with the MariaDB engine, which speaks the same protocol.
+pub(super) fn connect_lazy(config: &ConnectionConfig) -> Result<MySqlPool, EngineError> {
+    let connection_error = |e: sqlx::Error| {
+        EngineError::Connection(format!(
+            "Failed to connect to {}: {}",
+            config.engine_type,
+            redact_message(&e.to_string(), &config.connection_string)
+        ))
+    };
+
+    let mut options: MySqlConnectOptions =
+        config.connection_string.parse().map_err(connection_error)?;
+    if let Some(source) = &config.password_source {
+        let password = source
+            .resolve()
+            .map_err(|e| EngineError::Configuration(e.to_string()))?;
+        options = options.password(&password);
+    }
+    config.tls.validate().map_err(EngineError::Configuration)?;
+    let options = config.tls.apply_mysql(options);
+    if let Some(socket) = options.get_socket() {
+        check_socket(socket)?;
+    }
+
+    let mut pool_options = MySqlPoolOptions::new();
+    if let Some(max_connections) = config.max_connections {
+        pool_options = pool_options.max_connections(max_connections);
+    }
+    if let Some(timeout) = config.timeout_seconds {
+        pool_options = pool_options.acquire_timeout(std::time::Duration::from_secs(timeout));
+    }
+    Ok(pool_options.connect_lazy_with(options))
+}
+
+/// Run `sql` inside a transaction opened with `begin` and always roll it back
+///
+/// Used for statements that execute the query they analyze, so that nothing
+/// they do persists. The transaction is opened by hand because `SET TRANSACTION
+/// READ ONLY` only applies to the next transaction, not to one `Pool::begin`
+/// already started.
+pub(super) async fn fetch_all_rolled_back(
+    pool: &MySqlPool,
+    begin: &str,
+    sql: &str,
+) -> Result<Vec<MySqlRow>, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    conn.execute(begin).await?;
+    let rows = sqlx::query(sql).fetch_all(&mut *conn).await;
+    if conn.execute("ROLLBACK").await.is_err() {
+        // Never hand a connection with an open transaction back to the pool
+        let _ = conn.close().await;
+    }
+    rows
+}
+
+/// Whether the server rejected a statement as a syntax error (`ER_PARSE_ERROR`)
+fn is_parse_error(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .and_then(|e| e.try_downcast_ref::<MySqlDatabaseError>())
+        .is_some_and(|e| e.number() == 1064)
+}
+
+/// The node for a `query_block`, with the work it contains as its child
+fn query_block_node(block: &Map<String, Value>) -> PlanNode {
+    let mut plans: Vec<PlanNode> = operation(block).into_iter().collect();
+    plans.extend(subqueries(block));
+
+    let total_cost = block
+        .get("cost_info")
+        .and_then(Value::as_object)
+        .and_then(|info| cost(info, "query_cost"))
+        .unwrap_or_else(|| plans.first().map_or(0.0, |p| p.total_cost));
+    let mut extra = json!({});
+    if let Some(select_id) = block.get("select_id") {
+        extra["Select ID"] = select_id.clone();
+    }
+
+    PlanNode {
+        node_type: "Query Block".to_string(),
+        total_cost,
+        actual_rows: plans.first().map_or(0, |p| p.actual_rows),
+        actual_loops: 1,
+        plans,
+        extra,
+        ..Default::default()
+    }
+}
+
+/// The first operation nested in `object`, in the order MySQL applies them
+fn operation(object: &Map<String, Value>) -> Option<PlanNode> {
+    let (key, value) = OPERATION_KEYS
+        .iter()
+        .find_map(|key| object.get(*key).map(|value| (*key, value)))?;
+
+    match key {
+        "table" => value.as_object().map(table_node),
+        "nested_loop" => value.as_array().and_then(|tables| nested_loop(tables)),
+        "union_result" => value.as_object().map(union_node),
+        _ => {
+            let wrapped = value.as_object()?;
+            let child = operation(wrapped);
+            wrapper_node(key, wrapped, child)
+        }
+    }
+}
+
+/// Join a `nested_loop` list left-deep, the way MySQL executes it
+fn nested_loop(tables: &[Value]) -> Option<PlanNode> {
+    let mut nodes = tables
+        .iter()
+        .filter_map(Value::as_object)
+        .filter_map(operation);
+    let first = nodes.next()?;
+
+    Some(nodes.fold(first, |outer, inner| {
+        let total_cost = inner
+            .extra
+            .get("Prefix Cost")
+            .and_then(Value::as_f64)
+            .unwrap_or(outer.total_cost + inner.total_cost);
+        PlanNode {
+            node_type: "Nested Loop".to_string(),
+            total_cost,
+            actual_rows: inner.actual_rows,
+            actual_loops: 1,
+            plans: vec![outer, inner],
+            extra: json!({}),
+            ..Default::default()
+        }
+    }))
+}
+
+/// A sort, aggregate, or similar node wrapping `child`
+///
+/// Ordering satisfied by an index has no node of its own.
+fn wrapper_node(
+    key: &str,
+    object: &Map<String, Value>,
+    child: Option<PlanNode>,
+) -> Option<PlanNode> {
+    let flag = |name: &str| object.get(name).and_then(Value::as_bool).unwrap_or(false);
+
+    let node_type = match key {
+        "ordering_operation" if flag("using_filesort") => "Sort",
+        "ordering_operation" => return child,
+        "grouping_operation" => "Aggregate",
+        "duplicates_removal" => "Unique",
+        "windowing" => "WindowAgg",
+        _ => "Materialize",
+    };
+
+    let mut extra = json!({});
+    if flag("using_temporary_table") {
+        extra["Using Temporary Table"] = json!(true);
+    }
+    if flag("using_filesort") {
+        extra["Using Filesort"] = json!(true);
+    }
+
+    let child_cost = child.as_ref().map_or(0.0, |c| c.total_cost);
+    let own_cost = object
+        .get("cost_info")
+        .and_then(Value::as_object)
+        .and_then(|info| cost(info, "sort_cost"))
+        .unwrap_or(0.0);
+
+    Some(PlanNode {
+        node_type: node_type.to_string(),
+        total_cost: child_cost + own_cost,
+        actual_rows: child.as_ref().map_or(0, |c| c.actual_rows),
+        actual_loops: 1,
+        plans: child.into_iter().collect(),
+        extra,
+        ..Default::default()
+    })
+}
+
+/// A `UNION`, with one child per query specification
+fn union_node(union: &Map<String, Value>) -> PlanNode {
+    let plans: Vec<PlanNode> = union
+        .get("query_specifications")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|spec| spec.get("query_block").and_then(Value::as_object))
+        .map(query_block_node)
+        .collect();
+
+    PlanNode {
+        node_type: "Append".to_string(),
+        total_cost: plans.iter().map(|p| p.total_cost).sum(),
+        actual_rows: plans.iter().map(|p| p.actual_rows).sum(),
+        actual_loops: 1,
+        plans,
+        extra: json!({}),
+        ..Default::default()
+    }
+}
+
+/// A table access, named after the closest PostgreSQL scan type
+fn table_node(table: &Map<String, Value>) -> PlanNode {
+    let access_type = table
+        .get("access_type")
+        .and_then(Value::as_str)
+        .unwrap_or("ALL");
+    let covering = table
+        .get("using_index")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let node_type = scan_node_type(access_type, covering);
+
+    let cost_info = table.get("cost_info").and_then(Value::as_object);
+    let own_cost = cost_info.map_or(0.0, |info| {
+        cost(info, "read_cost").unwrap_or(0.0) + cost(info, "eval_cost").unwrap_or(0.0)
+    });
+    let rows = table
+        .get("rows_produced_per_join")
+        .and_then(number)
+        .unwrap_or(0.0) as u64;
+
+    let mut extra = json!({ "Access Type": access_type, "Plan Rows": rows });
+    if let Some(examined) = table.get("rows_examined_per_scan").and_then(number) {
+        extra["Rows Examined"] = json!(examined as u64);
+    }
+    if let Some(prefix_cost) = cost_info.and_then(|info| cost(info, "prefix_cost")) {
+        extra["Prefix Cost"] = json!(prefix_cost);
+    }
+    if let Some(key) = table.get("key") {
+        extra["Index Name"] = key.clone();
+    }
+    if let Some(possible_keys) = table.get("possible_keys") {
+        extra["Possible Keys"] = possible_keys.clone();
+    }
+    if let Some(filtered) = table.get("filtered").and_then(number) {
+        extra["Filtered Percent"] = json!(filtered);
+    }
+
+    let mut plans: Vec<PlanNode> = table
+        .get("materialized_from_subquery")
+        .and_then(|m| m.get("query_block"))
+        .and_then(Value::as_object)
+        .map(query_block_node)
+        .into_iter()
+        .collect();
+    plans.extend(subqueries(table));
+
+    PlanNode {
+        node_type: node_type.to_string(),
+        relation_name: table
+            .get("table_name")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        total_cost: own_cost,
+        actual_rows: rows,
+        actual_loops: 1,
+        filter: table
+            .get("attached_condition")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        plans,
+        extra,
+        ..Default::default()
+    }
+}
+
+/// The PostgreSQL scan type closest to a MySQL or MariaDB `access_type`
+pub(super) fn scan_node_type(access_type: &str, covering: bool) -> &'static str {
+    match access_type {
+        "ALL" => "Seq Scan",
+        "index" | "range" | "ref" | "eq_ref" | "ref_or_null" | "const" | "system" if covering => {
+            "Index Only Scan"
+        }
+        "index" => "Full Index Scan",
+        "index_merge" => "Index Merge",
+        _ => "Index Scan",
+    }
+}
+
+/// Query blocks of subqueries attached to a table or query block
+fn subqueries(object: &Map<String, Value>) -> Vec<PlanNode> {
+    ["attached_subqueries", "optimized_away_subqueries"]
+        .iter()
+        .filter_map(|key| object.get(*key).and_then(Value::as_array))
+        .flatten()
+        .filter_map(|sub| sub.get("query_block").and_then(Value::as_object))
+        .map(query_block_node)
+        .collect()
+}
+
+/// A `cost_info` figure; MySQL reports costs as strings
+fn cost(cost_info: &Map<String, Value>, key: &str) -> Option<f64> {
+    cost_info.get(key).and_then(number)
+}
+
+/// A JSON number, or a string holding one
+pub(super) fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        other => other.as_f64(),
+    }
+}
+
 #[async_trait]
 impl DatabaseEngine for MySQLEngine {
     fn engine_type(&self) -> EngineType {
@@ -59,34 +644,129 @@ impl DatabaseEngine for MySQLEngine {
     }
 
     async fn test_connection(&self) -> Result<bool, EngineError> {
-        // Placeholder implementation
-        Err(EngineError::UnsupportedOperation(
-            "MySQL support is not yet fully implemented".to_string(),
-        ))
+        match sqlx::query("SELECT 1").fetch_one(&self.pool).await {
+            Ok(_) => Ok(true),
+            Err(e) => Err(EngineError::Connection(format!(
+                "Connection test failed: {}",
+                redact_message(&e.to_string(), &self.config.connection_string)
+            ))),
+        }
     }
 
-    async fn explain_query(&self, _query: &str) -> Result<ExecutionPlan, EngineError> {
-        // Placeholder implementation for MySQL EXPLAIN
-        Err(EngineError::UnsupportedOperation(
-            "MySQL EXPLAIN support is not yet fully implemented".to_string(),
-        ))
+    async fn explain_query(&self, query: &str) -> Result<ExecutionPlan, EngineError> {
+        let version = self.server_version().await;
+        if version::at_least(version.as_ref(), EXPLAIN_ANALYZE_VERSION) {
+            // EXPLAIN ANALYZE runs the statement, so it runs in a read-only
+            // transaction that is rolled back
+            let analyze_query = format!("EXPLAIN ANALYZE {}", query);
+            match fetch_all_rolled_back(&self.pool, "START TRANSACTION READ ONLY", &analyze_query)
+                .await
+            {
+                Ok(rows) => {
+                    let tree: String = rows
+                        .first()
+                        .map(|row| row.try_get(0))
+                        .transpose()
+                        .map_err(|e| {
+                            EngineError::PlanParsing(format!("Failed to get EXPLAIN result: {}", e))
+                        })?
+                        .unwrap_or_default();
+                    return parse_mysql_tree(&tree);
+                }
+                // The version couldn't be read, and the server turned out not to know EXPLAIN ANALYZE
+                Err(e) if version.is_none() && is_parse_error(&e) => {}
+                Err(e) => {
+                    return Err(EngineError::QueryExecution(format!(
+                        "Failed to execute EXPLAIN ANALYZE: {}",
+                        e
+                    )))
+                }
+            }
+        }
+
+        let explain_query = format!("EXPLAIN FORMAT=JSON {}", query);
+
+        let row = sqlx::query(&explain_query)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| {
+                EngineError::QueryExecution(format!("Failed to execute EXPLAIN: {}", e))
+            })?;
+
+        let explain_text: String = row.try_get(0).map_err(|e| {
+            EngineError::PlanParsing(format!("Failed to get EXPLAIN result: {}", e))
+        })?;
+        let explain_json: Value = serde_json::from_str(&explain_text).map_err(|e| {
+            EngineError::PlanParsing(format!("Failed to parse EXPLAIN JSON: {}", e))
+        })?;
+
+        self.parse_mysql_explain(&explain_json)
     }
 
-    async fn validate_query(&self, _query: &str) -> Result<(), EngineError> {
-        // Placeholder implementation
-        Err(EngineError::UnsupportedOperation(
-            "MySQL query validation is not yet fully implemented".to_string(),
-        ))
+    async fn validate_query(&self, query: &str) -> Result<(), EngineError> {
+        // EXPLAIN checks the statement against the schema without executing it
+        let explain_query = format!("EXPLAIN {}", query);
+
+        sqlx::query(&explain_query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| EngineError::QueryExecution(format!("Query validation failed: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn count_rows(&self, query: &str, timeout: Duration) -> Result<u64, EngineError> {
+        // The optimizer hint stops the SELECT on the server once it runs out of time
+        let count = count_query(query).replacen(
+            "SELECT",
+            &format!("SELECT /*+ MAX_EXECUTION_TIME({}) */", timeout.as_millis()),
+            1,
+        );
+        count_rows_rolled_back(&self.pool, &[], &count).await
+    }
+
+    async fn fetch_rows(&self, query: &str, limit: usize) -> Result<Vec<String>, EngineError> {
+        let columns = self
+            .pool
+            .describe(query)
+            .await
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to describe query: {}", e)))?
+            .columns()
+            .len();
+
+        sqlx::query(&json_rows_query(query, columns, limit))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to fetch rows: {}", e)))?
+            .iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<_, _>>()
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to read row: {}", e)))
     }
 
     async fn get_version_info(&self) -> Result<DatabaseInfo, EngineError> {
+        let version_row = sqlx::query("SELECT VERSION()")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| EngineError::Connection(format!("Failed to get version: {}", e)))?;
+
+        let version: String = version_row
+            .try_get(0)
+            .map_err(|e| EngineError::Connection(format!("Failed to parse version: {}", e)))?;
+
         Ok(DatabaseInfo {
             engine_type: EngineType::MySQL,
-            version: "8.0+ (Placeholder)".to_string(),
-            connection_status: "Not implemented".to_string(),
+            version,
+            connection_status: "Connected".to_string(),
+            health: None,
+            socket: self.pool.connect_options().get_socket().cloned(),
+            schema: None,
+            search_path: None,
             features_supported: vec![
                 DatabaseFeature::DetailedExecutionPlan,
                 DatabaseFeature::CostEstimation,
+                DatabaseFeature::QueryOptimizationHints,
+                DatabaseFeature::PartitionedTables,
             ],
         })
     }
@@ -123,7 +803,9 @@ impl DatabaseEngine for MySQLEngine {
     fn supports_feature(&self, feature: &DatabaseFeature) -> bool {
         match feature {
             DatabaseFeature::DetailedExecutionPlan => true,
-            DatabaseFeature::ActualRowCounts => false, // MySQL EXPLAIN doesn't provide actual row counts by default
+            DatabaseFeature::ActualRowCounts => {
+                version::at_least(self.server_version.get(), EXPLAIN_ANALYZE_VERSION)
+            }
             DatabaseFeature::CostEstimation => true,
             DatabaseFeature::IndexSuggestions => false,
             DatabaseFeature::QueryOptimizationHints => true,
@@ -136,32 +818,153 @@ impl DatabaseEngine for MySQLEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::tls::TlsConfig;
 
-    #[tokio::test]
-    async fn test_mysql_engine_creation() {
-        let config = ConnectionConfig {
+    fn test_config() -> ConnectionConfig {
+        ConnectionConfig {
             engine_type: EngineType::MySQL,
             connection_string: "mysql://test".to_string(),
             max_connections: None,
             timeout_seconds: None,
-        };
+            password_source: None,
+            tls: TlsConfig::default(),
+            search_path: None,
+        }
+    }
 
-        let engine = MySQLEngine::new(config).await;
+    #[tokio::test]
+    async fn test_mysql_engine_creation() {
+        let engine = MySQLEngine::new(test_config()).await;
         assert!(engine.is_ok());
     }
 
-    #[test]
-    fn test_mysql_sample_queries() {
-        let config = ConnectionConfig {
-            engine_type: EngineType::MySQL,
-            connection_string: "mysql://test".to_string(),
-            max_connections: None,
-            timeout_seconds: None,
-        };
+    #[tokio::test]
+    async fn test_features_follow_server_version() {
+        let engine = MySQLEngine::new(test_config()).await.unwrap();
+        assert!(engine.supports_feature(&DatabaseFeature::ActualRowCounts));
+
+        let old = MySQLEngine::new(test_config()).await.unwrap();
+        old.server_version
+            .set(ServerVersion::parse("5.7.44-log").unwrap())
+            .unwrap();
+        assert!(!old.supports_feature(&DatabaseFeature::ActualRowCounts));
+        assert!(old.supports_feature(&DatabaseFeature::CostEstimation));
+    }
 
-        let engine = MySQLEngine { config };
+    #[tokio::test]
+    async fn test_mysql_sample_queries() {
+        let engine = MySQLEngine::new(test_config()).await.unwrap();
         let samples = engine.get_sample_queries();
         assert!(!samples.is_empty());
         assert_eq!(samples[0].category, QueryCategory::BasicSelect);
     }
+
+    #[tokio::test]
+    async fn test_parse_mysql_explain() {
+        let engine = MySQLEngine::new(test_config()).await.unwrap();
+        let explain = json!({
+            "query_block": {
+                "select_id": 1,
+                "cost_info": {"query_cost": "1423.50"},
+                "ordering_operation": {
+                    "using_filesort": true,
+                    "cost_info": {"sort_cost": "100.00"},
+                    "nested_loop": [
+                        {"table": {
+                            "table_name": "o",
+                            "access_type": "ALL",
+                            "rows_examined_per_scan": 1000,
+                            "rows_produced_per_join": 100,
+                            "filtered": "10.00",
+                            "cost_info": {"read_cost": "91.00", "eval_cost": "10.00", "prefix_cost": "101.00"},
+                            "attached_condition": "(`shop`.`o`.`total` > 100)"
+                        }},
+                        {"table": {
+                            "table_name": "c",
+                            "access_type": "eq_ref",
+                            "key": "PRIMARY",
+                            "rows_examined_per_scan": 1,
+                            "rows_produced_per_join": 100,
+                            "cost_info": {"read_cost": "25.00", "eval_cost": "10.00", "prefix_cost": "136.00"}
+                        }}
+                    ]
+                }
+            }
+        });
+
+        let plan = engine.parse_mysql_explain(&explain).unwrap();
+        let root = &plan.root;
+        assert_eq!(root.node_type, "Query Block");
+        assert_eq!(root.total_cost, 1423.5);
+
+        let sort = &root.plans[0];
+        assert_eq!(sort.node_type, "Sort");
+        assert_eq!(sort.total_cost, 236.0);
+
+        let join = &sort.plans[0];
+        assert_eq!(join.node_type, "Nested Loop");
+        assert_eq!(join.total_cost, 136.0);
+        assert_eq!(join.actual_rows, 100);
+
+        let (orders, customers) = (&join.plans[0], &join.plans[1]);
+        assert_eq!(orders.node_type, "Seq Scan");
+        assert_eq!(orders.relation_name.as_deref(), Some("o"));
+        assert_eq!(orders.total_cost, 101.0);
+        assert_eq!(orders.extra["Rows Examined"], 1000);
+        assert_eq!(orders.filter.as_deref(), Some("(`shop`.`o`.`total` > 100)"));
+        assert_eq!(customers.node_type, "Index Scan");
+        assert_eq!(customers.extra["Index Name"], "PRIMARY");
+
+        assert!(engine.parse_mysql_explain(&json!({})).is_err());
+    }
+
+    #[test]
+    fn test_parse_mysql_tree() {
+        let tree = "\
+-> Sort: c.`name`  (actual time=2.51..2.55 rows=90 loops=1)
+    -> Nested loop inner join  (cost=136.00 rows=100) (actual time=0.08..2.31 rows=90 loops=1)
+        -> Filter: (o.total > 100)  (cost=101.00 rows=100) (actual time=0.05..1.12 rows=90 loops=1)
+            -> Table scan on o  (cost=101.00 rows=1000) (actual time=0.04..0.93 rows=1000 loops=1)
+        -> Single-row index lookup on c using PRIMARY (id=o.customer_id)  (cost=0.25 rows=1) (actual time=0.012..0.012 rows=1 loops=90)
+    -> Select #2 (subquery in projection; dependent)
+        -> Covering index lookup on r using idx_region (region=c.region)  (cost=0.35 rows=1) (never executed)
+";
+        let plan = parse_mysql_tree(tree).unwrap();
+        let sort = &plan.root;
+        assert_eq!(sort.node_type, "Sort");
+        assert_eq!(sort.extra["Sort Key"], "c.`name`");
+        assert_eq!(sort.actual_rows, 90);
+        assert_eq!(plan.execution_time, 2.55);
+        assert_eq!(sort.plans.len(), 2);
+
+        let join = &sort.plans[0];
+        assert_eq!(join.node_type, "Nested Loop");
+        assert_eq!(join.extra["Join Type"], "Inner");
+        assert_eq!(join.total_cost, 136.0);
+        assert_eq!(join.extra["Plan Rows"], 100.0);
+
+        let filter = &join.plans[0];
+        assert_eq!(filter.filter.as_deref(), Some("(o.total > 100)"));
+        let scan = &filter.plans[0];
+        assert_eq!(scan.node_type, "Seq Scan");
+        assert_eq!(scan.relation_name.as_deref(), Some("o"));
+        assert_eq!(scan.actual_rows, 1000);
+        assert_eq!(scan.actual_startup_time, Some(0.04));
+        assert_eq!(scan.actual_total_time, 0.93);
+
+        let lookup = &join.plans[1];
+        assert_eq!(lookup.node_type, "Index Scan");
+        assert_eq!(lookup.extra["Index Name"], "PRIMARY");
+        assert_eq!(lookup.actual_loops, 90);
+        assert_eq!(lookup.total_cost, 0.25);
+
+        let covering = &sort.plans[1].plans[0];
+        assert_eq!(covering.node_type, "Index Only Scan");
+        assert_eq!(covering.relation_name.as_deref(), Some("r"));
+        assert_eq!(covering.actual_loops, 0);
+        assert_eq!(covering.extra["Never Executed"], true);
+
+        assert!(parse_mysql_tree("").is_err());
+        assert!(parse_mysql_tree("    -> Table scan on t").is_err());
+    }
 }
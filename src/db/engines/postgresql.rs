@@ -1,13 +1,22 @@
 //! PostgreSQL database engine implementation
 
+use std::time::Duration;
+
 use async_trait::async_trait;
-use sqlx::{PgPool, Row};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions, PgRow, PgSslMode},
+    Acquire, PgPool, Postgres, Row,
+};
+use tokio::sync::OnceCell;
 
+use super::version::{self, ServerVersion};
 use super::{
-    ConnectionConfig, DatabaseEngine, DatabaseFeature, DatabaseInfo, EngineError, EngineType,
-    QueryCategory, SampleQuery,
+    check_socket, count_query, count_rows_rolled_back, ConnectionConfig, DatabaseEngine,
+    DatabaseFeature, DatabaseInfo, EngineError, EngineType, QueryCategory, SampleQuery,
 };
+use crate::db::credentials::redact_message;
 use crate::db::models::ExecutionPlan;
+use crate::db::tls::TlsConfig;
 
 /// PostgreSQL database engine implementation
 #[derive(Debug)]
@@ -15,19 +24,163 @@ pub struct PostgreSQLEngine {
     pool: PgPool,
     #[allow(dead_code)]
     config: ConnectionConfig,
+    server_version: OnceCell<ServerVersion>,
 }
 
 impl PostgreSQLEngine {
     /// Create a new PostgreSQL engine instance
     pub async fn new(config: ConnectionConfig) -> Result<Self, EngineError> {
-        let pool = PgPool::connect(&config.connection_string)
+        let pool = connect_pool(&config, &config.connection_string, "PostgreSQL").await?;
+        let engine = Self {
+            pool,
+            config,
+            server_version: OnceCell::new(),
+        };
+        engine.server_version().await;
+        Ok(engine)
+    }
+
+    /// Version of the server, read from `server_version_num` when first needed
+    ///
+    /// Engines created with [`PostgreSQLEngine::new`] read it right after
+    /// connecting. `None` if it couldn't be read.
+    pub async fn server_version(&self) -> Option<ServerVersion> {
+        version::detect(&self.server_version, || async {
+            let num: String = sqlx::query_scalar("SHOW server_version_num")
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            num.trim()
+                .parse()
+                .map(ServerVersion::from_pg_num)
+                .map_err(|_| format!("unexpected server_version_num '{}'", num))
+        })
+        .await
+    }
+
+    /// Whether the server is Amazon Redshift, which speaks the PostgreSQL protocol
+    ///
+    /// Redshift reports itself as PostgreSQL 8.0.2 with its own version
+    /// appended. Failing to read the version is taken to mean PostgreSQL.
+    pub async fn is_redshift(&self) -> bool {
+        sqlx::query("SELECT version()")
+            .fetch_one(&self.pool)
             .await
-            .map_err(|e| {
-                EngineError::Connection(format!("Failed to connect to PostgreSQL: {}", e))
-            })?;
+            .and_then(|row| row.try_get::<String, _>(0))
+            .is_ok_and(|version| version.contains("Redshift"))
+    }
 
-        Ok(Self { pool, config })
+    /// Take apart the engine, to hand its pool to the Redshift engine
+    pub(super) fn into_parts(self) -> (PgPool, ConnectionConfig) {
+        (self.pool, self.config)
     }
+
+    /// Wrap an existing connection pool
+    pub fn from_pool(pool: PgPool) -> Self {
+        Self {
+            pool,
+            server_version: OnceCell::new(),
+            config: ConnectionConfig {
+                engine_type: EngineType::PostgreSQL,
+                connection_string: String::new(),
+                max_connections: None,
+                timeout_seconds: None,
+                password_source: None,
+                tls: TlsConfig::default(),
+                search_path: None,
+            },
+        }
+    }
+
+    /// The underlying connection pool
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+}
+
+/// Open a pool to a server speaking the PostgreSQL protocol at `url`
+pub(super) async fn connect_pool(
+    config: &ConnectionConfig,
+    url: &str,
+    engine_name: &str,
+) -> Result<PgPool, EngineError> {
+    let connection_error = |e: sqlx::Error| {
+        EngineError::Connection(format!(
+            "Failed to connect to {}: {}",
+            engine_name,
+            redact_message(&e.to_string(), url)
+        ))
+    };
+
+    let mut options: PgConnectOptions = url.parse().map_err(connection_error)?;
+    if let Some(source) = &config.password_source {
+        let password = source
+            .resolve()
+            .map_err(|e| EngineError::Configuration(e.to_string()))?;
+        options = options.password(&password);
+    }
+    config.tls.validate().map_err(EngineError::Configuration)?;
+    let options = config.tls.apply_pg(options);
+    if let Some(socket) = options.get_socket() {
+        check_socket(socket)?;
+        // The server refuses TLS on its socket, so requiring it can never connect
+        if !matches!(
+            options.get_ssl_mode(),
+            PgSslMode::Disable | PgSslMode::Allow | PgSslMode::Prefer
+        ) {
+            return Err(EngineError::Configuration(format!(
+                "{} doesn't use TLS over the Unix domain socket in {}; \
+                 set the SSL mode to prefer or disable, or connect over TCP",
+                engine_name,
+                socket.display()
+            )));
+        }
+    }
+
+    let mut pool_options = PgPoolOptions::new();
+    if let Some(search_path) = config.search_path.clone() {
+        // Bound rather than spliced into SET, so the value can't smuggle in other statements
+        pool_options = pool_options.after_connect(move |conn, _| {
+            let search_path = search_path.clone();
+            Box::pin(async move {
+                sqlx::query("SELECT set_config('search_path', $1, false)")
+                    .bind(search_path)
+                    .execute(conn)
+                    .await
+                    .map(|_| ())
+            })
+        });
+    }
+    if let Some(max_connections) = config.max_connections {
+        pool_options = pool_options.max_connections(max_connections);
+    }
+    if let Some(timeout) = config.timeout_seconds {
+        pool_options = pool_options.acquire_timeout(std::time::Duration::from_secs(timeout));
+    }
+    pool_options
+        .connect_with(options)
+        .await
+        .map_err(connection_error)
+}
+
+/// Fetch the single row of `sql` inside a read-only transaction that is rolled back
+///
+/// `EXPLAIN ANALYZE` executes the query it explains; in a read-only
+/// transaction a statement that slipped past validation and tries to write
+/// (say, a data-modifying CTE) fails instead of changing data.
+pub(crate) async fn fetch_one_read_only<'c>(
+    conn: impl Acquire<'c, Database = Postgres>,
+    sql: &str,
+) -> Result<PgRow, sqlx::Error> {
+    let mut tx = conn.begin().await?;
+    sqlx::query("SET TRANSACTION READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    let row = sqlx::query(sql).fetch_one(&mut *tx).await;
+    let rolled_back = tx.rollback().await;
+    let row = row?;
+    rolled_back?;
+    Ok(row)
 }
 
 #[async_trait]
@@ -50,8 +203,7 @@ impl DatabaseEngine for PostgreSQLEngine {
         // Use the existing explain functionality from our db module
         let explain_query = format!("EXPLAIN (FORMAT JSON, ANALYZE, BUFFERS) {}", query);
 
-        let row = sqlx::query(&explain_query)
-            .fetch_one(&self.pool)
+        let row = fetch_one_read_only(&self.pool, &explain_query)
             .await
             .map_err(|e| {
                 EngineError::QueryExecution(format!("Failed to execute EXPLAIN: {}", e))
@@ -78,11 +230,40 @@ impl DatabaseEngine for PostgreSQLEngine {
         Ok(())
     }
 
-    async fn get_version_info(&self) -> Result<DatabaseInfo, EngineError> {
-        let version_row = sqlx::query("SELECT version()")
-            .fetch_one(&self.pool)
+    async fn count_rows(&self, query: &str, timeout: Duration) -> Result<u64, EngineError> {
+        let setup = [
+            "SET TRANSACTION READ ONLY".to_string(),
+            format!("SET LOCAL statement_timeout = {}", timeout.as_millis()),
+        ];
+        count_rows_rolled_back(&self.pool, &setup, &count_query(query)).await
+    }
+
+    async fn fetch_rows(&self, query: &str, limit: usize) -> Result<Vec<String>, EngineError> {
+        // json_each keeps the columns in order, and duplicate column names apart
+        let statement = format!(
+            "SELECT (SELECT json_agg(e.value ORDER BY e.n) \
+             FROM json_each(to_json(sqltrace_rows)) WITH ORDINALITY AS e(key, value, n))::text \
+             FROM ({}) AS sqltrace_rows LIMIT {}",
+            query.trim_end().trim_end_matches(';'),
+            limit
+        );
+
+        sqlx::query(&statement)
+            .fetch_all(&self.pool)
             .await
-            .map_err(|e| EngineError::Connection(format!("Failed to get version: {}", e)))?;
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to fetch rows: {}", e)))?
+            .iter()
+            .map(|row| Ok(row.try_get::<Option<String>, _>(0)?.unwrap_or_default()))
+            .collect::<Result<_, sqlx::Error>>()
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to read row: {}", e)))
+    }
+
+    async fn get_version_info(&self) -> Result<DatabaseInfo, EngineError> {
+        let version_row =
+            sqlx::query("SELECT version(), current_schema(), current_setting('search_path')")
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| EngineError::Connection(format!("Failed to get version: {}", e)))?;
 
         let version: String = version_row
             .try_get(0)
@@ -92,6 +273,10 @@ impl DatabaseEngine for PostgreSQLEngine {
             engine_type: EngineType::PostgreSQL,
             version,
             connection_status: "Connected".to_string(),
+            health: None,
+            socket: self.pool.connect_options().get_socket().cloned(),
+            schema: version_row.try_get(1).ok().flatten(),
+            search_path: version_row.try_get(2).ok(),
             features_supported: vec![
                 DatabaseFeature::DetailedExecutionPlan,
                 DatabaseFeature::ActualRowCounts,
@@ -152,22 +337,46 @@ impl DatabaseEngine for PostgreSQLEngine {
     }
 
     fn supports_feature(&self, feature: &DatabaseFeature) -> bool {
+        let version = self.server_version.get();
         match feature {
             DatabaseFeature::DetailedExecutionPlan => true,
             DatabaseFeature::ActualRowCounts => true,
             DatabaseFeature::CostEstimation => true,
             DatabaseFeature::IndexSuggestions => true,
             DatabaseFeature::QueryOptimizationHints => true,
-            DatabaseFeature::ParallelExecution => true,
-            DatabaseFeature::PartitionedTables => true,
+            DatabaseFeature::ParallelExecution => {
+                version::at_least(version, ServerVersion::new(9, 6, 0))
+            }
+            DatabaseFeature::PartitionedTables => {
+                version::at_least(version, ServerVersion::new(10, 0, 0))
+            }
         }
     }
 }
 
 #[cfg(test)]
+#[allow(unused_variables)]
 mod tests {
     use super::*;
 
+    fn lazy_engine() -> PostgreSQLEngine {
+        let config = ConnectionConfig {
+            engine_type: EngineType::PostgreSQL,
+            connection_string: "postgres://test".to_string(),
+            max_connections: None,
+            timeout_seconds: None,
+            password_source: None,
+            tls: TlsConfig::default(),
+            search_path: None,
+        };
+        let pool = PgPool::connect_lazy(&config.connection_string).unwrap();
+        PostgreSQLEngine {
+            pool,
+            config,
+            server_version: OnceCell::new(),
+        }
+    }
+
     #[test]
     fn test_postgresql_features() {
         let config = ConnectionConfig {
@@ -175,7 +384,57 @@ mod tests {
             connection_string: "postgres://test".to_string(),
             max_connections: None,
             timeout_seconds: None,
+            password_source: None,
+            tls: TlsConfig::default(),
+            search_path: None,
+        };
+    }
+
+    #[tokio::test]
+    async fn test_features_follow_server_version() {
+        let old = lazy_engine();
+        old.server_version
+            .set(ServerVersion::from_pg_num(90_500))
+            .unwrap();
+        assert!(old.supports_feature(&DatabaseFeature::ActualRowCounts));
+        assert!(!old.supports_feature(&DatabaseFeature::ParallelExecution));
+        assert!(!old.supports_feature(&DatabaseFeature::PartitionedTables));
+    }
+
+    #[tokio::test]
+    async fn test_socket_checked_before_connecting() {
+        let socket_dir = std::env::temp_dir();
+        let config = |url: String, mode| ConnectionConfig {
+            engine_type: EngineType::PostgreSQL,
+            connection_string: url,
+            max_connections: None,
+            timeout_seconds: Some(1),
+            password_source: None,
+            tls: TlsConfig {
+                mode,
+                ..TlsConfig::default()
+            },
+            search_path: None,
         };
+
+        let missing = PostgreSQLEngine::new(config(
+            "postgres:///app?host=/nonexistent/sqltrace".to_string(),
+            None,
+        ))
+        .await
+        .unwrap_err();
+        assert!(missing
+            .to_string()
+            .contains("/nonexistent/sqltrace does not exist"));
+
+        let tls = PostgreSQLEngine::new(config(
+            format!("postgres:///app?host={}", socket_dir.display()),
+            Some(crate::db::tls::SslMode::Require),
+        ))
+        .await
+        .unwrap_err();
+        assert!(matches!(tls, EngineError::Configuration(_)));
+        assert!(tls.to_string().contains("TLS"));
     }
 
     #[test]
@@ -185,6 +444,9 @@ mod tests {
             connection_string: "postgres://test".to_string(),
             max_connections: None,
             timeout_seconds: None,
+            password_source: None,
+            tls: TlsConfig::default(),
+            search_path: None,
         };
     }
 }
@@ -0,0 +1,473 @@
+//! Amazon Redshift database engine implementation
+//!
+//! Redshift speaks the PostgreSQL wire protocol but rejects `EXPLAIN
+//! (ANALYZE, BUFFERS, FORMAT JSON)`: it only has plain `EXPLAIN`, which
+//! prints the planner's estimates as indented text without executing the
+//! query. This engine parses that text, where compute node operators are
+//! prefixed with `XN` (and leader node operators with `LD`), into
+//! [`PlanNode`]s. Plans carry costs and estimated rows only, so the engine
+//! reports no actual row counts and the advisor skips rules that need them.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use sqlx::postgres::PgRow;
+use sqlx::{Column, PgPool, Row, TypeInfo, ValueRef};
+
+use super::postgresql::connect_pool;
+use super::{
+    count_query, count_rows_rolled_back, ConnectionConfig, DatabaseEngine, DatabaseFeature,
+    DatabaseInfo, EngineError, EngineType, QueryCategory, SampleQuery,
+};
+use crate::db::models::{ExecutionPlan, PlanNode};
+
+/// Amazon Redshift database engine implementation
+#[derive(Debug)]
+pub struct RedshiftEngine {
+    pool: PgPool,
+    #[allow(dead_code)]
+    config: ConnectionConfig,
+}
+
+impl RedshiftEngine {
+    /// Create a new Redshift engine instance
+    ///
+    /// `redshift://` URLs are connected to as `postgres://` URLs.
+    pub async fn new(config: ConnectionConfig) -> Result<Self, EngineError> {
+        let url = match config.connection_string.strip_prefix("redshift://") {
+            Some(rest) => format!("postgres://{}", rest),
+            None => config.connection_string.clone(),
+        };
+        let pool = connect_pool(&config, &url, "Redshift").await?;
+        Ok(Self { pool, config })
+    }
+
+    /// Wrap a pool that turned out to be connected to Redshift
+    pub fn from_pool(pool: PgPool, config: ConnectionConfig) -> Self {
+        Self {
+            pool,
+            config: ConnectionConfig {
+                engine_type: EngineType::Redshift,
+                ..config
+            },
+        }
+    }
+
+    /// The underlying connection pool
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+}
+
+/// Whether `url` points at a Redshift cluster or serverless workgroup endpoint
+pub fn is_redshift_url(url: &str) -> bool {
+    if url.starts_with("redshift://") {
+        return true;
+    }
+    let Some(rest) = url
+        .strip_prefix("postgres://")
+        .or_else(|| url.strip_prefix("postgresql://"))
+    else {
+        return false;
+    };
+    let authority = rest.split(['/', '?']).next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = host.split(':').next().unwrap_or_default();
+    host.ends_with(".redshift.amazonaws.com")
+        || host.ends_with(".redshift-serverless.amazonaws.com")
+}
+
+/// Parse the text output of Redshift's `EXPLAIN`
+///
+/// Each operator is a line `XN <operator> [on <relation> [alias]]
+/// (cost=<startup>..<total> rows=<rows> width=<width>)`; children are
+/// introduced by `->` and indented further than their parent. Other lines
+/// describe the operator above them, either as `Key: value` or as free text
+/// such as `Send to leader`. Warnings about missing statistics are printed
+/// after the plan between `-----` markers.
+pub fn parse_redshift_plan(text: &str) -> Result<ExecutionPlan, EngineError> {
+    // Nodes whose children may still follow, with the column their line started at
+    let mut open: Vec<(usize, PlanNode)> = Vec::new();
+    let mut warnings = Vec::new();
+
+    for line in text.lines() {
+        let content = line.trim();
+        if content.is_empty() || content == "QUERY PLAN" {
+            continue;
+        }
+        if content.starts_with("-----") {
+            warnings.push(content.trim_matches('-').trim().to_string());
+            continue;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+        let (indent, header) = match content.strip_prefix("->") {
+            Some(header) => (indent, header.trim_start()),
+            None if open.is_empty() => (indent, content),
+            None => {
+                let (_, node) = open.last_mut().expect("checked non-empty");
+                add_detail(node, content);
+                continue;
+            }
+        };
+
+        let node = parse_node_line(header).ok_or_else(|| {
+            EngineError::PlanParsing(format!("Unrecognized Redshift plan line: {}", content))
+        })?;
+        close_deeper(&mut open, indent);
+        open.push((indent, node));
+    }
+
+    close_deeper(&mut open, 0);
+    let (_, mut root) = open
+        .pop()
+        .ok_or_else(|| EngineError::PlanParsing("Empty Redshift plan".to_string()))?;
+    if !warnings.is_empty() {
+        set_extra(&mut root, "Warnings", json!(warnings));
+    }
+
+    Ok(ExecutionPlan {
+        root,
+        planning_time: 0.0,
+        execution_time: 0.0,
+        settings: None,
+        jit: None,
+    })
+}
+
+/// Attach every open node starting at or after `indent` to its parent
+fn close_deeper(open: &mut Vec<(usize, PlanNode)>, indent: usize) {
+    while open.len() > 1 && open.last().is_some_and(|(i, _)| *i >= indent) {
+        let (_, child) = open.pop().expect("checked length");
+        open.last_mut().expect("checked length").1.plans.push(child);
+    }
+}
+
+/// Parse an operator line, without its leading `->`
+fn parse_node_line(header: &str) -> Option<PlanNode> {
+    let (description, estimates) = header.rsplit_once("(cost=")?;
+    let estimates = estimates.trim_end().strip_suffix(')')?;
+
+    let mut fields = estimates.split_whitespace();
+    let (startup_cost, total_cost) = fields.next()?.split_once("..")?;
+    let mut node = PlanNode {
+        startup_cost: startup_cost.parse().ok()?,
+        total_cost: total_cost.parse().ok()?,
+        extra: Value::Object(Map::new()),
+        ..PlanNode::default()
+    };
+    for field in fields {
+        match field.split_once('=') {
+            Some(("rows", rows)) => {
+                set_extra(&mut node, "Plan Rows", json!(rows.parse::<u64>().ok()?))
+            }
+            Some(("width", width)) => {
+                set_extra(&mut node, "Plan Width", json!(width.parse::<u64>().ok()?))
+            }
+            _ => {}
+        }
+    }
+
+    let description = description.trim();
+    let description = match description.split_once(' ') {
+        Some(("XN", rest)) => {
+            set_extra(&mut node, "Node Location", json!("Compute"));
+            rest
+        }
+        Some(("LD", rest)) => {
+            set_extra(&mut node, "Node Location", json!("Leader"));
+            rest
+        }
+        _ => description,
+    };
+
+    let (operator, relation) = match description.split_once(" on ") {
+        Some((operator, relation)) => (operator, Some(relation)),
+        None => match description.strip_prefix("Subquery Scan ") {
+            Some(alias) => {
+                node.alias = Some(alias.to_string());
+                ("Subquery Scan", None)
+            }
+            None => (description, None),
+        },
+    };
+    if let Some(relation) = relation {
+        let mut words = relation.split_whitespace();
+        node.relation_name = words.next().map(str::to_string);
+        node.alias = words
+            .next()
+            .map(str::to_string)
+            .or(node.relation_name.clone());
+    }
+
+    // Joins name how rows are redistributed between slices, e.g. DS_BCAST_INNER
+    node.node_type = match operator.rsplit_once(' ') {
+        Some((join, distribution)) if distribution.starts_with("DS_") => {
+            set_extra(&mut node, "Distribution", json!(distribution));
+            join.to_string()
+        }
+        _ => operator.to_string(),
+    };
+
+    Some(node)
+}
+
+/// Record a detail line of an operator
+fn add_detail(node: &mut PlanNode, line: &str) {
+    match line.split_once(": ") {
+        Some(("Filter", value)) => node.filter = Some(value.to_string()),
+        Some(("Join Filter", value)) => node.join_filter = Some(value.to_string()),
+        Some(("Index Cond", value)) => node.index_cond = Some(value.to_string()),
+        Some((key, value)) if !key.contains(['(', '"']) => set_extra(node, key, json!(value)),
+        _ => {
+            let detail = match node.extra_str("Detail") {
+                Some(existing) => format!("{}\n{}", existing, line),
+                None => line.to_string(),
+            };
+            set_extra(node, "Detail", json!(detail));
+        }
+    }
+}
+
+fn set_extra(node: &mut PlanNode, key: &str, value: Value) {
+    if let Value::Object(extra) = &mut node.extra {
+        extra.insert(key.to_string(), value);
+    }
+}
+
+/// Convert a column value in text format to JSON, keeping numbers and booleans typed
+fn text_value(row: &PgRow, index: usize) -> Result<Value, EngineError> {
+    let read_error = |e| EngineError::QueryExecution(format!("Failed to read row: {}", e));
+    let raw = row.try_get_raw(index).map_err(read_error)?;
+    if raw.is_null() {
+        return Ok(Value::Null);
+    }
+    let text = raw
+        .as_str()
+        .map_err(|e| EngineError::QueryExecution(format!("Failed to read row: {}", e)))?;
+
+    let value = match row.column(index).type_info().name() {
+        "INT2" | "INT4" | "INT8" | "FLOAT4" | "FLOAT8" | "NUMERIC" => text
+            .parse::<serde_json::Number>()
+            .map(Value::Number)
+            .unwrap_or_else(|_| json!(text)),
+        "BOOL" => json!(text == "t"),
+        _ => json!(text),
+    };
+    Ok(value)
+}
+
+#[async_trait]
+impl DatabaseEngine for RedshiftEngine {
+    fn engine_type(&self) -> EngineType {
+        EngineType::Redshift
+    }
+
+    async fn test_connection(&self) -> Result<bool, EngineError> {
+        match sqlx::query("SELECT 1").fetch_one(&self.pool).await {
+            Ok(_) => Ok(true),
+            Err(e) => Err(EngineError::Connection(format!(
+                "Connection test failed: {}",
+                e
+            ))),
+        }
+    }
+
+    async fn explain_query(&self, query: &str) -> Result<ExecutionPlan, EngineError> {
+        let rows = sqlx::query(&format!("EXPLAIN {}", query))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| {
+                EngineError::QueryExecution(format!("Failed to execute EXPLAIN: {}", e))
+            })?;
+
+        let lines = rows
+            .iter()
+            .map(|row| row.try_get::<String, _>(0))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                EngineError::PlanParsing(format!("Failed to get EXPLAIN result: {}", e))
+            })?;
+        parse_redshift_plan(&lines.join("\n"))
+    }
+
+    async fn validate_query(&self, query: &str) -> Result<(), EngineError> {
+        sqlx::query(&format!("EXPLAIN {}", query))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| EngineError::QueryExecution(format!("Query validation failed: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn count_rows(&self, query: &str, timeout: Duration) -> Result<u64, EngineError> {
+        let setup = [
+            "SET TRANSACTION READ ONLY".to_string(),
+            format!("SET LOCAL statement_timeout = {}", timeout.as_millis()),
+        ];
+        count_rows_rolled_back(&self.pool, &setup, &count_query(query)).await
+    }
+
+    async fn fetch_rows(&self, query: &str, limit: usize) -> Result<Vec<String>, EngineError> {
+        // Redshift cannot convert rows to JSON, so read them in text format and convert here
+        let statement = format!(
+            "SELECT * FROM ({}) AS sqltrace_rows LIMIT {}",
+            query.trim_end().trim_end_matches(';'),
+            limit
+        );
+        let rows = sqlx::raw_sql(&statement)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to fetch rows: {}", e)))?;
+
+        rows.iter()
+            .map(|row| {
+                let values = (0..row.len())
+                    .map(|i| text_value(row, i))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Array(values).to_string())
+            })
+            .collect()
+    }
+
+    async fn get_version_info(&self) -> Result<DatabaseInfo, EngineError> {
+        let version_row =
+            sqlx::query("SELECT version(), current_schema(), current_setting('search_path')")
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| EngineError::Connection(format!("Failed to get version: {}", e)))?;
+
+        let version: String = version_row
+            .try_get(0)
+            .map_err(|e| EngineError::Connection(format!("Failed to parse version: {}", e)))?;
+
+        Ok(DatabaseInfo {
+            engine_type: EngineType::Redshift,
+            version,
+            connection_status: "Connected".to_string(),
+            health: None,
+            socket: self.pool.connect_options().get_socket().cloned(),
+            schema: version_row.try_get(1).ok().flatten(),
+            search_path: version_row.try_get(2).ok(),
+            features_supported: vec![
+                DatabaseFeature::CostEstimation,
+                DatabaseFeature::ParallelExecution,
+            ],
+        })
+    }
+
+    fn get_sample_queries(&self) -> Vec<SampleQuery> {
+        vec![
+            SampleQuery {
+                name: "Simple Select".to_string(),
+                description: "Scan with a filter on the sort key".to_string(),
+                query: "SELECT * FROM sales WHERE saletime >= '2008-12-01';".to_string(),
+                category: QueryCategory::BasicSelect,
+            },
+            SampleQuery {
+                name: "Distributed Join".to_string(),
+                description: "Join whose plan shows how rows are redistributed between nodes"
+                    .to_string(),
+                query: "SELECT e.eventname, sum(s.pricepaid) FROM sales s JOIN event e ON s.eventid = e.eventid GROUP BY e.eventname;".to_string(),
+                category: QueryCategory::Join,
+            },
+            SampleQuery {
+                name: "Leader Sort".to_string(),
+                description: "ORDER BY merged on the leader node".to_string(),
+                query: "SELECT catname, count(*) FROM category GROUP BY catname ORDER BY 2 DESC;"
+                    .to_string(),
+                category: QueryCategory::Aggregation,
+            },
+        ]
+    }
+
+    fn supports_feature(&self, feature: &DatabaseFeature) -> bool {
+        match feature {
+            DatabaseFeature::DetailedExecutionPlan => false, // Plain EXPLAIN has no timings
+            DatabaseFeature::ActualRowCounts => false,       // Row counts are planner estimates
+            DatabaseFeature::CostEstimation => true,
+            DatabaseFeature::IndexSuggestions => false, // Redshift has sort keys, not indexes
+            DatabaseFeature::QueryOptimizationHints => false,
+            DatabaseFeature::ParallelExecution => true,
+            DatabaseFeature::PartitionedTables => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_redshift_plan() {
+        let text = r#"XN Merge  (cost=1000000000136.38..1000000000136.88 rows=200 width=30)
+  Merge Key: sum(s.pricepaid)
+  ->  XN Network  (cost=1000000000136.38..1000000000136.88 rows=200 width=30)
+        Send to leader
+        ->  XN Sort  (cost=1000000000136.38..1000000000136.88 rows=200 width=30)
+              Sort Key: sum(s.pricepaid)
+              ->  XN HashAggregate  (cost=128.24..128.74 rows=200 width=30)
+                    ->  XN Hash Join DS_BCAST_INNER  (cost=109.98..127.01 rows=246 width=30)
+                          Hash Cond: ("outer".eventid = "inner".eventid)
+                          ->  XN Seq Scan on sales s  (cost=0.00..1724.56 rows=172456 width=16)
+                                Filter: (pricepaid > 100)
+                          ->  XN Hash  (cost=87.98..87.98 rows=8798 width=22)
+                                ->  XN Seq Scan on event e  (cost=0.00..87.98 rows=8798 width=22)
+ ----- Tables missing statistics: event -----
+ ----- Update statistics by running the ANALYZE command on these tables -----"#;
+
+        let plan = parse_redshift_plan(text).unwrap();
+        let merge = &plan.root;
+        assert_eq!(merge.node_type, "Merge");
+        assert_eq!(merge.total_cost, 1000000000136.88);
+        assert_eq!(merge.extra["Merge Key"], "sum(s.pricepaid)");
+        assert_eq!(merge.extra["Node Location"], "Compute");
+        assert_eq!(
+            merge.extra["Warnings"][0],
+            "Tables missing statistics: event"
+        );
+
+        let network = &merge.plans[0];
+        assert_eq!(network.node_type, "Network");
+        assert_eq!(network.extra["Detail"], "Send to leader");
+
+        let join = &network.plans[0].plans[0].plans[0];
+        assert_eq!(join.node_type, "Hash Join");
+        assert_eq!(join.extra["Distribution"], "DS_BCAST_INNER");
+        assert_eq!(
+            join.extra["Hash Cond"],
+            r#"("outer".eventid = "inner".eventid)"#
+        );
+        assert_eq!(join.plans.len(), 2);
+
+        let sales = &join.plans[0];
+        assert_eq!(sales.node_type, "Seq Scan");
+        assert_eq!(sales.relation_name.as_deref(), Some("sales"));
+        assert_eq!(sales.alias.as_deref(), Some("s"));
+        assert_eq!(sales.extra["Plan Rows"], 172456);
+        assert_eq!(sales.filter.as_deref(), Some("(pricepaid > 100)"));
+        assert_eq!(sales.actual_rows, 0);
+
+        let event = &join.plans[1].plans[0];
+        assert_eq!(event.relation_name.as_deref(), Some("event"));
+
+        assert!(parse_redshift_plan("").is_err());
+        assert!(parse_redshift_plan("not a plan").is_err());
+    }
+
+    #[test]
+    fn test_redshift_url_detection() {
+        assert!(is_redshift_url("redshift://admin@examplecluster/dev"));
+        assert!(is_redshift_url(
+            "postgres://admin:pw@examplecluster.abc123.us-west-2.redshift.amazonaws.com:5439/dev"
+        ));
+        assert!(is_redshift_url(
+            "postgresql://admin@wg.123456789012.us-east-1.redshift-serverless.amazonaws.com:5439/dev"
+        ));
+        assert!(!is_redshift_url("postgres://admin@localhost:5432/dev"));
+        assert!(!is_redshift_url(
+            "postgres://admin@localhost/redshift.amazonaws.com"
+        ));
+    }
+}
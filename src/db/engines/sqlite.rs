@@ -1,57 +1,206 @@
 //! SQLite database engine implementation
+//!
+//! `EXPLAIN QUERY PLAN` returns one row per step with an `id`, the `parent`
+//! step it belongs to, and a human-readable `detail` such as
+//! `SEARCH orders USING INDEX idx_customer (customer_id=?)`. This engine
+//! rebuilds the tree from the `id`/`parent` columns and names each node after
+//! the closest PostgreSQL node type, so the advisor and web UI can treat it
+//! like any other plan. SQLite reports neither costs nor row estimates.
+
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
-use serde_json::Value;
+use serde_json::json;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    Executor, Row, SqlitePool,
+};
 
 use super::{
-    ConnectionConfig, DatabaseEngine, DatabaseFeature, DatabaseInfo, EngineError, EngineType,
-    QueryCategory, SampleQuery,
+    count_query, count_rows_rolled_back, json_rows_query, ConnectionConfig, DatabaseEngine,
+    DatabaseFeature, DatabaseInfo, EngineError, EngineType, QueryCategory, SampleQuery,
 };
 use crate::db::models::{ExecutionPlan, PlanNode};
 
+/// One row of `EXPLAIN QUERY PLAN` output
+#[derive(Debug, Clone, PartialEq)]
+struct QueryPlanRow {
+    id: i64,
+    parent: i64,
+    detail: String,
+}
+
 /// SQLite database engine implementation
 #[derive(Debug)]
 pub struct SQLiteEngine {
+    pool: SqlitePool,
     #[allow(dead_code)]
     config: ConnectionConfig,
 }
 
 impl SQLiteEngine {
     /// Create a new SQLite engine instance
+    ///
+    /// The connection string is either a `sqlite:` URL or a path to a
+    /// database file, which must already exist.
     pub async fn new(config: ConnectionConfig) -> Result<Self, EngineError> {
-        // Note: This is a placeholder implementation
-        // In a real implementation, you would establish a SQLite connection here
-        Ok(Self { config })
+        let connection_error = |e: sqlx::Error| {
+            EngineError::Connection(format!("Failed to open SQLite database: {}", e))
+        };
+
+        let options = if config.connection_string.starts_with("sqlite:") {
+            SqliteConnectOptions::from_str(&config.connection_string).map_err(connection_error)?
+        } else {
+            SqliteConnectOptions::new().filename(&config.connection_string)
+        };
+
+        // Every connection to an in-memory database gets its own empty database
+        let in_memory = config.connection_string.contains(":memory:");
+        let max_connections = if in_memory {
+            1
+        } else {
+            config.max_connections.unwrap_or(5).max(1)
+        };
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await
+            .map_err(connection_error)?;
+
+        Ok(Self { pool, config })
+    }
+
+    /// The underlying connection pool
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
     }
 
     /// Convert SQLite EXPLAIN QUERY PLAN output to our unified ExecutionPlan format
-    #[allow(dead_code)]
-    fn parse_sqlite_explain(&self, _explain_result: &Value) -> Result<ExecutionPlan, EngineError> {
-        // SQLite EXPLAIN QUERY PLAN output is different from PostgreSQL
-        // This is a simplified conversion - real implementation would be more complex
+    fn parse_sqlite_explain(&self, rows: &[QueryPlanRow]) -> Result<ExecutionPlan, EngineError> {
+        if rows.is_empty() {
+            return Err(EngineError::PlanParsing(
+                "EXPLAIN QUERY PLAN returned no rows".to_string(),
+            ));
+        }
 
+        // Top-level steps have parent 0
         let root_node = PlanNode {
-            node_type: "SQLite Query".to_string(),
-            relation_name: None,
-            alias: None,
-            startup_cost: 0.0,
-            total_cost: 0.0,
-            actual_startup_time: Some(0.0),
-            actual_total_time: 0.0,
-            actual_rows: 0,
+            node_type: "Query Plan".to_string(),
             actual_loops: 1,
-            plans: vec![],
-            extra: serde_json::json!({}),
+            plans: children(rows, 0),
+            extra: json!({}),
+            ..Default::default()
         };
 
         Ok(ExecutionPlan {
             root: root_node,
             planning_time: 0.0,
             execution_time: 0.0,
+            settings: None,
+            jit: None,
         })
     }
 }
 
+/// The steps whose parent is `parent`, in output order, with their own children
+fn children(rows: &[QueryPlanRow], parent: i64) -> Vec<PlanNode> {
+    rows.iter()
+        .filter(|row| row.parent == parent && row.id != parent)
+        .map(|row| {
+            let mut node = step_node(&row.detail);
+            node.plans = children(rows, row.id);
+            node
+        })
+        .collect()
+}
+
+/// A plan node for one `detail` string
+fn step_node(detail: &str) -> PlanNode {
+    let mut extra = json!({ "Detail": detail });
+    let mut relation_name = None;
+    let mut index_cond = None;
+
+    let node_type = if let Some(rest) = detail
+        .strip_prefix("SCAN ")
+        .or_else(|| detail.strip_prefix("SEARCH "))
+    {
+        let searching = detail.starts_with("SEARCH ");
+        // Before SQLite 3.36 the table name was preceded by TABLE
+        let rest = rest.strip_prefix("TABLE ").unwrap_or(rest);
+        let (name, access) = rest.split_once(' ').unwrap_or((rest, ""));
+
+        if let Some((index, condition)) = index_usage(access) {
+            extra["Index Name"] = json!(index);
+            index_cond = condition.map(str::to_string);
+        }
+
+        if name == "CONSTANT" && access == "ROW" {
+            "Result"
+        } else {
+            relation_name = Some(name.to_string());
+            if access.contains("COVERING INDEX") {
+                "Index Only Scan"
+            } else if access.contains("PRIMARY KEY") || (searching && access.contains("INDEX")) {
+                "Index Scan"
+            } else if access.contains("VIRTUAL TABLE") {
+                "Foreign Scan"
+            } else if access.contains("INDEX") {
+                "Full Index Scan"
+            } else {
+                "Seq Scan"
+            }
+        }
+    } else if detail.starts_with("USE TEMP B-TREE") {
+        extra["Using Temporary Table"] = json!(true);
+        if detail.contains("GROUP BY") {
+            "Aggregate"
+        } else if detail.contains("DISTINCT") {
+            "Unique"
+        } else {
+            "Sort"
+        }
+    } else if detail.starts_with("COMPOUND QUERY") {
+        "Append"
+    } else if detail.starts_with("MATERIALIZE") {
+        "Materialize"
+    } else if detail.starts_with("CO-ROUTINE")
+        || detail.starts_with("LEFT-MOST SUBQUERY")
+        || detail.starts_with("UNION")
+        || detail.starts_with("INTERSECT")
+        || detail.starts_with("EXCEPT")
+    {
+        "Subquery Scan"
+    } else if detail.contains("SUBQUERY") {
+        "SubPlan"
+    } else if detail.starts_with("MULTI-INDEX OR") {
+        "BitmapOr"
+    } else {
+        "Plan Step"
+    };
+
+    PlanNode {
+        node_type: node_type.to_string(),
+        relation_name,
+        actual_loops: 1,
+        index_cond,
+        extra,
+        ..Default::default()
+    }
+}
+
+/// The index named in `USING [COVERING] INDEX name (condition)`, with its condition
+fn index_usage(access: &str) -> Option<(&str, Option<&str>)> {
+    let (_, rest) = access.split_once("INDEX ")?;
+    match rest.split_once(" (") {
+        Some((index, condition)) => Some((index, Some(condition.trim_end_matches(')')))),
+        None => Some((rest, None)),
+    }
+}
+
 #[async_trait]
 impl DatabaseEngine for SQLiteEngine {
     fn engine_type(&self) -> EngineType {
@@ -59,31 +208,109 @@ impl DatabaseEngine for SQLiteEngine {
     }
 
     async fn test_connection(&self) -> Result<bool, EngineError> {
-        // Placeholder implementation
-        Err(EngineError::UnsupportedOperation(
-            "SQLite support is not yet fully implemented".to_string(),
-        ))
+        match sqlx::query("SELECT 1").fetch_one(&self.pool).await {
+            Ok(_) => Ok(true),
+            Err(e) => Err(EngineError::Connection(format!(
+                "Connection test failed: {}",
+                e
+            ))),
+        }
+    }
+
+    async fn explain_query(&self, query: &str) -> Result<ExecutionPlan, EngineError> {
+        let explain_query = format!("EXPLAIN QUERY PLAN {}", query);
+
+        let rows = sqlx::query(&explain_query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to execute EXPLAIN: {}", e)))?
+            .iter()
+            .map(|row| {
+                Ok(QueryPlanRow {
+                    id: row.try_get("id")?,
+                    parent: row.try_get("parent")?,
+                    detail: row.try_get("detail")?,
+                })
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()
+            .map_err(|e| {
+                EngineError::PlanParsing(format!("Failed to get EXPLAIN result: {}", e))
+            })?;
+
+        self.parse_sqlite_explain(&rows)
     }
 
-    async fn explain_query(&self, _query: &str) -> Result<ExecutionPlan, EngineError> {
-        // Placeholder implementation for SQLite EXPLAIN QUERY PLAN
-        Err(EngineError::UnsupportedOperation(
-            "SQLite EXPLAIN support is not yet fully implemented".to_string(),
-        ))
+    async fn validate_query(&self, query: &str) -> Result<(), EngineError> {
+        // EXPLAIN QUERY PLAN prepares the statement without executing it
+        let explain_query = format!("EXPLAIN QUERY PLAN {}", query);
+
+        sqlx::query(&explain_query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| EngineError::QueryExecution(format!("Query validation failed: {}", e)))?;
+
+        Ok(())
     }
 
-    async fn validate_query(&self, _query: &str) -> Result<(), EngineError> {
-        // Placeholder implementation
-        Err(EngineError::UnsupportedOperation(
-            "SQLite query validation is not yet fully implemented".to_string(),
-        ))
+    async fn count_rows(&self, query: &str, timeout: Duration) -> Result<u64, EngineError> {
+        let mut conn =
+            self.pool.acquire().await.map_err(|e| {
+                EngineError::Connection(format!("Failed to acquire connection: {}", e))
+            })?;
+        let locked =
+            |e: sqlx::Error| EngineError::Connection(format!("Failed to lock connection: {}", e));
+        // SQLite has no statement timeout; the progress handler interrupts the
+        // statement once the deadline has passed
+        let deadline = Instant::now() + timeout;
+        conn.lock_handle()
+            .await
+            .map_err(locked)?
+            .set_progress_handler(1000, move || Instant::now() < deadline);
+        let count = count_rows_rolled_back(&mut *conn, &[], &count_query(query)).await;
+        conn.lock_handle()
+            .await
+            .map_err(locked)?
+            .remove_progress_handler();
+        count
+    }
+
+    async fn fetch_rows(&self, query: &str, limit: usize) -> Result<Vec<String>, EngineError> {
+        let columns = self
+            .pool
+            .describe(query)
+            .await
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to describe query: {}", e)))?
+            .columns()
+            .len();
+
+        sqlx::query(&json_rows_query(query, columns, limit))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to fetch rows: {}", e)))?
+            .iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<_, _>>()
+            .map_err(|e| EngineError::QueryExecution(format!("Failed to read row: {}", e)))
     }
 
     async fn get_version_info(&self) -> Result<DatabaseInfo, EngineError> {
+        let version_row = sqlx::query("SELECT sqlite_version()")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| EngineError::Connection(format!("Failed to get version: {}", e)))?;
+
+        let version: String = version_row
+            .try_get(0)
+            .map_err(|e| EngineError::Connection(format!("Failed to parse version: {}", e)))?;
+
         Ok(DatabaseInfo {
             engine_type: EngineType::SQLite,
-            version: "3.35+ (Placeholder)".to_string(),
-            connection_status: "Not implemented".to_string(),
+            version,
+            connection_status: "Connected".to_string(),
+            health: None,
+            socket: None,
+            schema: None,
+            search_path: None,
             features_supported: vec![DatabaseFeature::DetailedExecutionPlan],
         })
     }
@@ -139,47 +366,179 @@ impl DatabaseEngine for SQLiteEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::engines::EngineFactory;
+    use crate::db::tls::TlsConfig;
 
-    #[tokio::test]
-    async fn test_sqlite_engine_creation() {
+    async fn memory_engine() -> SQLiteEngine {
         let config = ConnectionConfig {
             engine_type: EngineType::SQLite,
-            connection_string: "/tmp/test.db".to_string(),
+            connection_string: "sqlite::memory:".to_string(),
             max_connections: None,
             timeout_seconds: None,
+            password_source: None,
+            tls: TlsConfig::default(),
+            search_path: None,
         };
 
-        let engine = SQLiteEngine::new(config).await;
-        assert!(engine.is_ok());
+        SQLiteEngine::new(config).await.unwrap()
     }
 
-    #[test]
-    fn test_sqlite_sample_queries() {
-        let config = ConnectionConfig {
+    fn row(id: i64, parent: i64, detail: &str) -> QueryPlanRow {
+        QueryPlanRow {
+            id,
+            parent,
+            detail: detail.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_engine_creation() {
+        let engine = memory_engine().await;
+        assert!(engine.test_connection().await.unwrap());
+
+        let missing = SQLiteEngine::new(ConnectionConfig {
             engine_type: EngineType::SQLite,
-            connection_string: "/tmp/test.db".to_string(),
+            connection_string: "/nonexistent/sqltrace-test.db".to_string(),
             max_connections: None,
             timeout_seconds: None,
-        };
+            password_source: None,
+            tls: TlsConfig::default(),
+            search_path: None,
+        })
+        .await;
+        assert!(missing.is_err());
+
+        // Only PostgreSQL and Redshift have a search path
+        let search_path = EngineFactory::create_engine(ConnectionConfig {
+            engine_type: EngineType::SQLite,
+            connection_string: "sqlite::memory:".to_string(),
+            max_connections: None,
+            timeout_seconds: None,
+            password_source: None,
+            tls: TlsConfig::default(),
+            search_path: Some("main".to_string()),
+        })
+        .await;
+        assert!(matches!(search_path, Err(EngineError::Configuration(_))));
+    }
 
-        let engine = SQLiteEngine { config };
+    #[tokio::test]
+    async fn test_sqlite_sample_queries() {
+        let engine = memory_engine().await;
         let samples = engine.get_sample_queries();
         assert!(!samples.is_empty());
         assert_eq!(samples[0].category, QueryCategory::BasicSelect);
     }
 
-    #[test]
-    fn test_sqlite_feature_support() {
-        let config = ConnectionConfig {
-            engine_type: EngineType::SQLite,
-            connection_string: "/tmp/test.db".to_string(),
-            max_connections: None,
-            timeout_seconds: None,
-        };
-
-        let engine = SQLiteEngine { config };
+    #[tokio::test]
+    async fn test_sqlite_feature_support() {
+        let engine = memory_engine().await;
         assert!(engine.supports_feature(&DatabaseFeature::DetailedExecutionPlan));
         assert!(!engine.supports_feature(&DatabaseFeature::ActualRowCounts));
         assert!(!engine.supports_feature(&DatabaseFeature::ParallelExecution));
     }
+
+    #[tokio::test]
+    async fn test_parse_sqlite_explain_hierarchy() {
+        let engine = memory_engine().await;
+        let plan = engine
+            .parse_sqlite_explain(&[
+                row(3, 0, "SCAN c"),
+                row(
+                    5,
+                    0,
+                    "SEARCH o USING INDEX idx_orders_customer (customer_id=?)",
+                ),
+                row(8, 0, "CORRELATED SCALAR SUBQUERY 1"),
+                row(
+                    12,
+                    8,
+                    "SEARCH r USING COVERING INDEX idx_refunds (order_id=?)",
+                ),
+                row(20, 0, "USE TEMP B-TREE FOR ORDER BY"),
+            ])
+            .unwrap();
+
+        let steps = &plan.root.plans;
+        assert_eq!(plan.root.node_type, "Query Plan");
+        assert_eq!(steps.len(), 4);
+        assert_eq!(steps[0].node_type, "Seq Scan");
+        assert_eq!(steps[0].relation_name.as_deref(), Some("c"));
+        assert_eq!(steps[1].node_type, "Index Scan");
+        assert_eq!(steps[1].extra["Index Name"], "idx_orders_customer");
+        assert_eq!(steps[1].index_cond.as_deref(), Some("customer_id=?"));
+        assert_eq!(steps[2].node_type, "SubPlan");
+        assert_eq!(steps[2].plans[0].node_type, "Index Only Scan");
+        assert_eq!(steps[3].node_type, "Sort");
+
+        assert!(engine.parse_sqlite_explain(&[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_explain_query_end_to_end() {
+        let engine = memory_engine().await;
+        sqlx::query(
+            "CREATE TABLE orders (id INTEGER PRIMARY KEY, customer_id INTEGER, total REAL)",
+        )
+        .execute(&engine.pool)
+        .await
+        .unwrap();
+        sqlx::query("CREATE INDEX idx_orders_customer ON orders (customer_id)")
+            .execute(&engine.pool)
+            .await
+            .unwrap();
+
+        let plan = engine
+            .explain_query("SELECT * FROM orders WHERE customer_id = 7")
+            .await
+            .unwrap();
+        let scan = &plan.root.plans[0];
+        assert_eq!(scan.node_type, "Index Scan");
+        assert_eq!(scan.relation_name.as_deref(), Some("orders"));
+
+        assert!(engine
+            .validate_query("SELECT * FROM missing")
+            .await
+            .is_err());
+
+        sqlx::query("INSERT INTO orders (customer_id, total) VALUES (7, 10), (7, 20), (8, 5)")
+            .execute(&engine.pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            engine
+                .count_rows(
+                    "SELECT * FROM orders WHERE customer_id = 7;",
+                    Duration::from_secs(5)
+                )
+                .await
+                .unwrap(),
+            2
+        );
+        // A count that never ends is interrupted once the timeout passes
+        let endless =
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) SELECT i FROM n";
+        assert!(engine
+            .count_rows(endless, Duration::from_millis(100))
+            .await
+            .is_err());
+        assert_eq!(
+            engine
+                .fetch_rows("SELECT customer_id, total FROM orders ORDER BY id", 2)
+                .await
+                .unwrap(),
+            vec!["[7,10.0]", "[7,20.0]"]
+        );
+        // Columns sharing a name keep their own values
+        assert_eq!(
+            engine
+                .fetch_rows(
+                    "SELECT a.id, b.id FROM orders a JOIN orders b ON b.id = a.id + 1 ORDER BY a.id",
+                    1
+                )
+                .await
+                .unwrap(),
+            vec!["[1,2]"]
+        );
+    }
 }
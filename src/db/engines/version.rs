@@ -0,0 +1,124 @@
+//! Server versions, for adapting to what a server supports
+//!
+//! EXPLAIN syntax and the fields plans report change between releases:
+//! PostgreSQL learned the `SETTINGS` option in 12 and `WAL` in 13, MySQL
+//! `EXPLAIN ANALYZE` in 8.0.18. Engines read the version of their server
+//! once and check it before using syntax an older server would reject.
+//! Until the version is known, a current server is assumed.
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+
+/// Release of a database server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ServerVersion {
+    /// Major version, such as 16 for PostgreSQL 16.2 or 8 for MySQL 8.0.35
+    pub major: u32,
+    /// Minor version; PostgreSQL 10 and later count their minor releases here
+    pub minor: u32,
+    /// Patch level, 0 where the server doesn't have one
+    pub patch: u32,
+}
+
+impl ServerVersion {
+    /// Version `major.minor.patch`
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Decode PostgreSQL's `server_version_num`: 160002 is 16.2, 90624 is 9.6.24
+    pub fn from_pg_num(num: u32) -> Self {
+        if num >= 100_000 {
+            Self::new(num / 10_000, num % 10_000, 0)
+        } else {
+            Self::new(num / 10_000, num / 100 % 100, num % 100)
+        }
+    }
+
+    /// The first dotted version number in `version`, such as `8.0.35` in
+    /// `8.0.35-0ubuntu0.22.04.1` or `16.2` in `PostgreSQL 16.2 on x86_64-pc-linux-gnu`
+    pub fn parse(version: &str) -> Option<Self> {
+        let start = version.find(|c: char| c.is_ascii_digit())?;
+        let number = version[start..]
+            .split(|c: char| !c.is_ascii_digit() && c != '.')
+            .next()?;
+        let mut parts = number.split('.').map(|part| part.parse::<u32>().ok());
+        let major = parts.next().flatten()?;
+        let minor = parts.next().flatten().unwrap_or(0);
+        let patch = parts.next().flatten().unwrap_or(0);
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl std::fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.patch == 0 {
+            write!(f, "{}.{}", self.major, self.minor)
+        } else {
+            write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+        }
+    }
+}
+
+/// Whether a server of `version` is at least `minimum`, assuming so while the version is unknown
+pub(super) fn at_least(version: Option<&ServerVersion>, minimum: ServerVersion) -> bool {
+    version.is_none_or(|version| *version >= minimum)
+}
+
+/// The version in `cell`, reading it with `detect` the first time
+///
+/// A failed read is logged and retried on the next call.
+pub(super) async fn detect<F, Fut>(
+    cell: &OnceCell<ServerVersion>,
+    detect: F,
+) -> Option<ServerVersion>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<ServerVersion, String>>,
+{
+    match cell.get_or_try_init(detect).await {
+        Ok(version) => Some(*version),
+        Err(e) => {
+            tracing::warn!("Failed to read the server version: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_versions() {
+        assert_eq!(
+            ServerVersion::from_pg_num(160002),
+            ServerVersion::new(16, 2, 0)
+        );
+        assert_eq!(
+            ServerVersion::from_pg_num(90624),
+            ServerVersion::new(9, 6, 24)
+        );
+        assert_eq!(
+            ServerVersion::parse("8.0.35-0ubuntu0.22.04.1"),
+            Some(ServerVersion::new(8, 0, 35))
+        );
+        assert_eq!(
+            ServerVersion::parse("PostgreSQL 16.2 on x86_64-pc-linux-gnu"),
+            Some(ServerVersion::new(16, 2, 0))
+        );
+        assert_eq!(ServerVersion::parse("unknown"), None);
+        assert_eq!(ServerVersion::new(16, 2, 0).to_string(), "16.2");
+        assert_eq!(ServerVersion::new(8, 0, 18).to_string(), "8.0.18");
+
+        assert!(at_least(None, ServerVersion::new(13, 0, 0)));
+        assert!(!at_least(
+            Some(&ServerVersion::new(12, 9, 0)),
+            ServerVersion::new(13, 0, 0)
+        ));
+    }
+}
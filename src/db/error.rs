@@ -2,11 +2,16 @@
 //!
 //! This module defines the error types used throughout the database module.
 
+use serde::{Deserialize, Serialize};
 use serde_json::Error as JsonError;
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::tokenizer::Tokenizer;
 use sqlx::Error as SqlxError;
 use std::io::Error as IoError;
 use thiserror::Error;
 
+use crate::db::engines::EngineError;
+
 /// Represents errors that can occur during database operations.
 #[derive(Error, Debug)]
 pub enum DbError {
@@ -41,6 +46,139 @@ pub enum DbError {
     /// Error occurred while parsing execution plan JSON
     #[error("Plan parsing error: {0}")]
     PlanParsing(String),
+
+    /// The operation is not available for the connected database engine
+    #[error("Unsupported operation: {0}")]
+    Unsupported(String),
+
+    /// The statement was cancelled on request
+    #[error("Query was cancelled")]
+    Cancelled,
+
+    /// Error reported by the database at a known position in the query
+    #[error("Query execution error: {message}")]
+    QueryAt {
+        /// The database's error message
+        message: String,
+        /// Where in the submitted query the error was reported
+        location: ErrorLocation,
+    },
+}
+
+impl From<EngineError> for DbError {
+    fn from(err: EngineError) -> Self {
+        match err {
+            EngineError::Connection(msg) => DbError::Connection(msg),
+            EngineError::QueryExecution(msg) => DbError::Query(msg),
+            EngineError::PlanParsing(msg) => DbError::PlanParsing(msg),
+            EngineError::UnsupportedOperation(msg) => DbError::Unsupported(msg),
+            EngineError::Configuration(msg) => DbError::Config(msg),
+        }
+    }
+}
+
+/// Position of an error in the submitted query text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorLocation {
+    /// Offset of the offending token, in characters from the start of the query
+    pub offset: usize,
+    /// Line of the offending token, starting from 1
+    pub line: usize,
+    /// Column of the offending token, in characters, starting from 1
+    pub column: usize,
+    /// Length of the offending token in characters (at least 1)
+    pub length: usize,
+}
+
+impl ErrorLocation {
+    /// Locate a character offset (0-based) in `query`
+    pub fn from_offset(query: &str, offset: usize) -> Option<Self> {
+        let chars: Vec<char> = query.chars().collect();
+        if offset >= chars.len() {
+            return None;
+        }
+
+        let before = &chars[..offset];
+        let line = before.iter().filter(|c| **c == '\n').count() + 1;
+        let column = offset - before.iter().rposition(|c| *c == '\n').map_or(0, |i| i + 1) + 1;
+
+        Some(Self {
+            offset,
+            line,
+            column,
+            length: token_length(query, line, column),
+        })
+    }
+
+    /// Locate a 1-based line and column in `query`
+    pub fn from_line_column(query: &str, line: usize, column: usize) -> Option<Self> {
+        if line == 0 || column == 0 {
+            return None;
+        }
+        let line_start: usize = query
+            .split('\n')
+            .take(line - 1)
+            .map(|l| l.chars().count() + 1)
+            .sum();
+        let location = Self::from_offset(query, line_start + column - 1)?;
+        (location.line == line).then_some(location)
+    }
+
+    /// Extract the "Line: N, Column M" position included in SQL parser errors
+    pub fn from_parser_message(query: &str, message: &str) -> Option<Self> {
+        let (_, position) = message.rsplit_once("Line: ")?;
+        let (line, column) = position.split_once(", Column ")?;
+        let column: String = column.chars().take_while(char::is_ascii_digit).collect();
+        Self::from_line_column(query, line.trim().parse().ok()?, column.parse().ok()?)
+    }
+}
+
+/// Length of the token starting at `line`/`column`, or 1 if it cannot be determined
+fn token_length(query: &str, line: usize, column: usize) -> usize {
+    let dialect = PostgreSqlDialect {};
+    Tokenizer::new(&dialect, query)
+        .tokenize_with_location()
+        .ok()
+        .and_then(|tokens| {
+            tokens
+                .into_iter()
+                .find(|t| t.location.line as usize == line && t.location.column as usize == column)
+        })
+        .map(|t| t.token.to_string().chars().count())
+        .filter(|length| *length > 0)
+        .unwrap_or(1)
+}
+
+impl DbError {
+    /// Convert a failed statement into an error, mapping any position the
+    /// database reports back into `query`
+    ///
+    /// `prefix` is whatever was prepended to `query` to form the statement
+    /// (e.g. `EXPLAIN (FORMAT JSON) `).
+    pub fn from_statement(err: SqlxError, query: &str, prefix: &str) -> Self {
+        if is_connection_error(&err) {
+            return DbError::Connection(err.to_string());
+        }
+        let location = err
+            .as_database_error()
+            .and_then(|db_err| db_err.try_downcast_ref::<sqlx::postgres::PgDatabaseError>())
+            .and_then(|pg_err| match pg_err.position() {
+                Some(sqlx::postgres::PgErrorPosition::Original(position)) => {
+                    // Positions are 1-based characters into the full statement
+                    position.checked_sub(prefix.chars().count() + 1)
+                }
+                _ => None,
+            })
+            .and_then(|offset| ErrorLocation::from_offset(query, offset));
+
+        match location {
+            Some(location) => DbError::QueryAt {
+                message: err.to_string(),
+                location,
+            },
+            None => DbError::Query(err.to_string()),
+        }
+    }
 }
 
 impl From<SqlxError> for DbError {
@@ -49,12 +187,69 @@ impl From<SqlxError> for DbError {
         match err {
             SqlxError::Io(io_err) => DbError::Io(io_err),
             SqlxError::Configuration(config_err) => DbError::Config(config_err.to_string()),
+            err if is_connection_error(&err) => DbError::Connection(err.to_string()),
             _ => DbError::Query(err.to_string()),
         }
     }
 }
 
+/// Whether `err` means the database could not be reached or the connection
+/// broke, rather than that the database rejected the statement
+///
+/// Besides transport failures this covers PostgreSQL's connection exceptions
+/// (class 08) and the server shutting down or starting up (57P01 to 57P03).
+pub fn is_connection_error(err: &SqlxError) -> bool {
+    match err {
+        SqlxError::Io(_)
+        | SqlxError::Tls(_)
+        | SqlxError::Protocol(_)
+        | SqlxError::PoolTimedOut
+        | SqlxError::PoolClosed
+        | SqlxError::WorkerCrashed => true,
+        SqlxError::Database(db_err) => db_err.code().is_some_and(|code| {
+            code.starts_with("08") || matches!(&*code, "57P01" | "57P02" | "57P03")
+        }),
+        _ => false,
+    }
+}
+
 /// Convenience type for Results that use DbError
 ///
 /// This is the standard result type returned by database operations.
 pub type Result<T> = std::result::Result<T, DbError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_from_offset() {
+        let query = "SELECT id,\n       nmae\nFROM users";
+        let location = ErrorLocation::from_offset(query, 18).unwrap();
+        assert_eq!(
+            location,
+            ErrorLocation {
+                offset: 18,
+                line: 2,
+                column: 8,
+                length: 4,
+            }
+        );
+        assert!(ErrorLocation::from_offset(query, 100).is_none());
+    }
+
+    #[test]
+    fn test_location_from_parser_message() {
+        let query = "SELECT 'abc\nFROM t";
+        let location = ErrorLocation::from_parser_message(
+            query,
+            "sql parser error: Unterminated string literal at Line: 1, Column 8",
+        )
+        .unwrap();
+        assert_eq!((location.offset, location.line, location.column), (7, 1, 8));
+        assert_eq!(location.length, 1);
+
+        assert!(ErrorLocation::from_parser_message(query, "Expected end of statement").is_none());
+        assert!(ErrorLocation::from_parser_message(query, "at Line: 1, Column 99").is_none());
+    }
+}
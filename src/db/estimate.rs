@@ -0,0 +1,130 @@
+//! Plan estimates without execution
+//!
+//! Some checks (query policies in particular) need to know what a query would
+//! touch and how expensive the planner thinks it is before it is allowed to
+//! run. A plain `EXPLAIN` answers both without executing anything.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::Row;
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// A relation referenced by a plan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedRelation {
+    /// Schema of the relation, when the plan reports it
+    pub schema: Option<String>,
+    /// Relation name
+    pub name: String,
+}
+
+/// What the planner expects a query to cost and touch
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlanEstimate {
+    /// Estimated total cost of the root node
+    pub total_cost: f64,
+    /// Relations scanned anywhere in the plan, without duplicates
+    pub relations: Vec<PlannedRelation>,
+}
+
+impl PlanEstimate {
+    /// Build an estimate from `EXPLAIN (VERBOSE, FORMAT JSON)` output
+    pub fn from_explain_json(explain_json: &Value) -> Result<Self, SqlTraceError> {
+        let root = explain_json
+            .get(0)
+            .and_then(|output| output.get("Plan"))
+            .ok_or_else(|| DbError::PlanParsing("Missing plan in EXPLAIN output".to_string()))?;
+
+        let mut estimate = PlanEstimate {
+            total_cost: root
+                .get("Total Cost")
+                .and_then(Value::as_f64)
+                .unwrap_or_default(),
+            relations: Vec::new(),
+        };
+
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            if let Some(name) = node.get("Relation Name").and_then(Value::as_str) {
+                let relation = PlannedRelation {
+                    schema: node
+                        .get("Schema")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                    name: name.to_string(),
+                };
+                if !estimate.relations.contains(&relation) {
+                    estimate.relations.push(relation);
+                }
+            }
+            if let Some(children) = node.get("Plans").and_then(Value::as_array) {
+                stack.extend(children.iter().rev());
+            }
+        }
+
+        Ok(estimate)
+    }
+}
+
+impl Database {
+    /// Plan a query without executing it
+    pub async fn estimate(&self, query: &str) -> Result<PlanEstimate, SqlTraceError> {
+        let prefix = "EXPLAIN (VERBOSE, FORMAT JSON) ";
+        let explain_query = format!("{}{}", prefix, query);
+
+        let row = sqlx::query(&explain_query)
+            .fetch_one(self.pg_pool()?)
+            .await
+            .map_err(|e| DbError::from_statement(e, query, prefix))?;
+
+        let plan_json: Value = row
+            .try_get("QUERY PLAN")
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+        PlanEstimate::from_explain_json(&plan_json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_estimate_from_explain_json() {
+        let explain = json!([{
+            "Plan": {
+                "Node Type": "Hash Join",
+                "Total Cost": 1250.5,
+                "Plans": [
+                    {"Node Type": "Seq Scan", "Relation Name": "orders", "Schema": "public"},
+                    {"Node Type": "Hash", "Plans": [
+                        {"Node Type": "Seq Scan", "Relation Name": "customers", "Schema": "pii"}
+                    ]},
+                    {"Node Type": "Seq Scan", "Relation Name": "orders", "Schema": "public"}
+                ]
+            }
+        }]);
+
+        let estimate = PlanEstimate::from_explain_json(&explain).unwrap();
+        assert_eq!(estimate.total_cost, 1250.5);
+        assert_eq!(
+            estimate.relations,
+            vec![
+                PlannedRelation {
+                    schema: Some("public".to_string()),
+                    name: "orders".to_string(),
+                },
+                PlannedRelation {
+                    schema: Some("pii".to_string()),
+                    name: "customers".to_string(),
+                },
+            ]
+        );
+
+        assert!(PlanEstimate::from_explain_json(&json!([])).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Options of PostgreSQL's EXPLAIN statement
+//!
+//! By default a query is explained with `ANALYZE, BUFFERS`, which executes
+//! it. Callers can instead ask for planner estimates only, or for extra
+//! detail such as output columns (`VERBOSE`), non-default planner settings
+//! (`SETTINGS`) or WAL records generated (`WAL`). Options newer than the
+//! server are refused before the statement is sent.
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::engines::version::ServerVersion;
+
+/// Which EXPLAIN options to explain a query with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExplainOptions {
+    /// Execute the query and report actual row counts and timings
+    pub analyze: bool,
+    /// Report buffer usage
+    pub buffers: bool,
+    /// Report output columns, schema-qualified names and other detail
+    pub verbose: bool,
+    /// Report planner settings that differ from their defaults (PostgreSQL 12+)
+    pub settings: bool,
+    /// Time each node; turning this off reduces the overhead of ANALYZE
+    pub timing: bool,
+    /// Report WAL records generated (PostgreSQL 13+); requires `analyze`
+    pub wal: bool,
+}
+
+impl Default for ExplainOptions {
+    fn default() -> Self {
+        Self {
+            analyze: true,
+            buffers: true,
+            verbose: false,
+            settings: false,
+            timing: true,
+            wal: false,
+        }
+    }
+}
+
+impl ExplainOptions {
+    /// Planner estimates only; the query is not executed
+    pub fn plan_only() -> Self {
+        Self {
+            analyze: false,
+            buffers: false,
+            ..Self::default()
+        }
+    }
+
+    /// Check that the options can be combined
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.analyze && self.wal {
+            return Err("EXPLAIN option wal requires analyze".to_string());
+        }
+        if !self.analyze && !self.timing {
+            return Err("EXPLAIN option timing can only be turned off with analyze".to_string());
+        }
+        Ok(())
+    }
+
+    /// Check that a PostgreSQL server of `version` knows the options
+    pub fn check_server(&self, version: ServerVersion) -> Result<(), String> {
+        for (requested, name, major) in [(self.settings, "settings", 12), (self.wal, "wal", 13)] {
+            if requested && version < ServerVersion::new(major, 0, 0) {
+                return Err(format!(
+                    "EXPLAIN option {} requires PostgreSQL {} or later, but the server runs {}",
+                    name, major, version
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The EXPLAIN statement prefix for these options, with JSON output
+    pub fn prefix(&self) -> String {
+        let mut options = Vec::new();
+        if self.analyze {
+            options.push("ANALYZE");
+        }
+        if self.buffers {
+            options.push("BUFFERS");
+        }
+        if self.verbose {
+            options.push("VERBOSE");
+        }
+        if self.settings {
+            options.push("SETTINGS");
+        }
+        if self.analyze && !self.timing {
+            options.push("TIMING FALSE");
+        }
+        if self.wal {
+            options.push("WAL");
+        }
+        options.push("FORMAT JSON");
+        format!("EXPLAIN ({}) ", options.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain_prefix() {
+        assert_eq!(
+            ExplainOptions::default().prefix(),
+            "EXPLAIN (ANALYZE, BUFFERS, FORMAT JSON) "
+        );
+        assert_eq!(
+            ExplainOptions::plan_only().prefix(),
+            "EXPLAIN (FORMAT JSON) "
+        );
+
+        let everything = ExplainOptions {
+            verbose: true,
+            settings: true,
+            timing: false,
+            wal: true,
+            ..ExplainOptions::default()
+        };
+        assert!(everything.validate().is_ok());
+        assert_eq!(
+            everything.prefix(),
+            "EXPLAIN (ANALYZE, BUFFERS, VERBOSE, SETTINGS, TIMING FALSE, WAL, FORMAT JSON) "
+        );
+    }
+
+    #[test]
+    fn test_explain_options_validation() {
+        assert!(ExplainOptions::plan_only().validate().is_ok());
+        let wal_without_analyze = ExplainOptions {
+            wal: true,
+            ..ExplainOptions::plan_only()
+        };
+        assert!(wal_without_analyze.validate().is_err());
+        let untimed_without_analyze = ExplainOptions {
+            timing: false,
+            ..ExplainOptions::plan_only()
+        };
+        assert!(untimed_without_analyze.validate().is_err());
+    }
+
+    #[test]
+    fn test_explain_options_server_version() {
+        let wal = ExplainOptions {
+            settings: true,
+            wal: true,
+            ..ExplainOptions::default()
+        };
+        assert!(wal.check_server(ServerVersion::new(13, 4, 0)).is_ok());
+        assert_eq!(
+            wal.check_server(ServerVersion::new(12, 9, 0)),
+            Err(
+                "EXPLAIN option wal requires PostgreSQL 13 or later, but the server runs 12.9"
+                    .to_string()
+            )
+        );
+        assert!(wal
+            .check_server(ServerVersion::new(11, 0, 0))
+            .unwrap_err()
+            .contains("option settings"));
+        assert!(ExplainOptions::default()
+            .check_server(ServerVersion::new(9, 6, 24))
+            .is_ok());
+    }
+}
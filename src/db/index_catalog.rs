@@ -0,0 +1,93 @@
+//! Indexes of the tables a plan reads, for PostgreSQL
+//!
+//! A filter evaluated row by row may mean an index is missing, or that one
+//! exists and the planner chose not to use it; the fixes are different. The
+//! catalog lists each table's indexes with their key columns, so advisor
+//! rules can tell the two apart.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// An index of a table, as far as the planner's choice of it is concerned
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableIndex {
+    /// Schema of the table
+    pub schema_name: String,
+    /// Table the index belongs to
+    pub table_name: String,
+    /// Name of the index
+    pub index_name: String,
+    /// Index access method (e.g., "btree", "gin")
+    pub access_method: String,
+    /// Key columns or expressions, in index order, excluding `INCLUDE` columns
+    pub columns: Vec<String>,
+    /// Whether the index enforces uniqueness
+    pub is_unique: bool,
+    /// Whether the index is valid; invalid indexes are never used by the planner
+    pub is_valid: bool,
+    /// `WHERE` clause of a partial index, which only covers the rows matching it
+    pub predicate: Option<String>,
+}
+
+impl TableIndex {
+    /// The first key column, the one a condition must constrain for a
+    /// B-tree index to narrow the scan
+    pub fn leading_column(&self) -> Option<&str> {
+        self.columns.first().map(String::as_str)
+    }
+}
+
+const TABLE_INDEXES_QUERY: &str = r#"
+SELECT n.nspname AS schema_name,
+       c.relname AS table_name,
+       i.relname AS index_name,
+       am.amname AS access_method,
+       ARRAY(SELECT pg_get_indexdef(x.indexrelid, k, true)
+               FROM generate_series(1, x.indnkeyatts) AS k
+              ORDER BY k) AS columns,
+       x.indisunique AS is_unique,
+       x.indisvalid AS is_valid,
+       pg_get_expr(x.indpred, x.indrelid, true) AS predicate
+  FROM pg_index x
+  JOIN pg_class c ON c.oid = x.indrelid
+  JOIN pg_class i ON i.oid = x.indexrelid
+  JOIN pg_namespace n ON n.oid = c.relnamespace
+  JOIN pg_am am ON am.oid = i.relam
+ WHERE c.relname = ANY ($1)
+ ORDER BY n.nspname, c.relname, i.relname
+"#;
+
+impl Database {
+    /// List the indexes of the given tables
+    pub async fn table_indexes(&self, tables: &[String]) -> Result<Vec<TableIndex>, SqlTraceError> {
+        if tables.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let rows = sqlx::query(TABLE_INDEXES_QUERY)
+            .bind(tables)
+            .fetch_all(self.pg_pool()?)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+        rows.iter()
+            .map(|row| -> Result<TableIndex, sqlx::Error> {
+                Ok(TableIndex {
+                    schema_name: row.try_get("schema_name")?,
+                    table_name: row.try_get("table_name")?,
+                    index_name: row.try_get("index_name")?,
+                    access_method: row.try_get("access_method")?,
+                    columns: row.try_get("columns")?,
+                    is_unique: row.try_get("is_unique")?,
+                    is_valid: row.try_get("is_valid")?,
+                    predicate: row.try_get("predicate")?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DbError::Query(e.to_string()).into())
+    }
+}
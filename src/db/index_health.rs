@@ -0,0 +1,338 @@
+//! Index health reporting for PostgreSQL
+//!
+//! This module inspects the catalog for per-index bloat estimates, invalid
+//! indexes left behind by failed concurrent builds, and indexes whose leading
+//! key is dominated by duplicates, and suggests maintenance for each.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// B-tree page header plus special space, in bytes
+const BTREE_PAGE_OVERHEAD: f64 = 24.0 + 16.0;
+
+/// Index tuple header plus line pointer, in bytes
+const BTREE_TUPLE_OVERHEAD: f64 = 8.0 + 4.0;
+
+/// Minimum share of an index that must be reclaimable to report it as bloated
+const BLOAT_RATIO_THRESHOLD: f64 = 0.3;
+
+/// Minimum number of reclaimable bytes to report an index as bloated
+const BLOAT_BYTES_THRESHOLD: i64 = 1024 * 1024;
+
+/// Distinct-to-rows ratio of the leading key below which an index is duplicate-heavy
+const DUPLICATE_RATIO_THRESHOLD: f64 = 0.01;
+
+/// Problems detected for a single index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IndexIssue {
+    /// The index is significantly larger than its estimated ideal size
+    Bloated,
+    /// The index is marked invalid and is not used by the planner
+    Invalid,
+    /// Most entries share a handful of leading key values
+    DuplicateKeyHeavy,
+}
+
+/// Raw catalog statistics for one index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexStats {
+    /// Schema containing the index
+    pub schema_name: String,
+    /// Table the index belongs to
+    pub table_name: String,
+    /// Name of the index
+    pub index_name: String,
+    /// Index definition as returned by pg_get_indexdef
+    pub definition: String,
+    /// Index access method (e.g., "btree", "gin")
+    pub access_method: String,
+    /// On-disk size of the index in bytes
+    pub size_bytes: i64,
+    /// Estimated number of index entries
+    pub reltuples: f64,
+    /// Number of pages the index occupies
+    pub relpages: i64,
+    /// Server block size in bytes
+    pub block_size: i64,
+    /// Index fillfactor in percent
+    pub fillfactor: i32,
+    /// Sum of the average widths of the indexed columns
+    pub key_width: i64,
+    /// `n_distinct` of the leading column from pg_stats, if analyzed
+    pub leading_n_distinct: Option<f64>,
+    /// Whether the index is valid
+    pub is_valid: bool,
+    /// Whether the index enforces uniqueness
+    pub is_unique: bool,
+}
+
+/// Health assessment for one index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexHealth {
+    /// Schema containing the index
+    pub schema_name: String,
+    /// Table the index belongs to
+    pub table_name: String,
+    /// Name of the index
+    pub index_name: String,
+    /// Index definition
+    pub definition: String,
+    /// On-disk size of the index in bytes
+    pub size_bytes: i64,
+    /// Estimated reclaimable bytes (B-tree indexes only)
+    pub estimated_bloat_bytes: Option<i64>,
+    /// Estimated reclaimable share of the index (0.0 - 1.0)
+    pub bloat_ratio: Option<f64>,
+    /// Distinct leading key values per index entry, if known
+    pub distinct_ratio: Option<f64>,
+    /// Whether the index is valid
+    pub is_valid: bool,
+    /// Detected problems
+    pub issues: Vec<IndexIssue>,
+    /// Suggested maintenance, if any issue was found
+    pub suggestion: Option<String>,
+}
+
+impl IndexStats {
+    /// Estimate the reclaimable bytes of a B-tree index from its statistics
+    pub fn estimated_bloat_bytes(&self) -> Option<i64> {
+        if self.access_method != "btree" || self.block_size <= 0 || self.relpages <= 0 {
+            return None;
+        }
+
+        let key_width = (self.key_width as f64 / 8.0).ceil() * 8.0;
+        let tuple_size = BTREE_TUPLE_OVERHEAD + key_width;
+        let usable_page =
+            (self.block_size as f64 - BTREE_PAGE_OVERHEAD) * self.fillfactor as f64 / 100.0;
+        // One extra page for the metapage
+        let estimated_pages = (self.reltuples.max(0.0) * tuple_size / usable_page).ceil() + 1.0;
+
+        let bloat_pages = (self.relpages as f64 - estimated_pages).max(0.0);
+        Some(bloat_pages as i64 * self.block_size)
+    }
+
+    /// Ratio of distinct leading key values to index entries
+    pub fn distinct_ratio(&self) -> Option<f64> {
+        let n_distinct = self.leading_n_distinct?;
+        if n_distinct < 0.0 {
+            // Negative values are already a fraction of the row count
+            Some(-n_distinct)
+        } else if self.reltuples > 0.0 {
+            Some((n_distinct / self.reltuples).min(1.0))
+        } else {
+            None
+        }
+    }
+
+    /// Assess the health of the index
+    pub fn assess(self) -> IndexHealth {
+        let estimated_bloat_bytes = self.estimated_bloat_bytes();
+        let bloat_ratio = estimated_bloat_bytes
+            .filter(|_| self.size_bytes > 0)
+            .map(|bloat| bloat as f64 / self.size_bytes as f64);
+        let distinct_ratio = self.distinct_ratio();
+        let qualified_name = format!("{}.{}", self.schema_name, self.index_name);
+
+        let mut issues = Vec::new();
+        let mut suggestions = Vec::new();
+
+        if !self.is_valid {
+            issues.push(IndexIssue::Invalid);
+            suggestions.push(format!(
+                "REINDEX INDEX CONCURRENTLY {}; -- invalid index ({}) is maintained on writes but never used",
+                qualified_name,
+                format_bytes(self.size_bytes)
+            ));
+        }
+
+        if let (Some(bloat), Some(ratio)) = (estimated_bloat_bytes, bloat_ratio) {
+            if self.is_valid && bloat >= BLOAT_BYTES_THRESHOLD && ratio >= BLOAT_RATIO_THRESHOLD {
+                issues.push(IndexIssue::Bloated);
+                suggestions.push(format!(
+                    "REINDEX INDEX CONCURRENTLY {}; -- reclaims about {} of {}",
+                    qualified_name,
+                    format_bytes(bloat),
+                    format_bytes(self.size_bytes)
+                ));
+            }
+        }
+
+        if let Some(ratio) = distinct_ratio {
+            if !self.is_unique && self.reltuples > 0.0 && ratio < DUPLICATE_RATIO_THRESHOLD {
+                issues.push(IndexIssue::DuplicateKeyHeavy);
+                suggestions.push(format!(
+                    "Index {} has only {:.2}% distinct leading keys; consider a partial index on the rare values, or REINDEX on PostgreSQL 13+ to apply B-tree deduplication",
+                    qualified_name,
+                    ratio * 100.0
+                ));
+            }
+        }
+
+        IndexHealth {
+            schema_name: self.schema_name,
+            table_name: self.table_name,
+            index_name: self.index_name,
+            definition: self.definition,
+            size_bytes: self.size_bytes,
+            estimated_bloat_bytes,
+            bloat_ratio,
+            distinct_ratio,
+            is_valid: self.is_valid,
+            issues,
+            suggestion: if suggestions.is_empty() {
+                None
+            } else {
+                Some(suggestions.join("\n"))
+            },
+        }
+    }
+}
+
+/// Format a byte count for display
+pub fn format_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+const INDEX_STATS_QUERY: &str = r#"
+SELECT n.nspname AS schema_name,
+       t.relname AS table_name,
+       c.relname AS index_name,
+       pg_get_indexdef(c.oid) AS definition,
+       am.amname::text AS access_method,
+       pg_relation_size(c.oid) AS size_bytes,
+       c.reltuples::float8 AS reltuples,
+       c.relpages::int8 AS relpages,
+       current_setting('block_size')::int8 AS block_size,
+       coalesce(substring(array_to_string(c.reloptions, ' ') from 'fillfactor=([0-9]+)')::int4, 90) AS fillfactor,
+       (SELECT coalesce(sum(s.avg_width), 0)::int8
+          FROM pg_attribute a
+          JOIN pg_stats s ON s.schemaname = n.nspname AND s.tablename = t.relname AND s.attname = a.attname
+         WHERE a.attrelid = t.oid AND a.attnum = ANY (i.indkey)) AS key_width,
+       (SELECT s.n_distinct::float8
+          FROM pg_attribute a
+          JOIN pg_stats s ON s.schemaname = n.nspname AND s.tablename = t.relname AND s.attname = a.attname
+         WHERE a.attrelid = t.oid AND a.attnum = i.indkey[0]) AS leading_n_distinct,
+       i.indisvalid AS is_valid,
+       i.indisunique AS is_unique
+  FROM pg_index i
+  JOIN pg_class c ON c.oid = i.indexrelid
+  JOIN pg_class t ON t.oid = i.indrelid
+  JOIN pg_namespace n ON n.oid = c.relnamespace
+  JOIN pg_am am ON am.oid = c.relam
+ WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+   AND n.nspname !~ '^pg_toast'
+   AND ($1::text IS NULL OR n.nspname = $1)
+ ORDER BY pg_relation_size(c.oid) DESC
+"#;
+
+impl Database {
+    /// Build an index health report, optionally restricted to one schema
+    pub async fn index_health_report(
+        &self,
+        schema: Option<&str>,
+    ) -> Result<Vec<IndexHealth>, SqlTraceError> {
+        let rows = sqlx::query(INDEX_STATS_QUERY)
+            .bind(schema)
+            .fetch_all(self.pg_pool()?)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+        rows.iter()
+            .map(|row| -> Result<IndexHealth, sqlx::Error> {
+                let stats = IndexStats {
+                    schema_name: row.try_get("schema_name")?,
+                    table_name: row.try_get("table_name")?,
+                    index_name: row.try_get("index_name")?,
+                    definition: row.try_get("definition")?,
+                    access_method: row.try_get("access_method")?,
+                    size_bytes: row.try_get("size_bytes")?,
+                    reltuples: row.try_get("reltuples")?,
+                    relpages: row.try_get("relpages")?,
+                    block_size: row.try_get("block_size")?,
+                    fillfactor: row.try_get("fillfactor")?,
+                    key_width: row.try_get("key_width")?,
+                    leading_n_distinct: row.try_get("leading_n_distinct")?,
+                    is_valid: row.try_get("is_valid")?,
+                    is_unique: row.try_get("is_unique")?,
+                };
+                Ok(stats.assess())
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DbError::Query(e.to_string()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(relpages: i64, reltuples: f64) -> IndexStats {
+        IndexStats {
+            schema_name: "public".to_string(),
+            table_name: "orders".to_string(),
+            index_name: "orders_customer_id_idx".to_string(),
+            definition:
+                "CREATE INDEX orders_customer_id_idx ON public.orders USING btree (customer_id)"
+                    .to_string(),
+            access_method: "btree".to_string(),
+            size_bytes: relpages * 8192,
+            reltuples,
+            relpages,
+            block_size: 8192,
+            fillfactor: 90,
+            key_width: 4,
+            leading_n_distinct: Some(-0.5),
+            is_valid: true,
+            is_unique: false,
+        }
+    }
+
+    #[test]
+    fn test_bloated_index_suggests_reindex() {
+        // One million 4-byte keys fit in roughly 2,700 pages
+        let health = stats(10_000, 1_000_000.0).assess();
+        assert_eq!(health.issues, vec![IndexIssue::Bloated]);
+        assert!(health.bloat_ratio.unwrap() > 0.7);
+        let suggestion = health.suggestion.unwrap();
+        assert!(suggestion.starts_with("REINDEX INDEX CONCURRENTLY public.orders_customer_id_idx"));
+        assert!(suggestion.contains("of 78.1 MB"));
+    }
+
+    #[test]
+    fn test_healthy_index_has_no_suggestion() {
+        let health = stats(2_800, 1_000_000.0).assess();
+        assert!(health.issues.is_empty());
+        assert!(health.suggestion.is_none());
+    }
+
+    #[test]
+    fn test_invalid_and_duplicate_heavy_indexes() {
+        let mut invalid = stats(100, 10_000.0);
+        invalid.is_valid = false;
+        assert_eq!(invalid.assess().issues, vec![IndexIssue::Invalid]);
+
+        let mut duplicates = stats(100, 10_000.0);
+        duplicates.leading_n_distinct = Some(3.0);
+        assert_eq!(
+            duplicates.assess().issues,
+            vec![IndexIssue::DuplicateKeyHeavy]
+        );
+    }
+}
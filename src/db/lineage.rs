@@ -0,0 +1,503 @@
+//! Column-level lineage for query output
+//!
+//! Wide reporting queries make it hard to tell which base columns feed an
+//! output column, or where an expensive expression comes from. Lineage is
+//! derived from the AST: every output column of the top-level SELECT is
+//! traced through aliases, derived tables, and CTEs down to the base table
+//! columns it reads. When the plan carries target lists (`EXPLAIN VERBOSE`),
+//! the planner's expression for each output column is attached as well.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{
+    Expr, FunctionArg, FunctionArgExpr, Ident, ObjectName, Query, Select, SelectItem, SetExpr,
+    Statement, TableFactor, TableWithJoins,
+};
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::parser::Parser;
+
+use crate::db::models::ExecutionPlan;
+use crate::db::relation_check::fold;
+
+/// Name PostgreSQL gives output columns it cannot name
+const UNNAMED_COLUMN: &str = "?column?";
+
+/// A base table column an output column reads
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnSource {
+    /// Table as referenced in the query, if it could be determined
+    pub table: Option<String>,
+    /// Column name, or `*` for a wildcard
+    pub column: String,
+}
+
+/// Where one output column of a query comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnLineage {
+    /// Position of the column in the output, starting from 0
+    pub position: usize,
+    /// Output column name
+    pub name: String,
+    /// SQL expression producing the column, if it is not a plain column reference
+    pub expression: Option<String>,
+    /// Base columns feeding the column
+    pub sources: Vec<ColumnSource>,
+    /// The planner's expression for the column, when the plan includes target lists
+    pub plan_output: Option<String>,
+}
+
+/// Trace the output columns of `sql` back to base table columns
+///
+/// Returns an empty list for statements that are not queries or cannot be parsed.
+pub fn column_lineage(sql: &str) -> Vec<ColumnLineage> {
+    let Ok(statements) = Parser::parse_sql(&PostgreSqlDialect {}, sql) else {
+        return Vec::new();
+    };
+    let Some(Statement::Query(query)) = statements.first() else {
+        return Vec::new();
+    };
+
+    query_columns(query, &HashMap::new(), None)
+        .into_iter()
+        .enumerate()
+        .map(|(position, column)| ColumnLineage {
+            position,
+            name: column.name,
+            expression: column.expression,
+            sources: column.sources,
+            plan_output: None,
+        })
+        .collect()
+}
+
+/// Attach the planner's output expressions from the plan's root target list
+pub fn attach_plan_outputs(lineage: &mut [ColumnLineage], plan: &ExecutionPlan) {
+    let Some(outputs) = plan.root.extra.get("Output").and_then(|o| o.as_array()) else {
+        return;
+    };
+    for (column, output) in lineage.iter_mut().zip(outputs) {
+        column.plan_output = output.as_str().map(str::to_string);
+    }
+}
+
+/// An output column while lineage is being computed
+#[derive(Debug, Clone)]
+struct Column {
+    name: String,
+    expression: Option<String>,
+    sources: Vec<ColumnSource>,
+}
+
+/// Something a FROM clause makes visible under a name
+#[derive(Debug, Clone)]
+enum Source {
+    /// A base table, by the name it was referenced with
+    Table(String),
+    /// A derived table or CTE with known output columns
+    Derived(Vec<Column>),
+}
+
+/// Names visible while resolving column references
+struct Scope<'a> {
+    sources: Vec<(String, Source)>,
+    parent: Option<&'a Scope<'a>>,
+}
+
+impl Scope<'_> {
+    fn resolve(&self, qualifier: Option<&str>, column: &str) -> Vec<ColumnSource> {
+        let found = match qualifier {
+            Some(qualifier) => self
+                .sources
+                .iter()
+                .find(|(alias, _)| alias == qualifier)
+                .map(|(_, source)| source_column(source, column)),
+            None => self.resolve_unqualified(column),
+        };
+
+        match (found, self.parent) {
+            (Some(sources), _) => sources,
+            (None, Some(parent)) => parent.resolve(qualifier, column),
+            (None, None) => vec![ColumnSource {
+                table: qualifier.map(str::to_string),
+                column: column.to_string(),
+            }],
+        }
+    }
+
+    fn resolve_unqualified(&self, column: &str) -> Option<Vec<ColumnSource>> {
+        // Derived tables list their columns, so a match there is certain
+        for (_, source) in &self.sources {
+            if let Source::Derived(columns) = source {
+                if let Some(found) = columns.iter().find(|c| c.name == column) {
+                    return Some(found.sources.clone());
+                }
+            }
+        }
+
+        let tables: Vec<&String> = self
+            .sources
+            .iter()
+            .filter_map(|(_, source)| match source {
+                Source::Table(table) => Some(table),
+                Source::Derived(_) => None,
+            })
+            .collect();
+        match tables.as_slice() {
+            [] => None,
+            [table] => Some(vec![ColumnSource {
+                table: Some((*table).clone()),
+                column: column.to_string(),
+            }]),
+            // Without the schema the owning table is ambiguous
+            _ => Some(vec![ColumnSource {
+                table: None,
+                column: column.to_string(),
+            }]),
+        }
+    }
+}
+
+fn source_column(source: &Source, column: &str) -> Vec<ColumnSource> {
+    match source {
+        Source::Table(table) => vec![ColumnSource {
+            table: Some(table.clone()),
+            column: column.to_string(),
+        }],
+        Source::Derived(columns) => columns
+            .iter()
+            .find(|c| c.name == column)
+            .map(|c| c.sources.clone())
+            .unwrap_or_default(),
+    }
+}
+
+fn query_columns(
+    query: &Query,
+    ctes: &HashMap<String, Vec<Column>>,
+    parent: Option<&Scope>,
+) -> Vec<Column> {
+    let mut ctes = ctes.clone();
+    if let Some(with) = &query.with {
+        for cte in &with.cte_tables {
+            let mut columns = query_columns(&cte.query, &ctes, parent);
+            rename_columns(&mut columns, &cte.alias.columns);
+            ctes.insert(fold(&cte.alias.name), columns);
+        }
+    }
+    set_expr_columns(&query.body, &ctes, parent)
+}
+
+fn set_expr_columns(
+    body: &SetExpr,
+    ctes: &HashMap<String, Vec<Column>>,
+    parent: Option<&Scope>,
+) -> Vec<Column> {
+    match body {
+        SetExpr::Select(select) => select_columns(select, ctes, parent),
+        SetExpr::Query(query) => query_columns(query, ctes, parent),
+        SetExpr::SetOperation { left, right, .. } => {
+            // Names come from the left side; each side feeds the column at the same position
+            let mut columns = set_expr_columns(left, ctes, parent);
+            for (column, other) in columns
+                .iter_mut()
+                .zip(set_expr_columns(right, ctes, parent))
+            {
+                for source in other.sources {
+                    push_unique(&mut column.sources, source);
+                }
+            }
+            columns
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn select_columns(
+    select: &Select,
+    ctes: &HashMap<String, Vec<Column>>,
+    parent: Option<&Scope>,
+) -> Vec<Column> {
+    let mut scope = Scope {
+        sources: Vec::new(),
+        parent,
+    };
+    for table in &select.from {
+        add_table_with_joins(&mut scope, table, ctes);
+    }
+
+    let mut columns = Vec::new();
+    for item in &select.projection {
+        match item {
+            SelectItem::UnnamedExpr(expr) => columns.push(expr_column(expr, None, &scope, ctes)),
+            SelectItem::ExprWithAlias { expr, alias } => {
+                columns.push(expr_column(expr, Some(alias), &scope, ctes))
+            }
+            SelectItem::Wildcard(_) => {
+                for (alias, source) in &scope.sources {
+                    columns.extend(wildcard_columns(alias, source));
+                }
+            }
+            SelectItem::QualifiedWildcard(name, _) => {
+                let qualifier = name.0.last().map(fold).unwrap_or_default();
+                if let Some((alias, source)) = scope.sources.iter().find(|(a, _)| *a == qualifier) {
+                    columns.extend(wildcard_columns(alias, source));
+                }
+            }
+        }
+    }
+    columns
+}
+
+fn wildcard_columns(alias: &str, source: &Source) -> Vec<Column> {
+    match source {
+        Source::Table(table) => vec![Column {
+            name: format!("{}.*", alias),
+            expression: None,
+            sources: vec![ColumnSource {
+                table: Some(table.clone()),
+                column: "*".to_string(),
+            }],
+        }],
+        Source::Derived(columns) => columns.clone(),
+    }
+}
+
+fn add_table_with_joins(
+    scope: &mut Scope,
+    table: &TableWithJoins,
+    ctes: &HashMap<String, Vec<Column>>,
+) {
+    add_table_factor(scope, &table.relation, ctes);
+    for join in &table.joins {
+        add_table_factor(scope, &join.relation, ctes);
+    }
+}
+
+fn add_table_factor(scope: &mut Scope, factor: &TableFactor, ctes: &HashMap<String, Vec<Column>>) {
+    match factor {
+        TableFactor::Table { name, alias, .. } => {
+            let table = object_name(name);
+            let last = name.0.last().map(fold).unwrap_or_default();
+            let visible = alias
+                .as_ref()
+                .map(|a| fold(&a.name))
+                .unwrap_or(last.clone());
+            let source = match (name.0.len(), ctes.get(&last)) {
+                (1, Some(columns)) => {
+                    let mut columns = columns.clone();
+                    if let Some(alias) = alias {
+                        rename_columns(&mut columns, &alias.columns);
+                    }
+                    Source::Derived(columns)
+                }
+                _ => Source::Table(table),
+            };
+            scope.sources.push((visible, source));
+        }
+        TableFactor::Derived {
+            subquery, alias, ..
+        } => {
+            let mut columns = query_columns(subquery, ctes, None);
+            let visible = match alias {
+                Some(alias) => {
+                    rename_columns(&mut columns, &alias.columns);
+                    fold(&alias.name)
+                }
+                None => String::new(),
+            };
+            scope.sources.push((visible, Source::Derived(columns)));
+        }
+        TableFactor::NestedJoin {
+            table_with_joins, ..
+        } => add_table_with_joins(scope, table_with_joins, ctes),
+        _ => {}
+    }
+}
+
+fn rename_columns(columns: &mut [Column], names: &[Ident]) {
+    for (column, name) in columns.iter_mut().zip(names) {
+        column.name = fold(name);
+    }
+}
+
+fn object_name(name: &ObjectName) -> String {
+    name.0.iter().map(fold).collect::<Vec<_>>().join(".")
+}
+
+fn expr_column(
+    expr: &Expr,
+    alias: Option<&Ident>,
+    scope: &Scope,
+    ctes: &HashMap<String, Vec<Column>>,
+) -> Column {
+    let mut sources = Vec::new();
+    collect_sources(expr, scope, ctes, &mut sources);
+
+    let (default_name, expression) = match expr {
+        Expr::Identifier(ident) => (fold(ident), None),
+        Expr::CompoundIdentifier(idents) => (idents.last().map(fold).unwrap_or_default(), None),
+        Expr::Function(function) => (
+            function.name.0.last().map(fold).unwrap_or_default(),
+            Some(expr.to_string()),
+        ),
+        _ => (UNNAMED_COLUMN.to_string(), Some(expr.to_string())),
+    };
+
+    Column {
+        name: alias.map(fold).unwrap_or(default_name),
+        expression,
+        sources,
+    }
+}
+
+fn collect_sources(
+    expr: &Expr,
+    scope: &Scope,
+    ctes: &HashMap<String, Vec<Column>>,
+    sources: &mut Vec<ColumnSource>,
+) {
+    let visit =
+        |e: &Expr, sources: &mut Vec<ColumnSource>| collect_sources(e, scope, ctes, sources);
+
+    match expr {
+        Expr::Identifier(ident) => {
+            for source in scope.resolve(None, &fold(ident)) {
+                push_unique(sources, source);
+            }
+        }
+        Expr::CompoundIdentifier(idents) => {
+            if let [.., qualifier, column] = idents.as_slice() {
+                for source in scope.resolve(Some(&fold(qualifier)), &fold(column)) {
+                    push_unique(sources, source);
+                }
+            }
+        }
+        Expr::Subquery(query) => {
+            for column in query_columns(query, ctes, Some(scope)) {
+                for source in column.sources {
+                    push_unique(sources, source);
+                }
+            }
+        }
+        Expr::Function(function) => {
+            for arg in &function.args {
+                let (FunctionArg::Named { arg, .. } | FunctionArg::Unnamed(arg)) = arg;
+                if let FunctionArgExpr::Expr(e) = arg {
+                    visit(e, sources);
+                }
+            }
+        }
+        Expr::BinaryOp { left, right, .. } => {
+            visit(left, sources);
+            visit(right, sources);
+        }
+        Expr::UnaryOp { expr, .. }
+        | Expr::Nested(expr)
+        | Expr::Cast { expr, .. }
+        | Expr::IsNull(expr)
+        | Expr::IsNotNull(expr) => visit(expr, sources),
+        Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => {
+            operand
+                .iter()
+                .chain(else_result.iter())
+                .for_each(|e| visit(e, sources));
+            conditions
+                .iter()
+                .chain(results.iter())
+                .for_each(|e| visit(e, sources));
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            visit(expr, sources);
+            visit(low, sources);
+            visit(high, sources);
+        }
+        Expr::InList { expr, list, .. } => {
+            visit(expr, sources);
+            list.iter().for_each(|e| visit(e, sources));
+        }
+        _ => {}
+    }
+}
+
+fn push_unique(sources: &mut Vec<ColumnSource>, source: ColumnSource) {
+    if !sources.contains(&source) {
+        sources.push(source);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(table: Option<&str>, column: &str) -> ColumnSource {
+        ColumnSource {
+            table: table.map(str::to_string),
+            column: column.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_lineage_through_aliases_and_ctes() {
+        let lineage = column_lineage(
+            r#"WITH totals (customer, spent) AS (
+                   SELECT customer_id, sum(amount * quantity) FROM sales.orders GROUP BY 1
+               )
+               SELECT c.name, t.spent AS lifetime_value, upper(c.email), 1
+               FROM customers c JOIN totals t ON t.customer = c.id"#,
+        );
+
+        let names: Vec<_> = lineage.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["name", "lifetime_value", "upper", "?column?"]);
+
+        assert_eq!(lineage[0].sources, vec![source(Some("customers"), "name")]);
+        assert_eq!(lineage[0].expression, None);
+        assert_eq!(
+            lineage[1].sources,
+            vec![
+                source(Some("sales.orders"), "amount"),
+                source(Some("sales.orders"), "quantity"),
+            ]
+        );
+        assert_eq!(lineage[2].expression.as_deref(), Some("upper(c.email)"));
+        assert_eq!(lineage[2].sources, vec![source(Some("customers"), "email")]);
+        assert!(lineage[3].sources.is_empty());
+    }
+
+    #[test]
+    fn test_lineage_wildcards_and_unions() {
+        let lineage = column_lineage(
+            "SELECT * FROM (SELECT id, total FROM orders) o UNION ALL SELECT id, amount FROM refunds",
+        );
+        assert_eq!(lineage.len(), 2);
+        assert_eq!(
+            lineage[1].sources,
+            vec![
+                source(Some("orders"), "total"),
+                source(Some("refunds"), "amount"),
+            ]
+        );
+
+        let joined = column_lineage("SELECT o.*, id FROM orders o, customers c");
+        assert_eq!(joined[0].sources, vec![source(Some("orders"), "*")]);
+        // Unqualified columns are ambiguous with several base tables
+        assert_eq!(joined[1].sources, vec![source(None, "id")]);
+
+        assert!(column_lineage("DELETE FROM orders").is_empty());
+    }
+
+    #[test]
+    fn test_correlated_subquery() {
+        let lineage = column_lineage(
+            "SELECT c.id, (SELECT max(o.total) FROM orders o WHERE o.customer_id = c.id) AS biggest FROM customers c",
+        );
+        assert_eq!(lineage[1].sources, vec![source(Some("orders"), "total")]);
+    }
+}
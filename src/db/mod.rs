@@ -1,58 +1,321 @@
 //! Database interaction layer for SQLTrace
 //!
-//! This module handles connections to PostgreSQL and parses execution plans
-//! returned by EXPLAIN queries. It also provides an abstract interface for
-//! multiple database engines.
+//! This module wraps the database engine detected from the connection string
+//! and parses execution plans returned by EXPLAIN queries. Features that read
+//! PostgreSQL catalogs directly are only available on PostgreSQL connections.
 
 use serde_json::Value;
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres, Row};
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
 use std::time::Duration;
 
+pub mod background_activity;
+pub mod baseline;
+pub mod breaker;
+pub mod cancel;
+pub mod connections;
+pub mod credentials;
 pub mod engines;
 pub mod error;
+pub mod estimate;
+pub mod explain_options;
+pub mod index_catalog;
+pub mod index_health;
+pub mod lineage;
 pub mod models;
+pub mod monitor;
+pub mod plan_diff;
+pub mod plan_overlay;
+pub mod pool_metrics;
+pub mod prepared;
+pub mod preview;
+pub mod privileges;
+pub mod relation_check;
+pub mod relation_sizes;
+pub mod schema;
+pub mod schema_cache;
+pub mod sequences;
+pub mod session;
+pub mod settings;
+pub mod stats;
+pub mod table_definitions;
+pub mod tls;
+pub mod transaction;
+pub mod workload;
 
+use crate::db::cancel::RunningQuery;
+use crate::db::credentials::PasswordSource;
+use crate::db::engines::postgresql::PostgreSQLEngine;
+use crate::db::engines::version::ServerVersion;
+use crate::db::engines::{
+    ConnectionConfig, DatabaseEngine, DatabaseEngineImpl, DatabaseFeature, DatabaseInfo,
+    EngineFactory, EngineType,
+};
 use crate::db::error::DbError;
+use crate::db::explain_options::ExplainOptions;
 use crate::db::models::plan::{ExecutionPlan, ExplainPlan, PlanNode};
+use crate::db::pool_metrics::AcquireStats;
+use crate::db::tls::TlsConfig;
 use crate::SqlTraceError;
 
+/// Pool size used when a connection does not set one
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 5;
+
+/// Seconds to wait for a pooled connection when a connection does not set a timeout
+pub const DEFAULT_TIMEOUT_SECONDS: u64 = 3;
+
 /// Database connection manager
 #[derive(Debug, Clone)]
 pub struct Database {
-    pool: Pool<Postgres>,
+    engine: Arc<DatabaseEngineImpl>,
+    /// Connection string with any password removed, safe to log or return
+    redacted_url: Option<String>,
+    /// Settings the database was connected with, so it can be connected again
+    config: Option<Arc<ConnectionConfig>>,
+    /// Waits for connections from the pool
+    acquire_stats: Arc<AcquireStats>,
 }
 
 impl Database {
-    /// Create a new database connection pool
+    /// Connect to the database, detecting the engine from the connection string
     pub async fn new(connection_string: &str) -> Result<Self, SqlTraceError> {
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .acquire_timeout(Duration::from_secs(3))
-            .connect(connection_string)
+        Self::connect(connection_string, None, &TlsConfig::default()).await
+    }
+
+    /// Connect to the database, reading the password from `password_source`
+    pub async fn with_password_source(
+        connection_string: &str,
+        password_source: &PasswordSource,
+    ) -> Result<Self, SqlTraceError> {
+        Self::connect(
+            connection_string,
+            Some(password_source),
+            &TlsConfig::default(),
+        )
+        .await
+    }
+
+    /// Connect to the database with TLS settings, reading the password from `password_source` if given
+    pub async fn with_tls(
+        connection_string: &str,
+        password_source: Option<&PasswordSource>,
+        tls: &TlsConfig,
+    ) -> Result<Self, SqlTraceError> {
+        Self::connect(connection_string, password_source, tls).await
+    }
+
+    /// Connect to the database described by `config`
+    ///
+    /// Unset pool settings default to [`DEFAULT_MAX_CONNECTIONS`] and
+    /// [`DEFAULT_TIMEOUT_SECONDS`].
+    pub async fn with_config(mut config: ConnectionConfig) -> Result<Self, SqlTraceError> {
+        config.max_connections = Some(config.max_connections.unwrap_or(DEFAULT_MAX_CONNECTIONS));
+        config.timeout_seconds = Some(config.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS));
+        let redacted_url = config.redacted_connection_string();
+        let engine = EngineFactory::create_engine(config.clone())
             .await
-            .map_err(|e| DbError::Connection(e.to_string()))?;
+            .map_err(DbError::from)?;
+
+        Ok(Self {
+            engine: Arc::new(engine),
+            redacted_url: Some(redacted_url),
+            config: Some(Arc::new(config)),
+            acquire_stats: Arc::default(),
+        })
+    }
 
-        Ok(Self { pool })
+    /// Connect again with the settings this database was connected with
+    ///
+    /// Returns a database with a new pool; this one is left as it is.
+    /// Databases created from an existing pool can't be reconnected.
+    pub async fn reconnect(&self) -> Result<Self, SqlTraceError> {
+        let config = self.config.as_deref().ok_or_else(|| {
+            DbError::Unsupported("the connection settings of this database are unknown".to_string())
+        })?;
+        Self::with_config(config.clone()).await
     }
 
-    /// Create a new Database instance from an existing connection pool
-    pub fn from_pool(pool: Pool<Postgres>) -> Self {
-        Self { pool }
+    async fn connect(
+        connection_string: &str,
+        password_source: Option<&PasswordSource>,
+        tls: &TlsConfig,
+    ) -> Result<Self, SqlTraceError> {
+        let engine_type =
+            EngineFactory::detect_engine_type(connection_string).map_err(DbError::from)?;
+        Self::with_config(ConnectionConfig {
+            engine_type,
+            connection_string: connection_string.to_string(),
+            max_connections: None,
+            timeout_seconds: None,
+            password_source: password_source.cloned(),
+            tls: tls.clone(),
+            search_path: None,
+        })
+        .await
+    }
+
+    /// Create a new Database instance from an existing PostgreSQL connection pool
+    pub fn from_pool(pool: PgPool) -> Self {
+        Self {
+            engine: Arc::new(DatabaseEngineImpl::PostgreSQL(PostgreSQLEngine::from_pool(
+                pool,
+            ))),
+            redacted_url: None,
+            config: None,
+            acquire_stats: Arc::default(),
+        }
+    }
+
+    /// The engine this database is served by
+    pub fn engine_type(&self) -> EngineType {
+        self.engine.engine_type()
+    }
+
+    /// Whether the engine supports `feature`
+    pub fn supports_feature(&self, feature: &DatabaseFeature) -> bool {
+        self.engine.supports_feature(feature)
+    }
+
+    /// Version of the database server, where the engine reads it
+    ///
+    /// Only PostgreSQL and MySQL connections know their version; others return `None`.
+    pub async fn server_version(&self) -> Option<ServerVersion> {
+        match self.engine.as_ref() {
+            DatabaseEngineImpl::PostgreSQL(engine) => engine.server_version().await,
+            DatabaseEngineImpl::MySQL(engine) => engine.server_version().await,
+            _ => None,
+        }
+    }
+
+    /// The PostgreSQL pool, for features that query PostgreSQL directly
+    pub(crate) fn pg_pool(&self) -> Result<&PgPool, DbError> {
+        match self.engine.as_ref() {
+            DatabaseEngineImpl::PostgreSQL(engine) => Ok(engine.pool()),
+            other => Err(DbError::Unsupported(format!(
+                "this feature requires PostgreSQL, but the connection is {}",
+                other.engine_type()
+            ))),
+        }
+    }
+
+    /// Periodically re-resolve the password from `source` so rotated credentials are
+    /// picked up by new pool connections
+    ///
+    /// Existing connections stay open; only connections opened after a refresh use
+    /// the new password. Failed refreshes are logged and the previous password is kept.
+    /// Returns `None` for engines that do not support refreshing credentials.
+    pub fn spawn_credential_refresh(
+        &self,
+        source: PasswordSource,
+        interval: Duration,
+    ) -> Option<tokio::task::JoinHandle<()>> {
+        let pool = self.pg_pool().ok()?.clone();
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately; the pool already has a fresh password
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+
+                let resolver = source.clone();
+                match tokio::task::spawn_blocking(move || resolver.resolve()).await {
+                    Ok(Ok(password)) => {
+                        let options = (*pool.connect_options()).clone().password(&password);
+                        pool.set_connect_options(options);
+                        tracing::debug!("Refreshed database credentials");
+                    }
+                    Ok(Err(e)) => {
+                        tracing::warn!("Failed to refresh database credentials: {}", e)
+                    }
+                    Err(e) => tracing::warn!("Credential refresh task failed: {}", e),
+                }
+            }
+        }))
+    }
+
+    /// The connection string with any password redacted, if known
+    pub fn redacted_url(&self) -> Option<&str> {
+        self.redacted_url.as_deref()
     }
 
     /// Execute a query and get the execution plan
     pub async fn explain(&self, query: &str) -> Result<ExecutionPlan, SqlTraceError> {
+        self.explain_with_options(query, &ExplainOptions::default())
+            .await
+    }
+
+    /// Get the execution plan of a query with the given EXPLAIN options
+    ///
+    /// Options other than the defaults are only supported on PostgreSQL.
+    pub async fn explain_with_options(
+        &self,
+        query: &str,
+        options: &ExplainOptions,
+    ) -> Result<ExecutionPlan, SqlTraceError> {
+        self.explain_raw(query, options, None)
+            .await
+            .map(|(plan, _)| plan)
+    }
+
+    /// Get the execution plan of a query together with the raw EXPLAIN JSON it was parsed from
+    ///
+    /// The raw output is only available on PostgreSQL; other engines return `None`.
+    /// With `running`, the statement can be cancelled through it on PostgreSQL.
+    pub async fn explain_raw(
+        &self,
+        query: &str,
+        options: &ExplainOptions,
+        running: Option<&RunningQuery>,
+    ) -> Result<(ExecutionPlan, Option<Value>), SqlTraceError> {
         // First validate the query
         self.validate_query(query)?;
+        options.validate().map_err(SqlTraceError::InvalidQuery)?;
+
+        match self.engine.as_ref() {
+            DatabaseEngineImpl::PostgreSQL(engine) => {
+                if let Some(version) = engine.server_version().await {
+                    options
+                        .check_server(version)
+                        .map_err(DbError::Unsupported)?;
+                }
+            }
+            other => {
+                if *options != ExplainOptions::default() {
+                    return Err(DbError::Unsupported(format!(
+                        "EXPLAIN options are not supported for {}",
+                        self.engine_type()
+                    ))
+                    .into());
+                }
+                return other
+                    .explain_query(query)
+                    .await
+                    .map(|plan| (plan, None))
+                    .map_err(|e| DbError::from(e).into());
+            }
+        }
 
-        // Execute EXPLAIN ANALYZE with JSON output
-        let explain_query = format!("EXPLAIN (ANALYZE, BUFFERS, FORMAT JSON) {}", query);
+        let prefix = options.prefix();
+        let explain_query = format!("{}{}", prefix, query);
 
-        // Execute the EXPLAIN query directly
-        let row = sqlx::query(&explain_query)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e: sqlx::Error| DbError::Query(e.to_string()))
+        let mut conn = self.acquire_pg().await?;
+        // Read on the same connection, so the values are the ones the planner sees
+        let settings = settings::plan_settings(&mut conn).await;
+        if let Some(running) = running {
+            running.attach(&mut conn).await?;
+        }
+
+        // ANALYZE executes the query, so nothing it does may persist
+        let row = engines::postgresql::fetch_one_read_only(&mut *conn, &explain_query).await;
+        if let Some(running) = running {
+            running.detach().await;
+        }
+        let row = row
+            .map_err(|e| match running {
+                Some(running) if running.is_cancelled() => DbError::Cancelled,
+                _ => DbError::from_statement(e, query, &prefix),
+            })
             .map_err(SqlTraceError::from)?;
 
         // The result is a single column containing the JSON plan
@@ -61,21 +324,6 @@ impl Database {
             .map_err(|e: sqlx::Error| DbError::Query(e.to_string()))
             .map_err(SqlTraceError::from)?;
 
-        // Log the raw plan JSON to a file for debugging
-        let debug_path = "debug_plan.json";
-        let json_str = serde_json::to_string_pretty(&plan_json)
-            .unwrap_or_else(|_| "<invalid JSON>".to_string());
-
-        if let Err(e) = std::fs::write(debug_path, &json_str) {
-            eprintln!("Failed to write debug plan to {}: {}", debug_path, e);
-        } else {
-            eprintln!("Wrote debug plan to {}", debug_path);
-            eprintln!(
-                "Raw plan JSON (first 1000 chars): {}",
-                &json_str.chars().take(1000).collect::<String>()
-            );
-        }
-
         // First, try to parse as an array of plans (the common case)
         let explain_plan = if let Ok(explain_outputs) =
             serde_json::from_value::<Vec<ExplainPlan>>(plan_json.clone())
@@ -105,6 +353,9 @@ impl Database {
                     .get("Execution Time")
                     .and_then(|v| v.as_f64())
                     .unwrap_or(0.0),
+                jit: plan_json
+                    .get("JIT")
+                    .and_then(|v| serde_json::from_value(v.clone()).ok()),
             }
         }
         // If all else fails, return a descriptive error with the actual JSON for debugging
@@ -118,11 +369,47 @@ impl Database {
         };
 
         // Convert to our internal ExecutionPlan format
-        Ok(ExecutionPlan {
+        let plan = ExecutionPlan {
             root: explain_plan.plan,
             planning_time: explain_plan.planning_time,
             execution_time: explain_plan.execution_time,
-        })
+            settings,
+            jit: explain_plan.jit,
+        };
+        Ok((plan, Some(plan_json)))
+    }
+
+    /// Check that the database answers queries and report its version
+    pub async fn version_info(&self) -> Result<DatabaseInfo, SqlTraceError> {
+        Ok(self
+            .engine
+            .get_version_info()
+            .await
+            .map_err(DbError::from)?)
+    }
+
+    /// Execute `query` and return the number of rows it produces, stopping it after `timeout`
+    pub async fn count_rows(&self, query: &str, timeout: Duration) -> Result<u64, SqlTraceError> {
+        self.validate_query(query)?;
+        Ok(self
+            .engine
+            .count_rows(query, timeout)
+            .await
+            .map_err(DbError::from)?)
+    }
+
+    /// Execute `query` and return up to `limit` rows, each as a JSON array of its values
+    pub async fn fetch_rows(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<String>, SqlTraceError> {
+        self.validate_query(query)?;
+        Ok(self
+            .engine
+            .fetch_rows(query, limit)
+            .await
+            .map_err(DbError::from)?)
     }
 
     /// Validate that a query is a SELECT query
@@ -168,6 +455,8 @@ pub fn parse_execution_plan(explain_json: &Value) -> Result<ExecutionPlan, SqlTr
         root: explain_plan.plan,
         planning_time: explain_plan.planning_time,
         execution_time: explain_plan.execution_time,
+        settings: None,
+        jit: explain_plan.jit,
     })
 }
 
@@ -208,6 +497,18 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_explain_through_sqlite_engine() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        assert_eq!(db.engine_type(), EngineType::SQLite);
+
+        let plan = db.explain("SELECT 1").await.unwrap();
+        assert_eq!(plan.root.node_type, "Query Plan");
+
+        // Catalog-based features are PostgreSQL-only
+        assert!(db.index_health_report(None).await.is_err());
+    }
+
     #[tokio::test]
     async fn test_validate_query() {
         let db = get_test_db().await;
@@ -3,8 +3,10 @@
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+use crate::db::settings::ServerSettings;
+
 /// Represents a single node in an execution plan
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PlanNode {
     /// Type of the plan node (e.g., "Seq Scan", "Index Scan")
     #[serde(rename = "Node Type")]
@@ -30,37 +32,780 @@ pub struct PlanNode {
     #[serde(rename = "Actual Startup Time")]
     pub actual_startup_time: Option<f64>,
 
-    /// Actual total time in milliseconds
-    #[serde(rename = "Actual Total Time")]
+    /// Actual total time in milliseconds; zero when the query was not executed
+    #[serde(rename = "Actual Total Time", default)]
     pub actual_total_time: f64,
 
     /// Actual number of rows returned by this node
-    #[serde(rename = "Actual Rows")]
+    #[serde(rename = "Actual Rows", default)]
     pub actual_rows: u64,
 
     /// Number of loops executed by this node
-    #[serde(rename = "Actual Loops")]
+    #[serde(rename = "Actual Loops", default)]
     pub actual_loops: u64,
 
+    /// Condition an index scan looks rows up by
+    #[serde(
+        rename = "Index Cond",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub index_cond: Option<String>,
+
+    /// Condition rows are checked against after they are read
+    #[serde(rename = "Filter", default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+
+    /// Condition a join checks pairs of rows against once they are matched
+    #[serde(
+        rename = "Join Filter",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub join_filter: Option<String>,
+
+    /// Rows per loop that `filter` removed (EXPLAIN ANALYZE)
+    #[serde(
+        rename = "Rows Removed by Filter",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub rows_removed_by_filter: Option<u64>,
+
+    /// Rows per loop that `join_filter` removed (EXPLAIN ANALYZE)
+    #[serde(
+        rename = "Rows Removed by Join Filter",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub rows_removed_by_join_filter: Option<u64>,
+
+    /// Aggregation strategy (e.g., "Hashed", "Sorted", "Plain", "Mixed")
+    #[serde(rename = "Strategy", default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+
+    /// Number of partitions the planner expected a hash aggregate to spill into (PG13+)
+    #[serde(
+        rename = "Planned Partitions",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub planned_partitions: Option<u64>,
+
+    /// Number of batches a hash aggregate actually used; more than one means it spilled (PG13+)
+    #[serde(
+        rename = "HashAgg Batches",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub hash_agg_batches: Option<u64>,
+
+    /// Buckets of a Hash node's hash table when it finished
+    #[serde(
+        rename = "Hash Buckets",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub hash_buckets: Option<u64>,
+
+    /// Buckets a Hash node's hash table was sized with; fewer than `hash_buckets` means it grew
+    #[serde(
+        rename = "Original Hash Buckets",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub original_hash_buckets: Option<u64>,
+
+    /// Batches a Hash node split its input into; more than one means the join spilled to disk
+    #[serde(
+        rename = "Hash Batches",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub hash_batches: Option<u64>,
+
+    /// Batches the planner expected a Hash node to need; fewer than `hash_batches` means
+    /// the inner side outgrew its estimate during execution
+    #[serde(
+        rename = "Original Hash Batches",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub original_hash_batches: Option<u64>,
+
+    /// Peak memory used by the node in kilobytes
+    #[serde(
+        rename = "Peak Memory Usage",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub peak_memory_usage_kb: Option<u64>,
+
+    /// Disk space used by the node in kilobytes
+    #[serde(
+        rename = "Disk Usage",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub disk_usage_kb: Option<u64>,
+
+    /// How a sort ran: "quicksort" or "top-N heapsort" in memory, "external sort" or
+    /// "external merge" when it spilled to disk
+    #[serde(
+        rename = "Sort Method",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sort_method: Option<String>,
+
+    /// Space a sort used in kilobytes, in memory or on disk depending on `sort_space_type`
+    #[serde(
+        rename = "Sort Space Used",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sort_space_used_kb: Option<u64>,
+
+    /// Where a sort ran: "Memory", or "Disk" when it spilled
+    #[serde(
+        rename = "Sort Space Type",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sort_space_type: Option<String>,
+
+    /// Leading sort keys an Incremental Sort's input is already ordered by (PG13+)
+    #[serde(
+        rename = "Presorted Key",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub presorted_key: Option<Vec<String>>,
+
+    /// Groups an Incremental Sort sorted by all of its keys (PG13+)
+    #[serde(
+        rename = "Full-sort Groups",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub full_sort_groups: Option<SortGroups>,
+
+    /// Large groups of equal presorted keys an Incremental Sort sorted by the remaining keys only (PG13+)
+    #[serde(
+        rename = "Pre-sorted Groups",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub pre_sorted_groups: Option<SortGroups>,
+
+    /// Lookups a Memoize node answered from its cache (PG14+)
+    #[serde(
+        rename = "Cache Hits",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cache_hits: Option<u64>,
+
+    /// Lookups a Memoize node had to pass to its subplan (PG14+)
+    #[serde(
+        rename = "Cache Misses",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cache_misses: Option<u64>,
+
+    /// Entries a Memoize node removed to stay within its memory limit (PG14+)
+    #[serde(
+        rename = "Cache Evictions",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cache_evictions: Option<u64>,
+
+    /// Times a Memoize node could not cache a lookup's rows even after evicting (PG14+)
+    #[serde(
+        rename = "Cache Overflows",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cache_overflows: Option<u64>,
+
+    /// Query a postgres_fdw Foreign Scan sends to the remote server (EXPLAIN VERBOSE)
+    #[serde(
+        rename = "Remote SQL",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub remote_sql: Option<String>,
+
+    /// Remote tables a Foreign Scan joins on the remote server, e.g.
+    /// `(public.orders o) INNER JOIN (public.users u)`; absent for a single table
+    #[serde(rename = "Relations", default, skip_serializing_if = "Option::is_none")]
+    pub foreign_relations: Option<String>,
+
+    /// Parallel workers the planner asked for (Gather and Gather Merge)
+    #[serde(
+        rename = "Workers Planned",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub workers_planned: Option<u64>,
+
+    /// Parallel workers that actually started (EXPLAIN ANALYZE)
+    #[serde(
+        rename = "Workers Launched",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub workers_launched: Option<u64>,
+
+    /// Figures of each parallel worker that ran the node; timings and rows need VERBOSE
+    #[serde(rename = "Workers", default, skip_serializing_if = "Vec::is_empty")]
+    pub workers: Vec<WorkerStats>,
+
+    /// Time spent reading blocks, by the node and its children, in milliseconds
+    /// (BUFFERS with `track_io_timing` on); "Shared I/O Read Time" since PostgreSQL 17
+    #[serde(
+        rename = "I/O Read Time",
+        alias = "Shared I/O Read Time",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub io_read_time: Option<f64>,
+
+    /// Time spent writing blocks, by the node and its children, in milliseconds
+    #[serde(
+        rename = "I/O Write Time",
+        alias = "Shared I/O Write Time",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub io_write_time: Option<f64>,
+
     /// Child nodes in the execution plan
     #[serde(default, rename = "Plans")]
     pub plans: Vec<PlanNode>,
 
+    /// Blocks hit, read, dirtied and written by the node and its children (EXPLAIN BUFFERS)
+    #[serde(
+        flatten,
+        default,
+        deserialize_with = "deserialize_buffers",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub buffers: Option<BufferUsage>,
+
     /// Additional node-specific output
     #[serde(flatten)]
     pub extra: serde_json::Value,
 }
 
+/// Block counts reported by EXPLAIN (BUFFERS), in blocks of 8 kB by default
+///
+/// PostgreSQL reports the counts of a node including those of its children.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BufferUsage {
+    /// Shared blocks found in shared buffers
+    #[serde(rename = "Shared Hit Blocks")]
+    pub shared_hit: u64,
+    /// Shared blocks read from the operating system
+    #[serde(rename = "Shared Read Blocks")]
+    pub shared_read: u64,
+    /// Shared blocks modified by the node
+    #[serde(rename = "Shared Dirtied Blocks")]
+    pub shared_dirtied: u64,
+    /// Shared blocks evicted and written out by the node
+    #[serde(rename = "Shared Written Blocks")]
+    pub shared_written: u64,
+    /// Blocks of temporary tables found in local buffers
+    #[serde(rename = "Local Hit Blocks")]
+    pub local_hit: u64,
+    /// Blocks of temporary tables read
+    #[serde(rename = "Local Read Blocks")]
+    pub local_read: u64,
+    /// Blocks of temporary tables modified
+    #[serde(rename = "Local Dirtied Blocks")]
+    pub local_dirtied: u64,
+    /// Blocks of temporary tables written out
+    #[serde(rename = "Local Written Blocks")]
+    pub local_written: u64,
+    /// Blocks read back from temporary files, e.g. by spilled sorts and hashes
+    #[serde(rename = "Temp Read Blocks")]
+    pub temp_read: u64,
+    /// Blocks written to temporary files
+    #[serde(rename = "Temp Written Blocks")]
+    pub temp_written: u64,
+}
+
+impl BufferUsage {
+    /// Blocks found in memory, shared or local
+    pub fn hit(&self) -> u64 {
+        self.shared_hit + self.local_hit
+    }
+
+    /// Blocks read from outside the buffer caches, temporary files included
+    pub fn read(&self) -> u64 {
+        self.shared_read + self.local_read + self.temp_read
+    }
+
+    /// Blocks written, temporary files included
+    pub fn written(&self) -> u64 {
+        self.shared_written + self.local_written + self.temp_written
+    }
+
+    /// Share of shared and local block accesses found in memory, if there were any
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let accessed = self.hit() + self.shared_read + self.local_read;
+        (accessed > 0).then(|| self.hit() as f64 / accessed as f64)
+    }
+
+    /// Counts of `self` less those of `other`, stopping at zero
+    pub fn saturating_sub(&self, other: &BufferUsage) -> BufferUsage {
+        BufferUsage {
+            shared_hit: self.shared_hit.saturating_sub(other.shared_hit),
+            shared_read: self.shared_read.saturating_sub(other.shared_read),
+            shared_dirtied: self.shared_dirtied.saturating_sub(other.shared_dirtied),
+            shared_written: self.shared_written.saturating_sub(other.shared_written),
+            local_hit: self.local_hit.saturating_sub(other.local_hit),
+            local_read: self.local_read.saturating_sub(other.local_read),
+            local_dirtied: self.local_dirtied.saturating_sub(other.local_dirtied),
+            local_written: self.local_written.saturating_sub(other.local_written),
+            temp_read: self.temp_read.saturating_sub(other.temp_read),
+            temp_written: self.temp_written.saturating_sub(other.temp_written),
+        }
+    }
+}
+
+impl std::ops::Add for BufferUsage {
+    type Output = BufferUsage;
+
+    fn add(self, other: BufferUsage) -> BufferUsage {
+        BufferUsage {
+            shared_hit: self.shared_hit + other.shared_hit,
+            shared_read: self.shared_read + other.shared_read,
+            shared_dirtied: self.shared_dirtied + other.shared_dirtied,
+            shared_written: self.shared_written + other.shared_written,
+            local_hit: self.local_hit + other.local_hit,
+            local_read: self.local_read + other.local_read,
+            local_dirtied: self.local_dirtied + other.local_dirtied,
+            local_written: self.local_written + other.local_written,
+            temp_read: self.temp_read + other.temp_read,
+            temp_written: self.temp_written + other.temp_written,
+        }
+    }
+}
+
+/// Read the buffer counts of a node, if it has any
+///
+/// EXPLAIN prints every count once BUFFERS is on, but plans written by hand
+/// or trimmed by other tools may carry only some; those missing count as zero.
+fn deserialize_buffers<'de, D>(deserializer: D) -> Result<Option<BufferUsage>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Counts {
+        #[serde(rename = "Shared Hit Blocks")]
+        shared_hit: Option<u64>,
+        #[serde(rename = "Shared Read Blocks")]
+        shared_read: Option<u64>,
+        #[serde(rename = "Shared Dirtied Blocks")]
+        shared_dirtied: Option<u64>,
+        #[serde(rename = "Shared Written Blocks")]
+        shared_written: Option<u64>,
+        #[serde(rename = "Local Hit Blocks")]
+        local_hit: Option<u64>,
+        #[serde(rename = "Local Read Blocks")]
+        local_read: Option<u64>,
+        #[serde(rename = "Local Dirtied Blocks")]
+        local_dirtied: Option<u64>,
+        #[serde(rename = "Local Written Blocks")]
+        local_written: Option<u64>,
+        #[serde(rename = "Temp Read Blocks")]
+        temp_read: Option<u64>,
+        #[serde(rename = "Temp Written Blocks")]
+        temp_written: Option<u64>,
+    }
+
+    let counts = Counts::deserialize(deserializer)?;
+    let all = [
+        counts.shared_hit,
+        counts.shared_read,
+        counts.shared_dirtied,
+        counts.shared_written,
+        counts.local_hit,
+        counts.local_read,
+        counts.local_dirtied,
+        counts.local_written,
+        counts.temp_read,
+        counts.temp_written,
+    ];
+    if all.iter().all(Option::is_none) {
+        return Ok(None);
+    }
+    Ok(Some(BufferUsage {
+        shared_hit: counts.shared_hit.unwrap_or(0),
+        shared_read: counts.shared_read.unwrap_or(0),
+        shared_dirtied: counts.shared_dirtied.unwrap_or(0),
+        shared_written: counts.shared_written.unwrap_or(0),
+        local_hit: counts.local_hit.unwrap_or(0),
+        local_read: counts.local_read.unwrap_or(0),
+        local_dirtied: counts.local_dirtied.unwrap_or(0),
+        local_written: counts.local_written.unwrap_or(0),
+        temp_read: counts.temp_read.unwrap_or(0),
+        temp_written: counts.temp_written.unwrap_or(0),
+    }))
+}
+
+/// What one parallel worker did in a node below a Gather or Gather Merge
+///
+/// The node's own figures are averages over the leader and the workers, so
+/// these show whether the work was spread evenly.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkerStats {
+    /// Number of the worker, from 0
+    #[serde(rename = "Worker Number")]
+    pub worker_number: u32,
+    /// Time until the worker returned its first row, in milliseconds
+    #[serde(
+        rename = "Actual Startup Time",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub actual_startup_time: Option<f64>,
+    /// Time the worker spent in the node, in milliseconds
+    #[serde(
+        rename = "Actual Total Time",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub actual_total_time: Option<f64>,
+    /// Rows the worker returned per loop
+    #[serde(
+        rename = "Actual Rows",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub actual_rows: Option<u64>,
+    /// Times the worker ran the node
+    #[serde(
+        rename = "Actual Loops",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub actual_loops: Option<u64>,
+    /// Other per-worker output, such as the sort method or buffer counts
+    #[serde(flatten)]
+    pub extra: serde_json::Value,
+}
+
+/// Space used to sort the groups of one kind of an Incremental Sort
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SortSpace {
+    /// Average space a group used in kilobytes
+    #[serde(rename = "Average Sort Space Used", default)]
+    pub average_kb: u64,
+    /// Most space a group used in kilobytes
+    #[serde(rename = "Peak Sort Space Used", default)]
+    pub peak_kb: u64,
+}
+
+/// Groups of one kind sorted by an Incremental Sort
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SortGroups {
+    /// Number of groups sorted
+    #[serde(rename = "Group Count", default)]
+    pub group_count: u64,
+    /// Sort methods used for the groups (e.g., "quicksort", "top-N heapsort")
+    #[serde(rename = "Sort Methods Used", default)]
+    pub sort_methods_used: Vec<String>,
+    /// Space used by groups sorted in memory
+    #[serde(
+        rename = "Sort Space Memory",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub memory: Option<SortSpace>,
+    /// Space used by groups that spilled to disk
+    #[serde(
+        rename = "Sort Space Disk",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub disk: Option<SortSpace>,
+}
+
 impl PlanNode {
     /// Get the actual time as a Duration
     ///
     /// Returns a `Duration` representing the time spent in this node.
     /// The duration is in milliseconds, matching PostgreSQL's EXPLAIN ANALYZE output.
     pub fn actual_duration(&self) -> Duration {
-        Duration::from_millis((self.actual_total_time * self.actual_loops as f64) as u64)
+        Duration::from_millis(self.total_time() as u64)
+    }
+
+    /// Get the total number of rows produced across all loops
+    ///
+    /// PostgreSQL reports `Actual Rows` as a per-loop average, so nodes on the
+    /// inner side of a nested loop need to be multiplied by `Actual Loops`.
+    pub fn total_rows(&self) -> u64 {
+        self.actual_rows.saturating_mul(self.actual_loops.max(1))
+    }
+
+    /// Get the total time spent in this node across all loops, in milliseconds
+    ///
+    /// Like `Actual Rows`, `Actual Total Time` is a per-loop average.
+    pub fn total_time(&self) -> f64 {
+        self.actual_total_time * self.actual_loops.max(1) as f64
+    }
+
+    /// Iterate over this node and all of its descendants in pre-order
+    pub fn iter(&self) -> impl Iterator<Item = &PlanNode> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.plans.iter().rev());
+            Some(node)
+        })
+    }
+
+    /// Whether this node is an aggregate using the hashed strategy
+    pub fn is_hash_aggregate(&self) -> bool {
+        self.node_type == "HashAggregate"
+            || (self.node_type == "Aggregate" && self.strategy.as_deref() == Some("Hashed"))
+    }
+
+    /// Whether a hash aggregate had to spill to disk
+    pub fn hash_agg_spilled(&self) -> bool {
+        self.hash_agg_batches.unwrap_or(0) > 1 || self.disk_usage_kb.unwrap_or(0) > 0
+    }
+
+    /// Whether a Hash node split its input into batches written to disk
+    pub fn hash_spilled(&self) -> bool {
+        self.hash_batches.unwrap_or(0) > 1
+    }
+
+    /// Whether a Hash node needed more batches than it was planned with
+    pub fn hash_batches_grew(&self) -> bool {
+        match (self.original_hash_batches, self.hash_batches) {
+            (Some(original), Some(batches)) => batches > original,
+            _ => false,
+        }
+    }
+
+    /// Whether a sort had to spill to disk
+    pub fn sort_spilled(&self) -> bool {
+        self.sort_space_type.as_deref() == Some("Disk")
+            || self
+                .sort_method
+                .as_deref()
+                .is_some_and(|method| method.starts_with("external"))
+    }
+
+    /// Whether a sort ran entirely in memory
+    pub fn sort_in_memory(&self) -> bool {
+        self.sort_space_type.as_deref() == Some("Memory") && !self.sort_spilled()
+    }
+
+    /// Keys the node sorts by, from its `Sort Key`
+    pub fn sort_keys(&self) -> Vec<&str> {
+        self.extra
+            .get("Sort Key")
+            .and_then(|v| v.as_array())
+            .map(|keys| keys.iter().filter_map(|k| k.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Groups of both kinds sorted by an Incremental Sort
+    pub fn incremental_sort_groups(&self) -> impl Iterator<Item = &SortGroups> {
+        self.full_sort_groups
+            .iter()
+            .chain(self.pre_sorted_groups.iter())
+    }
+
+    /// Most disk space any group of an Incremental Sort spilled, in kilobytes
+    pub fn incremental_sort_disk_kb(&self) -> Option<u64> {
+        self.incremental_sort_groups()
+            .filter_map(|groups| groups.disk.as_ref().map(|disk| disk.peak_kb))
+            .max()
+    }
+
+    /// Memory this node held at its peak in kilobytes, if it reported any
+    ///
+    /// Hash, hash aggregate and Memoize nodes report their peak memory; sorts
+    /// report the space they used, which is memory only if they didn't spill.
+    /// An Incremental Sort sorts one group at a time, so its peak is that of
+    /// its largest group.
+    pub fn memory_used_kb(&self) -> Option<u64> {
+        self.peak_memory_usage_kb
+            .or_else(|| {
+                self.sort_space_used_kb
+                    .filter(|_| self.sort_space_type.as_deref() == Some("Memory"))
+            })
+            .or_else(|| {
+                self.incremental_sort_groups()
+                    .filter_map(|groups| groups.memory.as_ref().map(|memory| memory.peak_kb))
+                    .max()
+            })
+    }
+
+    /// Share of a Memoize node's lookups answered from its cache
+    pub fn cache_hit_ratio(&self) -> Option<f64> {
+        let hits = self.cache_hits?;
+        let lookups = hits + self.cache_misses.unwrap_or(0);
+        (lookups > 0).then(|| hits as f64 / lookups as f64)
+    }
+
+    /// Whether this node reads a foreign table, or a join of them pushed to the remote server
+    pub fn is_foreign_scan(&self) -> bool {
+        self.node_type == "Foreign Scan"
+    }
+
+    /// Rows the node produced across all loops before its Filter removed any
+    pub fn rows_before_filter(&self) -> u64 {
+        let removed = self
+            .rows_removed_by_filter
+            .unwrap_or(0)
+            .saturating_mul(self.actual_loops.max(1));
+        self.total_rows().saturating_add(removed)
+    }
+
+    /// Buffer counts of this node alone, without those of its children
+    ///
+    /// `None` if the plan was made without BUFFERS. Children of an InitPlan or
+    /// SubPlan are counted like any other, though their counts may already be
+    /// missing from the node's own.
+    pub fn own_buffers(&self) -> Option<BufferUsage> {
+        let total = self.buffers?;
+        let children = self
+            .plans
+            .iter()
+            .filter_map(|child| child.buffers)
+            .fold(BufferUsage::default(), |sum, child| sum + child);
+        Some(total.saturating_sub(&children))
+    }
+
+    /// Planned parallel workers that did not start, if any
+    ///
+    /// Workers start only while `max_parallel_workers` and
+    /// `max_worker_processes` leave slots free, so a busy server runs the
+    /// node with fewer, or with the leader alone.
+    pub fn workers_not_launched(&self) -> Option<u64> {
+        let missing = self.workers_planned?.saturating_sub(self.workers_launched?);
+        (missing > 0).then_some(missing)
+    }
+
+    /// Time spent reading and writing blocks, by the node and its children, in milliseconds
+    ///
+    /// `None` unless the plan was made with BUFFERS and `track_io_timing` on.
+    pub fn io_time(&self) -> Option<f64> {
+        match (self.io_read_time, self.io_write_time) {
+            (None, None) => None,
+            (read, write) => Some(read.unwrap_or(0.0) + write.unwrap_or(0.0)),
+        }
+    }
+
+    /// Time spent on I/O by this node alone, without its children's, in milliseconds
+    pub fn own_io_time(&self) -> Option<f64> {
+        let children: f64 = self.plans.iter().filter_map(PlanNode::io_time).sum();
+        self.io_time().map(|total| (total - children).max(0.0))
+    }
+
+    /// Look up a string-valued field that was not mapped to a typed field
+    pub fn extra_str(&self, key: &str) -> Option<&str> {
+        self.extra.get(key).and_then(|v| v.as_str())
+    }
+
+    /// Look up an integer-valued field that was not mapped to a typed field
+    pub fn extra_u64(&self, key: &str) -> Option<u64> {
+        self.extra.get(key).and_then(|v| v.as_u64())
     }
 }
 
+/// Which JIT compilation steps were applied to the query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JitOptions {
+    /// Small functions and operators were inlined into the generated code
+    #[serde(rename = "Inlining", default)]
+    pub inlining: bool,
+    /// The generated code was optimized by LLVM
+    #[serde(rename = "Optimization", default)]
+    pub optimization: bool,
+    /// Expressions were compiled
+    #[serde(rename = "Expressions", default)]
+    pub expressions: bool,
+    /// Tuple deforming was compiled
+    #[serde(rename = "Deforming", default)]
+    pub deforming: bool,
+}
+
+/// Time each JIT compilation step took, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct JitTiming {
+    /// Generating the code, including deforming
+    #[serde(
+        rename = "Generation",
+        default,
+        deserialize_with = "deserialize_step_time"
+    )]
+    pub generation: f64,
+    /// Inlining functions into the generated code
+    #[serde(
+        rename = "Inlining",
+        default,
+        deserialize_with = "deserialize_step_time"
+    )]
+    pub inlining: f64,
+    /// Optimizing the generated code
+    #[serde(
+        rename = "Optimization",
+        default,
+        deserialize_with = "deserialize_step_time"
+    )]
+    pub optimization: f64,
+    /// Emitting machine code
+    #[serde(
+        rename = "Emission",
+        default,
+        deserialize_with = "deserialize_step_time"
+    )]
+    pub emission: f64,
+    /// All of the steps together
+    #[serde(rename = "Total", default, deserialize_with = "deserialize_step_time")]
+    pub total: f64,
+}
+
+/// JIT compilation of a query, from the `JIT` block of a PostgreSQL plan
+///
+/// PostgreSQL compiles a query once its estimated cost passes `jit_above_cost`.
+/// Compiling pays off for long-running analytical queries, but on a query
+/// that the planner overestimated it can take longer than running it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JitInfo {
+    /// Number of functions compiled
+    #[serde(rename = "Functions", default)]
+    pub functions: u64,
+    /// Steps that were applied
+    #[serde(rename = "Options", default)]
+    pub options: JitOptions,
+    /// Time the steps took; `None` unless the plan was made with ANALYZE and TIMING
+    #[serde(rename = "Timing", default, skip_serializing_if = "Option::is_none")]
+    pub timing: Option<JitTiming>,
+}
+
+/// Read a JIT step time, which PostgreSQL 17 reports as an object with a
+/// `Total` and a breakdown for `Generation`
+fn deserialize_step_time<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(value
+        .as_f64()
+        .or_else(|| value.get("Total").and_then(serde_json::Value::as_f64))
+        .unwrap_or(0.0))
+}
+
 /// Represents a single plan in the PostgreSQL EXPLAIN output
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExplainPlan {
@@ -69,12 +814,16 @@ pub struct ExplainPlan {
     pub plan: PlanNode,
 
     /// Planning time in milliseconds
-    #[serde(rename = "Planning Time")]
+    #[serde(rename = "Planning Time", default)]
     pub planning_time: f64,
 
     /// Execution time in milliseconds
-    #[serde(rename = "Execution Time")]
+    #[serde(rename = "Execution Time", default)]
     pub execution_time: f64,
+
+    /// JIT compilation of the query, if it was compiled
+    #[serde(rename = "JIT", default, skip_serializing_if = "Option::is_none")]
+    pub jit: Option<JitInfo>,
 }
 
 /// Represents the top-level structure of a PostgreSQL EXPLAIN output
@@ -91,6 +840,14 @@ pub struct ExecutionPlan {
 
     /// Total execution time in milliseconds
     pub execution_time: f64,
+
+    /// Planner-relevant server settings the plan was made under, if they were read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<ServerSettings>,
+
+    /// JIT compilation of the query, if it was compiled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jit: Option<JitInfo>,
 }
 
 impl ExecutionPlan {
@@ -107,4 +864,66 @@ impl ExecutionPlan {
     pub fn execution_duration(&self) -> Duration {
         Duration::from_millis(self.execution_time as u64)
     }
+
+    /// Rows produced by all nodes of the plan, across loops
+    pub fn rows_processed(&self) -> u64 {
+        self.root
+            .iter()
+            .fold(0u64, |rows, node| rows.saturating_add(node.total_rows()))
+    }
+
+    /// Estimated peak memory of the plan in kilobytes, if any node reported its memory
+    ///
+    /// Nodes of one plan hold their memory at the same time (a hash table is
+    /// kept until the join above it finishes), so the estimate adds up the
+    /// peaks of all nodes. That overstates plans whose memory-hungry nodes run
+    /// one after another, but is what the query may need in the worst case.
+    pub fn peak_memory_kb(&self) -> Option<u64> {
+        self.root
+            .iter()
+            .filter_map(PlanNode::memory_used_kb)
+            .reduce(|total, kb| total.saturating_add(kb))
+    }
+
+    /// Whether the plan read blocks but carries no I/O timings, because
+    /// `track_io_timing` was off when it was made
+    ///
+    /// With `track_io_timing` on, EXPLAIN reports read and write times on
+    /// every node, even when they are zero.
+    pub fn io_timing_missing(&self) -> bool {
+        let read = self.root.buffers.is_some_and(|b| b.read() > 0);
+        let tracked = match self
+            .settings
+            .as_ref()
+            .and_then(|s| s.get("track_io_timing"))
+        {
+            Some(value) => value == "on",
+            None => self.root.iter().any(|node| node.io_read_time.is_some()),
+        };
+        read && !tracked
+    }
+
+    /// Time spent compiling the query, in milliseconds, if it was compiled and timed
+    pub fn jit_time(&self) -> Option<f64> {
+        self.jit.as_ref()?.timing.map(|timing| timing.total)
+    }
+
+    /// Share of the execution time spent on JIT compilation, which the execution time includes
+    pub fn jit_share(&self) -> Option<f64> {
+        let jit_time = self.jit_time()?;
+        (self.execution_time > 0.0).then(|| (jit_time / self.execution_time).min(1.0))
+    }
+
+    /// Names of all relations accessed anywhere in the plan, without duplicates
+    pub fn relation_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for node in self.root.iter() {
+            if let Some(name) = &node.relation_name {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+        }
+        names
+    }
 }
@@ -0,0 +1,278 @@
+//! Background health checks of registered connections
+//!
+//! Every connection is pinged on an interval and its latency and last error
+//! are recorded, so a connection that has started failing shows up as
+//! degraded before a user's request runs into it. A connection that keeps
+//! failing is connected again with a fresh pool, backing off exponentially
+//! between attempts; requests already holding the old pool finish on it.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::db::breaker::Circuit;
+use crate::db::connections::{ConnectionManager, ManagedConnection};
+use crate::storage::now_millis;
+
+/// How often connections are checked and when they are reconnected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonitorConfig {
+    /// Time between checks of each connection, in seconds; 0 disables monitoring
+    pub interval_secs: u64,
+    /// Time a check may take before it counts as failed, in seconds
+    pub timeout_secs: u64,
+    /// Consecutive failed checks after which a connection is down and reconnected
+    pub reconnect_after_failures: u32,
+    /// Longest wait between reconnection attempts, in seconds
+    pub max_backoff_secs: u64,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 30,
+            timeout_secs: 5,
+            reconnect_after_failures: 3,
+            max_backoff_secs: 300,
+        }
+    }
+}
+
+impl MonitorConfig {
+    /// Wait before reconnection attempt number `attempt`, counting from 0:
+    /// one check interval, doubling with every attempt up to `max_backoff_secs`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let secs = self
+            .interval_secs
+            .max(1)
+            .saturating_mul(1u64 << attempt.min(32));
+        Duration::from_secs(secs.min(self.max_backoff_secs.max(1)))
+    }
+}
+
+/// State of a connection as of its last check
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionStatus {
+    /// Not checked yet
+    #[default]
+    Unknown,
+    /// The last check succeeded
+    Healthy,
+    /// Recent checks failed, but fewer than `reconnect_after_failures` in a row
+    Degraded,
+    /// At least `reconnect_after_failures` checks in a row failed
+    Down,
+}
+
+/// Outcome of the recent checks of a connection
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionHealth {
+    /// State as of the last check
+    pub status: ConnectionStatus,
+    /// Round trip time of the last successful check in milliseconds
+    pub latency_ms: Option<f64>,
+    /// When the connection was last checked, in milliseconds since the epoch
+    pub last_checked_at: Option<i64>,
+    /// Why the last check or reconnection attempt failed, cleared by a successful check
+    pub last_error: Option<String>,
+    /// Checks that failed since the last successful one
+    pub consecutive_failures: u32,
+    /// Reconnection attempts since the last successful check
+    pub reconnect_attempts: u32,
+    /// When the connection was last reconnected or attempted to be, in milliseconds since the epoch
+    pub last_reconnect_at: Option<i64>,
+    /// Circuit breaker fed by the analyses run on the connection
+    #[serde(default)]
+    pub circuit: Circuit,
+}
+
+impl ConnectionHealth {
+    /// Record a successful check that took `latency_ms`
+    pub fn record_success(&mut self, latency_ms: f64, now: i64) {
+        self.status = ConnectionStatus::Healthy;
+        self.latency_ms = Some(latency_ms);
+        self.last_checked_at = Some(now);
+        self.last_error = None;
+        self.consecutive_failures = 0;
+        self.reconnect_attempts = 0;
+    }
+
+    /// Record a failed check
+    pub fn record_failure(&mut self, error: String, now: i64, config: &MonitorConfig) {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        self.status = if self.consecutive_failures >= config.reconnect_after_failures.max(1) {
+            ConnectionStatus::Down
+        } else {
+            ConnectionStatus::Degraded
+        };
+        self.last_checked_at = Some(now);
+        self.last_error = Some(error);
+    }
+
+    /// Whether the connection is down and has waited out the backoff since its last attempt
+    pub fn reconnect_due(&self, now: i64, config: &MonitorConfig) -> bool {
+        if self.status != ConnectionStatus::Down {
+            return false;
+        }
+        match (
+            self.last_reconnect_at,
+            self.reconnect_attempts.checked_sub(1),
+        ) {
+            (Some(last), Some(previous)) => {
+                now.saturating_sub(last) >= config.backoff(previous).as_millis() as i64
+            }
+            _ => true,
+        }
+    }
+
+    /// Record a reconnection attempt, with the error if it failed
+    pub fn record_reconnect(&mut self, error: Option<String>, now: i64) {
+        self.reconnect_attempts = self.reconnect_attempts.saturating_add(1);
+        self.last_reconnect_at = Some(now);
+        if let Some(error) = error {
+            self.last_error = Some(format!("Reconnect failed: {}", error));
+        }
+    }
+}
+
+/// Check every registered connection every `config.interval_secs`
+///
+/// Returns `None` without spawning anything when monitoring is disabled.
+pub fn spawn_monitor(
+    connections: Arc<ConnectionManager>,
+    config: MonitorConfig,
+) -> Option<tokio::task::JoinHandle<()>> {
+    if config.interval_secs == 0 {
+        return None;
+    }
+
+    let interval = Duration::from_secs(config.interval_secs);
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            check_all(&connections, &config).await;
+        }
+    }))
+}
+
+/// Check each registered connection once, reconnecting those that are due
+pub async fn check_all(connections: &ConnectionManager, config: &MonitorConfig) {
+    for connection in connections.list() {
+        check(connections, &connection, config).await;
+    }
+}
+
+async fn check(
+    connections: &ConnectionManager,
+    connection: &ManagedConnection,
+    config: &MonitorConfig,
+) {
+    let timeout = Duration::from_secs(config.timeout_secs.max(1));
+    let started = Instant::now();
+    let outcome = match tokio::time::timeout(timeout, connection.db.version_info()).await {
+        Ok(Ok(_)) => Ok(started.elapsed().as_secs_f64() * 1000.0),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("No answer within {}s", timeout.as_secs())),
+    };
+
+    let now = now_millis();
+    let reconnect = connection.update_health(|health| {
+        let previous = health.status;
+        match outcome {
+            Ok(latency_ms) => {
+                health.record_success(latency_ms, now);
+                if matches!(
+                    previous,
+                    ConnectionStatus::Degraded | ConnectionStatus::Down
+                ) {
+                    tracing::info!("Connection '{}' recovered", connection.name);
+                }
+            }
+            Err(error) => {
+                tracing::warn!(
+                    "Health check of connection '{}' failed: {}",
+                    connection.name,
+                    error
+                );
+                health.record_failure(error, now, config);
+                if health.status == ConnectionStatus::Down && previous != ConnectionStatus::Down {
+                    tracing::warn!(
+                        "Connection '{}' is down after {} failed checks",
+                        connection.name,
+                        health.consecutive_failures
+                    );
+                }
+            }
+        }
+        health.reconnect_due(now, config)
+    });
+    if !reconnect {
+        return;
+    }
+
+    match connection.db.reconnect().await {
+        Ok(db) => {
+            connection.update_health(|health| health.record_reconnect(None, now_millis()));
+            if connections.replace_database(connection, db) {
+                tracing::info!("Reconnected connection '{}'", connection.name);
+            }
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Failed to reconnect connection '{}': {}",
+                connection.name,
+                e
+            );
+            connection
+                .update_health(|health| health.record_reconnect(Some(e.to_string()), now_millis()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_and_backoff() {
+        let config = MonitorConfig {
+            interval_secs: 10,
+            reconnect_after_failures: 2,
+            max_backoff_secs: 60,
+            ..MonitorConfig::default()
+        };
+        assert_eq!(config.backoff(0), Duration::from_secs(10));
+        assert_eq!(config.backoff(2), Duration::from_secs(40));
+        assert_eq!(config.backoff(5), Duration::from_secs(60));
+
+        let mut health = ConnectionHealth::default();
+        health.record_failure("refused".to_string(), 1_000, &config);
+        assert_eq!(health.status, ConnectionStatus::Degraded);
+        assert!(!health.reconnect_due(1_000, &config));
+
+        health.record_failure("refused".to_string(), 11_000, &config);
+        assert_eq!(health.status, ConnectionStatus::Down);
+        assert!(health.reconnect_due(11_000, &config));
+
+        health.record_reconnect(Some("refused".to_string()), 11_000);
+        assert_eq!(
+            health.last_error.as_deref(),
+            Some("Reconnect failed: refused")
+        );
+        assert!(!health.reconnect_due(20_000, &config));
+        assert!(health.reconnect_due(21_000, &config));
+        health.record_reconnect(None, 21_000);
+        assert!(!health.reconnect_due(40_000, &config));
+        assert!(health.reconnect_due(41_000, &config));
+
+        health.record_success(1.5, 42_000);
+        assert_eq!(health.status, ConnectionStatus::Healthy);
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.reconnect_attempts, 0);
+        assert_eq!(health.last_error, None);
+        assert_eq!(health.latency_ms, Some(1.5));
+    }
+}
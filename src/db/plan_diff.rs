@@ -0,0 +1,286 @@
+//! Differences between two execution plans
+//!
+//! Comparing the plan of a query with the plan of its rewrite answers what
+//! the planner did differently: which operators appeared or went away, how
+//! each table is now read, and what that did to cost, time and row counts.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::db::models::{ExecutionPlan, PlanNode};
+use crate::db::settings::{setting_changes, SettingChange};
+
+/// A metric of the plan before and after a change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricChange {
+    /// Value in the first plan
+    pub before: f64,
+    /// Value in the second plan
+    pub after: f64,
+    /// Relative change in percent (negative means lower), unless `before` is zero
+    pub change_pct: Option<f64>,
+}
+
+impl MetricChange {
+    fn new(before: f64, after: f64) -> Self {
+        let change_pct = (before != 0.0).then(|| (after - before) / before * 100.0);
+        Self {
+            before,
+            after,
+            change_pct,
+        }
+    }
+}
+
+/// How often an operator appears in each plan, for operators whose count changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeTypeChange {
+    /// Plan node type
+    pub node_type: String,
+    /// Occurrences in the first plan
+    pub before: usize,
+    /// Occurrences in the second plan
+    pub after: usize,
+}
+
+/// How a relation is read in each plan, for relations whose access changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessPathChange {
+    /// Relation name
+    pub relation: String,
+    /// Node types reading the relation in the first plan; empty if it is not read
+    pub before: Vec<String>,
+    /// Node types reading the relation in the second plan; empty if it is not read
+    pub after: Vec<String>,
+}
+
+/// Structural and metric differences between two plans
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanDiff {
+    /// Estimated total cost of the root node
+    pub total_cost: MetricChange,
+    /// Execution time in milliseconds
+    pub execution_time_ms: MetricChange,
+    /// Planning time in milliseconds
+    pub planning_time_ms: MetricChange,
+    /// Rows returned by the root node
+    pub rows: MetricChange,
+    /// Number of plan nodes
+    pub node_count: MetricChange,
+    /// Operators added, removed, or used a different number of times
+    pub node_types: Vec<NodeTypeChange>,
+    /// Relations read through different operators
+    pub access_paths: Vec<AccessPathChange>,
+    /// Whether both plans have the same shape: same operators on the same relations
+    pub same_shape: bool,
+    /// Server settings that differed between the two plans, when both recorded them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub settings: Vec<SettingChange>,
+}
+
+/// Compare the plan of a query (`before`) with the plan of its rewrite (`after`)
+pub fn diff_plans(before: &ExecutionPlan, after: &ExecutionPlan) -> PlanDiff {
+    let (counts_before, counts_after) = (node_counts(&before.root), node_counts(&after.root));
+    let node_types: Vec<NodeTypeChange> = counts_before
+        .keys()
+        .chain(counts_after.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|node_type| {
+            let before = counts_before.get(node_type).copied().unwrap_or(0);
+            let after = counts_after.get(node_type).copied().unwrap_or(0);
+            (before != after).then(|| NodeTypeChange {
+                node_type: node_type.to_string(),
+                before,
+                after,
+            })
+        })
+        .collect();
+
+    let (paths_before, paths_after) = (access_paths(&before.root), access_paths(&after.root));
+    let mut access_path_changes = Vec::new();
+    for relation in paths_before
+        .keys()
+        .chain(paths_after.keys())
+        .collect::<BTreeSet<_>>()
+    {
+        let before = paths_before.get(relation).cloned().unwrap_or_default();
+        let after = paths_after.get(relation).cloned().unwrap_or_default();
+        if before != after {
+            access_path_changes.push(AccessPathChange {
+                relation: relation.clone(),
+                before,
+                after,
+            });
+        }
+    }
+
+    PlanDiff {
+        total_cost: MetricChange::new(before.root.total_cost, after.root.total_cost),
+        execution_time_ms: MetricChange::new(before.execution_time, after.execution_time),
+        planning_time_ms: MetricChange::new(before.planning_time, after.planning_time),
+        rows: MetricChange::new(
+            before.root.total_rows() as f64,
+            after.root.total_rows() as f64,
+        ),
+        node_count: MetricChange::new(
+            before.root.iter().count() as f64,
+            after.root.iter().count() as f64,
+        ),
+        same_shape: node_types.is_empty() && access_path_changes.is_empty(),
+        node_types,
+        access_paths: access_path_changes,
+        settings: setting_changes(before.settings.as_ref(), after.settings.as_ref()),
+    }
+}
+
+/// Occurrences of each node type
+fn node_counts(root: &PlanNode) -> BTreeMap<&str, usize> {
+    let mut counts = BTreeMap::new();
+    for node in root.iter() {
+        *counts.entry(node.node_type.as_str()).or_default() += 1;
+    }
+    counts
+}
+
+/// Sorted node types reading each relation
+fn access_paths(root: &PlanNode) -> BTreeMap<String, Vec<String>> {
+    let mut paths: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for node in root.iter() {
+        if let Some(relation) = &node.relation_name {
+            paths
+                .entry(relation.clone())
+                .or_default()
+                .push(node.node_type.clone());
+        }
+    }
+    for types in paths.values_mut() {
+        types.sort();
+        types.dedup();
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::settings::ServerSettings;
+
+    fn node(node_type: &str, relation: Option<&str>, cost: f64, plans: Vec<PlanNode>) -> PlanNode {
+        PlanNode {
+            node_type: node_type.to_string(),
+            relation_name: relation.map(str::to_string),
+            total_cost: cost,
+            actual_rows: 10,
+            actual_loops: 1,
+            plans,
+            ..PlanNode::default()
+        }
+    }
+
+    fn plan(root: PlanNode, execution_time: f64) -> ExecutionPlan {
+        ExecutionPlan {
+            root,
+            planning_time: 0.1,
+            execution_time,
+            settings: None,
+            jit: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_plans() {
+        let before = plan(
+            node(
+                "Hash Join",
+                None,
+                400.0,
+                vec![
+                    node("Seq Scan", Some("orders"), 300.0, vec![]),
+                    node(
+                        "Hash",
+                        None,
+                        50.0,
+                        vec![node("Seq Scan", Some("customers"), 50.0, vec![])],
+                    ),
+                ],
+            ),
+            8.0,
+        );
+        let after = plan(
+            node(
+                "Nested Loop",
+                None,
+                100.0,
+                vec![
+                    node("Seq Scan", Some("customers"), 50.0, vec![]),
+                    node("Index Scan", Some("orders"), 4.0, vec![]),
+                ],
+            ),
+            2.0,
+        );
+
+        let diff = diff_plans(&before, &after);
+        assert_eq!(diff.total_cost.change_pct, Some(-75.0));
+        assert_eq!(diff.execution_time_ms.change_pct, Some(-75.0));
+        assert_eq!(diff.rows.change_pct, Some(0.0));
+        assert!(!diff.same_shape);
+
+        let types: Vec<_> = diff
+            .node_types
+            .iter()
+            .map(|c| (c.node_type.as_str(), c.before, c.after))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("Hash", 1, 0),
+                ("Hash Join", 1, 0),
+                ("Index Scan", 0, 1),
+                ("Nested Loop", 0, 1),
+                ("Seq Scan", 2, 1),
+            ]
+        );
+        assert_eq!(
+            diff.access_paths,
+            [AccessPathChange {
+                relation: "orders".to_string(),
+                before: vec!["Seq Scan".to_string()],
+                after: vec!["Index Scan".to_string()],
+            }]
+        );
+
+        let same = diff_plans(&before, &before);
+        assert!(same.same_shape);
+        assert_eq!(same.total_cost.change_pct, Some(0.0));
+        assert!(same.settings.is_empty());
+    }
+
+    #[test]
+    fn test_diff_plans_reports_setting_changes() {
+        let with_work_mem = |value: &str| {
+            let mut plan = plan(node("Sort", None, 10.0, vec![]), 1.0);
+            plan.settings = Some(ServerSettings(BTreeMap::from([(
+                "work_mem".to_string(),
+                value.to_string(),
+            )])));
+            plan
+        };
+
+        let diff = diff_plans(&with_work_mem("4MB"), &with_work_mem("64MB"));
+        assert_eq!(
+            diff.settings,
+            [SettingChange {
+                name: "work_mem".to_string(),
+                before: Some("4MB".to_string()),
+                after: Some("64MB".to_string()),
+            }]
+        );
+
+        // Without both snapshots nothing can be said about the settings
+        let unknown = plan(node("Sort", None, 10.0, vec![]), 1.0);
+        assert!(diff_plans(&unknown, &with_work_mem("64MB"))
+            .settings
+            .is_empty());
+    }
+}
@@ -0,0 +1,266 @@
+//! Several plans of one query merged into a single tree
+//!
+//! A query that has run with three different plans is easier to understand
+//! from one tree that shows where the plans agree and where they branch off
+//! than from three trees side by side. The overlay aligns the nodes the plans
+//! have in common and records, for every node, which plans contain it and
+//! what it cost and returned in each.
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::{ExecutionPlan, PlanNode};
+use crate::snapshot;
+
+/// A node of one plan, as placed in the overlay
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeInstance {
+    /// Index of the plan in the overlay's input
+    pub plan: usize,
+    /// Estimated total cost
+    pub total_cost: f64,
+    /// Estimated rows per loop
+    pub plan_rows: Option<u64>,
+    /// Actual rows across all loops
+    pub actual_rows: u64,
+    /// Actual time across all loops, in milliseconds
+    pub actual_time_ms: f64,
+}
+
+/// A node of the overlay, standing for matching nodes of one or more plans
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OverlayNode {
+    /// Plan node type
+    pub node_type: String,
+    /// Relation read by the node
+    pub relation: Option<String>,
+    /// Index used by the node
+    pub index: Option<String>,
+    /// The node in each plan that contains it, in plan order
+    pub instances: Vec<NodeInstance>,
+    /// Whether every plan contains the node
+    pub shared: bool,
+    /// Child nodes
+    pub children: Vec<OverlayNode>,
+}
+
+impl OverlayNode {
+    fn new(node: &PlanNode) -> Self {
+        Self {
+            node_type: node.node_type.clone(),
+            relation: node.relation_name.clone(),
+            index: node.extra_str("Index Name").map(str::to_string),
+            instances: Vec::new(),
+            shared: false,
+            children: Vec::new(),
+        }
+    }
+
+    fn matches(&self, node: &PlanNode) -> bool {
+        self.node_type == node.node_type
+            && self.relation == node.relation_name
+            && self.index.as_deref() == node.extra_str("Index Name")
+    }
+
+    /// Indexes of the plans that contain the node
+    pub fn plans(&self) -> impl Iterator<Item = usize> + '_ {
+        self.instances.iter().map(|i| i.plan)
+    }
+
+    fn contains(&self, plan: usize) -> bool {
+        self.plans().any(|p| p == plan)
+    }
+
+    fn add(&mut self, node: &PlanNode, plan: usize) {
+        self.instances.push(NodeInstance {
+            plan,
+            total_cost: node.total_cost,
+            plan_rows: node.extra_u64("Plan Rows"),
+            actual_rows: node.total_rows(),
+            actual_time_ms: node.total_time(),
+        });
+        merge_into(&mut self.children, &node.plans, plan);
+    }
+
+    fn mark_shared(&mut self, plan_count: usize) {
+        self.shared = self.instances.len() == plan_count;
+        for child in &mut self.children {
+            child.mark_shared(plan_count);
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &OverlayNode> + '_> {
+        Box::new(std::iter::once(self).chain(self.children.iter().flat_map(|c| c.iter())))
+    }
+}
+
+/// Plans merged into one tree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanOverlay {
+    /// Number of plans merged
+    pub plan_count: usize,
+    /// Root nodes; more than one when the plans differ at the top
+    pub roots: Vec<OverlayNode>,
+    /// Nodes every plan contains
+    pub shared_nodes: usize,
+    /// Nodes only some of the plans contain, where the plans diverge
+    pub divergent_nodes: usize,
+}
+
+/// Merge `plans` into one tree
+///
+/// Children are aligned in order: a node joins the first node of the overlay
+/// after its previously aligned sibling with the same type, relation and
+/// index that does not hold a node of its plan yet, so repeated operators
+/// such as two scans of one table stay apart. Nodes that match nothing are
+/// the points where the plans diverge and become branches of their own.
+pub fn overlay_plans(plans: &[&ExecutionPlan]) -> PlanOverlay {
+    let mut roots = Vec::new();
+    for (i, plan) in plans.iter().enumerate() {
+        merge_into(&mut roots, std::slice::from_ref(&plan.root), i);
+    }
+    for root in &mut roots {
+        root.mark_shared(plans.len());
+    }
+
+    let (shared_nodes, divergent_nodes) =
+        roots
+            .iter()
+            .flat_map(|root| root.iter())
+            .fold((0, 0), |(shared, divergent), node| {
+                if node.shared {
+                    (shared + 1, divergent)
+                } else {
+                    (shared, divergent + 1)
+                }
+            });
+    PlanOverlay {
+        plan_count: plans.len(),
+        roots,
+        shared_nodes,
+        divergent_nodes,
+    }
+}
+
+fn merge_into(overlay: &mut Vec<OverlayNode>, nodes: &[PlanNode], plan: usize) {
+    let free = |o: &OverlayNode, node: &PlanNode| o.matches(node) && !o.contains(plan);
+    let mut cursor = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        let position = match overlay[cursor..].iter().position(|o| free(o, node)) {
+            Some(offset) => cursor + offset,
+            None => {
+                // Place a node of this plan alone before the next node a later
+                // sibling joins, so siblings keep their order
+                let position = overlay[cursor..]
+                    .iter()
+                    .position(|o| nodes[i + 1..].iter().any(|n| free(o, n)))
+                    .map_or(overlay.len(), |offset| cursor + offset);
+                overlay.insert(position, OverlayNode::new(node));
+                position
+            }
+        };
+        overlay[position].add(node, plan);
+        cursor = position + 1;
+    }
+}
+
+/// Plans grouped by shape, in order of first appearance
+///
+/// Each group lists the indexes of its plans in input order, so with plans
+/// ordered newest first the first member is the most recent plan of that shape.
+pub fn group_by_shape(plans: &[ExecutionPlan]) -> Vec<Vec<usize>> {
+    let mut shapes: Vec<(snapshot::PlanShape, Vec<usize>)> = Vec::new();
+    for (i, plan) in plans.iter().enumerate() {
+        let shape = snapshot::normalize(plan);
+        match shapes.iter_mut().find(|(s, _)| *s == shape) {
+            Some((_, members)) => members.push(i),
+            None => shapes.push((shape, vec![i])),
+        }
+    }
+    shapes.into_iter().map(|(_, members)| members).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(node_type: &str, relation: Option<&str>, plans: Vec<PlanNode>) -> PlanNode {
+        PlanNode {
+            node_type: node_type.to_string(),
+            relation_name: relation.map(str::to_string),
+            total_cost: 10.0,
+            plans,
+            ..PlanNode::default()
+        }
+    }
+
+    fn plan(root: PlanNode) -> ExecutionPlan {
+        ExecutionPlan {
+            root,
+            planning_time: 0.1,
+            execution_time: 1.0,
+            settings: None,
+            jit: None,
+        }
+    }
+
+    fn hash_join(outer: PlanNode) -> ExecutionPlan {
+        plan(node(
+            "Hash Join",
+            None,
+            vec![
+                outer,
+                node(
+                    "Hash",
+                    None,
+                    vec![node("Seq Scan", Some("customers"), vec![])],
+                ),
+            ],
+        ))
+    }
+
+    #[test]
+    fn test_overlay_plans() {
+        let a = hash_join(node("Seq Scan", Some("orders"), vec![]));
+        let b = hash_join(node("Bitmap Heap Scan", Some("orders"), vec![]));
+        let c = plan(node(
+            "Nested Loop",
+            None,
+            vec![
+                node("Seq Scan", Some("customers"), vec![]),
+                node("Index Scan", Some("orders"), vec![]),
+            ],
+        ));
+
+        let overlay = overlay_plans(&[&a, &b, &c]);
+        assert_eq!(overlay.plan_count, 3);
+        assert_eq!(overlay.roots.len(), 2);
+
+        let join = &overlay.roots[0];
+        assert_eq!(join.node_type, "Hash Join");
+        assert_eq!(join.plans().collect::<Vec<_>>(), [0, 1]);
+        assert!(!join.shared);
+        let children: Vec<&str> = join.children.iter().map(|c| c.node_type.as_str()).collect();
+        assert_eq!(children, ["Seq Scan", "Bitmap Heap Scan", "Hash"]);
+        assert_eq!(join.children[2].children[0].instances.len(), 2);
+
+        assert_eq!(overlay.roots[1].node_type, "Nested Loop");
+        assert_eq!(overlay.shared_nodes, 0);
+        assert_eq!(overlay.divergent_nodes, 8);
+
+        let same = overlay_plans(&[&a, &a]);
+        assert_eq!(same.roots.len(), 1);
+        assert_eq!(same.divergent_nodes, 0);
+        assert_eq!(same.shared_nodes, 4);
+    }
+
+    #[test]
+    fn test_group_by_shape() {
+        let seq = hash_join(node("Seq Scan", Some("orders"), vec![]));
+        let bitmap = hash_join(node("Bitmap Heap Scan", Some("orders"), vec![]));
+        let mut slower = seq.clone();
+        slower.execution_time = 50.0;
+
+        let groups = group_by_shape(&[seq, bitmap, slower]);
+        assert_eq!(groups, [vec![0, 2], vec![1]]);
+    }
+}
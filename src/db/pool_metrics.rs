@@ -0,0 +1,168 @@
+//! Connection pool statistics
+//!
+//! Every analysis borrows a connection from the pool of the database it
+//! runs on. When the pool is too small for the load, requests queue for a
+//! connection before the database sees them, and slow analyses look like a
+//! slow database. Pool metrics show how full each pool is and how long
+//! explains, prepared statement analyses and sessions waited for their
+//! connection, so operators can tell the two apart.
+
+use serde::{Deserialize, Serialize};
+use sqlx::pool::PoolConnection;
+use sqlx::{Pool, Postgres};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::db::engines::{DatabaseEngine, DatabaseEngineImpl, EngineType};
+use crate::db::error::DbError;
+use crate::db::Database;
+
+/// Counts of connection acquisitions from one pool
+#[derive(Debug, Default)]
+pub(crate) struct AcquireStats {
+    acquired: AtomicU64,
+    timeouts: AtomicU64,
+    wait_micros: AtomicU64,
+    max_wait_micros: AtomicU64,
+}
+
+impl AcquireStats {
+    /// Record an acquisition that waited `wait`, and whether it timed out
+    fn record(&self, wait: Duration, timed_out: bool) {
+        let micros = u64::try_from(wait.as_micros()).unwrap_or(u64::MAX);
+        if timed_out {
+            self.timeouts.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.acquired.fetch_add(1, Ordering::Relaxed);
+            self.wait_micros.fetch_add(micros, Ordering::Relaxed);
+        }
+        self.max_wait_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+}
+
+/// State of the connection pool of a database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolMetrics {
+    /// Database engine
+    pub engine_type: EngineType,
+    /// Open connections, idle or in use
+    pub size: u32,
+    /// Open connections not in use
+    pub idle: usize,
+    /// Connections in use
+    pub in_use: u32,
+    /// Most connections the pool opens
+    pub max_connections: u32,
+    /// Time an acquisition waits for a connection before timing out, in milliseconds
+    pub acquire_timeout_ms: u64,
+    /// Connections acquired by explains, prepared statement analyses and sessions
+    pub acquired: u64,
+    /// Acquisitions that gave up because no connection became free in time
+    pub acquire_timeouts: u64,
+    /// Mean wait of successful acquisitions in milliseconds, if there were any
+    pub mean_acquire_wait_ms: Option<f64>,
+    /// Longest wait of an acquisition in milliseconds, including ones that timed out
+    pub max_acquire_wait_ms: f64,
+}
+
+impl PoolMetrics {
+    fn new<DB: sqlx::Database>(
+        engine_type: EngineType,
+        pool: &Pool<DB>,
+        stats: &AcquireStats,
+    ) -> Self {
+        let size = pool.size();
+        let idle = pool.num_idle();
+        let acquired = stats.acquired.load(Ordering::Relaxed);
+        let wait_micros = stats.wait_micros.load(Ordering::Relaxed);
+        Self {
+            engine_type,
+            size,
+            idle,
+            in_use: size.saturating_sub(idle as u32),
+            max_connections: pool.options().get_max_connections(),
+            acquire_timeout_ms: pool.options().get_acquire_timeout().as_millis() as u64,
+            acquired,
+            acquire_timeouts: stats.timeouts.load(Ordering::Relaxed),
+            mean_acquire_wait_ms: (acquired > 0)
+                .then(|| wait_micros as f64 / acquired as f64 / 1000.0),
+            max_acquire_wait_ms: stats.max_wait_micros.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+impl Database {
+    /// Statistics of the connection pool
+    ///
+    /// DuckDB runs its shell per query and has no pool, so it returns `None`.
+    pub fn pool_metrics(&self) -> Option<PoolMetrics> {
+        let engine_type = self.engine.engine_type();
+        let stats = &self.acquire_stats;
+        Some(match self.engine.as_ref() {
+            DatabaseEngineImpl::PostgreSQL(engine) => {
+                PoolMetrics::new(engine_type, engine.pool(), stats)
+            }
+            DatabaseEngineImpl::Redshift(engine) => {
+                PoolMetrics::new(engine_type, engine.pool(), stats)
+            }
+            DatabaseEngineImpl::MySQL(engine) => {
+                PoolMetrics::new(engine_type, engine.pool(), stats)
+            }
+            DatabaseEngineImpl::MariaDB(engine) => {
+                PoolMetrics::new(engine_type, engine.pool(), stats)
+            }
+            DatabaseEngineImpl::TiDB(engine) => PoolMetrics::new(engine_type, engine.pool(), stats),
+            DatabaseEngineImpl::SQLite(engine) => {
+                PoolMetrics::new(engine_type, engine.pool(), stats)
+            }
+            DatabaseEngineImpl::DuckDB(_) => return None,
+        })
+    }
+
+    /// Acquire a connection from the PostgreSQL pool, recording how long it took
+    pub(crate) async fn acquire_pg(&self) -> Result<PoolConnection<Postgres>, DbError> {
+        let pool = self.pg_pool()?;
+        let started = Instant::now();
+        let conn = pool.acquire().await;
+        self.acquire_stats.record(
+            started.elapsed(),
+            matches!(conn, Err(sqlx::Error::PoolTimedOut)),
+        );
+        conn.map_err(|e| DbError::Connection(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acquire_stats() {
+        let stats = AcquireStats::default();
+        stats.record(Duration::from_millis(2), false);
+        stats.record(Duration::from_millis(6), false);
+        stats.record(Duration::from_millis(3000), true);
+
+        assert_eq!(stats.acquired.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.timeouts.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.wait_micros.load(Ordering::Relaxed), 8000);
+        assert_eq!(stats.max_wait_micros.load(Ordering::Relaxed), 3_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_pool_metrics() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        db.version_info().await.unwrap();
+
+        let metrics = db.pool_metrics().unwrap();
+        assert_eq!(metrics.engine_type, EngineType::SQLite);
+        assert!(metrics.size >= 1);
+        assert!(metrics.size <= metrics.max_connections);
+        assert_eq!(
+            metrics.in_use as usize + metrics.idle,
+            metrics.size as usize
+        );
+        assert_eq!(metrics.acquired, 0);
+        assert_eq!(metrics.mean_acquire_wait_ms, None);
+    }
+}
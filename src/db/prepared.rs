@@ -0,0 +1,574 @@
+//! EXPLAIN of prepared statements and plan-cache behavior
+//!
+//! Applications usually run parameterized statements through the extended
+//! protocol, where PostgreSQL may switch from per-execution "custom" plans to
+//! a cached "generic" plan after five executions. When the generic plan is
+//! much worse, a query is fast when typed into psql with literal values and
+//! slow from the application. This module prepares the statement, explains
+//! both plan kinds, and replays executions under the default plan cache mode
+//! to show whether and when PostgreSQL would switch.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::pool::PoolConnection;
+use sqlx::{Postgres, Row};
+
+use crate::db::cancel::RunningQuery;
+use crate::db::error::DbError;
+use crate::db::estimate::PlanEstimate;
+use crate::db::explain_options::ExplainOptions;
+use crate::db::models::plan::PlanNode;
+use crate::db::models::ExecutionPlan;
+use crate::db::settings::plan_settings;
+use crate::db::{parse_execution_plan, Database};
+use crate::policy::QueryPolicy;
+use crate::SqlTraceError;
+
+/// Executions PostgreSQL uses custom plans for before it considers a generic plan
+pub const CUSTOM_PLAN_EXECUTIONS: usize = 5;
+
+/// Options for explaining a prepared statement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreparedExplainOptions {
+    /// Values for `$1`, `$2`, ...; `null` is passed as SQL NULL
+    pub params: Vec<Value>,
+    /// Executions to replay under the default plan cache mode
+    pub executions: usize,
+    /// Execute the statement (EXPLAIN ANALYZE) instead of only planning it
+    pub analyze: bool,
+    /// Generic-to-custom ratio above which the generic plan counts as significantly worse
+    pub threshold: f64,
+}
+
+impl Default for PreparedExplainOptions {
+    fn default() -> Self {
+        Self {
+            params: Vec::new(),
+            executions: CUSTOM_PLAN_EXECUTIONS + 2,
+            analyze: false,
+            threshold: 1.5,
+        }
+    }
+}
+
+/// A value bound to a `$n` placeholder of a parameterized query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryParameter {
+    /// The value; `null` is passed as SQL NULL
+    pub value: Value,
+    /// PostgreSQL type of the parameter, e.g. `int8` or `timestamptz`;
+    /// inferred from the query when not given
+    #[serde(default, rename = "type")]
+    pub type_hint: Option<String>,
+}
+
+/// A parameter as it was bound when explaining a query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoundParameter {
+    /// Placeholder the value was bound to, e.g. `$1`
+    pub placeholder: String,
+    /// The value
+    pub value: Value,
+    /// Type PostgreSQL used for the parameter
+    pub data_type: String,
+}
+
+/// Check that a type hint is a type name and nothing else
+///
+/// Hints are spliced into `PREPARE`, so only characters that appear in type
+/// names are accepted, e.g. `numeric(10,2)`, `text[]` or `timestamp with time zone`,
+/// and parentheses must balance so a hint cannot close the type list.
+fn check_type_hint(hint: &str) -> Result<(), String> {
+    let mut depth = 0i32;
+    let valid = !hint.trim().is_empty()
+        && hint.chars().all(|c| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            depth >= 0 && (c.is_ascii_alphanumeric() || " _.,()[]".contains(c))
+        })
+        && depth == 0;
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid parameter type '{}'", hint))
+    }
+}
+
+/// Type list for `PREPARE`; parameters without a hint are left for PostgreSQL to infer
+fn type_list(params: &[QueryParameter]) -> String {
+    if params.iter().all(|p| p.type_hint.is_none()) {
+        return String::new();
+    }
+    let types: Vec<&str> = params
+        .iter()
+        .map(|p| p.type_hint.as_deref().unwrap_or("unknown"))
+        .collect();
+    format!(" ({})", types.join(", "))
+}
+
+/// Summary of one plan of a prepared statement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreparedPlan {
+    /// Node type of the plan root
+    pub root_node_type: String,
+    /// Estimated total cost of the plan root
+    pub total_cost: f64,
+    /// Execution time in milliseconds, when the plan was analyzed
+    pub execution_time_ms: Option<f64>,
+    /// Whether the plan uses parameter placeholders, i.e. is a generic plan
+    pub is_generic: bool,
+    /// Raw EXPLAIN output
+    pub plan: Value,
+}
+
+impl PreparedPlan {
+    fn from_explain_json(explain_json: Value) -> Result<Self, SqlTraceError> {
+        let output = explain_json
+            .get(0)
+            .ok_or_else(|| DbError::PlanParsing("Empty EXPLAIN output".to_string()))?;
+        let root = output
+            .get("Plan")
+            .ok_or_else(|| DbError::PlanParsing("Missing plan in EXPLAIN output".to_string()))?;
+
+        Ok(Self {
+            root_node_type: root
+                .get("Node Type")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            total_cost: root
+                .get("Total Cost")
+                .and_then(Value::as_f64)
+                .unwrap_or_default(),
+            execution_time_ms: output.get("Execution Time").and_then(Value::as_f64),
+            is_generic: uses_placeholders(root),
+            plan: explain_json,
+        })
+    }
+
+    /// Rows produced by all nodes of the plan, across loops; zero if it was not analyzed
+    pub fn rows_processed(&self) -> u64 {
+        self.plan
+            .get(0)
+            .and_then(|output| output.get("Plan"))
+            .and_then(|root| serde_json::from_value::<PlanNode>(root.clone()).ok())
+            .map(|root| {
+                root.iter()
+                    .fold(0u64, |rows, node| rows.saturating_add(node.total_rows()))
+            })
+            .unwrap_or_default()
+    }
+
+    /// The figure plans are compared by: execution time if analyzed, otherwise cost
+    fn weight(&self) -> f64 {
+        self.execution_time_ms.unwrap_or(self.total_cost)
+    }
+}
+
+/// How a prepared statement is planned over repeated executions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanCacheReport {
+    /// Plan chosen with the parameter values known (`force_custom_plan`)
+    pub custom_plan: PreparedPlan,
+    /// Plan chosen without looking at parameter values (`force_generic_plan`)
+    pub generic_plan: PreparedPlan,
+    /// Generic plan cost (or time, when analyzed) divided by the custom plan's
+    pub generic_to_custom_ratio: f64,
+    /// Whether the generic plan exceeds the configured threshold
+    pub generic_is_worse: bool,
+    /// First execution (1-based) at which the default mode used the generic plan
+    pub switched_to_generic_at: Option<usize>,
+    /// Executions replayed under the default mode
+    pub executions: usize,
+    /// Human-readable conclusion
+    pub summary: String,
+}
+
+impl PlanCacheReport {
+    fn new(
+        custom_plan: PreparedPlan,
+        generic_plan: PreparedPlan,
+        switched_to_generic_at: Option<usize>,
+        options: &PreparedExplainOptions,
+    ) -> Self {
+        let ratio = if custom_plan.weight() > 0.0 {
+            generic_plan.weight() / custom_plan.weight()
+        } else {
+            1.0
+        };
+        let generic_is_worse = ratio > options.threshold;
+
+        let summary = match (generic_is_worse, switched_to_generic_at) {
+            _ if options.params.is_empty() => {
+                "The statement has no parameters, so its custom and generic plans are the same"
+                    .to_string()
+            }
+            (true, Some(execution)) => format!(
+                "The generic plan is {:.1}x worse than the custom plan and PostgreSQL switched to it at execution {}; \
+                 consider plan_cache_mode = force_custom_plan for this statement",
+                ratio, execution
+            ),
+            (true, None) => format!(
+                "The generic plan is {:.1}x worse than the custom plan, but PostgreSQL kept using custom plans for {} executions",
+                ratio, options.executions
+            ),
+            (false, Some(execution)) => format!(
+                "PostgreSQL switched to the generic plan at execution {}; it performs comparably to the custom plan",
+                execution
+            ),
+            (false, None) => "PostgreSQL kept using custom plans; plan caching does not affect this statement"
+                .to_string(),
+        };
+
+        Self {
+            custom_plan,
+            generic_plan,
+            generic_to_custom_ratio: ratio,
+            generic_is_worse,
+            switched_to_generic_at,
+            executions: options.executions,
+            summary,
+        }
+    }
+}
+
+impl Database {
+    /// Prepare `query` and compare its custom and generic plans
+    ///
+    /// `policy` is checked against the custom plan before anything is executed.
+    pub async fn explain_prepared(
+        &self,
+        query: &str,
+        options: &PreparedExplainOptions,
+        policy: Option<&QueryPolicy>,
+    ) -> Result<PlanCacheReport, SqlTraceError> {
+        let mut conn = self.acquire_pg().await?;
+
+        let name = format!("sqltrace_{}", uuid::Uuid::new_v4().simple());
+        let prepare = format!("PREPARE {} AS {}", name, query);
+        run(&mut conn, &prepare)
+            .await
+            .map_err(|e| DbError::from_statement(e, query, &format!("PREPARE {} AS ", name)))?;
+
+        // With ANALYZE the statement is executed, so nothing it does may persist
+        let result = match run(&mut conn, "BEGIN READ ONLY").await {
+            Ok(()) => compare_plans(&mut conn, &name, &prepare, options, policy).await,
+            Err(e) => Err(DbError::Query(e.to_string()).into()),
+        };
+
+        // The connection goes back to the pool, so always leave it as we found it
+        let _ = run(&mut conn, "ROLLBACK").await;
+        let _ = run(&mut conn, &format!("DEALLOCATE {}", name)).await;
+        let _ = run(&mut conn, "RESET plan_cache_mode").await;
+
+        result
+    }
+}
+
+impl Database {
+    /// Explain a query with `$n` placeholders, executing it with `params` bound
+    ///
+    /// The query is prepared and run through `EXPLAIN EXECUTE` with `options`
+    /// in a read-only transaction that is rolled back. `policy` is checked
+    /// against the plan for the bound values before anything is executed.
+    /// Returns the plan, the parameters as bound and the raw EXPLAIN JSON.
+    pub async fn explain_with_params(
+        &self,
+        query: &str,
+        params: &[QueryParameter],
+        options: &ExplainOptions,
+        policy: Option<&QueryPolicy>,
+        running: Option<&RunningQuery>,
+    ) -> Result<(ExecutionPlan, Vec<BoundParameter>, Value), SqlTraceError> {
+        self.validate_query(query)?;
+        options.validate().map_err(SqlTraceError::InvalidQuery)?;
+        for hint in params.iter().filter_map(|p| p.type_hint.as_deref()) {
+            check_type_hint(hint).map_err(SqlTraceError::InvalidQuery)?;
+        }
+
+        let mut conn = self.acquire_pg().await?;
+        let settings = plan_settings(&mut conn).await;
+        if let Some(running) = running {
+            running.attach(&mut conn).await?;
+        }
+
+        let name = format!("sqltrace_{}", uuid::Uuid::new_v4().simple());
+        let prepare = format!("PREPARE {}{} AS ", name, type_list(params));
+        let result = match run(&mut conn, &format!("{}{}", prepare, query)).await {
+            Ok(()) => explain_bound(&mut conn, &name, params, options, policy).await,
+            Err(e) => Err(DbError::from_statement(e, query, &prepare).into()),
+        };
+        let result = match running {
+            Some(running) => {
+                running.detach().await;
+                result.map_err(|e| {
+                    if running.is_cancelled() {
+                        SqlTraceError::Cancelled
+                    } else {
+                        e
+                    }
+                })
+            }
+            None => result,
+        };
+
+        // The connection goes back to the pool, so always leave it as we found it
+        let _ = run(&mut conn, "ROLLBACK").await;
+        let _ = run(&mut conn, &format!("DEALLOCATE {}", name)).await;
+
+        result.map(|(plan, parameters, raw)| (ExecutionPlan { settings, ..plan }, parameters, raw))
+    }
+}
+
+async fn explain_bound(
+    conn: &mut PoolConnection<Postgres>,
+    name: &str,
+    params: &[QueryParameter],
+    options: &ExplainOptions,
+    policy: Option<&QueryPolicy>,
+) -> Result<(ExecutionPlan, Vec<BoundParameter>, Value), SqlTraceError> {
+    let types: Vec<String> = sqlx::query(
+        "SELECT parameter_types::text[] AS types FROM pg_prepared_statements WHERE name = $1",
+    )
+    .bind(name)
+    .fetch_one(&mut **conn)
+    .await
+    .and_then(|row| row.try_get("types"))
+    .map_err(|e| DbError::Query(e.to_string()))?;
+    if types.len() != params.len() {
+        return Err(SqlTraceError::InvalidQuery(format!(
+            "The query has {} parameters, but {} values were given",
+            types.len(),
+            params.len()
+        )));
+    }
+
+    let values: Vec<Value> = params.iter().map(|p| p.value.clone()).collect();
+    let execute = format!("EXECUTE {}{}", name, parameter_list(&values));
+    if let Some(policy) = policy.filter(|p| p.needs_estimate()) {
+        let estimate = explain_json(conn, &format!("EXPLAIN (VERBOSE, FORMAT JSON) {}", execute))
+            .await
+            .and_then(|plan| PlanEstimate::from_explain_json(&plan))?;
+        policy
+            .check_estimate(&estimate)
+            .map_err(|e| SqlTraceError::InvalidQuery(e.to_string()))?;
+    }
+
+    // ANALYZE executes the statement, so nothing it does may persist
+    run(conn, "BEGIN READ ONLY")
+        .await
+        .map_err(|e| DbError::Query(e.to_string()))?;
+    let plan_json = explain_json(conn, &format!("{}{}", options.prefix(), execute)).await?;
+    let plan = parse_execution_plan(&plan_json)?;
+
+    let bound = values
+        .into_iter()
+        .zip(types)
+        .enumerate()
+        .map(|(i, (value, data_type))| BoundParameter {
+            placeholder: format!("${}", i + 1),
+            value,
+            data_type,
+        })
+        .collect();
+    Ok((plan, bound, plan_json))
+}
+
+/// Plan the statement `name` with forced custom and generic plans, then replay it
+///
+/// `prepare` is the `PREPARE` statement that created `name`.
+async fn compare_plans(
+    conn: &mut PoolConnection<Postgres>,
+    name: &str,
+    prepare: &str,
+    options: &PreparedExplainOptions,
+    policy: Option<&QueryPolicy>,
+) -> Result<PlanCacheReport, SqlTraceError> {
+    let execute = format!("EXECUTE {}{}", name, parameter_list(&options.params));
+    let explain_options = if options.analyze {
+        "ANALYZE, BUFFERS, FORMAT JSON"
+    } else {
+        "FORMAT JSON"
+    };
+    let explain = format!("EXPLAIN ({}) {}", explain_options, execute);
+
+    run(conn, "SET plan_cache_mode = force_custom_plan").await?;
+    if let Some(policy) = policy.filter(|p| p.needs_estimate()) {
+        let estimate = explain_json(conn, &format!("EXPLAIN (VERBOSE, FORMAT JSON) {}", execute))
+            .await
+            .and_then(|plan| PlanEstimate::from_explain_json(&plan))?;
+        policy
+            .check_estimate(&estimate)
+            .map_err(|e| SqlTraceError::InvalidQuery(e.to_string()))?;
+    }
+    let custom_plan = PreparedPlan::from_explain_json(explain_json(conn, &explain).await?)?;
+
+    run(conn, "SET plan_cache_mode = force_generic_plan").await?;
+    let generic_plan = PreparedPlan::from_explain_json(explain_json(conn, &explain).await?)?;
+
+    // Replay executions under the default mode, which only considers the
+    // generic plan once custom plans have been used a few times. The forced
+    // plans above count toward that, so the replay starts from a statement
+    // prepared afresh.
+    run(conn, &format!("DEALLOCATE {}", name)).await?;
+    run(conn, prepare).await?;
+    run(conn, "SET plan_cache_mode = auto").await?;
+    let mut switched_to_generic_at = None;
+    for execution in 1..=options.executions {
+        let plan = PreparedPlan::from_explain_json(explain_json(conn, &explain).await?)?;
+        if plan.is_generic && switched_to_generic_at.is_none() {
+            switched_to_generic_at = Some(execution);
+        }
+    }
+
+    Ok(PlanCacheReport::new(
+        custom_plan,
+        generic_plan,
+        switched_to_generic_at,
+        options,
+    ))
+}
+
+async fn run(conn: &mut PoolConnection<Postgres>, statement: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(statement)
+        .execute(&mut **conn)
+        .await
+        .map(|_| ())
+}
+
+async fn explain_json(
+    conn: &mut PoolConnection<Postgres>,
+    statement: &str,
+) -> Result<Value, SqlTraceError> {
+    let row = sqlx::query(statement)
+        .fetch_one(&mut **conn)
+        .await
+        .map_err(|e| DbError::Query(e.to_string()))?;
+    Ok(row
+        .try_get("QUERY PLAN")
+        .map_err(|e| DbError::Query(e.to_string()))?)
+}
+
+/// Render parameter values as an `EXECUTE` argument list
+///
+/// Values are passed as untyped literals so PostgreSQL coerces them to the
+/// parameter types it inferred when preparing the statement.
+fn parameter_list(params: &[Value]) -> String {
+    if params.is_empty() {
+        return String::new();
+    }
+    let values: Vec<String> = params
+        .iter()
+        .map(|value| match value {
+            Value::Null => "NULL".to_string(),
+            Value::String(s) => quote_literal(s),
+            other => quote_literal(&other.to_string()),
+        })
+        .collect();
+    format!("({})", values.join(", "))
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Whether any expression in the plan refers to a `$n` parameter placeholder
+fn uses_placeholders(node: &Value) -> bool {
+    match node {
+        Value::String(s) => {
+            let mut in_literal = false;
+            let mut chars = s.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\'' => in_literal = !in_literal,
+                    '$' if !in_literal && chars.peek().is_some_and(char::is_ascii_digit) => {
+                        return true
+                    }
+                    _ => {}
+                }
+            }
+            false
+        }
+        Value::Array(items) => items.iter().any(uses_placeholders),
+        Value::Object(fields) => fields.values().any(uses_placeholders),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn plan(cost: f64, filter: &str) -> PreparedPlan {
+        PreparedPlan::from_explain_json(json!([{
+            "Plan": {"Node Type": "Seq Scan", "Total Cost": cost, "Filter": filter}
+        }]))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parameter_list() {
+        assert_eq!(parameter_list(&[]), "");
+        assert_eq!(
+            parameter_list(&[json!("O'Brien"), json!(42), Value::Null]),
+            "('O''Brien', '42', NULL)"
+        );
+    }
+
+    #[test]
+    fn test_parameter_types() {
+        let param = |hint: Option<&str>| QueryParameter {
+            value: json!(1),
+            type_hint: hint.map(str::to_string),
+        };
+        assert_eq!(type_list(&[param(None), param(None)]), "");
+        assert_eq!(
+            type_list(&[param(None), param(Some("numeric(10,2)"))]),
+            " (unknown, numeric(10,2))"
+        );
+
+        assert!(check_type_hint("timestamp with time zone").is_ok());
+        assert!(check_type_hint("text[]").is_ok());
+        assert!(check_type_hint("int4) AS SELECT 1; --").is_err());
+        assert!(check_type_hint("int4) AS SELECT (1").is_err());
+        assert!(check_type_hint(" ").is_err());
+    }
+
+    #[test]
+    fn test_generic_plan_detection() {
+        assert!(plan(10.0, "(status = $1)").is_generic);
+        assert!(!plan(10.0, "(price = '$5'::text)").is_generic);
+        assert!(!plan(10.0, "(status = 'active'::text)").is_generic);
+    }
+
+    #[test]
+    fn test_report_flags_worse_generic_plan() {
+        let options = PreparedExplainOptions {
+            params: vec![json!("rare")],
+            ..PreparedExplainOptions::default()
+        };
+        let report = PlanCacheReport::new(
+            plan(100.0, "(status = 'rare'::text)"),
+            plan(5000.0, "(status = $1)"),
+            Some(6),
+            &options,
+        );
+
+        assert!(report.generic_is_worse);
+        assert_eq!(report.generic_to_custom_ratio, 50.0);
+        assert!(report.summary.contains("execution 6"));
+
+        let comparable = PlanCacheReport::new(
+            plan(100.0, "(id = 1)"),
+            plan(110.0, "(id = $1)"),
+            None,
+            &options,
+        );
+        assert!(!comparable.generic_is_worse);
+    }
+}
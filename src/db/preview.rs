@@ -0,0 +1,185 @@
+//! Bounded preview of query results
+//!
+//! After tuning a query it is worth checking that it still returns the right
+//! data. The preview runs the query in a read-only transaction with a
+//! statement timeout and returns at most a configured number of rows, so an
+//! accidental cross join cannot flood the server or the browser.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlparser::ast::Statement;
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::parser::Parser;
+use sqlx::{Column, Executor, Row, TypeInfo};
+
+use crate::db::error::DbError;
+use crate::db::models::ExecutionPlan;
+use crate::db::{parse_execution_plan, Database};
+use crate::SqlTraceError;
+
+/// Wraps the previewed query so each row comes back as a JSON array in column order
+const PREVIEW_PREFIX: &str = "SELECT (SELECT json_agg(e.value ORDER BY e.n) \
+     FROM json_each(to_json(sqltrace_preview)) WITH ORDINALITY AS e(key, value, n)) AS row_values \
+     FROM (";
+
+/// Result preview configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreviewConfig {
+    /// Allow queries to be executed for a result preview
+    pub enabled: bool,
+    /// Most rows returned by one preview
+    pub max_rows: usize,
+    /// Statement timeout for the previewed query, in milliseconds
+    pub timeout_ms: u64,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_rows: 100,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+/// The first rows returned by a query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultPreview {
+    /// Column names, in output order
+    pub columns: Vec<String>,
+    /// Database type of each column, such as `INT4` or `TEXT`, in the order of `columns`
+    #[serde(default)]
+    pub column_types: Vec<String>,
+    /// Row values, one array per row in column order
+    pub rows: Vec<Vec<Value>>,
+    /// Whether the query returned more rows than were included
+    pub truncated: bool,
+}
+
+/// Check that `sql` is a single query that can be previewed
+pub fn check_previewable(sql: &str) -> Result<(), String> {
+    let statements = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+        .map_err(|e| format!("Query could not be parsed: {}", e))?;
+    match statements.as_slice() {
+        [Statement::Query(_)] => Ok(()),
+        [_] => Err("Only SELECT queries can be previewed".to_string()),
+        _ => Err("Only a single statement can be previewed".to_string()),
+    }
+}
+
+/// The statement that fetches up to `limit + 1` rows of `query`
+///
+/// The extra row tells whether the preview was truncated.
+fn preview_statement(query: &str, limit: usize) -> String {
+    format!(
+        "{}{}) AS sqltrace_preview LIMIT {}",
+        PREVIEW_PREFIX,
+        query.trim_end().trim_end_matches(';'),
+        limit + 1
+    )
+}
+
+impl Database {
+    /// Explain `query` and return at most `limit` of its rows
+    ///
+    /// Both run in one read-only transaction under `timeout`, so neither the
+    /// plan nor the preview can modify data or run unbounded.
+    pub async fn preview(
+        &self,
+        query: &str,
+        limit: usize,
+        timeout: Duration,
+    ) -> Result<(ExecutionPlan, ResultPreview), SqlTraceError> {
+        let mut tx = self
+            .pg_pool()?
+            .begin()
+            .await
+            .map_err(|e| DbError::Connection(e.to_string()))?;
+
+        for statement in [
+            "SET TRANSACTION READ ONLY".to_string(),
+            format!("SET LOCAL statement_timeout = {}", timeout.as_millis()),
+        ] {
+            sqlx::query(&statement)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| DbError::Query(e.to_string()))?;
+        }
+
+        let prefix = "EXPLAIN (ANALYZE, BUFFERS, FORMAT JSON) ";
+        let plan_json: Value = sqlx::query(&format!("{}{}", prefix, query))
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| DbError::from_statement(e, query, prefix))?
+            .try_get("QUERY PLAN")
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        let plan = parse_execution_plan(&plan_json)?;
+
+        let (columns, column_types) = (&mut *tx)
+            .describe(query)
+            .await
+            .map_err(|e| DbError::from_statement(e, query, ""))?
+            .columns()
+            .iter()
+            .map(|column| {
+                (
+                    column.name().to_string(),
+                    column.type_info().name().to_string(),
+                )
+            })
+            .unzip();
+
+        let mut rows = sqlx::query(&preview_statement(query, limit))
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| DbError::from_statement(e, query, PREVIEW_PREFIX))?
+            .iter()
+            .map(|row| {
+                let values: Option<Value> = row.try_get("row_values")?;
+                Ok(match values {
+                    Some(Value::Array(values)) => values,
+                    _ => Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+        // Nothing was written, but end the transaction explicitly rather than on drop
+        let _ = tx.rollback().await;
+
+        let truncated = rows.len() > limit;
+        rows.truncate(limit);
+        Ok((
+            plan,
+            ResultPreview {
+                columns,
+                column_types,
+                rows,
+                truncated,
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_previewable() {
+        assert!(check_previewable("WITH t AS (SELECT 1) SELECT * FROM t").is_ok());
+        assert!(check_previewable("DELETE FROM orders").is_err());
+        assert!(check_previewable("SELECT 1; SELECT 2").is_err());
+    }
+
+    #[test]
+    fn test_preview_statement() {
+        let statement = preview_statement("SELECT * FROM orders;\n", 10);
+        assert!(statement.starts_with(PREVIEW_PREFIX));
+        assert!(statement.ends_with("FROM (SELECT * FROM orders) AS sqltrace_preview LIMIT 11"));
+    }
+}
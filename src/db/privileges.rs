@@ -0,0 +1,488 @@
+//! What the connected role can read and use on PostgreSQL
+//!
+//! Explaining a query needs `SELECT` on every relation it reads and `USAGE`
+//! on their schemas, listing top statements needs `pg_stat_statements` to be
+//! preloaded and created in the database, and without `pg_read_all_stats`
+//! the text of other roles' statements is hidden. Reading all of it in one
+//! go lets the preflight checks say what is missing before a request trips
+//! over it, and lets the UI grey out the optional integrations a connection
+//! cannot use, with the statement that would enable each of them.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+const ACCESS_QUERY: &str = r#"
+SELECT current_user::text AS role,
+       current_database()::text AS database,
+       (SELECT rolsuper FROM pg_roles WHERE rolname = current_user) AS superuser,
+       CASE WHEN EXISTS (SELECT 1 FROM pg_roles WHERE rolname = 'pg_read_all_stats')
+            THEN pg_has_role(current_user, 'pg_read_all_stats', 'MEMBER')
+            ELSE false
+       END AS read_all_stats,
+       (SELECT extversion FROM pg_extension WHERE extname = 'pg_stat_statements') AS pss_version,
+       EXISTS (SELECT 1 FROM pg_available_extensions WHERE name = 'pg_stat_statements')
+           AS pss_available,
+       current_setting('shared_preload_libraries') AS preload_libraries,
+       current_setting('track_io_timing') = 'on' AS track_io_timing,
+       current_setting('server_version_num')::int4 AS version
+"#;
+
+/// First server version where the right to `SET` a superuser-only parameter can be granted
+const PARAMETER_PRIVILEGES_VERSION: i32 = 150_000;
+
+/// Whether each optional extension is available, created, and callable through its main function
+const EXTENSIONS_QUERY: &str = r#"
+SELECT e.name,
+       x.extversion AS installed_version,
+       EXISTS (SELECT 1 FROM pg_available_extensions a WHERE a.name = e.name) AS available,
+       (SELECT bool_or(has_function_privilege(p.oid, 'EXECUTE'))
+          FROM pg_depend d
+          JOIN pg_proc p ON p.oid = d.objid
+         WHERE d.refobjid = x.oid
+           AND d.classid = 'pg_proc'::regclass
+           AND d.refclassid = 'pg_extension'::regclass
+           AND p.proname = e.function) AS executable
+  FROM unnest($1::text[], $2::text[]) AS e(name, function)
+  LEFT JOIN pg_extension x ON x.extname = e.name
+"#;
+
+/// An optional extension and what using it takes
+struct ExtensionSpec {
+    name: &'static str,
+    title: &'static str,
+    /// Function every use of the extension calls
+    function: &'static str,
+    /// Features that stay disabled without it
+    features: &'static [&'static str],
+    /// Whether it must be in `shared_preload_libraries` to work
+    preload: bool,
+    /// Grant that lets a role call `function`, with `{role}` for the role
+    grant: &'static str,
+}
+
+const EXTENSIONS: [ExtensionSpec; 3] = [
+    ExtensionSpec {
+        name: "pg_stat_statements",
+        title: "Statement statistics",
+        function: "pg_stat_statements",
+        features: &["top_statements"],
+        preload: true,
+        grant: "GRANT EXECUTE ON FUNCTION pg_stat_statements(boolean) TO {role}",
+    },
+    ExtensionSpec {
+        name: "hypopg",
+        title: "Hypothetical indexes",
+        function: "hypopg_create_index",
+        features: &["hypothetical_indexes"],
+        preload: false,
+        grant: "GRANT EXECUTE ON FUNCTION hypopg_create_index(text) TO {role}",
+    },
+    ExtensionSpec {
+        name: "pg_buffercache",
+        title: "Shared buffer contents",
+        function: "pg_buffercache_pages",
+        features: &["buffer_cache"],
+        preload: false,
+        grant: "GRANT pg_monitor TO {role}",
+    },
+];
+
+const UNREADABLE_RELATIONS_QUERY: &str = r#"
+SELECT count(*) AS relations,
+       coalesce(array_agg(format('%I.%I', n.nspname, c.relname) ORDER BY n.nspname, c.relname)
+                    FILTER (WHERE NOT (has_schema_privilege(n.oid, 'USAGE')
+                                       AND has_table_privilege(c.oid, 'SELECT'))),
+                '{}') AS unreadable
+  FROM pg_class c
+  JOIN pg_namespace n ON n.oid = c.relnamespace
+ WHERE c.relkind IN ('r', 'p', 'v', 'm', 'f')
+   AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+   AND n.nspname NOT LIKE 'pg\_toast%'
+   AND n.nspname NOT LIKE 'pg\_temp%'
+"#;
+
+/// Whether `pg_stat_statements` can be used in the current database
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtensionStatus {
+    /// Version created in the database; `None` if `CREATE EXTENSION` was not run
+    pub installed_version: Option<String>,
+    /// Whether the server has the extension's files, so it can be created
+    pub available: bool,
+    /// Whether it is listed in `shared_preload_libraries`, without which it records nothing
+    pub preloaded: bool,
+}
+
+/// Privileges of the connected role that analyses depend on
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessReport {
+    /// Role the connection logged in as
+    pub role: String,
+    /// Database the connection is to
+    pub database: String,
+    /// Whether the role is a superuser, which bypasses every privilege check
+    pub superuser: bool,
+    /// Whether the role is a member of `pg_read_all_stats`
+    pub read_all_stats: bool,
+    /// Tables, views and foreign tables outside the system schemas
+    pub relations: u64,
+    /// Those the role cannot `SELECT` from, as `schema.name`
+    pub unreadable_relations: Vec<String>,
+    /// State of the `pg_stat_statements` extension
+    pub pg_stat_statements: ExtensionStatus,
+    /// Value of `shared_preload_libraries`
+    pub preload_libraries: String,
+    /// Whether `track_io_timing` is on for new sessions
+    pub track_io_timing: bool,
+    /// Whether the role may turn `track_io_timing` on in its session
+    pub can_set_io_timing: bool,
+}
+
+/// An optional integration and whether the connected role can use it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Integration {
+    /// Stable identifier, such as `pg_stat_statements`
+    pub id: String,
+    /// What the integration provides
+    pub title: String,
+    /// Features that are disabled while it is not usable
+    pub features: Vec<String>,
+    /// Whether the role can use it on this connection
+    pub usable: bool,
+    /// Version of the extension created in the database, for extensions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_version: Option<String>,
+    /// What stands in the way, if it is not usable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missing: Option<String>,
+    /// Statement or change that makes it usable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Integration {
+    fn new(id: &str, title: &str, features: &[&str]) -> Self {
+        Self {
+            id: id.to_string(),
+            title: title.to_string(),
+            features: features.iter().map(|f| f.to_string()).collect(),
+            usable: true,
+            installed_version: None,
+            missing: None,
+            fix: None,
+        }
+    }
+
+    fn blocked(mut self, missing: impl Into<String>, fix: impl Into<String>) -> Self {
+        self.usable = false;
+        self.missing = Some(missing.into());
+        self.fix = Some(fix.into());
+        self
+    }
+}
+
+/// An extension as the database reports it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct ExtensionState {
+    installed_version: Option<String>,
+    available: bool,
+    preloaded: bool,
+    /// Whether the role may call the extension's main function; `None` if it is not created
+    executable: Option<bool>,
+}
+
+/// Whether `spec` is usable in `state`, and what would make it usable otherwise
+fn extension_integration(
+    spec: &ExtensionSpec,
+    state: &ExtensionState,
+    report: &AccessReport,
+) -> Integration {
+    let integration = Integration {
+        installed_version: state.installed_version.clone(),
+        ..Integration::new(spec.name, spec.title, spec.features)
+    };
+    let create = format!(
+        "CREATE EXTENSION {} in database {}",
+        spec.name, report.database
+    );
+    let preload = format!(
+        "add {} to shared_preload_libraries in postgresql.conf and restart the server",
+        spec.name
+    );
+    let needs_preload = spec.preload && !state.preloaded;
+
+    if state.installed_version.is_none() {
+        let (missing, fix) = if !state.available {
+            let then = if needs_preload {
+                format!("{}, then run {}", preload, create)
+            } else {
+                format!("run {}", create)
+            };
+            (
+                format!("{} is not available on the server", spec.name),
+                format!(
+                    "install the package that provides {} on the server, then {}",
+                    spec.name, then
+                ),
+            )
+        } else if needs_preload {
+            (
+                format!("{} is not loaded or created", spec.name),
+                format!("{}, then run {}", preload, create),
+            )
+        } else {
+            (
+                format!(
+                    "{} is not created in database {}",
+                    spec.name, report.database
+                ),
+                format!("run {}", create),
+            )
+        };
+        return integration.blocked(missing, fix);
+    }
+    if needs_preload {
+        return integration.blocked(
+            format!(
+                "{} is not in shared_preload_libraries, so it records nothing",
+                spec.name
+            ),
+            preload,
+        );
+    }
+    if state.executable == Some(false) && !report.superuser {
+        return integration.blocked(
+            format!("role {} may not call {}()", report.role, spec.function),
+            spec.grant.replace("{role}", &report.role),
+        );
+    }
+    integration
+}
+
+/// Integrations that depend on the role's privileges rather than on an extension
+fn role_integrations(report: &AccessReport) -> Vec<Integration> {
+    let mut statistics = Integration::new(
+        "all_statistics",
+        "Statement text of all roles",
+        &["top_statements"],
+    );
+    if !report.superuser && !report.read_all_stats {
+        statistics = statistics.blocked(
+            format!(
+                "role {} only sees the statements and activity of its own sessions",
+                report.role
+            ),
+            format!("GRANT pg_read_all_stats TO {}", report.role),
+        );
+    }
+
+    let mut io_timing = Integration::new("io_timing", "I/O timings in plans", &["io_bound"]);
+    if !report.track_io_timing && !report.superuser && !report.can_set_io_timing {
+        io_timing = io_timing.blocked(
+            format!(
+                "track_io_timing is off and role {} may not turn it on",
+                report.role
+            ),
+            format!(
+                "ALTER SYSTEM SET track_io_timing = on and reload, or, on PostgreSQL 15+, \
+                 GRANT SET ON PARAMETER track_io_timing TO {}",
+                report.role
+            ),
+        );
+    }
+    vec![statistics, io_timing]
+}
+
+/// Whether the `shared_preload_libraries` value `setting` lists `library`
+///
+/// Entries are separated by commas and may be quoted or surrounded by spaces.
+pub fn preloads(setting: &str, library: &str) -> bool {
+    setting
+        .split(',')
+        .map(|entry| entry.trim().trim_matches('"'))
+        .any(|entry| entry == library)
+}
+
+impl Database {
+    /// Privileges of the connected role and the state of `pg_stat_statements`
+    ///
+    /// Fails with [`DbError::Unsupported`] on engines other than PostgreSQL.
+    pub async fn access_report(&self) -> Result<AccessReport, SqlTraceError> {
+        let pool = self.pg_pool()?;
+        let query_error = |e: sqlx::Error| DbError::Query(e.to_string());
+
+        let row = sqlx::query(ACCESS_QUERY)
+            .fetch_one(pool)
+            .await
+            .map_err(DbError::from)?;
+        let relations = sqlx::query(UNREADABLE_RELATIONS_QUERY)
+            .fetch_one(pool)
+            .await
+            .map_err(DbError::from)?;
+
+        let preload_libraries: String = row.try_get("preload_libraries").map_err(query_error)?;
+        let version: i32 = row.try_get("version").map_err(query_error)?;
+        let can_set_io_timing = if version >= PARAMETER_PRIVILEGES_VERSION {
+            sqlx::query_scalar("SELECT has_parameter_privilege('track_io_timing', 'SET')")
+                .fetch_one(pool)
+                .await
+                .map_err(DbError::from)?
+        } else {
+            false
+        };
+        Ok(AccessReport {
+            role: row.try_get("role").map_err(query_error)?,
+            database: row.try_get("database").map_err(query_error)?,
+            superuser: row
+                .try_get::<Option<bool>, _>("superuser")
+                .map_err(query_error)?
+                .unwrap_or(false),
+            read_all_stats: row.try_get("read_all_stats").map_err(query_error)?,
+            relations: relations
+                .try_get::<i64, _>("relations")
+                .map_err(query_error)?
+                .max(0) as u64,
+            unreadable_relations: relations.try_get("unreadable").map_err(query_error)?,
+            pg_stat_statements: ExtensionStatus {
+                installed_version: row.try_get("pss_version").map_err(query_error)?,
+                available: row.try_get("pss_available").map_err(query_error)?,
+                preloaded: preloads(&preload_libraries, "pg_stat_statements"),
+            },
+            preload_libraries,
+            track_io_timing: row.try_get("track_io_timing").map_err(query_error)?,
+            can_set_io_timing,
+        })
+    }
+
+    /// Optional integrations and whether the connected role can use each of them
+    ///
+    /// Fails with [`DbError::Unsupported`] on engines other than PostgreSQL.
+    pub async fn integrations(&self) -> Result<Vec<Integration>, SqlTraceError> {
+        let report = self.access_report().await?;
+        let rows = sqlx::query(EXTENSIONS_QUERY)
+            .bind(EXTENSIONS.iter().map(|e| e.name).collect::<Vec<_>>())
+            .bind(EXTENSIONS.iter().map(|e| e.function).collect::<Vec<_>>())
+            .fetch_all(self.pg_pool()?)
+            .await
+            .map_err(DbError::from)?;
+
+        let mut integrations = Vec::new();
+        for spec in &EXTENSIONS {
+            let row = rows.iter().find(|row| {
+                row.try_get::<String, _>("name")
+                    .is_ok_and(|n| n == spec.name)
+            });
+            let state = match row {
+                Some(row) => ExtensionState {
+                    installed_version: row
+                        .try_get("installed_version")
+                        .map_err(|e| DbError::Query(e.to_string()))?,
+                    available: row
+                        .try_get("available")
+                        .map_err(|e| DbError::Query(e.to_string()))?,
+                    preloaded: preloads(&report.preload_libraries, spec.name),
+                    executable: row
+                        .try_get("executable")
+                        .map_err(|e| DbError::Query(e.to_string()))?,
+                },
+                None => ExtensionState::default(),
+            };
+            integrations.push(extension_integration(spec, &state, &report));
+        }
+        integrations.extend(role_integrations(&report));
+        Ok(integrations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preloads() {
+        assert!(preloads("pg_stat_statements", "pg_stat_statements"));
+        assert!(preloads(
+            "auto_explain, \"pg_stat_statements\"",
+            "pg_stat_statements"
+        ));
+        assert!(!preloads("", "pg_stat_statements"));
+        assert!(!preloads("pg_stat_statements_v2", "pg_stat_statements"));
+    }
+
+    fn report() -> AccessReport {
+        AccessReport {
+            role: "app".to_string(),
+            database: "shop".to_string(),
+            ..AccessReport::default()
+        }
+    }
+
+    #[test]
+    fn test_extension_integration() {
+        let [statements, hypopg, buffercache] = &EXTENSIONS;
+        let report = report();
+
+        let missing = extension_integration(statements, &ExtensionState::default(), &report);
+        assert!(!missing.usable);
+        assert_eq!(
+            missing.missing.as_deref(),
+            Some("pg_stat_statements is not available on the server")
+        );
+        assert!(missing.fix.unwrap().ends_with(
+            "restart the server, then run CREATE EXTENSION pg_stat_statements in database shop"
+        ));
+
+        let available = ExtensionState {
+            available: true,
+            ..ExtensionState::default()
+        };
+        let uncreated = extension_integration(hypopg, &available, &report);
+        assert_eq!(
+            uncreated.fix.as_deref(),
+            Some("run CREATE EXTENSION hypopg in database shop")
+        );
+
+        let created = ExtensionState {
+            installed_version: Some("1.11".to_string()),
+            available: true,
+            preloaded: false,
+            executable: Some(true),
+        };
+        let unloaded = extension_integration(statements, &created, &report);
+        assert!(unloaded.missing.unwrap().contains("records nothing"));
+        assert!(extension_integration(hypopg, &created, &report).usable);
+
+        let revoked = ExtensionState {
+            executable: Some(false),
+            ..created
+        };
+        let blocked = extension_integration(buffercache, &revoked, &report);
+        assert_eq!(blocked.installed_version.as_deref(), Some("1.11"));
+        assert_eq!(blocked.fix.as_deref(), Some("GRANT pg_monitor TO app"));
+        let superuser = AccessReport {
+            superuser: true,
+            ..report
+        };
+        assert!(extension_integration(buffercache, &revoked, &superuser).usable);
+    }
+
+    #[test]
+    fn test_role_integrations() {
+        let mut report = report();
+        let blocked = role_integrations(&report);
+        assert_eq!(blocked[0].id, "all_statistics");
+        assert_eq!(
+            blocked[0].fix.as_deref(),
+            Some("GRANT pg_read_all_stats TO app")
+        );
+        assert_eq!(blocked[1].id, "io_timing");
+        assert!(!blocked[1].usable);
+
+        report.read_all_stats = true;
+        report.can_set_io_timing = true;
+        assert!(role_integrations(&report).iter().all(|i| i.usable));
+    }
+}
@@ -0,0 +1,378 @@
+//! Relation existence pre-check
+//!
+//! A typo in a table name otherwise costs a database round trip and comes
+//! back as a bare "relation does not exist" error. This module extracts the
+//! relations a query references from its AST and checks them against a
+//! [`SchemaSnapshot`], suggesting similarly named relations for any that are
+//! missing.
+
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{
+    Expr, Ident, ObjectName, Query, Select, SelectItem, SetExpr, Statement, TableFactor,
+    TableWithJoins,
+};
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::parser::Parser;
+
+use crate::db::schema_cache::SchemaSnapshot;
+
+/// Most suggestions returned for one missing relation
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Relation pre-check configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelationCheckConfig {
+    /// Check relations before executing queries
+    pub enabled: bool,
+    /// How long the cached list of relations is trusted, in seconds
+    pub cache_ttl_secs: u64,
+}
+
+impl Default for RelationCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cache_ttl_secs: 300,
+        }
+    }
+}
+
+/// A relation name as written in a query, with identifier case folding applied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelationRef {
+    /// Schema, if the name was qualified
+    pub schema: Option<String>,
+    /// Relation name
+    pub name: String,
+}
+
+impl std::fmt::Display for RelationRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.schema {
+            Some(schema) => write!(f, "{}.{}", schema, self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// A referenced relation that does not exist
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MissingRelation {
+    /// The relation as referenced
+    pub relation: String,
+    /// Existing relations with similar names, closest first
+    pub suggestions: Vec<String>,
+}
+
+impl std::fmt::Display for MissingRelation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "relation \"{}\" does not exist", self.relation)?;
+        if !self.suggestions.is_empty() {
+            write!(f, "; did you mean {}?", self.suggestions.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Relations referenced by `sql`, excluding CTE names, without duplicates
+///
+/// Returns an empty list if the query cannot be parsed; the database will
+/// report the syntax error itself.
+pub fn referenced_relations(sql: &str) -> Vec<RelationRef> {
+    let mut collector = Collector::default();
+    if let Ok(statements) = Parser::parse_sql(&PostgreSqlDialect {}, sql) {
+        for statement in &statements {
+            if let Statement::Query(query) = statement {
+                collector.query(query);
+            }
+        }
+    }
+    collector.relations
+}
+
+/// Check `relations` against `snapshot`, returning the missing ones with suggestions
+pub fn find_missing(relations: &[RelationRef], snapshot: &SchemaSnapshot) -> Vec<MissingRelation> {
+    relations
+        .iter()
+        .filter(|r| !snapshot.resolves(r.schema.as_deref(), &r.name))
+        .map(|r| MissingRelation {
+            relation: r.to_string(),
+            suggestions: suggest(r, snapshot),
+        })
+        .collect()
+}
+
+/// Similarly named relations that would resolve, closest first
+fn suggest(relation: &RelationRef, snapshot: &SchemaSnapshot) -> Vec<String> {
+    let max_distance = (relation.name.chars().count() / 3).max(2);
+    let mut candidates: Vec<(usize, String)> = Vec::new();
+
+    for (schema, names) in &snapshot.relations {
+        let on_path = match &relation.schema {
+            Some(wanted) => wanted == schema,
+            None => snapshot.search_path.contains(schema),
+        };
+        for name in names {
+            let distance = edit_distance(&relation.name, name);
+            // Exact names in other schemas are suggested qualified
+            if distance > max_distance || (distance == 0 && on_path) {
+                continue;
+            }
+            let display = if on_path && relation.schema.is_none() {
+                name.clone()
+            } else {
+                format!("{}.{}", schema, name)
+            };
+            candidates.push((distance, display));
+        }
+    }
+
+    candidates.sort();
+    candidates.dedup_by(|a, b| a.1 == b.1);
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// Levenshtein distance between two strings, in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Fold an identifier the way PostgreSQL does: unquoted names are lowercased
+pub(crate) fn fold(ident: &Ident) -> String {
+    match ident.quote_style {
+        Some(_) => ident.value.clone(),
+        None => ident.value.to_lowercase(),
+    }
+}
+
+/// Walks a query AST collecting table references
+#[derive(Default)]
+struct Collector {
+    relations: Vec<RelationRef>,
+    ctes: Vec<String>,
+}
+
+impl Collector {
+    fn query(&mut self, query: &Query) {
+        if let Some(with) = &query.with {
+            // CTE names shadow relations for the whole query
+            for cte in &with.cte_tables {
+                self.ctes.push(fold(&cte.alias.name));
+            }
+            for cte in &with.cte_tables {
+                self.query(&cte.query);
+            }
+        }
+        self.set_expr(&query.body);
+    }
+
+    fn set_expr(&mut self, body: &SetExpr) {
+        match body {
+            SetExpr::Select(select) => self.select(select),
+            SetExpr::Query(query) => self.query(query),
+            SetExpr::SetOperation { left, right, .. } => {
+                self.set_expr(left);
+                self.set_expr(right);
+            }
+            _ => {}
+        }
+    }
+
+    fn select(&mut self, select: &Select) {
+        for table in &select.from {
+            self.table_with_joins(table);
+        }
+        for item in &select.projection {
+            match item {
+                SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                    self.expr(expr)
+                }
+                _ => {}
+            }
+        }
+        if let Some(selection) = &select.selection {
+            self.expr(selection);
+        }
+        if let Some(having) = &select.having {
+            self.expr(having);
+        }
+    }
+
+    fn table_with_joins(&mut self, table: &TableWithJoins) {
+        self.table_factor(&table.relation);
+        for join in &table.joins {
+            self.table_factor(&join.relation);
+        }
+    }
+
+    fn table_factor(&mut self, factor: &TableFactor) {
+        match factor {
+            TableFactor::Table { name, .. } => self.object_name(name),
+            TableFactor::Derived { subquery, .. } => self.query(subquery),
+            TableFactor::NestedJoin {
+                table_with_joins, ..
+            } => self.table_with_joins(table_with_joins),
+            _ => {}
+        }
+    }
+
+    fn object_name(&mut self, name: &ObjectName) {
+        let relation = match name.0.as_slice() {
+            [name] => {
+                let name = fold(name);
+                if self.ctes.contains(&name) {
+                    return;
+                }
+                RelationRef { schema: None, name }
+            }
+            [.., schema, name] => RelationRef {
+                schema: Some(fold(schema)),
+                name: fold(name),
+            },
+            [] => return,
+        };
+        if !self.relations.contains(&relation) {
+            self.relations.push(relation);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Subquery(query) => self.query(query),
+            Expr::Exists { subquery, .. } => self.query(subquery),
+            Expr::InSubquery { expr, subquery, .. } => {
+                self.expr(expr);
+                self.query(subquery);
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::UnaryOp { expr, .. } | Expr::Nested(expr) => self.expr(expr),
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                self.expr(expr);
+                self.expr(low);
+                self.expr(high);
+            }
+            Expr::InList { expr, list, .. } => {
+                self.expr(expr);
+                list.iter().for_each(|e| self.expr(e));
+            }
+            Expr::Case {
+                operand,
+                conditions,
+                results,
+                else_result,
+            } => {
+                operand.iter().for_each(|e| self.expr(e));
+                conditions.iter().for_each(|e| self.expr(e));
+                results.iter().for_each(|e| self.expr(e));
+                else_result.iter().for_each(|e| self.expr(e));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relation(schema: Option<&str>, name: &str) -> RelationRef {
+        RelationRef {
+            schema: schema.map(str::to_string),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_referenced_relations() {
+        let relations = referenced_relations(
+            r#"WITH recent AS (SELECT * FROM Orders WHERE created_at > now())
+               SELECT * FROM recent r
+               JOIN sales."Customers" c ON c.id = r.customer_id
+               WHERE EXISTS (SELECT 1 FROM refunds WHERE refunds.order_id = r.id)
+               UNION SELECT * FROM (SELECT * FROM archive.orders) a"#,
+        );
+
+        assert_eq!(
+            relations,
+            vec![
+                relation(None, "orders"),
+                relation(Some("sales"), "Customers"),
+                relation(None, "refunds"),
+                relation(Some("archive"), "orders"),
+            ]
+        );
+        assert!(referenced_relations("SELECT FROM WHERE").is_empty());
+    }
+
+    #[test]
+    fn test_find_missing_with_suggestions() {
+        let snapshot = SchemaSnapshot::new(
+            vec!["pg_catalog".to_string(), "public".to_string()],
+            [
+                ("public", "orders_2024"),
+                ("public", "customers"),
+                ("sales", "refunds"),
+            ]
+            .map(|(s, n)| (s.to_string(), n.to_string())),
+        );
+
+        let missing = find_missing(
+            &[
+                relation(None, "customers"),
+                relation(None, "orders_2023"),
+                relation(None, "refunds"),
+                relation(Some("public"), "custmers"),
+            ],
+            &snapshot,
+        );
+
+        assert_eq!(
+            missing,
+            vec![
+                MissingRelation {
+                    relation: "orders_2023".to_string(),
+                    suggestions: vec!["orders_2024".to_string()],
+                },
+                MissingRelation {
+                    relation: "refunds".to_string(),
+                    suggestions: vec!["sales.refunds".to_string()],
+                },
+                MissingRelation {
+                    relation: "public.custmers".to_string(),
+                    suggestions: vec!["public.customers".to_string()],
+                },
+            ]
+        );
+        assert_eq!(
+            missing[0].to_string(),
+            "relation \"orders_2023\" does not exist; did you mean orders_2024?"
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("orders", "orders"), 0);
+        assert_eq!(edit_distance("ordrs", "orders"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
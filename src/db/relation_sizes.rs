@@ -0,0 +1,68 @@
+//! Relation size statistics for PostgreSQL
+//!
+//! Whether a scan deserves attention depends on the table it reads: a full
+//! scan of a 200-row lookup table is the cheapest plan there is. The planner's
+//! row estimates in `pg_class` let advisor rules tell small tables from large
+//! ones without counting rows.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// Planner statistics about the size of a table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelationSize {
+    /// Schema of the table
+    pub schema_name: String,
+    /// Table name
+    pub table_name: String,
+    /// Estimated number of rows; `None` if the table was never vacuumed or analyzed
+    pub estimated_rows: Option<u64>,
+    /// Size of the table in 8 kB pages as of the last vacuum or analyze
+    pub pages: i64,
+}
+
+const RELATION_SIZES_QUERY: &str = r#"
+SELECT n.nspname AS schema_name,
+       c.relname AS table_name,
+       CASE WHEN c.reltuples < 0 THEN NULL ELSE c.reltuples::int8 END AS estimated_rows,
+       c.relpages::int8 AS pages
+  FROM pg_class c
+  JOIN pg_namespace n ON n.oid = c.relnamespace
+ WHERE c.relkind IN ('r', 'p', 'm')
+   AND c.relname = ANY ($1)
+"#;
+
+impl Database {
+    /// Fetch size statistics for the given tables
+    pub async fn relation_sizes(
+        &self,
+        tables: &[String],
+    ) -> Result<Vec<RelationSize>, SqlTraceError> {
+        if tables.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let rows = sqlx::query(RELATION_SIZES_QUERY)
+            .bind(tables)
+            .fetch_all(self.pg_pool()?)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+        rows.iter()
+            .map(|row| -> Result<RelationSize, sqlx::Error> {
+                let estimated_rows: Option<i64> = row.try_get("estimated_rows")?;
+                Ok(RelationSize {
+                    schema_name: row.try_get("schema_name")?,
+                    table_name: row.try_get("table_name")?,
+                    estimated_rows: estimated_rows.map(|rows| rows.max(0) as u64),
+                    pages: row.try_get("pages")?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DbError::Query(e.to_string()).into())
+    }
+}
@@ -0,0 +1,781 @@
+//! Introspection of tables, columns, indexes and constraints
+//!
+//! Plans say which indexes were used, not which ones exist, and nothing
+//! about the keys between tables. This module reads the catalogs of the
+//! connected database (`pg_catalog` on PostgreSQL, `information_schema` on
+//! MySQL, MariaDB and TiDB, the table-valued pragmas on SQLite) into one
+//! engine-independent description, for advisor rules that need to know the
+//! schema and for browsing it in the UI.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::MySqlPool;
+use sqlx::postgres::PgPool;
+use sqlx::sqlite::SqlitePool;
+use sqlx::Row;
+
+use crate::db::engines::DatabaseEngineImpl;
+use crate::db::error::DbError;
+use crate::db::table_definitions::ColumnDefinition;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// Kinds of relations described
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TableKind {
+    /// An ordinary table
+    Table,
+    /// The parent of a partitioned table
+    PartitionedTable,
+    /// A view
+    View,
+    /// A materialized view
+    MaterializedView,
+    /// A table stored by a foreign data wrapper
+    ForeignTable,
+}
+
+/// An index of a table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaIndex {
+    /// Index name
+    pub name: String,
+    /// Key columns or expressions, in index order
+    pub columns: Vec<String>,
+    /// Whether the index backs the primary key
+    pub is_primary: bool,
+    /// Whether the index enforces uniqueness
+    pub is_unique: bool,
+    /// Statement recreating the index, where the engine reports one
+    pub definition: Option<String>,
+}
+
+/// Kinds of table constraints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConstraintKind {
+    /// `PRIMARY KEY`
+    PrimaryKey,
+    /// `UNIQUE`
+    Unique,
+    /// `FOREIGN KEY ... REFERENCES`
+    ForeignKey,
+    /// `CHECK`
+    Check,
+    /// `EXCLUDE` (PostgreSQL)
+    Exclusion,
+}
+
+/// The table and columns a foreign key points at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForeignKeyTarget {
+    /// Schema of the referenced table
+    pub schema_name: String,
+    /// Referenced table
+    pub table_name: String,
+    /// Referenced columns, matching the constraint's columns in order;
+    /// empty when they are the referenced table's primary key (SQLite)
+    pub columns: Vec<String>,
+}
+
+/// A constraint of a table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Constraint {
+    /// Constraint name; `None` where the engine does not name constraints (SQLite)
+    pub name: Option<String>,
+    /// What the constraint enforces
+    pub kind: ConstraintKind,
+    /// Constrained columns, in key order; empty for checks on expressions
+    pub columns: Vec<String>,
+    /// Constraint definition, where the engine reports one
+    pub definition: Option<String>,
+    /// Referenced table, for foreign keys
+    pub references: Option<ForeignKeyTarget>,
+}
+
+/// A table or view with its columns, indexes and constraints
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableSchema {
+    /// Schema name; `main` on SQLite, the database name on MySQL
+    pub schema_name: String,
+    /// Table name
+    pub table_name: String,
+    /// Kind of relation
+    pub kind: TableKind,
+    /// Columns in table order
+    pub columns: Vec<ColumnDefinition>,
+    /// Indexes ordered by name
+    pub indexes: Vec<SchemaIndex>,
+    /// Constraints ordered by name
+    pub constraints: Vec<Constraint>,
+}
+
+impl TableSchema {
+    fn new(schema_name: String, table_name: String, kind: TableKind) -> Self {
+        Self {
+            schema_name,
+            table_name,
+            kind,
+            columns: Vec::new(),
+            indexes: Vec::new(),
+            constraints: Vec::new(),
+        }
+    }
+
+    /// Schema-qualified table name
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", self.schema_name, self.table_name)
+    }
+
+    /// The primary key constraint, if the table has one
+    pub fn primary_key(&self) -> Option<&Constraint> {
+        self.constraints
+            .iter()
+            .find(|c| c.kind == ConstraintKind::PrimaryKey)
+    }
+
+    /// Foreign key constraints of the table
+    pub fn foreign_keys(&self) -> impl Iterator<Item = &Constraint> {
+        self.constraints
+            .iter()
+            .filter(|c| c.kind == ConstraintKind::ForeignKey)
+    }
+
+    /// Whether an index starts with `columns`, in order, so it can serve lookups on them
+    pub fn has_index_on(&self, columns: &[String]) -> bool {
+        !columns.is_empty()
+            && self
+                .indexes
+                .iter()
+                .any(|index| index.columns.starts_with(columns))
+    }
+
+    /// Foreign keys no index can serve
+    ///
+    /// Joins from the referenced table and deletes of referenced rows have
+    /// to scan this table for each such key.
+    pub fn unindexed_foreign_keys(&self) -> Vec<&Constraint> {
+        self.foreign_keys()
+            .filter(|fk| !self.has_index_on(&fk.columns))
+            .collect()
+    }
+}
+
+/// Collects tables keyed by schema and name, so catalog rows can be attached in any order
+#[derive(Default)]
+struct Tables(BTreeMap<(String, String), TableSchema>);
+
+impl Tables {
+    fn insert(&mut self, table: TableSchema) {
+        self.0
+            .insert((table.schema_name.clone(), table.table_name.clone()), table);
+    }
+
+    fn get_mut(&mut self, schema_name: &str, table_name: &str) -> Option<&mut TableSchema> {
+        self.0
+            .get_mut(&(schema_name.to_string(), table_name.to_string()))
+    }
+
+    fn into_vec(self) -> Vec<TableSchema> {
+        self.0.into_values().collect()
+    }
+}
+
+fn query_error(e: sqlx::Error) -> DbError {
+    DbError::Query(e.to_string())
+}
+
+impl Database {
+    /// Describe the tables and views of `schema`, or of every user schema if `None`
+    ///
+    /// On MySQL, MariaDB and TiDB `schema` is a database name and defaults to
+    /// the connection's database. SQLite has the single schema `main`.
+    pub async fn introspect_schema(
+        &self,
+        schema: Option<&str>,
+    ) -> Result<Vec<TableSchema>, SqlTraceError> {
+        let tables = match self.engine.as_ref() {
+            DatabaseEngineImpl::PostgreSQL(engine) => postgres_schema(engine.pool(), schema).await,
+            DatabaseEngineImpl::MySQL(engine) => mysql_schema(engine.pool(), schema).await,
+            DatabaseEngineImpl::MariaDB(engine) => mysql_schema(engine.pool(), schema).await,
+            DatabaseEngineImpl::TiDB(engine) => mysql_schema(engine.pool(), schema).await,
+            DatabaseEngineImpl::SQLite(engine) => sqlite_schema(engine.pool()).await,
+            _ => Err(DbError::Unsupported(format!(
+                "schema introspection is not available for {}",
+                self.engine_type()
+            ))),
+        }?;
+        Ok(tables)
+    }
+}
+
+const PG_TABLES_QUERY: &str = r#"
+SELECT n.nspname AS schema_name, c.relname AS table_name, c.relkind::text AS kind
+  FROM pg_class c
+  JOIN pg_namespace n ON n.oid = c.relnamespace
+ WHERE c.relkind IN ('r', 'p', 'v', 'm', 'f')
+   AND NOT c.relispartition
+   AND n.nspname <> 'information_schema' AND n.nspname !~ '^pg_'
+   AND ($1::text IS NULL OR n.nspname = $1)
+"#;
+
+const PG_COLUMNS_QUERY: &str = r#"
+SELECT n.nspname AS schema_name,
+       c.relname AS table_name,
+       a.attname::text AS column_name,
+       format_type(a.atttypid, a.atttypmod) AS data_type,
+       NOT a.attnotnull AS nullable,
+       pg_get_expr(ad.adbin, ad.adrelid) AS column_default
+  FROM pg_class c
+  JOIN pg_namespace n ON n.oid = c.relnamespace
+  JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+  LEFT JOIN pg_attrdef ad ON ad.adrelid = c.oid AND ad.adnum = a.attnum
+ WHERE c.relkind IN ('r', 'p', 'v', 'm', 'f')
+   AND n.nspname <> 'information_schema' AND n.nspname !~ '^pg_'
+   AND ($1::text IS NULL OR n.nspname = $1)
+ ORDER BY n.nspname, c.relname, a.attnum
+"#;
+
+const PG_INDEXES_QUERY: &str = r#"
+SELECT n.nspname AS schema_name,
+       c.relname AS table_name,
+       i.relname AS index_name,
+       ARRAY(SELECT pg_get_indexdef(x.indexrelid, k, true)
+               FROM generate_series(1, x.indnkeyatts) AS k
+              ORDER BY k) AS columns,
+       x.indisprimary AS is_primary,
+       x.indisunique AS is_unique,
+       pg_get_indexdef(x.indexrelid) AS definition
+  FROM pg_index x
+  JOIN pg_class c ON c.oid = x.indrelid
+  JOIN pg_class i ON i.oid = x.indexrelid
+  JOIN pg_namespace n ON n.oid = c.relnamespace
+ WHERE n.nspname <> 'information_schema' AND n.nspname !~ '^pg_'
+   AND ($1::text IS NULL OR n.nspname = $1)
+ ORDER BY n.nspname, c.relname, i.relname
+"#;
+
+const PG_CONSTRAINTS_QUERY: &str = r#"
+SELECT n.nspname AS schema_name,
+       c.relname AS table_name,
+       con.conname::text AS constraint_name,
+       con.contype::text AS kind,
+       pg_get_constraintdef(con.oid) AS definition,
+       ARRAY(SELECT a.attname::text
+               FROM unnest(con.conkey) WITH ORDINALITY AS k(attnum, ord)
+               JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+              ORDER BY k.ord) AS columns,
+       fn.nspname::text AS referenced_schema,
+       fc.relname::text AS referenced_table,
+       ARRAY(SELECT a.attname::text
+               FROM unnest(con.confkey) WITH ORDINALITY AS k(attnum, ord)
+               JOIN pg_attribute a ON a.attrelid = con.confrelid AND a.attnum = k.attnum
+              ORDER BY k.ord) AS referenced_columns
+  FROM pg_constraint con
+  JOIN pg_class c ON c.oid = con.conrelid
+  JOIN pg_namespace n ON n.oid = c.relnamespace
+  LEFT JOIN pg_class fc ON fc.oid = con.confrelid
+  LEFT JOIN pg_namespace fn ON fn.oid = fc.relnamespace
+ WHERE con.contype IN ('p', 'u', 'f', 'c', 'x')
+   AND n.nspname <> 'information_schema' AND n.nspname !~ '^pg_'
+   AND ($1::text IS NULL OR n.nspname = $1)
+ ORDER BY n.nspname, c.relname, con.conname
+"#;
+
+async fn postgres_schema(pool: &PgPool, schema: Option<&str>) -> Result<Vec<TableSchema>, DbError> {
+    let mut tables = Tables::default();
+    for row in sqlx::query(PG_TABLES_QUERY)
+        .bind(schema)
+        .fetch_all(pool)
+        .await
+        .map_err(query_error)?
+    {
+        let kind: String = row.try_get("kind").map_err(query_error)?;
+        let kind = match kind.as_str() {
+            "p" => TableKind::PartitionedTable,
+            "v" => TableKind::View,
+            "m" => TableKind::MaterializedView,
+            "f" => TableKind::ForeignTable,
+            _ => TableKind::Table,
+        };
+        tables.insert(TableSchema::new(
+            row.try_get("schema_name").map_err(query_error)?,
+            row.try_get("table_name").map_err(query_error)?,
+            kind,
+        ));
+    }
+
+    for row in sqlx::query(PG_COLUMNS_QUERY)
+        .bind(schema)
+        .fetch_all(pool)
+        .await
+        .map_err(query_error)?
+    {
+        let schema_name: String = row.try_get("schema_name").map_err(query_error)?;
+        let table_name: String = row.try_get("table_name").map_err(query_error)?;
+        if let Some(table) = tables.get_mut(&schema_name, &table_name) {
+            table.columns.push(ColumnDefinition {
+                name: row.try_get("column_name").map_err(query_error)?,
+                data_type: row.try_get("data_type").map_err(query_error)?,
+                nullable: row.try_get("nullable").map_err(query_error)?,
+                default: row.try_get("column_default").map_err(query_error)?,
+            });
+        }
+    }
+
+    for row in sqlx::query(PG_INDEXES_QUERY)
+        .bind(schema)
+        .fetch_all(pool)
+        .await
+        .map_err(query_error)?
+    {
+        let schema_name: String = row.try_get("schema_name").map_err(query_error)?;
+        let table_name: String = row.try_get("table_name").map_err(query_error)?;
+        if let Some(table) = tables.get_mut(&schema_name, &table_name) {
+            table.indexes.push(SchemaIndex {
+                name: row.try_get("index_name").map_err(query_error)?,
+                columns: row.try_get("columns").map_err(query_error)?,
+                is_primary: row.try_get("is_primary").map_err(query_error)?,
+                is_unique: row.try_get("is_unique").map_err(query_error)?,
+                definition: row.try_get("definition").map_err(query_error)?,
+            });
+        }
+    }
+
+    for row in sqlx::query(PG_CONSTRAINTS_QUERY)
+        .bind(schema)
+        .fetch_all(pool)
+        .await
+        .map_err(query_error)?
+    {
+        let schema_name: String = row.try_get("schema_name").map_err(query_error)?;
+        let table_name: String = row.try_get("table_name").map_err(query_error)?;
+        let Some(table) = tables.get_mut(&schema_name, &table_name) else {
+            continue;
+        };
+        let kind: String = row.try_get("kind").map_err(query_error)?;
+        let kind = match kind.as_str() {
+            "p" => ConstraintKind::PrimaryKey,
+            "u" => ConstraintKind::Unique,
+            "f" => ConstraintKind::ForeignKey,
+            "x" => ConstraintKind::Exclusion,
+            _ => ConstraintKind::Check,
+        };
+        let referenced_table: Option<String> =
+            row.try_get("referenced_table").map_err(query_error)?;
+        let references = match referenced_table {
+            Some(table_name) if kind == ConstraintKind::ForeignKey => Some(ForeignKeyTarget {
+                schema_name: row.try_get("referenced_schema").map_err(query_error)?,
+                table_name,
+                columns: row.try_get("referenced_columns").map_err(query_error)?,
+            }),
+            _ => None,
+        };
+        table.constraints.push(Constraint {
+            name: row.try_get("constraint_name").map_err(query_error)?,
+            kind,
+            columns: row.try_get("columns").map_err(query_error)?,
+            definition: row.try_get("definition").map_err(query_error)?,
+            references,
+        });
+    }
+
+    Ok(tables.into_vec())
+}
+
+// information_schema columns are cast to CHAR because MySQL 8 reports some
+// of them as binary strings
+const MYSQL_TABLES_QUERY: &str = r#"
+SELECT CAST(TABLE_SCHEMA AS CHAR) AS schema_name,
+       CAST(TABLE_NAME AS CHAR) AS table_name,
+       CAST(TABLE_TYPE AS CHAR) AS table_type
+  FROM information_schema.TABLES
+ WHERE TABLE_SCHEMA = COALESCE(?, DATABASE())
+   AND TABLE_TYPE IN ('BASE TABLE', 'VIEW')
+"#;
+
+const MYSQL_COLUMNS_QUERY: &str = r#"
+SELECT CAST(TABLE_SCHEMA AS CHAR) AS schema_name,
+       CAST(TABLE_NAME AS CHAR) AS table_name,
+       CAST(COLUMN_NAME AS CHAR) AS column_name,
+       CAST(COLUMN_TYPE AS CHAR) AS data_type,
+       CAST(IS_NULLABLE = 'YES' AS SIGNED) AS nullable,
+       CAST(COLUMN_DEFAULT AS CHAR) AS column_default
+  FROM information_schema.COLUMNS
+ WHERE TABLE_SCHEMA = COALESCE(?, DATABASE())
+ ORDER BY TABLE_NAME, ORDINAL_POSITION
+"#;
+
+const MYSQL_INDEXES_QUERY: &str = r#"
+SELECT CAST(TABLE_SCHEMA AS CHAR) AS schema_name,
+       CAST(TABLE_NAME AS CHAR) AS table_name,
+       CAST(INDEX_NAME AS CHAR) AS index_name,
+       CAST(COLUMN_NAME AS CHAR) AS column_name,
+       CAST(NON_UNIQUE AS SIGNED) AS non_unique
+  FROM information_schema.STATISTICS
+ WHERE TABLE_SCHEMA = COALESCE(?, DATABASE())
+ ORDER BY TABLE_NAME, INDEX_NAME, SEQ_IN_INDEX
+"#;
+
+const MYSQL_CONSTRAINTS_QUERY: &str = r#"
+SELECT CAST(tc.TABLE_SCHEMA AS CHAR) AS schema_name,
+       CAST(tc.TABLE_NAME AS CHAR) AS table_name,
+       CAST(tc.CONSTRAINT_NAME AS CHAR) AS constraint_name,
+       CAST(tc.CONSTRAINT_TYPE AS CHAR) AS constraint_type,
+       CAST(kcu.COLUMN_NAME AS CHAR) AS column_name,
+       CAST(kcu.REFERENCED_TABLE_SCHEMA AS CHAR) AS referenced_schema,
+       CAST(kcu.REFERENCED_TABLE_NAME AS CHAR) AS referenced_table,
+       CAST(kcu.REFERENCED_COLUMN_NAME AS CHAR) AS referenced_column
+  FROM information_schema.TABLE_CONSTRAINTS tc
+  LEFT JOIN information_schema.KEY_COLUMN_USAGE kcu
+    ON kcu.CONSTRAINT_SCHEMA = tc.CONSTRAINT_SCHEMA
+   AND kcu.TABLE_NAME = tc.TABLE_NAME
+   AND kcu.CONSTRAINT_NAME = tc.CONSTRAINT_NAME
+ WHERE tc.TABLE_SCHEMA = COALESCE(?, DATABASE())
+ ORDER BY tc.TABLE_NAME, tc.CONSTRAINT_NAME, kcu.ORDINAL_POSITION
+"#;
+
+async fn mysql_schema(pool: &MySqlPool, schema: Option<&str>) -> Result<Vec<TableSchema>, DbError> {
+    let mut tables = Tables::default();
+    for row in sqlx::query(MYSQL_TABLES_QUERY)
+        .bind(schema)
+        .fetch_all(pool)
+        .await
+        .map_err(query_error)?
+    {
+        let table_type: String = row.try_get("table_type").map_err(query_error)?;
+        let kind = match table_type.as_str() {
+            "VIEW" => TableKind::View,
+            _ => TableKind::Table,
+        };
+        tables.insert(TableSchema::new(
+            row.try_get("schema_name").map_err(query_error)?,
+            row.try_get("table_name").map_err(query_error)?,
+            kind,
+        ));
+    }
+
+    for row in sqlx::query(MYSQL_COLUMNS_QUERY)
+        .bind(schema)
+        .fetch_all(pool)
+        .await
+        .map_err(query_error)?
+    {
+        let schema_name: String = row.try_get("schema_name").map_err(query_error)?;
+        let table_name: String = row.try_get("table_name").map_err(query_error)?;
+        let nullable: i64 = row.try_get("nullable").map_err(query_error)?;
+        if let Some(table) = tables.get_mut(&schema_name, &table_name) {
+            table.columns.push(ColumnDefinition {
+                name: row.try_get("column_name").map_err(query_error)?,
+                data_type: row.try_get("data_type").map_err(query_error)?,
+                nullable: nullable != 0,
+                default: row.try_get("column_default").map_err(query_error)?,
+            });
+        }
+    }
+
+    // One row per index column, in index order
+    for row in sqlx::query(MYSQL_INDEXES_QUERY)
+        .bind(schema)
+        .fetch_all(pool)
+        .await
+        .map_err(query_error)?
+    {
+        let schema_name: String = row.try_get("schema_name").map_err(query_error)?;
+        let table_name: String = row.try_get("table_name").map_err(query_error)?;
+        let Some(table) = tables.get_mut(&schema_name, &table_name) else {
+            continue;
+        };
+        let name: String = row.try_get("index_name").map_err(query_error)?;
+        // Functional key parts have no column name
+        let column: Option<String> = row.try_get("column_name").map_err(query_error)?;
+        let non_unique: i64 = row.try_get("non_unique").map_err(query_error)?;
+        if table.indexes.last().is_none_or(|index| index.name != name) {
+            table.indexes.push(SchemaIndex {
+                is_primary: name == "PRIMARY",
+                is_unique: non_unique == 0,
+                name,
+                columns: Vec::new(),
+                definition: None,
+            });
+        }
+        if let (Some(index), Some(column)) = (table.indexes.last_mut(), column) {
+            index.columns.push(column);
+        }
+    }
+
+    // One row per constraint column, in key order; checks have none
+    for row in sqlx::query(MYSQL_CONSTRAINTS_QUERY)
+        .bind(schema)
+        .fetch_all(pool)
+        .await
+        .map_err(query_error)?
+    {
+        let schema_name: String = row.try_get("schema_name").map_err(query_error)?;
+        let table_name: String = row.try_get("table_name").map_err(query_error)?;
+        let Some(table) = tables.get_mut(&schema_name, &table_name) else {
+            continue;
+        };
+        let name: String = row.try_get("constraint_name").map_err(query_error)?;
+        let constraint_type: String = row.try_get("constraint_type").map_err(query_error)?;
+        let kind = match constraint_type.as_str() {
+            "PRIMARY KEY" => ConstraintKind::PrimaryKey,
+            "UNIQUE" => ConstraintKind::Unique,
+            "FOREIGN KEY" => ConstraintKind::ForeignKey,
+            _ => ConstraintKind::Check,
+        };
+        if table
+            .constraints
+            .last()
+            .is_none_or(|c| c.name.as_deref() != Some(name.as_str()))
+        {
+            let referenced_table: Option<String> =
+                row.try_get("referenced_table").map_err(query_error)?;
+            let references = match referenced_table {
+                Some(table_name) if kind == ConstraintKind::ForeignKey => Some(ForeignKeyTarget {
+                    schema_name: row
+                        .try_get::<Option<String>, _>("referenced_schema")
+                        .map_err(query_error)?
+                        .unwrap_or_else(|| schema_name.clone()),
+                    table_name,
+                    columns: Vec::new(),
+                }),
+                _ => None,
+            };
+            table.constraints.push(Constraint {
+                name: Some(name),
+                kind,
+                columns: Vec::new(),
+                definition: None,
+                references,
+            });
+        }
+        let constraint = table
+            .constraints
+            .last_mut()
+            .expect("a constraint was just pushed");
+        let column: Option<String> = row.try_get("column_name").map_err(query_error)?;
+        constraint.columns.extend(column);
+        if let Some(references) = &mut constraint.references {
+            let column: Option<String> = row.try_get("referenced_column").map_err(query_error)?;
+            references.columns.extend(column);
+        }
+    }
+
+    Ok(tables.into_vec())
+}
+
+const SQLITE_TABLES_QUERY: &str = r#"
+SELECT name, type, sql
+  FROM sqlite_master
+ WHERE type IN ('table', 'view', 'index')
+   AND name NOT LIKE 'sqlite\_%' ESCAPE '\'
+"#;
+
+async fn sqlite_schema(pool: &SqlitePool) -> Result<Vec<TableSchema>, DbError> {
+    const SCHEMA: &str = "main";
+
+    let mut tables = Tables::default();
+    let mut index_sql: BTreeMap<String, String> = BTreeMap::new();
+    for row in sqlx::query(SQLITE_TABLES_QUERY)
+        .fetch_all(pool)
+        .await
+        .map_err(query_error)?
+    {
+        let name: String = row.try_get("name").map_err(query_error)?;
+        let object_type: String = row.try_get("type").map_err(query_error)?;
+        match object_type.as_str() {
+            "index" => {
+                if let Some(sql) = row.try_get("sql").map_err(query_error)? {
+                    index_sql.insert(name, sql);
+                }
+            }
+            "view" => tables.insert(TableSchema::new(SCHEMA.to_string(), name, TableKind::View)),
+            _ => tables.insert(TableSchema::new(SCHEMA.to_string(), name, TableKind::Table)),
+        }
+    }
+
+    for table in tables.0.values_mut() {
+        let mut primary_key: Vec<(i64, String)> = Vec::new();
+        for row in sqlx::query("SELECT * FROM pragma_table_info(?) ORDER BY cid")
+            .bind(&table.table_name)
+            .fetch_all(pool)
+            .await
+            .map_err(query_error)?
+        {
+            let name: String = row.try_get("name").map_err(query_error)?;
+            let not_null: i64 = row.try_get("notnull").map_err(query_error)?;
+            let pk: i64 = row.try_get("pk").map_err(query_error)?;
+            if pk > 0 {
+                primary_key.push((pk, name.clone()));
+            }
+            table.columns.push(ColumnDefinition {
+                name,
+                data_type: row.try_get("type").map_err(query_error)?,
+                nullable: not_null == 0 && pk == 0,
+                default: row.try_get("dflt_value").map_err(query_error)?,
+            });
+        }
+        if table.kind == TableKind::View {
+            continue;
+        }
+        if !primary_key.is_empty() {
+            primary_key.sort();
+            table.constraints.push(Constraint {
+                name: None,
+                kind: ConstraintKind::PrimaryKey,
+                columns: primary_key.into_iter().map(|(_, name)| name).collect(),
+                definition: None,
+                references: None,
+            });
+        }
+
+        for row in sqlx::query("SELECT * FROM pragma_index_list(?) ORDER BY name")
+            .bind(&table.table_name)
+            .fetch_all(pool)
+            .await
+            .map_err(query_error)?
+        {
+            let name: String = row.try_get("name").map_err(query_error)?;
+            let unique: i64 = row.try_get("unique").map_err(query_error)?;
+            let origin: String = row.try_get("origin").map_err(query_error)?;
+            // Expression key parts have no column name
+            let columns: Vec<String> =
+                sqlx::query("SELECT name FROM pragma_index_info(?) ORDER BY seqno")
+                    .bind(&name)
+                    .fetch_all(pool)
+                    .await
+                    .map_err(query_error)?
+                    .iter()
+                    .map(|row| row.try_get::<Option<String>, _>("name"))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(query_error)?
+                    .into_iter()
+                    .flatten()
+                    .collect();
+            if origin == "u" {
+                table.constraints.push(Constraint {
+                    name: None,
+                    kind: ConstraintKind::Unique,
+                    columns: columns.clone(),
+                    definition: None,
+                    references: None,
+                });
+            }
+            table.indexes.push(SchemaIndex {
+                definition: index_sql.get(&name).cloned(),
+                name,
+                columns,
+                is_primary: origin == "pk",
+                is_unique: unique != 0,
+            });
+        }
+
+        // One row per key column; `id` numbers the foreign keys of the table
+        let mut foreign_keys: BTreeMap<i64, Constraint> = BTreeMap::new();
+        for row in sqlx::query("SELECT * FROM pragma_foreign_key_list(?) ORDER BY id, seq")
+            .bind(&table.table_name)
+            .fetch_all(pool)
+            .await
+            .map_err(query_error)?
+        {
+            let id: i64 = row.try_get("id").map_err(query_error)?;
+            let referenced_table: String = row.try_get("table").map_err(query_error)?;
+            let column: String = row.try_get("from").map_err(query_error)?;
+            // NULL when the key references the primary key implicitly
+            let referenced_column: Option<String> = row.try_get("to").map_err(query_error)?;
+            let fk = foreign_keys.entry(id).or_insert_with(|| Constraint {
+                name: None,
+                kind: ConstraintKind::ForeignKey,
+                columns: Vec::new(),
+                definition: None,
+                references: Some(ForeignKeyTarget {
+                    schema_name: SCHEMA.to_string(),
+                    table_name: referenced_table,
+                    columns: Vec::new(),
+                }),
+            });
+            fk.columns.push(column);
+            if let (Some(references), Some(column)) = (&mut fk.references, referenced_column) {
+                references.columns.push(column);
+            }
+        }
+        table.constraints.extend(foreign_keys.into_values());
+    }
+
+    Ok(tables.into_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_introspect_sqlite_schema() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        let DatabaseEngineImpl::SQLite(engine) = db.engine.as_ref() else {
+            panic!("expected a SQLite engine");
+        };
+        for ddl in [
+            "CREATE TABLE customers (id INTEGER PRIMARY KEY, email TEXT NOT NULL UNIQUE)",
+            "CREATE TABLE orders (id INTEGER PRIMARY KEY, \
+             customer_id INTEGER NOT NULL REFERENCES customers (id), \
+             status TEXT DEFAULT 'open')",
+            "CREATE INDEX orders_status ON orders (status)",
+            "CREATE VIEW open_orders AS SELECT * FROM orders WHERE status = 'open'",
+        ] {
+            sqlx::query(ddl).execute(engine.pool()).await.unwrap();
+        }
+
+        let tables = db.introspect_schema(None).await.unwrap();
+        let names: Vec<String> = tables.iter().map(TableSchema::qualified_name).collect();
+        assert_eq!(names, ["main.customers", "main.open_orders", "main.orders"]);
+
+        let customers = &tables[0];
+        assert_eq!(customers.primary_key().unwrap().columns, ["id"]);
+        assert!(customers
+            .constraints
+            .iter()
+            .any(|c| c.kind == ConstraintKind::Unique && c.columns == ["email"]));
+        assert!(customers.indexes.iter().any(|i| i.is_unique));
+
+        assert_eq!(tables[1].kind, TableKind::View);
+
+        let orders = &tables[2];
+        assert_eq!(orders.columns.len(), 3);
+        assert!(!orders.columns[1].nullable);
+        assert_eq!(orders.columns[2].default.as_deref(), Some("'open'"));
+        let index = &orders.indexes[0];
+        assert_eq!(index.columns, ["status"]);
+        assert_eq!(
+            index.definition.as_deref(),
+            Some("CREATE INDEX orders_status ON orders (status)")
+        );
+        let fk = orders.foreign_keys().next().unwrap();
+        assert_eq!(fk.columns, ["customer_id"]);
+        let target = fk.references.as_ref().unwrap();
+        assert_eq!(target.table_name, "customers");
+        assert_eq!(target.columns, ["id"]);
+        assert_eq!(orders.unindexed_foreign_keys().len(), 1);
+    }
+
+    #[test]
+    fn test_has_index_on() {
+        let mut table =
+            TableSchema::new("public".to_string(), "orders".to_string(), TableKind::Table);
+        table.indexes.push(SchemaIndex {
+            name: "orders_customer_status".to_string(),
+            columns: vec!["customer_id".to_string(), "status".to_string()],
+            is_primary: false,
+            is_unique: false,
+            definition: None,
+        });
+        let columns = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert!(table.has_index_on(&columns(&["customer_id"])));
+        assert!(table.has_index_on(&columns(&["customer_id", "status"])));
+        assert!(!table.has_index_on(&columns(&["status"])));
+        assert!(!table.has_index_on(&[]));
+    }
+}
@@ -0,0 +1,124 @@
+//! Cached view of the relations in the connected database
+//!
+//! Checks that run before every query (such as the relation pre-check) need
+//! the list of relations without paying a catalog round trip each time. The
+//! cache holds one snapshot and reloads it once it is older than its TTL or
+//! when a caller knows it is stale.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use sqlx::Row;
+use tokio::sync::RwLock;
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+const RELATIONS_QUERY: &str = r#"
+SELECT n.nspname AS schema_name, c.relname AS relation_name
+  FROM pg_class c
+  JOIN pg_namespace n ON n.oid = c.relnamespace
+ WHERE c.relkind IN ('r', 'p', 'v', 'm', 'f')
+   AND n.nspname NOT LIKE 'pg\_toast%'
+"#;
+
+/// Relations visible in the database at one point in time
+#[derive(Debug, Clone)]
+pub struct SchemaSnapshot {
+    /// Schemas searched for unqualified names, in order
+    pub search_path: Vec<String>,
+    /// Relation names, keyed by schema
+    pub relations: HashMap<String, HashSet<String>>,
+    loaded_at: Instant,
+}
+
+impl SchemaSnapshot {
+    /// Build a snapshot from a search path and `(schema, relation)` pairs
+    pub fn new(
+        search_path: Vec<String>,
+        relations: impl IntoIterator<Item = (String, String)>,
+    ) -> Self {
+        let mut by_schema: HashMap<String, HashSet<String>> = HashMap::new();
+        for (schema, name) in relations {
+            by_schema.entry(schema).or_default().insert(name);
+        }
+        Self {
+            search_path,
+            relations: by_schema,
+            loaded_at: Instant::now(),
+        }
+    }
+
+    /// Whether `name` exists in `schema`
+    pub fn contains(&self, schema: &str, name: &str) -> bool {
+        self.relations
+            .get(schema)
+            .is_some_and(|names| names.contains(name))
+    }
+
+    /// Resolve a possibly unqualified name through the search path
+    pub fn resolves(&self, schema: Option<&str>, name: &str) -> bool {
+        match schema {
+            Some(schema) => self.contains(schema, name),
+            None => self.search_path.iter().any(|s| self.contains(s, name)),
+        }
+    }
+}
+
+/// Time-limited cache of a [`SchemaSnapshot`]
+#[derive(Debug)]
+pub struct SchemaCache {
+    ttl: Duration,
+    snapshot: RwLock<Option<Arc<SchemaSnapshot>>>,
+}
+
+impl SchemaCache {
+    /// Create an empty cache whose snapshots expire after `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            snapshot: RwLock::new(None),
+        }
+    }
+
+    /// The current snapshot, loading it if missing or expired
+    pub async fn get(&self, db: &Database) -> Result<Arc<SchemaSnapshot>, SqlTraceError> {
+        if let Some(snapshot) = self.snapshot.read().await.as_ref() {
+            if snapshot.loaded_at.elapsed() < self.ttl {
+                return Ok(snapshot.clone());
+            }
+        }
+        self.refresh(db).await
+    }
+
+    /// Reload the snapshot from the database
+    pub async fn refresh(&self, db: &Database) -> Result<Arc<SchemaSnapshot>, SqlTraceError> {
+        let snapshot = Arc::new(db.schema_snapshot().await?);
+        *self.snapshot.write().await = Some(snapshot.clone());
+        Ok(snapshot)
+    }
+}
+
+impl Database {
+    /// Load the search path and every table, view, and foreign table
+    pub async fn schema_snapshot(&self) -> Result<SchemaSnapshot, SqlTraceError> {
+        let search_path: Vec<String> = sqlx::query("SELECT current_schemas(true) AS search_path")
+            .fetch_one(self.pg_pool()?)
+            .await
+            .and_then(|row| row.try_get("search_path"))
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+        let relations = sqlx::query(RELATIONS_QUERY)
+            .fetch_all(self.pg_pool()?)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?
+            .iter()
+            .map(|row| Ok((row.try_get("schema_name")?, row.try_get("relation_name")?)))
+            .collect::<Result<Vec<(String, String)>, sqlx::Error>>()
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+        Ok(SchemaSnapshot::new(search_path, relations))
+    }
+}
@@ -0,0 +1,155 @@
+//! Sequence headroom inspection for PostgreSQL
+//!
+//! Serial and identity columns silently stop accepting inserts once their
+//! sequence (or the column type backing it) runs out of values. This module
+//! reports how much of that range has been consumed for a set of tables.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// Consumption of a sequence backing a serial or identity column
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequenceUsage {
+    /// Schema of the table owning the column
+    pub schema_name: String,
+    /// Table owning the column
+    pub table_name: String,
+    /// Serial or identity column
+    pub column_name: String,
+    /// SQL type of the column (e.g., "integer")
+    pub data_type: String,
+    /// Fully qualified name of the backing sequence
+    pub sequence_name: String,
+    /// Last value handed out by the sequence, if it was ever used
+    pub last_value: Option<i64>,
+    /// Maximum value of the sequence
+    pub sequence_max: i64,
+    /// Maximum value the column type can store
+    pub column_max: i64,
+    /// Whether the column is (part of) the primary key
+    pub is_primary_key: bool,
+}
+
+impl SequenceUsage {
+    /// The effective upper bound: whichever of the sequence and column runs out first
+    pub fn limit(&self) -> i64 {
+        self.sequence_max.min(self.column_max)
+    }
+
+    /// Number of values left before inserts start failing
+    pub fn remaining(&self) -> i64 {
+        self.limit().saturating_sub(self.last_value.unwrap_or(0))
+    }
+
+    /// Share of the available range already consumed (0.0 - 1.0)
+    pub fn usage_ratio(&self) -> f64 {
+        let limit = self.limit();
+        if limit <= 0 {
+            return 0.0;
+        }
+        self.last_value.unwrap_or(0).max(0) as f64 / limit as f64
+    }
+
+    /// Whether the column is narrower than bigint
+    pub fn is_narrow_integer(&self) -> bool {
+        self.column_max <= i32::MAX as i64
+    }
+}
+
+const SEQUENCE_USAGE_QUERY: &str = r#"
+SELECT n.nspname AS schema_name,
+       t.relname AS table_name,
+       a.attname AS column_name,
+       format_type(a.atttypid, a.atttypmod) AS data_type,
+       s.schemaname || '.' || s.sequencename AS sequence_name,
+       s.last_value,
+       s.max_value AS sequence_max,
+       CASE a.atttypid
+           WHEN 'int2'::regtype THEN 32767
+           WHEN 'int4'::regtype THEN 2147483647
+           ELSE 9223372036854775807
+       END::int8 AS column_max,
+       EXISTS (
+           SELECT 1 FROM pg_index i
+            WHERE i.indrelid = t.oid AND i.indisprimary AND a.attnum = ANY (i.indkey)
+       ) AS is_primary_key
+  FROM pg_class t
+  JOIN pg_namespace n ON n.oid = t.relnamespace
+  JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum > 0 AND NOT a.attisdropped
+  JOIN pg_depend d ON d.refobjid = t.oid
+                  AND d.refobjsubid = a.attnum
+                  AND d.classid = 'pg_class'::regclass
+                  AND d.deptype IN ('a', 'i')
+  JOIN pg_class sc ON sc.oid = d.objid AND sc.relkind = 'S'
+  JOIN pg_namespace sn ON sn.oid = sc.relnamespace
+  JOIN pg_sequences s ON s.schemaname = sn.nspname AND s.sequencename = sc.relname
+ WHERE t.oid IN (SELECT to_regclass(quote_ident(name)) FROM unnest($1::text[]) AS name)
+"#;
+
+impl Database {
+    /// Fetch sequence consumption for serial/identity columns of the given tables
+    ///
+    /// Table names are resolved through the search path, as the analyzed query
+    /// resolved them, so a table of the same name in another schema is not
+    /// reported.
+    pub async fn sequence_usage(
+        &self,
+        tables: &[String],
+    ) -> Result<Vec<SequenceUsage>, SqlTraceError> {
+        if tables.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let rows = sqlx::query(SEQUENCE_USAGE_QUERY)
+            .bind(tables)
+            .fetch_all(self.pg_pool()?)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+        rows.iter()
+            .map(|row| -> Result<SequenceUsage, sqlx::Error> {
+                Ok(SequenceUsage {
+                    schema_name: row.try_get("schema_name")?,
+                    table_name: row.try_get("table_name")?,
+                    column_name: row.try_get("column_name")?,
+                    data_type: row.try_get("data_type")?,
+                    sequence_name: row.try_get("sequence_name")?,
+                    last_value: row.try_get("last_value")?,
+                    sequence_max: row.try_get("sequence_max")?,
+                    column_max: row.try_get("column_max")?,
+                    is_primary_key: row.try_get("is_primary_key")?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DbError::Query(e.to_string()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_int4_column_limits_bigint_sequence() {
+        let usage = SequenceUsage {
+            schema_name: "public".to_string(),
+            table_name: "events".to_string(),
+            column_name: "id".to_string(),
+            data_type: "integer".to_string(),
+            sequence_name: "public.events_id_seq".to_string(),
+            last_value: Some(1_932_735_283),
+            sequence_max: i64::MAX,
+            column_max: i32::MAX as i64,
+            is_primary_key: true,
+        };
+
+        assert_eq!(usage.limit(), i32::MAX as i64);
+        assert_eq!(usage.remaining(), 214_748_364);
+        assert!((usage.usage_ratio() - 0.9).abs() < 0.001);
+        assert!(usage.is_narrow_integer());
+    }
+}
@@ -0,0 +1,217 @@
+//! Dedicated database sessions with setup and teardown SQL
+//!
+//! Benchmarks sometimes need a session prepared in a particular way: a
+//! planner setting turned off, a temporary table to join against, a relation
+//! loaded into shared buffers. A [`Session`] holds one connection for the
+//! whole benchmark, runs the setup statements on it first and the teardown
+//! statements last, and then closes the connection so none of its state
+//! reaches other requests through the pool.
+
+use std::time::Duration;
+
+use sqlparser::ast::Statement;
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::parser::Parser;
+use sqlx::pool::PoolConnection;
+use sqlx::{Acquire, Postgres, Row};
+
+use crate::db::engines::postgresql::fetch_one_read_only;
+use crate::db::error::DbError;
+use crate::db::explain_options::ExplainOptions;
+use crate::db::models::ExecutionPlan;
+use crate::db::settings::{read_settings, ServerSettings};
+use crate::db::transaction::{run_transaction_on, TransactionMode, TransactionOutcome};
+use crate::db::{parse_execution_plan, Database};
+use crate::SqlTraceError;
+
+/// How a setup or teardown statement is run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HookKind {
+    /// Changes session state (`SET`, `RESET`, `CREATE TEMPORARY TABLE`, `DISCARD`), run as is
+    Session,
+    /// A query, run in a read-only transaction for its side effects on caches
+    Query,
+}
+
+fn hook_kind(sql: &str) -> Result<HookKind, String> {
+    // The parser does not know RESET, so recognize `RESET name` and `RESET ALL` here
+    let words: Vec<&str> = sql
+        .trim()
+        .trim_end_matches(';')
+        .split_whitespace()
+        .collect();
+    if let [reset, name] = words.as_slice() {
+        if reset.eq_ignore_ascii_case("reset")
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            return Ok(HookKind::Session);
+        }
+    }
+
+    let parsed = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+        .map_err(|e| format!("Hook '{}' could not be parsed: {}", sql, e))?;
+    match parsed.as_slice() {
+        [Statement::SetVariable { .. } | Statement::Discard { .. }] => Ok(HookKind::Session),
+        [Statement::CreateTable {
+            temporary: true, ..
+        }] => Ok(HookKind::Session),
+        [Statement::Query(_)] => Ok(HookKind::Query),
+        [_] => Err(format!(
+            "Hook '{}' is not SET, RESET, DISCARD, CREATE TEMPORARY TABLE or a query",
+            sql
+        )),
+        _ => Err(format!(
+            "Hook '{}' must contain exactly one SQL statement",
+            sql
+        )),
+    }
+}
+
+/// Check that every statement can be used as a setup or teardown hook
+///
+/// Hooks may only change the state of their own session or read data, so
+/// `SET`, `RESET`, `DISCARD`, `CREATE TEMPORARY TABLE` and queries are allowed.
+pub fn check_hooks(hooks: &[String]) -> Result<(), String> {
+    hooks.iter().try_for_each(|sql| hook_kind(sql).map(|_| ()))
+}
+
+/// A connection set aside for one benchmark
+pub struct Session {
+    db: Database,
+    conn: PoolConnection<Postgres>,
+    teardown: Vec<String>,
+}
+
+impl Database {
+    /// Open a dedicated session and run `setup` on it
+    ///
+    /// `teardown` runs when the session is closed with [`Session::close`].
+    pub async fn open_session(
+        &self,
+        setup: &[String],
+        teardown: &[String],
+    ) -> Result<Session, SqlTraceError> {
+        check_hooks(setup).map_err(SqlTraceError::InvalidQuery)?;
+        check_hooks(teardown).map_err(SqlTraceError::InvalidQuery)?;
+
+        let conn = self.acquire_pg().await?;
+        let mut session = Session {
+            db: self.clone(),
+            conn,
+            teardown: teardown.to_vec(),
+        };
+        if let Err(e) = session.run_hooks(setup).await {
+            let _ = session.conn.close().await;
+            return Err(e);
+        }
+        Ok(session)
+    }
+}
+
+impl Session {
+    async fn run_hooks(&mut self, hooks: &[String]) -> Result<(), SqlTraceError> {
+        for sql in hooks {
+            let kind = hook_kind(sql).map_err(SqlTraceError::InvalidQuery)?;
+            let result = match kind {
+                HookKind::Session => sqlx::query(sql).execute(&mut *self.conn).await.map(|_| ()),
+                HookKind::Query => execute_read_only(&mut *self.conn, sql).await,
+            };
+            result.map_err(|e| DbError::Query(format!("Hook '{}' failed: {}", sql, e)))?;
+        }
+        Ok(())
+    }
+
+    /// Get the execution plan of a query in this session
+    pub async fn explain(
+        &mut self,
+        query: &str,
+        options: &ExplainOptions,
+    ) -> Result<ExecutionPlan, SqlTraceError> {
+        self.db.validate_query(query)?;
+        options.validate().map_err(SqlTraceError::InvalidQuery)?;
+
+        let prefix = options.prefix();
+        let row = fetch_one_read_only(&mut *self.conn, &format!("{}{}", prefix, query))
+            .await
+            .map_err(|e| DbError::from_statement(e, query, &prefix))?;
+        let plan_json: serde_json::Value = row
+            .try_get("QUERY PLAN")
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        parse_execution_plan(&plan_json)
+    }
+
+    /// Values of the tracked server settings in this session, after setup
+    pub async fn settings(&mut self) -> Result<ServerSettings, SqlTraceError> {
+        read_settings(&mut self.conn).await.map_err(Into::into)
+    }
+
+    /// Run `statements` in one transaction in this session and roll it back
+    pub async fn run_transaction(
+        &mut self,
+        statements: &[String],
+        mode: TransactionMode,
+        timeout: Duration,
+    ) -> Result<TransactionOutcome, SqlTraceError> {
+        run_transaction_on(&mut *self.conn, statements, mode, timeout).await
+    }
+
+    /// Run the teardown statements and close the connection
+    ///
+    /// The connection is closed even if teardown fails, since it may still
+    /// carry state from the setup statements.
+    pub async fn close(mut self) -> Result<(), SqlTraceError> {
+        let teardown = std::mem::take(&mut self.teardown);
+        let result = self.run_hooks(&teardown).await;
+        let _ = self.conn.close().await;
+        result
+    }
+}
+
+async fn execute_read_only<'c>(
+    conn: impl Acquire<'c, Database = Postgres>,
+    sql: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+    sqlx::query("SET TRANSACTION READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    let result = sqlx::query(sql).execute(&mut *tx).await;
+    let rolled_back = tx.rollback().await;
+    result?;
+    rolled_back
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook_kinds() {
+        for sql in [
+            "SET enable_nestloop = off",
+            "SET work_mem TO '64MB'",
+            "RESET enable_nestloop",
+            "CREATE TEMPORARY TABLE ids AS SELECT id FROM users WHERE active",
+            "DISCARD TEMP",
+        ] {
+            assert_eq!(hook_kind(sql), Ok(HookKind::Session), "{}", sql);
+        }
+        assert_eq!(
+            hook_kind("SELECT pg_prewarm('orders')"),
+            Ok(HookKind::Query)
+        );
+
+        for sql in [
+            "CREATE TABLE ids (id int)",
+            "DROP TABLE users",
+            "DELETE FROM users",
+            "RESET ALL; DROP TABLE users",
+            "SET enable_seqscan = off; DROP TABLE users",
+        ] {
+            assert!(hook_kind(sql).is_err(), "{} should be refused", sql);
+        }
+        assert!(check_hooks(&["SET jit = off".to_string()]).is_ok());
+    }
+}
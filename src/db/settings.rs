@@ -0,0 +1,180 @@
+//! Server settings that shape plans and timings
+//!
+//! The same query gets a different plan when `random_page_cost` is lowered,
+//! spills to disk when `work_mem` is small, and pays for compilation when
+//! `jit` kicks in, and `track_io_timing` decides whether plans say how long
+//! their reads took. Advice about a plan, and the difference between two runs,
+//! only means something next to the settings the plan was made under, so
+//! explains and benchmarks record the values of the settings below.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// Settings recorded with plans and benchmark results
+pub const TRACKED_SETTINGS: [&str; 9] = [
+    "effective_cache_size",
+    "hash_mem_multiplier",
+    "jit",
+    "max_parallel_workers_per_gather",
+    "random_page_cost",
+    "seq_page_cost",
+    "shared_buffers",
+    "track_io_timing",
+    "work_mem",
+];
+
+const SETTINGS_QUERY: &str = r#"
+SELECT name, current_setting(name) AS value
+  FROM pg_settings
+ WHERE name = ANY ($1)
+"#;
+
+/// Values of the tracked settings, as `SHOW` prints them (`4MB`, `on`, `1.1`)
+///
+/// Settings the server does not have, such as `hash_mem_multiplier` before
+/// PostgreSQL 13, are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ServerSettings(pub BTreeMap<String, String>);
+
+impl ServerSettings {
+    /// Value of `name`, if it was recorded
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    /// Settings whose value differs between `self` and `other`
+    pub fn differences(&self, other: &ServerSettings) -> Vec<SettingChange> {
+        self.0
+            .keys()
+            .chain(other.0.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter_map(|name| {
+                let before = self.0.get(name);
+                let after = other.0.get(name);
+                (before != after).then(|| SettingChange {
+                    name: name.clone(),
+                    before: before.cloned(),
+                    after: after.cloned(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A setting with different values in two snapshots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingChange {
+    /// Setting name
+    pub name: String,
+    /// Value in the first snapshot; `None` if it was not recorded
+    pub before: Option<String>,
+    /// Value in the second snapshot; `None` if it was not recorded
+    pub after: Option<String>,
+}
+
+/// Settings that changed between two snapshots, if both were taken
+///
+/// A comparison with a missing snapshot cannot tell whether settings changed,
+/// so it reports none.
+pub fn setting_changes(
+    before: Option<&ServerSettings>,
+    after: Option<&ServerSettings>,
+) -> Vec<SettingChange> {
+    match (before, after) {
+        (Some(before), Some(after)) => before.differences(after),
+        _ => Vec::new(),
+    }
+}
+
+/// Read the tracked settings on `conn`, so `SET`s made in its session count
+pub(crate) async fn read_settings(conn: &mut PgConnection) -> Result<ServerSettings, DbError> {
+    let rows = sqlx::query(SETTINGS_QUERY)
+        .bind(&TRACKED_SETTINGS[..])
+        .fetch_all(conn)
+        .await
+        .map_err(|e| DbError::Query(e.to_string()))?;
+
+    rows.iter()
+        .map(|row| Ok((row.try_get("name")?, row.try_get("value")?)))
+        .collect::<Result<BTreeMap<_, _>, sqlx::Error>>()
+        .map(ServerSettings)
+        .map_err(|e| DbError::Query(e.to_string()))
+}
+
+/// Read the settings a plan made on `conn` will be made under
+///
+/// Plans are still worth having without their settings, so a failure is
+/// logged and leaves the snapshot out.
+pub(crate) async fn plan_settings(conn: &mut PgConnection) -> Option<ServerSettings> {
+    match read_settings(conn).await {
+        Ok(settings) => Some(settings),
+        Err(e) => {
+            tracing::warn!("Failed to read server settings: {}", e);
+            None
+        }
+    }
+}
+
+impl Database {
+    /// Current values of the tracked settings for new connections
+    pub async fn server_settings(&self) -> Result<ServerSettings, SqlTraceError> {
+        let mut conn = self.acquire_pg().await?;
+        read_settings(&mut conn).await.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(pairs: &[(&str, &str)]) -> ServerSettings {
+        ServerSettings(
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_setting_differences() {
+        let before = settings(&[
+            ("work_mem", "4MB"),
+            ("jit", "on"),
+            ("random_page_cost", "4"),
+        ]);
+        let after = settings(&[
+            ("work_mem", "64MB"),
+            ("jit", "on"),
+            ("hash_mem_multiplier", "2"),
+        ]);
+
+        let changes = before.differences(&after);
+        let names: Vec<&str> = changes.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["hash_mem_multiplier", "random_page_cost", "work_mem"]
+        );
+        assert_eq!(changes[0].before, None);
+        assert_eq!(changes[1].after, None);
+        assert_eq!(changes[2].before.as_deref(), Some("4MB"));
+        assert_eq!(changes[2].after.as_deref(), Some("64MB"));
+        assert!(before.differences(&before).is_empty());
+    }
+
+    #[test]
+    fn test_settings_serialize_as_map() {
+        let snapshot = settings(&[("work_mem", "4MB")]);
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json, serde_json::json!({"work_mem": "4MB"}));
+        assert_eq!(snapshot.get("work_mem"), Some("4MB"));
+        assert_eq!(snapshot.get("jit"), None);
+    }
+}
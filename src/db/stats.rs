@@ -0,0 +1,236 @@
+//! Planner statistics of tables and their columns for PostgreSQL
+//!
+//! Row estimates are only as good as the statistics behind them. After a bulk
+//! load or delete, and before autovacuum gets round to analyzing the table,
+//! the planner works from counts that no longer hold. Reading `pg_class`,
+//! `pg_stat_user_tables` and `pg_stats` side by side lets advisor rules tell
+//! a misestimate caused by stale statistics from one caused by the query.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// Statistics the planner keeps about a column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnStatistics {
+    /// Column name
+    pub column_name: String,
+    /// Fraction of rows that are NULL
+    pub null_frac: f64,
+    /// Distinct values as in `pg_stats`: a count if positive, minus a
+    /// fraction of the rows if negative (-1 means every value is distinct)
+    pub n_distinct: f64,
+}
+
+/// Planner statistics and activity counters of a table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableStatistics {
+    /// Schema of the table
+    pub schema_name: String,
+    /// Table name
+    pub table_name: String,
+    /// Row count the planner assumes (`pg_class.reltuples`); `None` if the
+    /// table was never vacuumed or analyzed
+    pub reltuples: Option<u64>,
+    /// Live rows counted by the statistics collector
+    pub live_rows: Option<u64>,
+    /// Rows inserted, updated or deleted since the table was last analyzed
+    pub modified_since_analyze: Option<u64>,
+    /// When the table was last analyzed, manually or by autovacuum, in
+    /// milliseconds since the epoch
+    pub last_analyzed: Option<i64>,
+    /// Statistics of the table's analyzed columns, by name
+    pub columns: Vec<ColumnStatistics>,
+}
+
+impl TableStatistics {
+    /// Share of the table changed since it was last analyzed
+    pub fn modified_fraction(&self) -> Option<f64> {
+        let modified = self.modified_since_analyze? as f64;
+        let rows = self.reltuples.max(self.live_rows).unwrap_or(0).max(1) as f64;
+        Some(modified / rows)
+    }
+
+    /// Whether the table holds rows but was never analyzed, or more than
+    /// `max_modified_fraction` of it changed since it was
+    pub fn is_stale(&self, max_modified_fraction: f64) -> bool {
+        let never_analyzed =
+            self.last_analyzed.is_none() && self.live_rows.is_some_and(|rows| rows > 0);
+        never_analyzed
+            || self
+                .modified_fraction()
+                .is_some_and(|fraction| fraction > max_modified_fraction)
+    }
+
+    /// Estimated number of distinct values of `column`, if it was analyzed
+    pub fn distinct_values(&self, column: &str) -> Option<f64> {
+        let stats = self.columns.iter().find(|c| c.column_name == column)?;
+        if stats.n_distinct >= 0.0 {
+            Some(stats.n_distinct)
+        } else {
+            Some(-stats.n_distinct * self.reltuples? as f64)
+        }
+    }
+}
+
+const TABLE_STATISTICS_QUERY: &str = r#"
+SELECT n.nspname AS schema_name,
+       c.relname AS table_name,
+       CASE WHEN c.reltuples < 0 THEN NULL ELSE c.reltuples::int8 END AS reltuples,
+       s.n_live_tup AS live_rows,
+       s.n_mod_since_analyze AS modified_since_analyze,
+       (extract(epoch FROM GREATEST(s.last_analyze, s.last_autoanalyze)) * 1000)::int8
+           AS last_analyzed
+  FROM pg_class c
+  JOIN pg_namespace n ON n.oid = c.relnamespace
+  LEFT JOIN pg_stat_user_tables s ON s.relid = c.oid
+ WHERE c.relkind IN ('r', 'p', 'm')
+   AND c.relname = ANY ($1)
+ ORDER BY n.nspname, c.relname
+"#;
+
+// Partitioned tables only have statistics over their partitions (`inherited`);
+// other tables list their own first
+const COLUMN_STATISTICS_QUERY: &str = r#"
+SELECT schemaname::text AS schema_name,
+       tablename::text AS table_name,
+       attname::text AS column_name,
+       null_frac::float8 AS null_frac,
+       n_distinct::float8 AS n_distinct
+  FROM pg_stats
+ WHERE tablename = ANY ($1)
+ ORDER BY schemaname, tablename, attname, inherited
+"#;
+
+impl Database {
+    /// Fetch planner statistics for the given tables and their columns
+    pub async fn table_statistics(
+        &self,
+        tables: &[String],
+    ) -> Result<Vec<TableStatistics>, SqlTraceError> {
+        if tables.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pool = self.pg_pool()?;
+
+        let rows = sqlx::query(TABLE_STATISTICS_QUERY)
+            .bind(tables)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        let mut statistics = rows
+            .iter()
+            .map(|row| -> Result<TableStatistics, sqlx::Error> {
+                let count = |column: &str| -> Result<Option<u64>, sqlx::Error> {
+                    let value: Option<i64> = row.try_get(column)?;
+                    Ok(value.map(|v| v.max(0) as u64))
+                };
+                Ok(TableStatistics {
+                    schema_name: row.try_get("schema_name")?,
+                    table_name: row.try_get("table_name")?,
+                    reltuples: count("reltuples")?,
+                    live_rows: count("live_rows")?,
+                    modified_since_analyze: count("modified_since_analyze")?,
+                    last_analyzed: row.try_get("last_analyzed")?,
+                    columns: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+        let rows = sqlx::query(COLUMN_STATISTICS_QUERY)
+            .bind(tables)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        for row in rows {
+            let read = || -> Result<(String, String, ColumnStatistics), sqlx::Error> {
+                Ok((
+                    row.try_get("schema_name")?,
+                    row.try_get("table_name")?,
+                    ColumnStatistics {
+                        column_name: row.try_get("column_name")?,
+                        null_frac: row.try_get("null_frac")?,
+                        n_distinct: row.try_get("n_distinct")?,
+                    },
+                ))
+            };
+            let (schema_name, table_name, column) =
+                read().map_err(|e| DbError::Query(e.to_string()))?;
+            let Some(table) = statistics
+                .iter_mut()
+                .find(|t| t.schema_name == schema_name && t.table_name == table_name)
+            else {
+                continue;
+            };
+            if !table
+                .columns
+                .iter()
+                .any(|c| c.column_name == column.column_name)
+            {
+                table.columns.push(column);
+            }
+        }
+
+        Ok(statistics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(
+        reltuples: Option<u64>,
+        live_rows: Option<u64>,
+        modified: Option<u64>,
+        analyzed: bool,
+    ) -> TableStatistics {
+        TableStatistics {
+            schema_name: "public".to_string(),
+            table_name: "orders".to_string(),
+            reltuples,
+            live_rows,
+            modified_since_analyze: modified,
+            last_analyzed: analyzed.then_some(1_700_000_000_000),
+            columns: vec![
+                ColumnStatistics {
+                    column_name: "status".to_string(),
+                    null_frac: 0.0,
+                    n_distinct: 4.0,
+                },
+                ColumnStatistics {
+                    column_name: "id".to_string(),
+                    null_frac: 0.0,
+                    n_distinct: -1.0,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_stale_statistics() {
+        let fresh = table(Some(10_000), Some(10_100), Some(100), true);
+        assert!(!fresh.is_stale(0.2));
+        assert!((fresh.modified_fraction().unwrap() - 100.0 / 10_100.0).abs() < 1e-9);
+
+        let bulk_loaded = table(Some(10_000), Some(60_000), Some(50_000), true);
+        assert!(bulk_loaded.is_stale(0.2));
+
+        let never_analyzed = table(None, Some(5_000), Some(5_000), false);
+        assert!(never_analyzed.is_stale(0.2));
+        assert!(!table(None, Some(0), Some(0), false).is_stale(0.2));
+    }
+
+    #[test]
+    fn test_distinct_values() {
+        let stats = table(Some(10_000), None, None, true);
+        assert_eq!(stats.distinct_values("status"), Some(4.0));
+        assert_eq!(stats.distinct_values("id"), Some(10_000.0));
+        assert_eq!(stats.distinct_values("total"), None);
+        assert_eq!(table(None, None, None, false).distinct_values("id"), None);
+    }
+}
@@ -0,0 +1,403 @@
+//! Definitions of the tables a plan reads
+//!
+//! A stored plan only makes sense against the schema it was planned on: a
+//! Seq Scan from last month may be explained by an index that did not exist
+//! yet. Columns and indexes of the relations in a plan are captured when an
+//! analysis is stored, and can later be compared with the current schema or
+//! with another analysis.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// A column of a table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnDefinition {
+    /// Column name
+    pub name: String,
+    /// Type as PostgreSQL prints it, e.g. `character varying(255)`
+    pub data_type: String,
+    /// Whether the column accepts NULL
+    pub nullable: bool,
+    /// Default expression, if any
+    pub default: Option<String>,
+}
+
+impl ColumnDefinition {
+    fn ddl(&self) -> String {
+        let mut ddl = format!("{} {}", self.name, self.data_type);
+        if !self.nullable {
+            ddl.push_str(" NOT NULL");
+        }
+        if let Some(default) = &self.default {
+            ddl.push_str(" DEFAULT ");
+            ddl.push_str(default);
+        }
+        ddl
+    }
+}
+
+/// An index of a table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexDefinition {
+    /// Index name
+    pub name: String,
+    /// `CREATE INDEX` statement recreating the index
+    pub definition: String,
+    /// Whether the index backs the primary key
+    pub is_primary: bool,
+    /// Whether the index enforces uniqueness
+    pub is_unique: bool,
+}
+
+/// Columns and indexes of a table at one point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableDefinition {
+    /// Schema name
+    pub schema_name: String,
+    /// Table name
+    pub table_name: String,
+    /// Columns in table order
+    pub columns: Vec<ColumnDefinition>,
+    /// Indexes ordered by name
+    pub indexes: Vec<IndexDefinition>,
+}
+
+impl TableDefinition {
+    /// Schema-qualified table name
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", self.schema_name, self.table_name)
+    }
+
+    /// `CREATE TABLE` and `CREATE INDEX` statements describing the table
+    pub fn ddl(&self) -> String {
+        let columns: Vec<String> = self
+            .columns
+            .iter()
+            .map(|c| format!("    {}", c.ddl()))
+            .collect();
+        let mut ddl = format!(
+            "CREATE TABLE {} (\n{}\n);",
+            self.qualified_name(),
+            columns.join(",\n")
+        );
+        for index in &self.indexes {
+            ddl.push('\n');
+            ddl.push_str(&index.definition);
+            ddl.push(';');
+        }
+        ddl
+    }
+}
+
+/// Kinds of differences between two table definitions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaChangeKind {
+    /// The table only exists in the later schema
+    TableAdded,
+    /// The table only exists in the earlier schema
+    TableRemoved,
+    /// The column only exists in the later schema
+    ColumnAdded,
+    /// The column only exists in the earlier schema
+    ColumnRemoved,
+    /// The column's type, nullability or default differs
+    ColumnChanged,
+    /// The index only exists in the later schema
+    IndexAdded,
+    /// The index only exists in the earlier schema
+    IndexRemoved,
+    /// The index has a different definition
+    IndexChanged,
+}
+
+/// One difference between an earlier and a later schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaChange {
+    /// Schema-qualified table name
+    pub table: String,
+    /// What changed
+    pub kind: SchemaChangeKind,
+    /// Column or index name; `None` for table changes
+    pub name: Option<String>,
+    /// Definition in the earlier schema
+    pub before: Option<String>,
+    /// Definition in the later schema
+    pub after: Option<String>,
+}
+
+/// Differences from the `before` definitions to the `after` definitions
+///
+/// Tables, columns and indexes are matched by name; changes are ordered by
+/// table, then columns before indexes.
+pub fn diff_definitions(
+    before: &[TableDefinition],
+    after: &[TableDefinition],
+) -> Vec<SchemaChange> {
+    let by_name = |tables: &[TableDefinition]| -> BTreeMap<String, TableDefinition> {
+        tables
+            .iter()
+            .map(|t| (t.qualified_name(), t.clone()))
+            .collect()
+    };
+    let (before, after) = (by_name(before), by_name(after));
+
+    let mut changes = Vec::new();
+    for table in before.keys().chain(after.keys()).collect::<BTreeSet<_>>() {
+        match (before.get(table), after.get(table)) {
+            (Some(old), Some(new)) => {
+                diff_named(
+                    &mut changes,
+                    table,
+                    &old.columns,
+                    &new.columns,
+                    |c| &c.name,
+                    ColumnDefinition::ddl,
+                    [
+                        SchemaChangeKind::ColumnAdded,
+                        SchemaChangeKind::ColumnRemoved,
+                        SchemaChangeKind::ColumnChanged,
+                    ],
+                );
+                diff_named(
+                    &mut changes,
+                    table,
+                    &old.indexes,
+                    &new.indexes,
+                    |i| &i.name,
+                    |i| i.definition.clone(),
+                    [
+                        SchemaChangeKind::IndexAdded,
+                        SchemaChangeKind::IndexRemoved,
+                        SchemaChangeKind::IndexChanged,
+                    ],
+                );
+            }
+            (old, new) => changes.push(SchemaChange {
+                table: table.clone(),
+                kind: if new.is_some() {
+                    SchemaChangeKind::TableAdded
+                } else {
+                    SchemaChangeKind::TableRemoved
+                },
+                name: None,
+                before: old.map(TableDefinition::ddl),
+                after: new.map(TableDefinition::ddl),
+            }),
+        }
+    }
+    changes
+}
+
+/// Compare items matched by name; `kinds` are the added, removed and changed kinds
+fn diff_named<T>(
+    changes: &mut Vec<SchemaChange>,
+    table: &str,
+    before: &[T],
+    after: &[T],
+    name: impl Fn(&T) -> &String,
+    ddl: impl Fn(&T) -> String,
+    [added, removed, changed]: [SchemaChangeKind; 3],
+) {
+    let find =
+        |items: &[T], wanted: &String| items.iter().find(|item| name(item) == wanted).map(&ddl);
+    let mut names: Vec<&String> = before.iter().map(&name).collect();
+    names.extend(
+        after
+            .iter()
+            .map(&name)
+            .filter(|n| !before.iter().any(|b| name(b) == *n)),
+    );
+
+    for item in names {
+        let (old, new) = (find(before, item), find(after, item));
+        let kind = match (&old, &new) {
+            (None, Some(_)) => added,
+            (Some(_), None) => removed,
+            (Some(old), Some(new)) if old != new => changed,
+            _ => continue,
+        };
+        changes.push(SchemaChange {
+            table: table.to_string(),
+            kind,
+            name: Some(item.clone()),
+            before: old,
+            after: new,
+        });
+    }
+}
+
+const COLUMNS_QUERY: &str = r#"
+SELECT n.nspname AS schema_name,
+       c.relname AS table_name,
+       a.attname AS column_name,
+       format_type(a.atttypid, a.atttypmod) AS data_type,
+       NOT a.attnotnull AS nullable,
+       pg_get_expr(ad.adbin, ad.adrelid) AS column_default
+  FROM pg_class c
+  JOIN pg_namespace n ON n.oid = c.relnamespace
+  JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+  LEFT JOIN pg_attrdef ad ON ad.adrelid = c.oid AND ad.adnum = a.attnum
+ WHERE c.relname = ANY ($1)
+   AND c.relkind IN ('r', 'p', 'm', 'f')
+ ORDER BY n.nspname, c.relname, a.attnum
+"#;
+
+const INDEXES_QUERY: &str = r#"
+SELECT n.nspname AS schema_name,
+       c.relname AS table_name,
+       i.relname AS index_name,
+       pg_get_indexdef(x.indexrelid) AS definition,
+       x.indisprimary AS is_primary,
+       x.indisunique AS is_unique
+  FROM pg_index x
+  JOIN pg_class c ON c.oid = x.indrelid
+  JOIN pg_class i ON i.oid = x.indexrelid
+  JOIN pg_namespace n ON n.oid = c.relnamespace
+ WHERE c.relname = ANY ($1)
+ ORDER BY n.nspname, c.relname, i.relname
+"#;
+
+impl Database {
+    /// Fetch the columns and indexes of the given tables, in every schema that has them
+    pub async fn table_definitions(
+        &self,
+        tables: &[String],
+    ) -> Result<Vec<TableDefinition>, SqlTraceError> {
+        if tables.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pool = self.pg_pool()?;
+        let query_error = |e: sqlx::Error| DbError::Query(e.to_string());
+
+        let mut definitions: BTreeMap<(String, String), TableDefinition> = BTreeMap::new();
+        for row in sqlx::query(COLUMNS_QUERY)
+            .bind(tables)
+            .fetch_all(pool)
+            .await
+            .map_err(query_error)?
+        {
+            let schema_name: String = row.try_get("schema_name").map_err(query_error)?;
+            let table_name: String = row.try_get("table_name").map_err(query_error)?;
+            definitions
+                .entry((schema_name.clone(), table_name.clone()))
+                .or_insert_with(|| TableDefinition {
+                    schema_name,
+                    table_name,
+                    columns: Vec::new(),
+                    indexes: Vec::new(),
+                })
+                .columns
+                .push(ColumnDefinition {
+                    name: row.try_get("column_name").map_err(query_error)?,
+                    data_type: row.try_get("data_type").map_err(query_error)?,
+                    nullable: row.try_get("nullable").map_err(query_error)?,
+                    default: row.try_get("column_default").map_err(query_error)?,
+                });
+        }
+
+        for row in sqlx::query(INDEXES_QUERY)
+            .bind(tables)
+            .fetch_all(pool)
+            .await
+            .map_err(query_error)?
+        {
+            let key = (
+                row.try_get("schema_name").map_err(query_error)?,
+                row.try_get("table_name").map_err(query_error)?,
+            );
+            if let Some(table) = definitions.get_mut(&key) {
+                table.indexes.push(IndexDefinition {
+                    name: row.try_get("index_name").map_err(query_error)?,
+                    definition: row.try_get("definition").map_err(query_error)?,
+                    is_primary: row.try_get("is_primary").map_err(query_error)?,
+                    is_unique: row.try_get("is_unique").map_err(query_error)?,
+                });
+            }
+        }
+
+        Ok(definitions.into_values().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orders(indexes: &[(&str, &str)]) -> TableDefinition {
+        TableDefinition {
+            schema_name: "public".to_string(),
+            table_name: "orders".to_string(),
+            columns: vec![
+                ColumnDefinition {
+                    name: "id".to_string(),
+                    data_type: "bigint".to_string(),
+                    nullable: false,
+                    default: Some("nextval('orders_id_seq'::regclass)".to_string()),
+                },
+                ColumnDefinition {
+                    name: "user_id".to_string(),
+                    data_type: "integer".to_string(),
+                    nullable: true,
+                    default: None,
+                },
+            ],
+            indexes: indexes
+                .iter()
+                .map(|(name, definition)| IndexDefinition {
+                    name: name.to_string(),
+                    definition: definition.to_string(),
+                    is_primary: false,
+                    is_unique: false,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_table_ddl() {
+        let table = orders(&[(
+            "idx_orders_user_id",
+            "CREATE INDEX idx_orders_user_id ON public.orders USING btree (user_id)",
+        )]);
+        assert_eq!(
+            table.ddl(),
+            "CREATE TABLE public.orders (\n    \
+             id bigint NOT NULL DEFAULT nextval('orders_id_seq'::regclass),\n    \
+             user_id integer\n);\n\
+             CREATE INDEX idx_orders_user_id ON public.orders USING btree (user_id);"
+        );
+    }
+
+    #[test]
+    fn test_diff_definitions() {
+        let index = (
+            "idx_orders_user_id",
+            "CREATE INDEX idx_orders_user_id ON public.orders USING btree (user_id)",
+        );
+        let before = vec![orders(&[])];
+        let mut after = vec![orders(&[index])];
+        after[0].columns[1].nullable = false;
+
+        let changes = diff_definitions(&before, &after);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].kind, SchemaChangeKind::ColumnChanged);
+        assert_eq!(
+            changes[0].after.as_deref(),
+            Some("user_id integer NOT NULL")
+        );
+        assert_eq!(changes[1].kind, SchemaChangeKind::IndexAdded);
+        assert_eq!(changes[1].name.as_deref(), Some("idx_orders_user_id"));
+        assert_eq!(changes[1].before, None);
+
+        let removed = diff_definitions(&after, &[]);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].kind, SchemaChangeKind::TableRemoved);
+        assert!(diff_definitions(&after, &after).is_empty());
+    }
+}
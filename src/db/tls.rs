@@ -0,0 +1,198 @@
+//! TLS settings for database connections
+//!
+//! Managed databases usually require TLS and often a specific CA bundle or a
+//! client certificate. These settings take precedence over any `sslmode`
+//! parameters in the connection string and are translated into the options
+//! of the PostgreSQL and MySQL drivers; SQLite and DuckDB open local files
+//! and ignore them.
+
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::{MySqlConnectOptions, MySqlSslMode};
+use sqlx::postgres::{PgConnectOptions, PgSslMode};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// How strictly a connection uses TLS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    /// Never use TLS
+    Disable,
+    /// Use TLS if the server supports it
+    Prefer,
+    /// Require TLS without checking the server certificate
+    Require,
+    /// Require TLS and a server certificate signed by a trusted CA
+    VerifyCa,
+    /// Like `VerifyCa`, and the certificate must match the server's host name
+    VerifyFull,
+}
+
+impl FromStr for SslMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "disable" | "disabled" => Ok(SslMode::Disable),
+            "prefer" | "preferred" => Ok(SslMode::Prefer),
+            "require" | "required" => Ok(SslMode::Require),
+            "verify-ca" => Ok(SslMode::VerifyCa),
+            "verify-full" | "verify-identity" => Ok(SslMode::VerifyFull),
+            other => Err(format!(
+                "unknown SSL mode '{}'; expected disable, prefer, require, verify-ca or verify-full",
+                other
+            )),
+        }
+    }
+}
+
+/// TLS settings of a connection; unset fields keep what the connection string says
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// How strictly to use TLS
+    pub mode: Option<SslMode>,
+    /// PEM file with the CA certificates to verify the server against
+    pub ca_cert: Option<PathBuf>,
+    /// PEM file with the client certificate, for servers that authenticate clients by certificate
+    pub client_cert: Option<PathBuf>,
+    /// PEM file with the private key of `client_cert`
+    pub client_key: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    /// Whether any setting is given
+    pub fn is_empty(&self) -> bool {
+        self == &TlsConfig::default()
+    }
+
+    /// Check that the settings are consistent and the files exist
+    pub fn validate(&self) -> Result<(), String> {
+        if self.client_cert.is_some() != self.client_key.is_some() {
+            return Err("a client certificate and its key must be given together".to_string());
+        }
+        if self.mode == Some(SslMode::Disable) && !self.is_disabled_only() {
+            return Err("certificates were given but the SSL mode is disable".to_string());
+        }
+        for path in [&self.ca_cert, &self.client_cert, &self.client_key]
+            .into_iter()
+            .flatten()
+        {
+            if !path.is_file() {
+                return Err(format!("TLS file {} does not exist", path.display()));
+            }
+        }
+        Ok(())
+    }
+
+    fn is_disabled_only(&self) -> bool {
+        self.ca_cert.is_none() && self.client_cert.is_none() && self.client_key.is_none()
+    }
+
+    /// Apply the settings to PostgreSQL connect options
+    pub fn apply_pg(&self, mut options: PgConnectOptions) -> PgConnectOptions {
+        if let Some(mode) = self.mode {
+            options = options.ssl_mode(match mode {
+                SslMode::Disable => PgSslMode::Disable,
+                SslMode::Prefer => PgSslMode::Prefer,
+                SslMode::Require => PgSslMode::Require,
+                SslMode::VerifyCa => PgSslMode::VerifyCa,
+                SslMode::VerifyFull => PgSslMode::VerifyFull,
+            });
+        }
+        if let Some(ca_cert) = &self.ca_cert {
+            options = options.ssl_root_cert(ca_cert);
+        }
+        if let Some(client_cert) = &self.client_cert {
+            options = options.ssl_client_cert(client_cert);
+        }
+        if let Some(client_key) = &self.client_key {
+            options = options.ssl_client_key(client_key);
+        }
+        options
+    }
+
+    /// Apply the settings to MySQL connect options
+    pub fn apply_mysql(&self, mut options: MySqlConnectOptions) -> MySqlConnectOptions {
+        if let Some(mode) = self.mode {
+            options = options.ssl_mode(match mode {
+                SslMode::Disable => MySqlSslMode::Disabled,
+                SslMode::Prefer => MySqlSslMode::Preferred,
+                SslMode::Require => MySqlSslMode::Required,
+                SslMode::VerifyCa => MySqlSslMode::VerifyCa,
+                SslMode::VerifyFull => MySqlSslMode::VerifyIdentity,
+            });
+        }
+        if let Some(ca_cert) = &self.ca_cert {
+            options = options.ssl_ca(ca_cert);
+        }
+        if let Some(client_cert) = &self.client_cert {
+            options = options.ssl_client_cert(client_cert);
+        }
+        if let Some(client_key) = &self.client_key {
+            options = options.ssl_client_key(client_key);
+        }
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ssl_mode() {
+        assert_eq!("verify-full".parse(), Ok(SslMode::VerifyFull));
+        assert_eq!("VERIFY-IDENTITY".parse(), Ok(SslMode::VerifyFull));
+        assert_eq!("required".parse(), Ok(SslMode::Require));
+        assert!("sometimes".parse::<SslMode>().is_err());
+    }
+
+    #[test]
+    fn test_validate_tls_config() {
+        assert!(TlsConfig::default().validate().is_ok());
+
+        let cert = std::env::temp_dir().join(format!("sqltrace-tls-{}.pem", std::process::id()));
+        std::fs::write(&cert, "-----BEGIN CERTIFICATE-----\n").unwrap();
+
+        let verify = TlsConfig {
+            mode: Some(SslMode::VerifyFull),
+            ca_cert: Some(cert.clone()),
+            ..TlsConfig::default()
+        };
+        assert!(verify.validate().is_ok());
+
+        let half_client = TlsConfig {
+            client_cert: Some(cert.clone()),
+            ..verify.clone()
+        };
+        assert!(half_client.validate().unwrap_err().contains("together"));
+
+        let disabled = TlsConfig {
+            mode: Some(SslMode::Disable),
+            ..verify.clone()
+        };
+        assert!(disabled.validate().is_err());
+
+        let missing = TlsConfig {
+            ca_cert: Some(cert.with_extension("missing")),
+            ..TlsConfig::default()
+        };
+        assert!(missing.validate().unwrap_err().contains("does not exist"));
+
+        std::fs::remove_file(&cert).unwrap();
+    }
+
+    #[test]
+    fn test_apply_pg() {
+        let options: PgConnectOptions = "postgres://app@db.example.com/app?sslmode=disable"
+            .parse()
+            .unwrap();
+        let tls = TlsConfig {
+            mode: Some(SslMode::VerifyFull),
+            ..TlsConfig::default()
+        };
+        let debug = format!("{:?}", tls.apply_pg(options));
+        assert!(debug.contains("VerifyFull"), "{}", debug);
+    }
+}
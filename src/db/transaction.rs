@@ -0,0 +1,441 @@
+//! Multi-statement transactions for benchmarking
+//!
+//! Some performance questions are about a flow rather than a query: read a
+//! row, update it, insert an audit record. Such a flow is run as its
+//! statements in order inside one transaction that is always rolled back, so
+//! it can be timed end to end without leaving anything behind. Read-only
+//! transactions additionally make the database reject any write.
+//!
+//! A flow may also stage data in temporary tables. Those are tracked: which
+//! statement creates each one, whether it is analyzed and indexed before a
+//! query joins it, and how large it has grown by the end of a run.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{ObjectName, Statement};
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::parser::Parser;
+use sqlx::{Acquire, Postgres, Row};
+
+use crate::db::error::DbError;
+use crate::db::relation_check::{fold, referenced_relations};
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// How the statements of a benchmarked transaction may touch data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionMode {
+    /// Only queries, in a `READ ONLY` transaction
+    #[default]
+    ReadOnly,
+    /// Queries, INSERT, UPDATE, DELETE and temporary tables, in a transaction that is rolled back
+    RolledBack,
+}
+
+/// Timing of one statement of a transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementRun {
+    /// Time from sending the statement to receiving all of its results
+    pub execution_time: Duration,
+    /// Rows returned by a query or affected by a write
+    pub rows: u64,
+}
+
+/// Outcome of one transaction run
+#[derive(Debug, Clone, Default)]
+pub struct TransactionOutcome {
+    /// Timing of each statement, in order
+    pub statements: Vec<StatementRun>,
+    /// Temporary tables as they were before the rollback
+    pub temp_relations: Vec<TempRelation>,
+    /// The session's `temp_buffers` in bytes, read if temporary tables were created
+    pub temp_buffers_bytes: Option<i64>,
+}
+
+/// Size of a temporary table at the end of a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TempRelation {
+    /// Table name
+    pub name: String,
+    /// Total size with indexes and TOAST, in bytes
+    pub size_bytes: i64,
+}
+
+/// A temporary table created by a transaction, and what its statements do with it
+///
+/// Statements are numbered from 1. Autovacuum never processes temporary
+/// tables, so one that is not analyzed explicitly has no statistics.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TempTableUsage {
+    /// Table name, with identifier case folding applied
+    pub name: String,
+    /// Statement creating the table
+    pub created_in: usize,
+    /// First statement running ANALYZE on the table
+    pub analyzed_in: Option<usize>,
+    /// First statement creating an index on the table
+    pub indexed_in: Option<usize>,
+    /// First query reading the table together with other relations
+    pub joined_in: Option<usize>,
+    /// Largest size the table reached at the end of a run, in bytes
+    pub size_bytes: Option<i64>,
+}
+
+impl TempTableUsage {
+    /// Whether a query joins the table before it is analyzed
+    pub fn joined_before_analyze(&self) -> bool {
+        self.joined_in
+            .is_some_and(|joined| self.analyzed_in.is_none_or(|analyzed| analyzed > joined))
+    }
+
+    /// Whether a query joins the table before it has an index
+    pub fn joined_before_index(&self) -> bool {
+        self.joined_in
+            .is_some_and(|joined| self.indexed_in.is_none_or(|indexed| indexed > joined))
+    }
+}
+
+/// What a statement of a transaction is
+enum StatementKind {
+    Query,
+    Write,
+    /// `CREATE TEMPORARY TABLE`, with the table name
+    CreateTemp(String),
+    /// `CREATE INDEX`, with the table name
+    CreateIndex(String),
+    /// `ANALYZE` of one table, with its name
+    Analyze(String),
+    Other,
+    /// Several statements in one entry
+    Multiple,
+}
+
+fn statement_kind(sql: &str) -> Result<StatementKind, String> {
+    // The parser only knows Hive's `ANALYZE TABLE`, so recognize `ANALYZE [VERBOSE] name` here
+    let words: Vec<&str> = sql
+        .trim()
+        .trim_end_matches(';')
+        .split_whitespace()
+        .collect();
+    if let [analyze, .., name] = words.as_slice() {
+        let verbose = &words[1..words.len() - 1];
+        if analyze.eq_ignore_ascii_case("analyze")
+            && (verbose.is_empty()
+                || (verbose.len() == 1 && verbose[0].eq_ignore_ascii_case("verbose")))
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            let table = name.rsplit('.').next().unwrap_or(name);
+            return Ok(StatementKind::Analyze(table.to_lowercase()));
+        }
+    }
+
+    let parsed = Parser::parse_sql(&PostgreSqlDialect {}, sql).map_err(|e| e.to_string())?;
+    let [statement] = parsed.as_slice() else {
+        return Ok(StatementKind::Multiple);
+    };
+    Ok(match statement {
+        Statement::Query(_) => StatementKind::Query,
+        Statement::Insert { .. } | Statement::Update { .. } | Statement::Delete { .. } => {
+            StatementKind::Write
+        }
+        Statement::CreateTable {
+            temporary: true,
+            name,
+            ..
+        } => StatementKind::CreateTemp(table_name(name)),
+        Statement::CreateIndex { table_name: t, .. } => StatementKind::CreateIndex(table_name(t)),
+        _ => StatementKind::Other,
+    })
+}
+
+/// The unqualified, case folded name of a table
+fn table_name(name: &ObjectName) -> String {
+    name.0.last().map(fold).unwrap_or_default()
+}
+
+/// Check that every entry of `statements` is a single statement allowed in `mode`
+///
+/// Transaction control, DDL and utility statements are always refused: they
+/// would end the transaction early or have effects a rollback cannot undo.
+/// The exception, in rolled_back mode, is creating temporary tables and
+/// indexing and analyzing those created by an earlier statement.
+pub fn check_transaction(statements: &[String], mode: TransactionMode) -> Result<(), String> {
+    if statements.is_empty() {
+        return Err("A transaction needs at least one statement".to_string());
+    }
+
+    let mut temp_tables: Vec<String> = Vec::new();
+    for (i, sql) in statements.iter().enumerate() {
+        let number = i + 1;
+        let kind = statement_kind(sql)
+            .map_err(|e| format!("Statement {} could not be parsed: {}", number, e))?;
+        let table = match &kind {
+            StatementKind::Query => continue,
+            StatementKind::Write => None,
+            StatementKind::CreateTemp(table)
+            | StatementKind::CreateIndex(table)
+            | StatementKind::Analyze(table) => Some(table),
+            StatementKind::Other => {
+                return Err(format!(
+                "Statement {} is not a query, INSERT, UPDATE, DELETE or temporary table statement",
+                number
+            ))
+            }
+            StatementKind::Multiple => {
+                return Err(format!(
+                    "Statement {} must contain exactly one SQL statement",
+                    number
+                ))
+            }
+        };
+        if mode != TransactionMode::RolledBack {
+            return Err(format!(
+                "Statement {} writes data, which requires the rolled_back mode",
+                number
+            ));
+        }
+        match (&kind, table) {
+            (StatementKind::CreateTemp(_), Some(table)) => temp_tables.push(table.clone()),
+            (StatementKind::CreateIndex(_) | StatementKind::Analyze(_), Some(table))
+                if !temp_tables.contains(table) =>
+            {
+                return Err(format!(
+                    "Statement {} may only index or analyze a temporary table created by an earlier statement, not {}",
+                    number, table
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The temporary tables `statements` create, and when they are analyzed, indexed and joined
+///
+/// Statements that cannot be parsed are skipped; sizes are left unknown.
+pub fn temp_table_usage(statements: &[String]) -> Vec<TempTableUsage> {
+    let mut tables: Vec<TempTableUsage> = Vec::new();
+    for (i, sql) in statements.iter().enumerate() {
+        let number = i + 1;
+        match statement_kind(sql) {
+            Ok(StatementKind::CreateTemp(name)) => tables.push(TempTableUsage {
+                name,
+                created_in: number,
+                analyzed_in: None,
+                indexed_in: None,
+                joined_in: None,
+                size_bytes: None,
+            }),
+            Ok(StatementKind::Analyze(name)) => {
+                if let Some(table) = tables.iter_mut().find(|t| t.name == name) {
+                    table.analyzed_in.get_or_insert(number);
+                }
+            }
+            Ok(StatementKind::CreateIndex(name)) => {
+                if let Some(table) = tables.iter_mut().find(|t| t.name == name) {
+                    table.indexed_in.get_or_insert(number);
+                }
+            }
+            Ok(StatementKind::Query) => {
+                let relations = referenced_relations(sql);
+                if relations.len() < 2 {
+                    continue;
+                }
+                for table in &mut tables {
+                    if relations.iter().any(|r| r.name == table.name) {
+                        table.joined_in.get_or_insert(number);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    tables
+}
+
+impl Database {
+    /// Run `statements` in order in one transaction and roll it back
+    ///
+    /// Each statement is subject to `timeout` as its `statement_timeout`.
+    /// Callers are expected to have checked the statements with
+    /// [`check_transaction`].
+    pub async fn run_transaction(
+        &self,
+        statements: &[String],
+        mode: TransactionMode,
+        timeout: Duration,
+    ) -> Result<TransactionOutcome, SqlTraceError> {
+        run_transaction_on(self.pg_pool()?, statements, mode, timeout).await
+    }
+}
+
+/// Run `statements` in one transaction begun on `conn` and roll it back
+pub(crate) async fn run_transaction_on<'c>(
+    conn: impl Acquire<'c, Database = Postgres>,
+    statements: &[String],
+    mode: TransactionMode,
+    timeout: Duration,
+) -> Result<TransactionOutcome, SqlTraceError> {
+    let mut tx = conn
+        .begin()
+        .await
+        .map_err(|e| DbError::Connection(e.to_string()))?;
+
+    let mut setup = Vec::new();
+    if mode == TransactionMode::ReadOnly {
+        setup.push("SET TRANSACTION READ ONLY".to_string());
+    }
+    setup.push(format!(
+        "SET LOCAL statement_timeout = {}",
+        timeout.as_millis()
+    ));
+    for statement in &setup {
+        sqlx::query(statement)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+    }
+
+    let mut runs = Vec::with_capacity(statements.len());
+    for (i, statement) in statements.iter().enumerate() {
+        let started = Instant::now();
+        let result = sqlx::query(statement)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| DbError::Query(format!("Statement {} failed: {}", i + 1, e)))?;
+        runs.push(StatementRun {
+            execution_time: started.elapsed(),
+            rows: result.rows_affected(),
+        });
+    }
+
+    // Temporary tables disappear with the rollback, so measure them first
+    let mut outcome = TransactionOutcome {
+        statements: runs,
+        ..TransactionOutcome::default()
+    };
+    if mode == TransactionMode::RolledBack {
+        let rows = sqlx::query(TEMP_RELATIONS_QUERY)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        for row in rows {
+            outcome.temp_relations.push(TempRelation {
+                name: row
+                    .try_get("name")
+                    .map_err(|e| DbError::Query(e.to_string()))?,
+                size_bytes: row
+                    .try_get("size_bytes")
+                    .map_err(|e| DbError::Query(e.to_string()))?,
+            });
+        }
+        if !outcome.temp_relations.is_empty() {
+            let temp_buffers: i64 =
+                sqlx::query_scalar("SELECT pg_size_bytes(current_setting('temp_buffers'))")
+                    .fetch_one(&mut *tx)
+                    .await
+                    .map_err(|e| DbError::Query(e.to_string()))?;
+            outcome.temp_buffers_bytes = Some(temp_buffers);
+        }
+    }
+
+    // Nothing may persist, whatever the mode
+    tx.rollback()
+        .await
+        .map_err(|e| DbError::Query(e.to_string()))?;
+    Ok(outcome)
+}
+
+/// Tables in the session's temporary schema, which only exists once one was created
+const TEMP_RELATIONS_QUERY: &str = "SELECT c.relname::text AS name, \
+    pg_total_relation_size(c.oid) AS size_bytes \
+    FROM pg_class c \
+    WHERE c.relnamespace = pg_my_temp_schema() AND c.relkind IN ('r', 'p') \
+    ORDER BY c.relname";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statements(sql: &[&str]) -> Vec<String> {
+        sql.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_check_transaction() {
+        let flow = statements(&[
+            "SELECT balance FROM accounts WHERE id = 1",
+            "UPDATE accounts SET balance = balance - 10 WHERE id = 1",
+            "INSERT INTO ledger (account_id, amount) VALUES (1, -10)",
+        ]);
+        assert!(check_transaction(&flow, TransactionMode::RolledBack).is_ok());
+        assert!(check_transaction(&flow, TransactionMode::ReadOnly)
+            .unwrap_err()
+            .contains("Statement 2 writes data"));
+        assert!(check_transaction(&flow[..1], TransactionMode::ReadOnly).is_ok());
+
+        for sql in ["COMMIT", "DROP TABLE accounts", "SELECT 1; SELECT 2"] {
+            let flow = statements(&["SELECT 1", sql]);
+            assert!(
+                check_transaction(&flow, TransactionMode::RolledBack).is_err(),
+                "{} should be refused",
+                sql
+            );
+        }
+        assert!(check_transaction(&[], TransactionMode::ReadOnly).is_err());
+
+        let staged = statements(&[
+            "CREATE TEMPORARY TABLE staged AS SELECT id FROM accounts WHERE balance < 0",
+            "CREATE INDEX staged_id ON staged (id)",
+            "ANALYZE staged",
+            "SELECT * FROM staged s JOIN ledger l ON l.account_id = s.id",
+        ]);
+        assert!(check_transaction(&staged, TransactionMode::RolledBack).is_ok());
+        assert!(check_transaction(&staged, TransactionMode::ReadOnly)
+            .unwrap_err()
+            .contains("Statement 1 writes data"));
+        for sql in [
+            "CREATE INDEX accounts_id ON accounts (id)",
+            "ANALYZE accounts",
+        ] {
+            let flow = statements(&[&staged[0], sql]);
+            assert!(check_transaction(&flow, TransactionMode::RolledBack)
+                .unwrap_err()
+                .contains("not accounts"));
+        }
+    }
+
+    #[test]
+    fn test_temp_table_usage() {
+        let flow = statements(&[
+            "CREATE TEMP TABLE staged (id int)",
+            "CREATE TEMPORARY TABLE Totals (id int, total numeric)",
+            "INSERT INTO staged SELECT id FROM accounts",
+            "SELECT count(*) FROM staged",
+            "SELECT * FROM staged s JOIN ledger l ON l.account_id = s.id",
+            "ANALYZE VERBOSE staged",
+            "CREATE INDEX totals_id ON totals (id)",
+        ]);
+        let usage = temp_table_usage(&flow);
+        assert_eq!(usage.len(), 2);
+
+        let staged = &usage[0];
+        assert_eq!(staged.name, "staged");
+        assert_eq!(staged.created_in, 1);
+        assert_eq!(staged.joined_in, Some(5));
+        assert_eq!(staged.analyzed_in, Some(6));
+        assert_eq!(staged.indexed_in, None);
+        assert!(staged.joined_before_analyze());
+        assert!(staged.joined_before_index());
+
+        let totals = &usage[1];
+        assert_eq!(totals.name, "totals");
+        assert_eq!(totals.indexed_in, Some(7));
+        assert_eq!(totals.joined_in, None);
+        assert!(!totals.joined_before_analyze());
+    }
+}
@@ -0,0 +1,194 @@
+//! The most expensive statements of a PostgreSQL database
+//!
+//! `pg_stat_statements` aggregates every statement the server has run by
+//! its normalized text, with constants replaced by `$n` placeholders. Sorted
+//! by total time it answers which queries are worth analyzing first, without
+//! collecting logs from the application.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::db::error::DbError;
+use crate::db::Database;
+use crate::SqlTraceError;
+
+/// First server version whose `pg_stat_statements` reports `*_exec_time` columns
+const EXEC_TIME_COLUMNS_VERSION: i32 = 130_000;
+
+/// What to rank statements by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementOrder {
+    /// Time spent executing the statement across all calls
+    #[default]
+    TotalTime,
+    /// Average time per call
+    MeanTime,
+    /// Number of calls
+    Calls,
+    /// Rows returned or affected across all calls
+    Rows,
+}
+
+impl StatementOrder {
+    fn column(self, exec_time_columns: bool) -> &'static str {
+        match (self, exec_time_columns) {
+            (Self::TotalTime, true) => "total_exec_time",
+            (Self::TotalTime, false) => "total_time",
+            (Self::MeanTime, true) => "mean_exec_time",
+            (Self::MeanTime, false) => "mean_time",
+            (Self::Calls, _) => "calls",
+            (Self::Rows, _) => "rows",
+        }
+    }
+}
+
+/// Execution statistics of one normalized statement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatementStats {
+    /// Identifier `pg_stat_statements` groups the statement by
+    pub query_id: Option<i64>,
+    /// Normalized statement text, with constants replaced by `$n` placeholders
+    pub query: String,
+    /// Number of times the statement ran
+    pub calls: u64,
+    /// Time spent executing the statement across all calls, in milliseconds
+    pub total_time_ms: f64,
+    /// Average execution time per call, in milliseconds
+    pub mean_time_ms: f64,
+    /// Rows returned or affected across all calls
+    pub rows: u64,
+    /// Shared buffer hits across all calls
+    pub shared_blks_hit: u64,
+    /// Shared blocks read from disk or the OS cache across all calls
+    pub shared_blks_read: u64,
+    /// Number of `$n` placeholders, each of which needs a value to explain the statement
+    pub placeholders: usize,
+}
+
+impl Database {
+    /// The `limit` statements of the current database that rank highest by `order`
+    ///
+    /// Fails with [`DbError::Unsupported`] if the `pg_stat_statements`
+    /// extension is not installed in the database.
+    pub async fn top_statements(
+        &self,
+        order: StatementOrder,
+        limit: i64,
+    ) -> Result<Vec<StatementStats>, SqlTraceError> {
+        let pool = self.pg_pool()?;
+        let row = sqlx::query(
+            "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'pg_stat_statements') \
+                    AS installed, \
+                    current_setting('server_version_num')::int4 AS version",
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| DbError::Query(e.to_string()))?;
+        let installed: bool = row
+            .try_get("installed")
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        if !installed {
+            return Err(DbError::Unsupported(
+                "pg_stat_statements is not installed; add it to shared_preload_libraries and \
+                 run CREATE EXTENSION pg_stat_statements"
+                    .to_string(),
+            )
+            .into());
+        }
+        let version: i32 = row
+            .try_get("version")
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        let exec_time_columns = version >= EXEC_TIME_COLUMNS_VERSION;
+
+        let query = format!(
+            "SELECT queryid, query, calls, {total}::float8 AS total_time_ms, \
+                    {mean}::float8 AS mean_time_ms, rows, shared_blks_hit, shared_blks_read \
+               FROM pg_stat_statements \
+              WHERE dbid = (SELECT oid FROM pg_database WHERE datname = current_database()) \
+              ORDER BY {order} DESC NULLS LAST \
+              LIMIT $1",
+            total = StatementOrder::TotalTime.column(exec_time_columns),
+            mean = StatementOrder::MeanTime.column(exec_time_columns),
+            order = order.column(exec_time_columns),
+        );
+        let rows = sqlx::query(&query)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+        rows.iter()
+            .map(|row| -> Result<StatementStats, sqlx::Error> {
+                let count = |column: &str| -> Result<u64, sqlx::Error> {
+                    Ok(row.try_get::<i64, _>(column)?.max(0) as u64)
+                };
+                // Without pg_read_all_stats, the text of other users' statements is hidden
+                let query: Option<String> = row.try_get("query")?;
+                let query = query.unwrap_or_default();
+                Ok(StatementStats {
+                    query_id: row.try_get("queryid")?,
+                    placeholders: placeholder_count(&query),
+                    query,
+                    calls: count("calls")?,
+                    total_time_ms: row.try_get("total_time_ms")?,
+                    mean_time_ms: row.try_get("mean_time_ms")?,
+                    rows: count("rows")?,
+                    shared_blks_hit: count("shared_blks_hit")?,
+                    shared_blks_read: count("shared_blks_read")?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DbError::Query(e.to_string()).into())
+    }
+}
+
+/// Highest `$n` placeholder number in `query`, ignoring string literals and quoted identifiers
+fn placeholder_count(query: &str) -> usize {
+    let mut highest = 0;
+    let mut quote: Option<char> = None;
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '$') => {
+                let mut digits = String::new();
+                while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(d);
+                }
+                if let Ok(n) = digits.parse::<usize>() {
+                    highest = highest.max(n);
+                }
+            }
+            (None, _) => {}
+        }
+    }
+    highest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placeholder_count() {
+        assert_eq!(
+            placeholder_count("SELECT * FROM orders WHERE customer_id = $1 AND total > $2"),
+            2
+        );
+        assert_eq!(
+            placeholder_count("SELECT '$9' AS price, \"col$5\" FROM t WHERE id = $3"),
+            3
+        );
+        assert_eq!(placeholder_count("SELECT now()"), 0);
+    }
+
+    #[test]
+    fn test_order_columns() {
+        assert_eq!(StatementOrder::TotalTime.column(true), "total_exec_time");
+        assert_eq!(StatementOrder::MeanTime.column(false), "mean_time");
+        assert_eq!(StatementOrder::Calls.column(true), "calls");
+    }
+}
@@ -3,7 +3,8 @@
 //! This module defines the main error type `SqlTraceError` used throughout the application,
 //! along with convenient type aliases and conversion implementations.
 
-use crate::db::error::DbError;
+use crate::db::error::{DbError, ErrorLocation};
+use crate::storage::StorageError;
 use thiserror::Error;
 
 /// The main error type for the SQL Trace application.
@@ -17,6 +18,11 @@ pub enum SqlTraceError {
     #[error("Database error: {0}")]
     Database(String),
 
+    /// The database could not be reached, or the connection to it broke.
+    /// Contains a message describing the failure.
+    #[error("Database error: {0}")]
+    Connection(String),
+
     /// An error that occurred during JSON serialization or deserialization.
     /// Wraps the underlying `serde_json::Error`.
     #[error("JSON parsing error: {0}")]
@@ -41,18 +47,56 @@ pub enum SqlTraceError {
     /// Contains a message describing why the query is invalid.
     #[error("Query error: {0}")]
     InvalidQuery(String),
+
+    /// An error that occurred while reading or writing SQLTrace's own storage.
+    /// Contains a message describing the storage error.
+    #[error("Storage error: {0}")]
+    Storage(String),
+
+    /// A query that was cancelled on request before it finished.
+    #[error("Query was cancelled")]
+    Cancelled,
+
+    /// A database error reported at a known position in the submitted query.
+    /// Carries the message and the location of the offending token.
+    #[error("Database error: {message}")]
+    QueryAt {
+        /// The database's error message
+        message: String,
+        /// Where in the submitted query the error was reported
+        location: ErrorLocation,
+    },
+}
+
+impl SqlTraceError {
+    /// Where in the submitted query the error occurred, if known
+    pub fn location(&self) -> Option<&ErrorLocation> {
+        match self {
+            SqlTraceError::QueryAt { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Whether the error means the database was not reached, rather than that it rejected the query
+    pub fn is_connection_failure(&self) -> bool {
+        matches!(self, SqlTraceError::Connection(_) | SqlTraceError::Io(_))
+    }
 }
 
 impl From<sqlx::Error> for SqlTraceError {
     fn from(err: sqlx::Error) -> Self {
-        SqlTraceError::Database(err.to_string())
+        if crate::db::error::is_connection_error(&err) {
+            SqlTraceError::Connection(err.to_string())
+        } else {
+            SqlTraceError::Database(err.to_string())
+        }
     }
 }
 
 impl From<DbError> for SqlTraceError {
     fn from(err: DbError) -> Self {
         match err {
-            DbError::Connection(msg) => SqlTraceError::Database(msg),
+            DbError::Connection(msg) => SqlTraceError::Connection(msg),
             DbError::Query(msg) => SqlTraceError::Database(msg),
             DbError::Json(e) => SqlTraceError::Json(e),
             DbError::Io(e) => SqlTraceError::Io(e),
@@ -60,6 +104,18 @@ impl From<DbError> for SqlTraceError {
             DbError::PlanError(msg) => SqlTraceError::PlanError(msg),
             DbError::InvalidQuery(msg) => SqlTraceError::InvalidQuery(msg),
             DbError::PlanParsing(msg) => SqlTraceError::PlanError(msg),
+            other @ DbError::Unsupported(_) => SqlTraceError::Database(other.to_string()),
+            DbError::Cancelled => SqlTraceError::Cancelled,
+            DbError::QueryAt { message, location } => SqlTraceError::QueryAt { message, location },
+        }
+    }
+}
+
+impl From<StorageError> for SqlTraceError {
+    fn from(err: StorageError) -> Self {
+        match err {
+            StorageError::Config(msg) => SqlTraceError::Config(msg),
+            other => SqlTraceError::Storage(other.to_string()),
         }
     }
 }
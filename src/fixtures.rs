@@ -0,0 +1,312 @@
+//! Plan fixtures with golden advisor output, for regression tests
+//!
+//! Plans from unusual setups (partitioned tables, parallel queries, foreign
+//! tables) are where parser and advisor changes tend to regress. A fixture
+//! directory holds such plans as `<name>.json` files, each the output of
+//! `EXPLAIN (FORMAT JSON)` as PostgreSQL prints it, or a plan as sqltrace
+//! stores it in history. Next to each plan, `<name>.golden` records what
+//! parsing and analyzing it produced: the shape of the plan, the performance
+//! score, and the suggestions the advisor made. Checking the directory runs
+//! every plan through the parser and the advisor again and compares the
+//! result with its golden file.
+//!
+//! ```no_run
+//! use sqltrace_rs::{advisor::QueryAdvisor, fixtures};
+//!
+//! # fn example() -> Result<(), sqltrace_rs::SqlTraceError> {
+//! let report = fixtures::check_dir("tests/plans".as_ref(), &QueryAdvisor::new(), false)?;
+//! assert!(report.is_match(), "{}", report);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Missing golden files are written. Set `SQLTRACE_UPDATE_SNAPSHOTS=1` to
+//! have [`check_dir`] rewrite mismatching ones instead of reporting them.
+
+use serde_json::Value;
+use std::fmt;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::advisor::{AnalysisContext, QueryAdvisor};
+use crate::db::models::ExecutionPlan;
+use crate::db::parse_execution_plan;
+use crate::snapshot::{self, SnapshotOutcome, UPDATE_ENV};
+use crate::SqlTraceError;
+
+/// Extension of plan fixture files
+pub const FIXTURE_EXTENSION: &str = "json";
+
+/// Extension of golden files
+pub const GOLDEN_EXTENSION: &str = "golden";
+
+/// A plan loaded from a fixture file
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    /// File name without its extension
+    pub name: String,
+    /// Path of the plan file
+    pub path: PathBuf,
+    /// The parsed plan
+    pub plan: ExecutionPlan,
+}
+
+impl Fixture {
+    /// Path of the golden file kept next to the plan
+    pub fn golden_path(&self) -> PathBuf {
+        self.path.with_extension(GOLDEN_EXTENSION)
+    }
+
+    /// Analyze the plan and render the result as stored in golden files
+    ///
+    /// Fixtures carry no catalog metadata, so rules that need relation sizes,
+    /// statistics, or indexes do not fire; plans without actual row counts are
+    /// analyzed as estimates.
+    pub fn render(&self, advisor: &QueryAdvisor) -> String {
+        let context = AnalysisContext {
+            estimates_only: self.plan.root.actual_loops == 0,
+            ..AnalysisContext::default()
+        };
+        let analysis = advisor.analyze_plan_with_context(&self.plan, &context);
+
+        let mut out = String::from("shape:\n");
+        for line in snapshot::normalize(&self.plan).render().lines() {
+            let _ = writeln!(out, "  {}", line);
+        }
+        let _ = writeln!(out, "score: {}", analysis.performance_score);
+        if analysis.suggestions.is_empty() {
+            out.push_str("suggestions: none\n");
+            return out;
+        }
+        out.push_str("suggestions:\n");
+        for suggestion in &analysis.suggestions {
+            let node = suggestion
+                .node_index
+                .map_or_else(|| "plan".to_string(), |index| format!("node {}", index));
+            let _ = writeln!(
+                out,
+                "  [{:?}] {} at {}: {}",
+                suggestion.severity, suggestion.rule_id, node, suggestion.title
+            );
+        }
+        out
+    }
+}
+
+/// Parse a plan fixture
+///
+/// Accepts `EXPLAIN (FORMAT JSON)` output, with or without its enclosing
+/// array, and plans as sqltrace stores them, with the plan under `root`.
+pub fn parse_fixture(value: &Value) -> Result<ExecutionPlan, SqlTraceError> {
+    match value {
+        Value::Array(_) => parse_execution_plan(value),
+        Value::Object(fields) if fields.contains_key("Plan") => {
+            parse_execution_plan(&Value::Array(vec![value.clone()]))
+        }
+        _ => Ok(serde_json::from_value(value.clone())?),
+    }
+}
+
+/// Load every plan fixture in `dir`, ordered by name
+pub fn load_dir(dir: &Path) -> Result<Vec<Fixture>, SqlTraceError> {
+    let mut fixtures = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(FIXTURE_EXTENSION) {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let contents = std::fs::read_to_string(&path)?;
+        let plan = serde_json::from_str(&contents)
+            .map_err(SqlTraceError::from)
+            .and_then(|value| parse_fixture(&value))
+            .map_err(|e| SqlTraceError::PlanError(format!("{}: {}", path.display(), e)))?;
+        fixtures.push(Fixture {
+            name: name.to_string(),
+            path,
+            plan,
+        });
+    }
+    fixtures.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(fixtures)
+}
+
+/// Outcome of checking one fixture against its golden file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixtureOutcome {
+    /// Name of the fixture
+    pub name: String,
+    /// How the analysis compared with the golden file
+    pub outcome: SnapshotOutcome,
+}
+
+/// Outcome of checking a fixture directory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FixtureReport {
+    /// Outcome of each fixture, ordered by name
+    pub fixtures: Vec<FixtureOutcome>,
+}
+
+impl FixtureReport {
+    /// Whether every fixture matched its golden file, or had one written
+    pub fn is_match(&self) -> bool {
+        self.fixtures.iter().all(|f| f.outcome.is_match())
+    }
+
+    /// Fixtures whose analysis differs from their golden file
+    pub fn mismatches(&self) -> impl Iterator<Item = &FixtureOutcome> {
+        self.fixtures.iter().filter(|f| !f.outcome.is_match())
+    }
+}
+
+impl fmt::Display for FixtureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.mismatches().count();
+        writeln!(
+            f,
+            "{} of {} plan fixtures match their golden files",
+            self.fixtures.len() - failed,
+            self.fixtures.len()
+        )?;
+        for fixture in self.mismatches() {
+            if let SnapshotOutcome::Mismatch { expected, actual } = &fixture.outcome {
+                write!(
+                    f,
+                    "\n{}\n--- expected\n{}+++ actual\n{}",
+                    fixture.name, expected, actual
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check every plan fixture in `dir` against its golden file
+///
+/// A missing golden file is written and counts as a match. A mismatching one
+/// is rewritten when `update` is set or [`UPDATE_ENV`] is `1`.
+pub fn check_dir(
+    dir: &Path,
+    advisor: &QueryAdvisor,
+    update: bool,
+) -> Result<FixtureReport, SqlTraceError> {
+    let update = update || std::env::var(UPDATE_ENV).is_ok_and(|v| v == "1");
+    let mut report = FixtureReport::default();
+    for fixture in load_dir(dir)? {
+        let actual = fixture.render(advisor);
+        let golden = fixture.golden_path();
+        let outcome = match std::fs::read_to_string(&golden) {
+            Ok(expected) if expected == actual => SnapshotOutcome::Matched,
+            Ok(_) if update => {
+                std::fs::write(&golden, &actual)?;
+                SnapshotOutcome::Updated
+            }
+            Ok(expected) => SnapshotOutcome::Mismatch { expected, actual },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                std::fs::write(&golden, &actual)?;
+                SnapshotOutcome::Created
+            }
+            Err(e) => return Err(e.into()),
+        };
+        report.fixtures.push(FixtureOutcome {
+            name: fixture.name,
+            outcome,
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_check_dir() {
+        let dir = std::env::temp_dir().join(format!("sqltrace-fixtures-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let explain = json!([{
+            "Plan": {
+                "Node Type": "Sort",
+                "Startup Cost": 0.0, "Total Cost": 900.0,
+                "Plan Rows": 50000, "Plan Width": 8,
+                "Actual Startup Time": 0.1, "Actual Total Time": 40.0,
+                "Actual Rows": 50000, "Actual Loops": 1,
+                "Sort Method": "external merge",
+                "Sort Space Used": 2048,
+                "Sort Space Type": "Disk",
+                "Plans": [{
+                    "Node Type": "Seq Scan",
+                    "Relation Name": "orders",
+                    "Startup Cost": 0.0, "Total Cost": 500.0,
+                    "Plan Rows": 50000, "Plan Width": 8,
+                    "Actual Startup Time": 0.1, "Actual Total Time": 20.0,
+                    "Actual Rows": 50000, "Actual Loops": 1
+                }]
+            },
+            "Planning Time": 0.1,
+            "Execution Time": 40.0
+        }]);
+        std::fs::write(dir.join("sort_spill.json"), explain.to_string()).unwrap();
+        let stored = json!({
+            "root": {
+                "Node Type": "Index Scan",
+                "Relation Name": "users",
+                "Index Name": "users_pkey",
+                "Startup Cost": 0.0, "Total Cost": 8.0,
+                "Plan Rows": 1, "Plan Width": 8
+            },
+            "planning_time": 0.1,
+            "execution_time": 0.0
+        });
+        std::fs::write(dir.join("by_key.json"), stored.to_string()).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a fixture").unwrap();
+
+        let advisor = QueryAdvisor::new();
+        let report = check_dir(&dir, &advisor, false).unwrap();
+        let names: Vec<_> = report.fixtures.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["by_key", "sort_spill"]);
+        assert!(report
+            .fixtures
+            .iter()
+            .all(|f| f.outcome == SnapshotOutcome::Created));
+
+        let golden = std::fs::read_to_string(dir.join("sort_spill.golden")).unwrap();
+        assert!(golden.contains("  Sort\n    Seq Scan on orders\n"));
+        assert!(golden.contains("sort_spill at node 0"));
+        let golden = std::fs::read_to_string(dir.join("by_key.golden")).unwrap();
+        assert!(golden.contains("Index Scan on users using users_pkey"));
+        assert!(golden.ends_with("suggestions: none\n"));
+
+        assert!(
+            check_dir(&dir, &advisor, false).unwrap().fixtures[1].outcome
+                == SnapshotOutcome::Matched
+        );
+
+        // An advisor that no longer reports the spill fails the check
+        let mut config = advisor.config().clone();
+        config.disabled_rules.insert("sort_spill".to_string());
+        let changed = QueryAdvisor::with_config(config);
+        let report = check_dir(&dir, &changed, false).unwrap();
+        assert!(!report.is_match());
+        assert_eq!(report.mismatches().count(), 1);
+        assert!(report.to_string().contains("1 of 2 plan fixtures"));
+        assert_eq!(
+            check_dir(&dir, &changed, true).unwrap().fixtures[1].outcome,
+            SnapshotOutcome::Updated
+        );
+        assert!(check_dir(&dir, &changed, false).unwrap().is_match());
+
+        std::fs::write(dir.join("broken.json"), "{\"root\": 1}").unwrap();
+        assert!(check_dir(&dir, &advisor, false)
+            .unwrap_err()
+            .to_string()
+            .contains("broken.json"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
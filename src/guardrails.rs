@@ -0,0 +1,259 @@
+//! Query plan guardrails for CI
+//!
+//! A guardrails file is checked in next to the application and names the
+//! queries whose plans matter, together with what must hold for each plan:
+//! an index that has to be used, tables that must never be read with a
+//! sequential scan, a ceiling on the estimated cost. `sqltrace-rs ci`
+//! explains every query against a live database and fails when any
+//! assertion is violated, so a dropped index or a planner regression is
+//! caught before it reaches production.
+//!
+//! ```toml
+//! [[query]]
+//! name = "orders_by_user"
+//! sql = "SELECT * FROM orders WHERE user_id = 42"
+//! must_use_index = ["idx_orders_user_id"]
+//! no_seq_scan = ["orders"]
+//! max_cost = 5000.0
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::db::models::ExecutionPlan;
+use crate::{Database, SqlTraceError};
+
+/// Assertions about the plan of one named query
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryGuardrail {
+    /// Name reported with violations
+    pub name: String,
+    /// The SQL to explain
+    pub sql: String,
+    /// Indexes the plan must use
+    pub must_use_index: Vec<String>,
+    /// Relations that must not be read with a sequential scan; `*` means any relation
+    pub no_seq_scan: Vec<String>,
+    /// Node types that must not appear anywhere in the plan, e.g. "Nested Loop"
+    pub forbid_nodes: Vec<String>,
+    /// Highest estimated total cost of the root node
+    pub max_cost: Option<f64>,
+    /// Highest execution time in milliseconds
+    pub max_execution_ms: Option<f64>,
+}
+
+/// Contents of a guardrails file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuardrailsFile {
+    /// The guarded queries, in the order they are checked
+    #[serde(rename = "query")]
+    pub queries: Vec<QueryGuardrail>,
+}
+
+impl GuardrailsFile {
+    /// Read and parse a guardrails file
+    pub fn load(path: &Path) -> Result<Self, SqlTraceError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            SqlTraceError::Config(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        Self::parse(&contents)
+            .map_err(|e| SqlTraceError::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Parse the contents of a guardrails file
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let file: Self = toml::from_str(contents).map_err(|e| e.to_string())?;
+        for (i, query) in file.queries.iter().enumerate() {
+            if query.name.trim().is_empty() {
+                return Err(format!("query #{} has no name", i + 1));
+            }
+            if query.sql.trim().is_empty() {
+                return Err(format!("query '{}' has no sql", query.name));
+            }
+        }
+        Ok(file)
+    }
+}
+
+/// Outcome of checking one guarded query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardrailResult {
+    /// Name of the query
+    pub name: String,
+    /// Assertions the plan violated
+    pub violations: Vec<String>,
+    /// Why the query could not be explained, if it could not
+    pub error: Option<String>,
+}
+
+impl GuardrailResult {
+    /// Whether the query was explained and every assertion held
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty() && self.error.is_none()
+    }
+}
+
+/// Check a plan against the assertions of one query
+pub fn evaluate(guardrail: &QueryGuardrail, plan: &ExecutionPlan) -> Vec<String> {
+    let mut violations = Vec::new();
+
+    for index in &guardrail.must_use_index {
+        let used = plan.root.iter().any(|node| {
+            node.extra_str("Index Name")
+                .is_some_and(|name| name.eq_ignore_ascii_case(index))
+        });
+        if !used {
+            violations.push(format!("index {} is not used", index));
+        }
+    }
+
+    for node in plan.root.iter().filter(|node| node.node_type == "Seq Scan") {
+        let relation = node.relation_name.as_deref().unwrap_or("?");
+        let forbidden = guardrail
+            .no_seq_scan
+            .iter()
+            .any(|r| r == "*" || r.eq_ignore_ascii_case(relation));
+        if forbidden {
+            violations.push(format!("Seq Scan on {}", relation));
+        }
+    }
+
+    for node_type in &guardrail.forbid_nodes {
+        if plan
+            .root
+            .iter()
+            .any(|node| node.node_type.eq_ignore_ascii_case(node_type))
+        {
+            violations.push(format!("plan contains a {} node", node_type));
+        }
+    }
+
+    if let Some(max_cost) = guardrail.max_cost {
+        if plan.root.total_cost > max_cost {
+            violations.push(format!(
+                "estimated cost {:.2} exceeds {:.2}",
+                plan.root.total_cost, max_cost
+            ));
+        }
+    }
+
+    if let Some(max_ms) = guardrail.max_execution_ms {
+        if plan.execution_time > max_ms {
+            violations.push(format!(
+                "execution time {:.2}ms exceeds {:.2}ms",
+                plan.execution_time, max_ms
+            ));
+        }
+    }
+
+    violations
+}
+
+/// Explain every guarded query and check its plan
+pub async fn check(db: &Database, file: &GuardrailsFile) -> Vec<GuardrailResult> {
+    let mut results = Vec::with_capacity(file.queries.len());
+    for guardrail in &file.queries {
+        let (violations, error) = match db.explain(&guardrail.sql).await {
+            Ok(plan) => (evaluate(guardrail, &plan), None),
+            Err(e) => (Vec::new(), Some(e.to_string())),
+        };
+        results.push(GuardrailResult {
+            name: guardrail.name.clone(),
+            violations,
+            error,
+        });
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::PlanNode;
+    use serde_json::json;
+
+    fn plan() -> ExecutionPlan {
+        ExecutionPlan {
+            root: PlanNode {
+                node_type: "Nested Loop".to_string(),
+                total_cost: 6200.0,
+                plans: vec![
+                    PlanNode {
+                        node_type: "Seq Scan".to_string(),
+                        relation_name: Some("users".to_string()),
+                        ..PlanNode::default()
+                    },
+                    PlanNode {
+                        node_type: "Index Scan".to_string(),
+                        relation_name: Some("orders".to_string()),
+                        extra: json!({"Index Name": "idx_orders_user_id"}),
+                        ..PlanNode::default()
+                    },
+                ],
+                ..PlanNode::default()
+            },
+            planning_time: 0.2,
+            execution_time: 12.5,
+            settings: None,
+            jit: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_guardrails_file() {
+        let file = GuardrailsFile::parse(
+            r#"
+            [[query]]
+            name = "orders_by_user"
+            sql = "SELECT * FROM orders WHERE user_id = 42"
+            must_use_index = ["idx_orders_user_id"]
+            no_seq_scan = ["orders"]
+            max_cost = 5000.0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.queries.len(), 1);
+        assert_eq!(file.queries[0].must_use_index, ["idx_orders_user_id"]);
+        assert_eq!(file.queries[0].max_cost, Some(5000.0));
+
+        let err = GuardrailsFile::parse("[[query]]\nname = \"x\"\n").unwrap_err();
+        assert!(err.contains("no sql"));
+    }
+
+    #[test]
+    fn test_evaluate_passing_plan() {
+        let guardrail = QueryGuardrail {
+            name: "orders_by_user".to_string(),
+            must_use_index: vec!["idx_orders_user_id".to_string()],
+            no_seq_scan: vec!["orders".to_string()],
+            max_cost: Some(10_000.0),
+            ..QueryGuardrail::default()
+        };
+        assert!(evaluate(&guardrail, &plan()).is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_violations() {
+        let guardrail = QueryGuardrail {
+            name: "orders_by_user".to_string(),
+            must_use_index: vec!["idx_orders_created_at".to_string()],
+            no_seq_scan: vec!["*".to_string()],
+            forbid_nodes: vec!["nested loop".to_string()],
+            max_cost: Some(5000.0),
+            max_execution_ms: Some(10.0),
+            ..QueryGuardrail::default()
+        };
+        assert_eq!(
+            evaluate(&guardrail, &plan()),
+            [
+                "index idx_orders_created_at is not used",
+                "Seq Scan on users",
+                "plan contains a nested loop node",
+                "estimated cost 6200.00 exceeds 5000.00",
+                "execution time 12.50ms exceeds 10.00ms",
+            ]
+        );
+    }
+}
@@ -10,10 +10,15 @@
 //! - PostgreSQL execution plan parsing and analysis
 //! - MySQL execution plan parsing and analysis
 //! - SQLite execution plan parsing and analysis
+//! - DuckDB execution profile parsing and analysis
+//! - Amazon Redshift plan parsing and analysis
+//! - TiDB execution plan parsing and analysis
 //! - Interactive web-based visualization
 //! - REST API for programmatic access
 //! - Performance metrics and optimization insights
 //! - Rule-based optimization advisor
+//! - Plan guardrails and plan snapshots for CI and test suites
+//! - Plan fixtures with golden advisor output for regression tests
 //!
 //! # Example
 //!
@@ -33,11 +38,26 @@
 
 pub mod advisor;
 pub mod benchmark;
+pub mod budget;
+pub mod capture;
+pub mod config;
 pub mod db;
 pub mod error;
+pub mod fixtures;
+pub mod guardrails;
+pub mod masking;
+pub mod msgpack;
+pub mod policy;
+pub mod preflight;
+pub mod queue;
 pub mod server;
+pub mod settings;
+pub mod snapshot;
+pub mod storage;
+pub mod telemetry;
 pub mod ui;
 pub mod web;
+pub mod workload;
 
 /// Re-export common types for easier use in tests and examples
 pub use db::Database;
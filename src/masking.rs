@@ -0,0 +1,280 @@
+//! Masking of literal values in analyzed queries
+//!
+//! Queries often embed customer data (email addresses, account ids) as
+//! literals. With masking enabled, SQLTrace replaces string and numeric
+//! literals before a query or plan reaches its own storage or logs, so the
+//! tool does not become a second copy of that data. Literals are found with
+//! the SQL tokenizer rather than pattern matching, so identifiers, comments,
+//! and formatting are left untouched.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::tokenizer::{Token, Tokenizer};
+
+/// Replacement for string literals
+pub const MASKED_STRING: &str = "'?'";
+
+/// Replacement for numeric literals
+pub const MASKED_NUMBER: &str = "?";
+
+/// Stored in place of text that could not be tokenized, since its literals cannot be located
+pub const UNMASKABLE: &str = "<masked: text could not be tokenized>";
+
+/// Plan fields holding expressions that may contain literals
+const PLAN_EXPRESSION_KEYS: [&str; 13] = [
+    "Filter",
+    "Index Cond",
+    "Recheck Cond",
+    "Join Filter",
+    "Hash Cond",
+    "Merge Cond",
+    "One-Time Filter",
+    "TID Cond",
+    "Output",
+    "Sort Key",
+    "Group Key",
+    "Cache Key",
+    "Remote SQL",
+];
+
+/// Masking configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaskingConfig {
+    /// Mask literals in stored history and logs
+    pub mask_literals: bool,
+}
+
+impl MaskingConfig {
+    /// Mask `sql` if masking is enabled, otherwise return it unchanged
+    pub fn query(&self, sql: &str) -> String {
+        if self.mask_literals {
+            mask_literals(sql)
+        } else {
+            sql.to_string()
+        }
+    }
+
+    /// Mask the expressions of a serialized plan in place if masking is enabled
+    pub fn plan(&self, plan: &mut Value) {
+        if self.mask_literals {
+            mask_plan(plan);
+        }
+    }
+
+    /// Mask the plan expressions quoted in a serialized advisor analysis in place if masking is enabled
+    ///
+    /// `plan` is the serialized plan the analysis was made from, before masking.
+    pub fn analysis(&self, plan: &Value, analysis: &mut Value) {
+        if self.mask_literals {
+            mask_analysis(plan, analysis);
+        }
+    }
+}
+
+/// Replace every string and numeric literal in `sql`
+pub fn mask_literals(sql: &str) -> String {
+    let dialect = PostgreSqlDialect {};
+    let Ok(tokens) = Tokenizer::new(&dialect, sql).tokenize() else {
+        return UNMASKABLE.to_string();
+    };
+
+    let mut masked = String::with_capacity(sql.len());
+    for token in tokens {
+        match token {
+            Token::SingleQuotedString(_)
+            | Token::DollarQuotedString(_)
+            | Token::NationalStringLiteral(_)
+            | Token::EscapedStringLiteral(_)
+            | Token::HexStringLiteral(_)
+            | Token::SingleQuotedByteStringLiteral(_)
+            | Token::DoubleQuotedByteStringLiteral(_)
+            | Token::RawStringLiteral(_) => masked.push_str(MASKED_STRING),
+            Token::Number(..) => masked.push_str(MASKED_NUMBER),
+            other => masked.push_str(&other.to_string()),
+        }
+    }
+    masked
+}
+
+/// Mask literals in the expression fields of a serialized plan, recursively
+pub fn mask_plan(plan: &mut Value) {
+    match plan {
+        Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                if PLAN_EXPRESSION_KEYS.contains(&key.as_str()) {
+                    mask_expressions(value);
+                } else {
+                    mask_plan(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(mask_plan),
+        _ => {}
+    }
+}
+
+/// Replace the expressions of `plan` wherever a serialized analysis quotes them
+///
+/// Suggestions quote filters, conditions, and remote SQL verbatim, next to row
+/// counts and costs that are not customer data. Masking only the quoted
+/// expressions keeps the rest of the text readable.
+pub fn mask_analysis(plan: &Value, analysis: &mut Value) {
+    let mut expressions = Vec::new();
+    collect_expressions(plan, &mut expressions);
+    let mut replacements: Vec<(String, String)> = expressions
+        .into_iter()
+        .map(|expression| {
+            let masked = mask_literals(&expression);
+            (expression, masked)
+        })
+        .filter(|(expression, masked)| expression != masked)
+        .collect();
+    // Longer expressions first, so one containing another is replaced whole
+    replacements.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+    replacements.dedup();
+    replace_quoted(analysis, &replacements);
+}
+
+fn collect_expressions(plan: &Value, expressions: &mut Vec<String>) {
+    match plan {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                if PLAN_EXPRESSION_KEYS.contains(&key.as_str()) {
+                    match value {
+                        Value::String(expression) => expressions.push(expression.clone()),
+                        Value::Array(items) => expressions.extend(
+                            items
+                                .iter()
+                                .filter_map(|item| item.as_str().map(str::to_string)),
+                        ),
+                        _ => {}
+                    }
+                } else {
+                    collect_expressions(value, expressions);
+                }
+            }
+        }
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| collect_expressions(item, expressions)),
+        _ => {}
+    }
+}
+
+fn replace_quoted(value: &mut Value, replacements: &[(String, String)]) {
+    match value {
+        Value::String(text) => {
+            for (expression, masked) in replacements {
+                if text.contains(expression.as_str()) {
+                    *text = text.replace(expression.as_str(), masked);
+                }
+            }
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| replace_quoted(item, replacements)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|item| replace_quoted(item, replacements)),
+        _ => {}
+    }
+}
+
+fn mask_expressions(value: &mut Value) {
+    match value {
+        Value::String(expression) => *expression = mask_literals(expression),
+        Value::Array(items) => items.iter_mut().for_each(mask_expressions),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_mask_literals() {
+        assert_eq!(
+            mask_literals("SELECT * FROM users WHERE email = 'bob@example.com' AND id = 42"),
+            "SELECT * FROM users WHERE email = '?' AND id = ?"
+        );
+        // Identifiers, comments, and layout are preserved
+        assert_eq!(
+            mask_literals("SELECT t1.\"col2\" -- note 7\nFROM t1 LIMIT 10"),
+            "SELECT t1.\"col2\" -- note 7\nFROM t1 LIMIT ?"
+        );
+        assert_eq!(
+            mask_literals("SELECT $$secret$$, E'a\\'b', 1.5e3"),
+            "SELECT '?', '?', ?"
+        );
+        assert_eq!(mask_literals("SELECT 'unterminated"), UNMASKABLE);
+    }
+
+    #[test]
+    fn test_mask_plan_expressions() {
+        let mut plan = json!({
+            "Node Type": "Index Scan",
+            "Index Name": "users_2024_email_idx",
+            "Index Cond": "(email = 'bob@example.com'::text)",
+            "Plans": [
+                {"Node Type": "Seq Scan", "Filter": "(id = 42)", "Output": ["id", "'x'::text"]},
+                {"Node Type": "Foreign Scan", "Remote SQL": "SELECT id FROM public.orders WHERE ((status = 'open'::text))"}
+            ]
+        });
+
+        MaskingConfig {
+            mask_literals: true,
+        }
+        .plan(&mut plan);
+
+        assert_eq!(plan["Index Name"], "users_2024_email_idx");
+        assert_eq!(plan["Index Cond"], "(email = '?'::text)");
+        assert_eq!(plan["Plans"][0]["Filter"], "(id = ?)");
+        assert_eq!(plan["Plans"][0]["Output"][1], "'?'::text");
+        assert_eq!(
+            plan["Plans"][1]["Remote SQL"],
+            "SELECT id FROM public.orders WHERE ((status = '?'::text))"
+        );
+
+        assert_eq!(MaskingConfig::default().query("SELECT 1"), "SELECT 1");
+    }
+
+    #[test]
+    fn test_mask_analysis_quotes() {
+        let plan = json!({
+            "Node Type": "Foreign Scan",
+            "Filter": "(email = 'bob@example.com'::text)",
+            "Remote SQL": "SELECT id FROM public.users WHERE ((id = 42))",
+            "Plans": [{"Node Type": "Aggregate", "Group Key": ["region", "'x'::text"]}]
+        });
+        let mut analysis = json!({
+            "suggestions": [{
+                "description": "Fetched 90000 rows and discarded them with Filter: (email = 'bob@example.com'::text). The remote server was sent: SELECT id FROM public.users WHERE ((id = 42))",
+                "recommendation": "Add an index on (region, 'x'::text)."
+            }],
+            "performance_score": 40
+        });
+
+        MaskingConfig::default().analysis(&plan, &mut analysis);
+        assert!(analysis.to_string().contains("bob@example.com"));
+
+        MaskingConfig {
+            mask_literals: true,
+        }
+        .analysis(&plan, &mut analysis);
+        let suggestion = &analysis["suggestions"][0];
+        // Counts outside the quoted expressions are kept
+        assert_eq!(
+            suggestion["description"],
+            "Fetched 90000 rows and discarded them with Filter: (email = '?'::text). The remote server was sent: SELECT id FROM public.users WHERE ((id = ?))"
+        );
+        assert_eq!(
+            suggestion["recommendation"],
+            "Add an index on (region, '?'::text)."
+        );
+        assert_eq!(analysis["performance_score"], 40);
+    }
+}
@@ -0,0 +1,303 @@
+//! MessagePack encoding of JSON values
+//!
+//! Plans are stored and sent as JSON, which spends many of its bytes on
+//! quoting, punctuation and numbers written out in decimal. MessagePack keeps
+//! the same structure in a binary form, somewhat smaller than compact JSON
+//! and about half the size of pretty-printed JSON for large plans, and every
+//! value decodes back to the JSON it came from. Only the types JSON has are
+//! written or read: nil, booleans, integers, floats, strings, arrays, and
+//! maps with string keys.
+
+use serde::de::Error as _;
+use serde_json::{Map, Number, Value};
+
+/// Media type of MessagePack responses
+pub const CONTENT_TYPE: &str = "application/msgpack";
+
+/// Encode `value` as MessagePack
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_value(&mut out, value);
+    out
+}
+
+/// Decode a MessagePack document back into JSON
+pub fn decode(bytes: &[u8]) -> serde_json::Result<Value> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let value = reader.value()?;
+    if reader.pos != bytes.len() {
+        return Err(serde_json::Error::custom(
+            "trailing bytes after MessagePack value",
+        ));
+    }
+    Ok(value)
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(0xc0),
+        Value::Bool(false) => out.push(0xc2),
+        Value::Bool(true) => out.push(0xc3),
+        Value::Number(number) => write_number(out, number),
+        Value::String(s) => write_str(out, s),
+        Value::Array(items) => {
+            write_len(out, items.len(), 0x90, 16, 0xdc, 0xdd);
+            for item in items {
+                write_value(out, item);
+            }
+        }
+        Value::Object(map) => {
+            write_len(out, map.len(), 0x80, 16, 0xde, 0xdf);
+            for (key, child) in map {
+                write_str(out, key);
+                write_value(out, child);
+            }
+        }
+    }
+}
+
+fn write_number(out: &mut Vec<u8>, number: &Number) {
+    if let Some(n) = number.as_u64() {
+        match n {
+            0..=0x7f => out.push(n as u8),
+            0x80..=0xff => out.extend([0xcc, n as u8]),
+            0x100..=0xffff => {
+                out.push(0xcd);
+                out.extend((n as u16).to_be_bytes());
+            }
+            0x1_0000..=0xffff_ffff => {
+                out.push(0xce);
+                out.extend((n as u32).to_be_bytes());
+            }
+            _ => {
+                out.push(0xcf);
+                out.extend(n.to_be_bytes());
+            }
+        }
+    } else if let Some(n) = number.as_i64() {
+        // Only negative numbers are left here
+        match n {
+            -32..=-1 => out.push(n as i8 as u8),
+            -0x80..=-33 => out.extend([0xd0, n as i8 as u8]),
+            -0x8000..=-0x81 => {
+                out.push(0xd1);
+                out.extend((n as i16).to_be_bytes());
+            }
+            -0x8000_0000..=-0x8001 => {
+                out.push(0xd2);
+                out.extend((n as i32).to_be_bytes());
+            }
+            _ => {
+                out.push(0xd3);
+                out.extend(n.to_be_bytes());
+            }
+        }
+    } else {
+        let n = number.as_f64().unwrap_or_default();
+        let narrow = n as f32;
+        if f64::from(narrow) == n {
+            out.push(0xca);
+            out.extend(narrow.to_be_bytes());
+        } else {
+            out.push(0xcb);
+            out.extend(n.to_be_bytes());
+        }
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    let len = s.len();
+    match len {
+        0..=31 => out.push(0xa0 | len as u8),
+        32..=0xff => out.extend([0xd9, len as u8]),
+        0x100..=0xffff => {
+            out.push(0xda);
+            out.extend((len as u16).to_be_bytes());
+        }
+        _ => {
+            out.push(0xdb);
+            out.extend((len as u32).to_be_bytes());
+        }
+    }
+    out.extend(s.as_bytes());
+}
+
+/// Write the header of an array or map of `len` elements
+fn write_len(out: &mut Vec<u8>, len: usize, fix: u8, fix_max: usize, len16: u8, len32: u8) {
+    if len < fix_max {
+        out.push(fix | len as u8);
+    } else if len <= 0xffff {
+        out.push(len16);
+        out.extend((len as u16).to_be_bytes());
+    } else {
+        out.push(len32);
+        out.extend((len as u32).to_be_bytes());
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> serde_json::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| serde_json::Error::custom("truncated MessagePack value"))?;
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> serde_json::Result<[u8; N]> {
+        let mut buf = [0; N];
+        buf.copy_from_slice(self.take(N)?);
+        Ok(buf)
+    }
+
+    fn len(&mut self, width: usize) -> serde_json::Result<usize> {
+        Ok(match width {
+            1 => self.array::<1>()?[0] as usize,
+            2 => u16::from_be_bytes(self.array()?) as usize,
+            _ => u32::from_be_bytes(self.array()?) as usize,
+        })
+    }
+
+    fn value(&mut self) -> serde_json::Result<Value> {
+        let marker = self.array::<1>()?[0];
+        Ok(match marker {
+            0x00..=0x7f => Value::from(marker),
+            0x80..=0x8f => self.map((marker & 0x0f) as usize)?,
+            0x90..=0x9f => self.items((marker & 0x0f) as usize)?,
+            0xa0..=0xbf => self.string((marker & 0x1f) as usize)?,
+            0xc0 => Value::Null,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xca => float(f64::from(f32::from_be_bytes(self.array()?)))?,
+            0xcb => float(f64::from_be_bytes(self.array()?))?,
+            0xcc => Value::from(self.array::<1>()?[0]),
+            0xcd => Value::from(u16::from_be_bytes(self.array()?)),
+            0xce => Value::from(u32::from_be_bytes(self.array()?)),
+            0xcf => Value::from(u64::from_be_bytes(self.array()?)),
+            0xd0 => Value::from(self.array::<1>()?[0] as i8),
+            0xd1 => Value::from(i16::from_be_bytes(self.array()?)),
+            0xd2 => Value::from(i32::from_be_bytes(self.array()?)),
+            0xd3 => Value::from(i64::from_be_bytes(self.array()?)),
+            0xd9 => {
+                let len = self.len(1)?;
+                self.string(len)?
+            }
+            0xda => {
+                let len = self.len(2)?;
+                self.string(len)?
+            }
+            0xdb => {
+                let len = self.len(4)?;
+                self.string(len)?
+            }
+            0xdc => {
+                let len = self.len(2)?;
+                self.items(len)?
+            }
+            0xdd => {
+                let len = self.len(4)?;
+                self.items(len)?
+            }
+            0xde => {
+                let len = self.len(2)?;
+                self.map(len)?
+            }
+            0xdf => {
+                let len = self.len(4)?;
+                self.map(len)?
+            }
+            0xe0..=0xff => Value::from(marker as i8),
+            _ => {
+                return Err(serde_json::Error::custom(format!(
+                    "unsupported MessagePack type 0x{:02x}",
+                    marker
+                )))
+            }
+        })
+    }
+
+    fn string(&mut self, len: usize) -> serde_json::Result<Value> {
+        let bytes = self.take(len)?;
+        std::str::from_utf8(bytes)
+            .map(|s| Value::String(s.to_string()))
+            .map_err(serde_json::Error::custom)
+    }
+
+    fn items(&mut self, len: usize) -> serde_json::Result<Value> {
+        // Every element takes at least one byte, so a bogus length fails before allocating
+        let mut items = Vec::with_capacity(len.min(self.bytes.len() - self.pos));
+        for _ in 0..len {
+            items.push(self.value()?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, len: usize) -> serde_json::Result<Value> {
+        let mut map = Map::new();
+        for _ in 0..len {
+            let Value::String(key) = self.value()? else {
+                return Err(serde_json::Error::custom(
+                    "MessagePack map key is not a string",
+                ));
+            };
+            let child = self.value()?;
+            map.insert(key, child);
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+fn float(n: f64) -> serde_json::Result<Value> {
+    Number::from_f64(n)
+        .map(Value::Number)
+        .ok_or_else(|| serde_json::Error::custom("MessagePack float is not a JSON number"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trip() {
+        let plan = json!({
+            "root": {
+                "Node Type": "Hash Join",
+                "Total Cost": 1250.75,
+                "Startup Cost": 0.5,
+                "Plan Rows": 70000,
+                "Plan Width": -1,
+                "Offset": -200,
+                "Big": 5_000_000_000u64,
+                "Small Negative": -3_000_000_000i64,
+                "Parallel Aware": false,
+                "Filter": null,
+                "Output": ["a.id", "b.name", "x".repeat(40), "y".repeat(300), "z".repeat(70_000)],
+                "Plans": (0..20).map(|i| json!({"Node Type": "Seq Scan", "i": i})).collect::<Vec<_>>()
+            },
+            "planning_time": 0.1,
+            "execution_time": 12.0
+        });
+
+        let bytes = encode(&plan);
+        assert_eq!(decode(&bytes).unwrap(), plan);
+        assert!(bytes.len() < serde_json::to_vec_pretty(&plan).unwrap().len());
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_input() {
+        assert!(decode(&[]).is_err());
+        assert!(decode(&[0xdd, 0xff, 0xff, 0xff, 0xff]).is_err());
+        assert!(decode(&[0x81, 0x01, 0x02]).is_err());
+        assert!(decode(&[0xc0, 0xc0]).is_err());
+        assert!(decode(&[0xc1]).is_err());
+    }
+}
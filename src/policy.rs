@@ -0,0 +1,305 @@
+//! Per-connection query policies
+//!
+//! A policy restricts what may be run against a connection: which statement
+//! types, which schemas and tables, and how expensive the planner may expect
+//! a query to be. Policies are checked before a query is executed, so a
+//! production replica can be exposed to a wide audience without letting
+//! anyone read sensitive relations or start a runaway scan.
+
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::Statement;
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::parser::Parser;
+use thiserror::Error;
+
+use crate::db::estimate::{PlanEstimate, PlannedRelation};
+
+/// Why a query was rejected by a policy
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Query rejected by policy: {0}")]
+pub struct PolicyViolation(pub String);
+
+/// Kinds of SQL statements a policy can allow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementKind {
+    /// SELECT, WITH, VALUES and other read-only queries
+    Select,
+    /// INSERT
+    Insert,
+    /// UPDATE
+    Update,
+    /// DELETE
+    Delete,
+    /// Anything else (DDL, utility statements, ...)
+    Other,
+}
+
+impl StatementKind {
+    fn of(statement: &Statement) -> Self {
+        match statement {
+            Statement::Query(_) => StatementKind::Select,
+            Statement::Insert { .. } => StatementKind::Insert,
+            Statement::Update { .. } => StatementKind::Update,
+            Statement::Delete { .. } => StatementKind::Delete,
+            _ => StatementKind::Other,
+        }
+    }
+}
+
+/// Restrictions applied to queries on one connection
+///
+/// Empty lists and `None` leave that aspect unrestricted. Relation patterns
+/// are `schema.table` or a bare `table` (any schema), where `*` matches any
+/// sequence of characters, e.g. `pii.*` or `*.audit_*`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryPolicy {
+    /// Statement types that may be run
+    pub allowed_statements: Vec<StatementKind>,
+    /// Schemas every accessed relation must belong to
+    pub allowed_schemas: Vec<String>,
+    /// Patterns every accessed relation must match
+    pub allowed_tables: Vec<String>,
+    /// Patterns no accessed relation may match; takes precedence over the allow lists
+    pub denied_relations: Vec<String>,
+    /// Highest planner cost estimate a query may have
+    pub max_estimated_cost: Option<f64>,
+}
+
+impl QueryPolicy {
+    /// Whether checking this policy requires planning the query first
+    pub fn needs_estimate(&self) -> bool {
+        self.restricts_relations() || self.max_estimated_cost.is_some()
+    }
+
+    fn restricts_relations(&self) -> bool {
+        !self.allowed_schemas.is_empty()
+            || !self.allowed_tables.is_empty()
+            || !self.denied_relations.is_empty()
+    }
+
+    /// Check the statement types in `sql`
+    pub fn check_statement(&self, sql: &str) -> Result<(), PolicyViolation> {
+        if self.allowed_statements.is_empty() {
+            return Ok(());
+        }
+
+        let statements = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+            .map_err(|e| PolicyViolation(format!("query could not be parsed: {}", e)))?;
+
+        for statement in &statements {
+            let kind = StatementKind::of(statement);
+            if !self.allowed_statements.contains(&kind) {
+                return Err(PolicyViolation(format!(
+                    "{:?} statements are not allowed on this connection",
+                    kind
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the relations and cost the planner expects for a query
+    pub fn check_estimate(&self, estimate: &PlanEstimate) -> Result<(), PolicyViolation> {
+        if self.restricts_relations() {
+            for relation in &estimate.relations {
+                self.check_relation(relation)?;
+            }
+        }
+
+        if let Some(max_cost) = self.max_estimated_cost {
+            if estimate.total_cost > max_cost {
+                return Err(PolicyViolation(format!(
+                    "estimated cost {:.2} exceeds the limit of {:.2}",
+                    estimate.total_cost, max_cost
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_relation(&self, relation: &PlannedRelation) -> Result<(), PolicyViolation> {
+        // Without a schema a relation cannot be matched reliably, so refuse it
+        let schema = relation.schema.as_deref().ok_or_else(|| {
+            PolicyViolation(format!(
+                "could not determine the schema of relation {}",
+                relation.name
+            ))
+        })?;
+        let qualified = format!("{}.{}", schema, relation.name);
+
+        if let Some(pattern) = self
+            .denied_relations
+            .iter()
+            .find(|p| relation_matches(p, schema, &relation.name))
+        {
+            return Err(PolicyViolation(format!(
+                "relation {} is denied by pattern {}",
+                qualified, pattern
+            )));
+        }
+
+        if !self.allowed_schemas.is_empty()
+            && !self.allowed_schemas.iter().any(|p| glob_matches(p, schema))
+        {
+            return Err(PolicyViolation(format!(
+                "schema {} is not allowed (relation {})",
+                schema, qualified
+            )));
+        }
+
+        if !self.allowed_tables.is_empty()
+            && !self
+                .allowed_tables
+                .iter()
+                .any(|p| relation_matches(p, schema, &relation.name))
+        {
+            return Err(PolicyViolation(format!(
+                "relation {} is not allowed",
+                qualified
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Match a `schema.table` or bare `table` pattern against a relation
+fn relation_matches(pattern: &str, schema: &str, name: &str) -> bool {
+    match pattern.split_once('.') {
+        Some((schema_pattern, name_pattern)) => {
+            glob_matches(schema_pattern, schema) && glob_matches(name_pattern, name)
+        }
+        None => glob_matches(pattern, name),
+    }
+}
+
+/// Match `text` against a pattern where `*` matches any sequence of characters
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard: the whole text must equal the pattern
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relation(schema: &str, name: &str) -> PlannedRelation {
+        PlannedRelation {
+            schema: Some(schema.to_string()),
+            name: name.to_string(),
+        }
+    }
+
+    fn estimate(total_cost: f64, relations: Vec<PlannedRelation>) -> PlanEstimate {
+        PlanEstimate {
+            total_cost,
+            relations,
+        }
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*", "anything"));
+        assert!(glob_matches("orders", "orders"));
+        assert!(!glob_matches("orders", "orders_archive"));
+        assert!(glob_matches("audit_*", "audit_log"));
+        assert!(glob_matches("*_log", "audit_log"));
+        assert!(glob_matches("a*b*c", "a-b-c"));
+        assert!(!glob_matches("a*b*c", "a-c-b"));
+    }
+
+    #[test]
+    fn test_statement_kinds() {
+        let policy = QueryPolicy {
+            allowed_statements: vec![StatementKind::Select],
+            ..QueryPolicy::default()
+        };
+
+        assert!(policy.check_statement("SELECT * FROM orders").is_ok());
+        assert!(policy.check_statement("DELETE FROM orders").is_err());
+        assert!(QueryPolicy::default()
+            .check_statement("DELETE FROM orders")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_relation_rules() {
+        let policy = QueryPolicy {
+            allowed_schemas: vec!["public".to_string(), "pii".to_string()],
+            denied_relations: vec!["pii.*".to_string()],
+            ..QueryPolicy::default()
+        };
+        assert!(policy.needs_estimate());
+
+        assert!(policy
+            .check_estimate(&estimate(10.0, vec![relation("public", "orders")]))
+            .is_ok());
+        // Deny wins over an allowed schema
+        assert!(policy
+            .check_estimate(&estimate(10.0, vec![relation("pii", "customers")]))
+            .is_err());
+        assert!(policy
+            .check_estimate(&estimate(10.0, vec![relation("internal", "jobs")]))
+            .is_err());
+        assert!(policy
+            .check_estimate(&estimate(
+                10.0,
+                vec![PlannedRelation {
+                    schema: None,
+                    name: "orders".to_string(),
+                }]
+            ))
+            .is_err());
+
+        let tables = QueryPolicy {
+            allowed_tables: vec!["orders".to_string(), "reporting.*".to_string()],
+            ..QueryPolicy::default()
+        };
+        assert!(tables
+            .check_estimate(&estimate(
+                1.0,
+                vec![relation("public", "orders"), relation("reporting", "daily")]
+            ))
+            .is_ok());
+        assert!(tables
+            .check_estimate(&estimate(1.0, vec![relation("public", "users")]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_max_estimated_cost() {
+        let policy = QueryPolicy {
+            max_estimated_cost: Some(1000.0),
+            ..QueryPolicy::default()
+        };
+
+        assert!(policy.check_estimate(&estimate(999.0, Vec::new())).is_ok());
+        let err = policy
+            .check_estimate(&estimate(5000.0, Vec::new()))
+            .unwrap_err();
+        assert!(err.to_string().contains("exceeds"));
+        assert!(!QueryPolicy::default().needs_estimate());
+    }
+}
@@ -0,0 +1,416 @@
+//! Preflight checks of the environment the server depends on
+//!
+//! A role without `SELECT` on a table, a missing `pg_stat_statements` or a
+//! wrong `--static-dir` otherwise only shows up when a request fails, long
+//! after startup and far from its cause. The checks below run against the
+//! connected database and the frontend directory, and each one that does
+//! not pass says how to fix it. The server logs them once connected, and
+//! `sqltrace-rs doctor` prints them as a checklist, exiting with status 1 if
+//! any check fails.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::db::engines::EngineType;
+use crate::db::privileges::AccessReport;
+use crate::db::workload::StatementOrder;
+use crate::ui::assets::FrontendConfig;
+use crate::Database;
+
+/// Files the web UI cannot work without, relative to the static directory
+pub const REQUIRED_ASSETS: [&str; 3] = ["index.html", "script.js", "styles.css"];
+
+/// Unreadable relations named in a check before the rest are counted
+const LISTED_RELATIONS: usize = 5;
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    /// Everything the check covers works
+    Pass,
+    /// Some features will not work, the rest are unaffected
+    Warn,
+    /// The server cannot do its job until this is fixed
+    Fail,
+}
+
+/// One line of the checklist
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Check {
+    /// What was checked
+    pub name: String,
+    /// Whether it passed
+    pub status: CheckStatus,
+    /// What was found
+    pub detail: String,
+    /// How to fix it, for checks that did not pass
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &str, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    /// The check for a database that could not be connected to
+    pub fn unreachable(error: impl fmt::Display) -> Self {
+        Self::fail(
+            "connection",
+            error.to_string(),
+            "check the host, port, database name and credentials of --database-url, and that \
+             the server accepts connections from this host (pg_hba.conf on PostgreSQL)",
+        )
+    }
+}
+
+impl fmt::Display for Check {
+    /// `ok    name: detail`, with the fix on the next line for checks that did not pass
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.status {
+            CheckStatus::Pass => "ok",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+        };
+        write!(f, "{:<6}{}: {}", label, self.name, self.detail)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n      fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether no check failed; warnings pass
+pub fn passed(checks: &[Check]) -> bool {
+    checks.iter().all(|check| check.status != CheckStatus::Fail)
+}
+
+/// Run every check against `db` and the frontend directory
+///
+/// When the database does not answer, the checks that need it are skipped.
+pub async fn run(db: &Database, frontend: &FrontendConfig) -> Vec<Check> {
+    let mut checks = Vec::new();
+    match db.version_info().await {
+        Ok(info) => {
+            // PostgreSQL's version() already starts with its name
+            let engine = info.engine_type.to_string();
+            let detail = if info.version.starts_with(&engine) {
+                info.version
+            } else {
+                format!("{} {}", engine, info.version)
+            };
+            checks.push(Check::pass("connection", detail));
+        }
+        Err(e) => {
+            checks.push(Check::unreachable(e));
+            checks.push(static_assets(frontend).await);
+            return checks;
+        }
+    }
+
+    checks.push(explain(db).await);
+    if db.engine_type() == EngineType::PostgreSQL {
+        match db.access_report().await {
+            Ok(report) => {
+                checks.push(relation_access(&report));
+                checks.push(pg_stat_statements(db, &report).await);
+                checks.push(statistics_access(&report));
+            }
+            Err(e) => checks.push(Check::warn(
+                "privileges",
+                format!("could not read the privileges of the role: {}", e),
+                "grant the role access to pg_catalog, which every role has unless it was revoked",
+            )),
+        }
+    }
+    checks.push(static_assets(frontend).await);
+    checks
+}
+
+/// Whether the database plans a trivial query
+async fn explain(db: &Database) -> Check {
+    // SQLite has no plan steps for a query without a table
+    let query = match db.engine_type() {
+        EngineType::SQLite => "SELECT * FROM sqlite_master",
+        _ => "SELECT 1",
+    };
+    match db.explain(query).await {
+        Ok(_) => Check::pass("explain", "EXPLAIN runs"),
+        Err(e) => Check::fail(
+            "explain",
+            format!("EXPLAIN {} failed: {}", query, e),
+            "connect as a role that may run queries in this database, and check that the \
+             connection is not restricted to a replication or read-only pooler mode",
+        ),
+    }
+}
+
+/// Whether the role can read the relations queries will be explained against
+fn relation_access(report: &AccessReport) -> Check {
+    const NAME: &str = "relation access";
+    let unreadable = &report.unreadable_relations;
+    if unreadable.is_empty() {
+        return Check::pass(
+            NAME,
+            format!(
+                "role {} can read all {} relations",
+                report.role, report.relations
+            ),
+        );
+    }
+
+    let mut listed = unreadable[..unreadable.len().min(LISTED_RELATIONS)].join(", ");
+    if unreadable.len() > LISTED_RELATIONS {
+        listed.push_str(&format!(
+            " and {} more",
+            unreadable.len() - LISTED_RELATIONS
+        ));
+    }
+    let detail = format!(
+        "role {} cannot read {} of {} relations ({}); queries on them cannot be explained",
+        report.role,
+        unreadable.len(),
+        report.relations,
+        listed
+    );
+    let fix = format!(
+        "GRANT USAGE ON SCHEMA <schema> TO {role}; GRANT SELECT ON ALL TABLES IN SCHEMA \
+         <schema> TO {role}; or, on PostgreSQL 14+, GRANT pg_read_all_data TO {role}",
+        role = report.role
+    );
+    if unreadable.len() as u64 == report.relations {
+        Check::fail(NAME, detail, fix)
+    } else {
+        Check::warn(NAME, detail, fix)
+    }
+}
+
+/// Whether top statements can be listed
+async fn pg_stat_statements(db: &Database, report: &AccessReport) -> Check {
+    const NAME: &str = "pg_stat_statements";
+    let extension = &report.pg_stat_statements;
+    let create = format!(
+        "CREATE EXTENSION pg_stat_statements in database {}",
+        report.database
+    );
+    let preload = "add pg_stat_statements to shared_preload_libraries in postgresql.conf and \
+                   restart the server";
+
+    let Some(version) = &extension.installed_version else {
+        let (detail, fix) = match (extension.available, extension.preloaded) {
+            (false, _) => (
+                "not available on the server; top statements are disabled".to_string(),
+                format!(
+                    "install the PostgreSQL contrib package, then {}, then run {}",
+                    preload, create
+                ),
+            ),
+            (true, false) => (
+                "not installed; top statements are disabled".to_string(),
+                format!("{}, then run {}", preload, create),
+            ),
+            (true, true) => (
+                "preloaded but not created in this database; top statements are disabled"
+                    .to_string(),
+                format!("run {}", create),
+            ),
+        };
+        return Check::warn(NAME, detail, fix);
+    };
+
+    if !extension.preloaded {
+        return Check::warn(
+            NAME,
+            format!(
+                "version {} is created but not preloaded, so it records nothing",
+                version
+            ),
+            preload,
+        );
+    }
+    match db.top_statements(StatementOrder::Calls, 1).await {
+        Ok(_) => Check::pass(NAME, format!("version {} is readable", version)),
+        Err(e) => Check::warn(
+            NAME,
+            format!("version {} cannot be read: {}", version, e),
+            format!(
+                "GRANT EXECUTE ON FUNCTION pg_stat_statements(boolean) TO {}",
+                report.role
+            ),
+        ),
+    }
+}
+
+/// Whether the role sees the text of statements run by other roles
+fn statistics_access(report: &AccessReport) -> Check {
+    const NAME: &str = "statistics access";
+    if report.superuser || report.read_all_stats {
+        return Check::pass(
+            NAME,
+            format!(
+                "role {} sees statements and activity of all roles",
+                report.role
+            ),
+        );
+    }
+    Check::warn(
+        NAME,
+        format!(
+            "role {} only sees the text of its own statements in pg_stat_statements \
+             and pg_stat_activity",
+            report.role
+        ),
+        format!("GRANT pg_read_all_stats TO {}", report.role),
+    )
+}
+
+/// Whether the files of the web UI are in the static directory
+pub async fn static_assets(frontend: &FrontendConfig) -> Check {
+    const NAME: &str = "static assets";
+    let dir = frontend.static_dir.display();
+    let mut missing = Vec::new();
+    for asset in REQUIRED_ASSETS {
+        if frontend.resolve(asset).await.is_none() {
+            missing.push(asset);
+        }
+    }
+    if missing.is_empty() {
+        return Check::pass(NAME, format!("web UI found in {}", dir));
+    }
+    Check::fail(
+        NAME,
+        format!("{} missing from {}", missing.join(", "), dir),
+        "pass --static-dir (or set static_dir under [frontend] in the config file) to the \
+         static directory of the sqltrace checkout or release archive",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::privileges::ExtensionStatus;
+
+    fn report(unreadable: &[&str], relations: u64) -> AccessReport {
+        AccessReport {
+            role: "app".to_string(),
+            database: "shop".to_string(),
+            relations,
+            unreadable_relations: unreadable.iter().map(|name| name.to_string()).collect(),
+            ..AccessReport::default()
+        }
+    }
+
+    #[test]
+    fn test_relation_access() {
+        assert_eq!(relation_access(&report(&[], 3)).status, CheckStatus::Pass);
+
+        let check = relation_access(&report(
+            &["a.t1", "a.t2", "a.t3", "a.t4", "a.t5", "a.t6"],
+            8,
+        ));
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check.detail.contains("6 of 8"));
+        assert!(check.detail.contains("a.t5 and 1 more"));
+        assert!(!check.detail.contains("a.t6"));
+        assert!(check.fix.unwrap().contains("TO app"));
+
+        let check = relation_access(&report(&["a.t1"], 1));
+        assert_eq!(check.status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn test_statistics_access() {
+        let mut access = report(&[], 0);
+        assert_eq!(statistics_access(&access).status, CheckStatus::Warn);
+        assert_eq!(
+            statistics_access(&access).fix.as_deref(),
+            Some("GRANT pg_read_all_stats TO app")
+        );
+        access.read_all_stats = true;
+        assert_eq!(statistics_access(&access).status, CheckStatus::Pass);
+    }
+
+    #[tokio::test]
+    async fn test_pg_stat_statements_missing() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        let mut access = report(&[], 0);
+        access.pg_stat_statements = ExtensionStatus {
+            installed_version: None,
+            available: true,
+            preloaded: false,
+        };
+        let check = pg_stat_statements(&db, &access).await;
+        assert_eq!(check.status, CheckStatus::Warn);
+        let fix = check.fix.unwrap();
+        assert!(fix.contains("shared_preload_libraries"));
+        assert!(fix.contains("CREATE EXTENSION pg_stat_statements in database shop"));
+
+        access.pg_stat_statements.preloaded = true;
+        let check = pg_stat_statements(&db, &access).await;
+        assert_eq!(
+            check.fix.as_deref(),
+            Some("run CREATE EXTENSION pg_stat_statements in database shop")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_on_sqlite() {
+        let base = std::env::temp_dir().join(format!("sqltrace-preflight-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("index.html"), "<html></html>").unwrap();
+        let frontend = FrontendConfig {
+            static_dir: base.clone(),
+            ..FrontendConfig::default()
+        };
+        let db = Database::new("sqlite::memory:").await.unwrap();
+
+        let checks = run(&db, &frontend).await;
+        let names: Vec<&str> = checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["connection", "explain", "static assets"]);
+        assert_eq!(checks[0].status, CheckStatus::Pass);
+        assert_eq!(checks[1].status, CheckStatus::Pass);
+        assert_eq!(checks[2].status, CheckStatus::Fail);
+        assert!(checks[2].detail.contains("script.js, styles.css missing"));
+        assert!(!passed(&checks));
+        assert!(checks[2].to_string().starts_with("FAIL  static assets: "));
+        assert!(checks[2]
+            .to_string()
+            .contains("\n      fix: pass --static-dir"));
+
+        std::fs::write(base.join("script.js"), "").unwrap();
+        std::fs::write(base.join("styles.css"), "").unwrap();
+        let checks = run(&db, &frontend).await;
+        assert!(passed(&checks));
+        assert_eq!(
+            checks[0].to_string(),
+            format!("ok    connection: {}", checks[0].detail)
+        );
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
@@ -0,0 +1,501 @@
+//! Fair queueing for benchmark slots
+//!
+//! Only `max_concurrent` benchmarks run at once. Requests beyond that wait
+//! here for a slot instead of failing, up to a configured number. A slot that
+//! becomes free goes to the waiting user with the fewest benchmarks running,
+//! then to the user served longest ago, and within one user to their oldest
+//! request, so a user queueing a batch takes turns with everybody else rather
+//! than making them wait until the batch is done. Requests named with an id
+//! can follow their position while they wait.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::{oneshot, watch};
+
+/// Why a request was not given a slot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueueRejection {
+    /// Every slot is taken and the queue is full
+    Full,
+    /// Every slot is taken and the user has as many requests waiting as allowed
+    UserFull,
+    /// No slot became free within the longest allowed wait
+    TimedOut,
+    /// A request with the same id is waiting or running
+    DuplicateId(String),
+}
+
+impl std::fmt::Display for QueueRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Full => write!(f, "The benchmark queue is full"),
+            Self::UserFull => write!(f, "Too many benchmarks of this user are queued"),
+            Self::TimedOut => write!(f, "Timed out waiting for a benchmark slot"),
+            Self::DuplicateId(id) => {
+                write!(f, "A request with id '{}' is already queued or running", id)
+            }
+        }
+    }
+}
+
+/// Where a request named with an id stands
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum QueueStatus {
+    /// Waiting for a slot
+    Queued {
+        /// Requests starting before this one as the queue stands, plus one
+        position: usize,
+        /// Requests waiting, this one included
+        queued: usize,
+    },
+    /// Holding a slot
+    Running,
+}
+
+/// Requests of one user
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct UserQueue {
+    /// Requests holding a slot
+    pub running: usize,
+    /// Requests waiting for a slot
+    pub queued: usize,
+}
+
+/// Occupancy of the queue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueueSnapshot {
+    /// Requests allowed to run at once
+    pub slots: usize,
+    /// Requests holding a slot
+    pub running: usize,
+    /// Requests waiting for a slot
+    pub queued: usize,
+    /// Running and waiting requests of each user with any
+    pub users: BTreeMap<String, UserQueue>,
+}
+
+/// How many requests may run and wait
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueLimits {
+    /// Requests allowed to run at once
+    pub slots: usize,
+    /// Requests allowed to wait across all users; 0 rejects as soon as every slot is taken
+    pub max_queued: usize,
+    /// Requests allowed to wait for one user
+    pub max_queued_per_user: usize,
+    /// Longest a request waits before it is rejected
+    pub max_wait: Duration,
+}
+
+struct Waiter {
+    ticket: u64,
+    user: String,
+    id: Option<String>,
+    grant: oneshot::Sender<QueuePermit>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Waiting requests in arrival order
+    waiting: Vec<Waiter>,
+    /// Requests holding a slot, per user
+    running: HashMap<String, usize>,
+    /// Ids of requests holding a slot
+    running_ids: HashSet<String>,
+    /// Number of the grant each user last received, for users with running or waiting requests
+    last_served: HashMap<String, u64>,
+    next_ticket: u64,
+    grants: u64,
+}
+
+impl QueueState {
+    fn running_total(&self) -> usize {
+        self.running.values().sum()
+    }
+
+    /// Indexes into `waiting` in the order the requests would start
+    fn schedule(&self) -> Vec<usize> {
+        let mut running: HashMap<&str, usize> = self
+            .running
+            .iter()
+            .map(|(user, count)| (user.as_str(), *count))
+            .collect();
+        let mut last_served: HashMap<&str, u64> = self
+            .last_served
+            .iter()
+            .map(|(user, grant)| (user.as_str(), *grant))
+            .collect();
+        let mut grants = self.grants;
+        let mut left: Vec<usize> = (0..self.waiting.len()).collect();
+        let mut order = Vec::with_capacity(left.len());
+        while !left.is_empty() {
+            let (slot, &index) = left
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, &index)| {
+                    let waiter = &self.waiting[index];
+                    let user = waiter.user.as_str();
+                    (
+                        running.get(user).copied().unwrap_or(0),
+                        last_served.get(user).copied().unwrap_or(0),
+                        waiter.ticket,
+                    )
+                })
+                .expect("left is not empty");
+            let user = self.waiting[index].user.as_str();
+            *running.entry(user).or_default() += 1;
+            grants += 1;
+            last_served.insert(user, grants);
+            order.push(index);
+            left.remove(slot);
+        }
+        order
+    }
+
+    /// Forget when `user` was last served once they have nothing running or waiting
+    fn forget_idle(&mut self, user: &str) {
+        if !self.running.contains_key(user) && !self.waiting.iter().any(|w| w.user == user) {
+            self.last_served.remove(user);
+        }
+    }
+}
+
+/// Benchmark slots shared fairly between users
+pub struct FairQueue {
+    limits: QueueLimits,
+    state: Mutex<QueueState>,
+    changes: watch::Sender<u64>,
+}
+
+impl FairQueue {
+    /// Create a queue enforcing `limits`
+    pub fn new(limits: QueueLimits) -> Self {
+        Self {
+            limits: QueueLimits {
+                slots: limits.slots.max(1),
+                ..limits
+            },
+            state: Mutex::new(QueueState::default()),
+            changes: watch::channel(0).0,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait for a slot for a request of `user`, optionally named `id`
+    ///
+    /// The slot is held until the returned permit is dropped. Dropping the
+    /// future while it waits leaves the queue.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        user: &str,
+        id: Option<&str>,
+    ) -> Result<QueuePermit, QueueRejection> {
+        let (grant, mut granted) = oneshot::channel();
+        let (ticket, grants) = {
+            let mut state = self.lock();
+            if let Some(id) = id {
+                if state.running_ids.contains(id)
+                    || state.waiting.iter().any(|w| w.id.as_deref() == Some(id))
+                {
+                    return Err(QueueRejection::DuplicateId(id.to_string()));
+                }
+            }
+            if state.running_total() >= self.limits.slots {
+                if state.waiting.len() >= self.limits.max_queued {
+                    return Err(QueueRejection::Full);
+                }
+                let user_waiting = state.waiting.iter().filter(|w| w.user == user).count();
+                if user_waiting >= self.limits.max_queued_per_user {
+                    return Err(QueueRejection::UserFull);
+                }
+            }
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            state.waiting.push(Waiter {
+                ticket,
+                user: user.to_string(),
+                id: id.map(str::to_string),
+                grant,
+            });
+            (ticket, self.dispatch(&mut state))
+        };
+        let _waiting = WaitGuard {
+            queue: self,
+            ticket,
+        };
+        self.deliver(grants);
+
+        match tokio::time::timeout(self.limits.max_wait, &mut granted).await {
+            Ok(Ok(permit)) => Ok(permit),
+            _ => {
+                self.withdraw(ticket);
+                // The slot may have been granted just as the wait ran out
+                granted.try_recv().map_err(|_| QueueRejection::TimedOut)
+            }
+        }
+    }
+
+    /// Where the request named `id` stands, if it is waiting or running
+    pub fn status(&self, id: &str) -> Option<QueueStatus> {
+        let state = self.lock();
+        if state.running_ids.contains(id) {
+            return Some(QueueStatus::Running);
+        }
+        let index = state
+            .waiting
+            .iter()
+            .position(|w| w.id.as_deref() == Some(id))?;
+        let position = state.schedule().iter().position(|&i| i == index)? + 1;
+        Some(QueueStatus::Queued {
+            position,
+            queued: state.waiting.len(),
+        })
+    }
+
+    /// Running and waiting requests
+    pub fn snapshot(&self) -> QueueSnapshot {
+        let state = self.lock();
+        let mut users: BTreeMap<String, UserQueue> = BTreeMap::new();
+        for (user, running) in &state.running {
+            users.entry(user.clone()).or_default().running = *running;
+        }
+        for waiter in &state.waiting {
+            users.entry(waiter.user.clone()).or_default().queued += 1;
+        }
+        QueueSnapshot {
+            slots: self.limits.slots,
+            running: state.running_total(),
+            queued: state.waiting.len(),
+            users,
+        }
+    }
+
+    /// Receiver notified whenever a request joins, starts or leaves
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.changes.subscribe()
+    }
+
+    /// Hand free slots to waiting requests, returning the grants to deliver
+    /// once the lock is released
+    fn dispatch(
+        self: &Arc<Self>,
+        state: &mut QueueState,
+    ) -> Vec<(oneshot::Sender<QueuePermit>, QueuePermit)> {
+        let free = self.limits.slots.saturating_sub(state.running_total());
+        let mut chosen: Vec<usize> = state.schedule().into_iter().take(free).collect();
+        for &index in &chosen {
+            let user = state.waiting[index].user.clone();
+            *state.running.entry(user.clone()).or_default() += 1;
+            state.grants += 1;
+            let grant = state.grants;
+            state.last_served.insert(user, grant);
+            if let Some(id) = state.waiting[index].id.clone() {
+                state.running_ids.insert(id);
+            }
+        }
+        chosen.sort_unstable_by(|a, b| b.cmp(a));
+        chosen
+            .into_iter()
+            .map(|index| {
+                let waiter = state.waiting.remove(index);
+                let permit = QueuePermit {
+                    queue: self.clone(),
+                    user: waiter.user,
+                    id: waiter.id,
+                };
+                (waiter.grant, permit)
+            })
+            .collect()
+    }
+
+    fn deliver(&self, grants: Vec<(oneshot::Sender<QueuePermit>, QueuePermit)>) {
+        for (grant, permit) in grants {
+            // A request that stopped waiting drops its permit, which frees the slot again
+            let _ = grant.send(permit);
+        }
+        self.changes.send_modify(|version| *version += 1);
+    }
+
+    fn withdraw(&self, ticket: u64) {
+        let mut state = self.lock();
+        let Some(index) = state.waiting.iter().position(|w| w.ticket == ticket) else {
+            return;
+        };
+        let waiter = state.waiting.remove(index);
+        state.forget_idle(&waiter.user);
+        drop(state);
+        self.changes.send_modify(|version| *version += 1);
+    }
+
+    fn release(self: &Arc<Self>, user: &str, id: Option<&str>) {
+        let grants = {
+            let mut state = self.lock();
+            if let Some(running) = state.running.get_mut(user) {
+                *running -= 1;
+                if *running == 0 {
+                    state.running.remove(user);
+                }
+            }
+            if let Some(id) = id {
+                state.running_ids.remove(id);
+            }
+            state.forget_idle(user);
+            self.dispatch(&mut state)
+        };
+        self.deliver(grants);
+    }
+}
+
+/// Removes a request from the queue if it stops waiting before getting a slot
+struct WaitGuard<'a> {
+    queue: &'a FairQueue,
+    ticket: u64,
+}
+
+impl Drop for WaitGuard<'_> {
+    fn drop(&mut self) {
+        self.queue.withdraw(self.ticket);
+    }
+}
+
+/// A slot held by a request; dropping it passes the slot on
+pub struct QueuePermit {
+    queue: Arc<FairQueue>,
+    user: String,
+    id: Option<String>,
+}
+
+impl Drop for QueuePermit {
+    fn drop(&mut self) {
+        self.queue.release(&self.user, self.id.as_deref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(slots: usize, max_queued: usize, max_queued_per_user: usize) -> Arc<FairQueue> {
+        Arc::new(FairQueue::new(QueueLimits {
+            slots,
+            max_queued,
+            max_queued_per_user,
+            max_wait: Duration::from_secs(5),
+        }))
+    }
+
+    /// Queue a request in the background and wait until it is in the queue
+    async fn enqueue(
+        queue: &Arc<FairQueue>,
+        user: &str,
+        id: &str,
+    ) -> tokio::task::JoinHandle<Result<QueuePermit, QueueRejection>> {
+        let (task_queue, user, task_id) = (queue.clone(), user.to_string(), id.to_string());
+        let task = tokio::spawn(async move { task_queue.acquire(&user, Some(&task_id)).await });
+        while queue.status(id).is_none() {
+            tokio::task::yield_now().await;
+        }
+        task
+    }
+
+    fn position(queue: &FairQueue, id: &str) -> Option<usize> {
+        match queue.status(id)? {
+            QueueStatus::Queued { position, .. } => Some(position),
+            QueueStatus::Running => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_users_take_turns() {
+        let queue = queue(1, 10, 10);
+        let running = queue.acquire("alice", Some("a0")).await.unwrap();
+        assert_eq!(queue.status("a0"), Some(QueueStatus::Running));
+
+        let mut alice = Vec::new();
+        for i in 1..=3 {
+            alice.push(enqueue(&queue, "alice", &format!("a{}", i)).await);
+        }
+        let bob = enqueue(&queue, "bob", "b1").await;
+
+        // Bob arrived last but goes before the rest of Alice's batch
+        assert_eq!(position(&queue, "b1"), Some(1));
+        assert_eq!(position(&queue, "a1"), Some(2));
+        assert_eq!(position(&queue, "a3"), Some(4));
+
+        drop(running);
+        let bob = bob.await.unwrap().unwrap();
+        assert_eq!(queue.status("b1"), Some(QueueStatus::Running));
+        assert_eq!(position(&queue, "a1"), Some(1));
+
+        drop(bob);
+        let first = alice.remove(0).await.unwrap().unwrap();
+        assert_eq!(
+            queue.snapshot().users["alice"],
+            UserQueue {
+                running: 1,
+                queued: 2
+            }
+        );
+        drop(first);
+        for task in alice {
+            drop(task.await.unwrap().unwrap());
+        }
+        let snapshot = queue.snapshot();
+        assert_eq!((snapshot.running, snapshot.queued), (0, 0));
+        assert!(snapshot.users.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_queue_limits() {
+        let queue = queue(1, 2, 1);
+        let _running = queue.acquire("alice", None).await.unwrap();
+        let _waiting = enqueue(&queue, "alice", "a1").await;
+
+        assert_eq!(
+            queue.acquire("alice", None).await.err(),
+            Some(QueueRejection::UserFull)
+        );
+        assert_eq!(
+            queue.acquire("bob", Some("a1")).await.err(),
+            Some(QueueRejection::DuplicateId("a1".to_string()))
+        );
+        let _bob = enqueue(&queue, "bob", "b1").await;
+        assert_eq!(
+            queue.acquire("carol", None).await.err(),
+            Some(QueueRejection::Full)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_request_leaves_queue() {
+        let queue = Arc::new(FairQueue::new(QueueLimits {
+            slots: 1,
+            max_queued: 4,
+            max_queued_per_user: 4,
+            max_wait: Duration::from_millis(20),
+        }));
+        let running = queue.acquire("alice", None).await.unwrap();
+
+        assert_eq!(
+            queue.acquire("bob", Some("b1")).await.err(),
+            Some(QueueRejection::TimedOut)
+        );
+        assert_eq!(queue.status("b1"), None);
+
+        let waiting = enqueue(&queue, "bob", "b2").await;
+        waiting.abort();
+        let _ = waiting.await;
+        assert_eq!(queue.status("b2"), None);
+
+        // The slot passes to nobody and is free for the next request
+        drop(running);
+        assert_eq!(queue.snapshot().running, 0);
+        let _next = queue.acquire("carol", None).await.unwrap();
+    }
+}
@@ -1,33 +1,393 @@
 //! Web server setup and configuration
 
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::{Html, Json},
-    routing::{get, post},
+    extract::{DefaultBodyLimit, Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Json, Response,
+    },
+    routing::{delete, get, post, put},
     Router,
 };
+use futures_util::stream::{self, Stream};
 use serde::{Deserialize, Serialize};
-use tower::ServiceBuilder;
-use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::{Service, ServiceBuilder};
+use tower_http::{cors::CorsLayer, services::ServeFile, trace::TraceLayer};
 
-use crate::advisor::QueryAdvisor;
-use crate::benchmark::{BenchmarkConfig, BenchmarkResult, BenchmarkSuite};
+use crate::advisor::dry_run::{Corpus, DryRun, DryRunReport};
+use crate::advisor::rules::{find_rule, RuleStatus, RULES};
+use crate::advisor::{AnalysisContext, QueryAdvisor, RuleOverrides, SharedAdvisor};
+use crate::benchmark::{
+    query_group, BenchmarkOverrides, BenchmarkResult, BenchmarkSuite, QueryGroupReport,
+    ResultCheckOptions, RewriteReport, RunTable, TransactionBenchmarkResult,
+};
+use crate::budget::{BudgetConfig, BudgetTracker, BudgetUsage};
+use crate::config::{ServerConfig, ServerProfile};
+use crate::db::breaker::{self, CircuitOpen};
+use crate::db::cancel::RunningQueries;
+use crate::db::connections::{
+    ConnectionError, ConnectionManager, ConnectionTest, ManagedConnection,
+};
+use crate::db::engines::{
+    ConnectionConfig, ConnectionSummary, DatabaseFeature, DatabaseInfo, EngineFactory, EngineType,
+};
+use crate::db::error::ErrorLocation;
+use crate::db::explain_options::ExplainOptions;
+use crate::db::index_health::IndexHealth;
+use crate::db::lineage::{self, ColumnLineage};
+use crate::db::models::ExecutionPlan;
+use crate::db::monitor::ConnectionStatus;
+use crate::db::plan_overlay::{self, PlanOverlay};
+use crate::db::pool_metrics::PoolMetrics;
+use crate::db::prepared::{
+    BoundParameter, PlanCacheReport, PreparedExplainOptions, QueryParameter,
+};
+use crate::db::preview::{self, ResultPreview};
+use crate::db::privileges::Integration;
+use crate::db::relation_check::{self, MissingRelation};
+use crate::db::settings::ServerSettings;
+use crate::db::table_definitions::{self, SchemaChange, TableDefinition};
+use crate::db::tls::TlsConfig;
+use crate::db::transaction::{self, TransactionMode};
+use crate::db::workload::{StatementOrder, StatementStats};
 use crate::db::Database;
+use crate::policy::{PolicyViolation, QueryPolicy};
+use crate::queue::{FairQueue, QueuePermit, QueueRejection, QueueSnapshot, QueueStatus};
+use crate::settings::{user_from_headers, UiSettings};
+use crate::storage::archive::{self, ImportReport, StateArchive};
+use crate::storage::calibration::{self, CostTimeSeries};
+use crate::storage::findings::{self, Observation};
+use crate::storage::pins::{self, PinCheck, PlanPin};
+use crate::storage::reanalysis;
+use crate::storage::trends;
+use crate::storage::{
+    now_millis, BenchmarkKind, ConnectionTrend, Finding, FindingFilter, HistoryEntry, HistoryStats,
+    NewBenchmark, NewHistoryEntry, NewSavedQuery, PruneReport, ReanalysisReport, RetentionPolicy,
+    SavedQuery, Storage,
+};
+use crate::telemetry::{self, Telemetry, TelemetryReport};
+use crate::ui::glossary::{self, GlossaryEntry};
+use crate::web::correlation::{self, Correlation};
+use crate::web::{negotiation, shaping};
+use crate::workload::auto_explain::{self, AutoExplainImport, PlanRecorder};
+use crate::workload::{self, OrmLogFormat, WorkloadEntry, WorkloadQuery, WorkloadReport};
+use crate::SqlTraceError;
+
+pub use crate::db::connections::DEFAULT_CONNECTION;
+
+/// Distinct queries of a workload analyzed when the request does not say
+const DEFAULT_WORKLOAD_QUERIES: usize = 20;
+
+/// Variants of a query group benchmarked when the request does not say
+const DEFAULT_GROUP_SAMPLE: usize = 5;
+
+/// Most variants of a query group benchmarked in one request
+const MAX_GROUP_SAMPLE: usize = 20;
+
+/// Recent history entries searched for the variants or plans of a query group
+const GROUP_HISTORY_SCAN: i64 = 1000;
+
+/// Analyses plotted per request when the request does not say
+const DEFAULT_COST_TIME_POINTS: i64 = 1000;
+
+/// Most analyses plotted in one request
+const MAX_COST_TIME_POINTS: i64 = 10_000;
+
+/// Days of a connection's trend reported when the request does not say
+const DEFAULT_TREND_DAYS: usize = 30;
+
+/// Most days of a connection's trend reported in one request
+const MAX_TREND_DAYS: usize = 365;
+
+/// Stored analyses the advisor is re-run over, by re-analysis or a dry run, when the request does not say
+const DEFAULT_ADVISOR_RERUN: i64 = 1000;
+
+/// Most stored analyses the advisor is re-run over in one request
+const MAX_ADVISOR_RERUN: i64 = 10_000;
+
+/// Plan shapes overlaid when the request does not say
+const DEFAULT_OVERLAY_PLANS: usize = 3;
+
+/// Most plan shapes overlaid in one request
+const MAX_OVERLAY_PLANS: usize = 8;
+
+/// Statements listed by the top statements endpoint when the request does not say
+const DEFAULT_TOP_STATEMENTS: i64 = 20;
+
+/// Most statements listed by the top statements endpoint in one request
+const MAX_TOP_STATEMENTS: i64 = 100;
+
+/// Most distinct queries of a workload analyzed in one request
+const MAX_WORKLOAD_QUERIES: usize = 100;
+
+/// Largest archive accepted by the import endpoint
+const IMPORT_BODY_LIMIT: usize = 512 * 1024 * 1024;
+
+/// Why an analysis request was turned away before it ran
+#[derive(Debug)]
+pub enum Rejection {
+    /// Answered with the status alone
+    Status(StatusCode),
+    /// The circuit of the requested connection is open; answered with 503 and `Retry-After`
+    Degraded(CircuitOpen),
+}
+
+impl From<StatusCode> for Rejection {
+    fn from(status: StatusCode) -> Self {
+        Rejection::Status(status)
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        match self {
+            Rejection::Status(status) => status.into_response(),
+            Rejection::Degraded(open) => {
+                let retry_after = open.retry_after.as_secs();
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(header::RETRY_AFTER, retry_after.to_string())],
+                    Json(serde_json::json!({
+                        "error": open.to_string(),
+                        "connection": open.connection,
+                        "retry_after_secs": retry_after,
+                    })),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
 
 /// Application state shared across handlers
 #[derive(Clone)]
 pub struct AppState {
-    /// Database connection pool
+    /// Database of the default connection as connected at startup; see
+    /// [`AppState::default_db`] for the one serving it now
     pub db: Database,
-    /// Query optimization advisor
-    pub advisor: QueryAdvisor,
+    /// Query optimization advisor, with the rule changes made at runtime
+    pub advisor: SharedAdvisor,
+    /// Storage for history and saved queries
+    pub storage: Arc<dyn Storage>,
+    /// Server configuration
+    pub config: Arc<ServerConfig>,
+    /// Slots limiting how many benchmarks run at once, shared fairly between users
+    pub benchmark_queue: Arc<FairQueue>,
+    /// Named connections, including the default one
+    pub connections: Arc<ConnectionManager>,
+    /// Daily execution spent by each user
+    pub budgets: Arc<BudgetTracker>,
+    /// Explain requests that can be cancelled, by their issued request id
+    pub running: Arc<RunningQueries>,
+    /// Advisor rule hits counted for opt-in telemetry
+    pub telemetry: Arc<Telemetry>,
+}
+
+impl AppState {
+    /// Create the application state, deriving shared limits from `config`
+    pub fn new(
+        db: Database,
+        advisor: QueryAdvisor,
+        storage: Arc<dyn Storage>,
+        config: ServerConfig,
+    ) -> Self {
+        let benchmark_queue = Arc::new(FairQueue::new(config.benchmark.queue_limits()));
+        let connections = Arc::new(ConnectionManager::new(
+            db.clone(),
+            Duration::from_secs(config.relation_check.cache_ttl_secs),
+        ));
+        let budgets = Arc::new(BudgetTracker::new(config.budget.clone()));
+        let telemetry = Arc::new(Telemetry::new(config.telemetry.clone()));
+        Self {
+            db,
+            advisor: SharedAdvisor::new(advisor),
+            storage,
+            config: Arc::new(config),
+            benchmark_queue,
+            connections,
+            budgets,
+            running: Arc::new(RunningQueries::default()),
+            telemetry,
+        }
+    }
+
+    /// Database of the default connection, which changes when the connection is reconnected
+    pub fn default_db(&self) -> Database {
+        self.connections.default_connection().db
+    }
+
+    /// Configured connections, with passwords redacted
+    pub fn connection_summaries(&self) -> Vec<ConnectionSummary> {
+        self.connections.summaries()
+    }
+
+    /// The connection named `name`, or the default connection if no name is given
+    pub fn connection(&self, name: Option<&str>) -> Result<ManagedConnection, String> {
+        self.connections
+            .get(name.unwrap_or(DEFAULT_CONNECTION))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Turn away an analysis on `connection` while its circuit is open
+    pub fn admit(&self, connection: &ManagedConnection) -> Result<(), Rejection> {
+        breaker::admit(connection).map_err(|open| {
+            tracing::warn!("Rejected analysis: {}", open);
+            Rejection::Degraded(open)
+        })
+    }
+
+    /// Feed the outcome of an analysis on `connection` to its circuit breaker
+    pub fn record_outcome<T>(
+        &self,
+        connection: &ManagedConnection,
+        result: &Result<T, SqlTraceError>,
+    ) {
+        let reached = !matches!(result, Err(e) if e.is_connection_failure());
+        breaker::record(&self.connections, connection, reached, &self.config.breaker);
+    }
+
+    /// Find relations referenced by `query` that do not exist, if the pre-check is enabled
+    ///
+    /// Failing to load the schema only disables the check; the database will
+    /// still report missing relations itself. The check reads PostgreSQL
+    /// catalogs, so other engines skip it.
+    pub async fn missing_relations(
+        &self,
+        connection: &ManagedConnection,
+        query: &str,
+    ) -> Vec<MissingRelation> {
+        let db = &connection.db;
+        if !self.config.relation_check.enabled || db.engine_type() != EngineType::PostgreSQL {
+            return Vec::new();
+        }
+        let relations = relation_check::referenced_relations(query);
+        if relations.is_empty() {
+            return Vec::new();
+        }
+
+        let missing = match connection.schema_cache.get(db).await {
+            Ok(snapshot) => relation_check::find_missing(&relations, &snapshot),
+            Err(e) => {
+                tracing::warn!("Failed to load schema for relation check: {}", e);
+                return Vec::new();
+            }
+        };
+        if missing.is_empty() {
+            return missing;
+        }
+
+        // The relation may have been created since the snapshot was taken
+        match connection.schema_cache.refresh(db).await {
+            Ok(snapshot) => relation_check::find_missing(&relations, &snapshot),
+            Err(_) => missing,
+        }
+    }
+
+    /// Reject a request of `user` with 429 once they have used up a daily budget
+    pub fn check_budget(&self, user: &str) -> Result<(), StatusCode> {
+        self.budgets.check(user, now_millis()).map_err(|exceeded| {
+            tracing::warn!("Rejected request of {}: {}", user, exceeded);
+            StatusCode::TOO_MANY_REQUESTS
+        })
+    }
+
+    /// Wait for a benchmark slot for `user`, taking turns with other users
+    ///
+    /// A full queue or a wait that runs out is answered with 429, and an id
+    /// already in use with 409.
+    pub async fn benchmark_slot(
+        &self,
+        user: &str,
+        request_id: Option<&str>,
+    ) -> Result<QueuePermit, StatusCode> {
+        self.benchmark_queue
+            .acquire(user, request_id)
+            .await
+            .map_err(|rejection| {
+                tracing::warn!("Rejected benchmark of {}: {}", user, rejection);
+                match rejection {
+                    QueueRejection::DuplicateId(_) => StatusCode::CONFLICT,
+                    _ => StatusCode::TOO_MANY_REQUESTS,
+                }
+            })
+    }
+
+    /// Query policy of the connection named `name`
+    ///
+    /// Once any policy is configured, a connection without its own
+    /// `[policies.<name>]` section is refused rather than left unrestricted,
+    /// so registering a database again under a new name cannot get around
+    /// its policy. An empty section leaves a connection unrestricted.
+    pub fn policy_for(&self, name: &str) -> Result<Option<&QueryPolicy>, PolicyViolation> {
+        let policies = &self.config.policies;
+        match policies.get(name) {
+            Some(policy) => Ok(Some(policy)),
+            None if policies.is_empty() => Ok(None),
+            None => Err(PolicyViolation(format!(
+                "connection {} has no [policies.{}] section",
+                name, name
+            ))),
+        }
+    }
+
+    /// Reject `query` if it violates the policy of the default connection
+    pub async fn enforce_policy(&self, query: &str) -> Result<(), String> {
+        self.enforce_policy_on(&self.connections.default_connection(), query)
+            .await
+    }
+
+    /// Reject `query` if it violates the policy of `connection`
+    ///
+    /// Relation and cost limits are checked against a plain `EXPLAIN`, so a
+    /// rejected query is never executed.
+    pub async fn enforce_policy_on(
+        &self,
+        connection: &ManagedConnection,
+        query: &str,
+    ) -> Result<(), String> {
+        let Some(policy) = self
+            .policy_for(&connection.name)
+            .map_err(|e| e.to_string())?
+        else {
+            return Ok(());
+        };
+
+        policy.check_statement(query).map_err(|e| e.to_string())?;
+        if policy.needs_estimate() {
+            let estimate = connection.db.estimate(query).await;
+            self.record_outcome(connection, &estimate);
+            let estimate = estimate.map_err(|e| format!("Failed to check query policy: {}", e))?;
+            policy
+                .check_estimate(&estimate)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
 }
 
 /// Request payload for the explain endpoint
 #[derive(Deserialize)]
 struct ExplainRequest {
     query: String,
+    /// Connection to explain on; the default connection if not given
+    connection: Option<String>,
+    /// Values for the `$n` placeholders of the query
+    #[serde(default)]
+    params: Vec<QueryParameter>,
+    /// EXPLAIN options; `ANALYZE, BUFFERS` if not given
+    #[serde(default)]
+    options: ExplainOptions,
+    /// Return the untouched EXPLAIN output as `raw_plan`
+    #[serde(default)]
+    include_raw_plan: bool,
+    /// Id issued by `/api/explain/requests`, under which the request can be cancelled while it runs
+    request_id: Option<String>,
 }
 
 /// Response payload for the explain endpoint
@@ -35,20 +395,216 @@ struct ExplainRequest {
 struct ExplainResponse {
     plan: Option<serde_json::Value>,
     error: Option<String>,
+    error_location: Option<ErrorLocation>,
+    missing_relations: Option<Vec<MissingRelation>>,
+    lineage: Option<Vec<ColumnLineage>>,
     advisor_analysis: Option<crate::advisor::AdvisorAnalysis>,
+    history_id: Option<i64>,
+    /// Parameters bound to the query's placeholders, when any were given
+    parameters: Option<Vec<BoundParameter>>,
+    /// EXPLAIN options the plan was produced with
+    explain_options: Option<ExplainOptions>,
+    /// Planner-relevant server settings the plan was made under, on PostgreSQL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    settings: Option<ServerSettings>,
+    /// Raw EXPLAIN output, when requested or raw plan capture retains it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_plan: Option<serde_json::Value>,
+    /// Comparison with the plan pinned for a saved query with the same fingerprint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pin_check: Option<PinCheck>,
+    /// Whether the request was cancelled before the plan was produced
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cancelled: bool,
+    /// Id the request was registered under for cancellation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+impl ExplainResponse {
+    fn failed(error: String, error_location: Option<ErrorLocation>) -> Self {
+        Self {
+            plan: Some(serde_json::json!({})),
+            error: Some(error),
+            error_location,
+            missing_relations: None,
+            lineage: None,
+            advisor_analysis: None,
+            history_id: None,
+            parameters: None,
+            explain_options: None,
+            settings: None,
+            raw_plan: None,
+            pin_check: None,
+            cancelled: false,
+            request_id: None,
+        }
+    }
+}
+
+/// Response payload for the request id endpoint
+#[derive(Serialize)]
+struct RequestIdResponse {
+    /// Id to send as the `request_id` of one explain request
+    request_id: String,
+}
+
+/// Response payload for the explain cancellation endpoint
+#[derive(Serialize)]
+struct CancelExplainResponse {
+    /// Whether the running statement was signalled; `false` if it was still
+    /// waiting for a connection, in which case it fails once it gets one
+    signalled: bool,
+    error: Option<String>,
+}
+
+/// Most executions replayed by the prepared statement endpoint
+const MAX_PREPARED_EXECUTIONS: usize = 20;
+
+/// Response payload for the glossary endpoint
+#[derive(Serialize)]
+struct GlossaryResponse {
+    entry: Option<&'static GlossaryEntry>,
+    error: Option<String>,
+}
+
+/// Request payload for the prepared statement explain endpoint
+#[derive(Deserialize)]
+struct PreparedExplainRequest {
+    query: String,
+    /// Connection to explain on; the default connection if not given
+    connection: Option<String>,
+    #[serde(flatten)]
+    options: PreparedExplainOptions,
+}
+
+/// Response payload for the prepared statement explain endpoint
+#[derive(Serialize)]
+struct PreparedExplainResponse {
+    report: Option<PlanCacheReport>,
+    error: Option<String>,
+}
+
+/// Request payload for the result preview endpoint
+#[derive(Deserialize)]
+struct PreviewRequest {
+    query: String,
+    /// Rows to return; defaults to and is capped at the configured maximum
+    limit: Option<usize>,
+}
+
+/// Response payload for the result preview endpoint
+#[derive(Serialize)]
+struct PreviewResponse {
+    plan: Option<serde_json::Value>,
+    preview: Option<ResultPreview>,
+    error: Option<String>,
+    error_location: Option<ErrorLocation>,
+}
+
+impl PreviewResponse {
+    fn failed(error: String, error_location: Option<ErrorLocation>) -> Self {
+        Self {
+            plan: None,
+            preview: None,
+            error: Some(error),
+            error_location,
+        }
+    }
 }
 
 /// Request payload for the benchmark endpoint
 #[derive(Deserialize)]
 struct BenchmarkRequest {
     query: String,
-    config: Option<BenchmarkConfig>,
+    config: Option<BenchmarkOverrides>,
+    /// Connection to benchmark on; the default connection if not given
+    connection: Option<String>,
+    /// Id under which the request's place in the benchmark queue can be followed
+    request_id: Option<String>,
 }
 
 /// Response payload for the benchmark endpoint
 #[derive(Serialize)]
 struct BenchmarkResponse {
     result: Option<BenchmarkResult>,
+    /// Id under which the result was stored, for `/api/benchmarks/:id/runs`
+    benchmark_id: Option<i64>,
+    error: Option<String>,
+}
+
+/// Request payload for the transaction benchmark endpoint
+#[derive(Deserialize)]
+struct TransactionBenchmarkRequest {
+    /// Statements run in order in each transaction
+    statements: Vec<String>,
+    #[serde(default)]
+    mode: TransactionMode,
+    config: Option<BenchmarkOverrides>,
+    /// Connection to benchmark on; the default connection if not given
+    connection: Option<String>,
+    /// Id under which the request's place in the benchmark queue can be followed
+    request_id: Option<String>,
+}
+
+/// Response payload for the transaction benchmark endpoint
+#[derive(Serialize)]
+struct TransactionBenchmarkResponse {
+    result: Option<TransactionBenchmarkResult>,
+    /// Id under which the result was stored, for `/api/benchmarks/:id/runs`
+    benchmark_id: Option<i64>,
+    error: Option<String>,
+}
+
+/// Output format of the benchmark runs endpoint
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum RunsFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+/// Query parameters of the benchmark runs endpoint
+#[derive(Deserialize)]
+struct BenchmarkRunsParams {
+    #[serde(default)]
+    format: RunsFormat,
+}
+
+/// Response payload for the benchmark runs endpoint
+#[derive(Serialize)]
+struct BenchmarkRunsResponse {
+    benchmark_id: i64,
+    kind: BenchmarkKind,
+    label: String,
+    created_at: i64,
+    run_count: usize,
+    /// One array per measurement, each with one value per run
+    columns: Option<RunTable>,
+    error: Option<String>,
+}
+
+/// Request payload for the query group benchmark endpoint
+///
+/// The group is given by a fingerprint or by a history entry whose query belongs to it.
+#[derive(Deserialize)]
+struct QueryGroupRequest {
+    fingerprint: Option<String>,
+    history_id: Option<i64>,
+    /// Variants to benchmark; capped at `MAX_GROUP_SAMPLE`
+    sample_size: Option<usize>,
+    config: Option<BenchmarkOverrides>,
+    /// Connection to benchmark on; the default connection if not given
+    connection: Option<String>,
+    /// Id under which the request's place in the benchmark queue can be followed
+    request_id: Option<String>,
+}
+
+/// Response payload for the query group benchmark endpoint
+#[derive(Serialize)]
+struct QueryGroupResponse {
+    report: Option<QueryGroupReport>,
     error: Option<String>,
 }
 
@@ -59,7 +615,16 @@ struct BenchmarkCompareRequest {
     query_b: String,
     label_a: String,
     label_b: String,
-    config: Option<BenchmarkConfig>,
+    config: Option<BenchmarkOverrides>,
+    /// Also check that both queries return the same number of rows
+    #[serde(default)]
+    check_row_counts: bool,
+    /// Also check that both queries return the same rows
+    check_results: Option<ResultCheckOptions>,
+    /// Connection to benchmark on; the default connection if not given
+    connection: Option<String>,
+    /// Id under which the request's place in the benchmark queue can be followed
+    request_id: Option<String>,
 }
 
 /// Response payload for benchmark comparison
@@ -69,145 +634,2693 @@ struct BenchmarkCompareResponse {
     error: Option<String>,
 }
 
-/// Create the main application router
-pub fn create_router(state: AppState) -> Router {
-    Router::new()
-        .route("/", get(serve_index))
-        .route("/api/explain", post(explain_handler))
-        .route("/api/health", get(health_handler))
-        .route("/api/benchmark", post(benchmark_handler))
-        .route("/api/benchmark/compare", post(benchmark_compare_handler))
-        .nest_service("/static", ServeDir::new("static"))
-        .layer(
-            ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive()),
-        )
-        .with_state(state)
+/// Request payload for the rewrite report endpoint
+#[derive(Deserialize)]
+struct RewriteRequest {
+    before: String,
+    after: String,
+    /// Also benchmark both queries; defaults to true
+    benchmark: Option<bool>,
+    config: Option<BenchmarkOverrides>,
+    /// Id under which the request's place in the benchmark queue can be followed
+    request_id: Option<String>,
 }
 
-/// Serve the main index.html file
-async fn serve_index() -> Html<String> {
-    let html = tokio::fs::read_to_string("static/index.html")
-        .await
-        .unwrap_or_else(|_| {
-            r#"
-            <!DOCTYPE html>
-            <html>
-            <head><title>SQLTrace - Error</title></head>
-            <body>
-                <h1>SQLTrace</h1>
-                <p>Error: Could not load index.html. Make sure the static files are present.</p>
-            </body>
-            </html>
-            "#
-            .to_string()
-        });
+/// Response payload for the rewrite report endpoint
+#[derive(Serialize)]
+struct RewriteResponse {
+    report: Option<RewriteReport>,
+    error: Option<String>,
+}
 
-    Html(html)
+/// Request payload for the ORM log workload endpoint
+#[derive(Deserialize)]
+struct OrmLogRequest {
+    format: OrmLogFormat,
+    log: String,
+    /// Explain and advise on the most frequent queries; defaults to true
+    analyze: Option<bool>,
+    /// How many of the most frequent queries to analyze
+    max_queries: Option<usize>,
 }
 
-/// Health check endpoint
-async fn health_handler() -> Json<serde_json::Value> {
-    Json(serde_json::json!({
-        "status": "healthy",
-        "service": "sqltrace-rs"
-    }))
+/// Response payload for the ORM log workload endpoint
+#[derive(Serialize)]
+struct WorkloadResponse {
+    report: Option<WorkloadReport>,
+    error: Option<String>,
 }
 
-/// Handle SQL query explanation requests
-async fn explain_handler(
-    State(state): State<AppState>,
-    Json(payload): Json<ExplainRequest>,
-) -> Result<Json<ExplainResponse>, StatusCode> {
-    // Validate the query syntax first
-    if let Err(validation_error) = crate::web::validate_query(&payload.query) {
-        return Ok(Json(ExplainResponse {
-            plan: Some(serde_json::json!({})),
-            error: Some(validation_error),
-            advisor_analysis: None,
-        }));
-    }
+/// Request payload for importing a PostgreSQL log written by auto_explain
+#[derive(Deserialize)]
+struct AutoExplainRequest {
+    log: String,
+    /// Connection the plans are recorded under; the default connection if omitted
+    connection: Option<String>,
+}
 
-    // Execute the query and get the execution plan
-    match state.db.explain(&payload.query).await {
-        Ok(plan) => {
-            // Run advisor analysis
-            let advisor_analysis = state.advisor.analyze_plan(&plan);
+/// Response payload for the auto_explain import endpoint
+#[derive(Serialize)]
+struct AutoExplainResponse {
+    report: Option<AutoExplainImport>,
+    error: Option<String>,
+}
 
-            // Convert the plan to the UI format for the frontend
-            let plan_tree = crate::ui::plan_to_web_format(&plan);
-            match serde_json::to_value(plan_tree) {
-                Ok(plan_value) => Ok(Json(ExplainResponse {
-                    plan: Some(plan_value),
-                    error: None,
-                    advisor_analysis: Some(advisor_analysis),
-                })),
-                Err(e) => Ok(Json(ExplainResponse {
-                    plan: Some(serde_json::json!({})),
-                    error: Some(format!("Failed to serialize execution plan: {}", e)),
-                    advisor_analysis: None,
-                })),
-            }
-        }
-        Err(e) => Ok(Json(ExplainResponse {
-            plan: Some(serde_json::json!({})),
-            error: Some(e.to_string()),
-            advisor_analysis: None,
-        })),
-    }
+/// Query parameters of the top statements endpoint
+#[derive(Deserialize)]
+struct TopStatementsParams {
+    /// Named connection to read; the default connection if omitted
+    connection: Option<String>,
+    #[serde(default)]
+    order_by: StatementOrder,
+    limit: Option<i64>,
 }
 
-/// Handle benchmark requests
-async fn benchmark_handler(
-    State(state): State<AppState>,
-    Json(payload): Json<BenchmarkRequest>,
-) -> Result<Json<BenchmarkResponse>, StatusCode> {
-    let config = payload.config.unwrap_or_default();
-    let benchmark_suite =
-        BenchmarkSuite::new(state.db.clone(), state.advisor.clone(), Some(config));
+/// Response payload for the top statements endpoint
+#[derive(Serialize)]
+struct TopStatementsResponse {
+    statements: Option<Vec<StatementStats>>,
+    error: Option<String>,
+}
 
-    match benchmark_suite.benchmark_query(&payload.query).await {
-        Ok(result) => Ok(Json(BenchmarkResponse {
-            result: Some(result),
-            error: None,
-        })),
-        Err(e) => Ok(Json(BenchmarkResponse {
-            result: None,
-            error: Some(e.to_string()),
-        })),
-    }
+/// Query parameters for the index health endpoint
+#[derive(Deserialize)]
+struct IndexHealthParams {
+    schema: Option<String>,
 }
 
-/// Handle benchmark comparison requests
-async fn benchmark_compare_handler(
-    State(state): State<AppState>,
-    Json(payload): Json<BenchmarkCompareRequest>,
-) -> Result<Json<BenchmarkCompareResponse>, StatusCode> {
-    let config = payload.config.unwrap_or_default();
-    let benchmark_suite =
-        BenchmarkSuite::new(state.db.clone(), state.advisor.clone(), Some(config));
+/// Response payload for the index health endpoint
+#[derive(Serialize)]
+struct IndexHealthResponse {
+    indexes: Option<Vec<IndexHealth>>,
+    error: Option<String>,
+}
 
-    // Run benchmarks for both queries
-    let result_a = benchmark_suite.benchmark_query(&payload.query_a).await;
-    let result_b = benchmark_suite.benchmark_query(&payload.query_b).await;
+/// Pagination parameters for the history endpoint
+#[derive(Deserialize)]
+struct HistoryParams {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// Only entries with this trace id or correlation id
+    correlation_id: Option<String>,
+}
 
-    match (result_a, result_b) {
-        (Ok(bench_a), Ok(bench_b)) => {
-            let comparison = benchmark_suite.compare_benchmarks(
-                &bench_a,
-                &bench_b,
-                payload.label_a,
-                payload.label_b,
-            );
-            Ok(Json(BenchmarkCompareResponse {
-                comparison: Some(comparison),
-                error: None,
-            }))
-        }
-        (Err(e), _) | (_, Err(e)) => Ok(Json(BenchmarkCompareResponse {
-            comparison: None,
-            error: Some(format!("Benchmark failed: {}", e)),
+/// Response payload for the history list endpoint
+#[derive(Serialize)]
+struct HistoryListResponse {
+    entries: Option<Vec<HistoryEntry>>,
+    error: Option<String>,
+}
+
+/// Response payload for a single history entry
+#[derive(Serialize)]
+struct HistoryEntryResponse {
+    entry: Option<HistoryEntry>,
+    error: Option<String>,
+}
+
+/// Query parameters of the schema diff endpoint
+#[derive(Deserialize)]
+struct SchemaDiffParams {
+    /// Compare with the tables stored with this analysis instead of the current schema
+    against: Option<i64>,
+}
+
+/// Response payload for the schema diff endpoint
+#[derive(Serialize)]
+struct SchemaDiffResponse {
+    changes: Option<Vec<SchemaChange>>,
+    error: Option<String>,
+}
+
+/// Query parameters of the cost/time endpoint
+#[derive(Deserialize)]
+struct CostTimeParams {
+    /// Only analyses run on this connection
+    connection: Option<String>,
+    /// Most recent analyses to include, across connections
+    limit: Option<i64>,
+}
+
+/// Response payload for the cost/time endpoint
+#[derive(Serialize)]
+struct CostTimeResponse {
+    series: Option<Vec<CostTimeSeries>>,
+    error: Option<String>,
+}
+
+/// Query parameters of the connection trend endpoint
+#[derive(Deserialize)]
+struct TrendParams {
+    /// Days to report, ending today
+    days: Option<usize>,
+}
+
+/// Response payload for the connection trend endpoint
+#[derive(Serialize)]
+struct ConnectionTrendResponse {
+    trend: Option<ConnectionTrend>,
+    error: Option<String>,
+}
+
+/// Query parameters of the plan overlay endpoint
+#[derive(Deserialize)]
+struct PlanOverlayParams {
+    /// Most distinct plan shapes to overlay
+    max_plans: Option<usize>,
+}
+
+/// A distinct plan shape of a query group, as recorded in history
+#[derive(Serialize)]
+struct PlanShapeSummary {
+    /// Most recent analysis with this shape, whose plan is overlaid
+    history_id: i64,
+    /// Query text of that analysis
+    query: String,
+    /// Execution time of that analysis, in milliseconds
+    execution_time_ms: f64,
+    /// When that analysis was recorded
+    last_seen: i64,
+    /// Analyses with this shape among those searched
+    occurrences: usize,
+}
+
+/// Response payload for the plan overlay endpoint
+#[derive(Serialize)]
+struct PlanOverlayResponse {
+    fingerprint: Option<String>,
+    /// Overlaid shapes, in the order of the overlay's plan indexes
+    shapes: Option<Vec<PlanShapeSummary>>,
+    /// Plans with this many distinct shapes in history, including those not overlaid
+    shapes_found: Option<usize>,
+    overlay: Option<PlanOverlay>,
+    error: Option<String>,
+}
+
+/// Response payload for the findings list endpoint
+#[derive(Serialize)]
+struct FindingsResponse {
+    findings: Option<Vec<Finding>>,
+    error: Option<String>,
+}
+
+/// Response payload for a single finding
+#[derive(Serialize)]
+struct FindingResponse {
+    finding: Option<Finding>,
+    error: Option<String>,
+}
+
+/// Response payload for the saved queries list endpoint
+#[derive(Serialize)]
+struct SavedQueriesResponse {
+    queries: Option<Vec<SavedQuery>>,
+    error: Option<String>,
+}
+
+/// Response payload for a single saved query
+#[derive(Serialize)]
+struct SavedQueryResponse {
+    query: Option<SavedQuery>,
+    error: Option<String>,
+}
+
+/// Request payload for pinning the plan of a saved query
+#[derive(Deserialize)]
+struct PinPlanRequest {
+    /// Stored analysis of the saved query whose plan is blessed
+    history_id: i64,
+}
+
+/// Response payload for the plan pin endpoints
+#[derive(Serialize)]
+struct PlanPinResponse {
+    pin: Option<PlanPin>,
+    error: Option<String>,
+}
+
+/// Response payload for delete endpoints
+#[derive(Serialize)]
+struct DeleteResponse {
+    deleted: bool,
+    error: Option<String>,
+}
+
+/// Request payload for the purge endpoint
+///
+/// With no limits given, the configured retention policy is applied.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct PurgeRequest {
+    all: bool,
+    max_age_days: Option<u64>,
+    max_count: Option<u64>,
+    max_size_mb: Option<u64>,
+}
+
+/// Response payload for the purge endpoint
+#[derive(Serialize)]
+struct PurgeResponse {
+    deleted: u64,
+    report: Option<PruneReport>,
+    stats: Option<HistoryStats>,
+    error: Option<String>,
+}
+
+/// Request payload for the re-analysis endpoint
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ReanalyzeRequest {
+    /// Most recent analyses to re-analyze
+    limit: Option<i64>,
+}
+
+/// Response payload for the re-analysis endpoint
+#[derive(Serialize)]
+struct ReanalyzeResponse {
+    report: Option<ReanalysisReport>,
+    error: Option<String>,
+}
+
+/// Response payload for the storage stats endpoint
+#[derive(Serialize)]
+struct StorageStatsResponse {
+    history: Option<HistoryStats>,
+    retention: RetentionPolicy,
+    error: Option<String>,
+}
+
+/// Response payload for the pool metrics endpoint
+#[derive(Serialize)]
+struct PoolsResponse {
+    /// Pool statistics by connection name; connections without a pool are left out
+    pools: BTreeMap<String, PoolMetrics>,
+}
+
+/// Response payload for the settings endpoint
+#[derive(Serialize)]
+struct SettingsResponse {
+    user: String,
+    settings: Option<UiSettings>,
+    /// Whether the settings were stored for this user, as opposed to defaults
+    stored: bool,
+    error: Option<String>,
+}
+
+/// Response payload for the connections endpoint
+#[derive(Serialize)]
+struct ConnectionsResponse {
+    connections: Vec<ConnectionSummary>,
+}
+
+/// Response payload for the connection info endpoint
+#[derive(Serialize)]
+struct ConnectionInfoResponse {
+    info: Option<DatabaseInfo>,
+    error: Option<String>,
+}
+
+/// Response payload for the connection integrations endpoint
+#[derive(Serialize)]
+struct IntegrationsResponse {
+    integrations: Option<Vec<Integration>>,
+    error: Option<String>,
+}
+
+/// Response payload for the budget endpoint
+#[derive(Serialize)]
+struct BudgetResponse {
+    user: String,
+    usage: BudgetUsage,
+    limits: BudgetConfig,
+}
+
+/// Response payload for the telemetry endpoint
+#[derive(Serialize)]
+struct TelemetryResponse {
+    enabled: bool,
+    endpoint: Option<String>,
+    interval_secs: u64,
+    /// Counts the next report would send
+    pending: Option<TelemetryReport>,
+}
+
+/// Response payload for listing advisor rules
+#[derive(Serialize)]
+struct RulesResponse {
+    rules: Vec<RuleStatus>,
+    /// Changes made at runtime on top of the startup configuration
+    overrides: RuleOverrides,
+}
+
+/// Request payload for changing an advisor rule
+#[derive(Deserialize)]
+struct UpdateRuleRequest {
+    /// Enable or disable the rule; left as it is when absent
+    enabled: Option<bool>,
+    /// New values of thresholds the rule reads, by name
+    #[serde(default)]
+    thresholds: BTreeMap<String, f64>,
+}
+
+/// Response payload for changing an advisor rule
+#[derive(Serialize)]
+struct UpdateRuleResponse {
+    rule: Option<RuleStatus>,
+    stored: bool,
+    error: Option<String>,
+}
+
+/// Request payload for a dry run of advisor rules
+#[derive(Deserialize)]
+struct DryRunRequest {
+    /// Rules to run and the thresholds to run them with
+    #[serde(flatten)]
+    dry_run: DryRun,
+    /// Most recent stored analyses to run the rules on
+    limit: Option<i64>,
+}
+
+/// Response payload for a dry run of advisor rules
+#[derive(Serialize)]
+struct DryRunResponse {
+    report: Option<DryRunReport>,
+    error: Option<String>,
+}
+
+/// Request payload for registering a connection
+#[derive(Deserialize)]
+struct RegisterConnectionRequest {
+    name: String,
+    url: String,
+    max_connections: Option<u32>,
+    timeout_seconds: Option<u64>,
+    #[serde(default)]
+    tls: TlsConfig,
+    /// Schemas searched for unqualified names, such as `sales, public`
+    search_path: Option<String>,
+}
+
+/// Response payload for registering a connection
+#[derive(Serialize)]
+struct ConnectionResponse {
+    connection: Option<ConnectionSummary>,
+    error: Option<String>,
+}
+
+/// Response payload for testing a connection
+#[derive(Serialize)]
+struct ConnectionTestResponse {
+    test: Option<ConnectionTest>,
+    error: Option<String>,
+}
+
+/// Response payload for the import endpoint
+#[derive(Serialize)]
+struct ImportResponse {
+    report: Option<ImportReport>,
+    error: Option<String>,
+}
+
+/// Create the main application router
+///
+/// The read-only profile serves only [`analysis_routes`].
+pub fn create_router(state: AppState) -> Router {
+    let routes = match state.config.profile {
+        ServerProfile::Full => analysis_routes().merge(full_routes()),
+        ServerProfile::ReadOnly => analysis_routes(),
+    };
+    routes
+        .route("/static/*path", get(static_asset_handler))
+        .layer(
+            ServiceBuilder::new()
+                .layer(TraceLayer::new_for_http().make_span_with(correlation::request_span))
+                .layer(CorsLayer::permissive()),
+        )
+        .with_state(state)
+}
+
+/// Endpoints that analyze queries against the configured connections without
+/// changing connections, settings or stored state beyond history
+fn analysis_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(serve_index))
+        .route(
+            "/api/explain",
+            post(explain_handler)
+                .layer(middleware::from_fn(shaping::shape_response))
+                .layer(middleware::from_fn(negotiation::negotiate_format)),
+        )
+        .route("/api/explain/prepared", post(explain_prepared_handler))
+        .route("/api/explain/requests", post(issue_request_id_handler))
+        .route("/api/explain/:id/cancel", post(cancel_explain_handler))
+        .route("/api/health", get(health_handler))
+        .route("/api/glossary/:node_type", get(glossary_handler))
+        .route("/api/workload/orm-log", post(orm_log_handler))
+        .route("/api/connections", get(connections_handler))
+        .route("/api/connections/:name/info", get(connection_info_handler))
+        .route(
+            "/api/connections/:name/integrations",
+            get(connection_integrations_handler),
+        )
+        .route("/api/budget", get(budget_handler))
+        .route("/api/telemetry", get(telemetry_handler))
+}
+
+/// Every other endpoint
+fn full_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/preview", post(preview_handler))
+        .route("/api/benchmark", post(benchmark_handler))
+        .route("/api/queue", get(queue_handler))
+        .route("/api/queue/:id/events", get(queue_events_handler))
+        .route("/api/benchmark/compare", post(benchmark_compare_handler))
+        .route(
+            "/api/benchmark/transaction",
+            post(benchmark_transaction_handler),
+        )
+        .route("/api/benchmark/group", post(benchmark_group_handler))
+        .route("/api/benchmarks/:id/runs", get(benchmark_runs_handler))
+        .route("/api/rewrite", post(rewrite_handler))
+        .route("/api/workload/top", get(top_statements_handler))
+        .route("/api/indexes/health", get(index_health_handler))
+        .route("/api/connections", post(register_connection_handler))
+        .route("/api/connections/:name", delete(remove_connection_handler))
+        .route("/api/connections/:name/test", post(test_connection_handler))
+        .route(
+            "/api/connections/:name/trends",
+            get(connection_trend_handler),
+        )
+        .route(
+            "/api/history",
+            get(list_history_handler)
+                .layer(middleware::from_fn(shaping::shape_response))
+                .layer(middleware::from_fn(negotiation::negotiate_format)),
+        )
+        .route(
+            "/api/history/:id",
+            get(get_history_handler)
+                .layer(middleware::from_fn(shaping::shape_response))
+                .layer(middleware::from_fn(negotiation::negotiate_format))
+                .delete(delete_history_handler),
+        )
+        .route("/api/history/cost-time", get(cost_time_handler))
+        .route("/api/history/reanalyze", post(reanalyze_handler))
+        .route(
+            "/api/history/auto-explain",
+            post(auto_explain_handler).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
+        .route("/api/history/:id/schema-diff", get(schema_diff_handler))
+        .route("/api/history/:id/plan-overlay", get(plan_overlay_handler))
+        .route("/api/findings", get(list_findings_handler))
+        .route(
+            "/api/findings/:id/acknowledge",
+            post(acknowledge_finding_handler),
+        )
+        .route(
+            "/api/settings",
+            get(get_settings_handler)
+                .put(put_settings_handler)
+                .delete(reset_settings_handler),
+        )
+        .route("/api/admin/storage", get(storage_stats_handler))
+        .route("/api/admin/pool", get(pool_metrics_handler))
+        .route("/api/admin/rules", get(list_rules_handler))
+        .route("/api/admin/rules/:id", put(update_rule_handler))
+        .route("/api/admin/rules/dry-run", post(dry_run_rules_handler))
+        .route("/api/admin/purge", post(purge_handler))
+        .route("/api/admin/export", get(export_handler))
+        .route(
+            "/api/admin/import",
+            post(import_handler).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
+        .route(
+            "/api/queries",
+            get(list_saved_queries_handler).post(save_query_handler),
+        )
+        .route(
+            "/api/queries/:id",
+            get(get_saved_query_handler).delete(delete_saved_query_handler),
+        )
+        .route(
+            "/api/queries/:id/pin",
+            get(get_plan_pin_handler)
+                .put(pin_plan_handler)
+                .delete(unpin_plan_handler),
+        )
+}
+
+/// Serve the main index.html file
+async fn serve_index(State(state): State<AppState>) -> Html<String> {
+    let html = tokio::fs::read_to_string(state.config.frontend.index_path())
+        .await
+        .unwrap_or_else(|_| {
+            r#"
+            <!DOCTYPE html>
+            <html>
+            <head><title>SQLTrace - Error</title></head>
+            <body>
+                <h1>SQLTrace</h1>
+                <p>Error: Could not load index.html. Make sure the static files are present.</p>
+            </body>
+            </html>
+            "#
+            .to_string()
+        });
+
+    Html(html)
+}
+
+/// Serve a file of the frontend directory, refusing paths that lead outside it
+async fn static_asset_handler(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    request: Request,
+) -> Response {
+    match state.config.frontend.resolve(&path).await {
+        // ServeFile is always ready, so it can be called without polling it first
+        Some(file) => match ServeFile::new(file).call(request).await {
+            Ok(response) => response.into_response(),
+            Err(never) => match never {},
+        },
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Health check endpoint
+async fn health_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let connections = state.connections.health();
+    let degraded = connections.values().any(|health| {
+        health.circuit.is_open()
+            || matches!(
+                health.status,
+                ConnectionStatus::Degraded | ConnectionStatus::Down
+            )
+    });
+    Json(serde_json::json!({
+        "status": if degraded { "degraded" } else { "healthy" },
+        "service": "sqltrace-rs",
+        "profile": state.config.profile,
+        "connections": connections
+    }))
+}
+
+/// Explain a plan node type for "what is this?" popovers
+async fn glossary_handler(Path(node_type): Path<String>) -> Json<GlossaryResponse> {
+    match glossary::lookup(&node_type) {
+        Some(entry) => Json(GlossaryResponse {
+            entry: Some(entry),
+            error: None,
+        }),
+        None => Json(GlossaryResponse {
+            entry: None,
+            error: Some(format!("No glossary entry for node type \"{}\"", node_type)),
+        }),
+    }
+}
+
+/// Handle SQL query explanation requests
+async fn explain_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<ExplainRequest>,
+) -> Result<Json<ExplainResponse>, Rejection> {
+    let user = user_from_headers(&headers);
+    if payload.options.analyze {
+        state.check_budget(&user)?;
+    }
+
+    let connection = match state.connection(payload.connection.as_deref()) {
+        Ok(connection) => connection,
+        Err(e) => return Ok(Json(ExplainResponse::failed(e, None))),
+    };
+    state.admit(&connection)?;
+    if let Err(e) = payload.options.validate() {
+        return Ok(Json(ExplainResponse::failed(e, None)));
+    }
+    let db = &connection.db;
+
+    // Validate the query syntax first, in the dialect of the connected engine
+    let validation = crate::web::validate_query_with_dialect(
+        &payload.query,
+        db.engine_type().dialect().as_ref(),
+    );
+    if let Err(validation_error) = validation {
+        let location = ErrorLocation::from_parser_message(&payload.query, &validation_error);
+        return Ok(Json(ExplainResponse::failed(validation_error, location)));
+    }
+
+    // Report typos in relation names without running the query
+    let missing = state.missing_relations(&connection, &payload.query).await;
+    if !missing.is_empty() {
+        let messages: Vec<String> = missing.iter().map(ToString::to_string).collect();
+        return Ok(Json(ExplainResponse {
+            missing_relations: Some(missing),
+            ..ExplainResponse::failed(
+                format!(
+                    "Query references missing relations: {}",
+                    messages.join("; ")
+                ),
+                None,
+            )
+        }));
+    }
+
+    // Placeholders cannot be planned without values, so with parameters the
+    // policy's plan checks run on the bound statement instead
+    let policy = match state.policy_for(&connection.name) {
+        Ok(policy) => policy,
+        Err(violation) => return Ok(Json(ExplainResponse::failed(violation.to_string(), None))),
+    };
+    let policy_check = if payload.params.is_empty() {
+        state.enforce_policy_on(&connection, &payload.query).await
+    } else {
+        policy
+            .map_or(Ok(()), |p| p.check_statement(&payload.query))
+            .map_err(|e| e.to_string())
+    };
+    if let Err(violation) = policy_check {
+        return Ok(Json(ExplainResponse::failed(violation, None)));
+    }
+
+    tracing::debug!(
+        "Explaining query: {}",
+        state.config.masking.query(&payload.query)
+    );
+
+    let guard = match payload.request_id.as_deref() {
+        Some(id) => match state.running.register(id, db) {
+            Ok(guard) => Some(guard),
+            Err(e) => return Ok(Json(ExplainResponse::failed(e, None))),
+        },
+        None => None,
+    };
+    let running = guard.as_ref().map(|guard| guard.query());
+
+    // Execute the query and get the execution plan
+    let explained = if payload.params.is_empty() {
+        db.explain_raw(&payload.query, &payload.options, running)
+            .await
+            .map(|(plan, raw)| (plan, None, raw))
+    } else {
+        db.explain_with_params(
+            &payload.query,
+            &payload.params,
+            &payload.options,
+            policy,
+            running,
+        )
+        .await
+        .map(|(plan, parameters, raw)| (plan, Some(parameters), Some(raw)))
+    };
+    drop(guard);
+    state.record_outcome(&connection, &explained);
+    match explained {
+        Ok((plan, parameters, raw)) => {
+            // Only plans retained by capture are stored; the request flag only adds it to the response
+            let (retained, raw_plan) = match raw {
+                Some(raw) => {
+                    let requested = payload.include_raw_plan.then(|| raw.clone());
+                    let retained = state
+                        .config
+                        .plan_capture
+                        .capture(raw, &state.config.masking)
+                        .await;
+                    let raw_plan = requested.or_else(|| retained.clone());
+                    (retained, raw_plan)
+                }
+                None => (None, None),
+            };
+
+            state.budgets.record(
+                &user,
+                now_millis(),
+                plan.execution_time,
+                plan.rows_processed(),
+            );
+
+            // Gather metadata for schema-aware rules; failures only disable those rules
+            let mut context = AnalysisContext {
+                estimates_only: !payload.options.analyze
+                    || !db.supports_feature(&DatabaseFeature::ActualRowCounts),
+                ..AnalysisContext::default()
+            };
+            if db.engine_type() == EngineType::PostgreSQL {
+                let relations = plan.relation_names();
+                match db.sequence_usage(&relations).await {
+                    Ok(sequences) => context.sequences = sequences,
+                    Err(e) => tracing::warn!("Failed to fetch sequence usage: {}", e),
+                }
+                match db.relation_sizes(&relations).await {
+                    Ok(sizes) => context.relation_sizes = sizes,
+                    Err(e) => tracing::warn!("Failed to fetch relation sizes: {}", e),
+                }
+                match db.table_statistics(&relations).await {
+                    Ok(statistics) => context.table_statistics = statistics,
+                    Err(e) => tracing::warn!("Failed to fetch table statistics: {}", e),
+                }
+                match db.table_indexes(&relations).await {
+                    Ok(indexes) => context.indexes = indexes,
+                    Err(e) => tracing::warn!("Failed to fetch table indexes: {}", e),
+                }
+            }
+
+            // Run advisor analysis
+            let advisor_analysis = state
+                .advisor
+                .current()
+                .analyze_plan_with_context(&plan, &context);
+            if state.telemetry.is_enabled() {
+                let engine = telemetry::engine_label(db.engine_type(), db.server_version().await);
+                state
+                    .telemetry
+                    .record(&engine, &advisor_analysis.suggestions);
+            }
+
+            // Store the analysis; history is best-effort and never fails the request
+            let correlation = Correlation::from_headers(&headers);
+            let history_id = match record_history(
+                &state,
+                &connection,
+                &payload.query,
+                &plan,
+                retained.as_ref(),
+                &advisor_analysis,
+                correlation,
+            )
+            .await
+            {
+                Ok(entry) => Some(entry.id),
+                Err(e) => {
+                    tracing::warn!("Failed to record analysis history: {}", e);
+                    None
+                }
+            };
+            if let Err(e) =
+                track_findings(&state, &payload.query, history_id, &advisor_analysis).await
+            {
+                tracing::warn!("Failed to track advisor findings: {}", e);
+            }
+            let pin_check = match pins::check(state.storage.as_ref(), &payload.query, &plan).await {
+                Ok(check) => check,
+                Err(e) => {
+                    tracing::warn!("Failed to compare the plan with its pin: {}", e);
+                    None
+                }
+            };
+            if let Some(check) = pin_check.as_ref().filter(|check| !check.matches) {
+                tracing::warn!(
+                    "Plan deviates from the plan pinned for saved query {}",
+                    check.saved_query_id
+                );
+            }
+
+            let mut column_lineage = lineage::column_lineage(&payload.query);
+            lineage::attach_plan_outputs(&mut column_lineage, &plan);
+
+            // Convert the plan to the UI format for the frontend
+            let plan_tree = crate::ui::plan_to_web_format(&plan);
+            match serde_json::to_value(plan_tree) {
+                Ok(plan_value) => Ok(Json(ExplainResponse {
+                    plan: Some(plan_value),
+                    error: None,
+                    error_location: None,
+                    missing_relations: None,
+                    lineage: Some(column_lineage),
+                    advisor_analysis: Some(advisor_analysis),
+                    history_id,
+                    parameters,
+                    explain_options: Some(payload.options),
+                    settings: plan.settings.clone(),
+                    raw_plan,
+                    pin_check,
+                    cancelled: false,
+                    request_id: payload.request_id,
+                })),
+                Err(e) => Ok(Json(ExplainResponse {
+                    history_id,
+                    ..ExplainResponse::failed(
+                        format!("Failed to serialize execution plan: {}", e),
+                        None,
+                    )
+                })),
+            }
+        }
+        Err(e) => Ok(Json(ExplainResponse {
+            cancelled: matches!(e, SqlTraceError::Cancelled),
+            request_id: payload.request_id,
+            ..ExplainResponse::failed(e.to_string(), e.location().cloned())
+        })),
+    }
+}
+
+/// Issue an id an explain request can be registered and cancelled under
+async fn issue_request_id_handler(State(state): State<AppState>) -> Json<RequestIdResponse> {
+    Json(RequestIdResponse {
+        request_id: state.running.issue(),
+    })
+}
+
+/// Cancel a running explain request by the issued `request_id` it was sent with
+async fn cancel_explain_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<CancelExplainResponse>, StatusCode> {
+    let running = state.running.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    match running.cancel().await {
+        Ok(signalled) => {
+            tracing::info!("Cancelled explain request '{}'", id);
+            Ok(Json(CancelExplainResponse {
+                signalled,
+                error: None,
+            }))
+        }
+        Err(e) => Ok(Json(CancelExplainResponse {
+            signalled: false,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Handle requests to compare the custom and generic plans of a prepared statement
+async fn explain_prepared_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(mut payload): Json<PreparedExplainRequest>,
+) -> Result<Json<PreparedExplainResponse>, Rejection> {
+    let failed = |error: String| {
+        Ok(Json(PreparedExplainResponse {
+            report: None,
+            error: Some(error),
+        }))
+    };
+
+    let user = user_from_headers(&headers);
+    if payload.options.analyze {
+        state.check_budget(&user)?;
+    }
+
+    let connection = match state.connection(payload.connection.as_deref()) {
+        Ok(connection) => connection,
+        Err(e) => return failed(e),
+    };
+    state.admit(&connection)?;
+
+    if let Err(validation_error) = crate::web::validate_query(&payload.query) {
+        return failed(validation_error);
+    }
+
+    let policy = match state.policy_for(&connection.name) {
+        Ok(policy) => policy,
+        Err(violation) => return failed(violation.to_string()),
+    };
+    if let Some(Err(violation)) = policy.map(|p| p.check_statement(&payload.query)) {
+        return failed(violation.to_string());
+    }
+
+    payload.options.executions = payload.options.executions.min(MAX_PREPARED_EXECUTIONS);
+    let result = connection
+        .db
+        .explain_prepared(&payload.query, &payload.options, policy)
+        .await;
+    match result {
+        Ok(report) => {
+            let plans = [&report.custom_plan, &report.generic_plan];
+            state.budgets.record(
+                &user,
+                now_millis(),
+                plans.iter().filter_map(|p| p.execution_time_ms).sum(),
+                plans.iter().map(|p| p.rows_processed()).sum(),
+            );
+            Ok(Json(PreparedExplainResponse {
+                report: Some(report),
+                error: None,
+            }))
+        }
+        Err(e) => Ok(Json(PreparedExplainResponse {
+            report: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Handle requests to run a query and return its first rows alongside the plan
+async fn preview_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<PreviewRequest>,
+) -> Result<Json<PreviewResponse>, StatusCode> {
+    let config = &state.config.preview;
+    if !config.enabled {
+        return Ok(Json(PreviewResponse::failed(
+            "Result preview is disabled; set enabled = true in the [preview] config section"
+                .to_string(),
+            None,
+        )));
+    }
+
+    if let Err(validation_error) = crate::web::validate_query(&payload.query) {
+        let location = ErrorLocation::from_parser_message(&payload.query, &validation_error);
+        return Ok(Json(PreviewResponse::failed(validation_error, location)));
+    }
+    if let Err(error) = preview::check_previewable(&payload.query) {
+        return Ok(Json(PreviewResponse::failed(error, None)));
+    }
+    if let Err(violation) = state.enforce_policy(&payload.query).await {
+        return Ok(Json(PreviewResponse::failed(violation, None)));
+    }
+
+    let limit = payload
+        .limit
+        .unwrap_or(config.max_rows)
+        .min(config.max_rows);
+    let timeout = Duration::from_millis(config.timeout_ms);
+    match state
+        .default_db()
+        .preview(&payload.query, limit, timeout)
+        .await
+    {
+        Ok((plan, result_preview)) => Ok(Json(PreviewResponse {
+            plan: serde_json::to_value(crate::ui::plan_to_web_format(&plan)).ok(),
+            preview: Some(result_preview),
+            error: None,
+            error_location: None,
+        })),
+        Err(e) => Ok(Json(PreviewResponse::failed(
+            e.to_string(),
+            e.location().cloned(),
+        ))),
+    }
+}
+
+/// Persist an explained plan, its advisor output, and the definitions of the tables it reads to history
+///
+/// Table definitions are read from PostgreSQL catalogs; on other engines, or
+/// if reading them fails, the entry is stored without them.
+async fn record_history(
+    state: &AppState,
+    connection: &ManagedConnection,
+    query: &str,
+    plan: &crate::db::models::ExecutionPlan,
+    raw_plan: Option<&serde_json::Value>,
+    analysis: &crate::advisor::AdvisorAnalysis,
+    correlation: Correlation,
+) -> Result<HistoryEntry, crate::SqlTraceError> {
+    let db = &connection.db;
+    let masking = &state.config.masking;
+    let mut plan_value = serde_json::to_value(plan)?;
+    if let (Some(raw), Some(fields)) = (raw_plan, plan_value.as_object_mut()) {
+        fields.insert("raw".to_string(), raw.clone());
+    }
+    let mut analysis_value = serde_json::to_value(analysis)?;
+    masking.analysis(&plan_value, &mut analysis_value);
+    masking.plan(&mut plan_value);
+
+    let table_definitions = if db.engine_type() == EngineType::PostgreSQL {
+        match db.table_definitions(&plan.relation_names()).await {
+            Ok(tables) => Some(serde_json::to_value(tables)?),
+            Err(e) => {
+                tracing::warn!("Failed to capture table definitions: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let entry = NewHistoryEntry {
+        query: masking.query(query),
+        plan: plan_value,
+        analysis: Some(analysis_value),
+        performance_score: Some(analysis.performance_score),
+        total_cost: plan.root.total_cost,
+        execution_time_ms: plan.execution_time,
+        trace_id: correlation.trace_id,
+        correlation_id: correlation.correlation_id,
+        table_definitions,
+        connection: Some(connection.name.clone()),
+    };
+    Ok(state.storage.record_history(entry).await?)
+}
+
+/// Update the stored findings of the query's fingerprint with an analysis
+async fn track_findings(
+    state: &AppState,
+    query: &str,
+    history_id: Option<i64>,
+    analysis: &crate::advisor::AdvisorAnalysis,
+) -> Result<(), crate::SqlTraceError> {
+    let config = &state.config.findings;
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let fingerprint = findings::fingerprint(query);
+    let query = state.config.masking.query(query);
+    let observation = Observation {
+        fingerprint: &fingerprint,
+        query: &query,
+        history_id,
+        now: now_millis(),
+    };
+    findings::track(
+        state.storage.as_ref(),
+        &analysis.suggestions,
+        observation,
+        &config.sla_days,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Report how many benchmarks hold a slot and how many wait for one, per user
+async fn queue_handler(State(state): State<AppState>) -> Json<QueueSnapshot> {
+    Json(state.benchmark_queue.snapshot())
+}
+
+/// Stream the queue position of the benchmark request named `id` as server-sent events
+///
+/// A `queued` event is sent whenever the position changes and a `running`
+/// event once the request has its slot, which ends the stream. If the request
+/// stops waiting without a slot, a `left` event ends it.
+async fn queue_events_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let queue = state.benchmark_queue.clone();
+    queue.status(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let changes = queue.subscribe();
+
+    let events = stream::unfold(
+        Some((queue, id, changes, None)),
+        |follow: Option<(_, String, _, Option<QueueStatus>)>| async move {
+            let (queue, id, mut changes, last) = follow?;
+            loop {
+                let status = queue.status(&id);
+                if status.is_some() && status == last {
+                    changes.changed().await.ok()?;
+                    continue;
+                }
+                let event = match &status {
+                    Some(status) => {
+                        let name = match status {
+                            QueueStatus::Queued { .. } => "queued",
+                            QueueStatus::Running => "running",
+                        };
+                        Event::default()
+                            .event(name)
+                            .data(serde_json::to_string(status).unwrap_or_default())
+                    }
+                    None => Event::default().event("left").data("{}"),
+                };
+                let next = matches!(status, Some(QueueStatus::Queued { .. }))
+                    .then(|| (queue, id, changes, status));
+                return Some((Ok(event), next));
+            }
+        },
+    );
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Handle benchmark requests
+async fn benchmark_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<BenchmarkRequest>,
+) -> Result<Json<BenchmarkResponse>, Rejection> {
+    let _permit = state
+        .benchmark_slot(&user_from_headers(&headers), payload.request_id.as_deref())
+        .await?;
+
+    let connection = match state.connection(payload.connection.as_deref()) {
+        Ok(connection) => connection,
+        Err(e) => {
+            return Ok(Json(BenchmarkResponse {
+                result: None,
+                benchmark_id: None,
+                error: Some(e),
+            }))
+        }
+    };
+    state.admit(&connection)?;
+    if let Err(violation) = state.enforce_policy_on(&connection, &payload.query).await {
+        return Ok(Json(BenchmarkResponse {
+            result: None,
+            benchmark_id: None,
+            error: Some(violation),
+        }));
+    }
+
+    let config = state.config.benchmark.resolve(payload.config.as_ref());
+    let benchmark_suite =
+        BenchmarkSuite::new(connection.db.clone(), state.advisor.current(), Some(config));
+
+    let benchmarked = benchmark_suite.benchmark_query(&payload.query).await;
+    state.record_outcome(&connection, &benchmarked);
+    match benchmarked {
+        Ok(result) => {
+            let mut stored = result.clone();
+            stored.query = state.config.masking.query(&result.query);
+            let benchmark_id =
+                record_benchmark(&state, BenchmarkKind::Query, stored.query.clone(), &stored).await;
+            Ok(Json(BenchmarkResponse {
+                result: Some(result),
+                benchmark_id,
+                error: None,
+            }))
+        }
+        Err(e) => Ok(Json(BenchmarkResponse {
+            result: None,
+            benchmark_id: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Handle benchmarks of multi-statement transactions
+async fn benchmark_transaction_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<TransactionBenchmarkRequest>,
+) -> Result<Json<TransactionBenchmarkResponse>, Rejection> {
+    let _permit = state
+        .benchmark_slot(&user_from_headers(&headers), payload.request_id.as_deref())
+        .await?;
+
+    let failed = |error: String| {
+        Ok(Json(TransactionBenchmarkResponse {
+            result: None,
+            benchmark_id: None,
+            error: Some(error),
+        }))
+    };
+
+    let connection = match state.connection(payload.connection.as_deref()) {
+        Ok(connection) => connection,
+        Err(e) => return failed(e),
+    };
+    state.admit(&connection)?;
+    if payload.mode == TransactionMode::RolledBack
+        && !state.config.benchmark.allow_write_transactions
+    {
+        return failed(
+            "Write transactions are disabled; set allow_write_transactions under [benchmark]"
+                .to_string(),
+        );
+    }
+    if let Err(e) = transaction::check_transaction(&payload.statements, payload.mode) {
+        return failed(e);
+    }
+    for statement in &payload.statements {
+        if let Err(violation) = state.enforce_policy_on(&connection, statement).await {
+            return failed(violation);
+        }
+    }
+
+    let config = state.config.benchmark.resolve(payload.config.as_ref());
+    let benchmark_suite =
+        BenchmarkSuite::new(connection.db.clone(), state.advisor.current(), Some(config));
+
+    let benchmarked = benchmark_suite
+        .benchmark_transaction(&payload.statements, payload.mode)
+        .await;
+    state.record_outcome(&connection, &benchmarked);
+    match benchmarked {
+        Ok(result) => {
+            let mut stored = result.clone();
+            stored.statements = result
+                .statements
+                .iter()
+                .map(|statement| state.config.masking.query(statement))
+                .collect();
+            let label = stored.statements.join("; ");
+            let benchmark_id =
+                record_benchmark(&state, BenchmarkKind::Transaction, label, &stored).await;
+            Ok(Json(TransactionBenchmarkResponse {
+                result: Some(result),
+                benchmark_id,
+                error: None,
+            }))
+        }
+        Err(e) => failed(e.to_string()),
+    }
+}
+
+/// Benchmark a sample of the literal variants of a query found in history
+async fn benchmark_group_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<QueryGroupRequest>,
+) -> Result<Json<QueryGroupResponse>, Rejection> {
+    let _permit = state
+        .benchmark_slot(&user_from_headers(&headers), payload.request_id.as_deref())
+        .await?;
+
+    let failed = |error: String| {
+        Ok(Json(QueryGroupResponse {
+            report: None,
+            error: Some(error),
+        }))
+    };
+
+    if state.config.masking.mask_literals {
+        return failed(
+            "Literal masking is enabled, so history holds no literal variants to benchmark"
+                .to_string(),
+        );
+    }
+    let fingerprint = match (payload.fingerprint, payload.history_id) {
+        (Some(fingerprint), _) => fingerprint,
+        (None, Some(id)) => match state.storage.get_history(id).await {
+            Ok(Some(entry)) => findings::fingerprint(&entry.query),
+            Ok(None) => return Err(StatusCode::NOT_FOUND.into()),
+            Err(e) => return failed(e.to_string()),
+        },
+        (None, None) => return failed("Give a fingerprint or a history_id".to_string()),
+    };
+
+    let entries = match state.storage.list_history(GROUP_HISTORY_SCAN, 0).await {
+        Ok(entries) => entries,
+        Err(e) => return failed(e.to_string()),
+    };
+    let variants = query_group::collect_variants(&entries, &fingerprint);
+    if variants.is_empty() {
+        return failed(format!(
+            "No query with fingerprint {} among the last {} analyses",
+            fingerprint, GROUP_HISTORY_SCAN
+        ));
+    }
+    let variants_found = variants.len();
+    let sample_size = payload
+        .sample_size
+        .unwrap_or(DEFAULT_GROUP_SAMPLE)
+        .clamp(1, MAX_GROUP_SAMPLE);
+    let sample = query_group::representative_sample(variants, sample_size);
+
+    let connection = match state.connection(payload.connection.as_deref()) {
+        Ok(connection) => connection,
+        Err(e) => return failed(e),
+    };
+    state.admit(&connection)?;
+    for variant in &sample {
+        if let Err(violation) = state.enforce_policy_on(&connection, &variant.query).await {
+            return failed(violation);
+        }
+    }
+
+    let config = state.config.benchmark.resolve(payload.config.as_ref());
+    let benchmark_suite = BenchmarkSuite::new(connection.db, state.advisor.current(), Some(config));
+    let report = benchmark_suite
+        .benchmark_group(&fingerprint, variants_found, sample)
+        .await;
+    Ok(Json(QueryGroupResponse {
+        report: Some(report),
+        error: None,
+    }))
+}
+
+/// Store a benchmark result so its runs can be fetched later
+///
+/// Plans are masked like those of history entries; the caller masks the
+/// query text. A failure to store is logged and the id left out.
+async fn record_benchmark(
+    state: &AppState,
+    kind: BenchmarkKind,
+    label: String,
+    result: &impl Serialize,
+) -> Option<i64> {
+    let stored = match serde_json::to_value(result) {
+        Ok(mut value) => {
+            state.config.masking.plan(&mut value);
+            state
+                .storage
+                .record_benchmark(NewBenchmark {
+                    kind,
+                    label,
+                    result: value,
+                })
+                .await
+                .map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    match stored {
+        Ok(benchmark) => Some(benchmark.id),
+        Err(e) => {
+            tracing::warn!("Failed to store benchmark result: {}", e);
+            None
+        }
+    }
+}
+
+/// Fetch the per-run data of a stored benchmark as columnar JSON or CSV
+async fn benchmark_runs_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<BenchmarkRunsParams>,
+) -> Result<Response, StatusCode> {
+    let benchmark = match state.storage.get_benchmark(id).await {
+        Ok(Some(benchmark)) => benchmark,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to load benchmark {}: {}", id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let columns = match benchmark.kind {
+        BenchmarkKind::Query => serde_json::from_value::<BenchmarkResult>(benchmark.result)
+            .map(|result| RunTable::from_query(&result)),
+        BenchmarkKind::Transaction => {
+            serde_json::from_value::<TransactionBenchmarkResult>(benchmark.result)
+                .map(|result| RunTable::from_transaction(&result))
+        }
+    }
+    .map_err(|e| format!("Stored benchmark could not be read: {}", e));
+
+    if params.format == RunsFormat::Csv {
+        let columns = columns.map_err(|e| {
+            tracing::error!("Benchmark {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        let disposition = format!("attachment; filename=\"benchmark-{}-runs.csv\"", id);
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (header::CONTENT_DISPOSITION, disposition),
+            ],
+            columns.to_csv(),
+        )
+            .into_response());
+    }
+
+    let (columns, error) = match columns {
+        Ok(columns) => (Some(columns), None),
+        Err(e) => (None, Some(e)),
+    };
+    Ok(Json(BenchmarkRunsResponse {
+        benchmark_id: benchmark.id,
+        kind: benchmark.kind,
+        label: benchmark.label,
+        created_at: benchmark.created_at,
+        run_count: columns.as_ref().map_or(0, RunTable::row_count),
+        columns,
+        error,
+    })
+    .into_response())
+}
+
+/// Handle benchmark comparison requests
+async fn benchmark_compare_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<BenchmarkCompareRequest>,
+) -> Result<Json<BenchmarkCompareResponse>, Rejection> {
+    let _permit = state
+        .benchmark_slot(&user_from_headers(&headers), payload.request_id.as_deref())
+        .await?;
+
+    let connection = match state.connection(payload.connection.as_deref()) {
+        Ok(connection) => connection,
+        Err(e) => {
+            return Ok(Json(BenchmarkCompareResponse {
+                comparison: None,
+                error: Some(e),
+            }))
+        }
+    };
+    state.admit(&connection)?;
+    for query in [&payload.query_a, &payload.query_b] {
+        if let Err(violation) = state.enforce_policy_on(&connection, query).await {
+            return Ok(Json(BenchmarkCompareResponse {
+                comparison: None,
+                error: Some(violation),
+            }));
+        }
+    }
+
+    let config = state.config.benchmark.resolve(payload.config.as_ref());
+    let benchmark_suite =
+        BenchmarkSuite::new(connection.db.clone(), state.advisor.current(), Some(config));
+
+    // Counting is cheap compared to the benchmark, so fail fast if it errors
+    let row_counts = if payload.check_row_counts {
+        match benchmark_suite
+            .compare_row_counts(&payload.query_a, &payload.query_b)
+            .await
+        {
+            Ok(check) => Some(check),
+            Err(e) => {
+                return Ok(Json(BenchmarkCompareResponse {
+                    comparison: None,
+                    error: Some(format!("Row count check failed: {}", e)),
+                }))
+            }
+        }
+    } else {
+        None
+    };
+
+    let results = match &payload.check_results {
+        Some(options) => {
+            let max_rows = state.config.benchmark.result_rows(options.max_rows);
+            match benchmark_suite
+                .compare_results(&payload.query_a, &payload.query_b, options, max_rows)
+                .await
+            {
+                Ok(check) => Some(check),
+                Err(e) => {
+                    return Ok(Json(BenchmarkCompareResponse {
+                        comparison: None,
+                        error: Some(format!("Result check failed: {}", e)),
+                    }))
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Run benchmarks for both queries
+    let result_a = benchmark_suite.benchmark_query(&payload.query_a).await;
+    let result_b = benchmark_suite.benchmark_query(&payload.query_b).await;
+    state.record_outcome(&connection, &result_a);
+    state.record_outcome(&connection, &result_b);
+
+    match (result_a, result_b) {
+        (Ok(bench_a), Ok(bench_b)) => {
+            let mut comparison = benchmark_suite.compare_benchmarks(
+                &bench_a,
+                &bench_b,
+                payload.label_a,
+                payload.label_b,
+            );
+            comparison.row_counts = row_counts;
+            comparison.results = results;
+            Ok(Json(BenchmarkCompareResponse {
+                comparison: Some(comparison),
+                error: None,
+            }))
+        }
+        (Err(e), _) | (_, Err(e)) => Ok(Json(BenchmarkCompareResponse {
+            comparison: None,
+            error: Some(format!("Benchmark failed: {}", e)),
+        })),
+    }
+}
+
+/// Handle requests for a before/after report on a query rewrite
+async fn rewrite_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<RewriteRequest>,
+) -> Result<Json<RewriteResponse>, StatusCode> {
+    // Benchmarks share the concurrency limit; explain-only reports do not
+    let benchmark = payload.benchmark.unwrap_or(true);
+    let _permit = if benchmark {
+        Some(
+            state
+                .benchmark_slot(&user_from_headers(&headers), payload.request_id.as_deref())
+                .await?,
+        )
+    } else {
+        None
+    };
+
+    for query in [&payload.before, &payload.after] {
+        if let Err(validation_error) = crate::web::validate_query(query) {
+            return Ok(Json(RewriteResponse {
+                report: None,
+                error: Some(validation_error),
+            }));
+        }
+        if let Err(violation) = state.enforce_policy(query).await {
+            return Ok(Json(RewriteResponse {
+                report: None,
+                error: Some(violation),
+            }));
+        }
+    }
+
+    let config = state.config.benchmark.resolve(payload.config.as_ref());
+    let benchmark_suite = BenchmarkSuite::new(
+        state.default_db().clone(),
+        state.advisor.current(),
+        Some(config),
+    );
+
+    match benchmark_suite
+        .compare_rewrite(&payload.before, &payload.after, benchmark)
+        .await
+    {
+        Ok(report) => Ok(Json(RewriteResponse {
+            report: Some(report),
+            error: None,
+        })),
+        Err(e) => Ok(Json(RewriteResponse {
+            report: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Handle requests to analyze the statements in an ORM debug log
+async fn orm_log_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<OrmLogRequest>,
+) -> Result<Json<WorkloadResponse>, Rejection> {
+    let statements = workload::parse_orm_log(payload.format, &payload.log);
+    if statements.is_empty() {
+        return Ok(Json(WorkloadResponse {
+            report: None,
+            error: Some("No SQL statements found in the log".to_string()),
+        }));
+    }
+
+    let analyze = payload.analyze.unwrap_or(true);
+    let user = user_from_headers(&headers);
+    let connection = state.connections.default_connection();
+    if analyze {
+        state.check_budget(&user)?;
+        state.admit(&connection)?;
+    }
+    let max_queries = payload
+        .max_queries
+        .unwrap_or(DEFAULT_WORKLOAD_QUERIES)
+        .min(MAX_WORKLOAD_QUERIES);
+
+    let mut entries = Vec::new();
+    for (i, query) in workload::group_statements(&statements)
+        .into_iter()
+        .enumerate()
+    {
+        let mut entry = WorkloadEntry {
+            query,
+            analysis: None,
+            execution_time_ms: None,
+            error: None,
+        };
+        if analyze && i < max_queries {
+            match analyze_workload_query(&state, &connection, &user, &entry.query).await {
+                Ok((analysis, execution_time_ms)) => {
+                    entry.analysis = Some(analysis);
+                    entry.execution_time_ms = Some(execution_time_ms);
+                }
+                Err(e) => entry.error = Some(e),
+            }
+        }
+        entries.push(entry);
+    }
+
+    Ok(Json(WorkloadResponse {
+        report: Some(WorkloadReport {
+            total_statements: statements.len(),
+            queries: entries,
+        }),
+        error: None,
+    }))
+}
+
+/// Record the plans of a PostgreSQL log written by auto_explain in history
+async fn auto_explain_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<AutoExplainRequest>,
+) -> Result<Json<AutoExplainResponse>, StatusCode> {
+    let failed = |error: String| {
+        Ok(Json(AutoExplainResponse {
+            report: None,
+            error: Some(error),
+        }))
+    };
+
+    let connection = match state.connection(payload.connection.as_deref()) {
+        Ok(connection) => connection,
+        Err(e) => return failed(e),
+    };
+    let log = auto_explain::parse_auto_explain_log(&payload.log);
+    if log.plans.is_empty() && log.skipped.is_empty() {
+        return failed("No auto_explain entries found in the log".to_string());
+    }
+
+    let recorder = PlanRecorder::new(
+        state.storage.clone(),
+        state.advisor.clone(),
+        state.config.masking.clone(),
+    );
+    Ok(Json(AutoExplainResponse {
+        report: Some(recorder.import(log, &connection.name).await),
+        error: None,
+    }))
+}
+
+/// List the statements of a database that take the most time, from pg_stat_statements
+async fn top_statements_handler(
+    State(state): State<AppState>,
+    Query(params): Query<TopStatementsParams>,
+) -> Result<Json<TopStatementsResponse>, Rejection> {
+    let failed = |error: String| {
+        Ok(Json(TopStatementsResponse {
+            statements: None,
+            error: Some(error),
+        }))
+    };
+
+    let connection = match state.connection(params.connection.as_deref()) {
+        Ok(connection) => connection,
+        Err(e) => return failed(e),
+    };
+    state.admit(&connection)?;
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_TOP_STATEMENTS)
+        .clamp(1, MAX_TOP_STATEMENTS);
+    let statements = connection.db.top_statements(params.order_by, limit).await;
+    state.record_outcome(&connection, &statements);
+    match statements {
+        Ok(mut statements) => {
+            for statement in &mut statements {
+                statement.query = state.config.masking.query(&statement.query);
+            }
+            Ok(Json(TopStatementsResponse {
+                statements: Some(statements),
+                error: None,
+            }))
+        }
+        Err(e) => failed(e.to_string()),
+    }
+}
+
+/// Explain one distinct query of a workload and run the advisor on its plan
+///
+/// Explaining executes the query, so only fully bound read-only queries are
+/// analyzed.
+async fn analyze_workload_query(
+    state: &AppState,
+    connection: &ManagedConnection,
+    user: &str,
+    query: &WorkloadQuery,
+) -> Result<(crate::advisor::AdvisorAnalysis, f64), String> {
+    if !query.bound {
+        return Err("The log does not show values for all placeholders".to_string());
+    }
+    let db = &connection.db;
+    if !workload::is_read_only(&query.sql, db.engine_type().dialect().as_ref()) {
+        return Err("Only read-only queries are analyzed, since explaining runs them".to_string());
+    }
+    state.enforce_policy_on(connection, &query.sql).await?;
+    state
+        .budgets
+        .check(user, now_millis())
+        .map_err(|e| e.to_string())?;
+
+    let explained = db.explain(&query.sql).await;
+    state.record_outcome(connection, &explained);
+    let plan = explained.map_err(|e| e.to_string())?;
+    state.budgets.record(
+        user,
+        now_millis(),
+        plan.execution_time,
+        plan.rows_processed(),
+    );
+    let mut context = AnalysisContext {
+        estimates_only: !db.supports_feature(&DatabaseFeature::ActualRowCounts),
+        ..AnalysisContext::default()
+    };
+    if db.engine_type() == EngineType::PostgreSQL {
+        match db.relation_sizes(&plan.relation_names()).await {
+            Ok(sizes) => context.relation_sizes = sizes,
+            Err(e) => tracing::warn!("Failed to fetch relation sizes: {}", e),
+        }
+    }
+    let analysis = state
+        .advisor
+        .current()
+        .analyze_plan_with_context(&plan, &context);
+    Ok((analysis, plan.execution_time))
+}
+
+/// Handle index health report requests
+async fn index_health_handler(
+    State(state): State<AppState>,
+    Query(params): Query<IndexHealthParams>,
+) -> Result<Json<IndexHealthResponse>, StatusCode> {
+    match state
+        .default_db()
+        .index_health_report(params.schema.as_deref())
+        .await
+    {
+        Ok(indexes) => Ok(Json(IndexHealthResponse {
+            indexes: Some(indexes),
+            error: None,
+        })),
+        Err(e) => Ok(Json(IndexHealthResponse {
+            indexes: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// List configured database connections without exposing credentials
+async fn connections_handler(State(state): State<AppState>) -> Json<ConnectionsResponse> {
+    Json(ConnectionsResponse {
+        connections: state.connection_summaries(),
+    })
+}
+
+/// Report the version, features and health of a registered connection
+async fn connection_info_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<ConnectionInfoResponse>, StatusCode> {
+    match state.connections.info(&name).await {
+        Ok(info) => Ok(Json(ConnectionInfoResponse {
+            info: Some(info),
+            error: None,
+        })),
+        Err(ConnectionError::NotFound(_)) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Ok(Json(ConnectionInfoResponse {
+            info: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Report which optional integrations the role of a registered connection can use
+async fn connection_integrations_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<IntegrationsResponse>, StatusCode> {
+    let connection = state
+        .connections
+        .get(&name)
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    match connection.db.integrations().await {
+        Ok(integrations) => Ok(Json(IntegrationsResponse {
+            integrations: Some(integrations),
+            error: None,
+        })),
+        Err(e) => Ok(Json(IntegrationsResponse {
+            integrations: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Connect to a database and register it under a name
+async fn register_connection_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<RegisterConnectionRequest>,
+) -> Result<Json<ConnectionResponse>, StatusCode> {
+    let engine_type = match EngineFactory::detect_engine_type(&payload.url) {
+        Ok(engine_type) => engine_type,
+        Err(e) => {
+            return Ok(Json(ConnectionResponse {
+                connection: None,
+                error: Some(e.to_string()),
+            }))
+        }
+    };
+    let config = ConnectionConfig {
+        engine_type,
+        connection_string: payload.url,
+        max_connections: payload.max_connections,
+        timeout_seconds: payload.timeout_seconds,
+        password_source: None,
+        tls: payload.tls,
+        search_path: payload.search_path,
+    };
+
+    match state.connections.connect(&payload.name, config).await {
+        Ok(summary) => {
+            tracing::info!(
+                "Registered connection {} to {}",
+                summary.name,
+                summary.url.as_deref().unwrap_or_default()
+            );
+            Ok(Json(ConnectionResponse {
+                connection: Some(summary),
+                error: None,
+            }))
+        }
+        Err(e) => Ok(Json(ConnectionResponse {
+            connection: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Check that a registered connection can run a query
+async fn test_connection_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<ConnectionTestResponse>, StatusCode> {
+    match state.connections.test(&name).await {
+        Ok(test) => Ok(Json(ConnectionTestResponse {
+            test: Some(test),
+            error: None,
+        })),
+        Err(ConnectionError::NotFound(_)) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Ok(Json(ConnectionTestResponse {
+            test: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Drop a registered connection
+async fn remove_connection_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<DeleteResponse>, StatusCode> {
+    match state.connections.remove(&name) {
+        Ok(deleted) => Ok(Json(DeleteResponse {
+            deleted,
+            error: None,
+        })),
+        Err(e) => Ok(Json(DeleteResponse {
+            deleted: false,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Report the caller's execution budget usage today
+async fn budget_handler(State(state): State<AppState>, headers: HeaderMap) -> Json<BudgetResponse> {
+    let user = user_from_headers(&headers);
+    Json(BudgetResponse {
+        usage: state.budgets.usage(&user, now_millis()),
+        limits: state.budgets.config().clone(),
+        user,
+    })
+}
+
+/// Show whether telemetry is enabled and the report it would send next
+async fn telemetry_handler(State(state): State<AppState>) -> Json<TelemetryResponse> {
+    let enabled = state.telemetry.is_enabled();
+    Json(TelemetryResponse {
+        enabled,
+        endpoint: state
+            .telemetry
+            .config()
+            .endpoint
+            .clone()
+            .filter(|_| enabled),
+        interval_secs: state.telemetry.config().interval_secs,
+        pending: enabled.then(|| state.telemetry.pending()),
+    })
+}
+
+/// List stored analyses, newest first
+async fn list_history_handler(
+    State(state): State<AppState>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<HistoryListResponse>, StatusCode> {
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    let entries = match &params.correlation_id {
+        Some(id) => state.storage.find_history_by_correlation(id, limit).await,
+        None => state.storage.list_history(limit, offset).await,
+    };
+    match entries {
+        Ok(entries) => Ok(Json(HistoryListResponse {
+            entries: Some(entries),
+            error: None,
+        })),
+        Err(e) => Ok(Json(HistoryListResponse {
+            entries: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Re-run the advisor over recent stored analyses with the current rules
+async fn reanalyze_handler(
+    State(state): State<AppState>,
+    payload: Option<Json<ReanalyzeRequest>>,
+) -> Result<Json<ReanalyzeResponse>, StatusCode> {
+    let request = payload.map(|Json(request)| request).unwrap_or_default();
+    let limit = request
+        .limit
+        .unwrap_or(DEFAULT_ADVISOR_RERUN)
+        .clamp(1, MAX_ADVISOR_RERUN);
+
+    match reanalysis::reanalyze(
+        state.storage.as_ref(),
+        &state.advisor.current(),
+        limit,
+        &state.config.findings,
+        now_millis(),
+    )
+    .await
+    {
+        Ok(report) => {
+            tracing::info!(
+                "Re-analyzed {} stored analyses; {} scores changed",
+                report.updated,
+                report.score_changed
+            );
+            Ok(Json(ReanalyzeResponse {
+                report: Some(report),
+                error: None,
+            }))
+        }
+        Err(e) => Ok(Json(ReanalyzeResponse {
+            report: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Fetch a single stored analysis
+async fn get_history_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<HistoryEntryResponse>, StatusCode> {
+    match state.storage.get_history(id).await {
+        Ok(Some(entry)) => Ok(Json(HistoryEntryResponse {
+            entry: Some(entry),
+            error: None,
+        })),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Ok(Json(HistoryEntryResponse {
+            entry: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Compare the tables stored with an analysis with the current schema or another analysis
+async fn schema_diff_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<SchemaDiffParams>,
+) -> Result<Json<SchemaDiffResponse>, StatusCode> {
+    let failed = |error: String| {
+        Ok(Json(SchemaDiffResponse {
+            changes: None,
+            error: Some(error),
+        }))
+    };
+
+    let before = match stored_table_definitions(&state, id).await {
+        Ok(Some(tables)) => tables,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => return failed(e),
+    };
+    let after = match params.against {
+        Some(other) => match stored_table_definitions(&state, other).await {
+            Ok(Some(tables)) => tables,
+            Ok(None) => return Err(StatusCode::NOT_FOUND),
+            Err(e) => return failed(e),
+        },
+        None => {
+            let names: Vec<String> = before.iter().map(|t| t.table_name.clone()).collect();
+            match state.default_db().table_definitions(&names).await {
+                Ok(tables) => tables,
+                Err(e) => return failed(e.to_string()),
+            }
+        }
+    };
+
+    Ok(Json(SchemaDiffResponse {
+        changes: Some(table_definitions::diff_definitions(&before, &after)),
+        error: None,
+    }))
+}
+
+/// Estimated cost against execution time of stored analyses, per connection
+async fn cost_time_handler(
+    State(state): State<AppState>,
+    Query(params): Query<CostTimeParams>,
+) -> Result<Json<CostTimeResponse>, StatusCode> {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_COST_TIME_POINTS)
+        .clamp(1, MAX_COST_TIME_POINTS);
+    match state
+        .storage
+        .cost_time_points(params.connection.as_deref(), limit)
+        .await
+    {
+        Ok(points) => Ok(Json(CostTimeResponse {
+            series: Some(calibration::by_connection(points)),
+            error: None,
+        })),
+        Err(e) => Ok(Json(CostTimeResponse {
+            series: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Day-by-day analyses, scores, new findings, and regressions of a connection
+///
+/// History is kept by connection name, so a connection that was removed
+/// still has its trend reported.
+async fn connection_trend_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<TrendParams>,
+) -> Result<Json<ConnectionTrendResponse>, StatusCode> {
+    let days = params
+        .days
+        .unwrap_or(DEFAULT_TREND_DAYS)
+        .clamp(1, MAX_TREND_DAYS);
+    match trends::connection_trend(state.storage.as_ref(), &name, days, now_millis()).await {
+        Ok(trend) => Ok(Json(ConnectionTrendResponse {
+            trend: Some(trend),
+            error: None,
+        })),
+        Err(e) => Ok(Json(ConnectionTrendResponse {
+            trend: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Overlay the distinct plans that the query group of an analysis ran with
+///
+/// The analysis's own plan comes first; the other shapes follow from the
+/// most recently seen.
+async fn plan_overlay_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<PlanOverlayParams>,
+) -> Result<Json<PlanOverlayResponse>, StatusCode> {
+    let failed = |error: String| {
+        Ok(Json(PlanOverlayResponse {
+            fingerprint: None,
+            shapes: None,
+            shapes_found: None,
+            overlay: None,
+            error: Some(error),
+        }))
+    };
+
+    let entry = match state.storage.get_history(id).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => return failed(e.to_string()),
+    };
+    let fingerprint = findings::fingerprint(&entry.query);
+    let recent = match state.storage.list_history(GROUP_HISTORY_SCAN, 0).await {
+        Ok(entries) => entries,
+        Err(e) => return failed(e.to_string()),
+    };
+
+    let mut entries = Vec::new();
+    let mut plans = Vec::new();
+    for entry in std::iter::once(entry).chain(
+        recent
+            .into_iter()
+            .filter(|e| e.id != id && findings::fingerprint(&e.query) == fingerprint),
+    ) {
+        match serde_json::from_value::<ExecutionPlan>(entry.plan.clone()) {
+            Ok(plan) => {
+                plans.push(plan);
+                entries.push(entry);
+            }
+            Err(e) if entry.id == id => {
+                return failed(format!("Invalid plan in analysis {}: {}", id, e))
+            }
+            Err(_) => {}
+        }
+    }
+
+    let groups = plan_overlay::group_by_shape(&plans);
+    let max_plans = params
+        .max_plans
+        .unwrap_or(DEFAULT_OVERLAY_PLANS)
+        .clamp(1, MAX_OVERLAY_PLANS);
+    let shapes_found = groups.len();
+    let overlaid = &groups[..shapes_found.min(max_plans)];
+
+    let shapes = overlaid
+        .iter()
+        .map(|members| {
+            let entry = &entries[members[0]];
+            PlanShapeSummary {
+                history_id: entry.id,
+                query: entry.query.clone(),
+                execution_time_ms: entry.execution_time_ms,
+                last_seen: entry.created_at,
+                occurrences: members.len(),
+            }
+        })
+        .collect();
+    let representatives: Vec<&ExecutionPlan> =
+        overlaid.iter().map(|members| &plans[members[0]]).collect();
+
+    Ok(Json(PlanOverlayResponse {
+        fingerprint: Some(fingerprint),
+        shapes: Some(shapes),
+        shapes_found: Some(shapes_found),
+        overlay: Some(plan_overlay::overlay_plans(&representatives)),
+        error: None,
+    }))
+}
+
+/// Table definitions stored with an analysis; `None` if the analysis does not exist
+async fn stored_table_definitions(
+    state: &AppState,
+    id: i64,
+) -> Result<Option<Vec<TableDefinition>>, String> {
+    let Some(entry) = state
+        .storage
+        .get_history(id)
+        .await
+        .map_err(|e| e.to_string())?
+    else {
+        return Ok(None);
+    };
+    let tables = entry
+        .table_definitions
+        .ok_or_else(|| format!("Analysis {} was stored without table definitions", entry.id))?;
+    serde_json::from_value(tables)
+        .map(Some)
+        .map_err(|e| format!("Invalid table definitions in analysis {}: {}", id, e))
+}
+
+/// List tracked advisor findings, most recently reported first
+async fn list_findings_handler(
+    State(state): State<AppState>,
+    Query(filter): Query<FindingFilter>,
+) -> Result<Json<FindingsResponse>, StatusCode> {
+    match state.storage.list_findings(&filter).await {
+        Ok(found) => Ok(Json(FindingsResponse {
+            findings: Some(found),
+            error: None,
+        })),
+        Err(e) => Ok(Json(FindingsResponse {
+            findings: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Mark a finding as acknowledged
+async fn acknowledge_finding_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<FindingResponse>, StatusCode> {
+    match findings::acknowledge(state.storage.as_ref(), id, now_millis()).await {
+        Ok(Some(finding)) => Ok(Json(FindingResponse {
+            finding: Some(finding),
+            error: None,
+        })),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Ok(Json(FindingResponse {
+            finding: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Delete a stored analysis
+async fn delete_history_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<DeleteResponse>, StatusCode> {
+    match state.storage.delete_history(id).await {
+        Ok(deleted) => Ok(Json(DeleteResponse {
+            deleted,
+            error: None,
+        })),
+        Err(e) => Ok(Json(DeleteResponse {
+            deleted: false,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// List saved queries
+async fn list_saved_queries_handler(
+    State(state): State<AppState>,
+) -> Result<Json<SavedQueriesResponse>, StatusCode> {
+    match state.storage.list_saved_queries().await {
+        Ok(queries) => Ok(Json(SavedQueriesResponse {
+            queries: Some(queries),
+            error: None,
+        })),
+        Err(e) => Ok(Json(SavedQueriesResponse {
+            queries: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Save a named query
+async fn save_query_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<NewSavedQuery>,
+) -> Result<Json<SavedQueryResponse>, StatusCode> {
+    if payload.name.trim().is_empty() || payload.query.trim().is_empty() {
+        return Ok(Json(SavedQueryResponse {
+            query: None,
+            error: Some("Saved queries need a name and a query".to_string()),
+        }));
+    }
+
+    match state.storage.save_query(payload).await {
+        Ok(query) => Ok(Json(SavedQueryResponse {
+            query: Some(query),
+            error: None,
+        })),
+        Err(e) => Ok(Json(SavedQueryResponse {
+            query: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Fetch a single saved query
+async fn get_saved_query_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<SavedQueryResponse>, StatusCode> {
+    match state.storage.get_saved_query(id).await {
+        Ok(Some(query)) => Ok(Json(SavedQueryResponse {
+            query: Some(query),
+            error: None,
+        })),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Ok(Json(SavedQueryResponse {
+            query: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Delete a saved query
+async fn delete_saved_query_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<DeleteResponse>, StatusCode> {
+    match state.storage.delete_saved_query(id).await {
+        Ok(deleted) => Ok(Json(DeleteResponse {
+            deleted,
+            error: None,
+        })),
+        Err(e) => Ok(Json(DeleteResponse {
+            deleted: false,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Fetch the plan pinned for a saved query
+async fn get_plan_pin_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<PlanPinResponse>, StatusCode> {
+    match state.storage.get_plan_pin(id).await {
+        Ok(Some(pin)) => Ok(Json(PlanPinResponse {
+            pin: Some(pin),
+            error: None,
+        })),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Ok(Json(PlanPinResponse {
+            pin: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Pin the plan of a stored analysis as the expected plan of a saved query
+async fn pin_plan_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(payload): Json<PinPlanRequest>,
+) -> Result<Json<PlanPinResponse>, StatusCode> {
+    let failed = |error: String| {
+        Ok(Json(PlanPinResponse {
+            pin: None,
+            error: Some(error),
+        }))
+    };
+
+    let saved = match state.storage.get_saved_query(id).await {
+        Ok(Some(saved)) => saved,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => return failed(e.to_string()),
+    };
+    let entry = match state.storage.get_history(payload.history_id).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return failed(format!("Analysis {} does not exist", payload.history_id)),
+        Err(e) => return failed(e.to_string()),
+    };
+    let pin = match PlanPin::new(&saved, &entry, now_millis()) {
+        Ok(Some(pin)) => pin,
+        Ok(None) => {
+            return failed(format!(
+                "Analysis {} is of a different query than saved query '{}'",
+                entry.id, saved.name
+            ))
+        }
+        Err(e) => return failed(format!("Invalid plan in analysis {}: {}", entry.id, e)),
+    };
+
+    match state.storage.put_plan_pin(&pin).await {
+        Ok(()) => Ok(Json(PlanPinResponse {
+            pin: Some(pin),
+            error: None,
+        })),
+        Err(e) => failed(e.to_string()),
+    }
+}
+
+/// Unpin the plan of a saved query
+async fn unpin_plan_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<DeleteResponse>, StatusCode> {
+    match state.storage.delete_plan_pin(id).await {
+        Ok(deleted) => Ok(Json(DeleteResponse {
+            deleted,
+            error: None,
+        })),
+        Err(e) => Ok(Json(DeleteResponse {
+            deleted: false,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Report stored history volume and the configured retention policy
+async fn storage_stats_handler(
+    State(state): State<AppState>,
+) -> Result<Json<StorageStatsResponse>, StatusCode> {
+    let retention = state.config.retention.clone();
+    match state.storage.history_stats().await {
+        Ok(history) => Ok(Json(StorageStatsResponse {
+            history: Some(history),
+            retention,
+            error: None,
+        })),
+        Err(e) => Ok(Json(StorageStatsResponse {
+            history: None,
+            retention,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Report the connection pool statistics of every registered connection
+async fn pool_metrics_handler(State(state): State<AppState>) -> Json<PoolsResponse> {
+    Json(PoolsResponse {
+        pools: state
+            .connections
+            .list()
+            .into_iter()
+            .filter_map(|connection| Some((connection.name.clone(), connection.db.pool_metrics()?)))
+            .collect(),
+    })
+}
+
+/// List the advisor rules with their current thresholds
+async fn list_rules_handler(State(state): State<AppState>) -> Json<RulesResponse> {
+    let advisor = state.advisor.current();
+    Json(RulesResponse {
+        rules: RULES
+            .iter()
+            .map(|rule| rule.status(advisor.config()))
+            .collect(),
+        overrides: state.advisor.overrides(),
+    })
+}
+
+/// Run advisor rules over recent stored analyses without changing them or the advisor
+async fn dry_run_rules_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<DryRunRequest>,
+) -> Result<Json<DryRunResponse>, StatusCode> {
+    let limit = payload
+        .limit
+        .unwrap_or(DEFAULT_ADVISOR_RERUN)
+        .clamp(1, MAX_ADVISOR_RERUN);
+    let result = match Corpus::from_history(state.storage.as_ref(), limit).await {
+        Ok(corpus) => payload
+            .dry_run
+            .run(state.advisor.current().config(), &corpus),
+        Err(e) => Err(e.to_string()),
+    };
+
+    match result {
+        Ok(report) => Ok(Json(DryRunResponse {
+            report: Some(report),
+            error: None,
+        })),
+        Err(e) => Ok(Json(DryRunResponse {
+            report: None,
+            error: Some(e),
+        })),
+    }
+}
+
+/// Enable or disable an advisor rule or change its thresholds, persisting the change
+async fn update_rule_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateRuleRequest>,
+) -> Result<Json<UpdateRuleResponse>, StatusCode> {
+    let rule = find_rule(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let failed = |error: String| {
+        Ok(Json(UpdateRuleResponse {
+            rule: None,
+            stored: false,
+            error: Some(error),
+        }))
+    };
+
+    let mut overrides = state.advisor.overrides();
+    match payload.enabled {
+        Some(true) => {
+            overrides.disabled_rules.remove(rule.id);
+        }
+        Some(false) => {
+            overrides.disabled_rules.insert(rule.id.to_string());
+        }
+        None => {}
+    }
+    for (name, value) in payload.thresholds {
+        if !rule.thresholds.contains(&name.as_str()) {
+            return failed(format!("Rule {} does not read threshold {}", rule.id, name));
+        }
+        overrides.thresholds.insert(name, value);
+    }
+    if let Err(e) = state.advisor.preview(&overrides) {
+        return failed(e);
+    }
+
+    let stored = match serde_json::to_value(&overrides) {
+        Ok(value) => state
+            .storage
+            .put_advisor_rules(&value)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = stored.and_then(|_| state.advisor.apply(overrides)) {
+        return failed(e);
+    }
+
+    Ok(Json(UpdateRuleResponse {
+        rule: Some(rule.status(state.advisor.current().config())),
+        stored: true,
+        error: None,
+    }))
+}
+
+/// Delete stored history, either all of it or what exceeds the given limits
+async fn purge_handler(
+    State(state): State<AppState>,
+    payload: Option<Json<PurgeRequest>>,
+) -> Result<Json<PurgeResponse>, StatusCode> {
+    let request = payload.map(|Json(request)| request).unwrap_or_default();
+
+    let result = if request.all {
+        state
+            .storage
+            .purge_history()
+            .await
+            .map(|deleted| (deleted, None))
+    } else {
+        let policy = if request.max_age_days.is_some()
+            || request.max_count.is_some()
+            || request.max_size_mb.is_some()
+        {
+            RetentionPolicy {
+                max_age_days: request.max_age_days,
+                max_count: request.max_count,
+                max_size_mb: request.max_size_mb,
+                ..state.config.retention.clone()
+            }
+        } else {
+            state.config.retention.clone()
+        };
+
+        state
+            .storage
+            .prune_history(&policy, now_millis())
+            .await
+            .map(|report| (report.total(), Some(report)))
+    };
+
+    match result {
+        Ok((deleted, report)) => Ok(Json(PurgeResponse {
+            deleted,
+            report,
+            stats: state.storage.history_stats().await.ok(),
+            error: None,
+        })),
+        Err(e) => Ok(Json(PurgeResponse {
+            deleted: 0,
+            report: None,
+            stats: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Download all stored state as an archive
+async fn export_handler(
+    State(state): State<AppState>,
+) -> Result<([(header::HeaderName, &'static str); 1], Json<StateArchive>), StatusCode> {
+    match archive::export_state(state.storage.as_ref(), state.connection_summaries()).await {
+        Ok(archive) => Ok((
+            [(
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"sqltrace-export.json\"",
+            )],
+            Json(archive),
+        )),
+        Err(e) => {
+            tracing::error!("State export failed: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Add the contents of an exported archive to storage
+async fn import_handler(
+    State(state): State<AppState>,
+    Json(archive): Json<StateArchive>,
+) -> Result<Json<ImportResponse>, StatusCode> {
+    match archive::import_state(state.storage.as_ref(), archive).await {
+        Ok(report) => Ok(Json(ImportResponse {
+            report: Some(report),
+            error: None,
+        })),
+        Err(e) => Ok(Json(ImportResponse {
+            report: None,
+            error: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Fetch the UI settings of the requesting user, falling back to defaults
+async fn get_settings_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<SettingsResponse>, StatusCode> {
+    let user = user_from_headers(&headers);
+    let stored = match state.storage.get_settings(&user).await {
+        Ok(stored) => stored,
+        Err(e) => {
+            return Ok(Json(SettingsResponse {
+                user,
+                settings: None,
+                stored: false,
+                error: Some(e.to_string()),
+            }))
+        }
+    };
+
+    let (settings, stored) = match stored {
+        // Unknown or missing fields fall back to defaults, so older documents keep working
+        Some(stored) => (
+            serde_json::from_value(stored.settings).unwrap_or_default(),
+            true,
+        ),
+        None => (
+            UiSettings {
+                benchmark: state.config.benchmark.defaults.clone(),
+                ..UiSettings::default()
+            },
+            false,
+        ),
+    };
+
+    Ok(Json(SettingsResponse {
+        user,
+        settings: Some(settings),
+        stored,
+        error: None,
+    }))
+}
+
+/// Store the UI settings of the requesting user
+async fn put_settings_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(settings): Json<UiSettings>,
+) -> Result<Json<SettingsResponse>, StatusCode> {
+    let user = user_from_headers(&headers);
+    if let Err(e) = settings.validate() {
+        return Ok(Json(SettingsResponse {
+            user,
+            settings: None,
+            stored: false,
+            error: Some(e),
+        }));
+    }
+
+    let result = match serde_json::to_value(&settings) {
+        Ok(value) => state
+            .storage
+            .put_settings(&user, &value)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+
+    match result {
+        Ok(_) => Ok(Json(SettingsResponse {
+            user,
+            settings: Some(settings),
+            stored: true,
+            error: None,
+        })),
+        Err(e) => Ok(Json(SettingsResponse {
+            user,
+            settings: None,
+            stored: false,
+            error: Some(e),
+        })),
+    }
+}
+
+/// Reset the UI settings of the requesting user to defaults
+async fn reset_settings_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<SettingsResponse>, StatusCode> {
+    let user = user_from_headers(&headers);
+    match state.storage.delete_settings(&user).await {
+        Ok(_) => Ok(Json(SettingsResponse {
+            user,
+            settings: Some(UiSettings {
+                benchmark: state.config.benchmark.defaults.clone(),
+                ..UiSettings::default()
+            }),
+            stored: false,
+            error: None,
+        })),
+        Err(e) => Ok(Json(SettingsResponse {
+            user,
+            settings: None,
+            stored: false,
+            error: Some(e.to_string()),
         })),
     }
 }
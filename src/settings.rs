@@ -0,0 +1,135 @@
+//! Per-user UI settings
+//!
+//! Preferences the web UI would otherwise keep in browser local storage are
+//! stored server-side, keyed by user, so they follow users across browsers.
+//! Users are identified by a header set by an authenticating proxy; without
+//! one, everybody shares the `default` settings.
+
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+use crate::benchmark::BenchmarkConfig;
+
+/// Headers checked, in order, for the user a request belongs to
+pub const USER_HEADERS: [&str; 2] = ["x-sqltrace-user", "x-forwarded-user"];
+
+/// User whose settings apply when no user header is present
+pub const DEFAULT_USER: &str = "default";
+
+/// Longest accepted user identifier
+const MAX_USER_LEN: usize = 128;
+
+/// Default options for EXPLAIN requests issued by the UI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExplainDefaults {
+    /// Execute the query (EXPLAIN ANALYZE) rather than only planning it
+    pub analyze: bool,
+    /// Include buffer usage
+    pub buffers: bool,
+    /// Include verbose output
+    pub verbose: bool,
+}
+
+impl Default for ExplainDefaults {
+    fn default() -> Self {
+        Self {
+            analyze: true,
+            buffers: true,
+            verbose: false,
+        }
+    }
+}
+
+/// Cost thresholds used to color plan hotspots
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotspotThresholds {
+    /// Costs above this are shown as medium
+    pub cost_medium: f64,
+    /// Costs above this are shown as high
+    pub cost_high: f64,
+}
+
+impl Default for HotspotThresholds {
+    fn default() -> Self {
+        Self {
+            cost_medium: 100.0,
+            cost_high: 1000.0,
+        }
+    }
+}
+
+/// UI preferences for one user
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiSettings {
+    /// Default EXPLAIN options
+    pub explain: ExplainDefaults,
+    /// Default benchmark configuration
+    pub benchmark: BenchmarkConfig,
+    /// Hotspot coloring thresholds
+    pub hotspots: HotspotThresholds,
+}
+
+impl UiSettings {
+    /// Check that the settings are internally consistent
+    pub fn validate(&self) -> Result<(), String> {
+        if self.benchmark.benchmark_runs == 0 {
+            return Err("benchmark.benchmark_runs must be at least 1".to_string());
+        }
+        if self.benchmark.timeout_seconds == 0 {
+            return Err("benchmark.timeout_seconds must be at least 1".to_string());
+        }
+        if self.hotspots.cost_medium < 0.0 || self.hotspots.cost_high < self.hotspots.cost_medium {
+            return Err(
+                "hotspots thresholds must satisfy 0 <= cost_medium <= cost_high".to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Identify the user a request belongs to from its headers
+pub fn user_from_headers(headers: &HeaderMap) -> String {
+    USER_HEADERS
+        .iter()
+        .filter_map(|name| headers.get(*name))
+        .filter_map(|value| value.to_str().ok())
+        .map(str::trim)
+        .find(|user| !user.is_empty() && user.len() <= MAX_USER_LEN)
+        .unwrap_or(DEFAULT_USER)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_settings_use_defaults() {
+        let settings: UiSettings =
+            serde_json::from_str(r#"{"hotspots": {"cost_high": 5000}}"#).unwrap();
+
+        assert_eq!(settings.hotspots.cost_high, 5000.0);
+        assert_eq!(settings.hotspots.cost_medium, 100.0);
+        assert!(settings.explain.analyze);
+        assert!(settings.validate().is_ok());
+
+        let inverted: UiSettings =
+            serde_json::from_str(r#"{"hotspots": {"cost_medium": 10, "cost_high": 5}}"#).unwrap();
+        assert!(inverted.validate().is_err());
+    }
+
+    #[test]
+    fn test_user_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(user_from_headers(&headers), DEFAULT_USER);
+
+        headers.insert("x-forwarded-user", "bob".parse().unwrap());
+        assert_eq!(user_from_headers(&headers), "bob");
+
+        headers.insert("x-sqltrace-user", "alice".parse().unwrap());
+        assert_eq!(user_from_headers(&headers), "alice");
+    }
+}
@@ -0,0 +1,213 @@
+//! Export and import of the application state
+//!
+//! An archive is a single versioned JSON document holding everything SQLTrace
+//! has stored, so an instance can be backed up or moved to another storage
+//! backend. Connection details are included for reference only, with
+//! passwords redacted; they are never restored.
+
+use serde::{Deserialize, Serialize};
+
+use super::{now_millis, HistoryEntry, Result, SavedQuery, Storage, StorageError, StoredSettings};
+use crate::db::engines::ConnectionSummary;
+
+/// Archive format written by this version
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+/// Page size used when reading history for export
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+/// Everything SQLTrace has stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateArchive {
+    /// Archive format version
+    pub format_version: u32,
+    /// Version of SQLTrace that wrote the archive
+    pub sqltrace_version: String,
+    /// When the archive was written, in milliseconds since the Unix epoch
+    pub exported_at: i64,
+    /// Connections configured at export time
+    #[serde(default)]
+    pub connections: Vec<ConnectionSummary>,
+    /// Saved queries
+    #[serde(default)]
+    pub saved_queries: Vec<SavedQuery>,
+    /// Stored analyses, newest first
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+    /// Per-user UI settings
+    #[serde(default)]
+    pub settings: Vec<StoredSettings>,
+}
+
+/// What an import added
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// Analyses added to history
+    pub history: usize,
+    /// Saved queries added
+    pub saved_queries: usize,
+    /// Users whose settings were restored (replacing any existing settings)
+    pub settings: usize,
+    /// Connections present in the archive but not restored (they carry no credentials)
+    pub connections_skipped: usize,
+}
+
+/// Read the full state of `storage` into an archive
+pub async fn export_state(
+    storage: &dyn Storage,
+    connections: Vec<ConnectionSummary>,
+) -> Result<StateArchive> {
+    let mut history = Vec::new();
+    loop {
+        let page = storage
+            .list_history(EXPORT_PAGE_SIZE, history.len() as i64)
+            .await?;
+        let done = (page.len() as i64) < EXPORT_PAGE_SIZE;
+        history.extend(page);
+        if done {
+            break;
+        }
+    }
+
+    Ok(StateArchive {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        sqltrace_version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: now_millis(),
+        connections,
+        saved_queries: storage.list_saved_queries().await?,
+        history,
+        settings: storage.list_settings().await?,
+    })
+}
+
+/// Add the contents of an archive to `storage`
+///
+/// Entries get new ids but keep their original timestamps. Importing the same
+/// archive twice duplicates its entries.
+pub async fn import_state(storage: &dyn Storage, archive: StateArchive) -> Result<ImportReport> {
+    if archive.format_version > ARCHIVE_FORMAT_VERSION {
+        return Err(StorageError::Config(format!(
+            "Archive format version {} is newer than the supported version {}",
+            archive.format_version, ARCHIVE_FORMAT_VERSION
+        )));
+    }
+
+    let mut report = ImportReport {
+        connections_skipped: archive.connections.len(),
+        ..ImportReport::default()
+    };
+
+    for query in archive.saved_queries {
+        storage.restore_saved_query(query).await?;
+        report.saved_queries += 1;
+    }
+
+    for stored in archive.settings {
+        storage
+            .put_settings(&stored.user_id, &stored.settings)
+            .await?;
+        report.settings += 1;
+    }
+
+    // Restore oldest first so new ids follow the original order
+    for entry in archive.history.into_iter().rev() {
+        storage.restore_history(entry).await?;
+        report.history += 1;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::engines::EngineType;
+    use crate::storage::{NewHistoryEntry, NewSavedQuery, SqliteStorage, StorageConfig};
+    use serde_json::json;
+
+    async fn memory_storage() -> SqliteStorage {
+        let storage = SqliteStorage::connect(&StorageConfig {
+            url: "sqlite::memory:".to_string(),
+            ..StorageConfig::default()
+        })
+        .await
+        .unwrap();
+        storage.migrate().await.unwrap();
+        storage
+    }
+
+    #[tokio::test]
+    async fn test_export_import_round_trip() {
+        let source = memory_storage().await;
+        for i in 0..3 {
+            source
+                .record_history(NewHistoryEntry {
+                    query: format!("SELECT {}", i),
+                    plan: json!({"root": {}}),
+                    analysis: None,
+                    performance_score: None,
+                    total_cost: i as f64,
+                    execution_time_ms: 1.0,
+                    trace_id: None,
+                    correlation_id: None,
+                    table_definitions: None,
+                    connection: None,
+                })
+                .await
+                .unwrap();
+        }
+        source
+            .save_query(NewSavedQuery {
+                name: "daily report".to_string(),
+                query: "SELECT 1".to_string(),
+                description: Some("used by finance".to_string()),
+            })
+            .await
+            .unwrap();
+
+        let connections = vec![ConnectionSummary {
+            name: "default".to_string(),
+            engine_type: EngineType::PostgreSQL,
+            url: Some("postgres://app:****@db/prod".to_string()),
+        }];
+        let archive = export_state(&source, connections).await.unwrap();
+        assert_eq!(archive.history.len(), 3);
+
+        // Archives survive a trip through JSON
+        let archive: StateArchive =
+            serde_json::from_str(&serde_json::to_string(&archive).unwrap()).unwrap();
+
+        let target = memory_storage().await;
+        let report = import_state(&target, archive.clone()).await.unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                history: 3,
+                saved_queries: 1,
+                settings: 0,
+                connections_skipped: 1,
+            }
+        );
+
+        let restored = target.list_history(10, 0).await.unwrap();
+        let queries: Vec<_> = restored.iter().map(|e| e.query.as_str()).collect();
+        let original: Vec<_> = archive.history.iter().map(|e| e.query.as_str()).collect();
+        assert_eq!(queries, original);
+        assert_eq!(restored[0].created_at, archive.history[0].created_at);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_newer_format() {
+        let target = memory_storage().await;
+        let archive = StateArchive {
+            format_version: ARCHIVE_FORMAT_VERSION + 1,
+            sqltrace_version: "99.0.0".to_string(),
+            exported_at: 0,
+            connections: Vec::new(),
+            saved_queries: Vec::new(),
+            history: Vec::new(),
+            settings: Vec::new(),
+        };
+        assert!(import_state(&target, archive).await.is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! How well planner cost predicts execution time
+//!
+//! Cost is in arbitrary units, and what a unit is worth in milliseconds
+//! depends on the hardware, the cache hit rate and the cost settings of each
+//! database. The analyses in history pair an estimated cost with a measured
+//! time, which is enough to see whether cost ranks queries the way their
+//! times do, and what a cost threshold means in time on a given connection.
+
+use serde::{Deserialize, Serialize};
+
+use super::CostTimePoint;
+
+/// Cost against time for the analyses of one connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostTimeSeries {
+    /// Connection name; `None` for analyses stored before connections were recorded
+    pub connection: Option<String>,
+    /// Analyses of the connection, newest first
+    pub points: Vec<CostTimePoint>,
+    /// Spearman rank correlation of cost and time, from -1 to 1; `None` with
+    /// fewer than 3 analyses or when all costs or all times are equal
+    pub rank_correlation: Option<f64>,
+    /// Median milliseconds per cost unit over analyses with a positive cost
+    pub ms_per_cost_unit: Option<f64>,
+}
+
+impl CostTimeSeries {
+    /// Milliseconds a query of `cost` is expected to take on this connection
+    pub fn expected_ms(&self, cost: f64) -> Option<f64> {
+        self.ms_per_cost_unit.map(|rate| rate * cost)
+    }
+}
+
+/// Split `points` by connection and fit each, in order of each connection's newest analysis
+pub fn by_connection(points: Vec<CostTimePoint>) -> Vec<CostTimeSeries> {
+    let mut groups: Vec<(Option<String>, Vec<CostTimePoint>)> = Vec::new();
+    for point in points {
+        match groups.iter_mut().find(|(c, _)| *c == point.connection) {
+            Some((_, members)) => members.push(point),
+            None => groups.push((point.connection.clone(), vec![point])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(connection, points)| fit(connection, points))
+        .collect()
+}
+
+/// Fit one connection's analyses
+pub fn fit(connection: Option<String>, points: Vec<CostTimePoint>) -> CostTimeSeries {
+    let costs: Vec<f64> = points.iter().map(|p| p.total_cost).collect();
+    let times: Vec<f64> = points.iter().map(|p| p.execution_time_ms).collect();
+    let rank_correlation = (points.len() >= 3)
+        .then(|| pearson(&ranks(&costs), &ranks(&times)))
+        .flatten();
+
+    let mut rates: Vec<f64> = points
+        .iter()
+        .filter(|p| p.total_cost > 0.0)
+        .map(|p| p.execution_time_ms / p.total_cost)
+        .collect();
+    rates.sort_by(f64::total_cmp);
+    let ms_per_cost_unit = match rates.len() {
+        0 => None,
+        n if n.is_multiple_of(2) => Some((rates[n / 2 - 1] + rates[n / 2]) / 2.0),
+        n => Some(rates[n / 2]),
+    };
+
+    CostTimeSeries {
+        connection,
+        points,
+        rank_correlation,
+        ms_per_cost_unit,
+    }
+}
+
+/// Ranks starting at 1, with tied values sharing the average of their ranks
+fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        // Positions start..end hold ranks start+1 ..= end
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            ranks[i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+fn pearson(x: &[f64], y: &[f64]) -> Option<f64> {
+    let n = x.len() as f64;
+    let (mean_x, mean_y) = (x.iter().sum::<f64>() / n, y.iter().sum::<f64>() / n);
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (a, b) in x.iter().zip(y) {
+        cov += (a - mean_x) * (b - mean_y);
+        var_x += (a - mean_x).powi(2);
+        var_y += (b - mean_y).powi(2);
+    }
+    (var_x > 0.0 && var_y > 0.0).then(|| cov / (var_x * var_y).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(connection: &str, total_cost: f64, execution_time_ms: f64) -> CostTimePoint {
+        CostTimePoint {
+            history_id: 0,
+            connection: Some(connection.to_string()),
+            total_cost,
+            execution_time_ms,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_ranks_with_ties() {
+        assert_eq!(ranks(&[10.0, 30.0, 20.0, 30.0]), [1.0, 3.5, 2.0, 3.5]);
+    }
+
+    #[test]
+    fn test_fit_by_connection() {
+        let series = by_connection(vec![
+            point("primary", 100.0, 2.0),
+            point("replica", 10.0, 50.0),
+            point("primary", 1000.0, 30.0),
+            point("primary", 10.0, 0.1),
+            point("replica", 20.0, 5.0),
+            point("replica", 30.0, 1.0),
+        ]);
+        assert_eq!(series.len(), 2);
+
+        let primary = &series[0];
+        assert_eq!(primary.connection.as_deref(), Some("primary"));
+        assert!((primary.rank_correlation.unwrap() - 1.0).abs() < 1e-9);
+        assert!((primary.ms_per_cost_unit.unwrap() - 0.02).abs() < 1e-9);
+        assert!((primary.expected_ms(500.0).unwrap() - 10.0).abs() < 1e-9);
+
+        // Cost ranks the replica's queries in the opposite order of their times
+        assert!((series[1].rank_correlation.unwrap() + 1.0).abs() < 1e-9);
+
+        let short = fit(None, vec![point("a", 1.0, 1.0), point("a", 2.0, 2.0)]);
+        assert!(short.rank_correlation.is_none());
+    }
+}
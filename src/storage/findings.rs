@@ -0,0 +1,440 @@
+//! Advisor findings tracked across analyses
+//!
+//! Each analysis of a query produces advisor suggestions. Stored as findings
+//! keyed by the query's fingerprint (its text with literals masked) and the
+//! rule that fired, they move through a lifecycle as the same query is
+//! analyzed again: a finding is `new` when first reported, `acknowledged`
+//! once someone has triaged it, `fixed` when a later analysis no longer
+//! reports it, and `regressed` if it comes back after that. Each severity
+//! maps to a number of days within which open findings are due.
+
+use serde::{Deserialize, Serialize};
+
+use super::{Result, Storage};
+use crate::advisor::{OptimizationSuggestion, Severity};
+use crate::masking::mask_literals;
+
+const DAY_MILLIS: i64 = 86_400_000;
+
+/// Where a finding is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FindingState {
+    /// Reported and not yet triaged
+    New,
+    /// Triaged; still reported by the latest analysis
+    Acknowledged,
+    /// No longer reported by the latest analysis
+    Fixed,
+    /// Reported again after being fixed
+    Regressed,
+}
+
+impl FindingState {
+    /// Name used in storage and the API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FindingState::New => "new",
+            FindingState::Acknowledged => "acknowledged",
+            FindingState::Fixed => "fixed",
+            FindingState::Regressed => "regressed",
+        }
+    }
+
+    /// Parse a stored state name
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "new" => Some(FindingState::New),
+            "acknowledged" => Some(FindingState::Acknowledged),
+            "fixed" => Some(FindingState::Fixed),
+            "regressed" => Some(FindingState::Regressed),
+            _ => None,
+        }
+    }
+}
+
+/// Days within which open findings of each severity are due; 0 sets no due date
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlaDays {
+    /// Days allowed for high severity findings
+    pub high: u32,
+    /// Days allowed for medium severity findings
+    pub medium: u32,
+    /// Days allowed for low severity findings
+    pub low: u32,
+}
+
+impl Default for SlaDays {
+    fn default() -> Self {
+        Self {
+            high: 7,
+            medium: 30,
+            low: 90,
+        }
+    }
+}
+
+impl SlaDays {
+    /// Due date (ms since epoch) of a finding of `severity` opened at `opened_at`
+    pub fn due_at(&self, severity: &Severity, opened_at: i64) -> Option<i64> {
+        let days = match severity {
+            Severity::High => self.high,
+            Severity::Medium => self.medium,
+            Severity::Low => self.low,
+        };
+        (days > 0).then(|| opened_at.saturating_add(i64::from(days) * DAY_MILLIS))
+    }
+}
+
+/// Finding tracking configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FindingsConfig {
+    /// Track advisor findings for every analyzed query
+    pub enabled: bool,
+    /// Due dates per severity
+    pub sla_days: SlaDays,
+}
+
+impl Default for FindingsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sla_days: SlaDays::default(),
+        }
+    }
+}
+
+/// An advisor finding for one query fingerprint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    /// Storage-assigned identifier
+    pub id: i64,
+    /// Fingerprint of the query the finding was reported for
+    pub fingerprint: String,
+    /// Identifies the finding within the fingerprint
+    pub finding_key: String,
+    /// Rule that reported the finding
+    pub rule_id: String,
+    /// Severity of the latest report
+    pub severity: Severity,
+    /// Title of the latest report
+    pub title: String,
+    /// Query text of the latest analysis, masked if masking is enabled
+    pub query: String,
+    /// Current lifecycle state
+    pub state: FindingState,
+    /// First report, in milliseconds since the Unix epoch
+    pub first_seen: i64,
+    /// Latest report, in milliseconds since the Unix epoch
+    pub last_seen: i64,
+    /// When the finding was acknowledged, if it has been since it was last opened
+    pub acknowledged_at: Option<i64>,
+    /// When a later analysis stopped reporting the finding, if it is fixed
+    pub fixed_at: Option<i64>,
+    /// When the finding is due under the severity's SLA, unless fixed
+    pub due_at: Option<i64>,
+    /// History entry of the latest analysis that reported the finding
+    pub history_id: Option<i64>,
+}
+
+impl Finding {
+    /// Whether the finding is open and past its due date
+    pub fn is_overdue(&self, now: i64) -> bool {
+        self.state != FindingState::Fixed && self.due_at.is_some_and(|due| now > due)
+    }
+}
+
+/// A finding that has not been stored yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewFinding {
+    /// Fingerprint of the query the finding was reported for
+    pub fingerprint: String,
+    /// Identifies the finding within the fingerprint
+    pub finding_key: String,
+    /// Rule that reported the finding
+    pub rule_id: String,
+    /// Severity of the report
+    pub severity: Severity,
+    /// Title of the report
+    pub title: String,
+    /// Query text of the analysis
+    pub query: String,
+    /// First report, in milliseconds since the Unix epoch
+    pub first_seen: i64,
+    /// When the finding is due under the severity's SLA
+    pub due_at: Option<i64>,
+    /// History entry of the analysis that reported the finding
+    pub history_id: Option<i64>,
+}
+
+/// Filter for listing findings; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FindingFilter {
+    /// Only findings for this query fingerprint
+    pub fingerprint: Option<String>,
+    /// Only findings in this state
+    pub state: Option<FindingState>,
+}
+
+/// One analysis of a query, as seen by finding tracking
+#[derive(Debug, Clone, Copy)]
+pub struct Observation<'a> {
+    /// Fingerprint of the analyzed query
+    pub fingerprint: &'a str,
+    /// Query text to store with the findings
+    pub query: &'a str,
+    /// History entry of the analysis, if it was stored
+    pub history_id: Option<i64>,
+    /// Time of the analysis, in milliseconds since the Unix epoch
+    pub now: i64,
+}
+
+/// Fingerprint of a query: a hash of its text with literals masked and whitespace collapsed
+///
+/// FNV-1a is used because, unlike the standard library hasher, its output is
+/// stable across Rust releases, and fingerprints are stored.
+pub fn fingerprint(sql: &str) -> String {
+    let normalized = mask_literals(sql.trim().trim_end_matches(';'))
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in normalized.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Key identifying a suggestion within a fingerprint
+///
+/// The title is included because some rules report per operator type.
+pub fn finding_key(suggestion: &OptimizationSuggestion) -> String {
+    format!("{}:{}", suggestion.rule_id, suggestion.title)
+}
+
+/// Apply an analysis to the stored findings of its fingerprint
+///
+/// Returns findings to insert and existing findings whose state or last
+/// report changed.
+pub fn reconcile(
+    existing: &[Finding],
+    suggestions: &[OptimizationSuggestion],
+    observation: Observation<'_>,
+    sla: &SlaDays,
+) -> (Vec<NewFinding>, Vec<Finding>) {
+    let now = observation.now;
+    let mut inserted: Vec<NewFinding> = Vec::new();
+    let mut updated = Vec::new();
+    let mut reported = Vec::new();
+
+    for suggestion in suggestions {
+        let key = finding_key(suggestion);
+        if reported.contains(&key) {
+            continue;
+        }
+        reported.push(key.clone());
+
+        match existing.iter().find(|f| f.finding_key == key) {
+            Some(finding) => {
+                let mut finding = finding.clone();
+                if finding.state == FindingState::Fixed {
+                    // The SLA starts over from the regression
+                    finding.state = FindingState::Regressed;
+                    finding.acknowledged_at = None;
+                    finding.fixed_at = None;
+                    finding.due_at = sla.due_at(&suggestion.severity, now);
+                }
+                finding.severity = suggestion.severity.clone();
+                finding.title = suggestion.title.clone();
+                finding.query = observation.query.to_string();
+                finding.last_seen = now;
+                finding.history_id = observation.history_id;
+                updated.push(finding);
+            }
+            None => inserted.push(NewFinding {
+                fingerprint: observation.fingerprint.to_string(),
+                finding_key: key,
+                rule_id: suggestion.rule_id.clone(),
+                severity: suggestion.severity.clone(),
+                title: suggestion.title.clone(),
+                query: observation.query.to_string(),
+                first_seen: now,
+                due_at: sla.due_at(&suggestion.severity, now),
+                history_id: observation.history_id,
+            }),
+        }
+    }
+
+    for finding in existing {
+        if finding.state != FindingState::Fixed && !reported.contains(&finding.finding_key) {
+            let mut finding = finding.clone();
+            finding.state = FindingState::Fixed;
+            finding.fixed_at = Some(now);
+            finding.due_at = None;
+            updated.push(finding);
+        }
+    }
+
+    (inserted, updated)
+}
+
+/// Record the suggestions of an analysis, returning the findings of its fingerprint
+pub async fn track(
+    storage: &dyn Storage,
+    suggestions: &[OptimizationSuggestion],
+    observation: Observation<'_>,
+    sla: &SlaDays,
+) -> Result<Vec<Finding>> {
+    let filter = FindingFilter {
+        fingerprint: Some(observation.fingerprint.to_string()),
+        state: None,
+    };
+    let existing = storage.list_findings(&filter).await?;
+
+    let (inserted, updated) = reconcile(&existing, suggestions, observation, sla);
+    for finding in &updated {
+        storage.update_finding(finding).await?;
+    }
+    for finding in inserted {
+        storage.record_finding(finding).await?;
+    }
+
+    storage.list_findings(&filter).await
+}
+
+/// Mark a finding as acknowledged, returning it, or `None` if it does not exist
+///
+/// Fixed findings are returned unchanged; there is nothing left to triage.
+pub async fn acknowledge(storage: &dyn Storage, id: i64, now: i64) -> Result<Option<Finding>> {
+    let Some(mut finding) = storage.get_finding(id).await? else {
+        return Ok(None);
+    };
+    if matches!(finding.state, FindingState::New | FindingState::Regressed) {
+        finding.state = FindingState::Acknowledged;
+        finding.acknowledged_at = Some(now);
+        storage.update_finding(&finding).await?;
+    }
+    Ok(Some(finding))
+}
+
+/// Name of a severity in storage
+pub(crate) fn severity_name(severity: &Severity) -> &'static str {
+    match severity {
+        Severity::High => "High",
+        Severity::Medium => "Medium",
+        Severity::Low => "Low",
+    }
+}
+
+/// Parse a stored severity name
+pub(crate) fn parse_severity(name: &str) -> Severity {
+    match name {
+        "High" => Severity::High,
+        "Medium" => Severity::Medium,
+        _ => Severity::Low,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggestion(rule_id: &str, severity: Severity) -> OptimizationSuggestion {
+        OptimizationSuggestion {
+            rule_id: rule_id.to_string(),
+            suggestion_type: "Index".to_string(),
+            severity,
+            title: "Expensive Sequential Scan Detected".to_string(),
+            description: String::new(),
+            recommendation: String::new(),
+            node_index: None,
+            impact: String::new(),
+        }
+    }
+
+    fn stored(new: NewFinding, id: i64) -> Finding {
+        Finding {
+            id,
+            fingerprint: new.fingerprint,
+            finding_key: new.finding_key,
+            rule_id: new.rule_id,
+            severity: new.severity,
+            title: new.title,
+            query: new.query,
+            state: FindingState::New,
+            first_seen: new.first_seen,
+            last_seen: new.first_seen,
+            acknowledged_at: None,
+            fixed_at: None,
+            due_at: new.due_at,
+            history_id: new.history_id,
+        }
+    }
+
+    fn observation(now: i64) -> Observation<'static> {
+        Observation {
+            fingerprint: "abc",
+            query: "SELECT * FROM orders WHERE id = ?",
+            history_id: None,
+            now,
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_literals_and_layout() {
+        assert_eq!(
+            fingerprint("SELECT * FROM orders WHERE id = 1"),
+            fingerprint("SELECT *\n  FROM orders\n  WHERE id = 42;")
+        );
+        assert_ne!(
+            fingerprint("SELECT * FROM orders WHERE id = 1"),
+            fingerprint("SELECT * FROM refunds WHERE id = 1")
+        );
+    }
+
+    #[test]
+    fn test_finding_lifecycle() {
+        let sla = SlaDays::default();
+        let scan = [suggestion("expensive_seq_scan", Severity::High)];
+
+        // First report opens a finding due in seven days
+        let (inserted, updated) = reconcile(&[], &scan, observation(0), &sla);
+        assert!(updated.is_empty());
+        let finding = stored(inserted.into_iter().next().unwrap(), 1);
+        assert_eq!(finding.due_at, Some(7 * DAY_MILLIS));
+        assert!(finding.is_overdue(8 * DAY_MILLIS));
+
+        // Reported again: still open, last seen moves
+        let (inserted, updated) =
+            reconcile(std::slice::from_ref(&finding), &scan, observation(10), &sla);
+        assert!(inserted.is_empty());
+        assert_eq!(updated[0].state, FindingState::New);
+        assert_eq!(updated[0].last_seen, 10);
+
+        // Not reported: fixed
+        let (_, updated) = reconcile(&[finding], &[], observation(20), &sla);
+        let fixed = updated[0].clone();
+        assert_eq!(fixed.state, FindingState::Fixed);
+        assert_eq!(fixed.fixed_at, Some(20));
+        assert!(!fixed.is_overdue(100 * DAY_MILLIS));
+
+        // Reported after being fixed: regressed, with a fresh due date
+        let (_, updated) = reconcile(&[fixed], &scan, observation(30), &sla);
+        assert_eq!(updated[0].state, FindingState::Regressed);
+        assert_eq!(updated[0].due_at, Some(30 + 7 * DAY_MILLIS));
+    }
+
+    #[test]
+    fn test_sla_days() {
+        let sla = SlaDays {
+            low: 0,
+            ..SlaDays::default()
+        };
+        assert_eq!(sla.due_at(&Severity::Medium, 0), Some(30 * DAY_MILLIS));
+        assert_eq!(sla.due_at(&Severity::Low, 0), None);
+    }
+}
@@ -0,0 +1,526 @@
+//! Persistence for analysis history and saved data
+//!
+//! SQLTrace keeps its own state (past analyses, saved queries) separate from
+//! the database being analyzed. The [`Storage`] trait abstracts that state so
+//! a single-user install can use a local SQLite file while a shared instance
+//! stores it in an existing PostgreSQL database.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::db::credentials::redact_connection_string;
+
+pub mod archive;
+pub mod calibration;
+pub mod findings;
+pub mod pins;
+pub mod postgres;
+pub mod reanalysis;
+pub mod retention;
+pub mod sqlite;
+pub mod trends;
+
+pub use findings::{Finding, FindingFilter, FindingState, FindingsConfig, NewFinding};
+pub use pins::{PinCheck, PlanPin};
+pub use postgres::PostgresStorage;
+pub use reanalysis::ReanalysisReport;
+pub use retention::{HistoryStats, PruneReport, RetentionPolicy};
+pub use sqlite::SqliteStorage;
+pub use trends::ConnectionTrend;
+
+/// Errors raised by storage backends
+#[derive(Error, Debug)]
+pub enum StorageError {
+    /// The storage backend could not be reached or rejected a statement
+    #[error("Storage backend error: {0}")]
+    Backend(String),
+
+    /// Stored data could not be serialized or deserialized
+    #[error("Storage serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    /// The storage configuration is invalid
+    #[error("Storage configuration error: {0}")]
+    Config(String),
+}
+
+impl From<sqlx::Error> for StorageError {
+    fn from(err: sqlx::Error) -> Self {
+        StorageError::Backend(err.to_string())
+    }
+}
+
+/// Convenience type for Results that use StorageError
+pub type Result<T> = std::result::Result<T, StorageError>;
+
+/// Storage backend kinds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// Local SQLite file (or in-memory database)
+    Sqlite,
+    /// Shared PostgreSQL database
+    Postgres,
+}
+
+/// Format plans of history entries are stored in
+///
+/// Entries keep the format they were written in, so changing it only affects
+/// new entries; both are read back the same way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanEncoding {
+    /// JSON, readable with the backend's own JSON functions
+    #[default]
+    Json,
+    /// MessagePack, smaller and faster to decode than JSON
+    #[serde(alias = "messagepack")]
+    Msgpack,
+}
+
+impl PlanEncoding {
+    /// The plan as stored in a `plan_packed` column, if this encoding packs plans
+    pub(crate) fn pack(&self, plan: &serde_json::Value) -> Option<Vec<u8>> {
+        match self {
+            Self::Json => None,
+            Self::Msgpack => Some(crate::msgpack::encode(plan)),
+        }
+    }
+}
+
+/// Storage configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// Connection URL; the scheme selects the backend (`sqlite:` or `postgres:`)
+    pub url: String,
+    /// Maximum number of pooled storage connections
+    pub max_connections: u32,
+    /// How plans of new history entries are stored
+    pub plan_encoding: PlanEncoding,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            url: "sqlite://sqltrace.db".to_string(),
+            max_connections: 5,
+            plan_encoding: PlanEncoding::Json,
+        }
+    }
+}
+
+impl StorageConfig {
+    /// Determine the backend from the URL scheme
+    pub fn backend(&self) -> Result<StorageBackend> {
+        if self.url.starts_with("sqlite:") {
+            Ok(StorageBackend::Sqlite)
+        } else if self.url.starts_with("postgres://") || self.url.starts_with("postgresql://") {
+            Ok(StorageBackend::Postgres)
+        } else {
+            Err(StorageError::Config(format!(
+                "Unsupported storage URL {}; expected sqlite: or postgres://",
+                redact_connection_string(&self.url)
+            )))
+        }
+    }
+}
+
+/// A stored analysis of a query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Storage-assigned identifier
+    pub id: i64,
+    /// The analyzed SQL text
+    pub query: String,
+    /// The parsed execution plan
+    pub plan: serde_json::Value,
+    /// Advisor output for the plan, if the advisor ran
+    pub analysis: Option<serde_json::Value>,
+    /// Advisor performance score (0-100), if the advisor ran
+    pub performance_score: Option<u8>,
+    /// Estimated total cost of the root node
+    pub total_cost: f64,
+    /// Execution time in milliseconds
+    pub execution_time_ms: f64,
+    /// W3C trace id of the request that ran the analysis
+    #[serde(default)]
+    pub trace_id: Option<String>,
+    /// Correlation id the request was sent with
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+    /// Columns and indexes of the tables the plan reads, as they were when the analysis ran
+    #[serde(default)]
+    pub table_definitions: Option<serde_json::Value>,
+    /// Name of the connection the analysis ran on
+    #[serde(default)]
+    pub connection: Option<String>,
+    /// When the analysis was stored, in milliseconds since the Unix epoch
+    pub created_at: i64,
+}
+
+/// A history entry that has not been stored yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewHistoryEntry {
+    /// The analyzed SQL text
+    pub query: String,
+    /// The parsed execution plan
+    pub plan: serde_json::Value,
+    /// Advisor output for the plan, if the advisor ran
+    pub analysis: Option<serde_json::Value>,
+    /// Advisor performance score (0-100), if the advisor ran
+    pub performance_score: Option<u8>,
+    /// Estimated total cost of the root node
+    pub total_cost: f64,
+    /// Execution time in milliseconds
+    pub execution_time_ms: f64,
+    /// W3C trace id of the request that ran the analysis
+    #[serde(default)]
+    pub trace_id: Option<String>,
+    /// Correlation id the request was sent with
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+    /// Columns and indexes of the tables the plan reads, as they were when the analysis ran
+    #[serde(default)]
+    pub table_definitions: Option<serde_json::Value>,
+    /// Name of the connection the analysis ran on
+    #[serde(default)]
+    pub connection: Option<String>,
+}
+
+impl From<HistoryEntry> for NewHistoryEntry {
+    fn from(entry: HistoryEntry) -> Self {
+        Self {
+            query: entry.query,
+            plan: entry.plan,
+            analysis: entry.analysis,
+            performance_score: entry.performance_score,
+            total_cost: entry.total_cost,
+            execution_time_ms: entry.execution_time_ms,
+            trace_id: entry.trace_id,
+            correlation_id: entry.correlation_id,
+            table_definitions: entry.table_definitions,
+            connection: entry.connection,
+        }
+    }
+}
+
+/// Estimated cost and measured time of one stored analysis
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CostTimePoint {
+    /// Id of the analysis
+    pub history_id: i64,
+    /// Name of the connection the analysis ran on, if recorded
+    pub connection: Option<String>,
+    /// Estimated total cost of the root node
+    pub total_cost: f64,
+    /// Execution time in milliseconds
+    pub execution_time_ms: f64,
+    /// When the analysis was stored, in milliseconds since the Unix epoch
+    pub created_at: i64,
+}
+
+/// Query and score of one stored analysis
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScorePoint {
+    /// Id of the analysis
+    pub history_id: i64,
+    /// The analyzed query, as stored
+    pub query: String,
+    /// Performance score the advisor gave the plan, if it was analyzed
+    pub performance_score: Option<u8>,
+    /// When the analysis was stored, in milliseconds since the Unix epoch
+    pub created_at: i64,
+}
+
+/// A named query saved for later reuse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedQuery {
+    /// Storage-assigned identifier
+    pub id: i64,
+    /// Display name
+    pub name: String,
+    /// The SQL text
+    pub query: String,
+    /// Optional free-form description
+    pub description: Option<String>,
+    /// When the query was saved, in milliseconds since the Unix epoch
+    pub created_at: i64,
+}
+
+/// A saved query that has not been stored yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewSavedQuery {
+    /// Display name
+    pub name: String,
+    /// The SQL text
+    pub query: String,
+    /// Optional free-form description
+    pub description: Option<String>,
+}
+
+impl From<SavedQuery> for NewSavedQuery {
+    fn from(query: SavedQuery) -> Self {
+        Self {
+            name: query.name,
+            query: query.query,
+            description: query.description,
+        }
+    }
+}
+
+/// Settings stored for one user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredSettings {
+    /// User the settings belong to
+    pub user_id: String,
+    /// Settings document
+    pub settings: serde_json::Value,
+    /// Last update, in milliseconds since the Unix epoch
+    pub updated_at: i64,
+}
+
+/// What kind of benchmark a stored result comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BenchmarkKind {
+    /// A single query
+    Query,
+    /// A multi-statement transaction
+    Transaction,
+}
+
+impl BenchmarkKind {
+    /// Name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Query => "query",
+            Self::Transaction => "transaction",
+        }
+    }
+
+    /// Parse a stored name
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "query" => Some(Self::Query),
+            "transaction" => Some(Self::Transaction),
+            _ => None,
+        }
+    }
+}
+
+/// A benchmark result kept for later analysis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredBenchmark {
+    /// Storage-assigned identifier
+    pub id: i64,
+    /// Whether `result` is a query or a transaction benchmark result
+    pub kind: BenchmarkKind,
+    /// The benchmarked query, or the statements of the transaction joined by `;`
+    pub label: String,
+    /// The full benchmark result, including every run
+    pub result: serde_json::Value,
+    /// When the benchmark finished, in milliseconds since the Unix epoch
+    pub created_at: i64,
+}
+
+/// A benchmark result that has not been stored yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewBenchmark {
+    /// Whether `result` is a query or a transaction benchmark result
+    pub kind: BenchmarkKind,
+    /// The benchmarked query, or the statements of the transaction joined by `;`
+    pub label: String,
+    /// The full benchmark result, including every run
+    pub result: serde_json::Value,
+}
+
+/// Persistence layer for SQLTrace's own state
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// The kind of backend
+    fn backend(&self) -> StorageBackend;
+
+    /// Create tables if they do not exist yet
+    async fn migrate(&self) -> Result<()>;
+
+    /// Check that the backend can be reached
+    async fn ping(&self) -> Result<()>;
+
+    /// Store an analysis and return it with its assigned id
+    async fn record_history(&self, entry: NewHistoryEntry) -> Result<HistoryEntry>;
+
+    /// List analyses, newest first
+    async fn list_history(&self, limit: i64, offset: i64) -> Result<Vec<HistoryEntry>>;
+
+    /// List analyses whose trace id or correlation id is `id`, newest first
+    async fn find_history_by_correlation(&self, id: &str, limit: i64) -> Result<Vec<HistoryEntry>>;
+
+    /// Cost and time of the most recent analyses that executed their query,
+    /// only of `connection` if given, newest first
+    async fn cost_time_points(
+        &self,
+        connection: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<CostTimePoint>>;
+
+    /// Query and score of the analyses of `connection` stored at or after
+    /// `since` (ms since epoch), oldest first
+    async fn score_points(&self, connection: &str, since: i64) -> Result<Vec<ScorePoint>>;
+
+    /// Fetch a single analysis
+    async fn get_history(&self, id: i64) -> Result<Option<HistoryEntry>>;
+
+    /// Store a previously exported analysis, keeping its timestamp but assigning a new id
+    async fn restore_history(&self, entry: HistoryEntry) -> Result<HistoryEntry>;
+
+    /// Replace the advisor output of a stored analysis, returning whether it existed
+    async fn update_history_analysis(
+        &self,
+        id: i64,
+        analysis: &serde_json::Value,
+        performance_score: u8,
+    ) -> Result<bool>;
+
+    /// Delete a single analysis, returning whether it existed
+    async fn delete_history(&self, id: i64) -> Result<bool>;
+
+    /// Delete analyses exceeding the limits of `policy`, as of `now` (ms since epoch)
+    ///
+    /// Limits are applied in order: age, then count, then size.
+    async fn prune_history(&self, policy: &RetentionPolicy, now: i64) -> Result<PruneReport>;
+
+    /// Delete every stored analysis, returning how many were removed
+    async fn purge_history(&self) -> Result<u64>;
+
+    /// Count and approximate size of stored history
+    async fn history_stats(&self) -> Result<HistoryStats>;
+
+    /// Save a named query and return it with its assigned id
+    async fn save_query(&self, query: NewSavedQuery) -> Result<SavedQuery>;
+
+    /// Store a previously exported saved query, keeping its timestamp but assigning a new id
+    async fn restore_saved_query(&self, query: SavedQuery) -> Result<SavedQuery>;
+
+    /// List saved queries ordered by name
+    async fn list_saved_queries(&self) -> Result<Vec<SavedQuery>>;
+
+    /// Fetch a single saved query
+    async fn get_saved_query(&self, id: i64) -> Result<Option<SavedQuery>>;
+
+    /// Delete a saved query and the plan pinned for it, returning whether it existed
+    async fn delete_saved_query(&self, id: i64) -> Result<bool>;
+
+    /// Pin a plan for a saved query, replacing the plan pinned for it before
+    async fn put_plan_pin(&self, pin: &PlanPin) -> Result<()>;
+
+    /// Fetch the plan pinned for a saved query
+    async fn get_plan_pin(&self, saved_query_id: i64) -> Result<Option<PlanPin>>;
+
+    /// Fetch the most recently pinned plan among saved queries with a query fingerprint
+    async fn find_plan_pin(&self, query_fingerprint: &str) -> Result<Option<PlanPin>>;
+
+    /// Unpin the plan of a saved query, returning whether one was pinned
+    async fn delete_plan_pin(&self, saved_query_id: i64) -> Result<bool>;
+
+    /// Fetch the settings document of a user
+    async fn get_settings(&self, user_id: &str) -> Result<Option<StoredSettings>>;
+
+    /// Create or replace the settings document of a user
+    async fn put_settings(
+        &self,
+        user_id: &str,
+        settings: &serde_json::Value,
+    ) -> Result<StoredSettings>;
+
+    /// Delete the settings of a user, returning whether they existed
+    async fn delete_settings(&self, user_id: &str) -> Result<bool>;
+
+    /// List the settings of every user
+    async fn list_settings(&self) -> Result<Vec<StoredSettings>>;
+
+    /// Fetch the advisor rule overrides made at runtime
+    async fn get_advisor_rules(&self) -> Result<Option<serde_json::Value>>;
+
+    /// Create or replace the advisor rule overrides
+    async fn put_advisor_rules(&self, rules: &serde_json::Value) -> Result<()>;
+
+    /// Store a finding and return it with its assigned id
+    async fn record_finding(&self, finding: NewFinding) -> Result<Finding>;
+
+    /// List findings matching `filter`, most recently reported first
+    async fn list_findings(&self, filter: &FindingFilter) -> Result<Vec<Finding>>;
+
+    /// Fetch a single finding
+    async fn get_finding(&self, id: i64) -> Result<Option<Finding>>;
+
+    /// Replace the mutable fields of a stored finding, returning whether it existed
+    async fn update_finding(&self, finding: &Finding) -> Result<bool>;
+
+    /// Store a benchmark result and return it with its assigned id
+    async fn record_benchmark(&self, benchmark: NewBenchmark) -> Result<StoredBenchmark>;
+
+    /// Fetch a single benchmark result
+    async fn get_benchmark(&self, id: i64) -> Result<Option<StoredBenchmark>>;
+}
+
+/// Open the storage backend selected by `config` and create its tables
+pub async fn open(config: &StorageConfig) -> Result<Arc<dyn Storage>> {
+    let storage: Arc<dyn Storage> = match config.backend()? {
+        StorageBackend::Sqlite => Arc::new(SqliteStorage::connect(config).await?),
+        StorageBackend::Postgres => Arc::new(PostgresStorage::connect(config).await?),
+    };
+    storage.migrate().await?;
+    Ok(storage)
+}
+
+/// Content address of a plan: the SHA-256 of its JSON, hex encoded
+///
+/// History entries store their plan under this hash, so analyses that
+/// produced the same plan share one stored copy of it.
+pub fn plan_hash(plan: &serde_json::Value) -> Result<String> {
+    let digest = Sha256::digest(serde_json::to_vec(plan)?);
+    Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Current time in milliseconds since the Unix epoch
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_from_url() {
+        let config = |url: &str| StorageConfig {
+            url: url.to_string(),
+            ..StorageConfig::default()
+        };
+
+        assert_eq!(
+            config("sqlite://history.db").backend().unwrap(),
+            StorageBackend::Sqlite
+        );
+        assert_eq!(
+            config("postgres://app:secret@db/sqltrace")
+                .backend()
+                .unwrap(),
+            StorageBackend::Postgres
+        );
+
+        let err = config("mysql://app:secret@db/sqltrace")
+            .backend()
+            .unwrap_err()
+            .to_string();
+        assert!(!err.contains("secret"));
+    }
+}
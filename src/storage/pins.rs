@@ -0,0 +1,207 @@
+//! Plans pinned as the expected plan of saved queries
+//!
+//! A critical query is usually reviewed with one plan, and a change of that
+//! plan after a migration or a statistics update is worth knowing about
+//! before it shows up as latency. Pinning a stored analysis of a saved query
+//! blesses the shape of its plan; every later analysis of a query with the
+//! same fingerprint is compared with the pin, and a different shape is
+//! reported together with the structural diff from the pinned plan.
+
+use serde::{Deserialize, Serialize};
+
+use super::findings::fingerprint;
+use super::{HistoryEntry, Result, SavedQuery, Storage};
+use crate::db::models::ExecutionPlan;
+use crate::db::plan_diff::{diff_plans, PlanDiff};
+use crate::snapshot;
+
+/// The plan a saved query is expected to keep
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanPin {
+    /// Saved query the plan is pinned for
+    pub saved_query_id: i64,
+    /// Fingerprint of the saved query, matched against analyzed queries
+    pub query_fingerprint: String,
+    /// Analysis the plan was pinned from
+    pub history_id: i64,
+    /// Fingerprint of the shape of the pinned plan
+    pub shape_fingerprint: String,
+    /// The pinned plan, kept apart from history so retention does not remove it
+    pub plan: serde_json::Value,
+    /// When the plan was pinned, in milliseconds since the Unix epoch
+    pub pinned_at: i64,
+}
+
+impl PlanPin {
+    /// Pin the plan of `entry` for `saved`
+    ///
+    /// Returns `None` if the analysis is of a different query than the saved
+    /// one, since its plan says nothing about the plan the saved query gets.
+    pub fn new(saved: &SavedQuery, entry: &HistoryEntry, now: i64) -> Result<Option<Self>> {
+        let query_fingerprint = fingerprint(&saved.query);
+        if fingerprint(&entry.query) != query_fingerprint {
+            return Ok(None);
+        }
+        // Parsing drops the raw EXPLAIN output history may keep with the plan
+        let plan: ExecutionPlan = serde_json::from_value(entry.plan.clone())?;
+        Ok(Some(Self {
+            saved_query_id: saved.id,
+            query_fingerprint,
+            history_id: entry.id,
+            shape_fingerprint: snapshot::normalize(&plan).fingerprint(),
+            plan: serde_json::to_value(&plan)?,
+            pinned_at: now,
+        }))
+    }
+
+    /// Compare a new plan of the saved query with the pinned one
+    pub fn check(&self, plan: &ExecutionPlan) -> Result<PinCheck> {
+        let fingerprint = snapshot::normalize(plan).fingerprint();
+        let matches = fingerprint == self.shape_fingerprint;
+        let diff = if matches {
+            None
+        } else {
+            let pinned: ExecutionPlan = serde_json::from_value(self.plan.clone())?;
+            Some(diff_plans(&pinned, plan))
+        };
+        Ok(PinCheck {
+            saved_query_id: self.saved_query_id,
+            pinned_history_id: self.history_id,
+            pinned_fingerprint: self.shape_fingerprint.clone(),
+            fingerprint,
+            matches,
+            diff,
+        })
+    }
+}
+
+/// Outcome of comparing a plan with the plan pinned for its query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinCheck {
+    /// Saved query whose pin the plan was compared with
+    pub saved_query_id: i64,
+    /// Analysis the pinned plan was taken from
+    pub pinned_history_id: i64,
+    /// Shape fingerprint of the pinned plan
+    pub pinned_fingerprint: String,
+    /// Shape fingerprint of the new plan
+    pub fingerprint: String,
+    /// Whether the new plan has the pinned shape
+    pub matches: bool,
+    /// Differences from the pinned plan to the new one, when the shapes differ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<PlanDiff>,
+}
+
+/// Compare the plan of an analyzed query with the plan pinned for it, if any
+pub async fn check(
+    storage: &dyn Storage,
+    query: &str,
+    plan: &ExecutionPlan,
+) -> Result<Option<PinCheck>> {
+    match storage.find_plan_pin(&fingerprint(query)).await? {
+        Some(pin) => pin.check(plan).map(Some),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::PlanNode;
+    use crate::storage::{NewHistoryEntry, NewSavedQuery, SqliteStorage, StorageConfig};
+    use serde_json::json;
+
+    fn plan(scan: &str) -> ExecutionPlan {
+        ExecutionPlan {
+            root: PlanNode {
+                node_type: scan.to_string(),
+                relation_name: Some("orders".to_string()),
+                total_cost: 42.0,
+                ..PlanNode::default()
+            },
+            planning_time: 0.1,
+            execution_time: 1.5,
+            settings: None,
+            jit: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pin_and_check() {
+        let storage = SqliteStorage::connect(&StorageConfig {
+            url: "sqlite::memory:".to_string(),
+            ..StorageConfig::default()
+        })
+        .await
+        .unwrap();
+        storage.migrate().await.unwrap();
+
+        let saved = storage
+            .save_query(NewSavedQuery {
+                name: "orders by user".to_string(),
+                query: "SELECT * FROM orders WHERE user_id = 42".to_string(),
+                description: None,
+            })
+            .await
+            .unwrap();
+        let mut plan_value = serde_json::to_value(plan("Index Scan")).unwrap();
+        plan_value["raw"] = json!([{"Plan": {}}]);
+        let entry = storage
+            .record_history(NewHistoryEntry {
+                query: "SELECT * FROM orders WHERE user_id = 7".to_string(),
+                plan: plan_value,
+                analysis: None,
+                performance_score: None,
+                total_cost: 42.0,
+                execution_time_ms: 1.5,
+                trace_id: None,
+                correlation_id: None,
+                table_definitions: None,
+                connection: None,
+            })
+            .await
+            .unwrap();
+
+        let pin = PlanPin::new(&saved, &entry, 1_000).unwrap().unwrap();
+        assert!(pin.plan.get("raw").is_none());
+        storage.put_plan_pin(&pin).await.unwrap();
+
+        let other = HistoryEntry {
+            query: "SELECT * FROM users".to_string(),
+            ..entry.clone()
+        };
+        assert!(PlanPin::new(&saved, &other, 1_000).unwrap().is_none());
+
+        let query = "SELECT * FROM orders WHERE user_id = 1";
+        let same = check(&storage, query, &plan("Index Scan"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(same.matches);
+        assert!(same.diff.is_none());
+
+        let changed = check(&storage, query, &plan("Seq Scan"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!changed.matches);
+        assert_eq!(changed.pinned_history_id, entry.id);
+        let diff = changed.diff.unwrap();
+        assert!(!diff.same_shape);
+        assert_eq!(diff.access_paths[0].after, ["Seq Scan"]);
+
+        assert!(check(&storage, "SELECT 1", &plan("Seq Scan"))
+            .await
+            .unwrap()
+            .is_none());
+
+        // Deleting the saved query removes its pin
+        assert!(storage.delete_saved_query(saved.id).await.unwrap());
+        assert!(storage.get_plan_pin(saved.id).await.unwrap().is_none());
+        assert!(check(&storage, query, &plan("Seq Scan"))
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,818 @@
+//! PostgreSQL storage backend
+//!
+//! Lets a shared SQLTrace instance keep its history in an existing PostgreSQL
+//! database. Tables are prefixed with `sqltrace_` so they can live alongside
+//! other applications' tables.
+
+use async_trait::async_trait;
+use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
+use sqlx::Row;
+
+use super::findings::{parse_severity, severity_name};
+use super::{
+    now_millis, plan_hash, BenchmarkKind, CostTimePoint, Finding, FindingFilter, FindingState,
+    HistoryEntry, HistoryStats, NewBenchmark, NewFinding, NewHistoryEntry, NewSavedQuery,
+    PlanEncoding, PlanPin, PruneReport, Result, RetentionPolicy, SavedQuery, ScorePoint, Storage,
+    StorageBackend, StorageConfig, StorageError, StoredBenchmark, StoredSettings,
+};
+use crate::db::credentials::redact_message;
+
+const SCHEMA: [&str; 21] = [
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_history (
+        id BIGSERIAL PRIMARY KEY,
+        query TEXT NOT NULL,
+        plan JSONB NOT NULL,
+        analysis JSONB,
+        performance_score SMALLINT,
+        total_cost DOUBLE PRECISION NOT NULL,
+        execution_time_ms DOUBLE PRECISION NOT NULL,
+        created_at BIGINT NOT NULL,
+        trace_id TEXT,
+        correlation_id TEXT,
+        table_definitions JSONB,
+        connection TEXT,
+        plan_packed BYTEA,
+        plan_hash TEXT
+    )"#,
+    "CREATE INDEX IF NOT EXISTS sqltrace_history_created_at ON sqltrace_history (created_at)",
+    // Columns added to tables created by earlier versions
+    "ALTER TABLE sqltrace_history ADD COLUMN IF NOT EXISTS trace_id TEXT",
+    "ALTER TABLE sqltrace_history ADD COLUMN IF NOT EXISTS correlation_id TEXT",
+    "ALTER TABLE sqltrace_history ADD COLUMN IF NOT EXISTS table_definitions JSONB",
+    "ALTER TABLE sqltrace_history ADD COLUMN IF NOT EXISTS connection TEXT",
+    "ALTER TABLE sqltrace_history ADD COLUMN IF NOT EXISTS plan_packed BYTEA",
+    "ALTER TABLE sqltrace_history ADD COLUMN IF NOT EXISTS plan_hash TEXT",
+    "CREATE INDEX IF NOT EXISTS sqltrace_history_trace_id ON sqltrace_history (trace_id)",
+    "CREATE INDEX IF NOT EXISTS sqltrace_history_correlation_id ON sqltrace_history (correlation_id)",
+    "CREATE INDEX IF NOT EXISTS sqltrace_history_plan_hash ON sqltrace_history (plan_hash)",
+    "CREATE INDEX IF NOT EXISTS sqltrace_history_connection ON sqltrace_history (connection, created_at)",
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_plans (
+        hash TEXT PRIMARY KEY,
+        plan JSONB NOT NULL,
+        plan_packed BYTEA,
+        stored_at BIGINT NOT NULL
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_saved_queries (
+        id BIGSERIAL PRIMARY KEY,
+        name TEXT NOT NULL,
+        query TEXT NOT NULL,
+        description TEXT,
+        created_at BIGINT NOT NULL
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_settings (
+        user_id TEXT PRIMARY KEY,
+        settings JSONB NOT NULL,
+        updated_at BIGINT NOT NULL
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_findings (
+        id BIGSERIAL PRIMARY KEY,
+        fingerprint TEXT NOT NULL,
+        finding_key TEXT NOT NULL,
+        rule_id TEXT NOT NULL,
+        severity TEXT NOT NULL,
+        title TEXT NOT NULL,
+        query TEXT NOT NULL,
+        state TEXT NOT NULL,
+        first_seen BIGINT NOT NULL,
+        last_seen BIGINT NOT NULL,
+        acknowledged_at BIGINT,
+        fixed_at BIGINT,
+        due_at BIGINT,
+        history_id BIGINT,
+        UNIQUE (fingerprint, finding_key)
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_benchmarks (
+        id BIGSERIAL PRIMARY KEY,
+        kind TEXT NOT NULL,
+        label TEXT NOT NULL,
+        result JSONB NOT NULL,
+        created_at BIGINT NOT NULL
+    )"#,
+    "CREATE INDEX IF NOT EXISTS sqltrace_benchmarks_created_at ON sqltrace_benchmarks (created_at)",
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_advisor_rules (
+        id SMALLINT PRIMARY KEY CHECK (id = 1),
+        overrides JSONB NOT NULL,
+        updated_at BIGINT NOT NULL
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_plan_pins (
+        saved_query_id BIGINT PRIMARY KEY,
+        query_fingerprint TEXT NOT NULL,
+        history_id BIGINT NOT NULL,
+        shape_fingerprint TEXT NOT NULL,
+        plan JSONB NOT NULL,
+        pinned_at BIGINT NOT NULL
+    )"#,
+    "CREATE INDEX IF NOT EXISTS sqltrace_plan_pins_query_fingerprint ON sqltrace_plan_pins (query_fingerprint)",
+];
+
+/// Approximate stored size of a history row, without the stored plan it references
+const ENTRY_SIZE: &str = "pg_column_size(query) + pg_column_size(plan) \
+     + coalesce(pg_column_size(analysis), 0) + coalesce(pg_column_size(table_definitions), 0) \
+     + coalesce(pg_column_size(plan_packed), 0)";
+
+/// Approximate stored size of a row of `sqltrace_plans`
+const PLAN_SIZE: &str = "pg_column_size(plan) + coalesce(pg_column_size(plan_packed), 0)";
+
+/// History entries with the stored plans they reference
+const SELECT_HISTORY: &str =
+    "SELECT h.*, p.plan AS stored_plan, p.plan_packed AS stored_plan_packed \
+     FROM sqltrace_history h LEFT JOIN sqltrace_plans p ON p.hash = h.plan_hash";
+
+/// Delete stored plans no history entry references any more
+const DELETE_UNUSED_PLANS: &str = "DELETE FROM sqltrace_plans WHERE NOT EXISTS \
+     (SELECT 1 FROM sqltrace_history WHERE plan_hash = sqltrace_plans.hash)";
+
+/// Storage backed by a PostgreSQL database
+#[derive(Debug, Clone)]
+pub struct PostgresStorage {
+    pool: PgPool,
+    plan_encoding: PlanEncoding,
+}
+
+impl PostgresStorage {
+    /// Connect to the PostgreSQL database at `config.url`
+    pub async fn connect(config: &StorageConfig) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(config.max_connections.max(1))
+            .connect(&config.url)
+            .await
+            .map_err(|e| StorageError::Backend(redact_message(&e.to_string(), &config.url)))?;
+
+        Ok(Self {
+            pool,
+            plan_encoding: config.plan_encoding,
+        })
+    }
+
+    /// Wrap an existing connection pool, storing plans as JSON
+    pub fn from_pool(pool: PgPool) -> Self {
+        Self {
+            pool,
+            plan_encoding: PlanEncoding::Json,
+        }
+    }
+
+    async fn insert_history(
+        &self,
+        entry: NewHistoryEntry,
+        created_at: i64,
+    ) -> Result<HistoryEntry> {
+        // Analyses that produced the same plan share one stored copy of it
+        let hash = plan_hash(&entry.plan)?;
+        let plan_packed = self.plan_encoding.pack(&entry.plan);
+        let plan = match plan_packed {
+            Some(_) => &serde_json::Value::Null,
+            None => &entry.plan,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO sqltrace_plans (hash, plan, plan_packed, stored_at) \
+             VALUES ($1, $2, $3, $4) ON CONFLICT (hash) DO NOTHING",
+        )
+        .bind(&hash)
+        .bind(plan)
+        .bind(plan_packed)
+        .bind(created_at)
+        .execute(&mut *tx)
+        .await?;
+        let id: i64 = sqlx::query(
+            "INSERT INTO sqltrace_history \
+             (query, plan, analysis, performance_score, total_cost, execution_time_ms, created_at, \
+              trace_id, correlation_id, table_definitions, connection, plan_hash) \
+             VALUES ($1, 'null', $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING id",
+        )
+        .bind(&entry.query)
+        .bind(&entry.analysis)
+        .bind(entry.performance_score.map(i16::from))
+        .bind(entry.total_cost)
+        .bind(entry.execution_time_ms)
+        .bind(created_at)
+        .bind(&entry.trace_id)
+        .bind(&entry.correlation_id)
+        .bind(&entry.table_definitions)
+        .bind(&entry.connection)
+        .bind(&hash)
+        .fetch_one(&mut *tx)
+        .await?
+        .try_get("id")?;
+        tx.commit().await?;
+
+        Ok(HistoryEntry {
+            id,
+            query: entry.query,
+            plan: entry.plan,
+            analysis: entry.analysis,
+            performance_score: entry.performance_score,
+            total_cost: entry.total_cost,
+            execution_time_ms: entry.execution_time_ms,
+            trace_id: entry.trace_id,
+            correlation_id: entry.correlation_id,
+            table_definitions: entry.table_definitions,
+            connection: entry.connection,
+            created_at,
+        })
+    }
+
+    async fn insert_saved_query(
+        &self,
+        query: NewSavedQuery,
+        created_at: i64,
+    ) -> Result<SavedQuery> {
+        let id: i64 = sqlx::query(
+            "INSERT INTO sqltrace_saved_queries (name, query, description, created_at) \
+             VALUES ($1, $2, $3, $4) RETURNING id",
+        )
+        .bind(&query.name)
+        .bind(&query.query)
+        .bind(&query.description)
+        .bind(created_at)
+        .fetch_one(&self.pool)
+        .await?
+        .try_get("id")?;
+
+        Ok(SavedQuery {
+            id,
+            name: query.name,
+            query: query.query,
+            description: query.description,
+            created_at,
+        })
+    }
+
+    async fn delete_unused_plans(&self) -> Result<()> {
+        sqlx::query(DELETE_UNUSED_PLANS).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    fn history_from_row(row: &PgRow) -> Result<HistoryEntry> {
+        let performance_score: Option<i16> = row.try_get("performance_score")?;
+        // Entries written before plans were stored by hash keep them inline
+        let plan_hash: Option<String> = row.try_get("plan_hash")?;
+        let (plan, plan_packed): (Option<serde_json::Value>, Option<Vec<u8>>) = match &plan_hash {
+            Some(_) => (
+                row.try_get("stored_plan")?,
+                row.try_get("stored_plan_packed")?,
+            ),
+            None => (row.try_get("plan")?, row.try_get("plan_packed")?),
+        };
+        let plan = match (plan_packed, plan) {
+            (Some(packed), _) => crate::msgpack::decode(&packed)?,
+            (None, Some(plan)) => plan,
+            (None, None) => {
+                return Err(StorageError::Backend(format!(
+                    "Stored plan {} is missing",
+                    plan_hash.unwrap_or_default()
+                )))
+            }
+        };
+
+        Ok(HistoryEntry {
+            id: row.try_get("id")?,
+            query: row.try_get("query")?,
+            plan,
+            analysis: row.try_get("analysis")?,
+            performance_score: performance_score.map(|s| s.clamp(0, 100) as u8),
+            total_cost: row.try_get("total_cost")?,
+            execution_time_ms: row.try_get("execution_time_ms")?,
+            trace_id: row.try_get("trace_id")?,
+            correlation_id: row.try_get("correlation_id")?,
+            table_definitions: row.try_get("table_definitions")?,
+            connection: row.try_get("connection")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+
+    fn saved_query_from_row(row: &PgRow) -> Result<SavedQuery> {
+        Ok(SavedQuery {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            query: row.try_get("query")?,
+            description: row.try_get("description")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+
+    fn settings_from_row(row: &PgRow) -> Result<StoredSettings> {
+        Ok(StoredSettings {
+            user_id: row.try_get("user_id")?,
+            settings: row.try_get("settings")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+
+    fn finding_from_row(row: &PgRow) -> Result<Finding> {
+        let severity: String = row.try_get("severity")?;
+        let state: String = row.try_get("state")?;
+        Ok(Finding {
+            id: row.try_get("id")?,
+            fingerprint: row.try_get("fingerprint")?,
+            finding_key: row.try_get("finding_key")?,
+            rule_id: row.try_get("rule_id")?,
+            severity: parse_severity(&severity),
+            title: row.try_get("title")?,
+            query: row.try_get("query")?,
+            state: FindingState::parse(&state).ok_or_else(|| {
+                StorageError::Backend(format!("Unknown finding state: {}", state))
+            })?,
+            first_seen: row.try_get("first_seen")?,
+            last_seen: row.try_get("last_seen")?,
+            acknowledged_at: row.try_get("acknowledged_at")?,
+            fixed_at: row.try_get("fixed_at")?,
+            due_at: row.try_get("due_at")?,
+            history_id: row.try_get("history_id")?,
+        })
+    }
+
+    fn plan_pin_from_row(row: &PgRow) -> Result<PlanPin> {
+        Ok(PlanPin {
+            saved_query_id: row.try_get("saved_query_id")?,
+            query_fingerprint: row.try_get("query_fingerprint")?,
+            history_id: row.try_get("history_id")?,
+            shape_fingerprint: row.try_get("shape_fingerprint")?,
+            plan: row.try_get("plan")?,
+            pinned_at: row.try_get("pinned_at")?,
+        })
+    }
+
+    fn benchmark_from_row(row: &PgRow) -> Result<StoredBenchmark> {
+        let kind: String = row.try_get("kind")?;
+        Ok(StoredBenchmark {
+            id: row.try_get("id")?,
+            kind: BenchmarkKind::parse(&kind).ok_or_else(|| {
+                StorageError::Backend(format!("Unknown benchmark kind: {}", kind))
+            })?,
+            label: row.try_get("label")?,
+            result: row.try_get("result")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    fn backend(&self) -> StorageBackend {
+        StorageBackend::Postgres
+    }
+
+    async fn migrate(&self) -> Result<()> {
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn record_history(&self, entry: NewHistoryEntry) -> Result<HistoryEntry> {
+        self.insert_history(entry, now_millis()).await
+    }
+
+    async fn restore_history(&self, entry: HistoryEntry) -> Result<HistoryEntry> {
+        let created_at = entry.created_at;
+        self.insert_history(entry.into(), created_at).await
+    }
+
+    async fn list_history(&self, limit: i64, offset: i64) -> Result<Vec<HistoryEntry>> {
+        sqlx::query(&format!(
+            "{} ORDER BY h.created_at DESC, h.id DESC LIMIT $1 OFFSET $2",
+            SELECT_HISTORY
+        ))
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(Self::history_from_row)
+        .collect()
+    }
+
+    async fn find_history_by_correlation(&self, id: &str, limit: i64) -> Result<Vec<HistoryEntry>> {
+        sqlx::query(&format!(
+            "{} WHERE h.trace_id = $1 OR h.correlation_id = $1 \
+             ORDER BY h.created_at DESC, h.id DESC LIMIT $2",
+            SELECT_HISTORY
+        ))
+        .bind(id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(Self::history_from_row)
+        .collect()
+    }
+
+    async fn cost_time_points(
+        &self,
+        connection: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<CostTimePoint>> {
+        sqlx::query(
+            "SELECT id, connection, total_cost, execution_time_ms, created_at \
+             FROM sqltrace_history WHERE execution_time_ms > 0 AND ($1::text IS NULL OR connection = $1) \
+             ORDER BY created_at DESC, id DESC LIMIT $2",
+        )
+        .bind(connection)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| {
+            Ok(CostTimePoint {
+                history_id: row.try_get("id")?,
+                connection: row.try_get("connection")?,
+                total_cost: row.try_get("total_cost")?,
+                execution_time_ms: row.try_get("execution_time_ms")?,
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .collect()
+    }
+
+    async fn score_points(&self, connection: &str, since: i64) -> Result<Vec<ScorePoint>> {
+        sqlx::query(
+            "SELECT id, query, performance_score, created_at FROM sqltrace_history \
+             WHERE connection = $1 AND created_at >= $2 ORDER BY created_at, id",
+        )
+        .bind(connection)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| {
+            let performance_score: Option<i16> = row.try_get("performance_score")?;
+            Ok(ScorePoint {
+                history_id: row.try_get("id")?,
+                query: row.try_get("query")?,
+                performance_score: performance_score.map(|s| s.clamp(0, 100) as u8),
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .collect()
+    }
+
+    async fn get_history(&self, id: i64) -> Result<Option<HistoryEntry>> {
+        sqlx::query(&format!("{} WHERE h.id = $1", SELECT_HISTORY))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::history_from_row)
+            .transpose()
+    }
+
+    async fn update_history_analysis(
+        &self,
+        id: i64,
+        analysis: &serde_json::Value,
+        performance_score: u8,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE sqltrace_history SET analysis = $1, performance_score = $2 WHERE id = $3",
+        )
+        .bind(analysis)
+        .bind(i64::from(performance_score))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_history(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM sqltrace_history WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        self.delete_unused_plans().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn prune_history(&self, policy: &RetentionPolicy, now: i64) -> Result<PruneReport> {
+        let mut report = PruneReport::default();
+
+        if let Some(cutoff) = policy.cutoff_millis(now) {
+            report.deleted_by_age =
+                sqlx::query("DELETE FROM sqltrace_history WHERE created_at < $1")
+                    .bind(cutoff)
+                    .execute(&self.pool)
+                    .await?
+                    .rows_affected();
+        }
+
+        if let Some(max_count) = policy.max_count {
+            report.deleted_by_count = sqlx::query(
+                "DELETE FROM sqltrace_history WHERE id IN (\
+                 SELECT id FROM sqltrace_history ORDER BY created_at DESC, id DESC OFFSET $1)",
+            )
+            .bind(max_count as i64)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        }
+
+        if let Some(max_bytes) = policy.max_size_bytes() {
+            let statement = format!(
+                "DELETE FROM sqltrace_history WHERE id IN (\
+                 SELECT id FROM (SELECT id, SUM({} + coalesce((SELECT {} FROM sqltrace_plans \
+                 WHERE hash = sqltrace_history.plan_hash), 0)) OVER (ORDER BY created_at DESC, id DESC) \
+                 AS running FROM sqltrace_history) sized WHERE running > $1)",
+                ENTRY_SIZE, PLAN_SIZE
+            );
+            report.deleted_by_size = sqlx::query(&statement)
+                .bind(max_bytes)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+        }
+
+        if report.total() > 0 {
+            self.delete_unused_plans().await?;
+        }
+        Ok(report)
+    }
+
+    async fn purge_history(&self) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM sqltrace_history")
+            .execute(&self.pool)
+            .await?;
+        self.delete_unused_plans().await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn history_stats(&self) -> Result<HistoryStats> {
+        let statement = format!(
+            "SELECT (SELECT COUNT(*) FROM sqltrace_history) AS count, \
+             (SELECT COUNT(*) FROM sqltrace_plans) AS plans, \
+             ((SELECT COALESCE(SUM({}), 0) FROM sqltrace_history) \
+             + (SELECT COALESCE(SUM({}), 0) FROM sqltrace_plans))::int8 AS size_bytes",
+            ENTRY_SIZE, PLAN_SIZE
+        );
+        let row = sqlx::query(&statement).fetch_one(&self.pool).await?;
+        Ok(HistoryStats {
+            count: row.try_get("count")?,
+            plans: row.try_get("plans")?,
+            size_bytes: row.try_get("size_bytes")?,
+        })
+    }
+
+    async fn save_query(&self, query: NewSavedQuery) -> Result<SavedQuery> {
+        self.insert_saved_query(query, now_millis()).await
+    }
+
+    async fn restore_saved_query(&self, query: SavedQuery) -> Result<SavedQuery> {
+        let created_at = query.created_at;
+        self.insert_saved_query(query.into(), created_at).await
+    }
+
+    async fn list_saved_queries(&self) -> Result<Vec<SavedQuery>> {
+        sqlx::query("SELECT * FROM sqltrace_saved_queries ORDER BY name, id")
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(Self::saved_query_from_row)
+            .collect()
+    }
+
+    async fn get_saved_query(&self, id: i64) -> Result<Option<SavedQuery>> {
+        sqlx::query("SELECT * FROM sqltrace_saved_queries WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::saved_query_from_row)
+            .transpose()
+    }
+
+    async fn delete_saved_query(&self, id: i64) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM sqltrace_plan_pins WHERE saved_query_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM sqltrace_saved_queries WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn put_plan_pin(&self, pin: &PlanPin) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO sqltrace_plan_pins \
+             (saved_query_id, query_fingerprint, history_id, shape_fingerprint, plan, pinned_at) \
+             VALUES ($1, $2, $3, $4, $5, $6) \
+             ON CONFLICT (saved_query_id) DO UPDATE SET \
+             query_fingerprint = excluded.query_fingerprint, history_id = excluded.history_id, \
+             shape_fingerprint = excluded.shape_fingerprint, plan = excluded.plan, \
+             pinned_at = excluded.pinned_at",
+        )
+        .bind(pin.saved_query_id)
+        .bind(&pin.query_fingerprint)
+        .bind(pin.history_id)
+        .bind(&pin.shape_fingerprint)
+        .bind(&pin.plan)
+        .bind(pin.pinned_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_plan_pin(&self, saved_query_id: i64) -> Result<Option<PlanPin>> {
+        sqlx::query("SELECT * FROM sqltrace_plan_pins WHERE saved_query_id = $1")
+            .bind(saved_query_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::plan_pin_from_row)
+            .transpose()
+    }
+
+    async fn find_plan_pin(&self, query_fingerprint: &str) -> Result<Option<PlanPin>> {
+        sqlx::query(
+            "SELECT * FROM sqltrace_plan_pins WHERE query_fingerprint = $1 \
+             ORDER BY pinned_at DESC, saved_query_id DESC LIMIT 1",
+        )
+        .bind(query_fingerprint)
+        .fetch_optional(&self.pool)
+        .await?
+        .as_ref()
+        .map(Self::plan_pin_from_row)
+        .transpose()
+    }
+
+    async fn delete_plan_pin(&self, saved_query_id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM sqltrace_plan_pins WHERE saved_query_id = $1")
+            .bind(saved_query_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_settings(&self, user_id: &str) -> Result<Option<StoredSettings>> {
+        sqlx::query("SELECT * FROM sqltrace_settings WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::settings_from_row)
+            .transpose()
+    }
+
+    async fn put_settings(
+        &self,
+        user_id: &str,
+        settings: &serde_json::Value,
+    ) -> Result<StoredSettings> {
+        let updated_at = now_millis();
+        sqlx::query(
+            "INSERT INTO sqltrace_settings (user_id, settings, updated_at) VALUES ($1, $2, $3) \
+             ON CONFLICT (user_id) DO UPDATE SET settings = excluded.settings, \
+             updated_at = excluded.updated_at",
+        )
+        .bind(user_id)
+        .bind(settings)
+        .bind(updated_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(StoredSettings {
+            user_id: user_id.to_string(),
+            settings: settings.clone(),
+            updated_at,
+        })
+    }
+
+    async fn delete_settings(&self, user_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM sqltrace_settings WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_settings(&self) -> Result<Vec<StoredSettings>> {
+        sqlx::query("SELECT * FROM sqltrace_settings ORDER BY user_id")
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(Self::settings_from_row)
+            .collect()
+    }
+
+    async fn get_advisor_rules(&self) -> Result<Option<serde_json::Value>> {
+        Ok(
+            sqlx::query_scalar("SELECT overrides FROM sqltrace_advisor_rules WHERE id = 1")
+                .fetch_optional(&self.pool)
+                .await?,
+        )
+    }
+
+    async fn put_advisor_rules(&self, rules: &serde_json::Value) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO sqltrace_advisor_rules (id, overrides, updated_at) VALUES (1, $1, $2) \
+             ON CONFLICT (id) DO UPDATE SET overrides = excluded.overrides, \
+             updated_at = excluded.updated_at",
+        )
+        .bind(rules)
+        .bind(now_millis())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn record_finding(&self, finding: NewFinding) -> Result<Finding> {
+        let row = sqlx::query(
+            "INSERT INTO sqltrace_findings \
+             (fingerprint, finding_key, rule_id, severity, title, query, state, \
+              first_seen, last_seen, due_at, history_id) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8, $9, $10) RETURNING *",
+        )
+        .bind(&finding.fingerprint)
+        .bind(&finding.finding_key)
+        .bind(&finding.rule_id)
+        .bind(severity_name(&finding.severity))
+        .bind(&finding.title)
+        .bind(&finding.query)
+        .bind(FindingState::New.as_str())
+        .bind(finding.first_seen)
+        .bind(finding.due_at)
+        .bind(finding.history_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Self::finding_from_row(&row)
+    }
+
+    async fn list_findings(&self, filter: &FindingFilter) -> Result<Vec<Finding>> {
+        sqlx::query(
+            "SELECT * FROM sqltrace_findings \
+             WHERE ($1::text IS NULL OR fingerprint = $1) AND ($2::text IS NULL OR state = $2) \
+             ORDER BY last_seen DESC, id DESC",
+        )
+        .bind(filter.fingerprint.as_deref())
+        .bind(filter.state.map(|state| state.as_str()))
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(Self::finding_from_row)
+        .collect()
+    }
+
+    async fn get_finding(&self, id: i64) -> Result<Option<Finding>> {
+        sqlx::query("SELECT * FROM sqltrace_findings WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::finding_from_row)
+            .transpose()
+    }
+
+    async fn update_finding(&self, finding: &Finding) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE sqltrace_findings SET severity = $1, title = $2, query = $3, state = $4, \
+             last_seen = $5, acknowledged_at = $6, fixed_at = $7, due_at = $8, history_id = $9 \
+             WHERE id = $10",
+        )
+        .bind(severity_name(&finding.severity))
+        .bind(&finding.title)
+        .bind(&finding.query)
+        .bind(finding.state.as_str())
+        .bind(finding.last_seen)
+        .bind(finding.acknowledged_at)
+        .bind(finding.fixed_at)
+        .bind(finding.due_at)
+        .bind(finding.history_id)
+        .bind(finding.id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn record_benchmark(&self, benchmark: NewBenchmark) -> Result<StoredBenchmark> {
+        let row = sqlx::query(
+            "INSERT INTO sqltrace_benchmarks (kind, label, result, created_at) \
+             VALUES ($1, $2, $3, $4) RETURNING *",
+        )
+        .bind(benchmark.kind.as_str())
+        .bind(&benchmark.label)
+        .bind(&benchmark.result)
+        .bind(now_millis())
+        .fetch_one(&self.pool)
+        .await?;
+        Self::benchmark_from_row(&row)
+    }
+
+    async fn get_benchmark(&self, id: i64) -> Result<Option<StoredBenchmark>> {
+        sqlx::query("SELECT * FROM sqltrace_benchmarks WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::benchmark_from_row)
+            .transpose()
+    }
+}
@@ -0,0 +1,224 @@
+//! Re-running the advisor over stored analyses
+//!
+//! Scores and findings in history were produced by the rules as they were
+//! when each query was analyzed. After rules are tuned, disabled, or added,
+//! re-running the advisor over the stored plans, without touching the
+//! database, brings history in line with the current rule set. Only the
+//! latest analysis of each query fingerprint updates its findings, since
+//! findings follow the latest analysis.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use super::findings::{self, FindingsConfig, Observation};
+use super::{Result, Storage};
+use crate::advisor::{AnalysisContext, QueryAdvisor};
+use crate::db::models::ExecutionPlan;
+
+/// Stored analyses read per page while re-analyzing
+const PAGE_SIZE: i64 = 100;
+
+/// A stored analysis that could not be re-analyzed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedAnalysis {
+    /// Id of the analysis
+    pub history_id: i64,
+    /// Why it was skipped
+    pub reason: String,
+}
+
+/// Outcome of re-analyzing stored analyses
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReanalysisReport {
+    /// Analyses read from history
+    pub scanned: usize,
+    /// Analyses whose advisor output was replaced
+    pub updated: usize,
+    /// Updated analyses whose performance score changed
+    pub score_changed: usize,
+    /// Query fingerprints whose findings were updated
+    pub fingerprints: usize,
+    /// Analyses left unchanged because their plan could not be read
+    pub skipped: Vec<SkippedAnalysis>,
+}
+
+/// Re-run `advisor` over the `limit` most recent stored analyses
+///
+/// Stored plans carry no catalog metadata, so rules that need relation sizes,
+/// statistics, or indexes do not fire; plans without actual row counts are
+/// analyzed as estimates, as imported auto_explain plans are.
+pub async fn reanalyze(
+    storage: &dyn Storage,
+    advisor: &QueryAdvisor,
+    limit: i64,
+    findings_config: &FindingsConfig,
+    now: i64,
+) -> Result<ReanalysisReport> {
+    let mut report = ReanalysisReport::default();
+    let mut tracked = HashSet::new();
+    let mut offset = 0;
+
+    while offset < limit {
+        let entries = storage
+            .list_history(PAGE_SIZE.min(limit - offset), offset)
+            .await?;
+        if entries.is_empty() {
+            break;
+        }
+        offset += entries.len() as i64;
+
+        // Entries come newest first, so the first one of a fingerprint is its latest
+        for entry in entries {
+            report.scanned += 1;
+            let plan: ExecutionPlan = match serde_json::from_value(entry.plan) {
+                Ok(plan) => plan,
+                Err(e) => {
+                    report.skipped.push(SkippedAnalysis {
+                        history_id: entry.id,
+                        reason: format!("Invalid plan: {}", e),
+                    });
+                    continue;
+                }
+            };
+
+            let context = AnalysisContext {
+                estimates_only: plan.root.actual_loops == 0,
+                ..AnalysisContext::default()
+            };
+            let analysis = advisor.analyze_plan_with_context(&plan, &context);
+            let score = analysis.performance_score;
+            if storage
+                .update_history_analysis(entry.id, &serde_json::to_value(&analysis)?, score)
+                .await?
+            {
+                report.updated += 1;
+                if entry.performance_score != Some(score) {
+                    report.score_changed += 1;
+                }
+            }
+
+            let fingerprint = findings::fingerprint(&entry.query);
+            if findings_config.enabled && tracked.insert(fingerprint.clone()) {
+                let observation = Observation {
+                    fingerprint: &fingerprint,
+                    query: &entry.query,
+                    history_id: Some(entry.id),
+                    now,
+                };
+                findings::track(
+                    storage,
+                    &analysis.suggestions,
+                    observation,
+                    &findings_config.sla_days,
+                )
+                .await?;
+                report.fingerprints += 1;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advisor::rules::RULES;
+    use crate::advisor::AdvisorConfig;
+    use crate::storage::{
+        FindingFilter, FindingState, NewHistoryEntry, SqliteStorage, StorageConfig,
+    };
+    use serde_json::json;
+
+    fn seq_scan_plan() -> serde_json::Value {
+        json!({
+            "root": {
+                "Node Type": "Seq Scan",
+                "Relation Name": "orders",
+                "Startup Cost": 0.0,
+                "Total Cost": 25000.0,
+                "Plan Rows": 1000000,
+                "Plan Width": 8,
+                "Actual Startup Time": 0.1,
+                "Actual Total Time": 900.0,
+                "Actual Rows": 1000000,
+                "Actual Loops": 1
+            },
+            "planning_time": 0.1,
+            "execution_time": 900.0
+        })
+    }
+
+    #[tokio::test]
+    async fn test_reanalyze_applies_current_rules() {
+        let storage = SqliteStorage::connect(&StorageConfig {
+            url: "sqlite::memory:".to_string(),
+            ..StorageConfig::default()
+        })
+        .await
+        .unwrap();
+        storage.migrate().await.unwrap();
+
+        let mut ids = Vec::new();
+        for (query, plan) in [
+            ("SELECT * FROM orders WHERE id = 1", seq_scan_plan()),
+            ("SELECT * FROM users", json!({"root": "not a plan"})),
+            ("SELECT * FROM orders WHERE id = 2", seq_scan_plan()),
+        ] {
+            let entry = storage
+                .record_history(NewHistoryEntry {
+                    query: query.to_string(),
+                    plan,
+                    analysis: None,
+                    performance_score: Some(100),
+                    total_cost: 25000.0,
+                    execution_time_ms: 900.0,
+                    trace_id: None,
+                    correlation_id: None,
+                    table_definitions: None,
+                    connection: None,
+                })
+                .await
+                .unwrap();
+            ids.push(entry.id);
+        }
+
+        let advisor = QueryAdvisor::new();
+        let config = FindingsConfig::default();
+        let report = reanalyze(&storage, &advisor, 10, &config, 1_000)
+            .await
+            .unwrap();
+        assert_eq!(report.scanned, 3);
+        assert_eq!(report.updated, 2);
+        assert_eq!(report.score_changed, 2);
+        assert_eq!(report.fingerprints, 1);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].history_id, ids[1]);
+
+        let entry = storage.get_history(ids[0]).await.unwrap().unwrap();
+        assert!(entry.performance_score.unwrap() < 100);
+        assert!(entry.analysis.is_some());
+        let open = storage
+            .list_findings(&FindingFilter::default())
+            .await
+            .unwrap();
+        assert!(!open.is_empty());
+        // The latest analysis of the fingerprint is the one findings point at
+        assert!(open.iter().all(|f| f.history_id == Some(ids[2])));
+
+        // With every rule disabled, the findings are fixed
+        let quiet = QueryAdvisor::with_config(AdvisorConfig {
+            disabled_rules: RULES.iter().map(|rule| rule.id.to_string()).collect(),
+            ..AdvisorConfig::default()
+        });
+        let report = reanalyze(&storage, &quiet, 1, &config, 2_000)
+            .await
+            .unwrap();
+        assert_eq!(report.scanned, 1);
+        let findings = storage
+            .list_findings(&FindingFilter::default())
+            .await
+            .unwrap();
+        assert!(findings.iter().all(|f| f.state == FindingState::Fixed));
+    }
+}
@@ -0,0 +1,132 @@
+//! Retention policies for stored history
+//!
+//! Long-running deployments accumulate analyses (and the raw plans stored
+//! with them) indefinitely. A [`RetentionPolicy`] bounds history by age,
+//! count, and approximate size; it is applied periodically by a background
+//! task and on demand through the purge API.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{now_millis, Storage};
+
+/// Limits applied to stored history; `None` disables a limit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Delete entries older than this many days
+    pub max_age_days: Option<u64>,
+    /// Keep at most this many entries, deleting the oldest first
+    pub max_count: Option<u64>,
+    /// Keep stored queries, plans, and analyses under this many megabytes
+    pub max_size_mb: Option<u64>,
+    /// How often the background task applies the policy, in seconds
+    pub prune_interval_secs: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_age_days: None,
+            max_count: None,
+            max_size_mb: None,
+            prune_interval_secs: 3600,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Whether any limit is configured
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_days.is_some() || self.max_count.is_some() || self.max_size_mb.is_some()
+    }
+
+    /// Creation time (ms since epoch) before which entries are expired, given the current time
+    pub fn cutoff_millis(&self, now: i64) -> Option<i64> {
+        self.max_age_days
+            .map(|days| now.saturating_sub((days as i64).saturating_mul(86_400_000)))
+    }
+
+    /// Size limit in bytes
+    pub fn max_size_bytes(&self) -> Option<i64> {
+        self.max_size_mb
+            .map(|mb| (mb as i64).saturating_mul(1024 * 1024))
+    }
+}
+
+/// Number of history entries removed by a pruning pass, per limit
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneReport {
+    /// Entries older than the maximum age
+    pub deleted_by_age: u64,
+    /// Entries beyond the maximum count
+    pub deleted_by_count: u64,
+    /// Entries removed to get under the size limit
+    pub deleted_by_size: u64,
+}
+
+impl PruneReport {
+    /// Total number of deleted entries
+    pub fn total(&self) -> u64 {
+        self.deleted_by_age + self.deleted_by_count + self.deleted_by_size
+    }
+}
+
+/// Current volume of stored history
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryStats {
+    /// Number of stored entries
+    pub count: i64,
+    /// Number of distinct plans the entries reference
+    #[serde(default)]
+    pub plans: i64,
+    /// Approximate bytes used by queries, plans, and analyses
+    pub size_bytes: i64,
+}
+
+/// Apply `policy` to `storage` every `policy.prune_interval_secs`
+///
+/// Returns `None` without spawning anything when the policy has no limits.
+pub fn spawn_pruning(
+    storage: Arc<dyn Storage>,
+    policy: RetentionPolicy,
+) -> Option<tokio::task::JoinHandle<()>> {
+    if !policy.is_enabled() {
+        return None;
+    }
+
+    let interval = Duration::from_secs(policy.prune_interval_secs.max(1));
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match storage.prune_history(&policy, now_millis()).await {
+                Ok(report) if report.total() > 0 => {
+                    tracing::info!("Pruned {} history entries: {:?}", report.total(), report)
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("History pruning failed: {}", e),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_limits() {
+        let policy = RetentionPolicy {
+            max_age_days: Some(2),
+            max_size_mb: Some(1),
+            ..RetentionPolicy::default()
+        };
+
+        assert!(policy.is_enabled());
+        assert_eq!(policy.cutoff_millis(3 * 86_400_000), Some(86_400_000));
+        assert_eq!(policy.max_size_bytes(), Some(1_048_576));
+        assert!(!RetentionPolicy::default().is_enabled());
+    }
+}
@@ -0,0 +1,1275 @@
+//! SQLite storage backend
+//!
+//! The default backend: history lives in a local file next to the binary, or
+//! in memory when the URL is `sqlite::memory:`.
+
+use async_trait::async_trait;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::Row;
+use std::str::FromStr;
+
+use super::findings::{parse_severity, severity_name};
+use super::{
+    now_millis, plan_hash, BenchmarkKind, CostTimePoint, Finding, FindingFilter, FindingState,
+    HistoryEntry, HistoryStats, NewBenchmark, NewFinding, NewHistoryEntry, NewSavedQuery,
+    PlanEncoding, PlanPin, PruneReport, Result, RetentionPolicy, SavedQuery, ScorePoint, Storage,
+    StorageBackend, StorageConfig, StorageError, StoredBenchmark, StoredSettings,
+};
+
+const SCHEMA: [&str; 11] = [
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        query TEXT NOT NULL,
+        plan TEXT NOT NULL,
+        analysis TEXT,
+        performance_score INTEGER,
+        total_cost REAL NOT NULL,
+        execution_time_ms REAL NOT NULL,
+        created_at INTEGER NOT NULL,
+        trace_id TEXT,
+        correlation_id TEXT,
+        table_definitions TEXT,
+        connection TEXT,
+        plan_packed BLOB,
+        plan_hash TEXT
+    )"#,
+    "CREATE INDEX IF NOT EXISTS sqltrace_history_created_at ON sqltrace_history (created_at)",
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_saved_queries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        query TEXT NOT NULL,
+        description TEXT,
+        created_at INTEGER NOT NULL
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_settings (
+        user_id TEXT PRIMARY KEY,
+        settings TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_findings (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        fingerprint TEXT NOT NULL,
+        finding_key TEXT NOT NULL,
+        rule_id TEXT NOT NULL,
+        severity TEXT NOT NULL,
+        title TEXT NOT NULL,
+        query TEXT NOT NULL,
+        state TEXT NOT NULL,
+        first_seen INTEGER NOT NULL,
+        last_seen INTEGER NOT NULL,
+        acknowledged_at INTEGER,
+        fixed_at INTEGER,
+        due_at INTEGER,
+        history_id INTEGER,
+        UNIQUE (fingerprint, finding_key)
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_benchmarks (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind TEXT NOT NULL,
+        label TEXT NOT NULL,
+        result TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    )"#,
+    "CREATE INDEX IF NOT EXISTS sqltrace_benchmarks_created_at ON sqltrace_benchmarks (created_at)",
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_plans (
+        hash TEXT PRIMARY KEY,
+        plan TEXT NOT NULL,
+        plan_packed BLOB,
+        stored_at INTEGER NOT NULL
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_advisor_rules (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        overrides TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS sqltrace_plan_pins (
+        saved_query_id INTEGER PRIMARY KEY,
+        query_fingerprint TEXT NOT NULL,
+        history_id INTEGER NOT NULL,
+        shape_fingerprint TEXT NOT NULL,
+        plan TEXT NOT NULL,
+        pinned_at INTEGER NOT NULL
+    )"#,
+    "CREATE INDEX IF NOT EXISTS sqltrace_plan_pins_query_fingerprint ON sqltrace_plan_pins (query_fingerprint)",
+];
+
+/// Columns added to `sqltrace_history` after its first release, with their types
+const ADDED_HISTORY_COLUMNS: [(&str, &str); 6] = [
+    ("trace_id", "TEXT"),
+    ("correlation_id", "TEXT"),
+    ("table_definitions", "TEXT"),
+    ("connection", "TEXT"),
+    ("plan_packed", "BLOB"),
+    ("plan_hash", "TEXT"),
+];
+
+/// Indexes on columns that older databases only have after migration
+const ADDED_HISTORY_INDEXES: [&str; 4] = [
+    "CREATE INDEX IF NOT EXISTS sqltrace_history_trace_id ON sqltrace_history (trace_id)",
+    "CREATE INDEX IF NOT EXISTS sqltrace_history_correlation_id ON sqltrace_history (correlation_id)",
+    "CREATE INDEX IF NOT EXISTS sqltrace_history_plan_hash ON sqltrace_history (plan_hash)",
+    "CREATE INDEX IF NOT EXISTS sqltrace_history_connection ON sqltrace_history (connection, created_at)",
+];
+
+/// History entries with the stored plans they reference
+const SELECT_HISTORY: &str =
+    "SELECT h.*, p.plan AS stored_plan, p.plan_packed AS stored_plan_packed \
+     FROM sqltrace_history h LEFT JOIN sqltrace_plans p ON p.hash = h.plan_hash";
+
+/// Delete stored plans no history entry references any more
+const DELETE_UNUSED_PLANS: &str = "DELETE FROM sqltrace_plans WHERE NOT EXISTS \
+     (SELECT 1 FROM sqltrace_history WHERE plan_hash = sqltrace_plans.hash)";
+
+/// Approximate stored size of a history row, without the stored plan it references
+const ENTRY_SIZE: &str = "length(CAST(query AS BLOB)) + length(CAST(plan AS BLOB)) \
+     + coalesce(length(CAST(analysis AS BLOB)), 0) \
+     + coalesce(length(CAST(table_definitions AS BLOB)), 0) + coalesce(length(plan_packed), 0)";
+
+/// Approximate stored size of a row of `sqltrace_plans`
+const PLAN_SIZE: &str = "length(CAST(plan AS BLOB)) + coalesce(length(plan_packed), 0)";
+
+/// Storage backed by a SQLite database
+#[derive(Debug, Clone)]
+pub struct SqliteStorage {
+    pool: SqlitePool,
+    plan_encoding: PlanEncoding,
+}
+
+impl SqliteStorage {
+    /// Open (creating if needed) the SQLite database at `config.url`
+    pub async fn connect(config: &StorageConfig) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(&config.url)?.create_if_missing(true);
+
+        // Every connection to an in-memory database gets its own empty database
+        let in_memory = config.url.contains(":memory:") || config.url.contains("mode=memory");
+        let max_connections = if in_memory {
+            1
+        } else {
+            config.max_connections.max(1)
+        };
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await?;
+
+        Ok(Self {
+            pool,
+            plan_encoding: config.plan_encoding,
+        })
+    }
+
+    async fn insert_history(
+        &self,
+        entry: NewHistoryEntry,
+        created_at: i64,
+    ) -> Result<HistoryEntry> {
+        let analysis = entry
+            .analysis
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        let table_definitions = entry
+            .table_definitions
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        // Analyses that produced the same plan share one stored copy of it
+        let hash = plan_hash(&entry.plan)?;
+        let plan_packed = self.plan_encoding.pack(&entry.plan);
+        let plan = match plan_packed {
+            Some(_) => "null".to_string(),
+            None => serde_json::to_string(&entry.plan)?,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO sqltrace_plans (hash, plan, plan_packed, stored_at) \
+             VALUES ($1, $2, $3, $4) ON CONFLICT (hash) DO NOTHING",
+        )
+        .bind(&hash)
+        .bind(plan)
+        .bind(plan_packed)
+        .bind(created_at)
+        .execute(&mut *tx)
+        .await?;
+        let id: i64 = sqlx::query(
+            "INSERT INTO sqltrace_history \
+             (query, plan, analysis, performance_score, total_cost, execution_time_ms, created_at, \
+              trace_id, correlation_id, table_definitions, connection, plan_hash) \
+             VALUES ($1, 'null', $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING id",
+        )
+        .bind(&entry.query)
+        .bind(analysis)
+        .bind(entry.performance_score.map(i64::from))
+        .bind(entry.total_cost)
+        .bind(entry.execution_time_ms)
+        .bind(created_at)
+        .bind(&entry.trace_id)
+        .bind(&entry.correlation_id)
+        .bind(table_definitions)
+        .bind(&entry.connection)
+        .bind(&hash)
+        .fetch_one(&mut *tx)
+        .await?
+        .try_get("id")?;
+        tx.commit().await?;
+
+        Ok(HistoryEntry {
+            id,
+            query: entry.query,
+            plan: entry.plan,
+            analysis: entry.analysis,
+            performance_score: entry.performance_score,
+            total_cost: entry.total_cost,
+            execution_time_ms: entry.execution_time_ms,
+            trace_id: entry.trace_id,
+            correlation_id: entry.correlation_id,
+            table_definitions: entry.table_definitions,
+            connection: entry.connection,
+            created_at,
+        })
+    }
+
+    async fn insert_saved_query(
+        &self,
+        query: NewSavedQuery,
+        created_at: i64,
+    ) -> Result<SavedQuery> {
+        let id: i64 = sqlx::query(
+            "INSERT INTO sqltrace_saved_queries (name, query, description, created_at) \
+             VALUES ($1, $2, $3, $4) RETURNING id",
+        )
+        .bind(&query.name)
+        .bind(&query.query)
+        .bind(&query.description)
+        .bind(created_at)
+        .fetch_one(&self.pool)
+        .await?
+        .try_get("id")?;
+
+        Ok(SavedQuery {
+            id,
+            name: query.name,
+            query: query.query,
+            description: query.description,
+            created_at,
+        })
+    }
+
+    fn history_from_row(row: &SqliteRow) -> Result<HistoryEntry> {
+        // Entries written before plans were stored by hash keep them inline
+        let plan_hash: Option<String> = row.try_get("plan_hash")?;
+        let (plan, plan_packed): (Option<String>, Option<Vec<u8>>) = match &plan_hash {
+            Some(_) => (
+                row.try_get("stored_plan")?,
+                row.try_get("stored_plan_packed")?,
+            ),
+            None => (row.try_get("plan")?, row.try_get("plan_packed")?),
+        };
+        let plan = match (plan_packed, plan) {
+            (Some(packed), _) => crate::msgpack::decode(&packed)?,
+            (None, Some(plan)) => serde_json::from_str(&plan)?,
+            (None, None) => {
+                return Err(StorageError::Backend(format!(
+                    "Stored plan {} is missing",
+                    plan_hash.unwrap_or_default()
+                )))
+            }
+        };
+        let analysis: Option<String> = row.try_get("analysis")?;
+        let table_definitions: Option<String> = row.try_get("table_definitions")?;
+        let performance_score: Option<i64> = row.try_get("performance_score")?;
+
+        Ok(HistoryEntry {
+            id: row.try_get("id")?,
+            query: row.try_get("query")?,
+            plan,
+            analysis: analysis.map(|a| serde_json::from_str(&a)).transpose()?,
+            performance_score: performance_score.map(|s| s.clamp(0, 100) as u8),
+            total_cost: row.try_get("total_cost")?,
+            execution_time_ms: row.try_get("execution_time_ms")?,
+            trace_id: row.try_get("trace_id")?,
+            correlation_id: row.try_get("correlation_id")?,
+            table_definitions: table_definitions
+                .map(|t| serde_json::from_str(&t))
+                .transpose()?,
+            connection: row.try_get("connection")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+
+    async fn delete_unused_plans(&self) -> Result<()> {
+        sqlx::query(DELETE_UNUSED_PLANS).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    fn saved_query_from_row(row: &SqliteRow) -> Result<SavedQuery> {
+        Ok(SavedQuery {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            query: row.try_get("query")?,
+            description: row.try_get("description")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+
+    fn settings_from_row(row: &SqliteRow) -> Result<StoredSettings> {
+        let settings: String = row.try_get("settings")?;
+        Ok(StoredSettings {
+            user_id: row.try_get("user_id")?,
+            settings: serde_json::from_str(&settings)?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+
+    fn finding_from_row(row: &SqliteRow) -> Result<Finding> {
+        let severity: String = row.try_get("severity")?;
+        let state: String = row.try_get("state")?;
+        Ok(Finding {
+            id: row.try_get("id")?,
+            fingerprint: row.try_get("fingerprint")?,
+            finding_key: row.try_get("finding_key")?,
+            rule_id: row.try_get("rule_id")?,
+            severity: parse_severity(&severity),
+            title: row.try_get("title")?,
+            query: row.try_get("query")?,
+            state: FindingState::parse(&state).ok_or_else(|| {
+                StorageError::Backend(format!("Unknown finding state: {}", state))
+            })?,
+            first_seen: row.try_get("first_seen")?,
+            last_seen: row.try_get("last_seen")?,
+            acknowledged_at: row.try_get("acknowledged_at")?,
+            fixed_at: row.try_get("fixed_at")?,
+            due_at: row.try_get("due_at")?,
+            history_id: row.try_get("history_id")?,
+        })
+    }
+
+    fn plan_pin_from_row(row: &SqliteRow) -> Result<PlanPin> {
+        let plan: String = row.try_get("plan")?;
+        Ok(PlanPin {
+            saved_query_id: row.try_get("saved_query_id")?,
+            query_fingerprint: row.try_get("query_fingerprint")?,
+            history_id: row.try_get("history_id")?,
+            shape_fingerprint: row.try_get("shape_fingerprint")?,
+            plan: serde_json::from_str(&plan)?,
+            pinned_at: row.try_get("pinned_at")?,
+        })
+    }
+
+    fn benchmark_from_row(row: &SqliteRow) -> Result<StoredBenchmark> {
+        let kind: String = row.try_get("kind")?;
+        let result: String = row.try_get("result")?;
+        Ok(StoredBenchmark {
+            id: row.try_get("id")?,
+            kind: BenchmarkKind::parse(&kind).ok_or_else(|| {
+                StorageError::Backend(format!("Unknown benchmark kind: {}", kind))
+            })?,
+            label: row.try_get("label")?,
+            result: serde_json::from_str(&result)?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    fn backend(&self) -> StorageBackend {
+        StorageBackend::Sqlite
+    }
+
+    async fn migrate(&self) -> Result<()> {
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&self.pool).await?;
+        }
+        // SQLite has no ADD COLUMN IF NOT EXISTS
+        for (column, column_type) in ADDED_HISTORY_COLUMNS {
+            let exists: i64 = sqlx::query(
+                "SELECT COUNT(*) AS count FROM pragma_table_info('sqltrace_history') WHERE name = $1",
+            )
+            .bind(column)
+            .fetch_one(&self.pool)
+            .await?
+            .try_get("count")?;
+            if exists == 0 {
+                sqlx::query(&format!(
+                    "ALTER TABLE sqltrace_history ADD COLUMN {} {}",
+                    column, column_type
+                ))
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+        for statement in ADDED_HISTORY_INDEXES {
+            sqlx::query(statement).execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn record_history(&self, entry: NewHistoryEntry) -> Result<HistoryEntry> {
+        self.insert_history(entry, now_millis()).await
+    }
+
+    async fn restore_history(&self, entry: HistoryEntry) -> Result<HistoryEntry> {
+        let created_at = entry.created_at;
+        self.insert_history(entry.into(), created_at).await
+    }
+
+    async fn list_history(&self, limit: i64, offset: i64) -> Result<Vec<HistoryEntry>> {
+        sqlx::query(&format!(
+            "{} ORDER BY h.created_at DESC, h.id DESC LIMIT $1 OFFSET $2",
+            SELECT_HISTORY
+        ))
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(Self::history_from_row)
+        .collect()
+    }
+
+    async fn find_history_by_correlation(&self, id: &str, limit: i64) -> Result<Vec<HistoryEntry>> {
+        sqlx::query(&format!(
+            "{} WHERE h.trace_id = $1 OR h.correlation_id = $1 \
+             ORDER BY h.created_at DESC, h.id DESC LIMIT $2",
+            SELECT_HISTORY
+        ))
+        .bind(id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(Self::history_from_row)
+        .collect()
+    }
+
+    async fn cost_time_points(
+        &self,
+        connection: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<CostTimePoint>> {
+        sqlx::query(
+            "SELECT id, connection, total_cost, execution_time_ms, created_at \
+             FROM sqltrace_history WHERE execution_time_ms > 0 AND ($1 IS NULL OR connection = $1) \
+             ORDER BY created_at DESC, id DESC LIMIT $2",
+        )
+        .bind(connection)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| {
+            Ok(CostTimePoint {
+                history_id: row.try_get("id")?,
+                connection: row.try_get("connection")?,
+                total_cost: row.try_get("total_cost")?,
+                execution_time_ms: row.try_get("execution_time_ms")?,
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .collect()
+    }
+
+    async fn score_points(&self, connection: &str, since: i64) -> Result<Vec<ScorePoint>> {
+        sqlx::query(
+            "SELECT id, query, performance_score, created_at FROM sqltrace_history \
+             WHERE connection = $1 AND created_at >= $2 ORDER BY created_at, id",
+        )
+        .bind(connection)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| {
+            let performance_score: Option<i64> = row.try_get("performance_score")?;
+            Ok(ScorePoint {
+                history_id: row.try_get("id")?,
+                query: row.try_get("query")?,
+                performance_score: performance_score.map(|s| s.clamp(0, 100) as u8),
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .collect()
+    }
+
+    async fn get_history(&self, id: i64) -> Result<Option<HistoryEntry>> {
+        sqlx::query(&format!("{} WHERE h.id = $1", SELECT_HISTORY))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::history_from_row)
+            .transpose()
+    }
+
+    async fn update_history_analysis(
+        &self,
+        id: i64,
+        analysis: &serde_json::Value,
+        performance_score: u8,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE sqltrace_history SET analysis = $1, performance_score = $2 WHERE id = $3",
+        )
+        .bind(serde_json::to_string(analysis)?)
+        .bind(i64::from(performance_score))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_history(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM sqltrace_history WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        self.delete_unused_plans().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn prune_history(&self, policy: &RetentionPolicy, now: i64) -> Result<PruneReport> {
+        let mut report = PruneReport::default();
+
+        if let Some(cutoff) = policy.cutoff_millis(now) {
+            report.deleted_by_age =
+                sqlx::query("DELETE FROM sqltrace_history WHERE created_at < $1")
+                    .bind(cutoff)
+                    .execute(&self.pool)
+                    .await?
+                    .rows_affected();
+        }
+
+        if let Some(max_count) = policy.max_count {
+            report.deleted_by_count = sqlx::query(
+                "DELETE FROM sqltrace_history WHERE id IN (\
+                 SELECT id FROM sqltrace_history ORDER BY created_at DESC, id DESC \
+                 LIMIT -1 OFFSET $1)",
+            )
+            .bind(max_count as i64)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        }
+
+        if let Some(max_bytes) = policy.max_size_bytes() {
+            let statement = format!(
+                "DELETE FROM sqltrace_history WHERE id IN (\
+                 SELECT id FROM (SELECT id, SUM({} + coalesce((SELECT {} FROM sqltrace_plans \
+                 WHERE hash = sqltrace_history.plan_hash), 0)) OVER (ORDER BY created_at DESC, id DESC) \
+                 AS running FROM sqltrace_history) WHERE running > $1)",
+                ENTRY_SIZE, PLAN_SIZE
+            );
+            report.deleted_by_size = sqlx::query(&statement)
+                .bind(max_bytes)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+        }
+
+        if report.total() > 0 {
+            self.delete_unused_plans().await?;
+        }
+        Ok(report)
+    }
+
+    async fn purge_history(&self) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM sqltrace_history")
+            .execute(&self.pool)
+            .await?;
+        self.delete_unused_plans().await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn history_stats(&self) -> Result<HistoryStats> {
+        let statement = format!(
+            "SELECT (SELECT COUNT(*) FROM sqltrace_history) AS count, \
+             (SELECT COUNT(*) FROM sqltrace_plans) AS plans, \
+             (SELECT COALESCE(SUM({}), 0) FROM sqltrace_history) \
+             + (SELECT COALESCE(SUM({}), 0) FROM sqltrace_plans) AS size_bytes",
+            ENTRY_SIZE, PLAN_SIZE
+        );
+        let row = sqlx::query(&statement).fetch_one(&self.pool).await?;
+        Ok(HistoryStats {
+            count: row.try_get("count")?,
+            plans: row.try_get("plans")?,
+            size_bytes: row.try_get("size_bytes")?,
+        })
+    }
+
+    async fn save_query(&self, query: NewSavedQuery) -> Result<SavedQuery> {
+        self.insert_saved_query(query, now_millis()).await
+    }
+
+    async fn restore_saved_query(&self, query: SavedQuery) -> Result<SavedQuery> {
+        let created_at = query.created_at;
+        self.insert_saved_query(query.into(), created_at).await
+    }
+
+    async fn list_saved_queries(&self) -> Result<Vec<SavedQuery>> {
+        sqlx::query("SELECT * FROM sqltrace_saved_queries ORDER BY name, id")
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(Self::saved_query_from_row)
+            .collect()
+    }
+
+    async fn get_saved_query(&self, id: i64) -> Result<Option<SavedQuery>> {
+        sqlx::query("SELECT * FROM sqltrace_saved_queries WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::saved_query_from_row)
+            .transpose()
+    }
+
+    async fn delete_saved_query(&self, id: i64) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM sqltrace_plan_pins WHERE saved_query_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM sqltrace_saved_queries WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn put_plan_pin(&self, pin: &PlanPin) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO sqltrace_plan_pins \
+             (saved_query_id, query_fingerprint, history_id, shape_fingerprint, plan, pinned_at) \
+             VALUES ($1, $2, $3, $4, $5, $6) \
+             ON CONFLICT (saved_query_id) DO UPDATE SET \
+             query_fingerprint = excluded.query_fingerprint, history_id = excluded.history_id, \
+             shape_fingerprint = excluded.shape_fingerprint, plan = excluded.plan, \
+             pinned_at = excluded.pinned_at",
+        )
+        .bind(pin.saved_query_id)
+        .bind(&pin.query_fingerprint)
+        .bind(pin.history_id)
+        .bind(&pin.shape_fingerprint)
+        .bind(serde_json::to_string(&pin.plan)?)
+        .bind(pin.pinned_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_plan_pin(&self, saved_query_id: i64) -> Result<Option<PlanPin>> {
+        sqlx::query("SELECT * FROM sqltrace_plan_pins WHERE saved_query_id = $1")
+            .bind(saved_query_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::plan_pin_from_row)
+            .transpose()
+    }
+
+    async fn find_plan_pin(&self, query_fingerprint: &str) -> Result<Option<PlanPin>> {
+        sqlx::query(
+            "SELECT * FROM sqltrace_plan_pins WHERE query_fingerprint = $1 \
+             ORDER BY pinned_at DESC, saved_query_id DESC LIMIT 1",
+        )
+        .bind(query_fingerprint)
+        .fetch_optional(&self.pool)
+        .await?
+        .as_ref()
+        .map(Self::plan_pin_from_row)
+        .transpose()
+    }
+
+    async fn delete_plan_pin(&self, saved_query_id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM sqltrace_plan_pins WHERE saved_query_id = $1")
+            .bind(saved_query_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_settings(&self, user_id: &str) -> Result<Option<StoredSettings>> {
+        sqlx::query("SELECT * FROM sqltrace_settings WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::settings_from_row)
+            .transpose()
+    }
+
+    async fn put_settings(
+        &self,
+        user_id: &str,
+        settings: &serde_json::Value,
+    ) -> Result<StoredSettings> {
+        let updated_at = now_millis();
+        sqlx::query(
+            "INSERT INTO sqltrace_settings (user_id, settings, updated_at) VALUES ($1, $2, $3) \
+             ON CONFLICT (user_id) DO UPDATE SET settings = excluded.settings, \
+             updated_at = excluded.updated_at",
+        )
+        .bind(user_id)
+        .bind(serde_json::to_string(settings)?)
+        .bind(updated_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(StoredSettings {
+            user_id: user_id.to_string(),
+            settings: settings.clone(),
+            updated_at,
+        })
+    }
+
+    async fn delete_settings(&self, user_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM sqltrace_settings WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_settings(&self) -> Result<Vec<StoredSettings>> {
+        sqlx::query("SELECT * FROM sqltrace_settings ORDER BY user_id")
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(Self::settings_from_row)
+            .collect()
+    }
+
+    async fn get_advisor_rules(&self) -> Result<Option<serde_json::Value>> {
+        let overrides: Option<String> =
+            sqlx::query_scalar("SELECT overrides FROM sqltrace_advisor_rules WHERE id = 1")
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(overrides
+            .map(|overrides| serde_json::from_str(&overrides))
+            .transpose()?)
+    }
+
+    async fn put_advisor_rules(&self, rules: &serde_json::Value) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO sqltrace_advisor_rules (id, overrides, updated_at) VALUES (1, $1, $2) \
+             ON CONFLICT (id) DO UPDATE SET overrides = excluded.overrides, \
+             updated_at = excluded.updated_at",
+        )
+        .bind(serde_json::to_string(rules)?)
+        .bind(now_millis())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn record_finding(&self, finding: NewFinding) -> Result<Finding> {
+        let row = sqlx::query(
+            "INSERT INTO sqltrace_findings \
+             (fingerprint, finding_key, rule_id, severity, title, query, state, \
+              first_seen, last_seen, due_at, history_id) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8, $9, $10) RETURNING *",
+        )
+        .bind(&finding.fingerprint)
+        .bind(&finding.finding_key)
+        .bind(&finding.rule_id)
+        .bind(severity_name(&finding.severity))
+        .bind(&finding.title)
+        .bind(&finding.query)
+        .bind(FindingState::New.as_str())
+        .bind(finding.first_seen)
+        .bind(finding.due_at)
+        .bind(finding.history_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Self::finding_from_row(&row)
+    }
+
+    async fn list_findings(&self, filter: &FindingFilter) -> Result<Vec<Finding>> {
+        sqlx::query(
+            "SELECT * FROM sqltrace_findings \
+             WHERE ($1 IS NULL OR fingerprint = $1) AND ($2 IS NULL OR state = $2) \
+             ORDER BY last_seen DESC, id DESC",
+        )
+        .bind(filter.fingerprint.as_deref())
+        .bind(filter.state.map(|state| state.as_str()))
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(Self::finding_from_row)
+        .collect()
+    }
+
+    async fn get_finding(&self, id: i64) -> Result<Option<Finding>> {
+        sqlx::query("SELECT * FROM sqltrace_findings WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::finding_from_row)
+            .transpose()
+    }
+
+    async fn update_finding(&self, finding: &Finding) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE sqltrace_findings SET severity = $1, title = $2, query = $3, state = $4, \
+             last_seen = $5, acknowledged_at = $6, fixed_at = $7, due_at = $8, history_id = $9 \
+             WHERE id = $10",
+        )
+        .bind(severity_name(&finding.severity))
+        .bind(&finding.title)
+        .bind(&finding.query)
+        .bind(finding.state.as_str())
+        .bind(finding.last_seen)
+        .bind(finding.acknowledged_at)
+        .bind(finding.fixed_at)
+        .bind(finding.due_at)
+        .bind(finding.history_id)
+        .bind(finding.id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn record_benchmark(&self, benchmark: NewBenchmark) -> Result<StoredBenchmark> {
+        let row = sqlx::query(
+            "INSERT INTO sqltrace_benchmarks (kind, label, result, created_at) \
+             VALUES ($1, $2, $3, $4) RETURNING *",
+        )
+        .bind(benchmark.kind.as_str())
+        .bind(&benchmark.label)
+        .bind(serde_json::to_string(&benchmark.result)?)
+        .bind(now_millis())
+        .fetch_one(&self.pool)
+        .await?;
+        Self::benchmark_from_row(&row)
+    }
+
+    async fn get_benchmark(&self, id: i64) -> Result<Option<StoredBenchmark>> {
+        sqlx::query("SELECT * FROM sqltrace_benchmarks WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::benchmark_from_row)
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn memory_storage() -> SqliteStorage {
+        let storage = SqliteStorage::connect(&StorageConfig {
+            url: "sqlite::memory:".to_string(),
+            ..StorageConfig::default()
+        })
+        .await
+        .unwrap();
+        storage.migrate().await.unwrap();
+        storage
+    }
+
+    fn entry(query: &str) -> NewHistoryEntry {
+        NewHistoryEntry {
+            query: query.to_string(),
+            plan: json!({"root": {"Node Type": "Seq Scan"}}),
+            analysis: None,
+            performance_score: Some(80),
+            total_cost: 12.5,
+            execution_time_ms: 0.4,
+            trace_id: None,
+            correlation_id: None,
+            table_definitions: None,
+            connection: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history_round_trip() {
+        let storage = memory_storage().await;
+
+        let first = storage.record_history(entry("SELECT 1")).await.unwrap();
+        let second = storage.record_history(entry("SELECT 2")).await.unwrap();
+
+        let listed = storage.list_history(10, 0).await.unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].id, second.id);
+
+        let fetched = storage.get_history(first.id).await.unwrap().unwrap();
+        assert_eq!(fetched.query, "SELECT 1");
+        assert_eq!(fetched.plan["root"]["Node Type"], "Seq Scan");
+        assert_eq!(fetched.performance_score, Some(80));
+
+        assert!(storage.delete_history(first.id).await.unwrap());
+        assert!(!storage.delete_history(first.id).await.unwrap());
+        assert!(storage.get_history(first.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_packed_plans_read_back_alongside_json() {
+        let mut storage = memory_storage().await;
+        let json_entry = storage.record_history(entry("SELECT 1")).await.unwrap();
+
+        storage.plan_encoding = PlanEncoding::Msgpack;
+        let mut big = entry("SELECT 2");
+        big.plan = json!({
+            "root": {
+                "Node Type": "Append",
+                "Plans": (0..200)
+                    .map(|i| json!({"Node Type": "Seq Scan", "Relation Name": format!("part_{}", i), "Total Cost": 431.5}))
+                    .collect::<Vec<_>>()
+            }
+        });
+        let packed_entry = storage.record_history(big.clone()).await.unwrap();
+
+        let (plan, packed): (String, Vec<u8>) =
+            sqlx::query_as("SELECT plan, plan_packed FROM sqltrace_plans WHERE hash = $1")
+                .bind(plan_hash(&big.plan).unwrap())
+                .fetch_one(&storage.pool)
+                .await
+                .unwrap();
+        assert_eq!(plan, "null");
+        assert!(packed.len() < serde_json::to_vec(&big.plan).unwrap().len());
+
+        let fetched = storage.get_history(packed_entry.id).await.unwrap().unwrap();
+        assert_eq!(fetched.plan, big.plan);
+        let fetched = storage.get_history(json_entry.id).await.unwrap().unwrap();
+        assert_eq!(fetched.plan["root"]["Node Type"], "Seq Scan");
+    }
+
+    #[tokio::test]
+    async fn test_repeated_plans_are_stored_once() {
+        let storage = memory_storage().await;
+        // An entry written before plans were stored by hash
+        sqlx::query(
+            "INSERT INTO sqltrace_history \
+             (query, plan, performance_score, total_cost, execution_time_ms, created_at) \
+             VALUES ('SELECT 0', '{\"root\": {\"Node Type\": \"Result\"}}', 90, 0.01, 0.1, 1)",
+        )
+        .execute(&storage.pool)
+        .await
+        .unwrap();
+
+        let first = storage.record_history(entry("SELECT 1")).await.unwrap();
+        let second = storage.record_history(entry("SELECT 1")).await.unwrap();
+        let mut other = entry("SELECT 2");
+        other.plan = json!({"root": {"Node Type": "Index Scan"}});
+        storage.record_history(other).await.unwrap();
+
+        let stats = storage.history_stats().await.unwrap();
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.plans, 2);
+
+        let listed = storage.list_history(10, 0).await.unwrap();
+        assert_eq!(listed[1].plan, listed[2].plan);
+        assert_eq!(listed[3].plan["root"]["Node Type"], "Result");
+
+        // The plan is kept while an entry still references it
+        storage.delete_history(first.id).await.unwrap();
+        assert_eq!(storage.history_stats().await.unwrap().plans, 2);
+        let fetched = storage.get_history(second.id).await.unwrap().unwrap();
+        assert_eq!(fetched.plan["root"]["Node Type"], "Seq Scan");
+
+        storage.delete_history(second.id).await.unwrap();
+        assert_eq!(storage.history_stats().await.unwrap().plans, 1);
+        storage.purge_history().await.unwrap();
+        assert_eq!(storage.history_stats().await.unwrap().plans, 0);
+    }
+
+    #[tokio::test]
+    async fn test_find_history_by_correlation() {
+        let storage = SqliteStorage::connect(&StorageConfig {
+            url: "sqlite::memory:".to_string(),
+            ..StorageConfig::default()
+        })
+        .await
+        .unwrap();
+        // A table created before correlation ids were stored gains the columns
+        sqlx::query(
+            "CREATE TABLE sqltrace_history (id INTEGER PRIMARY KEY AUTOINCREMENT, \
+             query TEXT NOT NULL, plan TEXT NOT NULL, analysis TEXT, performance_score INTEGER, \
+             total_cost REAL NOT NULL, execution_time_ms REAL NOT NULL, created_at INTEGER NOT NULL)",
+        )
+        .execute(&storage.pool)
+        .await
+        .unwrap();
+        storage.migrate().await.unwrap();
+
+        storage
+            .record_history(NewHistoryEntry {
+                trace_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()),
+                ..entry("SELECT 1")
+            })
+            .await
+            .unwrap();
+        let tagged = storage
+            .record_history(NewHistoryEntry {
+                correlation_id: Some("incident-42".to_string()),
+                ..entry("SELECT 2")
+            })
+            .await
+            .unwrap();
+        storage.record_history(entry("SELECT 3")).await.unwrap();
+
+        let found = storage
+            .find_history_by_correlation("incident-42", 10)
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, tagged.id);
+        assert_eq!(found[0].correlation_id.as_deref(), Some("incident-42"));
+
+        let found = storage
+            .find_history_by_correlation("4bf92f3577b34da6a3ce929d0e0e4736", 10)
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].query, "SELECT 1");
+    }
+
+    #[tokio::test]
+    async fn test_prune_history() {
+        let storage = memory_storage().await;
+        for i in 0..5 {
+            storage
+                .record_history(entry(&format!("SELECT {}", i)))
+                .await
+                .unwrap();
+        }
+
+        let keep_three = RetentionPolicy {
+            max_count: Some(3),
+            ..RetentionPolicy::default()
+        };
+        let report = storage
+            .prune_history(&keep_three, now_millis())
+            .await
+            .unwrap();
+        assert_eq!(report.deleted_by_count, 2);
+        let remaining = storage.list_history(10, 0).await.unwrap();
+        assert_eq!(remaining.len(), 3);
+        assert_eq!(remaining[2].query, "SELECT 2");
+
+        // Everything was created before a cutoff one day in the future
+        let expire_all = RetentionPolicy {
+            max_age_days: Some(1),
+            ..RetentionPolicy::default()
+        };
+        let report = storage
+            .prune_history(&expire_all, now_millis() + 2 * 86_400_000)
+            .await
+            .unwrap();
+        assert_eq!(report.deleted_by_age, 3);
+        assert_eq!(storage.history_stats().await.unwrap().count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_prune_history_by_size() {
+        let storage = memory_storage().await;
+        let mut big = entry("SELECT big");
+        big.plan = json!({ "padding": "x".repeat(700 * 1024) });
+        storage.record_history(big.clone()).await.unwrap();
+        // Identical plans would be stored once
+        big.plan = json!({ "padding": "y".repeat(700 * 1024) });
+        storage.record_history(big).await.unwrap();
+
+        let stats = storage.history_stats().await.unwrap();
+        assert_eq!(stats.count, 2);
+        assert!(stats.size_bytes > 1024 * 1024);
+
+        let one_mb = RetentionPolicy {
+            max_size_mb: Some(1),
+            ..RetentionPolicy::default()
+        };
+        let report = storage.prune_history(&one_mb, now_millis()).await.unwrap();
+        assert_eq!(report.deleted_by_size, 1);
+        assert_eq!(storage.history_stats().await.unwrap().count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_saved_queries() {
+        let storage = memory_storage().await;
+
+        let saved = storage
+            .save_query(NewSavedQuery {
+                name: "orders by user".to_string(),
+                query: "SELECT * FROM orders WHERE user_id = 1".to_string(),
+                description: None,
+            })
+            .await
+            .unwrap();
+
+        let listed = storage.list_saved_queries().await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "orders by user");
+
+        assert!(storage.delete_saved_query(saved.id).await.unwrap());
+        assert!(storage.list_saved_queries().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cost_time_points() {
+        let storage = memory_storage().await;
+        for (connection, time) in [(Some("primary"), 4.0), (Some("replica"), 2.0), (None, 1.0)] {
+            storage
+                .record_history(NewHistoryEntry {
+                    execution_time_ms: time,
+                    connection: connection.map(str::to_string),
+                    ..entry("SELECT 1")
+                })
+                .await
+                .unwrap();
+        }
+        // Plans that were not executed have no time to compare
+        storage
+            .record_history(NewHistoryEntry {
+                execution_time_ms: 0.0,
+                ..entry("SELECT 2")
+            })
+            .await
+            .unwrap();
+
+        let all = storage.cost_time_points(None, 10).await.unwrap();
+        assert_eq!(all.len(), 3);
+        let replica = storage.cost_time_points(Some("replica"), 10).await.unwrap();
+        assert_eq!(replica.len(), 1);
+        assert_eq!(replica[0].execution_time_ms, 2.0);
+        assert_eq!(replica[0].total_cost, 12.5);
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_round_trip() {
+        let storage = memory_storage().await;
+
+        let stored = storage
+            .record_benchmark(NewBenchmark {
+                kind: BenchmarkKind::Transaction,
+                label: "SELECT 1; SELECT 2".to_string(),
+                result: json!({"runs": [{"execution_time": {"secs": 0, "nanos": 1000}}]}),
+            })
+            .await
+            .unwrap();
+
+        let fetched = storage.get_benchmark(stored.id).await.unwrap().unwrap();
+        assert_eq!(fetched.kind, BenchmarkKind::Transaction);
+        assert_eq!(fetched.result["runs"][0]["execution_time"]["nanos"], 1000);
+        assert!(storage
+            .get_benchmark(stored.id + 1)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_settings_upsert() {
+        let storage = memory_storage().await;
+        assert!(storage.get_settings("alice").await.unwrap().is_none());
+
+        storage
+            .put_settings("alice", &json!({"hotspots": {"cost_high": 500}}))
+            .await
+            .unwrap();
+        storage
+            .put_settings("alice", &json!({"hotspots": {"cost_high": 800}}))
+            .await
+            .unwrap();
+
+        let stored = storage.get_settings("alice").await.unwrap().unwrap();
+        assert_eq!(stored.settings["hotspots"]["cost_high"], 800);
+        assert_eq!(storage.list_settings().await.unwrap().len(), 1);
+
+        assert!(storage.delete_settings("alice").await.unwrap());
+        assert!(storage.get_settings("alice").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_advisor_rules_upsert() {
+        let storage = memory_storage().await;
+        assert!(storage.get_advisor_rules().await.unwrap().is_none());
+
+        storage
+            .put_advisor_rules(&json!({"disabled_rules": ["nested_loop"]}))
+            .await
+            .unwrap();
+        storage
+            .put_advisor_rules(&json!({"thresholds": {"large_scan_threshold": 500}}))
+            .await
+            .unwrap();
+
+        let stored = storage.get_advisor_rules().await.unwrap().unwrap();
+        assert_eq!(stored, json!({"thresholds": {"large_scan_threshold": 500}}));
+    }
+
+    #[tokio::test]
+    async fn test_findings_lifecycle() {
+        use crate::advisor::{OptimizationSuggestion, Severity};
+        use crate::storage::findings::{self, Observation, SlaDays};
+
+        let storage = memory_storage().await;
+        let scan = OptimizationSuggestion {
+            rule_id: "expensive_seq_scan".to_string(),
+            suggestion_type: "Index".to_string(),
+            severity: Severity::High,
+            title: "Expensive Sequential Scan Detected".to_string(),
+            description: String::new(),
+            recommendation: String::new(),
+            node_index: Some(0),
+            impact: String::new(),
+        };
+        let observe = |now| Observation {
+            fingerprint: "f1",
+            query: "SELECT * FROM orders",
+            history_id: None,
+            now,
+        };
+        let sla = SlaDays::default();
+
+        let tracked = findings::track(&storage, std::slice::from_ref(&scan), observe(1), &sla)
+            .await
+            .unwrap();
+        assert_eq!(tracked.len(), 1);
+        assert_eq!(tracked[0].state, FindingState::New);
+
+        let acked = findings::acknowledge(&storage, tracked[0].id, 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(acked.state, FindingState::Acknowledged);
+        assert!(findings::acknowledge(&storage, 999, 2)
+            .await
+            .unwrap()
+            .is_none());
+
+        let tracked = findings::track(&storage, &[], observe(3), &sla)
+            .await
+            .unwrap();
+        assert_eq!(tracked[0].state, FindingState::Fixed);
+
+        let tracked = findings::track(&storage, &[scan], observe(4), &sla)
+            .await
+            .unwrap();
+        assert_eq!(tracked.len(), 1);
+        assert_eq!(tracked[0].state, FindingState::Regressed);
+        assert_eq!(tracked[0].acknowledged_at, None);
+
+        let regressed = FindingFilter {
+            state: Some(FindingState::Regressed),
+            ..FindingFilter::default()
+        };
+        assert_eq!(storage.list_findings(&regressed).await.unwrap().len(), 1);
+    }
+}
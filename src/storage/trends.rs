@@ -0,0 +1,219 @@
+//! Day-by-day query quality of a connection
+//!
+//! A team watching a service wants to know whether its queries are getting
+//! better or worse, not just how the latest one did. The analyses stored for
+//! a connection are grouped by day (UTC): how many were run, the average
+//! performance score, the findings first reported on the connection's queries,
+//! and regressions, analyses that scored markedly worse than the previous
+//! analysis of the same query.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use super::findings::{fingerprint, FindingFilter};
+use super::{Finding, Result, ScorePoint, Storage};
+
+/// Milliseconds in a day
+const DAY_MILLIS: i64 = 86_400_000;
+
+/// Points a query's score has to drop from its previous analysis to count as a regression
+pub const REGRESSION_POINTS: u8 = 10;
+
+/// Analyses and findings of one day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DayTrend {
+    /// Day, in days since the Unix epoch (UTC)
+    pub day: i64,
+    /// Start of the day, in milliseconds since the Unix epoch
+    pub starts_at: i64,
+    /// Analyses stored on the day
+    pub analyses: usize,
+    /// Average performance score of the day's analyses, if any were scored
+    pub average_score: Option<f64>,
+    /// Findings first reported on the day for queries the connection ran
+    pub new_findings: usize,
+    /// Analyses of the day that regressed from the previous analysis of their query
+    pub regressions: usize,
+}
+
+/// Day-by-day analyses and findings of a connection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionTrend {
+    /// Connection name
+    pub connection: String,
+    /// Every day of the period, oldest first, including days without analyses
+    pub days: Vec<DayTrend>,
+    /// Analyses over the period
+    pub analyses: usize,
+    /// Average performance score over the period
+    pub average_score: Option<f64>,
+    /// Findings first reported over the period
+    pub new_findings: usize,
+    /// Regressions over the period
+    pub regressions: usize,
+}
+
+/// Group the analyses of `connection` and the findings of their queries by day
+///
+/// `points` are the connection's analyses from the start of `first_day`,
+/// oldest first. A query's first analysis in the period has nothing to be
+/// compared with, so it never counts as a regression.
+pub fn summarize(
+    connection: &str,
+    points: &[ScorePoint],
+    findings: &[Finding],
+    first_day: i64,
+    days: usize,
+) -> ConnectionTrend {
+    let mut trend: Vec<DayTrend> = (0..days as i64)
+        .map(|offset| DayTrend {
+            day: first_day + offset,
+            starts_at: (first_day + offset) * DAY_MILLIS,
+            analyses: 0,
+            average_score: None,
+            new_findings: 0,
+            regressions: 0,
+        })
+        .collect();
+    let slot = |at: i64| {
+        usize::try_from(at.div_euclid(DAY_MILLIS) - first_day)
+            .ok()
+            .filter(|&index| index < days)
+    };
+
+    let mut score_sums = vec![(0u64, 0u64); days];
+    let mut previous: HashMap<String, u8> = HashMap::new();
+    let mut fingerprints = HashSet::new();
+    for point in points {
+        let query_fingerprint = fingerprint(&point.query);
+        fingerprints.insert(query_fingerprint.clone());
+        let Some(index) = slot(point.created_at) else {
+            continue;
+        };
+        trend[index].analyses += 1;
+        let Some(score) = point.performance_score else {
+            continue;
+        };
+        score_sums[index].0 += u64::from(score);
+        score_sums[index].1 += 1;
+        if let Some(before) = previous.insert(query_fingerprint, score) {
+            if before.saturating_sub(score) >= REGRESSION_POINTS {
+                trend[index].regressions += 1;
+            }
+        }
+    }
+    for (day, (sum, count)) in trend.iter_mut().zip(&score_sums) {
+        day.average_score = (*count > 0).then(|| *sum as f64 / *count as f64);
+    }
+
+    for finding in findings {
+        if fingerprints.contains(&finding.fingerprint) {
+            if let Some(index) = slot(finding.first_seen) {
+                trend[index].new_findings += 1;
+            }
+        }
+    }
+
+    let (sum, count) = score_sums
+        .iter()
+        .fold((0, 0), |(sum, count), (s, c)| (sum + s, count + c));
+    ConnectionTrend {
+        connection: connection.to_string(),
+        analyses: trend.iter().map(|day| day.analyses).sum(),
+        average_score: (count > 0).then(|| sum as f64 / count as f64),
+        new_findings: trend.iter().map(|day| day.new_findings).sum(),
+        regressions: trend.iter().map(|day| day.regressions).sum(),
+        days: trend,
+    }
+}
+
+/// The last `days` days of `connection`, up to and including the day of `now`
+pub async fn connection_trend(
+    storage: &dyn Storage,
+    connection: &str,
+    days: usize,
+    now: i64,
+) -> Result<ConnectionTrend> {
+    let days = days.max(1);
+    let first_day = now.div_euclid(DAY_MILLIS) - (days as i64 - 1);
+    let points = storage
+        .score_points(connection, first_day * DAY_MILLIS)
+        .await?;
+    let findings = storage.list_findings(&FindingFilter::default()).await?;
+    Ok(summarize(connection, &points, &findings, first_day, days))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advisor::Severity;
+    use crate::storage::FindingState;
+
+    fn point(id: i64, query: &str, score: Option<u8>, day: i64) -> ScorePoint {
+        ScorePoint {
+            history_id: id,
+            query: query.to_string(),
+            performance_score: score,
+            created_at: day * DAY_MILLIS + id,
+        }
+    }
+
+    fn finding(query: &str, day: i64) -> Finding {
+        Finding {
+            id: day,
+            fingerprint: fingerprint(query),
+            finding_key: "expensive_seq_scan:Expensive Sequential Scan Detected".to_string(),
+            rule_id: "expensive_seq_scan".to_string(),
+            severity: Severity::High,
+            title: "Expensive Sequential Scan Detected".to_string(),
+            query: query.to_string(),
+            state: FindingState::New,
+            first_seen: day * DAY_MILLIS + 1,
+            last_seen: day * DAY_MILLIS + 1,
+            acknowledged_at: None,
+            fixed_at: None,
+            due_at: None,
+            history_id: None,
+        }
+    }
+
+    #[test]
+    fn test_summarize_by_day() {
+        let points = [
+            point(1, "SELECT * FROM orders WHERE id = 1", Some(90), 100),
+            point(2, "SELECT * FROM users", Some(70), 100),
+            point(3, "SELECT * FROM orders WHERE id = 2", Some(60), 102),
+            point(4, "SELECT * FROM users", Some(65), 102),
+            point(5, "SELECT 1", None, 102),
+        ];
+        let findings = [
+            finding("SELECT * FROM orders WHERE id = 3", 102),
+            // Another connection's query, and a finding from before the period
+            finding("SELECT * FROM invoices", 102),
+            finding("SELECT * FROM users", 90),
+        ];
+
+        let trend = summarize("primary", &points, &findings, 100, 3);
+        assert_eq!(trend.days.len(), 3);
+        assert_eq!(trend.days[0].starts_at, 100 * DAY_MILLIS);
+        assert_eq!(trend.days[0].analyses, 2);
+        assert_eq!(trend.days[0].average_score, Some(80.0));
+        assert_eq!(trend.days[0].regressions, 0);
+
+        // A day without analyses is still listed
+        assert_eq!(trend.days[1].analyses, 0);
+        assert_eq!(trend.days[1].average_score, None);
+
+        // Only the orders query dropped by at least REGRESSION_POINTS
+        let last = &trend.days[2];
+        assert_eq!(last.analyses, 3);
+        assert_eq!(last.average_score, Some(62.5));
+        assert_eq!(last.regressions, 1);
+        assert_eq!(last.new_findings, 1);
+
+        assert_eq!(trend.analyses, 5);
+        assert_eq!(trend.average_score, Some(71.25));
+        assert_eq!(trend.regressions, 1);
+        assert_eq!(trend.new_findings, 1);
+    }
+}
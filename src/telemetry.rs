@@ -0,0 +1,390 @@
+//! Opt-in telemetry of advisor rule hits
+//!
+//! Off unless `[telemetry]` sets `enabled = true` and an `endpoint`. When on,
+//! the server counts how often each advisor rule fires and which engine
+//! versions analyses ran on, and every `interval_secs` POSTs those counts to
+//! the endpoint as JSON. That is all that is sent: no SQL, no plans, no
+//! relation, column or connection names, and nothing identifying the
+//! installation. `GET /api/telemetry` shows the report that would be sent
+//! next, and the server logs where reports go when it starts.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::advisor::OptimizationSuggestion;
+use crate::db::engines::version::ServerVersion;
+use crate::db::engines::EngineType;
+use crate::storage::now_millis;
+
+/// Time a report may take to be delivered
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether and where rule hit counts are reported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// Report rule hit counts; off by default
+    pub enabled: bool,
+    /// `http://` URL the reports are POSTed to
+    pub endpoint: Option<String>,
+    /// Time between reports, in seconds
+    pub interval_secs: u64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            interval_secs: 86_400,
+        }
+    }
+}
+
+impl TelemetryConfig {
+    /// The endpoint reports are sent to, if telemetry is enabled and the endpoint is usable
+    pub fn target(&self) -> Result<Option<Endpoint>, String> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        let endpoint = self
+            .endpoint
+            .as_deref()
+            .ok_or("Telemetry is enabled but has no endpoint")?;
+        Endpoint::parse(endpoint).map(Some)
+    }
+}
+
+/// Where reports are POSTed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    /// Parse an `http://host[:port][/path]` URL
+    ///
+    /// Reports carry no sensitive data, so plain HTTP is all that is spoken;
+    /// an HTTPS collector can be reached through a forwarding proxy.
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("Telemetry endpoint '{}' must be an http:// URL", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| format!("Invalid port in telemetry endpoint '{}'", url))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() || host.contains('@') {
+            return Err(format!("Invalid host in telemetry endpoint '{}'", url));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "http://{}:{}{}", self.host, self.port, self.path)
+    }
+}
+
+/// Aggregate counts sent in one report
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TelemetryReport {
+    /// Version of SQLTrace sending the report
+    pub sqltrace_version: String,
+    /// Start of the period counted, in milliseconds since the epoch
+    pub period_start: i64,
+    /// End of the period counted, in milliseconds since the epoch
+    pub period_end: i64,
+    /// Plans analyzed by the advisor
+    pub analyses: u64,
+    /// Suggestions made, by rule id
+    pub rule_hits: BTreeMap<String, u64>,
+    /// Analyses, by engine and major server version, such as `PostgreSQL 16`
+    pub engines: BTreeMap<String, u64>,
+}
+
+impl TelemetryReport {
+    fn is_empty(&self) -> bool {
+        self.analyses == 0
+    }
+
+    /// Add the counts of `other`, an earlier report that could not be sent
+    fn merge(&mut self, other: TelemetryReport) {
+        self.period_start = self.period_start.min(other.period_start);
+        self.analyses += other.analyses;
+        for (rule, hits) in other.rule_hits {
+            *self.rule_hits.entry(rule).or_default() += hits;
+        }
+        for (engine, analyses) in other.engines {
+            *self.engines.entry(engine).or_default() += analyses;
+        }
+    }
+}
+
+/// Engine and server version as reported: the major version, plus the minor
+/// version where that is how the engine numbers its releases
+pub fn engine_label(engine: EngineType, version: Option<ServerVersion>) -> String {
+    match version {
+        Some(version) if engine == EngineType::PostgreSQL && version.major >= 10 => {
+            format!("{} {}", engine, version.major)
+        }
+        Some(version) => format!("{} {}.{}", engine, version.major, version.minor),
+        None => engine.to_string(),
+    }
+}
+
+/// Counts of rule hits since the last report
+#[derive(Debug)]
+pub struct Telemetry {
+    config: TelemetryConfig,
+    pending: Mutex<TelemetryReport>,
+}
+
+impl Telemetry {
+    /// Create a counter for `config`; nothing is counted unless it is enabled
+    pub fn new(config: TelemetryConfig) -> Self {
+        Self {
+            config,
+            pending: Mutex::new(Self::empty_report(now_millis())),
+        }
+    }
+
+    /// Whether rule hits are counted
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// The configuration counts are reported with
+    pub fn config(&self) -> &TelemetryConfig {
+        &self.config
+    }
+
+    /// Count the suggestions of one analysis on `engine`, as labelled by [`engine_label`]
+    pub fn record(&self, engine: &str, suggestions: &[OptimizationSuggestion]) {
+        if !self.config.enabled {
+            return;
+        }
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.analyses += 1;
+        *pending.engines.entry(engine.to_string()).or_default() += 1;
+        for suggestion in suggestions {
+            if !suggestion.rule_id.is_empty() {
+                *pending
+                    .rule_hits
+                    .entry(suggestion.rule_id.clone())
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    /// The report that would be sent now
+    pub fn pending(&self) -> TelemetryReport {
+        let mut report = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        report.period_end = now_millis();
+        report
+    }
+
+    /// Take the counts so far, starting a new period
+    fn take(&self, now: i64) -> TelemetryReport {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let mut report = std::mem::replace(&mut *pending, Self::empty_report(now));
+        report.period_end = now;
+        report
+    }
+
+    /// Put back the counts of a report that could not be sent
+    fn restore(&self, report: TelemetryReport) {
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .merge(report);
+    }
+
+    fn empty_report(now: i64) -> TelemetryReport {
+        TelemetryReport {
+            sqltrace_version: env!("CARGO_PKG_VERSION").to_string(),
+            period_start: now,
+            ..TelemetryReport::default()
+        }
+    }
+}
+
+/// Send the counts of `telemetry` to its endpoint every `interval_secs`, at least a minute apart
+///
+/// Returns `None` without spawning anything when telemetry is disabled or
+/// has no usable endpoint; check [`TelemetryConfig::target`] at startup to
+/// report why. A report that can't be delivered is logged and its counts
+/// carried over to the next one.
+pub fn spawn_reporter(telemetry: Arc<Telemetry>) -> Option<tokio::task::JoinHandle<()>> {
+    let endpoint = telemetry.config.target().ok().flatten()?;
+    let interval = Duration::from_secs(telemetry.config.interval_secs.max(60));
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately, before anything was counted
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let report = telemetry.take(now_millis());
+            if report.is_empty() {
+                continue;
+            }
+            match tokio::time::timeout(SEND_TIMEOUT, send(&endpoint, &report)).await {
+                Ok(Ok(())) => tracing::debug!("Sent telemetry report to {}", endpoint),
+                Ok(Err(e)) => {
+                    tracing::debug!("Failed to send telemetry report to {}: {}", endpoint, e);
+                    telemetry.restore(report);
+                }
+                Err(_) => {
+                    tracing::debug!("Telemetry endpoint {} did not answer", endpoint);
+                    telemetry.restore(report);
+                }
+            }
+        }
+    }))
+}
+
+/// POST `report` to `endpoint` as JSON over HTTP/1.1
+async fn send(endpoint: &Endpoint, report: &TelemetryReport) -> Result<(), String> {
+    let body = serde_json::to_string(report).map_err(|e| e.to_string())?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: sqltrace/{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        endpoint.path,
+        endpoint.host,
+        env!("CARGO_PKG_VERSION"),
+        body.len(),
+        body
+    );
+
+    let mut stream = tokio::net::TcpStream::connect((endpoint.host.as_str(), endpoint.port))
+        .await
+        .map_err(|e| e.to_string())?;
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut response = Vec::new();
+    stream
+        .take(1024)
+        .read_to_end(&mut response)
+        .await
+        .map_err(|e| e.to_string())?;
+    let status_line = String::from_utf8_lossy(&response);
+    let status_line = status_line.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        _ => Err(format!("Unexpected response '{}'", status_line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advisor::Severity;
+
+    fn analysis(rules: &[&str]) -> Vec<OptimizationSuggestion> {
+        rules
+            .iter()
+            .map(|rule| OptimizationSuggestion {
+                rule_id: rule.to_string(),
+                suggestion_type: "Index".to_string(),
+                severity: Severity::Medium,
+                title: String::new(),
+                description: "Seq Scan on orders".to_string(),
+                recommendation: String::new(),
+                node_index: Some(0),
+                impact: String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_counts_only_when_enabled() {
+        let disabled = Telemetry::new(TelemetryConfig::default());
+        disabled.record("PostgreSQL 16", &analysis(&["index_opportunity"]));
+        assert!(disabled.pending().is_empty());
+
+        let telemetry = Telemetry::new(TelemetryConfig {
+            enabled: true,
+            endpoint: Some("http://collector.example.com/v1/rules".to_string()),
+            ..TelemetryConfig::default()
+        });
+        telemetry.record(
+            "PostgreSQL 16",
+            &analysis(&["index_opportunity", "index_opportunity"]),
+        );
+        telemetry.record("MySQL 8.0", &analysis(&["expensive_seq_scan", ""]));
+
+        let report = telemetry.take(now_millis());
+        assert_eq!(report.analyses, 2);
+        assert_eq!(report.rule_hits.len(), 2);
+        assert_eq!(report.rule_hits["index_opportunity"], 2);
+        assert_eq!(report.engines["MySQL 8.0"], 1);
+        // Only rule ids and engine labels leave the server
+        let sent = serde_json::to_string(&report).unwrap();
+        assert!(!sent.contains("orders"));
+        assert!(telemetry.pending().is_empty());
+
+        telemetry.record("PostgreSQL 16", &analysis(&["index_opportunity"]));
+        telemetry.restore(report);
+        let merged = telemetry.pending();
+        assert_eq!(merged.analyses, 3);
+        assert_eq!(merged.rule_hits["index_opportunity"], 3);
+    }
+
+    #[test]
+    fn test_endpoints_and_labels() {
+        let endpoint = Endpoint::parse("http://collector.example.com:8080/v1/rules").unwrap();
+        assert_eq!(
+            endpoint.to_string(),
+            "http://collector.example.com:8080/v1/rules"
+        );
+        assert_eq!(
+            Endpoint::parse("http://collector.example.com")
+                .unwrap()
+                .to_string(),
+            "http://collector.example.com:80/"
+        );
+        assert!(Endpoint::parse("https://collector.example.com").is_err());
+        assert!(Endpoint::parse("http://user@collector.example.com").is_err());
+
+        let enabled = TelemetryConfig {
+            enabled: true,
+            ..TelemetryConfig::default()
+        };
+        assert!(enabled.target().is_err());
+        assert_eq!(TelemetryConfig::default().target(), Ok(None));
+
+        assert_eq!(
+            engine_label(EngineType::PostgreSQL, Some(ServerVersion::new(16, 2, 0))),
+            "PostgreSQL 16"
+        );
+        assert_eq!(
+            engine_label(EngineType::MySQL, Some(ServerVersion::new(8, 0, 35))),
+            "MySQL 8.0"
+        );
+        assert_eq!(engine_label(EngineType::SQLite, None), "SQLite");
+    }
+}
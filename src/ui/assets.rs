@@ -0,0 +1,113 @@
+//! Frontend assets served from a directory on disk
+//!
+//! The web UI is plain HTML, CSS and JavaScript read from `static_dir` on
+//! every request, so a customized frontend can be dropped in, or edited in
+//! place, without rebuilding or restarting the server. Requests are resolved
+//! to files strictly inside the directory: `..` segments, hidden files and
+//! symlinks pointing elsewhere are refused, and directories are never listed.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+/// Where the web UI is served from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrontendConfig {
+    /// Directory holding `index.html` and the assets under `/static`
+    pub static_dir: PathBuf,
+    /// Serve a directory's `index.html` for requests naming the directory;
+    /// otherwise such requests are not found
+    pub directory_index: bool,
+}
+
+impl Default for FrontendConfig {
+    fn default() -> Self {
+        Self {
+            static_dir: PathBuf::from("static"),
+            directory_index: true,
+        }
+    }
+}
+
+impl FrontendConfig {
+    /// The file `request_path` names under `static_dir`, if it may be served
+    ///
+    /// Returns `None` for paths that are not plain relative paths, name a
+    /// hidden file or directory, resolve outside `static_dir` through a
+    /// symlink, or don't exist.
+    pub async fn resolve(&self, request_path: &str) -> Option<PathBuf> {
+        let relative = Path::new(request_path);
+        let plain = relative.components().all(|component| match component {
+            Component::Normal(name) => !name.to_string_lossy().starts_with('.'),
+            _ => false,
+        });
+        if !plain || request_path.contains('\\') {
+            return None;
+        }
+
+        let root = tokio::fs::canonicalize(&self.static_dir).await.ok()?;
+        let mut file = tokio::fs::canonicalize(root.join(relative)).await.ok()?;
+        if !file.starts_with(&root) {
+            return None;
+        }
+        if tokio::fs::metadata(&file).await.ok()?.is_dir() {
+            if !self.directory_index {
+                return None;
+            }
+            file.push("index.html");
+        }
+        tokio::fs::metadata(&file)
+            .await
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|_| file)
+    }
+
+    /// Path of the page served at `/`
+    pub fn index_path(&self) -> PathBuf {
+        self.static_dir.join("index.html")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let base = std::env::temp_dir().join(format!("sqltrace-assets-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let static_dir = base.join("static");
+        std::fs::create_dir_all(static_dir.join("docs")).unwrap();
+        std::fs::write(static_dir.join("app.js"), "").unwrap();
+        std::fs::write(static_dir.join(".env"), "").unwrap();
+        std::fs::write(static_dir.join("docs/index.html"), "").unwrap();
+        std::fs::write(base.join("secret.txt"), "").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(base.join("secret.txt"), static_dir.join("leak.txt")).unwrap();
+
+        let mut frontend = FrontendConfig {
+            static_dir: static_dir.clone(),
+            directory_index: true,
+        };
+        let root = std::fs::canonicalize(&static_dir).unwrap();
+        assert_eq!(frontend.resolve("app.js").await, Some(root.join("app.js")));
+        assert_eq!(
+            frontend.resolve("docs").await,
+            Some(root.join("docs/index.html"))
+        );
+        assert_eq!(frontend.resolve("../secret.txt").await, None);
+        assert_eq!(frontend.resolve("docs/../../secret.txt").await, None);
+        assert_eq!(frontend.resolve("/etc/passwd").await, None);
+        assert_eq!(frontend.resolve(".env").await, None);
+        assert_eq!(frontend.resolve("missing.css").await, None);
+        #[cfg(unix)]
+        assert_eq!(frontend.resolve("leak.txt").await, None);
+
+        frontend.directory_index = false;
+        assert_eq!(frontend.resolve("docs").await, None);
+        assert!(frontend.resolve("docs/index.html").await.is_some());
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
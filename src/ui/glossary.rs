@@ -0,0 +1,365 @@
+//! Plan operator glossary
+//!
+//! Short explanations of the node types that appear in plans, so the UI can
+//! show a "what is this?" popover next to each node. Node types are the
+//! unified names every engine maps its plans onto, so one entry covers, say,
+//! a PostgreSQL `Seq Scan`, a MySQL `ALL` access and a SQLite `SCAN`.
+
+use serde::Serialize;
+
+const POSTGRES_EXPLAIN: &str = "https://www.postgresql.org/docs/current/using-explain.html";
+const POSTGRES_PLANNER: &str = "https://www.postgresql.org/docs/current/runtime-config-query.html";
+const MYSQL_EXPLAIN: &str = "https://dev.mysql.com/doc/refman/8.0/en/explain-output.html";
+const MARIADB_ANALYZE: &str = "https://mariadb.com/kb/en/analyze-format-json/";
+const SQLITE_EQP: &str = "https://www.sqlite.org/eqp.html";
+
+/// Explanation of one plan operator
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GlossaryEntry {
+    /// Node type as it appears in plans
+    pub node_type: &'static str,
+    /// URL-friendly identifier used by `/api/glossary/{node_type}`
+    pub slug: &'static str,
+    /// What the operator does
+    pub summary: &'static str,
+    /// When the planner chooses it
+    pub appears_when: &'static str,
+    /// Settings and schema changes that affect whether and how it is used
+    pub knobs: &'static [&'static str],
+    /// Further reading
+    pub docs_url: &'static str,
+}
+
+const ENTRIES: &[GlossaryEntry] = &[
+    GlossaryEntry {
+        node_type: "Seq Scan",
+        slug: "seq-scan",
+        summary: "Reads every row of the table in physical order and applies the filter to each.",
+        appears_when: "No index matches the condition, or the condition keeps a large share of the table so reading it all is cheaper than following an index.",
+        knobs: &["enable_seqscan", "seq_page_cost", "random_page_cost", "an index on the filtered columns"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Index Scan",
+        slug: "index-scan",
+        summary: "Walks an index for matching keys and fetches each matching row from the table.",
+        appears_when: "A selective condition or an ORDER BY matches the leading columns of an index.",
+        knobs: &["enable_indexscan", "random_page_cost", "effective_cache_size", "index column order"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Index Only Scan",
+        slug: "index-only-scan",
+        summary: "Answers the query from the index alone, without visiting the table for most rows.",
+        appears_when: "Every column the query needs is stored in the index; in PostgreSQL the visibility map must also show most pages as all-visible.",
+        knobs: &["enable_indexonlyscan", "INCLUDE columns on the index", "VACUUM frequency"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Full Index Scan",
+        slug: "full-index-scan",
+        summary: "Reads an entire index in key order instead of the table.",
+        appears_when: "The index covers the query or provides the requested order, but no condition narrows the range.",
+        knobs: &["a condition on the leading index column", "covering indexes"],
+        docs_url: MYSQL_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Index Merge",
+        slug: "index-merge",
+        summary: "Scans several indexes on one table and combines the matching row sets.",
+        appears_when: "Conditions on different columns joined by AND or OR each match a separate index.",
+        knobs: &["optimizer_switch index_merge flags", "a composite index on the combined columns"],
+        docs_url: MYSQL_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Bitmap Index Scan",
+        slug: "bitmap-index-scan",
+        summary: "Collects the locations of all matching rows from an index into an in-memory bitmap.",
+        appears_when: "A condition matches too many rows for a plain index scan but too few for a sequential scan, or several indexes are combined.",
+        knobs: &["enable_bitmapscan", "work_mem", "random_page_cost"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Bitmap Heap Scan",
+        slug: "bitmap-heap-scan",
+        summary: "Fetches the table pages marked in a bitmap in physical order and rechecks the condition on their rows.",
+        appears_when: "Above one or more Bitmap Index Scans; reading pages in order avoids the random I/O of a plain index scan.",
+        knobs: &["enable_bitmapscan", "work_mem (lossy bitmaps)", "effective_io_concurrency"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "BitmapAnd",
+        slug: "bitmapand",
+        summary: "Intersects bitmaps from several index scans.",
+        appears_when: "Conditions combined with AND each match a different index.",
+        knobs: &["enable_bitmapscan", "a composite index on the combined columns"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "BitmapOr",
+        slug: "bitmapor",
+        summary: "Unions bitmaps from several index scans.",
+        appears_when: "Conditions combined with OR each match a different index.",
+        knobs: &["enable_bitmapscan", "rewriting the OR as a UNION"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Nested Loop",
+        slug: "nested-loop",
+        summary: "For each row of the outer input, scans the inner input for matching rows.",
+        appears_when: "The outer input is small, or the inner side can use an index on the join key.",
+        knobs: &["enable_nestloop", "an index on the inner join key", "join_buffer_size (MySQL)"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Hash Join",
+        slug: "hash-join",
+        summary: "Builds a hash table from one input and probes it with each row of the other.",
+        appears_when: "Equality joins over inputs too large for a nested loop, where neither input is already sorted on the join key.",
+        knobs: &["enable_hashjoin", "work_mem", "hash_mem_multiplier"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Hash",
+        slug: "hash",
+        summary: "Builds the in-memory hash table used by the Hash Join above it; more than one batch means it spilled to disk.",
+        appears_when: "Always as the inner child of a Hash Join.",
+        knobs: &["work_mem", "hash_mem_multiplier"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Merge Join",
+        slug: "merge-join",
+        summary: "Joins two inputs sorted on the join key by advancing through both in step.",
+        appears_when: "Both inputs are already sorted, for example by an index, or sorting them is cheaper than hashing.",
+        knobs: &["enable_mergejoin", "indexes on the join keys", "work_mem"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Sort",
+        slug: "sort",
+        summary: "Sorts its input; spills to disk when the rows do not fit in memory.",
+        appears_when: "ORDER BY, merge joins, DISTINCT or grouping need ordered input that no index provides.",
+        knobs: &["work_mem", "sort_buffer_size (MySQL)", "an index matching the sort order"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Incremental Sort",
+        slug: "incremental-sort",
+        summary: "Sorts input already ordered on a prefix of the keys, one group of equal prefixes at a time.",
+        appears_when: "An index provides the leading sort columns but not the rest.",
+        knobs: &["enable_incremental_sort", "work_mem"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Aggregate",
+        slug: "aggregate",
+        summary: "Computes aggregate functions, either over all rows or per group using hashing or sorted input.",
+        appears_when: "GROUP BY, aggregate functions, or DISTINCT implemented as grouping.",
+        knobs: &["enable_hashagg", "work_mem", "hash_mem_multiplier"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Unique",
+        slug: "unique",
+        summary: "Removes adjacent duplicate rows from sorted input.",
+        appears_when: "DISTINCT or UNION over input that is sorted on all output columns.",
+        knobs: &["UNION ALL where duplicates are impossible", "work_mem"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "WindowAgg",
+        slug: "windowagg",
+        summary: "Evaluates window functions over partitions of sorted input.",
+        appears_when: "The query uses OVER (...) clauses.",
+        knobs: &["work_mem", "an index matching PARTITION BY and ORDER BY"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Materialize",
+        slug: "materialize",
+        summary: "Stores its input's rows so they can be re-read without recomputing them.",
+        appears_when: "The inner side of a nested loop is rescanned, or a derived table or temporary table is built once and read many times.",
+        knobs: &["enable_material", "work_mem", "tmp_table_size (MySQL)"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Memoize",
+        slug: "memoize",
+        summary: "Caches inner-side results of a nested loop by parameter value, so repeated keys are not looked up again.",
+        appears_when: "The outer side of a nested loop repeats the same join keys many times.",
+        knobs: &["enable_memoize", "work_mem", "hash_mem_multiplier"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Append",
+        slug: "append",
+        summary: "Returns the rows of each child in turn.",
+        appears_when: "UNION ALL, or a scan of a partitioned or inherited table.",
+        knobs: &["enable_partition_pruning", "partition keys in the WHERE clause"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Merge Append",
+        slug: "merge-append",
+        summary: "Merges several sorted children into one sorted output.",
+        appears_when: "An ORDER BY over a partitioned table or UNION ALL whose branches are each sorted.",
+        knobs: &["enable_partition_pruning", "indexes matching the sort order on each partition"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Limit",
+        slug: "limit",
+        summary: "Stops reading its input once enough rows have been returned.",
+        appears_when: "LIMIT, OFFSET or FETCH FIRST; plans below it may be chosen for a fast start rather than the lowest total cost.",
+        knobs: &["an index matching the ORDER BY", "cursor_tuple_fraction"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Gather",
+        slug: "gather",
+        summary: "Collects rows from parallel workers running the plan below it.",
+        appears_when: "The table is large enough for a parallel plan to pay off.",
+        knobs: &["max_parallel_workers_per_gather", "parallel_setup_cost", "min_parallel_table_scan_size"],
+        docs_url: POSTGRES_PLANNER,
+    },
+    GlossaryEntry {
+        node_type: "Gather Merge",
+        slug: "gather-merge",
+        summary: "Collects sorted rows from parallel workers, preserving their order.",
+        appears_when: "A parallel plan whose workers each produce sorted output.",
+        knobs: &["max_parallel_workers_per_gather", "parallel_tuple_cost"],
+        docs_url: POSTGRES_PLANNER,
+    },
+    GlossaryEntry {
+        node_type: "Subquery Scan",
+        slug: "subquery-scan",
+        summary: "Reads the output of a subquery or view that could not be flattened into the outer query.",
+        appears_when: "Subqueries with LIMIT, aggregates, set operations or other constructs that block flattening.",
+        knobs: &["rewriting the subquery as a join"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "CTE Scan",
+        slug: "cte-scan",
+        summary: "Reads the stored result of a WITH query.",
+        appears_when: "A CTE is materialized, because it is referenced more than once or marked MATERIALIZED.",
+        knobs: &["NOT MATERIALIZED on the CTE"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Function Scan",
+        slug: "function-scan",
+        summary: "Returns the rows produced by a set-returning function.",
+        appears_when: "A function such as generate_series or unnest appears in FROM.",
+        knobs: &["ROWS estimate on the function definition"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Values Scan",
+        slug: "values-scan",
+        summary: "Returns the rows of a VALUES list.",
+        appears_when: "A VALUES list appears in FROM or as an IN list converted to a join.",
+        knobs: &[],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Foreign Scan",
+        slug: "foreign-scan",
+        summary: "Reads rows from an external source through a foreign data wrapper or virtual table.",
+        appears_when: "The query reads a foreign table (PostgreSQL) or a virtual table (SQLite).",
+        knobs: &["use_remote_estimate (postgres_fdw)", "fetch_size (postgres_fdw)"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Result",
+        slug: "result",
+        summary: "Computes rows without reading a table, or applies a one-time filter.",
+        appears_when: "SELECT without FROM, constant conditions, or projections that cannot be pushed down.",
+        knobs: &[],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "SubPlan",
+        slug: "subplan",
+        summary: "A correlated subquery evaluated for each row of the outer query.",
+        appears_when: "A subquery in SELECT or WHERE refers to outer columns and could not be turned into a join.",
+        knobs: &["rewriting the subquery as a join", "an index on the correlated columns"],
+        docs_url: POSTGRES_EXPLAIN,
+    },
+    GlossaryEntry {
+        node_type: "Query Block",
+        slug: "query-block",
+        summary: "One SELECT of the query; its children are the work MySQL or MariaDB does for it.",
+        appears_when: "At the root of every MySQL and MariaDB plan, and for each subquery or UNION branch.",
+        knobs: &[],
+        docs_url: MARIADB_ANALYZE,
+    },
+    GlossaryEntry {
+        node_type: "Query Plan",
+        slug: "query-plan",
+        summary: "The root of a SQLite plan; its children are the loops SQLite runs in order.",
+        appears_when: "At the root of every SQLite plan.",
+        knobs: &["ANALYZE (to gather sqlite_stat1 statistics)"],
+        docs_url: SQLITE_EQP,
+    },
+    GlossaryEntry {
+        node_type: "Plan Step",
+        slug: "plan-step",
+        summary: "A SQLite plan step with no closer match among the unified node types; see its detail text.",
+        appears_when: "SQLite reports an operation that has no PostgreSQL equivalent.",
+        knobs: &[],
+        docs_url: SQLITE_EQP,
+    },
+];
+
+/// Every glossary entry
+pub fn entries() -> &'static [GlossaryEntry] {
+    ENTRIES
+}
+
+/// Look up a node type by name or slug, ignoring case
+pub fn lookup(node_type: &str) -> Option<&'static GlossaryEntry> {
+    let wanted = slug(node_type);
+    ENTRIES.iter().find(|entry| entry.slug == wanted)
+}
+
+/// The path of the glossary entry for `node_type`, if it has one
+pub fn entry_path(node_type: &str) -> Option<String> {
+    lookup(node_type).map(|entry| format!("/api/glossary/{}", entry.slug))
+}
+
+/// Lowercase `name` and join its words with hyphens
+fn slug(name: &str) -> String {
+    name.split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_by_name_or_slug() {
+        let entry = lookup("Bitmap Heap Scan").unwrap();
+        assert_eq!(entry.slug, "bitmap-heap-scan");
+        assert_eq!(lookup("bitmap-heap-scan"), Some(entry));
+        assert_eq!(lookup("BITMAP_HEAP_SCAN"), Some(entry));
+        assert!(lookup("Warp Drive").is_none());
+
+        assert_eq!(
+            entry_path("Hash Join").as_deref(),
+            Some("/api/glossary/hash-join")
+        );
+    }
+
+    #[test]
+    fn test_slugs_match_node_types() {
+        for entry in entries() {
+            assert_eq!(slug(entry.node_type), entry.slug);
+        }
+    }
+}
@@ -2,9 +2,12 @@
 //!
 //! This module contains shared UI utilities and data structures for rendering execution plans.
 
-use crate::db::models::{ExecutionPlan, PlanNode};
+use crate::db::models::{BufferUsage, ExecutionPlan, JitInfo, PlanNode, SortGroups, WorkerStats};
 use serde::{Deserialize, Serialize};
 
+pub mod assets;
+pub mod glossary;
+
 /// Tree structure for representing execution plans in a hierarchical format
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct PlanTree {
@@ -14,6 +17,12 @@ pub struct PlanTree {
     pub root_indices: Vec<usize>,
     /// Hash of the last processed plan for caching
     pub last_plan_hash: Option<u64>,
+    /// JIT compilation of the query, if it was compiled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jit: Option<JitInfo>,
+    /// Share of the execution time spent on JIT compilation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jit_share: Option<f64>,
 }
 
 /// UI representation of a plan node
@@ -37,8 +46,125 @@ pub struct PlanNodeUI {
     pub actual_startup_time: Option<f64>,
     /// Actual total time in milliseconds
     pub actual_total_time: f64,
-    /// Actual number of rows returned
+    /// Actual number of rows returned per loop
     pub actual_rows: u64,
+    /// Number of times this node was executed
+    pub actual_loops: u64,
+    /// Total rows returned across all loops
+    pub total_rows: u64,
+    /// Total time spent across all loops in milliseconds
+    pub total_time: f64,
+    /// Condition an index scan looks rows up by
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_cond: Option<String>,
+    /// Condition rows are checked against after they are read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    /// Condition a join checks matched pairs of rows against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub join_filter: Option<String>,
+    /// Rows per loop removed by the filter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows_removed_by_filter: Option<u64>,
+    /// Rows per loop removed by the join filter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows_removed_by_join_filter: Option<u64>,
+    /// Aggregation strategy if applicable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+    /// Number of batches used by a hash aggregate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_agg_batches: Option<u64>,
+    /// Buckets of a Hash node's hash table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_buckets: Option<u64>,
+    /// Buckets the hash table was sized with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_hash_buckets: Option<u64>,
+    /// Batches a Hash node used; more than one means it spilled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_batches: Option<u64>,
+    /// Batches the Hash node was planned with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_hash_batches: Option<u64>,
+    /// Peak memory usage in kilobytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_memory_usage_kb: Option<u64>,
+    /// Disk usage in kilobytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_usage_kb: Option<u64>,
+    /// How a sort ran, e.g. "quicksort" or "external merge"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_method: Option<String>,
+    /// Space used by a sort in kilobytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_space_used_kb: Option<u64>,
+    /// Whether a sort ran in "Memory" or on "Disk"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_space_type: Option<String>,
+    /// Leading sort keys an Incremental Sort's input is already ordered by
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presorted_key: Option<Vec<String>>,
+    /// Groups an Incremental Sort sorted by all of its keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_sort_groups: Option<SortGroups>,
+    /// Groups an Incremental Sort sorted by the remaining keys only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_sorted_groups: Option<SortGroups>,
+    /// Lookups a Memoize node answered from its cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_hits: Option<u64>,
+    /// Lookups a Memoize node passed to its subplan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_misses: Option<u64>,
+    /// Entries a Memoize node evicted from its cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_evictions: Option<u64>,
+    /// Lookups whose rows a Memoize node could not cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_overflows: Option<u64>,
+    /// Share of a Memoize node's lookups answered from its cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_hit_ratio: Option<f64>,
+    /// Memory held by the node at its peak in kilobytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_used_kb: Option<u64>,
+    /// Query a Foreign Scan sends to the remote server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_sql: Option<String>,
+    /// Remote tables a Foreign Scan joins on the remote server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreign_relations: Option<String>,
+    /// Blocks accessed by the node and everything below it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffers: Option<BufferUsage>,
+    /// Blocks accessed by the node itself, its children's counts taken out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub own_buffers: Option<BufferUsage>,
+    /// Time the node and everything below it spent reading blocks, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_read_time: Option<f64>,
+    /// Time the node and everything below it spent writing blocks, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_write_time: Option<f64>,
+    /// Time the node itself spent on I/O, its children's taken out, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub own_io_time: Option<f64>,
+    /// Parallel workers the planner asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workers_planned: Option<u64>,
+    /// Parallel workers that actually started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workers_launched: Option<u64>,
+    /// Planned workers that did not start, when some did not
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workers_not_launched: Option<u64>,
+    /// Figures of each parallel worker that ran the node
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub workers: Vec<WorkerStats>,
+    /// Path of the glossary entry explaining this node type, if there is one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glossary: Option<String>,
     /// Additional node information
     pub extra: serde_json::Value,
 }
@@ -64,6 +190,46 @@ pub fn build_plan_tree_ui(
         actual_startup_time: node.actual_startup_time,
         actual_total_time: node.actual_total_time,
         actual_rows: node.actual_rows,
+        actual_loops: node.actual_loops,
+        total_rows: node.total_rows(),
+        total_time: node.total_time(),
+        index_cond: node.index_cond.clone(),
+        filter: node.filter.clone(),
+        join_filter: node.join_filter.clone(),
+        rows_removed_by_filter: node.rows_removed_by_filter,
+        rows_removed_by_join_filter: node.rows_removed_by_join_filter,
+        strategy: node.strategy.clone(),
+        hash_agg_batches: node.hash_agg_batches,
+        hash_buckets: node.hash_buckets,
+        original_hash_buckets: node.original_hash_buckets,
+        hash_batches: node.hash_batches,
+        original_hash_batches: node.original_hash_batches,
+        peak_memory_usage_kb: node.peak_memory_usage_kb,
+        disk_usage_kb: node.disk_usage_kb,
+        sort_method: node.sort_method.clone(),
+        sort_space_used_kb: node.sort_space_used_kb,
+        sort_space_type: node.sort_space_type.clone(),
+        presorted_key: node.presorted_key.clone(),
+        full_sort_groups: node.full_sort_groups.clone(),
+        pre_sorted_groups: node.pre_sorted_groups.clone(),
+        cache_hits: node.cache_hits,
+        cache_misses: node.cache_misses,
+        cache_evictions: node.cache_evictions,
+        cache_overflows: node.cache_overflows,
+        cache_hit_ratio: node.cache_hit_ratio(),
+        memory_used_kb: node.memory_used_kb(),
+        remote_sql: node.remote_sql.clone(),
+        foreign_relations: node.foreign_relations.clone(),
+        buffers: node.buffers,
+        own_buffers: node.own_buffers(),
+        io_read_time: node.io_read_time,
+        io_write_time: node.io_write_time,
+        own_io_time: node.own_io_time(),
+        workers_planned: node.workers_planned,
+        workers_launched: node.workers_launched,
+        workers_not_launched: node.workers_not_launched(),
+        workers: node.workers.clone(),
+        glossary: glossary::entry_path(&node.node_type),
         extra: node.extra.clone(),
     };
 
@@ -94,8 +260,129 @@ pub fn build_plan_tree_ui(
 
 /// Convert execution plan to a format suitable for web frontend
 pub fn plan_to_web_format(plan: &ExecutionPlan) -> serde_json::Value {
-    let mut tree = PlanTree::default();
+    let mut tree = PlanTree {
+        jit: plan.jit.clone(),
+        jit_share: plan.jit_share(),
+        ..PlanTree::default()
+    };
     build_plan_tree_ui(&plan.root, &mut tree, 0, None);
 
     serde_json::to_value(tree).unwrap_or_else(|_| serde_json::json!({}))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(node_type: &str, rows: u64, loops: u64, time: f64) -> PlanNode {
+        PlanNode {
+            node_type: node_type.to_string(),
+            relation_name: None,
+            alias: None,
+            startup_cost: 0.0,
+            total_cost: 1.0,
+            actual_startup_time: Some(0.0),
+            actual_total_time: time,
+            actual_rows: rows,
+            actual_loops: loops,
+            plans: vec![],
+            extra: serde_json::json!({}),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_web_format_accounts_for_loops() {
+        let mut root = node("Nested Loop", 100, 1, 5.0);
+        root.plans.push(node("Index Scan", 1, 100, 0.02));
+
+        let plan = ExecutionPlan {
+            root,
+            planning_time: 0.1,
+            execution_time: 5.0,
+            settings: None,
+            jit: None,
+        };
+
+        let value = plan_to_web_format(&plan);
+        let inner = &value["nodes"][1];
+        assert_eq!(inner["actual_rows"], 1);
+        assert_eq!(inner["actual_loops"], 100);
+        assert_eq!(inner["total_rows"], 100);
+        assert!((inner["total_time"].as_f64().unwrap() - 2.0).abs() < 1e-9);
+        assert_eq!(inner["glossary"], "/api/glossary/index-scan");
+    }
+
+    #[test]
+    fn test_web_format_splits_buffers_per_node() {
+        let root: PlanNode = serde_json::from_value(serde_json::json!({
+            "Node Type": "Hash Join",
+            "Startup Cost": 0.0,
+            "Total Cost": 10.0,
+            "Shared Hit Blocks": 30,
+            "Shared Read Blocks": 12,
+            "Temp Written Blocks": 5,
+            "Plans": [
+                {
+                    "Node Type": "Seq Scan",
+                    "Startup Cost": 0.0,
+                    "Total Cost": 4.0,
+                    "Shared Hit Blocks": 20,
+                    "Shared Read Blocks": 10
+                },
+                {
+                    "Node Type": "Hash",
+                    "Startup Cost": 0.0,
+                    "Total Cost": 4.0,
+                    "Shared Hit Blocks": 10,
+                    "Shared Read Blocks": 2
+                }
+            ]
+        }))
+        .unwrap();
+
+        let plan = ExecutionPlan {
+            root,
+            planning_time: 0.1,
+            execution_time: 1.0,
+            settings: None,
+            jit: None,
+        };
+
+        let value = plan_to_web_format(&plan);
+        let join = &value["nodes"][0];
+        assert_eq!(join["buffers"]["Shared Hit Blocks"], 30);
+        assert_eq!(join["own_buffers"]["Shared Hit Blocks"], 0);
+        assert_eq!(join["own_buffers"]["Shared Read Blocks"], 0);
+        assert_eq!(join["own_buffers"]["Temp Written Blocks"], 5);
+        assert!(join["extra"].get("Shared Hit Blocks").is_none());
+        let scan = &value["nodes"][1];
+        assert_eq!(scan["buffers"], scan["own_buffers"]);
+        assert_eq!(scan["own_buffers"]["Shared Read Blocks"], 10);
+    }
+
+    #[test]
+    fn test_web_format_carries_jit() {
+        let jit: JitInfo = serde_json::from_value(serde_json::json!({
+            "Functions": 3,
+            "Options": {"Inlining": false, "Optimization": false, "Expressions": true, "Deforming": true},
+            "Timing": {"Generation": 0.5, "Inlining": 0.0, "Optimization": 0.0, "Emission": 2.0, "Total": 2.5}
+        }))
+        .unwrap();
+        let plan = ExecutionPlan {
+            root: node("Seq Scan", 10, 1, 8.0),
+            planning_time: 0.1,
+            execution_time: 10.0,
+            settings: None,
+            jit: Some(jit),
+        };
+
+        let value = plan_to_web_format(&plan);
+        assert_eq!(value["jit"]["Functions"], 3);
+        assert_eq!(value["jit"]["Timing"]["Total"], 2.5);
+        assert_eq!(value["jit_share"], 0.25);
+
+        let uncompiled = ExecutionPlan { jit: None, ..plan };
+        assert!(plan_to_web_format(&uncompiled).get("jit").is_none());
+    }
+}
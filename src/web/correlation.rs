@@ -0,0 +1,169 @@
+//! Correlation of analysis requests with application traces
+//!
+//! Someone investigating a slow request in their APM tool usually runs the
+//! offending query through SQLTrace next. Requests can carry the W3C
+//! `traceparent` header of that trace, or a free-form correlation id, and both
+//! are stored with the analysis and added to the request's span so history
+//! entries and logs can be found from the trace and vice versa.
+
+use axum::http::{HeaderMap, Request};
+use serde::{Deserialize, Serialize};
+use tracing::Span;
+
+/// Header carrying the W3C trace context
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Headers checked, in order, for a correlation id
+pub const CORRELATION_HEADERS: [&str; 3] = [
+    "x-sqltrace-correlation-id",
+    "x-correlation-id",
+    "x-request-id",
+];
+
+/// Longest correlation id that is kept
+const MAX_CORRELATION_ID_LEN: usize = 128;
+
+/// Trace and correlation ids a request was sent with
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Correlation {
+    /// Trace id from a valid `traceparent` header, as 32 lowercase hex digits
+    pub trace_id: Option<String>,
+    /// Caller-supplied correlation id
+    pub correlation_id: Option<String>,
+}
+
+impl Correlation {
+    /// Read the ids from request headers, ignoring malformed values
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let trace_id = headers
+            .get(TRACEPARENT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_traceparent);
+        let correlation_id = CORRELATION_HEADERS
+            .iter()
+            .filter_map(|name| headers.get(*name))
+            .filter_map(|value| value.to_str().ok())
+            .map(str::trim)
+            .find(|id| is_valid_correlation_id(id))
+            .map(str::to_string);
+
+        Self {
+            trace_id,
+            correlation_id,
+        }
+    }
+
+    /// Whether the request carried neither id
+    pub fn is_empty(&self) -> bool {
+        self.trace_id.is_none() && self.correlation_id.is_none()
+    }
+}
+
+/// Extract the trace id from a W3C `traceparent` value
+///
+/// The value is `version-traceid-parentid-flags`. Version `ff` and all-zero
+/// ids are invalid; later versions may append fields after the flags.
+pub fn parse_traceparent(value: &str) -> Option<String> {
+    let mut parts = value.trim().split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let parent_id = parts.next()?;
+    let flags = parts.next()?;
+
+    let valid = is_hex(version, 2)
+        && version != "ff"
+        && (version != "00" || parts.next().is_none())
+        && is_hex(trace_id, 32)
+        && is_hex(parent_id, 16)
+        && is_hex(flags, 2)
+        && trace_id.bytes().any(|b| b != b'0')
+        && parent_id.bytes().any(|b| b != b'0');
+    valid.then(|| trace_id.to_ascii_lowercase())
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn is_valid_correlation_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_CORRELATION_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Span for an HTTP request, tagged with its trace and correlation ids
+pub fn request_span<B>(request: &Request<B>) -> Span {
+    let correlation = Correlation::from_headers(request.headers());
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        trace_id = tracing::field::Empty,
+        correlation_id = tracing::field::Empty,
+    );
+    if let Some(trace_id) = &correlation.trace_id {
+        span.record("trace_id", trace_id.as_str());
+    }
+    if let Some(correlation_id) = &correlation.correlation_id {
+        span.record("correlation_id", correlation_id.as_str());
+    }
+    span
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_traceparent() {
+        assert_eq!(
+            parse_traceparent("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01"),
+            Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string())
+        );
+        // Future versions may add fields
+        assert!(
+            parse_traceparent("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra")
+                .is_some()
+        );
+        assert!(
+            parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra")
+                .is_none()
+        );
+        assert!(
+            parse_traceparent("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").is_none()
+        );
+        assert!(
+            parse_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none()
+        );
+        assert!(parse_traceparent("00-4bf92f3577b34da6-00f067aa0ba902b7-01").is_none());
+        assert!(parse_traceparent("not a traceparent").is_none());
+    }
+
+    #[test]
+    fn test_correlation_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert!(Correlation::from_headers(&headers).is_empty());
+
+        headers.insert(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+        headers.insert("x-request-id", "req-1".parse().unwrap());
+        headers.insert("x-correlation-id", "has spaces".parse().unwrap());
+
+        let correlation = Correlation::from_headers(&headers);
+        assert_eq!(
+            correlation.trace_id.as_deref(),
+            Some("4bf92f3577b34da6a3ce929d0e0e4736")
+        );
+        assert_eq!(correlation.correlation_id.as_deref(), Some("req-1"));
+
+        headers.insert("x-sqltrace-correlation-id", "incident-42".parse().unwrap());
+        assert_eq!(
+            Correlation::from_headers(&headers)
+                .correlation_id
+                .as_deref(),
+            Some("incident-42")
+        );
+    }
+}
@@ -1,17 +1,26 @@
 //! Web-related utilities and validation functions
 
+pub mod correlation;
+pub mod negotiation;
+pub mod probes;
+pub mod shaping;
+
 use sqlparser::ast::Statement;
-use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::dialect::{Dialect, PostgreSqlDialect};
 use sqlparser::parser::Parser;
 
 /// Validate a SQL query for basic syntax correctness
 pub fn validate_query(query: &str) -> Result<(), String> {
+    validate_query_with_dialect(query, &PostgreSqlDialect {})
+}
+
+/// Validate a SQL query for basic syntax correctness in the given dialect
+pub fn validate_query_with_dialect(query: &str, dialect: &dyn Dialect) -> Result<(), String> {
     if query.trim().is_empty() {
         return Err("Query cannot be empty".to_string());
     }
 
-    let dialect = PostgreSqlDialect {};
-    match Parser::parse_sql(&dialect, query) {
+    match Parser::parse_sql(dialect, query) {
         Ok(statements) => {
             if statements.is_empty() {
                 return Err("No valid SQL statements found".to_string());
@@ -59,6 +68,13 @@ mod tests {
         assert!(validate_query("CREATE TABLE test (id INT)").is_err());
     }
 
+    #[test]
+    fn test_validate_query_with_dialect() {
+        let query = "SELECT `name` FROM `users`";
+        assert!(validate_query(query).is_err());
+        assert!(validate_query_with_dialect(query, &sqlparser::dialect::MySqlDialect {}).is_ok());
+    }
+
     #[test]
     fn test_validate_query_invalid_syntax() {
         assert!(validate_query("SELECT FROM").is_err());
@@ -0,0 +1,105 @@
+//! Binary response format negotiated with `Accept`
+//!
+//! Endpoints returning plans answer in JSON unless the request's `Accept`
+//! header prefers `application/msgpack` (or `application/x-msgpack`), in
+//! which case the same document is sent as MessagePack. Other responses,
+//! such as rejections that aren't JSON, are passed through unchanged.
+
+use axum::body::{to_bytes, Body};
+use axum::extract::Request;
+use axum::http::header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, VARY};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde_json::Value;
+
+use crate::msgpack;
+
+/// Media types accepted as asking for MessagePack
+const MSGPACK_TYPES: [&str; 2] = [msgpack::CONTENT_TYPE, "application/x-msgpack"];
+
+/// Whether `accept` ranks MessagePack at least as high as JSON
+///
+/// Wildcards count as JSON, so clients only get MessagePack by naming it.
+pub fn prefers_msgpack(accept: &str) -> bool {
+    let mut msgpack_q: f32 = 0.0;
+    let mut json_q: f32 = 0.0;
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let media_type = params
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let q = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if MSGPACK_TYPES.contains(&media_type.as_str()) {
+            msgpack_q = msgpack_q.max(q);
+        } else if matches!(
+            media_type.as_str(),
+            "application/json" | "application/*" | "*/*"
+        ) {
+            json_q = json_q.max(q);
+        }
+    }
+    msgpack_q > 0.0 && msgpack_q >= json_q
+}
+
+fn wants_msgpack(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(prefers_msgpack)
+}
+
+/// Send JSON responses as MessagePack when the request's `Accept` prefers it
+pub async fn negotiate_format(request: Request, next: Next) -> Response {
+    let msgpack = wants_msgpack(request.headers());
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .append(VARY, HeaderValue::from_static("accept"));
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if !msgpack || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let Ok(value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(msgpack::CONTENT_TYPE),
+    );
+    Response::from_parts(parts, Body::from(msgpack::encode(&value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefers_msgpack() {
+        assert!(prefers_msgpack("application/msgpack"));
+        assert!(prefers_msgpack("application/x-msgpack, application/json"));
+        assert!(prefers_msgpack(
+            "application/json;q=0.5, application/msgpack"
+        ));
+        assert!(!prefers_msgpack("application/json"));
+        assert!(!prefers_msgpack("*/*"));
+        assert!(!prefers_msgpack("application/msgpack;q=0.2, */*"));
+        assert!(!prefers_msgpack("application/msgpack;q=0"));
+        assert!(!prefers_msgpack(""));
+    }
+}
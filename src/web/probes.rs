@@ -0,0 +1,221 @@
+//! Liveness, readiness and startup probes for orchestrators such as Kubernetes
+//!
+//! Each probe answers a different question. `/livez` says the process is
+//! serving requests at all, the only thing a restart could fix. `/readyz`
+//! says whether requests can be served right now: the analyzed database
+//! answers on the default connection's pool and storage is reachable.
+//! `/startupz` says the server has started, which it has as soon as it
+//! listens: the analyzed database is connected in the background, so a
+//! database that is still booting holds back readiness without failing the
+//! startup probe and getting the server restarted.
+//!
+//! Until the database is connected, every other request is answered with
+//! `503 Service Unavailable`.
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    routing::get,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tower::Service;
+
+use crate::server::{create_router, AppState};
+use crate::storage::Storage;
+
+/// What the probes check and how the server waits for the analyzed database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Report not ready while the database of the default connection can't be reached
+    pub require_database: bool,
+    /// Report not ready while storage can't be reached
+    pub require_storage: bool,
+    /// Time each readiness check may take before it counts as failed, in seconds
+    pub check_timeout_secs: u64,
+    /// Time between attempts to connect to the analyzed database at startup, in seconds
+    pub connect_retry_secs: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            require_database: true,
+            require_storage: true,
+            check_timeout_secs: 2,
+            connect_retry_secs: 5,
+        }
+    }
+}
+
+/// Overall outcome of a readiness check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Readiness {
+    /// The analyzed database has not been connected yet
+    Starting,
+    /// Every required check passed
+    Ready,
+    /// A required check failed
+    NotReady,
+}
+
+/// One dependency checked for readiness
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeCheck {
+    /// What was checked: `database` or `storage`
+    pub name: String,
+    /// Whether the dependency answered
+    pub ok: bool,
+    /// Whether a failure makes the server not ready
+    pub required: bool,
+    /// Why the check failed
+    pub error: Option<String>,
+}
+
+/// Response of the readiness probe
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessReport {
+    /// Overall outcome
+    pub status: Readiness,
+    /// Each dependency checked
+    pub checks: Vec<ProbeCheck>,
+}
+
+/// Probe endpoints in front of the application, which is installed once the
+/// analyzed database is connected
+#[derive(Clone)]
+pub struct Probes {
+    config: Arc<HealthConfig>,
+    storage: Arc<dyn Storage>,
+    app: Arc<OnceLock<(AppState, Router)>>,
+}
+
+impl Probes {
+    /// Create probes checking `storage`, with no application installed yet
+    pub fn new(config: HealthConfig, storage: Arc<dyn Storage>) -> Self {
+        Self {
+            config: Arc::new(config),
+            storage,
+            app: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Install the application; later calls are ignored
+    pub fn set_app(&self, state: AppState) {
+        let router = create_router(state.clone());
+        let _ = self.app.set((state, router));
+    }
+
+    /// Router serving the probes and forwarding everything else to the application
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/livez", get(livez_handler))
+            .route("/readyz", get(readyz_handler))
+            .route("/startupz", get(startupz_handler))
+            .fallback(forward)
+            .with_state(self.clone())
+    }
+
+    /// Check the dependencies the server needs to serve requests
+    pub async fn readiness(&self) -> ReadinessReport {
+        let timeout = Duration::from_secs(self.config.check_timeout_secs.max(1));
+        let storage = check(
+            "storage",
+            self.config.require_storage,
+            timeout,
+            self.storage.ping(),
+        )
+        .await;
+
+        let Some((state, _)) = self.app.get() else {
+            return ReadinessReport {
+                status: Readiness::Starting,
+                checks: vec![storage],
+            };
+        };
+        let database = check(
+            "database",
+            self.config.require_database,
+            timeout,
+            state.default_db().version_info(),
+        )
+        .await;
+
+        let checks = vec![database, storage];
+        let status = if checks.iter().all(|c| c.ok || !c.required) {
+            Readiness::Ready
+        } else {
+            Readiness::NotReady
+        };
+        ReadinessReport { status, checks }
+    }
+}
+
+/// Run one readiness check, failing it if it takes longer than `timeout`
+async fn check<T, E: std::fmt::Display>(
+    name: &str,
+    required: bool,
+    timeout: Duration,
+    check: impl std::future::Future<Output = Result<T, E>>,
+) -> ProbeCheck {
+    let error = match tokio::time::timeout(timeout, check).await {
+        Ok(Ok(_)) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!("No answer within {}s", timeout.as_secs())),
+    };
+    ProbeCheck {
+        name: name.to_string(),
+        ok: error.is_none(),
+        required,
+        error,
+    }
+}
+
+/// The process is up and serving requests
+async fn livez_handler() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "alive" }))
+}
+
+/// Whether requests can be served; `503 Service Unavailable` if not
+async fn readyz_handler(State(probes): State<Probes>) -> Response {
+    let report = probes.readiness().await;
+    let status = match report.status {
+        Readiness::Ready => StatusCode::OK,
+        Readiness::Starting | Readiness::NotReady => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(report)).into_response()
+}
+
+/// Startup has finished; reports whether the database is connected yet
+async fn startupz_handler(State(probes): State<Probes>) -> Json<serde_json::Value> {
+    let database = if probes.app.get().is_some() {
+        "connected"
+    } else {
+        "connecting"
+    };
+    Json(serde_json::json!({ "status": "started", "database": database }))
+}
+
+/// Pass a request to the application, or refuse it while the database is being connected
+async fn forward(State(probes): State<Probes>, request: Request) -> Response {
+    match probes.app.get() {
+        // A router is always ready, so it can be called without polling it first
+        Some((_, router)) => match router.clone().call(request).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        },
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "status": "starting",
+                "error": "Still connecting to the database"
+            })),
+        )
+            .into_response(),
+    }
+}
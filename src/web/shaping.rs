@@ -0,0 +1,226 @@
+//! Response shaping by field selection
+//!
+//! Plans carry every field the database reported in `extra`, and raw EXPLAIN
+//! output or stored plans can run to megabytes. Clients that only need part
+//! of a response, such as a terminal UI over SSH or a CI job logging the
+//! score, name what to keep with `?fields=` or what to drop with `?exclude=`.
+//!
+//! Both take comma-separated dot paths like `plan.nodes.cost`; arrays are
+//! looked through, so a path applies to every element. `fields` paths start
+//! at the top of the response, and `error` is always kept. `exclude` paths
+//! match wherever they occur, so `exclude=extra` drops the `extra` of every
+//! plan node. When both are given, `fields` is applied first.
+
+use axum::body::{to_bytes, Body};
+use axum::extract::{Query, Request};
+use axum::http::header::CONTENT_LENGTH;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Field of a response that `fields` never removes
+const ALWAYS_KEPT: &str = "error";
+
+/// Parts of a response to keep or drop, from the query string
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct FieldSelection {
+    /// Paths to keep, separated by commas
+    pub fields: Option<String>,
+    /// Paths to drop, separated by commas
+    pub exclude: Option<String>,
+}
+
+impl FieldSelection {
+    /// Whether the selection leaves responses as they are
+    pub fn is_empty(&self) -> bool {
+        paths(self.fields.as_deref()).is_empty() && paths(self.exclude.as_deref()).is_empty()
+    }
+
+    /// Shape `value` as the selection says
+    pub fn apply(&self, value: &mut Value) {
+        let mut fields = paths(self.fields.as_deref());
+        if !fields.is_empty() {
+            fields.push(vec![ALWAYS_KEPT]);
+            keep(value, &fields);
+        }
+        for path in paths(self.exclude.as_deref()) {
+            remove_anywhere(value, &path);
+        }
+    }
+}
+
+/// Shape JSON responses by the `fields` and `exclude` query parameters
+///
+/// Responses that aren't JSON are passed through unchanged.
+pub async fn shape_response(request: Request, next: Next) -> Response {
+    let selection = match Query::<FieldSelection>::try_from_uri(request.uri()) {
+        Ok(Query(selection)) => selection,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let response = next.run(request).await;
+    if selection.is_empty() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let Ok(mut value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    selection.apply(&mut value);
+    match serde_json::to_vec(&value) {
+        Ok(shaped) => {
+            parts.headers.remove(CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(shaped))
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// The dot paths of a comma-separated list
+fn paths(list: Option<&str>) -> Vec<Vec<&str>> {
+    list.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(|path| path.split('.').collect())
+        .collect()
+}
+
+/// Keep only the parts of `value` that `paths` lead to
+fn keep(value: &mut Value, paths: &[Vec<&str>]) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                keep(item, paths);
+            }
+        }
+        Value::Object(map) => {
+            map.retain(|key, child| {
+                let rest: Vec<Vec<&str>> = paths
+                    .iter()
+                    .filter(|path| path.first() == Some(&key.as_str()))
+                    .map(|path| path[1..].to_vec())
+                    .collect();
+                if rest.is_empty() {
+                    return false;
+                }
+                if !rest.iter().any(Vec::is_empty) {
+                    keep(child, &rest);
+                }
+                true
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Remove what `path` leads to from `value` and from everything inside it
+fn remove_anywhere(value: &mut Value, path: &[&str]) {
+    remove_at(value, path);
+    match value {
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| remove_anywhere(item, path)),
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|child| remove_anywhere(child, path)),
+        _ => {}
+    }
+}
+
+/// Remove what `path` leads to, starting at `value`
+fn remove_at(value: &mut Value, path: &[&str]) {
+    let Some((first, rest)) = path.split_first() else {
+        return;
+    };
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(|item| remove_at(item, path)),
+        Value::Object(map) if rest.is_empty() => {
+            map.remove(*first);
+        }
+        Value::Object(map) => {
+            if let Some(child) = map.get_mut(*first) {
+                remove_at(child, rest);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn shaped(fields: Option<&str>, exclude: Option<&str>, mut value: Value) -> Value {
+        FieldSelection {
+            fields: fields.map(str::to_string),
+            exclude: exclude.map(str::to_string),
+        }
+        .apply(&mut value);
+        value
+    }
+
+    fn response() -> Value {
+        json!({
+            "plan": {
+                "nodes": [
+                    {"node_type": "Hash Join", "cost": 120.5, "extra": {"Hash Cond": "(a.id = b.id)"}},
+                    {"node_type": "Seq Scan", "cost": 40.0, "extra": {"Filter": "(b.x > 1)"}}
+                ],
+                "root_indices": [0]
+            },
+            "raw_plan": [{"Plan": {}}],
+            "advisor_analysis": {"performance_score": 80, "suggestions": []},
+            "error": null
+        })
+    }
+
+    #[test]
+    fn test_exclude_matches_anywhere() {
+        let value = shaped(None, Some("extra, raw_plan"), response());
+        assert!(value.get("raw_plan").is_none());
+        assert_eq!(value["plan"]["nodes"][1]["cost"], 40.0);
+        assert!(value["plan"]["nodes"][0].get("extra").is_none());
+        assert!(value["plan"]["nodes"][1].get("extra").is_none());
+
+        let value = shaped(None, Some("nodes.extra.Filter"), response());
+        assert!(value["plan"]["nodes"][1]["extra"].get("Filter").is_none());
+        assert!(value["plan"]["nodes"][0]["extra"]
+            .get("Hash Cond")
+            .is_some());
+    }
+
+    #[test]
+    fn test_fields_keep_paths_and_error() {
+        let value = shaped(
+            Some("advisor_analysis.performance_score,plan.nodes.cost"),
+            None,
+            response(),
+        );
+        assert_eq!(
+            value,
+            json!({
+                "plan": {"nodes": [{"cost": 120.5}, {"cost": 40.0}]},
+                "advisor_analysis": {"performance_score": 80},
+                "error": null
+            })
+        );
+
+        let value = shaped(Some("plan"), Some("extra"), response());
+        assert_eq!(value["plan"]["root_indices"], json!([0]));
+        assert!(value["plan"]["nodes"][0].get("extra").is_none());
+        assert!(value.get("advisor_analysis").is_none());
+
+        assert!(FieldSelection {
+            fields: Some(" , ".to_string()),
+            exclude: None,
+        }
+        .is_empty());
+    }
+}
@@ -0,0 +1,508 @@
+//! Plans logged by PostgreSQL's `auto_explain` module
+//!
+//! With `auto_explain` loaded, the server logs the plan of every statement
+//! slower than `auto_explain.log_min_duration`, as it ran in production and
+//! with the parameters it ran with. Entries look like this in the default
+//! `stderr` log format, with continuation lines indented by a tab (shown
+//! here as spaces):
+//!
+//! ```text
+//! 2024-05-01 12:00:00.123 UTC [4242] LOG:  duration: 1520.331 ms  plan:
+//!     {
+//!       "Query Text": "SELECT * FROM orders WHERE status = 'open'",
+//!       "Plan": {
+//!         "Node Type": "Seq Scan",
+//!         ...
+//! ```
+//!
+//! The `jsonlog` format (PostgreSQL 15 and later) holds the same message in
+//! the `message` field of one JSON object per line. Only plans logged with
+//! `auto_explain.log_format = json` can be read; entries in the text, YAML
+//! or XML formats are reported as skipped.
+//!
+//! Parsed plans are recorded in history like interactive analyses, so slow
+//! production queries show up without anyone having to paste them in.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::advisor::{AnalysisContext, SharedAdvisor};
+use crate::db::models::{ExecutionPlan, ExplainPlan};
+use crate::masking::MaskingConfig;
+use crate::storage::findings::fingerprint;
+use crate::storage::{HistoryEntry, NewHistoryEntry, Storage};
+use crate::SqlTraceError;
+
+/// Following a PostgreSQL log file for `auto_explain` plans
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoExplainConfig {
+    /// Log file to follow; ingestion is off when not set
+    pub log_path: Option<PathBuf>,
+    /// Connection the logged plans are recorded under
+    pub connection: String,
+    /// How often to check the log for new entries, in seconds
+    pub poll_interval_secs: u64,
+    /// Import the entries already in the log at startup instead of only new ones
+    pub from_start: bool,
+}
+
+impl Default for AutoExplainConfig {
+    fn default() -> Self {
+        Self {
+            log_path: None,
+            connection: "default".to_string(),
+            poll_interval_secs: 5,
+            from_start: false,
+        }
+    }
+}
+
+/// A plan found in the log
+#[derive(Debug, Clone)]
+pub struct LoggedPlan {
+    /// Line of the log the entry starts at, counting from 1
+    pub line: usize,
+    /// The statement, as sent by the client
+    pub query: String,
+    /// Fingerprint of the statement with literals masked
+    pub fingerprint: String,
+    /// Duration of the statement, in milliseconds
+    pub duration_ms: f64,
+    /// The logged plan
+    pub plan: ExecutionPlan,
+}
+
+/// An `auto_explain` entry that could not be recorded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedEntry {
+    /// Line of the log the entry starts at, counting from 1
+    pub line: usize,
+    /// Why the entry was skipped
+    pub reason: String,
+}
+
+/// The `auto_explain` entries of a log
+#[derive(Debug, Clone, Default)]
+pub struct AutoExplainLog {
+    /// Plans that could be read, in log order
+    pub plans: Vec<LoggedPlan>,
+    /// Entries whose plan could not be read
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// A logged plan stored in history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedPlan {
+    /// Id of the history entry
+    pub history_id: i64,
+    /// Line of the log the entry starts at
+    pub line: usize,
+    /// Fingerprint of the statement with literals masked
+    pub fingerprint: String,
+    /// Duration of the statement, in milliseconds
+    pub duration_ms: f64,
+}
+
+/// Outcome of importing the `auto_explain` entries of a log
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AutoExplainImport {
+    /// Plans stored in history, in log order
+    pub recorded: Vec<RecordedPlan>,
+    /// Entries that were not stored, with the reason
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// Extract the `auto_explain` entries of a PostgreSQL log
+///
+/// Other log lines, including `log_min_duration_statement` entries that
+/// carry no plan, are ignored.
+pub fn parse_auto_explain_log(log: &str) -> AutoExplainLog {
+    let mut parsed = AutoExplainLog::default();
+    let mut record = |line: usize, message: &str| match parse_message(line, message) {
+        Some(Ok(plan)) => parsed.plans.push(plan),
+        Some(Err(skipped)) => parsed.skipped.push(skipped),
+        None => {}
+    };
+
+    // The message being assembled from a stderr header and its continuation lines
+    let mut current: Option<(usize, String)> = None;
+    for (index, line) in log.lines().enumerate() {
+        if let Some(continuation) = line.strip_prefix('\t') {
+            if let Some((_, message)) = current.as_mut() {
+                message.push('\n');
+                message.push_str(continuation);
+            }
+            continue;
+        }
+        if let Some((start, message)) = current.take() {
+            record(start, &message);
+        }
+        match jsonlog_message(line) {
+            Some(message) => record(index + 1, &message),
+            None => current = Some((index + 1, line.to_string())),
+        }
+    }
+    if let Some((start, message)) = current {
+        record(start, &message);
+    }
+    parsed
+}
+
+/// The `message` of a `jsonlog` record
+fn jsonlog_message(line: &str) -> Option<String> {
+    if !line.starts_with('{') {
+        return None;
+    }
+    let record: Value = serde_json::from_str(line).ok()?;
+    record.get("message")?.as_str().map(str::to_string)
+}
+
+/// Parse a log message; `None` unless it is an `auto_explain` entry
+fn parse_message(line: usize, message: &str) -> Option<Result<LoggedPlan, SkippedEntry>> {
+    let (header, body) = message.split_once('\n').unwrap_or((message, ""));
+    let (_, rest) = header.split_once("duration: ")?;
+    let (duration, tail) = rest.split_once(" ms")?;
+    if tail.trim() != "plan:" {
+        return None;
+    }
+    let skipped = |reason: &str| {
+        Some(Err(SkippedEntry {
+            line,
+            reason: reason.to_string(),
+        }))
+    };
+
+    let Ok(duration_ms) = duration.trim().parse::<f64>() else {
+        return skipped("Unreadable duration");
+    };
+    if !body.trim_start().starts_with('{') {
+        return skipped("Plan is not in JSON format; set auto_explain.log_format = json");
+    }
+    let entry: Value = match serde_json::from_str(body) {
+        Ok(entry) => entry,
+        Err(e) => return skipped(&format!("Invalid plan JSON: {}", e)),
+    };
+    let Some(query) = entry.get("Query Text").and_then(Value::as_str) else {
+        return skipped("Plan has no query text");
+    };
+    let explained: ExplainPlan = match serde_json::from_value(entry.clone()) {
+        Ok(explained) => explained,
+        Err(e) => return skipped(&format!("Failed to parse plan: {}", e)),
+    };
+
+    let query = query.trim().trim_end_matches(';').trim_end().to_string();
+    Some(Ok(LoggedPlan {
+        line,
+        fingerprint: fingerprint(&query),
+        query,
+        duration_ms,
+        plan: ExecutionPlan {
+            root: explained.plan,
+            planning_time: explained.planning_time,
+            execution_time: duration_ms,
+            settings: None,
+            jit: explained.jit,
+        },
+    }))
+}
+
+/// Byte offset up to which `log` holds complete entries
+///
+/// A stderr entry may still be getting continuation lines until the next
+/// entry starts, so everything from the last line that isn't a continuation
+/// is held back.
+fn complete_prefix(log: &str) -> usize {
+    let mut cut = 0;
+    let mut offset = 0;
+    for line in log.split_inclusive('\n') {
+        if !line.starts_with('\t') {
+            cut = offset;
+        }
+        offset += line.len();
+    }
+    cut
+}
+
+/// Stores logged plans in history, with advisor analysis
+#[derive(Clone)]
+pub struct PlanRecorder {
+    storage: Arc<dyn Storage>,
+    advisor: SharedAdvisor,
+    masking: MaskingConfig,
+}
+
+impl PlanRecorder {
+    /// Create a recorder storing into `storage`, masked as `masking` says
+    pub fn new(storage: Arc<dyn Storage>, advisor: SharedAdvisor, masking: MaskingConfig) -> Self {
+        Self {
+            storage,
+            advisor,
+            masking,
+        }
+    }
+
+    /// Store one logged plan under `connection`
+    pub async fn record(
+        &self,
+        logged: &LoggedPlan,
+        connection: &str,
+    ) -> Result<HistoryEntry, SqlTraceError> {
+        // Without auto_explain.log_analyze the plan holds estimates only
+        let context = AnalysisContext {
+            estimates_only: logged.plan.root.actual_loops == 0,
+            ..AnalysisContext::default()
+        };
+        let analysis = self
+            .advisor
+            .current()
+            .analyze_plan_with_context(&logged.plan, &context);
+        let mut plan = serde_json::to_value(&logged.plan)?;
+        let mut analysis_value = serde_json::to_value(&analysis)?;
+        self.masking.analysis(&plan, &mut analysis_value);
+        self.masking.plan(&mut plan);
+
+        let entry = NewHistoryEntry {
+            query: self.masking.query(&logged.query),
+            plan,
+            analysis: Some(analysis_value),
+            performance_score: Some(analysis.performance_score),
+            total_cost: logged.plan.root.total_cost,
+            execution_time_ms: logged.duration_ms,
+            trace_id: None,
+            correlation_id: None,
+            table_definitions: None,
+            connection: Some(connection.to_string()),
+        };
+        Ok(self.storage.record_history(entry).await?)
+    }
+
+    /// Store every plan of `log` under `connection`
+    pub async fn import(&self, log: AutoExplainLog, connection: &str) -> AutoExplainImport {
+        let mut import = AutoExplainImport {
+            recorded: Vec::new(),
+            skipped: log.skipped,
+        };
+        for logged in &log.plans {
+            match self.record(logged, connection).await {
+                Ok(entry) => import.recorded.push(RecordedPlan {
+                    history_id: entry.id,
+                    line: logged.line,
+                    fingerprint: logged.fingerprint.clone(),
+                    duration_ms: logged.duration_ms,
+                }),
+                Err(e) => import.skipped.push(SkippedEntry {
+                    line: logged.line,
+                    reason: format!("Failed to record: {}", e),
+                }),
+            }
+        }
+        import
+    }
+}
+
+/// Follow `config.log_path` and record the plans appended to it
+///
+/// Returns `None` without spawning anything when no log is configured. A log
+/// that shrinks is taken to have been truncated or rotated in place and is
+/// read again from the start.
+pub fn spawn_tail(
+    config: AutoExplainConfig,
+    recorder: PlanRecorder,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let path = config.log_path.clone()?;
+    let interval = Duration::from_secs(config.poll_interval_secs.max(1));
+
+    Some(tokio::spawn(async move {
+        let mut offset = if config.from_start {
+            0
+        } else {
+            tokio::fs::metadata(&path)
+                .await
+                .map(|metadata| metadata.len())
+                .unwrap_or(0)
+        };
+        let mut pending = String::new();
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let appended = match read_appended(&path, &mut offset).await {
+                Ok(Some(appended)) => appended,
+                Ok(None) => {
+                    pending.clear();
+                    continue;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to read {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            pending.push_str(&appended);
+
+            let cut = complete_prefix(&pending);
+            if cut == 0 {
+                continue;
+            }
+            let complete: String = pending.drain(..cut).collect();
+            let import = recorder
+                .import(parse_auto_explain_log(&complete), &config.connection)
+                .await;
+            if !import.recorded.is_empty() {
+                tracing::info!(
+                    "Recorded {} auto_explain plans from {}",
+                    import.recorded.len(),
+                    path.display()
+                );
+            }
+            for skipped in &import.skipped {
+                tracing::warn!("Skipped an auto_explain entry: {}", skipped.reason);
+            }
+        }
+    }))
+}
+
+/// Read the whole lines appended to `path` since `offset`, advancing it past them
+///
+/// Returns `None`, and resets `offset`, when the file has shrunk.
+async fn read_appended(path: &PathBuf, offset: &mut u64) -> std::io::Result<Option<String>> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let len = file.metadata().await?.len();
+    if len < *offset {
+        *offset = 0;
+        return Ok(None);
+    }
+    file.seek(SeekFrom::Start(*offset)).await?;
+    let mut bytes = Vec::new();
+    file.take(len - *offset).read_to_end(&mut bytes).await?;
+
+    // A line still being written is read on a later poll
+    let Some(end) = bytes.iter().rposition(|&b| b == b'\n') else {
+        return Ok(Some(String::new()));
+    };
+    bytes.truncate(end + 1);
+    *offset += bytes.len() as u64;
+    Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STDERR_LOG: &str = "\
+2024-05-01 12:00:00.100 UTC [4242] LOG:  connection authorized: user=app database=shop
+2024-05-01 12:00:00.123 UTC [4242] LOG:  duration: 1520.331 ms  plan:
+\t{
+\t  \"Query Text\": \"SELECT * FROM orders WHERE status = 'open';\",
+\t  \"Plan\": {
+\t    \"Node Type\": \"Seq Scan\",
+\t    \"Relation Name\": \"orders\",
+\t    \"Startup Cost\": 0.00,
+\t    \"Total Cost\": 1834.00,
+\t    \"Plan Rows\": 500,
+\t    \"Plan Width\": 64,
+\t    \"Actual Startup Time\": 0.012,
+\t    \"Actual Total Time\": 1519.8,
+\t    \"Actual Rows\": 98000,
+\t    \"Actual Loops\": 1,
+\t    \"Filter\": \"(status = 'open'::text)\"
+\t  }
+\t}
+2024-05-01 12:00:01.000 UTC [4243] LOG:  duration: 812.004 ms  statement: VACUUM orders
+2024-05-01 12:00:02.000 UTC [4244] LOG:  duration: 990.500 ms  plan:
+\tQuery Text: SELECT count(*) FROM customers
+\tAggregate  (cost=10.00..10.01 rows=1 width=8)
+";
+
+    #[test]
+    fn test_parse_stderr_log() {
+        let log = parse_auto_explain_log(STDERR_LOG);
+
+        assert_eq!(log.plans.len(), 1);
+        let plan = &log.plans[0];
+        assert_eq!(plan.line, 2);
+        assert_eq!(plan.query, "SELECT * FROM orders WHERE status = 'open'");
+        assert_eq!(
+            plan.fingerprint,
+            fingerprint("SELECT * FROM orders WHERE status = 'closed'")
+        );
+        assert!((plan.duration_ms - 1520.331).abs() < 1e-9);
+        assert_eq!(plan.plan.execution_time, plan.duration_ms);
+        assert_eq!(plan.plan.root.node_type, "Seq Scan");
+        assert_eq!(plan.plan.root.actual_rows, 98000);
+
+        assert_eq!(log.skipped.len(), 1);
+        assert_eq!(log.skipped[0].line, 20);
+        assert!(log.skipped[0].reason.contains("log_format = json"));
+    }
+
+    #[test]
+    fn test_parse_jsonlog() {
+        let record = serde_json::json!({
+            "timestamp": "2024-05-01 12:00:00.123 UTC",
+            "error_severity": "LOG",
+            "message": "duration: 45.500 ms  plan:\n{\"Query Text\": \"SELECT 1\", \
+                        \"Plan\": {\"Node Type\": \"Result\", \"Startup Cost\": 0.0, \
+                        \"Total Cost\": 0.01}}"
+        });
+        let log = format!(
+            "{}\n{{\"message\": \"checkpoint starting: time\"}}\n",
+            record
+        );
+
+        let parsed = parse_auto_explain_log(&log);
+        assert_eq!(parsed.plans.len(), 1);
+        assert_eq!(parsed.plans[0].query, "SELECT 1");
+        assert_eq!(parsed.plans[0].plan.root.node_type, "Result");
+        assert!(parsed.skipped.is_empty());
+    }
+
+    #[test]
+    fn test_complete_prefix() {
+        let log = "LOG:  duration: 1.0 ms  plan:\n\t{}\nLOG:  duration: 2.0 ms  plan:\n\t{\n";
+        let cut = complete_prefix(log);
+        assert_eq!(&log[..cut], "LOG:  duration: 1.0 ms  plan:\n\t{}\n");
+        assert_eq!(complete_prefix("\t{\n"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_import_records_history() {
+        let storage = crate::storage::open(&crate::storage::StorageConfig {
+            url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let recorder = PlanRecorder::new(
+            storage.clone(),
+            SharedAdvisor::default(),
+            MaskingConfig {
+                mask_literals: true,
+            },
+        );
+
+        let import = recorder
+            .import(parse_auto_explain_log(STDERR_LOG), "production")
+            .await;
+        assert_eq!(import.recorded.len(), 1);
+        assert_eq!(import.skipped.len(), 1);
+
+        let entry = storage
+            .get_history(import.recorded[0].history_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.connection.as_deref(), Some("production"));
+        assert_eq!(entry.execution_time_ms, 1520.331);
+        assert!(!entry.query.contains("open"));
+        // Suggestions quoting the filter are stored with its literal masked
+        let analysis = entry.analysis.unwrap().to_string();
+        assert!(analysis.contains("(status = '?'::text)"), "{}", analysis);
+        assert!(!analysis.contains("open"), "{}", analysis);
+    }
+}
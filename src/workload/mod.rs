@@ -0,0 +1,135 @@
+//! Workload analysis
+//!
+//! A workload is the set of statements an application sends, usually far
+//! more than there are distinct queries: the same ORM call runs with
+//! different ids thousands of times. Statements are grouped by fingerprint
+//! (the SQL with literals masked), so each distinct query is explained and
+//! advised on once, and ranked by how often it ran.
+
+pub mod auto_explain;
+pub mod orm_log;
+
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::Statement;
+use sqlparser::dialect::Dialect;
+use sqlparser::parser::Parser;
+
+use crate::advisor::AdvisorAnalysis;
+use crate::storage::findings::fingerprint;
+
+pub use orm_log::{parse_orm_log, LoggedStatement, OrmLogFormat};
+
+/// Statements of a workload sharing one fingerprint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkloadQuery {
+    /// Fingerprint of the SQL with literals masked
+    pub fingerprint: String,
+    /// The first statement seen with this fingerprint
+    pub sql: String,
+    /// Whether `sql` has values for all of its placeholders
+    pub bound: bool,
+    /// Number of statements with this fingerprint
+    pub count: usize,
+}
+
+/// Group statements by fingerprint, most frequent first
+///
+/// A fully bound example is preferred over one with placeholders left, so
+/// the query can be explained. Ties keep the order queries were first seen.
+pub fn group_statements(statements: &[LoggedStatement]) -> Vec<WorkloadQuery> {
+    let mut queries: Vec<WorkloadQuery> = Vec::new();
+    for statement in statements {
+        let key = fingerprint(&statement.sql);
+        match queries.iter_mut().find(|q| q.fingerprint == key) {
+            Some(query) => {
+                query.count += 1;
+                if !query.bound && statement.bound {
+                    query.sql = statement.sql.clone();
+                    query.bound = true;
+                }
+            }
+            None => queries.push(WorkloadQuery {
+                fingerprint: key,
+                sql: statement.sql.clone(),
+                bound: statement.bound,
+                count: 1,
+            }),
+        }
+    }
+    queries.sort_by_key(|q| std::cmp::Reverse(q.count));
+    queries
+}
+
+/// Whether `sql` is a single read-only query that can safely be explained with ANALYZE
+pub fn is_read_only(sql: &str, dialect: &dyn Dialect) -> bool {
+    matches!(
+        Parser::parse_sql(dialect, sql).as_deref(),
+        Ok([Statement::Query(_)])
+    )
+}
+
+/// Analysis of one distinct query of a workload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadEntry {
+    /// The query and how often it ran
+    pub query: WorkloadQuery,
+    /// Advisor output for the query's plan, if it was analyzed
+    pub analysis: Option<AdvisorAnalysis>,
+    /// Execution time of the explained run in milliseconds
+    pub execution_time_ms: Option<f64>,
+    /// Why the query was not analyzed
+    pub error: Option<String>,
+}
+
+/// Distinct queries of a workload with their analyses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadReport {
+    /// Statements found, before grouping
+    pub total_statements: usize,
+    /// Distinct queries, most frequent first
+    pub queries: Vec<WorkloadEntry>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::dialect::PostgreSqlDialect;
+
+    fn statement(sql: &str, bound: bool) -> LoggedStatement {
+        LoggedStatement {
+            sql: sql.to_string(),
+            bound,
+        }
+    }
+
+    #[test]
+    fn test_group_statements() {
+        let queries = group_statements(&[
+            statement("SELECT * FROM users WHERE id = 1", true),
+            statement("SELECT * FROM orders WHERE user_id = $1", false),
+            statement("SELECT * FROM orders WHERE user_id = 7", true),
+            statement("SELECT * FROM orders WHERE user_id = 8", true),
+            statement("SELECT * FROM orders WHERE user_id = 8", true),
+        ]);
+
+        assert_eq!(queries.len(), 3);
+        assert_eq!(queries[0].count, 3);
+        assert_eq!(queries[0].sql, "SELECT * FROM orders WHERE user_id = 7");
+        assert!(queries[0].bound);
+        assert_eq!(queries[1].sql, "SELECT * FROM users WHERE id = 1");
+        assert_eq!(queries[2].sql, "SELECT * FROM orders WHERE user_id = $1");
+        assert!(!queries[2].bound);
+    }
+
+    #[test]
+    fn test_is_read_only() {
+        let dialect = PostgreSqlDialect {};
+        assert!(is_read_only("SELECT 1", &dialect));
+        assert!(is_read_only(
+            "WITH t AS (SELECT 1 AS x) SELECT x FROM t",
+            &dialect
+        ));
+        assert!(!is_read_only("UPDATE users SET name = 'x'", &dialect));
+        assert!(!is_read_only("SELECT 1; DELETE FROM users", &dialect));
+    }
+}
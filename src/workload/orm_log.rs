@@ -0,0 +1,726 @@
+//! Parsers for ORM debug logs
+//!
+//! ORMs log the SQL they send with placeholders and print the bound values
+//! separately, each in its own format:
+//!
+//! - SQLAlchemy (`echo=True` or the `sqlalchemy.engine` logger at INFO) logs
+//!   the statement, then the parameters as a Python dict or tuple.
+//! - ActiveRecord logs one line per statement, `User Load (0.4ms)  SELECT
+//!   ... $1  [["id", 1]]`, often with ANSI colors.
+//! - Hibernate logs the statement through `org.hibernate.SQL` or `show_sql`
+//!   (`Hibernate: select ...`), then one `binding parameter` line per value
+//!   when the binder logger is at TRACE.
+//!
+//! Each parser turns a log into the statements it contains with the values
+//! substituted for the placeholders, so they can be explained as written.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// ORM whose log format to parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrmLogFormat {
+    /// Python SQLAlchemy engine logging
+    SqlAlchemy,
+    /// Ruby on Rails ActiveRecord logging
+    ActiveRecord,
+    /// Java Hibernate SQL and binder logging
+    Hibernate,
+}
+
+/// A statement found in an ORM log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoggedStatement {
+    /// The SQL with bound values substituted for placeholders where known
+    pub sql: String,
+    /// Whether every placeholder was replaced by a value
+    pub bound: bool,
+}
+
+/// Extract the statements from an ORM log, in the order they were logged
+///
+/// Transaction control statements (BEGIN, COMMIT, ...) are left out.
+pub fn parse_orm_log(format: OrmLogFormat, log: &str) -> Vec<LoggedStatement> {
+    let statements = match format {
+        OrmLogFormat::SqlAlchemy => parse_sqlalchemy(log),
+        OrmLogFormat::ActiveRecord => parse_active_record(log),
+        OrmLogFormat::Hibernate => parse_hibernate(log),
+    };
+    statements
+        .into_iter()
+        .filter(|s| !is_transaction_control(&s.sql))
+        .collect()
+}
+
+/// A value bound to a placeholder
+#[derive(Debug, Clone, PartialEq)]
+enum BindValue {
+    Null,
+    Bool(bool),
+    Number(String),
+    Text(String),
+    /// A value printed in a form that cannot be turned into a SQL literal
+    Unknown,
+}
+
+impl BindValue {
+    fn to_sql(&self) -> Option<String> {
+        match self {
+            BindValue::Null => Some("NULL".to_string()),
+            BindValue::Bool(b) => Some(if *b { "TRUE" } else { "FALSE" }.to_string()),
+            BindValue::Number(n) => Some(n.clone()),
+            BindValue::Text(s) => Some(format!("'{}'", s.replace('\'', "''"))),
+            BindValue::Unknown => None,
+        }
+    }
+}
+
+/// Values bound to a statement, by position or by name
+#[derive(Debug, Clone, PartialEq)]
+enum Binds {
+    Positional(Vec<BindValue>),
+    Named(HashMap<String, BindValue>),
+}
+
+/// A literal as printed by Python's `repr` or Ruby's `inspect`
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Scalar(BindValue),
+    Seq(Vec<Literal>),
+    Map(Vec<(String, Literal)>),
+}
+
+impl Literal {
+    fn into_value(self) -> BindValue {
+        match self {
+            Literal::Scalar(value) => value,
+            Literal::Seq(_) | Literal::Map(_) => BindValue::Unknown,
+        }
+    }
+}
+
+/// Parse a whole Python or Ruby literal; `None` unless all of `text` is consumed
+fn parse_literal(text: &str) -> Option<Literal> {
+    let mut parser = LiteralParser {
+        chars: text.trim().chars().collect(),
+        pos: 0,
+    };
+    let literal = parser.literal()?;
+    parser.skip_whitespace();
+    (parser.pos == parser.chars.len()).then_some(literal)
+}
+
+struct LiteralParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl LiteralParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn literal(&mut self) -> Option<Literal> {
+        self.skip_whitespace();
+        match self.peek()? {
+            '[' => self.seq(']').map(Literal::Seq),
+            '(' => self.seq(')').map(Literal::Seq),
+            '{' => self.map(),
+            '\'' | '"' => self.string().map(|s| Literal::Scalar(BindValue::Text(s))),
+            _ => self.word().map(Literal::Scalar),
+        }
+    }
+
+    fn seq(&mut self, close: char) -> Option<Vec<Literal>> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek()? == close {
+                self.pos += 1;
+                return Some(items);
+            }
+            items.push(self.literal()?);
+            self.skip_whitespace();
+            match self.peek()? {
+                ',' => self.pos += 1,
+                c if c == close => {}
+                _ => return None,
+            }
+        }
+    }
+
+    fn map(&mut self) -> Option<Literal> {
+        self.pos += 1;
+        let mut entries = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek()? == '}' {
+                self.pos += 1;
+                return Some(Literal::Map(entries));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if self.peek()? == ':' {
+                self.pos += 1;
+            } else if self.chars[self.pos..].starts_with(&['=', '>']) {
+                self.pos += 2;
+            } else {
+                return None;
+            }
+            entries.push((key, self.literal()?));
+            self.skip_whitespace();
+            match self.peek()? {
+                ',' => self.pos += 1,
+                '}' => {}
+                _ => return None,
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        let quote = self.peek().filter(|c| *c == '\'' || *c == '"')?;
+        self.pos += 1;
+        let mut value = String::new();
+        loop {
+            let c = self.peek()?;
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    let escaped = self.peek()?;
+                    self.pos += 1;
+                    value.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        other => other,
+                    });
+                }
+                c if c == quote => return Some(value),
+                c => value.push(c),
+            }
+        }
+    }
+
+    /// A number, keyword, or an unparseable constructor call such as `datetime.date(2024, 1, 1)`
+    fn word(&mut self) -> Option<BindValue> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '+' | ':'))
+        {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return None;
+        }
+        let word: String = self.chars[start..self.pos].iter().collect();
+
+        if self.peek() == Some('(') {
+            self.seq(')')?;
+            return Some(BindValue::Unknown);
+        }
+        Some(match word.as_str() {
+            "None" | "nil" | "null" | "NULL" => BindValue::Null,
+            "True" | "true" => BindValue::Bool(true),
+            "False" | "false" => BindValue::Bool(false),
+            _ if word.parse::<f64>().is_ok() => BindValue::Number(word),
+            _ => BindValue::Unknown,
+        })
+    }
+}
+
+/// Replace the placeholders in `sql` with bound values
+///
+/// Understands `?`, `%s` and `$1` placeholders for positional values and
+/// `%(name)s` and `:name` for named ones. Placeholders inside string
+/// literals are left alone. Returns the SQL and whether every placeholder
+/// was replaced.
+fn bind(sql: &str, binds: &Binds) -> (String, bool) {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut out = String::with_capacity(sql.len());
+    let mut complete = true;
+    let mut next_positional = 0;
+    let mut i = 0;
+
+    let mut push_value =
+        |out: &mut String, value: Option<&BindValue>, placeholder: &str| match value
+            .and_then(BindValue::to_sql)
+        {
+            Some(literal) => out.push_str(&literal),
+            None => {
+                complete = false;
+                out.push_str(placeholder);
+            }
+        };
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\'' | '"' | '`' => {
+                // Copy quoted strings and identifiers verbatim
+                let end = (i + 1..chars.len())
+                    .find(|&j| chars[j] == c)
+                    .unwrap_or(chars.len() - 1);
+                out.extend(&chars[i..=end]);
+                i = end + 1;
+            }
+            '?' => {
+                let value = match binds {
+                    Binds::Positional(values) => values.get(next_positional),
+                    Binds::Named(_) => None,
+                };
+                next_positional += 1;
+                push_value(&mut out, value, "?");
+                i += 1;
+            }
+            '$' if chars.get(i + 1).is_some_and(char::is_ascii_digit) => {
+                let end = (i + 1..chars.len())
+                    .find(|&j| !chars[j].is_ascii_digit())
+                    .unwrap_or(chars.len());
+                let placeholder: String = chars[i..end].iter().collect();
+                let index: usize = placeholder[1..].parse().unwrap_or(0);
+                let value = match binds {
+                    Binds::Positional(values) => index.checked_sub(1).and_then(|n| values.get(n)),
+                    Binds::Named(_) => None,
+                };
+                push_value(&mut out, value, &placeholder);
+                i = end;
+            }
+            '%' if chars.get(i + 1) == Some(&'%') => {
+                out.push('%');
+                i += 2;
+            }
+            '%' if chars.get(i + 1) == Some(&'s') => {
+                let value = match binds {
+                    Binds::Positional(values) => values.get(next_positional),
+                    Binds::Named(_) => None,
+                };
+                next_positional += 1;
+                push_value(&mut out, value, "%s");
+                i += 2;
+            }
+            '%' if chars.get(i + 1) == Some(&'(') => {
+                let Some(close) = (i + 2..chars.len()).find(|&j| chars[j] == ')') else {
+                    out.push(c);
+                    i += 1;
+                    continue;
+                };
+                if chars.get(close + 1) != Some(&'s') {
+                    out.push(c);
+                    i += 1;
+                    continue;
+                }
+                let name: String = chars[i + 2..close].iter().collect();
+                let placeholder: String = chars[i..close + 2].iter().collect();
+                let value = match binds {
+                    Binds::Named(values) => values.get(&name),
+                    Binds::Positional(_) => None,
+                };
+                push_value(&mut out, value, &placeholder);
+                i = close + 2;
+            }
+            ':' if matches!(binds, Binds::Named(_))
+                && i.checked_sub(1).is_none_or(|p| chars[p] != ':')
+                && chars
+                    .get(i + 1)
+                    .is_some_and(|n| n.is_alphabetic() || *n == '_') =>
+            {
+                let end = (i + 1..chars.len())
+                    .find(|&j| !(chars[j].is_alphanumeric() || chars[j] == '_'))
+                    .unwrap_or(chars.len());
+                let name: String = chars[i + 1..end].iter().collect();
+                let value = match binds {
+                    Binds::Named(values) => values.get(&name),
+                    Binds::Positional(_) => None,
+                };
+                push_value(&mut out, value, &format!(":{}", name));
+                i = end;
+            }
+            _ => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    (out, complete)
+}
+
+fn bound_statement(sql: &str, binds: &Binds) -> LoggedStatement {
+    let (sql, bound) = bind(sql.trim(), binds);
+    LoggedStatement { sql, bound }
+}
+
+fn is_transaction_control(sql: &str) -> bool {
+    let keyword = sql
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(';')
+        .to_ascii_uppercase();
+    sql.trim().is_empty()
+        || matches!(
+            keyword.as_str(),
+            "BEGIN" | "COMMIT" | "ROLLBACK" | "SAVEPOINT" | "RELEASE" | "START" | "END"
+        )
+}
+
+/// Parse SQLAlchemy engine logging
+fn parse_sqlalchemy(log: &str) -> Vec<LoggedStatement> {
+    let mut statements = Vec::new();
+    // The statement logged last, until its parameters arrive
+    let mut pending: Option<String> = None;
+    let no_binds = Binds::Positional(Vec::new());
+
+    for line in log.lines() {
+        let Some(message) = sqlalchemy_message(line) else {
+            // SQL spanning several lines continues without a log prefix
+            if let Some(sql) = pending.as_mut() {
+                sql.push('\n');
+                sql.push_str(line);
+            }
+            continue;
+        };
+
+        if let Some(sql) = pending.as_deref() {
+            if let Some(binds) = sqlalchemy_params(message) {
+                statements.push(bound_statement(sql, &binds));
+                pending = None;
+                continue;
+            }
+            statements.push(bound_statement(sql, &no_binds));
+        }
+        pending = Some(message.to_string());
+    }
+    if let Some(sql) = pending {
+        statements.push(bound_statement(&sql, &no_binds));
+    }
+    statements
+}
+
+/// The message of a line logged by `sqlalchemy.engine`
+fn sqlalchemy_message(line: &str) -> Option<&str> {
+    let start = line.find("sqlalchemy.engine")?;
+    let rest = &line[start..];
+    let logger_end = rest.find([' ', ':']).unwrap_or(rest.len());
+    Some(rest[logger_end..].trim_start_matches([' ', ':']).trim_end())
+}
+
+/// Parameters logged after a statement, e.g. `[generated in 0.0001s] {'id_1': 5}`
+fn sqlalchemy_params(message: &str) -> Option<Binds> {
+    let mut message = message.trim();
+    // SQLAlchemy 1.4+ prefixes the parameters with how the statement was compiled
+    if message.starts_with('[') && message[1..].starts_with(|c: char| c.is_ascii_lowercase()) {
+        message = message[message.find(']')? + 1..].trim_start();
+    }
+
+    match parse_literal(message)? {
+        Literal::Map(entries) => Some(Binds::Named(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k, v.into_value()))
+                .collect(),
+        )),
+        Literal::Seq(items) => {
+            // executemany logs a list of parameter sets; the first is representative
+            let items = match items.first() {
+                Some(Literal::Seq(first)) => first.clone(),
+                Some(Literal::Map(first)) => {
+                    return Some(Binds::Named(
+                        first
+                            .iter()
+                            .map(|(k, v)| (k.clone(), v.clone().into_value()))
+                            .collect(),
+                    ))
+                }
+                _ => items,
+            };
+            Some(Binds::Positional(
+                items.into_iter().map(Literal::into_value).collect(),
+            ))
+        }
+        Literal::Scalar(_) => None,
+    }
+}
+
+/// Parse ActiveRecord logging
+fn parse_active_record(log: &str) -> Vec<LoggedStatement> {
+    log.lines()
+        .filter_map(|line| {
+            let line = strip_ansi(line);
+            let rest = after_duration(&line)?;
+            let (sql, binds) = match rest.rfind("  [[") {
+                Some(split) if rest.ends_with("]]") => {
+                    (&rest[..split], active_record_binds(&rest[split..])?)
+                }
+                _ => (rest, Binds::Positional(Vec::new())),
+            };
+            Some(bound_statement(sql, &binds))
+        })
+        .collect()
+}
+
+/// Remove ANSI color escapes
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip to the final byte of the escape sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// The text after an ActiveRecord `Name (0.4ms)` prefix
+fn after_duration(line: &str) -> Option<&str> {
+    let mut search = 0;
+    while let Some(open) = line[search..].find('(').map(|i| i + search) {
+        let close = line[open..].find(')').map(|i| i + open)?;
+        let duration = &line[open + 1..close];
+        if duration
+            .strip_suffix("ms")
+            .is_some_and(|ms| ms.parse::<f64>().is_ok())
+        {
+            return Some(line[close + 1..].trim());
+        }
+        search = open + 1;
+    }
+    None
+}
+
+/// `[["id", 1], ["LIMIT", 1]]` as positional values
+fn active_record_binds(text: &str) -> Option<Binds> {
+    let Literal::Seq(pairs) = parse_literal(text)? else {
+        return None;
+    };
+    let values = pairs
+        .into_iter()
+        .map(|pair| match pair {
+            Literal::Seq(mut items) if items.len() == 2 => items.pop().map(Literal::into_value),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Binds::Positional(values))
+}
+
+/// Parse Hibernate SQL and binder logging
+fn parse_hibernate(log: &str) -> Vec<LoggedStatement> {
+    let mut statements = Vec::new();
+    let mut pending: Option<(String, Vec<BindValue>)> = None;
+
+    let mut flush = |pending: &mut Option<(String, Vec<BindValue>)>| {
+        if let Some((sql, values)) = pending.take() {
+            statements.push(bound_statement(&sql, &Binds::Positional(values)));
+        }
+    };
+
+    for line in log.lines() {
+        if let Some(sql) = hibernate_sql(line) {
+            flush(&mut pending);
+            pending = Some((sql.to_string(), Vec::new()));
+        } else if let Some((index, value)) = hibernate_binding(line) {
+            if let Some((_, values)) = pending.as_mut() {
+                if values.len() < index {
+                    values.resize(index, BindValue::Unknown);
+                }
+                values[index - 1] = value;
+            }
+        } else if line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+            // format_sql prints the statement over several indented lines
+            if let Some((sql, values)) = pending.as_mut() {
+                if values.is_empty() {
+                    sql.push('\n');
+                    sql.push_str(line);
+                }
+            }
+        }
+    }
+    flush(&mut pending);
+    statements
+}
+
+/// The statement of a `Hibernate: ...` or `org.hibernate.SQL` line
+fn hibernate_sql(line: &str) -> Option<&str> {
+    if let Some(start) = line.find("Hibernate:") {
+        return Some(line[start + "Hibernate:".len()..].trim());
+    }
+    let start = line.find("org.hibernate.SQL")?;
+    let rest = line[start + "org.hibernate.SQL".len()..].trim_start();
+    let rest = rest
+        .strip_prefix(':')
+        .or_else(|| rest.strip_prefix('-'))
+        .unwrap_or(rest);
+    Some(rest.trim())
+}
+
+/// A `binding parameter` line as its 1-based index and value
+///
+/// Hibernate 5 logs `binding parameter [1] as [BIGINT] - [5]`, Hibernate 6
+/// `binding parameter (1:BIGINT) <- [5]`.
+fn hibernate_binding(line: &str) -> Option<(usize, BindValue)> {
+    let rest = &line[line.find("binding parameter")? + "binding parameter".len()..];
+    let rest = rest.trim_start();
+
+    let (index, sql_type, value) = if let Some(rest) = rest.strip_prefix('[') {
+        let (index, rest) = rest.split_once(']')?;
+        let rest = rest.trim_start().strip_prefix("as [")?;
+        let (sql_type, rest) = rest.split_once(']')?;
+        let value = rest.trim_start().strip_prefix("- [")?;
+        (index, sql_type, value)
+    } else {
+        let rest = rest.strip_prefix('(')?;
+        let (position, rest) = rest.split_once(')')?;
+        let (index, sql_type) = position.split_once(':').unwrap_or((position, ""));
+        let value = rest.trim_start().strip_prefix("<- [")?;
+        (index, sql_type, value)
+    };
+    let index: usize = index.trim().parse().ok().filter(|i| *i > 0)?;
+    let value = value.trim_end().strip_suffix(']')?;
+
+    let value = match sql_type.trim().to_ascii_uppercase().as_str() {
+        _ if value == "null" || value == "<null>" => BindValue::Null,
+        "BIGINT" | "INTEGER" | "SMALLINT" | "TINYINT" | "NUMERIC" | "DECIMAL" | "DOUBLE"
+        | "FLOAT" | "REAL"
+            if value.parse::<f64>().is_ok() =>
+        {
+            BindValue::Number(value.to_string())
+        }
+        "BOOLEAN" | "BIT" => match value {
+            "true" => BindValue::Bool(true),
+            "false" => BindValue::Bool(false),
+            _ => BindValue::Unknown,
+        },
+        _ => BindValue::Text(value.to_string()),
+    };
+    Some((index, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sqls(statements: &[LoggedStatement]) -> Vec<&str> {
+        statements.iter().map(|s| s.sql.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_sqlalchemy() {
+        let log = "\
+2024-05-01 10:00:00,001 INFO sqlalchemy.engine.Engine BEGIN (implicit)
+2024-05-01 10:00:00,002 INFO sqlalchemy.engine.Engine SELECT users.id, users.name
+FROM users
+WHERE users.email = %(email_1)s AND users.active = %(active_1)s
+2024-05-01 10:00:00,002 INFO sqlalchemy.engine.Engine [generated in 0.00015s] {'email_1': \"o'brien@example.com\", 'active_1': True}
+INFO:sqlalchemy.engine.Engine:SELECT orders.id FROM orders WHERE orders.user_id = ? LIMIT ? OFFSET ?
+INFO:sqlalchemy.engine.Engine:[cached since 12.5s ago] (42, 10, 0)
+2024-05-01 10:00:00,003 INFO sqlalchemy.engine.Engine INSERT INTO events (kind, at) VALUES (%s, %s)
+2024-05-01 10:00:00,003 INFO sqlalchemy.engine.Engine [generated in 0.0002s] [('login', datetime.datetime(2024, 5, 1, 10, 0)), ('logout', None)]
+2024-05-01 10:00:00,004 INFO sqlalchemy.engine.Engine COMMIT
+";
+        let statements = parse_orm_log(OrmLogFormat::SqlAlchemy, log);
+        assert_eq!(
+            sqls(&statements),
+            [
+                "SELECT users.id, users.name\nFROM users\nWHERE users.email = 'o''brien@example.com' AND users.active = TRUE",
+                "SELECT orders.id FROM orders WHERE orders.user_id = 42 LIMIT 10 OFFSET 0",
+                "INSERT INTO events (kind, at) VALUES ('login', %s)",
+            ]
+        );
+        assert!(statements[0].bound && statements[1].bound);
+        assert!(!statements[2].bound);
+    }
+
+    #[test]
+    fn test_parse_active_record() {
+        let log = "\
+Started GET \"/users/1\" for 127.0.0.1 at 2024-05-01 10:00:00 +0000
+  \u{1b}[1m\u{1b}[36mUser Load (0.4ms)\u{1b}[0m  \u{1b}[1m\u{1b}[34mSELECT \"users\".* FROM \"users\" WHERE \"users\".\"id\" = $1 LIMIT $2\u{1b}[0m  [[\"id\", 1], [\"LIMIT\", 1]]
+  \u{1b}[1m\u{1b}[35mTRANSACTION (0.1ms)\u{1b}[0m  \u{1b}[1m\u{1b}[35mBEGIN\u{1b}[0m
+  Post Count (1.2ms)  SELECT COUNT(*) FROM `posts` WHERE `posts`.`title` = ? AND `posts`.`deleted_at` IS NULL  [[\"title\", \"It's here\"]]
+  ↳ app/controllers/users_controller.rb:5:in `show'
+  CACHE Post Count (0.0ms)  SELECT COUNT(*) FROM `posts`
+";
+        let statements = parse_orm_log(OrmLogFormat::ActiveRecord, log);
+        assert_eq!(
+            sqls(&statements),
+            [
+                "SELECT \"users\".* FROM \"users\" WHERE \"users\".\"id\" = 1 LIMIT 1",
+                "SELECT COUNT(*) FROM `posts` WHERE `posts`.`title` = 'It''s here' AND `posts`.`deleted_at` IS NULL",
+                "SELECT COUNT(*) FROM `posts`",
+            ]
+        );
+        assert!(statements.iter().all(|s| s.bound));
+    }
+
+    #[test]
+    fn test_parse_hibernate() {
+        let log = "\
+2024-05-01 10:00:00.001 DEBUG 4242 --- [main] org.hibernate.SQL : select u1_0.id,u1_0.name from users u1_0 where u1_0.email=? and u1_0.id>?
+2024-05-01 10:00:00.002 TRACE 4242 --- [main] o.h.type.descriptor.sql.BasicBinder : binding parameter [1] as [VARCHAR] - [bob@example.com]
+2024-05-01 10:00:00.002 TRACE 4242 --- [main] o.h.type.descriptor.sql.BasicBinder : binding parameter [2] as [BIGINT] - [10]
+Hibernate:
+    select
+        o1_0.id
+    from
+        orders o1_0
+    where
+        o1_0.user_id=?
+10:00:00.003 [main] TRACE org.hibernate.orm.jdbc.bind - binding parameter (1:BIGINT) <- [7]
+Hibernate: update users set name=? where id=?
+";
+        let statements = parse_orm_log(OrmLogFormat::Hibernate, log);
+        assert_eq!(
+            statements[0].sql,
+            "select u1_0.id,u1_0.name from users u1_0 where u1_0.email='bob@example.com' and u1_0.id>10"
+        );
+        assert_eq!(
+            statements[1]
+                .sql
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+            "select o1_0.id from orders o1_0 where o1_0.user_id=7"
+        );
+        assert_eq!(statements[2].sql, "update users set name=? where id=?");
+        assert!(statements[0].bound && statements[1].bound && !statements[2].bound);
+    }
+
+    #[test]
+    fn test_bind_skips_strings_and_casts() {
+        let named = Binds::Named(HashMap::from([(
+            "id".to_string(),
+            BindValue::Number("3".to_string()),
+        )]));
+        assert_eq!(
+            bind("SELECT ':id', x::text FROM t WHERE id = :id", &named),
+            (
+                "SELECT ':id', x::text FROM t WHERE id = 3".to_string(),
+                true
+            )
+        );
+        assert_eq!(
+            bind(
+                "SELECT '?' WHERE a = ? AND b = ?",
+                &Binds::Positional(vec![BindValue::Null])
+            ),
+            ("SELECT '?' WHERE a = NULL AND b = ?".to_string(), false)
+        );
+    }
+}
@@ -2,6 +2,7 @@ class SQLTraceApp {
     constructor() {
         this.queryInput = document.getElementById('queryInput');
         this.executeBtn = document.getElementById('executeBtn');
+        this.cancelBtn = document.getElementById('cancelBtn');
         this.errorContainer = document.getElementById('errorContainer');
         this.errorText = document.getElementById('errorText');
         this.planContainer = document.getElementById('planContainer');
@@ -18,7 +19,9 @@ class SQLTraceApp {
         this.comparisonResults = document.getElementById('comparisonResults');
         
         this.currentPlanData = null;
+        this.runningRequestId = null;
         this.currentAdvisorAnalysis = null;
+        this.settings = null;
         this.queryHistory = this.loadHistoryFromStorage();
         this.comparisonMode = false;
         this.selectedQueries = [];
@@ -28,6 +31,7 @@ class SQLTraceApp {
 
     init() {
         this.executeBtn.addEventListener('click', () => this.executeQuery());
+        this.cancelBtn.addEventListener('click', () => this.cancelQuery());
         this.queryInput.addEventListener('keydown', (e) => {
             if (e.ctrlKey && e.key === 'Enter') {
                 this.executeQuery();
@@ -50,6 +54,11 @@ class SQLTraceApp {
         this.clearHistoryBtn.addEventListener('click', () => this.clearHistory());
         this.toggleComparisonBtn.addEventListener('click', () => this.toggleComparison());
         this.exitComparisonBtn.addEventListener('click', () => this.exitComparison());
+        ['warmup-runs', 'benchmark-runs', 'timeout-seconds'].forEach(id => {
+            const input = document.getElementById(id);
+            if (input) input.addEventListener('change', () => this.saveSettings());
+        });
+        this.loadSettings();
         this.initializeTheme();
         this.renderHistory();
     }
@@ -64,20 +73,33 @@ class SQLTraceApp {
 
         this.setLoading(true);
         this.hideError();
-
         try {
+            const requestId = await this.newRequestId();
+            this.runningRequestId = requestId;
+            this.cancelBtn.disabled = false;
+            this.cancelBtn.style.display = 'inline-block';
+
             const response = await fetch('/api/explain', {
                 method: 'POST',
                 headers: {
                     'Content-Type': 'application/json',
                 },
-                body: JSON.stringify({ query })
+                body: JSON.stringify({ query, request_id: requestId })
             });
 
             const data = await response.json();
 
-            if (data.error) {
-                this.showError(data.error);
+            if (data.cancelled) {
+                this.showError('Query cancelled');
+                this.showEmptyState();
+            } else if (data.error) {
+                if (data.error_location) {
+                    const { line, column } = data.error_location;
+                    this.showError(`${data.error} (line ${line}, column ${column})`);
+                    this.highlightErrorLocation(query, data.error_location);
+                } else {
+                    this.showError(data.error);
+                }
                 this.showEmptyState();
             } else {
                 this.renderPlan(data.plan);
@@ -91,11 +113,41 @@ class SQLTraceApp {
             console.error('Error executing query:', error);
             this.showError('Failed to execute query. Please check your SQL syntax and try again.');
         } finally {
+            this.runningRequestId = null;
+            this.cancelBtn.style.display = 'none';
             this.executeBtn.disabled = false;
             this.executeBtn.textContent = 'Analyze Query';
         }
     }
 
+    // Ids are issued by the server, so only this page can cancel its analysis
+    async newRequestId() {
+        const response = await fetch('/api/explain/requests', { method: 'POST' });
+        const data = await response.json();
+        return data.request_id;
+    }
+
+    async cancelQuery() {
+        const requestId = this.runningRequestId;
+        if (!requestId) return;
+
+        this.cancelBtn.disabled = true;
+        try {
+            const response = await fetch(`/api/explain/${encodeURIComponent(requestId)}/cancel`, {
+                method: 'POST'
+            });
+            // 404 means the request finished in the meantime
+            if (response.ok) {
+                const data = await response.json();
+                if (data.error) {
+                    this.showError(data.error);
+                }
+            }
+        } catch (error) {
+            console.error('Error cancelling query:', error);
+        }
+    }
+
     setLoading(loading) {
         this.executeBtn.disabled = loading;
         this.executeBtn.querySelector('.btn-text').textContent = loading ? 'Analyzing...' : 'Analyze Query';
@@ -118,6 +170,18 @@ class SQLTraceApp {
         this.errorContainer.style.display = 'none';
     }
 
+    highlightErrorLocation(query, location) {
+        // Offsets count characters of the trimmed query; the textarea counts UTF-16 units
+        const value = this.queryInput.value;
+        const leading = value.length - value.trimStart().length;
+        const chars = Array.from(query);
+        const start = leading + chars.slice(0, location.offset).join('').length;
+        const end = start + chars.slice(location.offset, location.offset + location.length).join('').length;
+
+        this.queryInput.focus();
+        this.queryInput.setSelectionRange(start, end);
+    }
+
     showEmptyState() {
         this.planContainer.innerHTML = `
             <div class="empty-state">
@@ -217,20 +281,86 @@ class SQLTraceApp {
             details.push(`<span class="plan-node-rows">Actual Rows: ${planNode.actual_rows}</span>`);
         }
 
-        if (planNode.extra && typeof planNode.extra === 'object') {
-            if (planNode.extra['Index Cond']) {
-                details.push(`Index Cond: ${planNode.extra['Index Cond']}`);
-            }
-            
-            if (planNode.extra['Filter']) {
-                details.push(`Filter: ${planNode.extra['Filter']}`);
-            }
-            
-            if (planNode.extra['Hash Cond']) {
-                details.push(`Hash Cond: ${planNode.extra['Hash Cond']}`);
+        if (planNode.actual_loops > 1) {
+            details.push(`<span class="plan-node-loops">Loops: ${planNode.actual_loops} (Total Rows: ${planNode.total_rows}, Total Time: ${planNode.total_time.toFixed(3)}ms)</span>`);
+        }
+
+        if (planNode.disk_usage_kb) {
+            details.push(`<span class="plan-node-spill">Spilled: ${planNode.disk_usage_kb} kB on disk, ${planNode.hash_agg_batches || 1} batches</span>`);
+        }
+
+        if (planNode.hash_batches > 1) {
+            const planned = planNode.original_hash_batches && planNode.original_hash_batches !== planNode.hash_batches
+                ? ` (planned ${planNode.original_hash_batches})` : '';
+            details.push(`<span class="plan-node-spill">Hash spilled: ${planNode.hash_batches} batches${planned}, ${planNode.hash_buckets} buckets</span>`);
+        } else if (planNode.hash_buckets !== undefined) {
+            details.push(`<span class="plan-node-hash">Hash: ${planNode.hash_buckets} buckets, 1 batch</span>`);
+        }
+
+        if (planNode.sort_space_type === 'Disk' || planNode.sort_method?.startsWith('external')) {
+            const method = planNode.sort_method ? ` (${planNode.sort_method})` : '';
+            details.push(`<span class="plan-node-spill">Sort spilled: ${planNode.sort_space_used_kb} kB on disk${method}</span>`);
+        } else if (planNode.sort_space_type === 'Memory') {
+            const method = planNode.sort_method ? `${planNode.sort_method}, ` : '';
+            details.push(`<span class="plan-node-sort">Sort: ${method}${planNode.sort_space_used_kb} kB in memory</span>`);
+        }
+
+        if (planNode.presorted_key) {
+            const groups = (planNode.full_sort_groups?.group_count || 0) + (planNode.pre_sorted_groups?.group_count || 0);
+            details.push(`<span class="plan-node-presorted">Presorted: ${planNode.presorted_key.join(', ')}${groups ? ` (${groups} groups)` : ''}</span>`);
+        }
+
+        if (planNode.memory_used_kb) {
+            details.push(`<span class="plan-node-memory">Memory: ${planNode.memory_used_kb} kB</span>`);
+        }
+
+        if (planNode.cache_hits !== undefined) {
+            const hitRatio = planNode.cache_hit_ratio !== undefined ? ` (${(planNode.cache_hit_ratio * 100).toFixed(1)}% hit)` : '';
+            details.push(`<span class="plan-node-cache">Cache: ${planNode.cache_hits} hits, ${planNode.cache_misses || 0} misses${hitRatio}, ${planNode.cache_evictions || 0} evictions</span>`);
+        }
+
+        if (planNode.workers_planned !== undefined) {
+            if (planNode.workers_launched === undefined) {
+                details.push(`<span class="plan-node-workers">Workers planned: ${planNode.workers_planned}</span>`);
+            } else {
+                const workersClass = planNode.workers_not_launched ? 'plan-node-workers-short' : 'plan-node-workers';
+                details.push(`<span class="${workersClass}">Workers: ${planNode.workers_launched} of ${planNode.workers_planned} launched</span>`);
             }
         }
 
+        if (planNode.workers && planNode.workers.some(worker => worker['Actual Rows'] !== undefined)) {
+            const perWorker = planNode.workers.map(worker => {
+                const time = worker['Actual Total Time'] !== undefined ? `, ${worker['Actual Total Time'].toFixed(3)}ms` : '';
+                return `#${worker['Worker Number']}: ${worker['Actual Rows'] ?? 0} rows${time}`;
+            });
+            details.push(`<span class="plan-node-workers">Per worker: ${perWorker.join('; ')}</span>`);
+        }
+
+        if (planNode.foreign_relations) {
+            details.push(`<span class="plan-node-remote">Remote join: ${escapeHtml(planNode.foreign_relations)}</span>`);
+        }
+
+        if (planNode.remote_sql) {
+            details.push(`<span class="plan-node-remote">Remote SQL: ${escapeHtml(planNode.remote_sql)}</span>`);
+        }
+
+        const removed = (rows) => rows !== undefined ? ` (removed ${rows} rows)` : '';
+        if (planNode.index_cond) {
+            details.push(`<span class="plan-node-cond">Index Cond: ${escapeHtml(planNode.index_cond)}</span>`);
+        }
+
+        if (planNode.filter) {
+            details.push(`<span class="plan-node-cond">Filter: ${escapeHtml(planNode.filter)}${removed(planNode.rows_removed_by_filter)}</span>`);
+        }
+
+        if (planNode.join_filter) {
+            details.push(`<span class="plan-node-cond">Join Filter: ${escapeHtml(planNode.join_filter)}${removed(planNode.rows_removed_by_join_filter)}</span>`);
+        }
+
+        if (planNode.extra && typeof planNode.extra === 'object' && planNode.extra['Hash Cond']) {
+            details.push(`<span class="plan-node-cond">Hash Cond: ${escapeHtml(planNode.extra['Hash Cond'])}</span>`);
+        }
+
         const detailsHtml = details.length > 0 
             ? `<div class="plan-node-details">${details.join(' • ')}</div>`
             : '';
@@ -301,6 +431,10 @@ class SQLTraceApp {
         if (node.actual_rows !== undefined) {
             output += ` | Rows: ${node.actual_rows}`;
         }
+
+        if (node.actual_loops > 1) {
+            output += ` | Loops: ${node.actual_loops} (Total Rows: ${node.total_rows}, Total Time: ${node.total_time?.toFixed(3) || 0}ms)`;
+        }
         
         output += '\n';
         
@@ -448,6 +582,10 @@ class SQLTraceApp {
         if (node.actual_rows !== undefined) {
             output += ` (Rows: ${node.actual_rows})`;
         }
+
+        if (node.actual_loops > 1) {
+            output += ` (Loops: ${node.actual_loops}, Total Rows: ${node.total_rows}, Total Time: ${node.total_time?.toFixed(3) || 0}ms)`;
+        }
         
         output += '\n';
         
@@ -474,6 +612,7 @@ class SQLTraceApp {
                     Score: ${analysis.performance_score}/100
                 </div>
                 <p>${analysis.summary.potential_improvement}</p>
+                ${analysis.summary.peak_memory_kb ? `<p>Estimated peak memory: ${analysis.summary.peak_memory_kb} kB</p>` : ''}
             </div>
             <div class="suggestions-list">
         `;
@@ -548,6 +687,59 @@ class SQLTraceApp {
         }
     }
 
+    async loadSettings() {
+        try {
+            const response = await fetch('/api/settings');
+            const data = await response.json();
+            if (data.settings) {
+                this.applySettings(data.settings);
+            }
+        } catch (error) {
+            console.error('Error loading settings:', error);
+        }
+    }
+
+    applySettings(settings) {
+        this.settings = settings;
+        const benchmark = settings.benchmark || {};
+        const inputs = {
+            'warmup-runs': benchmark.warmup_runs,
+            'benchmark-runs': benchmark.benchmark_runs,
+            'timeout-seconds': benchmark.timeout_seconds
+        };
+        Object.entries(inputs).forEach(([id, value]) => {
+            const input = document.getElementById(id);
+            if (input && value !== undefined) input.value = value;
+        });
+    }
+
+    async saveSettings() {
+        const settings = this.settings || {};
+        const readInt = (id, fallback) => parseInt(document.getElementById(id)?.value) || fallback;
+        settings.benchmark = {
+            ...(settings.benchmark || {}),
+            warmup_runs: readInt('warmup-runs', 2),
+            benchmark_runs: readInt('benchmark-runs', 5),
+            timeout_seconds: readInt('timeout-seconds', 30)
+        };
+
+        try {
+            const response = await fetch('/api/settings', {
+                method: 'PUT',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(settings)
+            });
+            const data = await response.json();
+            if (data.error) {
+                console.error('Error saving settings:', data.error);
+            } else if (data.settings) {
+                this.settings = data.settings;
+            }
+        } catch (error) {
+            console.error('Error saving settings:', error);
+        }
+    }
+
     calculatePerformanceMetrics(planData) {
         if (!planData || !planData.nodes) return null;
         
@@ -558,8 +750,8 @@ class SQLTraceApp {
         
         planData.nodes.forEach(node => {
             if (node.total_cost) totalCost = Math.max(totalCost, node.total_cost);
-            if (node.actual_total_time) totalTime += node.actual_total_time;
-            if (node.actual_rows) totalRows += node.actual_rows;
+            if (node.total_time) totalTime += node.total_time;
+            if (node.total_rows) totalRows += node.total_rows;
             nodeCount++;
         });
         
@@ -575,8 +767,9 @@ class SQLTraceApp {
         const metrics = this.calculatePerformanceMetrics(planData);
         if (!metrics) return '';
         
-        const costClass = metrics.totalCost > 1000 ? 'cost-high' : 
-                         metrics.totalCost > 100 ? 'cost-medium' : 'cost-low';
+        const hotspots = this.settings?.hotspots || { cost_medium: 100, cost_high: 1000 };
+        const costClass = metrics.totalCost > hotspots.cost_high ? 'cost-high' : 
+                         metrics.totalCost > hotspots.cost_medium ? 'cost-medium' : 'cost-low';
         
         return `
             <div class="performance-summary">
@@ -596,10 +789,24 @@ class SQLTraceApp {
                     <div class="metric-value">${metrics.nodeCount}</div>
                     <div class="metric-label">Plan Nodes</div>
                 </div>
+                ${this.renderJitMetric(planData)}
             </div>
         `;
     }
 
+    renderJitMetric(planData) {
+        const timing = planData.jit?.Timing;
+        if (!timing) return '';
+
+        const share = planData.jit_share ?? 0;
+        const shareClass = share >= 0.5 ? 'cost-high' : share >= 0.2 ? 'cost-medium' : '';
+        return `
+                <div class="metric-item">
+                    <div class="metric-value ${shareClass}">${timing.Total.toFixed(1)}ms</div>
+                    <div class="metric-label">JIT (${planData.jit.Functions} functions, ${(share * 100).toFixed(0)}% of execution)</div>
+                </div>`;
+    }
+
     loadHistoryFromStorage() {
         try {
             const history = localStorage.getItem('sqltrace-history');
@@ -1,8 +1,9 @@
 //! Integration tests for the database module
+#![allow(clippy::unnecessary_map_or)]
 
 mod test_utils;
 
-use sqltrace_rs::db::{models::PlanNode, Database};
+use sqltrace_rs::db::{models::PlanNode, prepared::PreparedExplainOptions, Database};
 use sqltrace_rs::SqlTraceError;
 use test_utils::with_test_database;
 
@@ -216,3 +217,47 @@ async fn test_explain_with_complex_query() -> anyhow::Result<()> {
     })
     .await
 }
+
+#[tokio::test]
+async fn test_explain_prepared_switches_at_sixth_execution() -> anyhow::Result<()> {
+    with_test_database(|pool| async move {
+        let db = Database::from_pool(pool);
+        let options = PreparedExplainOptions {
+            params: vec![serde_json::json!(1)],
+            executions: 8,
+            ..Default::default()
+        };
+        let report = db
+            .explain_prepared("SELECT * FROM users WHERE id = $1", &options, None)
+            .await?;
+
+        // The forced custom and generic plans must not count toward the five
+        // custom plans PostgreSQL tries before it considers the generic one
+        assert_eq!(report.switched_to_generic_at, Some(6));
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_sequence_usage_ignores_tables_in_other_schemas() -> anyhow::Result<()> {
+    with_test_database(|pool| async move {
+        sqlx::query("CREATE SCHEMA archive").execute(&pool).await?;
+        sqlx::query("CREATE TABLE archive.users (id SERIAL PRIMARY KEY)")
+            .execute(&pool)
+            .await?;
+        sqlx::query("SELECT setval('archive.users_id_seq', 2000000000)")
+            .execute(&pool)
+            .await?;
+        let db = Database::from_pool(pool);
+
+        // Only the users table on the search path is reported
+        let usage = db.sequence_usage(&["users".to_string()]).await?;
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].schema_name, "public");
+
+        Ok(())
+    })
+    .await
+}
@@ -5,8 +5,12 @@ use axum::{
     http::{Request, StatusCode},
     Router,
 };
+use futures_util::StreamExt;
 use serde_json::{json, Value};
+use sqltrace_rs::config::{ServerConfig, ServerProfile};
 use sqltrace_rs::db::Database;
+use sqltrace_rs::web::probes::{HealthConfig, Probes};
+use sqltrace_rs::SqlTraceError;
 use std::env;
 use tower::ServiceExt;
 
@@ -24,12 +28,25 @@ async fn create_app() -> Router {
     let db = Database::new(&db_url)
         .await
         .expect("Failed to connect to database - ensure PostgreSQL is running");
+    create_app_with(db, Default::default()).await
+}
 
+/// Create an app serving `db` with the given server configuration
+async fn create_app_with(db: Database, config: ServerConfig) -> Router {
     // Use the actual router from main
-    let state = sqltrace_rs::AppState {
+    let storage = sqltrace_rs::storage::open(&sqltrace_rs::storage::StorageConfig {
+        url: "sqlite::memory:".to_string(),
+        ..Default::default()
+    })
+    .await
+    .expect("Failed to open in-memory storage");
+
+    let state = sqltrace_rs::AppState::new(
         db,
-        advisor: sqltrace_rs::advisor::QueryAdvisor::new(),
-    };
+        sqltrace_rs::advisor::QueryAdvisor::new(),
+        storage,
+        config,
+    );
     sqltrace_rs::create_router(state)
 }
 
@@ -111,6 +128,214 @@ async fn test_simple_select_query() {
     );
 }
 
+#[tokio::test]
+async fn test_raw_plan_on_request() {
+    let app = create_app().await;
+
+    let (status, body) = make_request(
+        &app,
+        "POST",
+        "/api/explain",
+        Some(json!({"query": "SELECT 1 as test_value", "include_raw_plan": true})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["raw_plan"][0]["Plan"]["Node Type"], "Result");
+    assert!(body["raw_plan"][0]["Execution Time"].is_number());
+
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/explain",
+        Some(json!({"query": "SELECT 1 as test_value"})),
+    )
+    .await;
+    assert!(body.get("raw_plan").is_none());
+}
+
+#[tokio::test]
+async fn test_pool_metrics_count_explains() {
+    let app = create_app().await;
+
+    for _ in 0..2 {
+        let (status, _) = make_request(
+            &app,
+            "POST",
+            "/api/explain",
+            Some(json!({"query": "SELECT 1 as test_value"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, body) = make_request(&app, "GET", "/api/admin/pool", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let pool = &body["pools"]["default"];
+    assert_eq!(pool["engine_type"], "PostgreSQL");
+    assert!(pool["acquired"].as_u64().unwrap() >= 2);
+    assert_eq!(pool["acquire_timeouts"], 0);
+    assert!(pool["size"].as_u64().unwrap() <= pool["max_connections"].as_u64().unwrap());
+    assert!(pool["mean_acquire_wait_ms"].is_number());
+}
+
+#[tokio::test]
+async fn test_explain_reports_server_settings() {
+    let app = create_app().await;
+
+    let (status, body) = make_request(
+        &app,
+        "POST",
+        "/api/explain",
+        Some(json!({"query": "SELECT 1 as test_value"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let settings = &body["settings"];
+    for name in [
+        "work_mem",
+        "shared_buffers",
+        "random_page_cost",
+        "jit",
+        "max_parallel_workers_per_gather",
+    ] {
+        assert!(settings[name].is_string(), "missing {}: {}", name, settings);
+    }
+}
+
+#[tokio::test]
+async fn test_cancel_running_explain() {
+    let app = create_app().await;
+
+    // Ids the server did not issue are refused
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/explain",
+        Some(json!({"query": "SELECT 1", "request_id": "slow-1"})),
+    )
+    .await;
+    assert!(body["error"].as_str().unwrap().contains("slow-1"));
+
+    let (_, body) = make_request(&app, "POST", "/api/explain/requests", None).await;
+    let request_id = body["request_id"].as_str().unwrap().to_string();
+    let cancel_path = format!("/api/explain/{}/cancel", request_id);
+    let explain = {
+        let app = app.clone();
+        let request_id = request_id.clone();
+        tokio::spawn(async move {
+            make_request(
+                &app,
+                "POST",
+                "/api/explain",
+                Some(json!({"query": "SELECT pg_sleep(30)", "request_id": request_id})),
+            )
+            .await
+        })
+    };
+
+    // The request is only registered once the handler has validated it
+    let mut cancelled = false;
+    for _ in 0..100 {
+        let (status, body) = make_request(&app, "POST", &cancel_path, None).await;
+        if status == StatusCode::OK {
+            assert!(body["error"].is_null(), "{}", body["error"]);
+            cancelled = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert!(cancelled, "the explain request was never registered");
+
+    let (status, body) = explain.await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cancelled"], true);
+    assert_eq!(body["error"], "Query was cancelled");
+    assert_eq!(body["request_id"], request_id.as_str());
+
+    let (status, _) = make_request(&app, "POST", &cancel_path, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_connection_search_path() {
+    let db_url = get_database_url();
+    let pool = sqlx::PgPool::connect(&db_url).await.unwrap();
+    for statement in [
+        "CREATE SCHEMA IF NOT EXISTS sqltrace_sales",
+        "CREATE TABLE IF NOT EXISTS sqltrace_sales.invoices (id int PRIMARY KEY, total numeric)",
+    ] {
+        sqlx::query(statement).execute(&pool).await.unwrap();
+    }
+
+    let app = create_app().await;
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/connections",
+        Some(json!({"name": "sales", "url": db_url, "search_path": "sqltrace_sales, public"})),
+    )
+    .await;
+    assert!(body["error"].is_null(), "{}", body["error"]);
+
+    let (_, body) = make_request(&app, "GET", "/api/connections/sales/info", None).await;
+    assert_eq!(body["info"]["schema"], "sqltrace_sales");
+    assert_eq!(body["info"]["search_path"], "sqltrace_sales, public");
+
+    // Unqualified names resolve through the connection's search path
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/explain",
+        Some(json!({"query": "SELECT * FROM invoices", "connection": "sales"})),
+    )
+    .await;
+    assert!(body["error"].is_null(), "{}", body["error"]);
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/explain",
+        Some(json!({"query": "SELECT * FROM invoices"})),
+    )
+    .await;
+    assert!(body["error"].is_string());
+
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/connections",
+        Some(json!({"name": "local", "url": "sqlite::memory:", "search_path": "main"})),
+    )
+    .await;
+    assert!(body["error"].as_str().unwrap().contains("search_path"));
+}
+
+#[tokio::test]
+async fn test_preview_returns_columns_and_types() {
+    let db = Database::new(&get_database_url())
+        .await
+        .expect("Failed to connect to database - ensure PostgreSQL is running");
+    let mut config = ServerConfig::default();
+    config.preview.enabled = true;
+    let app = create_app_with(db, config).await;
+
+    let (status, body) = make_request(
+        &app,
+        "POST",
+        "/api/preview",
+        Some(json!({
+            "query": "SELECT g AS id, 'row ' || g AS label FROM generate_series(1, 5) g",
+            "limit": 2
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["error"].is_null(), "{}", body["error"]);
+    assert_eq!(body["preview"]["columns"], json!(["id", "label"]));
+    assert_eq!(body["preview"]["column_types"], json!(["INT4", "TEXT"]));
+    assert_eq!(body["preview"]["rows"], json!([[1, "row 1"], [2, "row 2"]]));
+    assert_eq!(body["preview"]["truncated"], true);
+}
+
 #[tokio::test]
 async fn test_table_scan_query() {
     let app = create_app().await;
@@ -288,3 +513,850 @@ async fn test_serve_static_files() {
     );
     assert!(html.contains("<!DOCTYPE html>"), "Should be valid HTML");
 }
+
+#[tokio::test]
+async fn test_connection_health_after_monitoring() {
+    let storage = sqltrace_rs::storage::open(&sqltrace_rs::storage::StorageConfig {
+        url: "sqlite::memory:".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    let state = sqltrace_rs::AppState::new(
+        db,
+        sqltrace_rs::advisor::QueryAdvisor::new(),
+        storage,
+        ServerConfig::default(),
+    );
+    let app = sqltrace_rs::create_router(state.clone());
+
+    let (_, body) = make_request(&app, "GET", "/api/health", None).await;
+    assert_eq!(body["connections"]["default"]["status"], "unknown");
+
+    sqltrace_rs::db::monitor::check_all(&state.connections, &state.config.monitor).await;
+    let (status, body) = make_request(&app, "GET", "/api/health", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "healthy");
+    assert_eq!(body["connections"]["default"]["status"], "healthy");
+    assert!(body["connections"]["default"]["latency_ms"].is_number());
+
+    let (status, body) = make_request(&app, "GET", "/api/connections/default/info", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["info"]["engine_type"], "SQLite");
+    assert_eq!(body["info"]["health"]["consecutive_failures"], 0);
+
+    let (status, _) = make_request(&app, "GET", "/api/connections/missing/info", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Integrations are PostgreSQL extensions and roles
+    let (status, body) =
+        make_request(&app, "GET", "/api/connections/default/integrations", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["integrations"].is_null());
+    assert!(body["error"].as_str().unwrap().contains("PostgreSQL"));
+    let (status, _) =
+        make_request(&app, "GET", "/api/connections/missing/integrations", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_open_circuit_rejects_analyses() {
+    let storage = sqltrace_rs::storage::open(&sqltrace_rs::storage::StorageConfig {
+        url: "sqlite::memory:".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let mut config = ServerConfig::default();
+    config.breaker.failure_threshold = 2;
+    let state = sqltrace_rs::AppState::new(
+        Database::new("sqlite::memory:").await.unwrap(),
+        sqltrace_rs::advisor::QueryAdvisor::new(),
+        storage,
+        config,
+    );
+    let app = sqltrace_rs::create_router(state.clone());
+    let connection = state.connections.default_connection();
+    let explain = json!({"query": "SELECT 1"});
+
+    let unreachable: Result<(), SqlTraceError> =
+        Err(SqlTraceError::Connection("pool timed out".to_string()));
+    state.record_outcome(&connection, &unreachable);
+    let (status, _) = make_request(&app, "POST", "/api/explain", Some(explain.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The successful explain reset the count, so it takes two more failures
+    state.record_outcome(&connection, &unreachable);
+    state.record_outcome(&connection, &unreachable);
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/explain")
+        .header("content-type", "application/json")
+        .body(Body::from(explain.to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let retry_after: u64 = response.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=10).contains(&retry_after));
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["connection"], "default");
+    assert!(body["error"].as_str().unwrap().contains("degraded"));
+
+    let (_, body) = make_request(&app, "GET", "/api/health", None).await;
+    assert_eq!(body["status"], "degraded");
+    assert_eq!(body["connections"]["default"]["circuit"]["state"], "open");
+
+    // Errors about the query itself show the database answered
+    let rejected: Result<(), SqlTraceError> =
+        Err(SqlTraceError::InvalidQuery("syntax error".to_string()));
+    state.record_outcome(&connection, &rejected);
+    let (status, body) = make_request(&app, "POST", "/api/explain", Some(explain)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["plan"].is_object());
+}
+
+#[tokio::test]
+async fn test_queue_position_events() {
+    let storage = sqltrace_rs::storage::open(&sqltrace_rs::storage::StorageConfig {
+        url: "sqlite::memory:".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let mut config = ServerConfig::default();
+    config.benchmark.max_concurrent = 1;
+    let state = sqltrace_rs::AppState::new(
+        Database::new("sqlite::memory:").await.unwrap(),
+        sqltrace_rs::advisor::QueryAdvisor::new(),
+        storage,
+        config,
+    );
+    let app = sqltrace_rs::create_router(state.clone());
+    let queue = state.benchmark_queue.clone();
+
+    let slot = queue.acquire("alice", None).await.unwrap();
+    let waiting = {
+        let queue = queue.clone();
+        tokio::spawn(async move { queue.acquire("bob", Some("b1")).await })
+    };
+    while queue.status("b1").is_none() {
+        tokio::task::yield_now().await;
+    }
+
+    let (status, body) = make_request(&app, "GET", "/api/queue", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        (body["running"].as_u64(), body["queued"].as_u64()),
+        (Some(1), Some(1))
+    );
+    assert_eq!(body["users"]["bob"]["queued"], 1);
+
+    let (status, _) = make_request(&app, "GET", "/api/queue/missing/events", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let request = Request::builder()
+        .uri("/api/queue/b1/events")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let mut events = response.into_body().into_data_stream();
+    let event = events.next().await.unwrap().unwrap();
+    let event = String::from_utf8_lossy(&event);
+    assert!(event.contains("event: queued"), "{}", event);
+    assert!(event.contains("\"position\":1"), "{}", event);
+
+    // Bob gets the slot once Alice's benchmark is done, which ends the stream
+    drop(slot);
+    let event = events.next().await.unwrap().unwrap();
+    assert!(String::from_utf8_lossy(&event).contains("event: running"));
+    assert!(events.next().await.is_none());
+    assert!(waiting.await.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn test_read_only_profile() {
+    // Routing doesn't depend on the engine, so SQLite will do
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    let app = create_app_with(
+        db,
+        ServerConfig {
+            profile: ServerProfile::ReadOnly,
+            ..Default::default()
+        },
+    )
+    .await;
+
+    let (status, body) = make_request(&app, "GET", "/api/health", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["profile"], "read_only");
+
+    let (status, _) = make_request(&app, "GET", "/api/connections", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = make_request(
+        &app,
+        "POST",
+        "/api/connections",
+        Some(json!({"name": "other", "url": "sqlite::memory:"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+
+    for (method, path) in [
+        ("DELETE", "/api/connections/default"),
+        ("GET", "/api/queries"),
+        ("POST", "/api/admin/purge"),
+        ("POST", "/api/benchmark"),
+    ] {
+        let (status, _) = make_request(&app, method, path, Some(json!({}))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", method, path);
+    }
+}
+
+#[tokio::test]
+async fn test_policies_apply_to_registered_connections() {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    let policy = sqltrace_rs::policy::QueryPolicy {
+        allowed_statements: vec![sqltrace_rs::policy::StatementKind::Select],
+        ..Default::default()
+    };
+    let app = create_app_with(
+        db,
+        ServerConfig {
+            policies: [("default".to_string(), policy)].into(),
+            ..Default::default()
+        },
+    )
+    .await;
+    let (status, body) = make_request(
+        &app,
+        "POST",
+        "/api/connections",
+        Some(json!({"name": "other", "url": "sqlite::memory:"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["error"].is_null(), "{}", body["error"]);
+
+    // A connection without its own policy section is refused, not left unrestricted
+    for path in ["/api/explain", "/api/explain/prepared"] {
+        let (_, body) = make_request(
+            &app,
+            "POST",
+            path,
+            Some(json!({"query": "SELECT 1", "connection": "other"})),
+        )
+        .await;
+        let error = body["error"].as_str().unwrap_or_default();
+        assert!(error.contains("[policies.other]"), "{}: {}", path, error);
+    }
+
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/explain/prepared",
+        Some(json!({"query": "SELECT 1", "connection": "missing"})),
+    )
+    .await;
+    assert!(body["error"].as_str().unwrap().contains("missing"));
+}
+
+#[tokio::test]
+async fn test_full_profile_serves_mutating_endpoints() {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    let app = create_app_with(db, ServerConfig::default()).await;
+
+    let (status, body) = make_request(&app, "GET", "/api/health", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["profile"], "full");
+    let (status, _) = make_request(&app, "GET", "/api/queries", None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_admin_rules_persist_changes() {
+    let storage = sqltrace_rs::storage::open(&sqltrace_rs::storage::StorageConfig {
+        url: "sqlite::memory:".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    let state = sqltrace_rs::AppState::new(
+        db,
+        sqltrace_rs::advisor::QueryAdvisor::new(),
+        storage,
+        ServerConfig::default(),
+    );
+    let app = sqltrace_rs::create_router(state.clone());
+
+    let (status, body) = make_request(&app, "GET", "/api/admin/rules", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let nested_loop = body["rules"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|rule| rule["id"] == "nested_loop")
+        .unwrap();
+    assert_eq!(nested_loop["enabled"], true);
+    assert_eq!(nested_loop["thresholds"]["large_scan_threshold"], 10000.0);
+
+    let (status, body) = make_request(
+        &app,
+        "PUT",
+        "/api/admin/rules/nested_loop",
+        Some(json!({"enabled": false, "thresholds": {"large_scan_threshold": 500}})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["stored"], true);
+    assert_eq!(body["rule"]["enabled"], false);
+    assert_eq!(body["rule"]["thresholds"]["large_scan_threshold"], 500.0);
+
+    // Thresholds are shared by the rules that read them
+    let config = state.advisor.current().config().clone();
+    assert_eq!(config.large_scan_threshold, 500);
+    assert!(!config.is_rule_enabled("nested_loop"));
+    let stored = state.storage.get_advisor_rules().await.unwrap().unwrap();
+    assert_eq!(stored["disabled_rules"], json!(["nested_loop"]));
+
+    let (_, body) = make_request(
+        &app,
+        "PUT",
+        "/api/admin/rules/nested_loop",
+        Some(json!({"thresholds": {"misestimate_factor": 5}})),
+    )
+    .await;
+    assert_eq!(body["stored"], false);
+    assert!(body["error"].as_str().unwrap().contains("does not read"));
+
+    let (status, _) = make_request(
+        &app,
+        "PUT",
+        "/api/admin/rules/no_such_rule",
+        Some(json!({"enabled": false})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_history_field_selection() {
+    let storage = sqltrace_rs::storage::open(&sqltrace_rs::storage::StorageConfig {
+        url: "sqlite::memory:".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let entry = storage
+        .record_history(sqltrace_rs::storage::NewHistoryEntry {
+            query: "SELECT * FROM orders".to_string(),
+            plan: json!({"root": {"Node Type": "Seq Scan"}, "raw": [{"Plan": {}}]}),
+            analysis: Some(json!({"performance_score": 70, "suggestions": []})),
+            performance_score: Some(70),
+            total_cost: 42.0,
+            execution_time_ms: 1.5,
+            trace_id: None,
+            correlation_id: None,
+            table_definitions: None,
+            connection: None,
+        })
+        .await
+        .unwrap();
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    let app = sqltrace_rs::create_router(sqltrace_rs::AppState::new(
+        db,
+        sqltrace_rs::advisor::QueryAdvisor::new(),
+        storage,
+        ServerConfig::default(),
+    ));
+
+    let (status, body) = make_request(
+        &app,
+        "GET",
+        "/api/history?fields=entries.id,entries.query",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({"entries": [{"id": entry.id, "query": "SELECT * FROM orders"}], "error": null})
+    );
+
+    let path = format!("/api/history/{}?exclude=plan.raw,analysis", entry.id);
+    let (status, body) = make_request(&app, "GET", &path, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["entry"]["plan"],
+        json!({"root": {"Node Type": "Seq Scan"}})
+    );
+    assert!(body["entry"].get("analysis").is_none());
+    assert_eq!(body["entry"]["total_cost"], 42.0);
+}
+
+#[tokio::test]
+async fn test_history_msgpack_encoding() {
+    let storage = sqltrace_rs::storage::open(&sqltrace_rs::storage::StorageConfig {
+        url: "sqlite::memory:".to_string(),
+        plan_encoding: sqltrace_rs::storage::PlanEncoding::Msgpack,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let plan = json!({"root": {"Node Type": "Seq Scan", "Total Cost": 42.0}});
+    let entry = storage
+        .record_history(sqltrace_rs::storage::NewHistoryEntry {
+            query: "SELECT * FROM orders".to_string(),
+            plan: plan.clone(),
+            analysis: None,
+            performance_score: Some(70),
+            total_cost: 42.0,
+            execution_time_ms: 1.5,
+            trace_id: None,
+            correlation_id: None,
+            table_definitions: None,
+            connection: None,
+        })
+        .await
+        .unwrap();
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    let app = sqltrace_rs::create_router(sqltrace_rs::AppState::new(
+        db,
+        sqltrace_rs::advisor::QueryAdvisor::new(),
+        storage,
+        ServerConfig::default(),
+    ));
+
+    // Plans stored packed come back as JSON by default
+    let path = format!("/api/history/{}", entry.id);
+    let (status, body) = make_request(&app, "GET", &path, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["entry"]["plan"], plan);
+
+    let request = Request::builder()
+        .method("GET")
+        .uri(format!("{}?fields=entry.plan", path))
+        .header("accept", "application/msgpack, application/json;q=0.5")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"],
+        sqltrace_rs::msgpack::CONTENT_TYPE
+    );
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(
+        sqltrace_rs::msgpack::decode(&bytes).unwrap(),
+        json!({"entry": {"plan": plan}, "error": null})
+    );
+}
+
+#[tokio::test]
+async fn test_probes_before_and_after_connecting() {
+    let storage = sqltrace_rs::storage::open(&sqltrace_rs::storage::StorageConfig {
+        url: "sqlite::memory:".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let probes = Probes::new(HealthConfig::default(), storage.clone());
+    let app = probes.router();
+
+    // The database is still being connected: alive and started, but not ready
+    let (status, _) = make_request(&app, "GET", "/livez", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = make_request(&app, "GET", "/startupz", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["database"], "connecting");
+    let (status, body) = make_request(&app, "GET", "/readyz", None).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "starting");
+    let (status, _) = make_request(&app, "GET", "/api/health", None).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    probes.set_app(sqltrace_rs::AppState::new(
+        db,
+        sqltrace_rs::advisor::QueryAdvisor::new(),
+        storage,
+        ServerConfig::default(),
+    ));
+
+    let (status, body) = make_request(&app, "GET", "/readyz", None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["checks"].as_array().unwrap().len(), 2);
+    let (_, body) = make_request(&app, "GET", "/startupz", None).await;
+    assert_eq!(body["database"], "connected");
+    let (status, body) = make_request(&app, "GET", "/api/health", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "healthy");
+}
+
+#[tokio::test]
+async fn test_static_assets_stay_inside_static_dir() {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    let app = create_app_with(db, ServerConfig::default()).await;
+
+    let request = Request::builder()
+        .uri("/static/styles.css")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/css");
+
+    for path in [
+        "/static/..%2FCargo.toml",
+        "/static/%2E%2E/Cargo.toml",
+        "/static/",
+    ] {
+        let request = Request::builder().uri(path).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn test_pinned_plan_checked_on_explain() {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    let app = create_app_with(db, ServerConfig::default()).await;
+    let query = "SELECT * FROM sqlite_master WHERE type = 'table'";
+
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/queries",
+        Some(json!({"name": "tables", "query": query})),
+    )
+    .await;
+    let saved_id = body["query"]["id"].as_i64().unwrap();
+    let pin_path = format!("/api/queries/{}/pin", saved_id);
+
+    let (_, body) = make_request(&app, "POST", "/api/explain", Some(json!({"query": query}))).await;
+    assert!(body["error"].is_null(), "{}", body["error"]);
+    assert!(body.get("pin_check").is_none());
+    let history_id = body["history_id"].as_i64().unwrap();
+
+    let (status, _) = make_request(&app, "GET", &pin_path, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, body) = make_request(
+        &app,
+        "PUT",
+        &pin_path,
+        Some(json!({"history_id": history_id})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["error"].is_null(), "{}", body["error"]);
+    assert_eq!(body["pin"]["history_id"], history_id);
+
+    // Later analyses of the query, with other literals, are compared with the pin
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/explain",
+        Some(json!({"query": "SELECT * FROM sqlite_master WHERE type = 'index'"})),
+    )
+    .await;
+    assert_eq!(body["pin_check"]["saved_query_id"], saved_id);
+    assert_eq!(body["pin_check"]["matches"], true);
+
+    // Only analyses of the saved query can be pinned
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/explain",
+        Some(json!({"query": "SELECT name FROM sqlite_master"})),
+    )
+    .await;
+    let other_id = body["history_id"].as_i64().unwrap();
+    let (_, body) = make_request(
+        &app,
+        "PUT",
+        &pin_path,
+        Some(json!({"history_id": other_id})),
+    )
+    .await;
+    assert!(body["error"].as_str().unwrap().contains("different query"));
+
+    let (status, _) = make_request(
+        &app,
+        "PUT",
+        "/api/queries/999/pin",
+        Some(json!({"history_id": history_id})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, body) = make_request(&app, "DELETE", &pin_path, None).await;
+    assert_eq!(body["deleted"], true);
+    let (status, _) = make_request(&app, "GET", &pin_path, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_history_reanalyze_after_rule_change() {
+    let storage = sqltrace_rs::storage::open(&sqltrace_rs::storage::StorageConfig {
+        url: "sqlite::memory:".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let entry = storage
+        .record_history(sqltrace_rs::storage::NewHistoryEntry {
+            query: "SELECT * FROM orders WHERE customer_id = 42".to_string(),
+            plan: json!({
+                "root": {
+                    "Node Type": "Seq Scan",
+                    "Relation Name": "orders",
+                    "Startup Cost": 0.0,
+                    "Total Cost": 25000.0,
+                    "Plan Rows": 1000000,
+                    "Plan Width": 8,
+                    "Actual Startup Time": 0.1,
+                    "Actual Total Time": 900.0,
+                    "Actual Rows": 1000000,
+                    "Actual Loops": 1
+                },
+                "planning_time": 0.1,
+                "execution_time": 900.0
+            }),
+            analysis: None,
+            performance_score: None,
+            total_cost: 25000.0,
+            execution_time_ms: 900.0,
+            trace_id: None,
+            correlation_id: None,
+            table_definitions: None,
+            connection: None,
+        })
+        .await
+        .unwrap();
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    let app = sqltrace_rs::create_router(sqltrace_rs::AppState::new(
+        db,
+        sqltrace_rs::advisor::QueryAdvisor::new(),
+        storage,
+        ServerConfig::default(),
+    ));
+    let entry_path = format!("/api/history/{}", entry.id);
+
+    let (status, body) = make_request(&app, "POST", "/api/history/reanalyze", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["error"].is_null(), "{}", body["error"]);
+    assert_eq!(body["report"]["updated"], 1);
+    assert_eq!(body["report"]["fingerprints"], 1);
+
+    let (_, body) = make_request(&app, "GET", &entry_path, None).await;
+    let rule_id = body["entry"]["analysis"]["suggestions"][0]["rule_id"]
+        .as_str()
+        .expect("a large sequential scan is reported")
+        .to_string();
+    let (_, body) = make_request(&app, "GET", "/api/findings", None).await;
+    assert!(body["findings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|f| f["rule_id"] == rule_id.as_str() && f["state"] == "new"));
+
+    // Disabling the rule and re-analyzing drops it from history and fixes its finding
+    make_request(
+        &app,
+        "PUT",
+        &format!("/api/admin/rules/{}", rule_id),
+        Some(json!({"enabled": false})),
+    )
+    .await;
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/history/reanalyze",
+        Some(json!({"limit": 10})),
+    )
+    .await;
+    assert_eq!(body["report"]["scanned"], 1);
+
+    let (_, body) = make_request(&app, "GET", &entry_path, None).await;
+    assert!(body["entry"]["analysis"]["suggestions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|s| s["rule_id"] != rule_id.as_str()));
+    let (_, body) = make_request(&app, "GET", "/api/findings", None).await;
+    assert!(body["findings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|f| f["rule_id"] == rule_id.as_str())
+        .all(|f| f["state"] == "fixed"));
+}
+
+#[tokio::test]
+async fn test_history_connection_trend() {
+    let storage = sqltrace_rs::storage::open(&sqltrace_rs::storage::StorageConfig {
+        url: "sqlite::memory:".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    for (query, score, connection) in [
+        ("SELECT * FROM orders WHERE id = 1", 90, "primary"),
+        ("SELECT * FROM orders WHERE id = 2", 70, "primary"),
+        ("SELECT * FROM orders WHERE id = 3", 20, "replica"),
+    ] {
+        storage
+            .record_history(sqltrace_rs::storage::NewHistoryEntry {
+                query: query.to_string(),
+                plan: json!({}),
+                analysis: None,
+                performance_score: Some(score),
+                total_cost: 100.0,
+                execution_time_ms: 5.0,
+                trace_id: None,
+                correlation_id: None,
+                table_definitions: None,
+                connection: Some(connection.to_string()),
+            })
+            .await
+            .unwrap();
+    }
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    let app = sqltrace_rs::create_router(sqltrace_rs::AppState::new(
+        db,
+        sqltrace_rs::advisor::QueryAdvisor::new(),
+        storage,
+        ServerConfig::default(),
+    ));
+
+    let (status, body) = make_request(&app, "GET", "/api/connections/primary/trends", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["error"].is_null(), "{}", body["error"]);
+    let trend = &body["trend"];
+    assert_eq!(trend["connection"], "primary");
+    assert_eq!(trend["days"].as_array().unwrap().len(), 30);
+    assert_eq!(trend["analyses"], 2);
+    assert_eq!(trend["average_score"], 80.0);
+    assert_eq!(trend["regressions"], 1);
+
+    // Both analyses ran today, the last day reported
+    let (_, body) = make_request(&app, "GET", "/api/connections/primary/trends?days=7", None).await;
+    let days = body["trend"]["days"].as_array().unwrap();
+    assert_eq!(days.len(), 7);
+    assert_eq!(days[6]["analyses"], 2);
+    assert_eq!(days[6]["regressions"], 1);
+    assert!(days[0]["average_score"].is_null());
+
+    let (_, body) = make_request(&app, "GET", "/api/connections/unknown/trends", None).await;
+    assert_eq!(body["trend"]["analyses"], 0);
+}
+
+#[tokio::test]
+async fn test_admin_rules_dry_run() {
+    let storage = sqltrace_rs::storage::open(&sqltrace_rs::storage::StorageConfig {
+        url: "sqlite::memory:".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    for rows in [2_000, 20_000] {
+        storage
+            .record_history(sqltrace_rs::storage::NewHistoryEntry {
+                query: format!("SELECT * FROM orders ORDER BY created_at LIMIT {}", rows),
+                plan: json!({
+                    "root": {
+                        "Node Type": "Sort",
+                        "Startup Cost": 0.0,
+                        "Total Cost": 900.0,
+                        "Plan Rows": rows,
+                        "Plan Width": 8,
+                        "Actual Startup Time": 0.1,
+                        "Actual Total Time": 40.0,
+                        "Actual Rows": rows,
+                        "Actual Loops": 1
+                    },
+                    "planning_time": 0.1,
+                    "execution_time": 40.0
+                }),
+                analysis: None,
+                performance_score: None,
+                total_cost: 900.0,
+                execution_time_ms: 40.0,
+                trace_id: None,
+                correlation_id: None,
+                table_definitions: None,
+                connection: None,
+            })
+            .await
+            .unwrap();
+    }
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    let app = sqltrace_rs::create_router(sqltrace_rs::AppState::new(
+        db,
+        sqltrace_rs::advisor::QueryAdvisor::new(),
+        storage,
+        ServerConfig::default(),
+    ));
+
+    let (status, body) = make_request(
+        &app,
+        "POST",
+        "/api/admin/rules/dry-run",
+        Some(json!({"rules": ["large_sort"], "limit": 10})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["error"].is_null(), "{}", body["error"]);
+    assert_eq!(body["report"]["plans"], 2);
+    assert_eq!(body["report"]["rules"][0]["plans_hit"], 1);
+
+    // A lower threshold catches both sorts, without changing the rule
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/admin/rules/dry-run",
+        Some(json!({"rules": ["large_sort"], "thresholds": {"large_scan_threshold": 1000}})),
+    )
+    .await;
+    let hits = &body["report"]["rules"][0];
+    assert_eq!(hits["plans_hit"], 2);
+    assert_eq!(hits["hit_rate"], 1.0);
+    assert_eq!(body["report"]["thresholds"]["large_scan_threshold"], 1000.0);
+    let (_, body) = make_request(&app, "GET", "/api/admin/rules", None).await;
+    assert!(body["overrides"]["thresholds"]
+        .get("large_scan_threshold")
+        .is_none());
+
+    let (_, body) = make_request(
+        &app,
+        "POST",
+        "/api/admin/rules/dry-run",
+        Some(json!({"rules": ["no_such_rule"]})),
+    )
+    .await;
+    assert!(body["report"].is_null());
+    assert!(body["error"].as_str().unwrap().contains("no_such_rule"));
+}
+
+#[test]
+fn test_plan_fixtures() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/plans");
+    let report =
+        sqltrace_rs::fixtures::check_dir(&dir, &sqltrace_rs::advisor::QueryAdvisor::new(), false)
+            .unwrap();
+    assert!(report.fixtures.len() >= 3);
+    assert!(report.is_match(), "{}", report);
+}
@@ -1,4 +1,9 @@
 //! Test utilities for integration tests
+#![allow(
+    dead_code,
+    clippy::useless_format,
+    clippy::needless_borrows_for_generic_args
+)]
 
 use sqlx::{postgres::PgPoolOptions, PgPool};
 use std::sync::Once;